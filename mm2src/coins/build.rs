@@ -3,6 +3,12 @@ fn main() {
     prost.out_dir("utxo");
     prost.compile_protos(&["utxo/bchrpc.proto"], &["utxo"]).unwrap();
 
+    let mut prost = prost_build::Config::new();
+    prost.out_dir("tendermint");
+    prost
+        .compile_protos(&["tendermint/tendermint_pb.proto"], &["tendermint"])
+        .unwrap();
+
     tonic_build::configure()
         .build_server(false)
         .compile(&["z_coin/service.proto"], &["z_coin"])
@@ -49,6 +49,7 @@ use serde_json::{self as json, Value as Json};
 use serialization::{CompactInteger, Serializable, Stream};
 use sha3::{Digest, Keccak256};
 use std::collections::HashMap;
+use std::fmt;
 use std::ops::Deref;
 use std::path::PathBuf;
 use std::str::FromStr;
@@ -58,22 +59,32 @@ use web3::types::{Action as TraceAction, BlockId, BlockNumber, Bytes, CallReques
                   TraceFilterBuilder, Transaction as Web3Transaction, TransactionId};
 use web3::{self, Web3};
 use web3_transport::{EthFeeHistoryNamespace, Web3Transport};
+#[cfg(not(target_arch = "wasm32"))]
+use web3_ws_subscriptions::EthWsSubscriptions;
 
+#[cfg(not(target_arch = "wasm32"))] use super::coin_proxy;
+use super::my_tx_history_v2::{load_history_from_storage, CoinWithTxHistoryV2, MyTxHistoryErrorV2, TxHistoryStorage};
+use super::tx_history_storage::{GetTxHistoryFilters, TxHistoryStorageBuilder, WalletId};
 use super::{AsyncMutex, BalanceError, BalanceFut, CoinBalance, CoinProtocol, CoinTransportMetrics, CoinsContext,
             FeeApproxStage, FoundSwapTxSpend, HistorySyncState, MarketCoinOps, MmCoin, NegotiateSwapContractAddrErr,
             NumConversError, NumConversResult, RawTransactionError, RawTransactionFut, RawTransactionRequest,
             RawTransactionRes, RawTransactionResult, RpcClientType, RpcTransportEventHandler,
             RpcTransportEventHandlerShared, SearchForSwapTxSpendInput, SignatureError, SignatureResult, SwapOps,
             TradeFee, TradePreimageError, TradePreimageFut, TradePreimageResult, TradePreimageValue, Transaction,
-            TransactionDetails, TransactionEnum, TransactionErr, TransactionFut, UnexpectedDerivationMethod,
-            ValidateAddressResult, ValidatePaymentInput, VerificationError, VerificationResult, WithdrawError,
-            WithdrawFee, WithdrawFut, WithdrawRequest, WithdrawResult};
+            TransactionDetails, TransactionEnum, TransactionErr, TransactionFut, TransactionType, TxHistoryFut,
+            UnbroadcastSwapPaymentInput, UnexpectedDerivationMethod, ValidateAddressResult, ValidatePaymentInput,
+            VerificationError, VerificationResult, WithdrawError, WithdrawFee, WithdrawFut, WithdrawRequest,
+            WithdrawResult};
 
 pub use rlp;
 
 #[cfg(test)] mod eth_tests;
 #[cfg(target_arch = "wasm32")] mod eth_wasm_tests;
 mod web3_transport;
+#[cfg(not(target_arch = "wasm32"))] mod web3_ws_subscriptions;
+
+mod swap_v2;
+use swap_v2::{decode_erc20_payment_input, SWAP_V2_CONTRACT};
 
 /// https://github.com/artemii235/etomic-swap/blob/master/contracts/EtomicSwap.sol
 /// Dev chain (195.201.0.6:8565) contract address: 0xa09ad3cd7e96586ebd05a2607ee56b56fb2db8fd
@@ -86,14 +97,17 @@ const ERC20_ABI: &str = r#"[{"constant":true,"inputs":[],"name":"name","outputs"
 /// Payment states from etomic swap smart contract: https://github.com/artemii235/etomic-swap/blob/master/contracts/EtomicSwap.sol#L5
 pub const PAYMENT_STATE_UNINITIALIZED: u8 = 0;
 pub const PAYMENT_STATE_SENT: u8 = 1;
-const _PAYMENT_STATE_SPENT: u8 = 2;
-const _PAYMENT_STATE_REFUNDED: u8 = 3;
+pub const PAYMENT_STATE_SPENT: u8 = 2;
+pub const PAYMENT_STATE_REFUNDED: u8 = 3;
 // Ethgasstation API returns response in 10^8 wei units. So 10 from their API mean 1 gwei
 const ETH_GAS_STATION_DECIMALS: u8 = 8;
 const GAS_PRICE_PERCENT: u64 = 10;
 /// It can change 12.5% max each block according to https://www.blocknative.com/blog/eip-1559-fees
 const BASE_BLOCK_FEE_DIFF_PCT: u64 = 13;
 const DEFAULT_LOGS_BLOCK_RANGE: u64 = 1000;
+/// The nodes are still polled this often if the WebSocket notifications are lost.
+#[cfg(not(target_arch = "wasm32"))]
+const WS_FALLBACK_POLL_INTERVAL_S: f64 = 30.;
 
 /// Take into account that the dynamic fee may increase by 3% during the swap.
 const GAS_PRICE_APPROXIMATION_PERCENT_ON_START_SWAP: u64 = 3;
@@ -288,6 +302,9 @@ pub struct EthCoinImpl {
     sign_message_prefix: Option<String>,
     swap_contract_address: Address,
     fallback_swap_contract: Option<Address>,
+    /// The swap contract v2 supporting the ERC20 payments with permit, the payment state queries and batched refunds.
+    /// It's used instead of `swap_contract_address` for the new swaps if both sides advertise the same address.
+    swap_v2_contract: Option<Address>,
     web3: Web3<Web3Transport>,
     /// The separate web3 instances kept to get nonce, will replace the web3 completely soon
     web3_instances: Vec<Web3Instance>,
@@ -304,6 +321,12 @@ pub struct EthCoinImpl {
     /// the block range used for eth_getLogs
     logs_block_range: u64,
     nonce_lock: Arc<AsyncMutex<()>>,
+    /// The number of `web3_instances` that must return the same payment receipt and logs
+    /// for the payment to pass the validation. The nodes aren't cross-checked if it's `None`.
+    quorum: Option<usize>,
+    /// The new heads and the swap contract logs subscriptions, if the WebSocket urls are configured.
+    #[cfg(not(target_arch = "wasm32"))]
+    ws_subscriptions: Option<Arc<EthWsSubscriptions>>,
 }
 
 #[derive(Clone, Debug)]
@@ -666,6 +689,7 @@ async fn withdraw_impl(coin: EthCoin, req: WithdrawRequest) -> WithdrawResult {
         internal_id: vec![].into(),
         timestamp: now_ms() / 1000,
         kmd_rewards: None,
+        spv_verified: None,
         transaction_type: Default::default(),
     })
 }
@@ -957,6 +981,11 @@ impl SwapOps for EthCoin {
         let swap_contract_address = try_fus!(swap_contract_address.try_to_address());
         let selfi = self.clone();
         let fut = async move {
+            if selfi.is_swap_v2_contract(swap_contract_address) {
+                let payment = try_s!(selfi.find_payment_tx_v2(swap_contract_address, id).await);
+                return Ok(payment.map(TransactionEnum::from));
+            }
+
             let status = try_s!(
                 selfi
                     .payment_status(swap_contract_address, Token::FixedBytes(id.clone()))
@@ -1065,6 +1094,10 @@ impl SwapOps for EthCoin {
                 if Some(other_addr) == self.fallback_swap_contract {
                     return Ok(self.fallback_swap_contract.map(|addr| addr.to_vec().into()));
                 }
+
+                if Some(other_addr) == self.swap_v2_contract {
+                    return Ok(self.swap_v2_contract.map(|addr| addr.to_vec().into()));
+                }
                 MmError::err(NegotiateSwapContractAddrErr::UnexpectedOtherAddr(bytes.into()))
             },
             None => self
@@ -1074,9 +1107,72 @@ impl SwapOps for EthCoin {
         }
     }
 
+    fn swap_v2_contract_address(&self) -> Option<BytesJson> { self.swap_v2_contract.map(|addr| addr.to_vec().into()) }
+
     fn derive_htlc_key_pair(&self, _swap_unique_data: &[u8]) -> keys::KeyPair {
         key_pair_from_secret(self.key_pair.secret()).expect("valid key")
     }
+
+    fn gen_unbroadcast_swap_payment(&self, input: UnbroadcastSwapPaymentInput) -> TransactionFut {
+        let receiver_addr = try_tx_fus!(addr_from_raw_pubkey(&input.other_pub));
+        let swap_contract_address = try_tx_fus!(input.swap_contract_address.try_to_address());
+        let value = try_tx_fus!(wei_from_big_decimal(&input.amount, self.decimals));
+        let id = self.etomic_swap_id(input.time_lock, &input.secret_hash);
+
+        // The ERC20 payment is generated without the preceding `approve` or permit,
+        // since the transaction is only signed and validated, not executed.
+        let (tx_value, data) = match &self.coin_type {
+            EthCoinType::Eth => {
+                let function = try_tx_fus!(SWAP_CONTRACT.function("ethPayment"));
+                let data = try_tx_fus!(function.encode_input(&[
+                    Token::FixedBytes(id),
+                    Token::Address(receiver_addr),
+                    Token::FixedBytes(input.secret_hash),
+                    Token::Uint(U256::from(input.time_lock))
+                ]));
+                (value, data)
+            },
+            EthCoinType::Erc20 {
+                platform: _,
+                token_addr,
+            } => {
+                let function = try_tx_fus!(SWAP_CONTRACT.function("erc20Payment"));
+                let data = try_tx_fus!(function.encode_input(&[
+                    Token::FixedBytes(id),
+                    Token::Uint(value),
+                    Token::Address(*token_addr),
+                    Token::Address(receiver_addr),
+                    Token::FixedBytes(input.secret_hash),
+                    Token::Uint(U256::from(input.time_lock))
+                ]));
+                (0.into(), data)
+            },
+        };
+
+        let coin = self.clone();
+        let fut = async move {
+            let signed = coin
+                .sign_transaction(tx_value, Action::Call(swap_contract_address), data, U256::from(150_000))
+                .await?;
+            Ok(TransactionEnum::from(signed))
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn validate_unbroadcast_swap_payment(
+        &self,
+        input: ValidatePaymentInput,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        let swap_contract_address = try_fus!(input.swap_contract_address.try_to_address());
+        self.validate_unbroadcast_payment(
+            &input.payment_tx,
+            input.time_lock,
+            &input.other_pub,
+            &input.secret_hash,
+            input.amount,
+            swap_contract_address,
+        )
+    }
 }
 
 #[cfg_attr(test, mockable)]
@@ -1331,7 +1427,7 @@ impl MarketCoinOps for EthCoin {
                         tx,
                     );
                 }
-                Timer::sleep(5.).await;
+                selfi.wait_for_chain_update(5.).await;
                 continue;
             }
         };
@@ -1447,6 +1543,127 @@ async fn sign_and_send_transaction_impl(
 }
 
 impl EthCoin {
+    /// Signs the transaction with the current nonce and gas price without broadcasting it.
+    async fn sign_transaction(
+        &self,
+        value: U256,
+        action: Action,
+        data: Vec<u8>,
+        gas: U256,
+    ) -> Result<SignedEthTx, TransactionErr> {
+        let nonce = try_tx_s!(
+            get_addr_nonce(self.my_address, self.web3_instances.clone())
+                .compat()
+                .await
+        );
+        let gas_price = try_tx_s!(self.get_gas_price().compat().await);
+        let tx = UnSignedEthTx {
+            nonce,
+            gas_price,
+            gas,
+            action,
+            value,
+            data,
+        };
+        Ok(tx.sign(self.key_pair.secret(), self.chain_id))
+    }
+
+    /// Waits for a new block or a swap contract log if the WebSocket subscriptions are enabled,
+    /// sleeps for `poll_interval_s` seconds otherwise.
+    async fn wait_for_chain_update(&self, poll_interval_s: f64) {
+        #[cfg(not(target_arch = "wasm32"))]
+        {
+            if let Some(ref subscriptions) = self.ws_subscriptions {
+                return subscriptions.wait_for_event(WS_FALLBACK_POLL_INTERVAL_S).await;
+            }
+        }
+        Timer::sleep(poll_interval_s).await
+    }
+
+    /// Cross-checks the receipt and the `PaymentSent` log of the payment across `web3_instances`
+    /// if the `quorum` is configured, so a single lying node can't make an invalid payment pass the validation.
+    async fn check_payment_quorum(&self, tx_hash: H256, swap_contract_address: Address) -> Result<(), String> {
+        let quorum = match self.quorum {
+            Some(quorum) => quorum,
+            None => return Ok(()),
+        };
+
+        let receipts = join_all(
+            self.web3_instances
+                .iter()
+                .map(|instance| instance.web3.eth().transaction_receipt(tx_hash).compat()),
+        )
+        .await;
+        let receipt = try_s!(quorum_result(receipts, quorum, "eth_getTransactionReceipt"));
+        if receipt.status != Some(1.into()) {
+            return ERR!("Payment tx {:?} receipt status is failed", tx_hash);
+        }
+        let block_number = match receipt.block_number {
+            Some(number) => number.as_u64(),
+            None => return ERR!("Payment tx {:?} is not mined yet", tx_hash),
+        };
+
+        let payment_sent = try_s!(SWAP_CONTRACT.event("PaymentSent"));
+        let filter = FilterBuilder::default()
+            .topics(Some(vec![payment_sent.signature()]), None, None, None)
+            .from_block(BlockNumber::Number(block_number))
+            .to_block(BlockNumber::Number(block_number))
+            .address(vec![swap_contract_address])
+            .build();
+        let logs = join_all(
+            self.web3_instances
+                .iter()
+                .map(|instance| instance.web3.eth().logs(filter.clone()).compat()),
+        )
+        .await;
+        let logs = logs.into_iter().map(|logs| logs.map(Some)).collect();
+        let logs = try_s!(quorum_result(logs, quorum, "eth_getLogs"));
+        if !logs.iter().any(|log| log.transaction_hash == Some(tx_hash)) {
+            return ERR!("PaymentSent event of tx {:?} is not found", tx_hash);
+        }
+        Ok(())
+    }
+
+    /// Requests the swap payment status from `web3_instances` and returns the one at least `quorum` nodes agree on.
+    async fn payment_status_by_quorum(
+        &self,
+        swap_contract_address: Address,
+        token: Token,
+        quorum: usize,
+    ) -> Result<U256, String> {
+        let contract = if self.is_swap_v2_contract(swap_contract_address) {
+            &*SWAP_V2_CONTRACT
+        } else {
+            &*SWAP_CONTRACT
+        };
+        let function = try_s!(contract.function("payments"));
+        let data = try_s!(function.encode_input(&[token]));
+        let request = CallRequest {
+            from: Some(self.my_address),
+            to: swap_contract_address,
+            gas: None,
+            gas_price: None,
+            value: None,
+            data: Some(data.into()),
+        };
+
+        let responses = join_all(self.web3_instances.iter().map(|instance| {
+            instance
+                .web3
+                .eth()
+                .call(request.clone(), Some(BlockNumber::Latest))
+                .compat()
+        }))
+        .await;
+        let responses = responses.into_iter().map(|response| response.map(Some)).collect();
+        let bytes = try_s!(quorum_result(responses, quorum, "eth_call"));
+        let decoded_tokens = try_s!(function.decode_output(&bytes.0));
+        match decoded_tokens[2] {
+            Token::Uint(state) => Ok(state),
+            _ => ERR!("Payment status must be uint, got {:?}", decoded_tokens[2]),
+        }
+    }
+
     /// Downloads and saves ETH transaction history of my_address, relies on Parity trace_filter API
     /// https://wiki.parity.io/JSONRPC-trace-module#trace_filter, this requires tracing to be enabled
     /// in node config. Other ETH clients (Geth, etc.) are `not` supported (yet).
@@ -1458,6 +1675,30 @@ impl EthCoin {
         // Also the Parity RPC server seem to get stuck while request in running (other requests performance is also lowered).
         let delta = U256::from(1000);
 
+        let storage = match TxHistoryStorageBuilder::new(ctx).build() {
+            Ok(storage) => storage,
+            Err(e) => {
+                ctx.log.log(
+                    "",
+                    &[&"tx_history", &self.ticker],
+                    &ERRL!("Error {} on creating the tx history storage, stop the history loop", e),
+                );
+                return;
+            },
+        };
+        let wallet_id = self.history_wallet_id();
+        if let Err(e) = storage.init(&wallet_id).await {
+            ctx.log.log(
+                "",
+                &[&"tx_history", &self.ticker],
+                &ERRL!(
+                    "Error {:?} on the tx history storage initialization, stop the history loop",
+                    e
+                ),
+            );
+            return;
+        }
+
         let mut success_iteration = 0i32;
         loop {
             if ctx.is_stopping() {
@@ -1497,18 +1738,6 @@ impl EthCoin {
                 "blocks_left": u64::from(saved_traces.earliest_block),
             }));
 
-            let mut existing_history = match self.load_history_from_file(ctx).compat().await {
-                Ok(history) => history,
-                Err(e) => {
-                    ctx.log.log(
-                        "",
-                        &[&"tx_history", &self.ticker],
-                        &ERRL!("Error {} on 'load_history_from_file', stop the history loop", e),
-                    );
-                    return;
-                },
-            };
-
             // AP: AFAIK ETH RPC doesn't support conditional filters like `get this OR this` so we have
             // to run several queries to get trace events including our address as sender `or` receiver
             // TODO refactor this to batch requests instead of single request per query
@@ -1641,9 +1870,17 @@ impl EthCoin {
             for trace in saved_traces.traces {
                 let hash = sha256(&json::to_vec(&trace).unwrap());
                 let internal_id = BytesJson::from(hash.to_vec());
-                let processed = existing_history.iter().find(|tx| tx.internal_id == internal_id);
-                if processed.is_some() {
-                    continue;
+                match storage.get_tx_from_history(&wallet_id, &internal_id).await {
+                    Ok(Some(_)) => continue,
+                    Ok(None) => (),
+                    Err(e) => {
+                        ctx.log.log(
+                            "",
+                            &[&"tx_history", &self.ticker],
+                            &ERRL!("Error {:?} on 'get_tx_from_history', stop the history loop", e),
+                        );
+                        return;
+                    },
                 }
 
                 // TODO Only standard Call traces are supported, contract creations, suicides and block rewards will be supported later
@@ -1777,16 +2014,15 @@ impl EthCoin {
                     internal_id,
                     timestamp: block.timestamp.into(),
                     kmd_rewards: None,
+                    spv_verified: None,
                     transaction_type: Default::default(),
                 };
 
-                existing_history.push(details);
-
-                if let Err(e) = self.save_history_to_file(ctx, existing_history.clone()).compat().await {
+                if let Err(e) = storage.add_transactions_to_history(&wallet_id, vec![details]).await {
                     ctx.log.log(
                         "",
                         &[&"tx_history", &self.ticker],
-                        &ERRL!("Error {} on 'save_history_to_file', stop the history loop", e),
+                        &ERRL!("Error {:?} on 'add_transactions_to_history', stop the history loop", e),
                     );
                     return;
                 }
@@ -1815,6 +2051,30 @@ impl EthCoin {
     async fn process_erc20_history(&self, token_addr: H160, ctx: &MmArc) {
         let delta = U256::from(10000);
 
+        let storage = match TxHistoryStorageBuilder::new(ctx).build() {
+            Ok(storage) => storage,
+            Err(e) => {
+                ctx.log.log(
+                    "",
+                    &[&"tx_history", &self.ticker],
+                    &ERRL!("Error {} on creating the tx history storage, stop the history loop", e),
+                );
+                return;
+            },
+        };
+        let wallet_id = self.history_wallet_id();
+        if let Err(e) = storage.init(&wallet_id).await {
+            ctx.log.log(
+                "",
+                &[&"tx_history", &self.ticker],
+                &ERRL!(
+                    "Error {:?} on the tx history storage initialization, stop the history loop",
+                    e
+                ),
+            );
+            return;
+        }
+
         let mut success_iteration = 0i32;
         loop {
             if ctx.is_stopping() {
@@ -1995,22 +2255,20 @@ impl EthCoin {
             all_events.sort_by(|a, b| b.block_number.unwrap().cmp(&a.block_number.unwrap()));
 
             for event in all_events {
-                let mut existing_history = match self.load_history_from_file(ctx).compat().await {
-                    Ok(history) => history,
+                let internal_id = BytesJson::from(sha256(&json::to_vec(&event).unwrap()).to_vec());
+                match storage.get_tx_from_history(&wallet_id, &internal_id).await {
+                    // the transaction already imported
+                    Ok(Some(_)) => continue,
+                    Ok(None) => (),
                     Err(e) => {
                         ctx.log.log(
                             "",
                             &[&"tx_history", &self.ticker],
-                            &ERRL!("Error {} on 'load_history_from_file', stop the history loop", e),
+                            &ERRL!("Error {:?} on 'get_tx_from_history', stop the history loop", e),
                         );
                         return;
                     },
-                };
-                let internal_id = BytesJson::from(sha256(&json::to_vec(&event).unwrap()).to_vec());
-                if existing_history.iter().any(|item| item.internal_id == internal_id) {
-                    // the transaction already imported
-                    continue;
-                };
+                }
 
                 let amount = U256::from(event.data.0.as_slice());
                 let total_amount = u256_to_big_decimal(amount, self.decimals).unwrap();
@@ -2143,16 +2401,15 @@ impl EthCoin {
                     internal_id: BytesJson(internal_id.to_vec()),
                     timestamp: block.timestamp.into(),
                     kmd_rewards: None,
-                    transaction_type: Default::default(),
+                    spv_verified: None,
+                    transaction_type: TransactionType::TokenTransfer(BytesJson(token_addr.0.to_vec())),
                 };
 
-                existing_history.push(details);
-
-                if let Err(e) = self.save_history_to_file(ctx, existing_history).compat().await {
+                if let Err(e) = storage.add_transactions_to_history(&wallet_id, vec![details]).await {
                     ctx.log.log(
                         "",
                         &[&"tx_history", &self.ticker],
-                        &ERRL!("Error {} on 'save_history_to_file', stop the history loop", e),
+                        &ERRL!("Error {:?} on 'add_transactions_to_history', stop the history loop", e),
                     );
                     return;
                 }
@@ -2230,47 +2487,80 @@ impl EthCoin {
                 platform: _,
                 token_addr,
             } => {
-                let allowance_fut = self
-                    .allowance(swap_contract_address)
-                    .map_err(|e| TransactionErr::Plain(ERRL!("{}", e)));
-
-                let function = try_tx_fus!(SWAP_CONTRACT.function("erc20Payment"));
-                let data = try_tx_fus!(function.encode_input(&[
-                    Token::FixedBytes(id),
-                    Token::Uint(value),
-                    Token::Address(*token_addr),
-                    Token::Address(receiver_addr),
-                    Token::FixedBytes(secret_hash.to_vec()),
-                    Token::Uint(U256::from(time_lock))
-                ]));
-
-                let arc = self.clone();
-                Box::new(allowance_fut.and_then(move |allowed| -> EthTxFut {
-                    if allowed < value {
-                        Box::new(
-                            arc.approve(swap_contract_address, U256::max_value())
-                                .and_then(move |_approved| {
-                                    arc.sign_and_send_transaction(
-                                        0.into(),
-                                        Action::Call(swap_contract_address),
-                                        data,
-                                        U256::from(150_000),
-                                    )
-                                }),
-                        )
-                    } else {
-                        Box::new(arc.sign_and_send_transaction(
-                            0.into(),
-                            Action::Call(swap_contract_address),
-                            data,
-                            U256::from(150_000),
-                        ))
-                    }
-                }))
+                if self.is_swap_v2_contract(swap_contract_address) {
+                    return self.send_erc20_payment_with_permit(
+                        id,
+                        value,
+                        time_lock,
+                        secret_hash,
+                        receiver_addr,
+                        swap_contract_address,
+                        *token_addr,
+                    );
+                }
+                self.send_erc20_payment_with_approve(
+                    id,
+                    value,
+                    time_lock,
+                    secret_hash,
+                    receiver_addr,
+                    swap_contract_address,
+                    *token_addr,
+                )
             },
         }
     }
 
+    /// Approves the tokens to the swap contract if the allowance is not enough and sends the `erc20Payment`.
+    fn send_erc20_payment_with_approve(
+        &self,
+        id: Vec<u8>,
+        value: U256,
+        time_lock: u32,
+        secret_hash: &[u8],
+        receiver_addr: Address,
+        swap_contract_address: Address,
+        token_addr: Address,
+    ) -> EthTxFut {
+        let allowance_fut = self
+            .allowance(swap_contract_address)
+            .map_err(|e| TransactionErr::Plain(ERRL!("{}", e)));
+
+        let function = try_tx_fus!(SWAP_CONTRACT.function("erc20Payment"));
+        let data = try_tx_fus!(function.encode_input(&[
+            Token::FixedBytes(id),
+            Token::Uint(value),
+            Token::Address(token_addr),
+            Token::Address(receiver_addr),
+            Token::FixedBytes(secret_hash.to_vec()),
+            Token::Uint(U256::from(time_lock))
+        ]));
+
+        let arc = self.clone();
+        Box::new(allowance_fut.and_then(move |allowed| -> EthTxFut {
+            if allowed < value {
+                Box::new(
+                    arc.approve(swap_contract_address, U256::max_value())
+                        .and_then(move |_approved| {
+                            arc.sign_and_send_transaction(
+                                0.into(),
+                                Action::Call(swap_contract_address),
+                                data,
+                                U256::from(150_000),
+                            )
+                        }),
+                )
+            } else {
+                Box::new(arc.sign_and_send_transaction(
+                    0.into(),
+                    Action::Call(swap_contract_address),
+                    data,
+                    U256::from(150_000),
+                ))
+            }
+        }))
+    }
+
     fn spend_hash_time_locked_payment(
         &self,
         payment: SignedEthTx,
@@ -2321,8 +2611,7 @@ impl EthCoin {
                 platform: _,
                 token_addr,
             } => {
-                let payment_func = try_tx_fus!(SWAP_CONTRACT.function("erc20Payment"));
-                let decoded = try_tx_fus!(payment_func.decode_input(&payment.data));
+                let decoded = try_tx_fus!(decode_erc20_payment_input(&payment.data));
                 let state_f = self.payment_status(swap_contract_address, decoded[0].clone());
 
                 Box::new(
@@ -2400,8 +2689,7 @@ impl EthCoin {
                 platform: _,
                 token_addr,
             } => {
-                let payment_func = try_tx_fus!(SWAP_CONTRACT.function("erc20Payment"));
-                let decoded = try_tx_fus!(payment_func.decode_input(&payment.data));
+                let decoded = try_tx_fus!(decode_erc20_payment_input(&payment.data));
                 let state_f = self.payment_status(swap_contract_address, decoded[0].clone());
                 Box::new(
                     state_f
@@ -2606,14 +2894,21 @@ impl EthCoin {
             let swap_id = selfi.etomic_swap_id(time_lock, &secret_hash);
             let status = try_s!(
                 selfi
-                    .payment_status(expected_swap_contract_address, Token::FixedBytes(swap_id.clone()))
-                    .compat()
+                    .swap_payment_status(expected_swap_contract_address, swap_id.clone())
                     .await
             );
             if status != PAYMENT_STATE_SENT.into() {
                 return ERR!("Payment state is not PAYMENT_STATE_SENT, got {}", status);
             }
 
+            try_s!(
+                selfi
+                    .check_payment_quorum(tx.hash, expected_swap_contract_address)
+                    .await
+            );
+
+            // The tx hash commits to the tx content, so the node is only asked whether the tx is known,
+            // and the fields of the locally decoded tx are validated.
             let tx_from_rpc = try_s!(
                 selfi
                     .web3
@@ -2622,130 +2917,199 @@ impl EthCoin {
                     .compat()
                     .await
             );
-            let tx_from_rpc = match tx_from_rpc {
-                Some(t) => t,
-                None => return ERR!("Didn't find provided tx {:?} on ETH node", tx),
-            };
+            if tx_from_rpc.is_none() {
+                return ERR!("Didn't find provided tx {:?} on ETH node", tx);
+            }
 
-            if tx_from_rpc.from != sender {
-                return ERR!(
-                    "Payment tx {:?} was sent from wrong address, expected {:?}",
-                    tx_from_rpc,
-                    sender
-                );
+            selfi.validate_payment_tx_content(
+                &tx,
+                sender,
+                &swap_id,
+                &secret_hash,
+                time_lock,
+                expected_value,
+                expected_swap_contract_address,
+            )
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    /// Validates the payment generated by [`SwapOps::gen_unbroadcast_swap_payment`].
+    /// The payment can be sent only if the swap id isn't used in the swap contract yet.
+    fn validate_unbroadcast_payment(
+        &self,
+        payment_tx: &[u8],
+        time_lock: u32,
+        sender_pub: &[u8],
+        secret_hash: &[u8],
+        amount: BigDecimal,
+        expected_swap_contract_address: Address,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        let unsigned: UnverifiedTransaction = try_fus!(rlp::decode(payment_tx));
+        let tx = try_fus!(SignedEthTx::new(unsigned));
+        let sender = try_fus!(addr_from_raw_pubkey(sender_pub));
+        let expected_value = try_fus!(wei_from_big_decimal(&amount, self.decimals));
+        let selfi = self.clone();
+        let secret_hash = secret_hash.to_vec();
+        let fut = async move {
+            let swap_id = selfi.etomic_swap_id(time_lock, &secret_hash);
+            let status = try_s!(
+                selfi
+                    .swap_payment_status(expected_swap_contract_address, swap_id.clone())
+                    .await
+            );
+            if status != PAYMENT_STATE_UNINITIALIZED.into() {
+                return ERR!("Payment state is not PAYMENT_STATE_UNINITIALIZED, got {}", status);
             }
 
-            match &selfi.coin_type {
-                EthCoinType::Eth => {
-                    if tx_from_rpc.to != Some(expected_swap_contract_address) {
-                        return ERR!(
-                            "Payment tx {:?} was sent to wrong address, expected {:?}",
-                            tx_from_rpc,
-                            expected_swap_contract_address
-                        );
-                    }
+            selfi.validate_payment_tx_content(
+                &tx,
+                sender,
+                &swap_id,
+                &secret_hash,
+                time_lock,
+                expected_value,
+                expected_swap_contract_address,
+            )
+        };
+        Box::new(fut.boxed().compat())
+    }
 
-                    if tx_from_rpc.value != expected_value {
-                        return ERR!(
-                            "Payment tx {:?} value is invalid, expected {:?}",
-                            tx_from_rpc,
-                            expected_value
-                        );
-                    }
+    /// Returns the swap payment state, by the quorum of the nodes if it's configured.
+    async fn swap_payment_status(&self, swap_contract_address: Address, swap_id: Vec<u8>) -> Result<U256, String> {
+        match self.quorum {
+            Some(quorum) => {
+                self.payment_status_by_quorum(swap_contract_address, Token::FixedBytes(swap_id), quorum)
+                    .await
+            },
+            None => {
+                self.payment_status(swap_contract_address, Token::FixedBytes(swap_id))
+                    .compat()
+                    .await
+            },
+        }
+    }
 
-                    let function = try_s!(SWAP_CONTRACT.function("ethPayment"));
-                    let decoded = try_s!(function.decode_input(&tx_from_rpc.input.0));
-                    if decoded[0] != Token::FixedBytes(swap_id.clone()) {
-                        return ERR!("Invalid 'swap_id' {:?}, expected {:?}", decoded, swap_id);
-                    }
+    /// Checks that the payment tx is sent by the `sender` to the swap contract with the expected arguments.
+    #[allow(clippy::too_many_arguments)]
+    fn validate_payment_tx_content(
+        &self,
+        tx: &SignedEthTx,
+        sender: Address,
+        swap_id: &[u8],
+        secret_hash: &[u8],
+        time_lock: u32,
+        expected_value: U256,
+        expected_swap_contract_address: Address,
+    ) -> Result<(), String> {
+        if tx.sender() != sender {
+            return ERR!("Payment tx {:?} was sent from wrong address, expected {:?}", tx, sender);
+        }
 
-                    if decoded[1] != Token::Address(selfi.my_address) {
-                        return ERR!(
-                            "Payment tx receiver arg {:?} is invalid, expected {:?}",
-                            decoded[1],
-                            Token::Address(selfi.my_address)
-                        );
-                    }
+        match &self.coin_type {
+            EthCoinType::Eth => {
+                if tx.action != Action::Call(expected_swap_contract_address) {
+                    return ERR!(
+                        "Payment tx {:?} was sent to wrong address, expected {:?}",
+                        tx,
+                        expected_swap_contract_address
+                    );
+                }
 
-                    if decoded[2] != Token::FixedBytes(secret_hash.to_vec()) {
-                        return ERR!(
-                            "Payment tx secret_hash arg {:?} is invalid, expected {:?}",
-                            decoded[2],
-                            Token::FixedBytes(secret_hash.to_vec())
-                        );
-                    }
+                if tx.value != expected_value {
+                    return ERR!("Payment tx {:?} value is invalid, expected {:?}", tx, expected_value);
+                }
 
-                    if decoded[3] != Token::Uint(U256::from(time_lock)) {
-                        return ERR!(
-                            "Payment tx time_lock arg {:?} is invalid, expected {:?}",
-                            decoded[3],
-                            Token::Uint(U256::from(time_lock))
-                        );
-                    }
-                },
-                EthCoinType::Erc20 {
-                    platform: _,
-                    token_addr,
-                } => {
-                    if tx_from_rpc.to != Some(expected_swap_contract_address) {
-                        return ERR!(
-                            "Payment tx {:?} was sent to wrong address, expected {:?}",
-                            tx_from_rpc,
-                            expected_swap_contract_address
-                        );
-                    }
+                let function = try_s!(SWAP_CONTRACT.function("ethPayment"));
+                let decoded = try_s!(function.decode_input(&tx.data));
+                if decoded[0] != Token::FixedBytes(swap_id.to_vec()) {
+                    return ERR!("Invalid 'swap_id' {:?}, expected {:?}", decoded, swap_id);
+                }
 
-                    let function = try_s!(SWAP_CONTRACT.function("erc20Payment"));
-                    let decoded = try_s!(function.decode_input(&tx_from_rpc.input.0));
-                    if decoded[0] != Token::FixedBytes(swap_id.clone()) {
-                        return ERR!("Invalid 'swap_id' {:?}, expected {:?}", decoded, swap_id);
-                    }
+                if decoded[1] != Token::Address(self.my_address) {
+                    return ERR!(
+                        "Payment tx receiver arg {:?} is invalid, expected {:?}",
+                        decoded[1],
+                        Token::Address(self.my_address)
+                    );
+                }
 
-                    if decoded[1] != Token::Uint(expected_value) {
-                        return ERR!(
-                            "Payment tx value arg {:?} is invalid, expected {:?}",
-                            decoded[1],
-                            Token::Uint(expected_value)
-                        );
-                    }
+                if decoded[2] != Token::FixedBytes(secret_hash.to_vec()) {
+                    return ERR!(
+                        "Payment tx secret_hash arg {:?} is invalid, expected {:?}",
+                        decoded[2],
+                        Token::FixedBytes(secret_hash.to_vec())
+                    );
+                }
 
-                    if decoded[2] != Token::Address(*token_addr) {
-                        return ERR!(
-                            "Payment tx token_addr arg {:?} is invalid, expected {:?}",
-                            decoded[2],
-                            Token::Address(*token_addr)
-                        );
-                    }
+                if decoded[3] != Token::Uint(U256::from(time_lock)) {
+                    return ERR!(
+                        "Payment tx time_lock arg {:?} is invalid, expected {:?}",
+                        decoded[3],
+                        Token::Uint(U256::from(time_lock))
+                    );
+                }
+            },
+            EthCoinType::Erc20 {
+                platform: _,
+                token_addr,
+            } => {
+                if tx.action != Action::Call(expected_swap_contract_address) {
+                    return ERR!(
+                        "Payment tx {:?} was sent to wrong address, expected {:?}",
+                        tx,
+                        expected_swap_contract_address
+                    );
+                }
 
-                    if decoded[3] != Token::Address(selfi.my_address) {
-                        return ERR!(
-                            "Payment tx receiver arg {:?} is invalid, expected {:?}",
-                            decoded[3],
-                            Token::Address(selfi.my_address)
-                        );
-                    }
+                let decoded = try_s!(decode_erc20_payment_input(&tx.data));
+                if decoded[0] != Token::FixedBytes(swap_id.to_vec()) {
+                    return ERR!("Invalid 'swap_id' {:?}, expected {:?}", decoded, swap_id);
+                }
 
-                    if decoded[4] != Token::FixedBytes(secret_hash.to_vec()) {
-                        return ERR!(
-                            "Payment tx secret_hash arg {:?} is invalid, expected {:?}",
-                            decoded[4],
-                            Token::FixedBytes(secret_hash.to_vec())
-                        );
-                    }
+                if decoded[1] != Token::Uint(expected_value) {
+                    return ERR!(
+                        "Payment tx value arg {:?} is invalid, expected {:?}",
+                        decoded[1],
+                        Token::Uint(expected_value)
+                    );
+                }
 
-                    if decoded[5] != Token::Uint(U256::from(time_lock)) {
-                        return ERR!(
-                            "Payment tx time_lock arg {:?} is invalid, expected {:?}",
-                            decoded[5],
-                            Token::Uint(U256::from(time_lock))
-                        );
-                    }
-                },
-            }
+                if decoded[2] != Token::Address(*token_addr) {
+                    return ERR!(
+                        "Payment tx token_addr arg {:?} is invalid, expected {:?}",
+                        decoded[2],
+                        Token::Address(*token_addr)
+                    );
+                }
 
-            Ok(())
-        };
-        Box::new(fut.boxed().compat())
+                if decoded[3] != Token::Address(self.my_address) {
+                    return ERR!(
+                        "Payment tx receiver arg {:?} is invalid, expected {:?}",
+                        decoded[3],
+                        Token::Address(self.my_address)
+                    );
+                }
+
+                if decoded[4] != Token::FixedBytes(secret_hash.to_vec()) {
+                    return ERR!(
+                        "Payment tx secret_hash arg {:?} is invalid, expected {:?}",
+                        decoded[4],
+                        Token::FixedBytes(secret_hash.to_vec())
+                    );
+                }
+
+                if decoded[5] != Token::Uint(U256::from(time_lock)) {
+                    return ERR!(
+                        "Payment tx time_lock arg {:?} is invalid, expected {:?}",
+                        decoded[5],
+                        Token::Uint(U256::from(time_lock))
+                    );
+                }
+            },
+        }
+
+        Ok(())
     }
 
     fn payment_status(
@@ -2753,7 +3117,12 @@ impl EthCoin {
         swap_contract_address: H160,
         token: Token,
     ) -> Box<dyn Future<Item = U256, Error = String> + Send + 'static> {
-        let function = try_fus!(SWAP_CONTRACT.function("payments"));
+        let contract = if self.is_swap_v2_contract(swap_contract_address) {
+            &*SWAP_V2_CONTRACT
+        } else {
+            &*SWAP_CONTRACT
+        };
+        let function = try_fus!(contract.function("payments"));
 
         let data = try_fus!(function.encode_input(&[token]));
 
@@ -2779,18 +3148,22 @@ impl EthCoin {
         let unverified: UnverifiedTransaction = try_s!(rlp::decode(tx));
         let tx = try_s!(SignedEthTx::new(unverified));
 
-        let func_name = match self.coin_type {
-            EthCoinType::Eth => "ethPayment",
-            EthCoinType::Erc20 { .. } => "erc20Payment",
+        let decoded = match self.coin_type {
+            EthCoinType::Eth => {
+                let payment_func = try_s!(SWAP_CONTRACT.function("ethPayment"));
+                try_s!(payment_func.decode_input(&tx.data))
+            },
+            EthCoinType::Erc20 { .. } => try_s!(decode_erc20_payment_input(&tx.data)),
         };
-
-        let payment_func = try_s!(SWAP_CONTRACT.function(func_name));
-        let decoded = try_s!(payment_func.decode_input(&tx.data));
         let id = match &decoded[0] {
             Token::FixedBytes(bytes) => bytes.clone(),
             _ => panic!(),
         };
 
+        if self.is_swap_v2_contract(swap_contract_address) {
+            return self.search_for_swap_tx_spend_v2(swap_contract_address, id).await;
+        }
+
         let mut current_block = try_s!(self.current_block().compat().await);
         if current_block < search_from_block {
             current_block = search_from_block;
@@ -2995,6 +3368,13 @@ impl MmCoin for EthCoin {
         cfg_native! {
             let coin = self.clone();
             let fut = async move {
+                if let Err(e) = coin.remove_history_file(&ctx).compat().await {
+                    ctx.log.log(
+                        "",
+                        &[&"tx_history", &coin.ticker],
+                        &ERRL!("Error {} on removing the legacy tx history file", e),
+                    );
+                }
                 match coin.coin_type {
                     EthCoinType::Eth => coin.process_eth_history(&ctx).await,
                     EthCoinType::Erc20 { ref token_addr, .. } => coin.process_erc20_history(*token_addr, &ctx).await,
@@ -3005,6 +3385,10 @@ impl MmCoin for EthCoin {
         }
     }
 
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+        load_history_from_storage(self, ctx)
+    }
+
     fn history_sync_status(&self) -> HistorySyncState { self.history_sync_state.lock().unwrap().clone() }
 
     fn get_trade_fee(&self) -> Box<dyn Future<Item = TradeFee, Error = String> + Send> {
@@ -3045,6 +3429,8 @@ impl MmCoin for EthCoin {
                         wei_from_big_decimal(&value, self.decimals)?
                     },
                 };
+                // The swap contract v2 is used only if the other side supports it,
+                // so the `approve` call required by the swap contract v1 is taken into account.
                 let allowed = self.allowance(self.swap_contract_address).compat().await?;
                 if allowed < value {
                     // estimate gas for the `approve` contract call
@@ -3168,6 +3554,22 @@ impl MmCoin for EthCoin {
     fn is_coin_protocol_supported(&self, _info: &Option<Vec<u8>>) -> bool { true }
 }
 
+#[async_trait]
+impl CoinWithTxHistoryV2 for EthCoin {
+    /// ERC20 token transfers are stored within the platform coin's history.
+    fn history_wallet_id(&self) -> WalletId { WalletId::new(self.platform_ticker().to_owned()) }
+
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        let filters = match self.coin_type {
+            EthCoinType::Eth => GetTxHistoryFilters::new(),
+            EthCoinType::Erc20 { token_addr, .. } => {
+                GetTxHistoryFilters::new().with_token_id(hex::encode(token_addr.0))
+            },
+        };
+        Ok(filters)
+    }
+}
+
 pub trait TryToAddress {
     fn try_to_address(&self) -> Result<Address, String>;
 }
@@ -3355,6 +3757,47 @@ fn rpc_event_handlers_for_eth_transport(ctx: &MmArc, ticker: String) -> Vec<RpcT
 #[inline]
 fn new_nonce_lock() -> Arc<AsyncMutex<()>> { Arc::new(AsyncMutex::new(())) }
 
+/// Returns the response at least `quorum` nodes agree on.
+/// The failed and `null` responses aren't counted, e.g. a lagging node may not know the requested tx yet.
+fn quorum_result<T: fmt::Debug + PartialEq>(
+    results: Vec<Result<Option<T>, web3::Error>>,
+    quorum: usize,
+    method: &str,
+) -> Result<T, String> {
+    let mut votes: Vec<(T, usize)> = Vec::with_capacity(results.len());
+    let mut null_responses = 0;
+    let mut errors = Vec::new();
+    for result in results {
+        match result {
+            Ok(Some(response)) => match votes.iter_mut().find(|(voted, _)| *voted == response) {
+                Some((_, count)) => *count += 1,
+                None => votes.push((response, 1)),
+            },
+            Ok(None) => null_responses += 1,
+            Err(e) => errors.push(e.to_string()),
+        }
+    }
+
+    let mut agreed = votes.into_iter().filter(|(_, count)| *count >= quorum);
+    match (agreed.next(), agreed.next()) {
+        (Some((response, _)), None) => Ok(response),
+        (Some((first, _)), Some((second, _))) => ERR!(
+            "Nodes returned different {} responses reaching the quorum {}: {:?} and {:?}",
+            method,
+            quorum,
+            first,
+            second
+        ),
+        (None, _) => ERR!(
+            "No {} response is returned by {} nodes required, null responses: {}, errors: {:?}",
+            method,
+            quorum,
+            null_responses,
+            errors
+        ),
+    }
+}
+
 pub async fn eth_coin_from_conf_and_request(
     ctx: &MmArc,
     ticker: &str,
@@ -3382,9 +3825,19 @@ pub async fn eth_coin_from_conf_and_request(
         }
     }
 
+    let swap_v2_contract: Option<Address> = try_s!(json::from_value(req["swap_v2_contract"].clone()));
+    if let Some(swap_v2) = swap_v2_contract {
+        if swap_v2 == Address::default() {
+            return ERR!("swap_v2_contract can't be zero address");
+        }
+    }
+
     let key_pair: KeyPair = try_s!(KeyPair::from_secret_slice(priv_key));
     let my_address = key_pair.address();
 
+    #[cfg(not(target_arch = "wasm32"))]
+    let proxy = try_s!(coin_proxy(ctx, conf));
+
     let mut web3_instances = vec![];
     let event_handlers = rpc_event_handlers_for_eth_transport(ctx, ticker.to_string());
     for url in urls.iter() {
@@ -3392,6 +3845,8 @@ pub async fn eth_coin_from_conf_and_request(
             vec![url.clone()],
             event_handlers.clone()
         ));
+        #[cfg(not(target_arch = "wasm32"))]
+        let transport = transport.with_proxy(proxy.clone());
         let web3 = Web3::new(transport);
         let version = match web3.web3().client_version().compat().await {
             Ok(v) => v,
@@ -3410,7 +3865,40 @@ pub async fn eth_coin_from_conf_and_request(
         return ERR!("Failed to get client version for all urls");
     }
 
+    let quorum: Option<usize> = try_s!(json::from_value(req["quorum"].clone()));
+    if let Some(quorum) = quorum {
+        if quorum == 0 || quorum > web3_instances.len() {
+            return ERR!(
+                "quorum {} must be between 1 and the number of the available nodes {}",
+                quorum,
+                web3_instances.len()
+            );
+        }
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let ws_subscriptions = {
+        let ws_urls: Option<Vec<String>> = try_s!(json::from_value(req["ws_urls"].clone()));
+        match ws_urls {
+            Some(ws_urls) if !ws_urls.is_empty() => {
+                let contracts = std::iter::once(swap_contract_address)
+                    .chain(fallback_swap_contract)
+                    .chain(swap_v2_contract)
+                    .collect();
+                Some(EthWsSubscriptions::spawn(
+                    ticker.to_owned(),
+                    ws_urls,
+                    contracts,
+                    proxy.clone(),
+                ))
+            },
+            _ => None,
+        }
+    };
+
     let transport = try_s!(Web3Transport::with_event_handlers(urls, event_handlers));
+    #[cfg(not(target_arch = "wasm32"))]
+    let transport = transport.with_proxy(proxy);
     let web3 = Web3::new(transport);
 
     let (coin_type, decimals) = match protocol {
@@ -3467,6 +3955,7 @@ pub async fn eth_coin_from_conf_and_request(
         sign_message_prefix,
         swap_contract_address,
         fallback_swap_contract,
+        swap_v2_contract,
         decimals,
         ticker: ticker.into(),
         gas_station_url: try_s!(json::from_value(req["gas_station_url"].clone())),
@@ -3480,6 +3969,9 @@ pub async fn eth_coin_from_conf_and_request(
         chain_id: conf["chain_id"].as_u64(),
         logs_block_range: conf["logs_block_range"].as_u64().unwrap_or(DEFAULT_LOGS_BLOCK_RANGE),
         nonce_lock,
+        quorum,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions,
     };
     Ok(EthCoin(Arc::new(coin)))
 }
@@ -1,6 +1,7 @@
 use super::*;
 use common::block_on;
 use mm2_core::mm_ctx::{MmArc, MmCtxBuilder};
+use mm2_test_helpers::for_tests::mm_ctx_with_custom_db;
 use mocktopus::mocking::*;
 
 /// The gas price for the tests
@@ -21,6 +22,15 @@ fn eth_coin_for_test(
     coin_type: EthCoinType,
     urls: Vec<String>,
     fallback_swap_contract: Option<Address>,
+) -> (MmArc, EthCoin) {
+    eth_coin_with_swap_v2_for_test(coin_type, urls, fallback_swap_contract, None)
+}
+
+fn eth_coin_with_swap_v2_for_test(
+    coin_type: EthCoinType,
+    urls: Vec<String>,
+    fallback_swap_contract: Option<Address>,
+    swap_v2_contract: Option<Address>,
 ) -> (MmArc, EthCoin) {
     let key_pair = KeyPair::from_secret_slice(
         &hex::decode("809465b17d0a4ddb3e4c69e8f23c2cabad868f51f8bed5c765ad1d6516c3306f").unwrap(),
@@ -52,6 +62,7 @@ fn eth_coin_for_test(
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract,
+        swap_v2_contract,
         ticker,
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
@@ -63,6 +74,9 @@ fn eth_coin_for_test(
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
     (ctx, eth_coin)
 }
@@ -216,6 +230,7 @@ fn send_and_refund_erc20_payment() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
             is_parity: true,
@@ -231,6 +246,9 @@ fn send_and_refund_erc20_payment() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     let payment = coin
@@ -283,6 +301,7 @@ fn send_and_refund_eth_payment() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
             is_parity: true,
@@ -298,6 +317,9 @@ fn send_and_refund_eth_payment() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     let payment = coin
@@ -359,6 +381,7 @@ fn test_nonce_several_urls() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         web3_instances: vec![
             Web3Instance {
                 web3: web3_infura.clone(),
@@ -384,6 +407,9 @@ fn test_nonce_several_urls() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     log!("My address {:?}", coin.my_address);
@@ -422,6 +448,7 @@ fn test_wait_for_payment_spend_timeout() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         ticker: "ETH".into(),
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
@@ -433,6 +460,9 @@ fn test_wait_for_payment_spend_timeout() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     };
 
     let coin = EthCoin(Arc::new(coin));
@@ -486,6 +516,7 @@ fn test_search_for_swap_tx_spend_was_spent() {
         key_pair,
         swap_contract_address,
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         ticker: "ETH".into(),
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
@@ -497,6 +528,9 @@ fn test_search_for_swap_tx_spend_was_spent() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     // raw transaction bytes of https://ropsten.etherscan.io/tx/0xb1c987e2ac79581bb8718267b5cb49a18274890494299239d1d0dfdb58d6d76a
@@ -597,6 +631,7 @@ fn test_search_for_swap_tx_spend_was_refunded() {
         key_pair,
         swap_contract_address,
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         ticker: "ETH".into(),
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
@@ -608,6 +643,9 @@ fn test_search_for_swap_tx_spend_was_refunded() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     // raw transaction bytes of https://ropsten.etherscan.io/tx/0xe18bbca69dea9a4624e1f5b0b2021d5fe4c8daa03f36084a8ba011b08e5cd938
@@ -1207,6 +1245,75 @@ fn test_negotiate_swap_contract_addr_has_fallback() {
     assert_eq!(Some(fallback.to_vec().into()), result);
 }
 
+#[test]
+fn test_swap_v2_contract_is_opt_in() {
+    let swap_v2_contract = "0x9130b257D37A52E52F21054c4DA3450c72f595CE".into();
+    let (_, coin) = eth_coin_with_swap_v2_for_test(
+        EthCoinType::Eth,
+        vec!["http://eth1.cipig.net:8555".into()],
+        None,
+        Some(swap_v2_contract),
+    );
+
+    // The swap contract v1 is still advertised as the swap contract.
+    let swap_contract: &[u8] = coin.swap_contract_address.as_ref();
+    assert_eq!(coin.swap_contract_address(), Some(swap_contract.to_vec().into()));
+    let v2_slice: &[u8] = swap_v2_contract.as_ref();
+    assert_eq!(coin.swap_v2_contract_address(), Some(v2_slice.to_vec().into()));
+    assert!(coin.is_swap_v2_contract(swap_v2_contract));
+    assert!(!coin.is_swap_v2_contract(coin.swap_contract_address));
+
+    // The swap contract v2 is accepted by the negotiation if configured.
+    // It's negotiated only if both sides advertise it as `swap_v2_contract_address` though.
+    let result = coin.negotiate_swap_contract_addr(Some(v2_slice)).unwrap();
+    assert_eq!(Some(v2_slice.to_vec().into()), result);
+    let result = coin.negotiate_swap_contract_addr(Some(swap_contract)).unwrap();
+    assert_eq!(Some(swap_contract.to_vec().into()), result);
+
+    let (_, coin) = eth_coin_for_test(EthCoinType::Eth, vec!["http://eth1.cipig.net:8555".into()], None);
+    assert_eq!(coin.swap_v2_contract_address(), None);
+    assert!(!coin.is_swap_v2_contract(swap_v2_contract));
+    let error = coin
+        .negotiate_swap_contract_addr(Some(v2_slice))
+        .unwrap_err()
+        .into_inner();
+    assert_eq!(
+        NegotiateSwapContractAddrErr::UnexpectedOtherAddr(v2_slice.to_vec().into()),
+        error
+    );
+}
+
+#[test]
+fn test_refund_hash_time_locked_payments_batch_requires_swap_v2() {
+    let swap_v2_contract = "0x9130b257D37A52E52F21054c4DA3450c72f595CE".into();
+    let (_, coin) = eth_coin_with_swap_v2_for_test(
+        EthCoinType::Eth,
+        vec!["http://eth1.cipig.net:8555".into()],
+        None,
+        Some(swap_v2_contract),
+    );
+
+    let error = coin
+        .refund_hash_time_locked_payments_batch(coin.swap_contract_address, vec![])
+        .wait()
+        .unwrap_err();
+    assert!(
+        error.get_plain_text_format().contains("swap contract v2 only"),
+        "{:?}",
+        error
+    );
+
+    let error = coin
+        .refund_hash_time_locked_payments_batch(swap_v2_contract, vec![])
+        .wait()
+        .unwrap_err();
+    assert!(
+        error.get_plain_text_format().contains("No payments to refund"),
+        "{:?}",
+        error
+    );
+}
+
 #[test]
 #[ignore]
 fn polygon_check_if_my_payment_sent() {
@@ -1280,6 +1387,7 @@ fn test_message_hash() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
             is_parity: true,
@@ -1295,6 +1403,9 @@ fn test_message_hash() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     let message_hash = coin.sign_message_hash("test").unwrap();
@@ -1321,6 +1432,7 @@ fn test_sign_verify_message() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
             is_parity: true,
@@ -1336,6 +1448,9 @@ fn test_sign_verify_message() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
+        #[cfg(not(target_arch = "wasm32"))]
+        ws_subscriptions: None,
     }));
 
     let message = "test";
@@ -1347,3 +1462,95 @@ fn test_sign_verify_message() {
         .unwrap();
     assert!(is_valid);
 }
+
+#[test]
+fn test_quorum_result() {
+    fn transport_error() -> web3::Error { web3::Error::from(web3::error::ErrorKind::Transport("timeout".into())) }
+
+    let results = vec![Ok(Some(1)), Err(transport_error()), Ok(Some(1))];
+    assert_eq!(quorum_result(results, 2, "eth_getLogs").unwrap(), 1);
+
+    // Not enough nodes responded.
+    let results = vec![Ok(Some(1)), Err(transport_error()), Err(transport_error())];
+    assert!(quorum_result(results, 2, "eth_getLogs").is_err());
+
+    // The null responses aren't counted.
+    let results = vec![Ok(Some(1)), Ok(None), Ok(None)];
+    let error = quorum_result(results, 2, "eth_getTransactionReceipt").unwrap_err();
+    assert!(error.contains("null responses: 2"), "{}", error);
+
+    // A single node returning a different response doesn't break the quorum.
+    let results = vec![Ok(Some(1)), Ok(Some(2)), Ok(Some(1)), Ok(None)];
+    assert_eq!(quorum_result(results, 2, "eth_getLogs").unwrap(), 1);
+
+    // No response reaches the quorum.
+    let results = vec![Ok(Some(1)), Ok(Some(2)), Ok(Some(3))];
+    assert!(quorum_result(results, 2, "eth_getLogs").is_err());
+
+    // Different responses reach the quorum.
+    let results = vec![Ok(Some(1)), Ok(Some(2)), Ok(Some(1)), Ok(Some(2))];
+    let error = quorum_result(results, 2, "eth_getLogs").unwrap_err();
+    assert!(error.contains("different eth_getLogs responses"), "{}", error);
+}
+
+#[test]
+fn test_load_history_from_storage() {
+    fn tx_details(coin: &str, tx_hash: &str, transaction_type: TransactionType) -> TransactionDetails {
+        TransactionDetails {
+            tx_hex: BytesJson::default(),
+            tx_hash: tx_hash.to_owned(),
+            from: vec!["0xbAB36286672fbdc7B250804bf6D14Be0dF69fa29".to_owned()],
+            to: vec!["0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94".to_owned()],
+            total_amount: 1.into(),
+            spent_by_me: 1.into(),
+            received_by_me: 0.into(),
+            my_balance_change: BigDecimal::from(-1),
+            block_height: 100,
+            timestamp: 1650000000,
+            fee_details: None,
+            coin: coin.to_owned(),
+            internal_id: BytesJson::from(hex::decode(tx_hash).unwrap()),
+            kmd_rewards: None,
+            transaction_type,
+            spv_verified: None,
+        }
+    }
+
+    let token_addr = Address::from("0x2b294F029Fde858b2c62184e8390591755521d8E");
+    let (_ctx, eth) = eth_coin_for_test(EthCoinType::Eth, vec!["http://dummy.dummy".into()], None);
+    let (_ctx, erc20) = eth_coin_for_test(
+        EthCoinType::Erc20 {
+            platform: "ETH".to_owned(),
+            token_addr,
+        },
+        vec!["http://dummy.dummy".into()],
+        None,
+    );
+    // ERC20 token transfers are stored within the platform coin's history.
+    assert_eq!(eth.history_wallet_id(), erc20.history_wallet_id());
+
+    let ctx = mm_ctx_with_custom_db();
+    // The history isn't loaded until the storage is initialized by the history loop.
+    assert!(block_on(eth.load_history(&ctx).compat()).unwrap().is_empty());
+
+    let eth_tx = tx_details(
+        "ETH",
+        "8e9a1c2a7e2a1bb9fd4fe9a3296a37a7aa0b5c6e2cd82dd51e3eb8fd38f7ccb1",
+        TransactionType::StandardTransfer,
+    );
+    let erc20_tx = tx_details(
+        "JST",
+        "cc0bd1cf3e53ba31dbd4ae61fd5bd3b27c1cd0ad4ff65a1a5bea8a2a20a3e5f8",
+        TransactionType::TokenTransfer(BytesJson(token_addr.0.to_vec())),
+    );
+    let storage = TxHistoryStorageBuilder::new(&ctx).build().unwrap();
+    let wallet_id = eth.history_wallet_id();
+    block_on(storage.init(&wallet_id)).unwrap();
+    block_on(storage.add_transactions_to_history(&wallet_id, vec![eth_tx.clone(), erc20_tx.clone()])).unwrap();
+
+    let eth_history = block_on(eth.load_history(&ctx).compat()).unwrap();
+    assert_eq!(eth_history, vec![eth_tx]);
+
+    let erc20_history = block_on(erc20.load_history(&ctx).compat()).unwrap();
+    assert_eq!(erc20_history, vec![erc20_tx]);
+}
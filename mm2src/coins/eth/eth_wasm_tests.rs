@@ -31,6 +31,7 @@ async fn test_send() {
         key_pair,
         swap_contract_address: Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"),
         fallback_swap_contract: None,
+        swap_v2_contract: None,
         web3_instances: vec![Web3Instance {
             web3: web3.clone(),
             is_parity: true,
@@ -46,6 +47,7 @@ async fn test_send() {
         chain_id: None,
         logs_block_range: DEFAULT_LOGS_BLOCK_RANGE,
         nonce_lock: new_nonce_lock(),
+        quorum: None,
     }));
     let tx = coin
         .send_maker_payment(
@@ -0,0 +1,456 @@
+//! The second version of the Etomic swap smart contract.
+//!
+//! The contract keeps the `ethPayment`, `erc20Payment`, `receiverSpend` and `senderRefund` methods of the first version
+//! and adds:
+//! * `erc20PaymentWithPermit` sending the ERC20 payment with the EIP-2612 permit instead of the separate `approve` tx;
+//! * the `sentBlock` and `finalizedBlock` fields of the `payments` record, so the payment, spend and refund txs
+//!   are found by the swap id without scanning the logs over `logs_block_range`;
+//! * `senderRefundBatch` refunding several payments within one tx.
+//!
+//! The contract is opt-in: it's used only if both sides of the swap advertise the same
+//! [`SwapOps::swap_v2_contract_address`], otherwise the swap goes through the contract v1.
+
+use super::*;
+
+const SWAP_V2_CONTRACT_ABI: &str = r#"[{"inputs":[{"name":"_id","type":"bytes32"},{"name":"_receiver","type":"address"},{"name":"_secretHash","type":"bytes20"},{"name":"_lockTime","type":"uint64"}],"name":"ethPayment","outputs":[],"stateMutability":"payable","type":"function"},{"inputs":[{"name":"_id","type":"bytes32"},{"name":"_amount","type":"uint256"},{"name":"_tokenAddress","type":"address"},{"name":"_receiver","type":"address"},{"name":"_secretHash","type":"bytes20"},{"name":"_lockTime","type":"uint64"}],"name":"erc20Payment","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"_id","type":"bytes32"},{"name":"_amount","type":"uint256"},{"name":"_tokenAddress","type":"address"},{"name":"_receiver","type":"address"},{"name":"_secretHash","type":"bytes20"},{"name":"_lockTime","type":"uint64"},{"name":"_deadline","type":"uint256"},{"name":"_v","type":"uint8"},{"name":"_r","type":"bytes32"},{"name":"_s","type":"bytes32"}],"name":"erc20PaymentWithPermit","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"_id","type":"bytes32"},{"name":"_amount","type":"uint256"},{"name":"_secret","type":"bytes32"},{"name":"_tokenAddress","type":"address"},{"name":"_sender","type":"address"}],"name":"receiverSpend","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"_id","type":"bytes32"},{"name":"_amount","type":"uint256"},{"name":"_paymentHash","type":"bytes20"},{"name":"_tokenAddress","type":"address"},{"name":"_receiver","type":"address"}],"name":"senderRefund","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"_ids","type":"bytes32[]"},{"name":"_amounts","type":"uint256[]"},{"name":"_paymentHashes","type":"bytes20[]"},{"name":"_tokenAddresses","type":"address[]"},{"name":"_receivers","type":"address[]"}],"name":"senderRefundBatch","outputs":[],"stateMutability":"nonpayable","type":"function"},{"inputs":[{"name":"","type":"bytes32"}],"name":"payments","outputs":[{"name":"paymentHash","type":"bytes20"},{"name":"lockTime","type":"uint64"},{"name":"state","type":"uint8"},{"name":"sentBlock","type":"uint64"},{"name":"finalizedBlock","type":"uint64"}],"stateMutability":"view","type":"function"},{"anonymous":false,"inputs":[{"indexed":false,"name":"id","type":"bytes32"}],"name":"PaymentSent","type":"event"},{"anonymous":false,"inputs":[{"indexed":false,"name":"id","type":"bytes32"},{"indexed":false,"name":"secret","type":"bytes32"}],"name":"ReceiverSpent","type":"event"},{"anonymous":false,"inputs":[{"indexed":false,"name":"id","type":"bytes32"}],"name":"SenderRefunded","type":"event"}]"#;
+/// https://github.com/ethereum/EIPs/blob/master/EIPS/eip-2612.md
+const ERC20_PERMIT_ABI: &str = r#"[{"inputs":[{"name":"owner","type":"address"}],"name":"nonces","outputs":[{"name":"","type":"uint256"}],"stateMutability":"view","type":"function"},{"inputs":[],"name":"DOMAIN_SEPARATOR","outputs":[{"name":"","type":"bytes32"}],"stateMutability":"view","type":"function"}]"#;
+
+const PERMIT_TYPE: &str = "Permit(address owner,address spender,uint256 value,uint256 nonce,uint256 deadline)";
+/// The permit must be used by the payment tx within this period, in seconds.
+const PERMIT_DEADLINE: u64 = 3600;
+
+lazy_static! {
+    pub static ref SWAP_V2_CONTRACT: Contract = Contract::load(SWAP_V2_CONTRACT_ABI.as_bytes()).unwrap();
+    static ref ERC20_PERMIT_CONTRACT: Contract = Contract::load(ERC20_PERMIT_ABI.as_bytes()).unwrap();
+}
+
+/// The payment record stored by the swap contract v2.
+pub(super) struct PaymentInfoV2 {
+    pub state: u8,
+    /// The number of the block the payment was sent in.
+    pub sent_block: u64,
+    /// The number of the block the payment was spent or refunded in.
+    pub finalized_block: u64,
+}
+
+/// The EIP-2612 permit allowing the swap contract to transfer the tokens.
+struct Erc20Permit {
+    deadline: U256,
+    v: u8,
+    r: Vec<u8>,
+    s: Vec<u8>,
+}
+
+/// Decodes the `erc20Payment` or `erc20PaymentWithPermit` call data.
+/// The permit arguments are truncated, so the tokens are the `erc20Payment` arguments in both cases.
+pub(super) fn decode_erc20_payment_input(data: &[u8]) -> Result<Vec<Token>, String> {
+    let permit_function = try_s!(SWAP_V2_CONTRACT.function("erc20PaymentWithPermit"));
+    if data.len() >= 4 && data[..4] == permit_function.short_signature() {
+        let mut decoded = try_s!(permit_function.decode_input(data));
+        decoded.truncate(6);
+        return Ok(decoded);
+    }
+    let function = try_s!(SWAP_CONTRACT.function("erc20Payment"));
+    function.decode_input(data).map_err(|e| ERRL!("{}", e))
+}
+
+/// Decodes the payment call data into the `senderRefundBatch` arguments of the payment:
+/// the id, amount, secret hash, token address and receiver.
+pub(super) fn refund_batch_args(coin_type: &EthCoinType, payment: &SignedEthTx) -> Result<[Token; 5], String> {
+    match coin_type {
+        EthCoinType::Eth => {
+            let payment_func = try_s!(SWAP_CONTRACT.function("ethPayment"));
+            let decoded = try_s!(payment_func.decode_input(&payment.data));
+            Ok([
+                decoded[0].clone(),
+                Token::Uint(payment.value),
+                decoded[2].clone(),
+                Token::Address(Address::default()),
+                decoded[1].clone(),
+            ])
+        },
+        EthCoinType::Erc20 { token_addr, .. } => {
+            let decoded = try_s!(decode_erc20_payment_input(&payment.data));
+            Ok([
+                decoded[0].clone(),
+                decoded[1].clone(),
+                decoded[4].clone(),
+                Token::Address(*token_addr),
+                decoded[3].clone(),
+            ])
+        },
+    }
+}
+
+fn uint_token_to_u64(token: &Token) -> Result<u64, String> {
+    match token {
+        Token::Uint(number) => Ok(number.low_u64()),
+        _ => ERR!("Expected uint, got {:?}", token),
+    }
+}
+
+impl EthCoin {
+    pub(super) fn is_swap_v2_contract(&self, swap_contract_address: Address) -> bool {
+        self.swap_v2_contract == Some(swap_contract_address)
+    }
+
+    /// Returns the payment record of the swap contract v2.
+    pub(super) async fn payment_info_v2(
+        &self,
+        swap_contract_address: Address,
+        id: Vec<u8>,
+    ) -> Result<PaymentInfoV2, String> {
+        let function = try_s!(SWAP_V2_CONTRACT.function("payments"));
+        let data = try_s!(function.encode_input(&[Token::FixedBytes(id)]));
+        let bytes = try_s!(
+            self.call_request(swap_contract_address, None, Some(data.into()))
+                .compat()
+                .await
+        );
+        let decoded = try_s!(function.decode_output(&bytes.0));
+        if decoded.len() < 5 {
+            return ERR!("Invalid 'payments' output {:?}", decoded);
+        }
+        Ok(PaymentInfoV2 {
+            state: try_s!(uint_token_to_u64(&decoded[2])) as u8,
+            sent_block: try_s!(uint_token_to_u64(&decoded[3])),
+            finalized_block: try_s!(uint_token_to_u64(&decoded[4])),
+        })
+    }
+
+    /// Sends the ERC20 payment with the permit if the token supports EIP-2612.
+    /// Otherwise, approves the tokens and sends the payment as it's done with the swap contract v1.
+    pub(super) fn send_erc20_payment_with_permit(
+        &self,
+        id: Vec<u8>,
+        value: U256,
+        time_lock: u32,
+        secret_hash: &[u8],
+        receiver_addr: Address,
+        swap_contract_address: Address,
+        token_addr: Address,
+    ) -> EthTxFut {
+        let coin = self.clone();
+        let secret_hash = secret_hash.to_vec();
+        let fut = async move {
+            let permit = match coin.sign_permit(token_addr, swap_contract_address, value).await {
+                Ok(permit) => permit,
+                Err(e) => {
+                    warn!(
+                        "{} doesn't support permit, approve the payment instead: {}",
+                        coin.ticker, e
+                    );
+                    return coin
+                        .send_erc20_payment_with_approve(
+                            id,
+                            value,
+                            time_lock,
+                            &secret_hash,
+                            receiver_addr,
+                            swap_contract_address,
+                            token_addr,
+                        )
+                        .compat()
+                        .await;
+                },
+            };
+
+            let function = try_tx_s!(SWAP_V2_CONTRACT.function("erc20PaymentWithPermit"));
+            let data = try_tx_s!(function.encode_input(&[
+                Token::FixedBytes(id),
+                Token::Uint(value),
+                Token::Address(token_addr),
+                Token::Address(receiver_addr),
+                Token::FixedBytes(secret_hash),
+                Token::Uint(U256::from(time_lock)),
+                Token::Uint(permit.deadline),
+                Token::Uint(U256::from(permit.v)),
+                Token::FixedBytes(permit.r),
+                Token::FixedBytes(permit.s),
+            ]));
+            coin.sign_and_send_transaction(0.into(), Action::Call(swap_contract_address), data, U256::from(200_000))
+                .compat()
+                .await
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    async fn permit_nonce(&self, token_addr: Address) -> Result<U256, String> {
+        let function = try_s!(ERC20_PERMIT_CONTRACT.function("nonces"));
+        let data = try_s!(function.encode_input(&[Token::Address(self.my_address)]));
+        let bytes = try_s!(self.call_request(token_addr, None, Some(data.into())).compat().await);
+        let decoded = try_s!(function.decode_output(&bytes.0));
+        match decoded.get(0) {
+            Some(Token::Uint(nonce)) => Ok(*nonce),
+            _ => ERR!("Expected uint as 'nonces' result, got {:?}", decoded),
+        }
+    }
+
+    async fn permit_domain_separator(&self, token_addr: Address) -> Result<Vec<u8>, String> {
+        let function = try_s!(ERC20_PERMIT_CONTRACT.function("DOMAIN_SEPARATOR"));
+        let data = try_s!(function.encode_input(&[]));
+        let bytes = try_s!(self.call_request(token_addr, None, Some(data.into())).compat().await);
+        let decoded = try_s!(function.decode_output(&bytes.0));
+        match decoded.get(0) {
+            Some(Token::FixedBytes(separator)) => Ok(separator.clone()),
+            _ => ERR!("Expected bytes32 as 'DOMAIN_SEPARATOR' result, got {:?}", decoded),
+        }
+    }
+
+    /// Signs the EIP-2612 permit allowing the `spender` to transfer `value` tokens.
+    async fn sign_permit(&self, token_addr: Address, spender: Address, value: U256) -> Result<Erc20Permit, String> {
+        let nonce = try_s!(self.permit_nonce(token_addr).await);
+        let domain_separator = try_s!(self.permit_domain_separator(token_addr).await);
+        let deadline = U256::from(now_ms() / 1000 + PERMIT_DEADLINE);
+
+        let struct_hash = keccak256(&ethabi::encode(&[
+            Token::FixedBytes(keccak256(PERMIT_TYPE.as_bytes()).take().to_vec()),
+            Token::Address(self.my_address),
+            Token::Address(spender),
+            Token::Uint(value),
+            Token::Uint(nonce),
+            Token::Uint(deadline),
+        ]));
+        let mut input = vec![0x19, 0x01];
+        input.extend_from_slice(&domain_separator);
+        input.extend_from_slice(&struct_hash.take());
+        let digest = keccak256(&input).take();
+
+        let signature = try_s!(sign(self.key_pair.secret(), &H256::from(digest)));
+        Ok(Erc20Permit {
+            deadline,
+            v: signature.v() + 27,
+            r: signature.r().to_vec(),
+            s: signature.s().to_vec(),
+        })
+    }
+
+    /// Finds the payment tx by the `PaymentSent` event emitted in the block stored in the payment record.
+    pub(super) async fn find_payment_tx_v2(
+        &self,
+        swap_contract_address: Address,
+        id: Vec<u8>,
+    ) -> Result<Option<SignedEthTx>, String> {
+        let info = try_s!(self.payment_info_v2(swap_contract_address, id.clone()).await);
+        if info.state == PAYMENT_STATE_UNINITIALIZED {
+            return Ok(None);
+        }
+        let events = try_s!(
+            self.payment_sent_events(swap_contract_address, info.sent_block, info.sent_block)
+                .compat()
+                .await
+        );
+        self.tx_by_swap_event(&events, &id, "PaymentSent").await
+    }
+
+    /// Finds the payment spend or refund tx by the event emitted in the block stored in the payment record.
+    pub(super) async fn search_for_swap_tx_spend_v2(
+        &self,
+        swap_contract_address: Address,
+        id: Vec<u8>,
+    ) -> Result<Option<FoundSwapTxSpend>, String> {
+        let info = try_s!(self.payment_info_v2(swap_contract_address, id.clone()).await);
+        match info.state {
+            PAYMENT_STATE_SPENT => {
+                let events = try_s!(
+                    self.spend_events(swap_contract_address, info.finalized_block, info.finalized_block)
+                        .compat()
+                        .await
+                );
+                let tx = try_s!(self.tx_by_swap_event(&events, &id, "ReceiverSpent").await);
+                Ok(tx.map(|tx| FoundSwapTxSpend::Spent(tx.into())))
+            },
+            PAYMENT_STATE_REFUNDED => {
+                let events = try_s!(
+                    self.refund_events(swap_contract_address, info.finalized_block, info.finalized_block)
+                        .compat()
+                        .await
+                );
+                let tx = try_s!(self.tx_by_swap_event(&events, &id, "SenderRefunded").await);
+                Ok(tx.map(|tx| FoundSwapTxSpend::Refunded(tx.into())))
+            },
+            _ => Ok(None),
+        }
+    }
+
+    async fn tx_by_swap_event(
+        &self,
+        events: &[Log],
+        id: &[u8],
+        event_name: &str,
+    ) -> Result<Option<SignedEthTx>, String> {
+        let event = match events.iter().find(|event| &event.data.0[..32] == id) {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+        let tx_hash = match event.transaction_hash {
+            Some(tx_hash) => tx_hash,
+            None => return ERR!("Found {} event, but it doesn't have tx_hash", event_name),
+        };
+        match try_s!(self.web3.eth().transaction(TransactionId::Hash(tx_hash)).compat().await) {
+            Some(tx) => Ok(Some(try_s!(signed_tx_from_web3_tx(tx)))),
+            None => ERR!("Found {} event, but transaction {:02x} is missing", event_name, tx_hash),
+        }
+    }
+
+    /// Refunds the given payments sent to the swap contract v2 within one tx.
+    /// Every payment must be in the `PAYMENT_STATE_SENT` state and its locktime must be expired.
+    pub fn refund_hash_time_locked_payments_batch(
+        &self,
+        swap_contract_address: Address,
+        payments: Vec<SignedEthTx>,
+    ) -> EthTxFut {
+        if !self.is_swap_v2_contract(swap_contract_address) {
+            return Box::new(futures01::future::err(TransactionErr::Plain(ERRL!(
+                "Batched refunds are supported by the swap contract v2 only, got {:?}",
+                swap_contract_address
+            ))));
+        }
+        if payments.is_empty() {
+            return Box::new(futures01::future::err(TransactionErr::Plain(ERRL!(
+                "No payments to refund"
+            ))));
+        }
+
+        let coin = self.clone();
+        let fut = async move {
+            let mut ids = Vec::with_capacity(payments.len());
+            let mut amounts = Vec::with_capacity(payments.len());
+            let mut payment_hashes = Vec::with_capacity(payments.len());
+            let mut token_addresses = Vec::with_capacity(payments.len());
+            let mut receivers = Vec::with_capacity(payments.len());
+
+            for payment in payments.iter() {
+                let [id, amount, payment_hash, token_addr, receiver] =
+                    try_tx_s!(refund_batch_args(&coin.coin_type, payment));
+
+                let state = try_tx_s!(coin.payment_status(swap_contract_address, id.clone()).compat().await);
+                if state != PAYMENT_STATE_SENT.into() {
+                    return Err(TransactionErr::Plain(ERRL!(
+                        "Payment {:?} state is not PAYMENT_STATE_SENT, got {}",
+                        payment,
+                        state
+                    )));
+                }
+
+                ids.push(id);
+                amounts.push(amount);
+                payment_hashes.push(payment_hash);
+                token_addresses.push(token_addr);
+                receivers.push(receiver);
+            }
+
+            let gas = U256::from(100_000) + U256::from(50_000) * U256::from(ids.len());
+            let function = try_tx_s!(SWAP_V2_CONTRACT.function("senderRefundBatch"));
+            let data = try_tx_s!(function.encode_input(&[
+                Token::Array(ids),
+                Token::Array(amounts),
+                Token::Array(payment_hashes),
+                Token::Array(token_addresses),
+                Token::Array(receivers),
+            ]));
+            coin.sign_and_send_transaction(0.into(), Action::Call(swap_contract_address), data, gas)
+                .compat()
+                .await
+        };
+        Box::new(fut.boxed().compat())
+    }
+}
+
+#[cfg(test)]
+mod swap_v2_tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_erc20_payment_input() {
+        let id = vec![1; 32];
+        let token_addr = Address::from("0x2b294F029Fde858b2c62184e8390591755521d8E");
+        let receiver = Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94");
+        let payment_args = vec![
+            Token::FixedBytes(id),
+            Token::Uint(1000.into()),
+            Token::Address(token_addr),
+            Token::Address(receiver),
+            Token::FixedBytes(vec![2; 20]),
+            Token::Uint(1600000000.into()),
+        ];
+
+        let erc20_payment = SWAP_CONTRACT.function("erc20Payment").unwrap();
+        let data = erc20_payment.encode_input(&payment_args).unwrap();
+        assert_eq!(decode_erc20_payment_input(&data).unwrap(), payment_args);
+
+        let mut permit_args = payment_args.clone();
+        permit_args.extend_from_slice(&[
+            Token::Uint(1600003600.into()),
+            Token::Uint(27.into()),
+            Token::FixedBytes(vec![3; 32]),
+            Token::FixedBytes(vec![4; 32]),
+        ]);
+        let erc20_payment_with_permit = SWAP_V2_CONTRACT.function("erc20PaymentWithPermit").unwrap();
+        let data = erc20_payment_with_permit.encode_input(&permit_args).unwrap();
+        assert_eq!(decode_erc20_payment_input(&data).unwrap(), payment_args);
+    }
+
+    fn signed_payment_for_test(value: U256, data: Vec<u8>) -> SignedEthTx {
+        let key_pair = KeyPair::from_secret_slice(
+            &hex::decode("809465b17d0a4ddb3e4c69e8f23c2cabad868f51f8bed5c765ad1d6516c3306f").unwrap(),
+        )
+        .unwrap();
+        let tx = UnSignedEthTx {
+            nonce: 0.into(),
+            gas_price: 0.into(),
+            gas: 0.into(),
+            action: Action::Call(Address::from("0x9130b257D37A52E52F21054c4DA3450c72f595CE")),
+            value,
+            data,
+        };
+        tx.sign(key_pair.secret(), None)
+    }
+
+    #[test]
+    fn test_refund_batch_args() {
+        let id = Token::FixedBytes(vec![1; 32]);
+        let receiver = Token::Address(Address::from("0x7Bc1bBDD6A0a722fC9bffC49c921B685ECB84b94"));
+        let secret_hash = Token::FixedBytes(vec![2; 20]);
+        let lock_time = Token::Uint(1600000000.into());
+
+        let eth_payment = SWAP_CONTRACT.function("ethPayment").unwrap();
+        let data = eth_payment
+            .encode_input(&[id.clone(), receiver.clone(), secret_hash.clone(), lock_time.clone()])
+            .unwrap();
+        let payment = signed_payment_for_test(1000.into(), data);
+        assert_eq!(refund_batch_args(&EthCoinType::Eth, &payment).unwrap(), [
+            id.clone(),
+            Token::Uint(1000.into()),
+            secret_hash.clone(),
+            Token::Address(Address::default()),
+            receiver.clone(),
+        ]);
+
+        let token_addr = Address::from("0x2b294F029Fde858b2c62184e8390591755521d8E");
+        let erc20_payment = SWAP_CONTRACT.function("erc20Payment").unwrap();
+        let data = erc20_payment
+            .encode_input(&[
+                id.clone(),
+                Token::Uint(2000.into()),
+                Token::Address(token_addr),
+                receiver.clone(),
+                secret_hash.clone(),
+                lock_time,
+            ])
+            .unwrap();
+        let payment = signed_payment_for_test(0.into(), data);
+        let coin_type = EthCoinType::Erc20 {
+            platform: "ETH".to_owned(),
+            token_addr,
+        };
+        assert_eq!(refund_batch_args(&coin_type, &payment).unwrap(), [
+            id,
+            Token::Uint(2000.into()),
+            secret_hash,
+            Token::Address(token_addr),
+            receiver,
+        ]);
+    }
+}
@@ -1,12 +1,16 @@
 use super::{RpcTransportEventHandler, RpcTransportEventHandlerShared};
+use common::now_ms;
 #[cfg(not(target_arch = "wasm32"))] use futures::FutureExt;
 use futures::TryFutureExt;
 use futures01::{Future, Poll};
 use jsonrpc_core::{Call, Response};
+#[cfg(not(target_arch = "wasm32"))]
+use mm2_net::socks5::Socks5Proxy;
 use serde_json::Value as Json;
 #[cfg(not(target_arch = "wasm32"))] use std::ops::Deref;
 use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
+use std::time::Duration;
 use web3::api::Namespace;
 use web3::error::{Error, ErrorKind};
 use web3::helpers::{self, build_request, to_result_from_output, to_string, CallFuture};
@@ -72,6 +76,9 @@ pub struct Web3Transport {
     id: Arc<AtomicUsize>,
     uris: Vec<http::Uri>,
     event_handlers: Vec<RpcTransportEventHandlerShared>,
+    /// The SOCKS5 proxy the requests are sent through instead of the global one.
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<Socks5Proxy>,
 }
 
 impl Web3Transport {
@@ -85,6 +92,8 @@ impl Web3Transport {
             id: Arc::new(AtomicUsize::new(0)),
             uris,
             event_handlers: Default::default(),
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
         })
     }
 
@@ -100,8 +109,16 @@ impl Web3Transport {
             id: Arc::new(AtomicUsize::new(0)),
             uris,
             event_handlers,
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
         })
     }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn with_proxy(mut self, proxy: Option<Socks5Proxy>) -> Self {
+        self.proxy = proxy;
+        self
+    }
 }
 
 struct SendFuture<T>(T);
@@ -130,9 +147,14 @@ impl Transport for Web3Transport {
     #[cfg(not(target_arch = "wasm32"))]
     fn send(&self, _id: RequestId, request: Call) -> Self::Out {
         Box::new(
-            send_request(request, self.uris.clone(), self.event_handlers.clone())
-                .boxed()
-                .compat(),
+            send_request(
+                request,
+                self.uris.clone(),
+                self.event_handlers.clone(),
+                self.proxy.clone(),
+            )
+            .boxed()
+            .compat(),
         )
     }
 
@@ -148,13 +170,14 @@ async fn send_request(
     request: Call,
     uris: Vec<http::Uri>,
     event_handlers: Vec<RpcTransportEventHandlerShared>,
+    proxy: Option<Socks5Proxy>,
 ) -> Result<Json, Error> {
     use common::executor::Timer;
     use common::log::warn;
     use futures::future::{select, Either};
     use gstuff::binprint;
     use http::header::HeaderValue;
-    use mm2_net::transport::slurp_req;
+    use mm2_net::transport::{slurp_req, slurp_req_via_proxy};
 
     const REQUEST_TIMEOUT_S: f64 = 60.;
 
@@ -169,11 +192,18 @@ async fn send_request(
         req.headers_mut()
             .insert(http::header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
         let timeout = Timer::sleep(REQUEST_TIMEOUT_S);
-        let req = Box::pin(slurp_req(req));
+        let req = match proxy {
+            Some(ref proxy) => slurp_req_via_proxy(req, Some(proxy)).boxed(),
+            None => slurp_req(req).boxed(),
+        };
+        let start = now_ms();
         let rc = select(req, timeout).await;
+        let latency = Duration::from_millis(now_ms().saturating_sub(start));
+        let server = uri.to_string();
         let res = match rc {
             Either::Left((r, _t)) => r,
             Either::Right((_t, _r)) => {
+                event_handlers.on_request_completed(&server, latency, false);
                 let error = ERRL!("Error requesting '{}': {}s timeout expired", uri, REQUEST_TIMEOUT_S);
                 warn!("{}", error);
                 errors.push(error);
@@ -184,12 +214,14 @@ async fn send_request(
         let (status, _headers, body) = match res {
             Ok(r) => r,
             Err(err) => {
+                event_handlers.on_request_completed(&server, latency, false);
                 errors.push(err.to_string());
                 continue;
             },
         };
 
         event_handlers.on_incoming_response(&body);
+        event_handlers.on_request_completed(&server, latency, status.is_success());
 
         if !status.is_success() {
             errors.push(ERRL!(
@@ -201,7 +233,7 @@ async fn send_request(
             continue;
         }
 
-        return single_response(body, &uri.to_string());
+        return single_response(body, &server);
     }
     Err(request_failed_error(&request, &errors))
 }
@@ -248,13 +280,18 @@ async fn send_request_once(
     // account for outgoing traffic
     event_handlers.on_outgoing_request(request_payload.as_bytes());
 
-    let result = FetchRequest::post(&uri.to_string())
+    let server = uri.to_string();
+    let start = now_ms();
+    let result = FetchRequest::post(&server)
         .cors()
         .body_utf8(request_payload)
         .header("Accept", "application/json")
         .header("Content-Type", "application/json")
         .request_str()
         .await;
+    let latency = Duration::from_millis(now_ms().saturating_sub(start));
+    let success = matches!(result, Ok((ref status_code, _)) if status_code.is_success());
+    event_handlers.on_request_completed(&server, latency, success);
     let (status_code, response_str) = try_or!(result, Transport);
     if !status_code.is_success() {
         return Err(Error::from(ErrorKind::Transport(ERRL!(
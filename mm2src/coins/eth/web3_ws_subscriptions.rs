@@ -0,0 +1,120 @@
+//! The `eth_subscribe` subscriptions to the new heads and the swap contract logs over WebSocket.
+//! The swaps wait for these notifications instead of polling the nodes on a fixed interval.
+
+use common::executor::{spawn, Timer};
+use common::log::{debug, info, warn};
+use futures::channel::oneshot;
+use futures::future::{select, Either};
+use futures::StreamExt;
+use mm2_net::native_ws::ws_connect;
+use mm2_net::socks5::Socks5Proxy;
+use parking_lot::Mutex as PaMutex;
+use serde_json::{self as json, Value as Json};
+use std::sync::{Arc, Weak};
+use web3::types::Address;
+
+const RECONNECT_INTERVAL_S: f64 = 10.;
+const NEW_HEADS_SUBSCRIPTION_ID: u64 = 1;
+const LOGS_SUBSCRIPTION_ID: u64 = 2;
+
+/// Wakes up the waiters on every new head or swap contract log.
+/// The subscriptions are kept alive as long as this struct isn't dropped.
+#[derive(Debug, Default)]
+pub struct EthWsSubscriptions {
+    waiters: PaMutex<Vec<oneshot::Sender<()>>>,
+}
+
+impl EthWsSubscriptions {
+    /// Spawns the loop subscribing to the new heads and the `contracts` logs through one of the `urls`.
+    /// The next url is used if the connection is lost.
+    pub fn spawn(
+        ticker: String,
+        urls: Vec<String>,
+        contracts: Vec<Address>,
+        proxy: Option<Socks5Proxy>,
+    ) -> Arc<EthWsSubscriptions> {
+        let subscriptions = Arc::new(EthWsSubscriptions::default());
+        spawn(subscriptions_loop(
+            Arc::downgrade(&subscriptions),
+            ticker,
+            urls,
+            contracts,
+            proxy,
+        ));
+        subscriptions
+    }
+
+    /// Waits for a new head or a swap contract log, but not longer than `timeout_s` seconds
+    /// in case the notifications are lost.
+    pub async fn wait_for_event(&self, timeout_s: f64) {
+        let (tx, rx) = oneshot::channel();
+        self.waiters.lock().push(tx);
+        if let Either::Right(_) = select(rx, Box::pin(Timer::sleep(timeout_s))).await {
+            debug!("No new heads or swap contract logs in {} seconds", timeout_s);
+        }
+    }
+
+    fn notify(&self) {
+        for waiter in self.waiters.lock().drain(..) {
+            waiter.send(()).ok();
+        }
+    }
+}
+
+async fn subscriptions_loop(
+    weak: Weak<EthWsSubscriptions>,
+    ticker: String,
+    urls: Vec<String>,
+    contracts: Vec<Address>,
+    proxy: Option<Socks5Proxy>,
+) {
+    for url in urls.iter().cycle() {
+        if weak.strong_count() == 0 {
+            break;
+        }
+        match subscribe(&weak, url, &contracts, proxy.as_ref()).await {
+            Ok(()) => warn!("{} WebSocket connection to {} is closed", ticker, url),
+            Err(e) => warn!("{} WebSocket subscriptions to {} failed: {}", ticker, url, e),
+        }
+        Timer::sleep(RECONNECT_INTERVAL_S).await;
+    }
+    info!("{} WebSocket subscriptions stopped", ticker);
+}
+
+/// Subscribes to the notifications and forwards them to the waiters until the connection is closed.
+async fn subscribe(
+    weak: &Weak<EthWsSubscriptions>,
+    url: &str,
+    contracts: &[Address],
+    proxy: Option<&Socks5Proxy>,
+) -> Result<(), String> {
+    let (sender, mut receiver) = try_s!(ws_connect(url, proxy).await);
+    let new_heads = json!({
+        "jsonrpc": "2.0",
+        "id": NEW_HEADS_SUBSCRIPTION_ID,
+        "method": "eth_subscribe",
+        "params": ["newHeads"],
+    });
+    let logs = json!({
+        "jsonrpc": "2.0",
+        "id": LOGS_SUBSCRIPTION_ID,
+        "method": "eth_subscribe",
+        "params": ["logs", { "address": contracts }],
+    });
+    try_s!(sender.send(new_heads.to_string()));
+    try_s!(sender.send(logs.to_string()));
+
+    while let Some(message) = receiver.next().await {
+        let subscriptions = match weak.upgrade() {
+            Some(subscriptions) => subscriptions,
+            None => return Ok(()),
+        };
+        let message: Json = try_s!(json::from_str(&message));
+        if message["method"] == "eth_subscription" {
+            subscriptions.notify();
+        } else if !message["error"].is_null() {
+            return ERR!("eth_subscribe error: {}", message["error"]);
+        }
+    }
+    Ok(())
+}
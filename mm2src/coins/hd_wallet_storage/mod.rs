@@ -255,6 +255,8 @@ impl HDWalletCoinStorage {
         HDWalletId::new(self.coin.clone(), &self.mm2_rmd160, &self.hd_wallet_rmd160)
     }
 
+    pub fn hd_wallet_rmd160(&self) -> H160 { self.hd_wallet_rmd160 }
+
     pub async fn load_all_accounts(&self) -> HDWalletStorageResult<Vec<HDAccountStorageItem>> {
         let wallet_id = self.wallet_id();
         self.inner.load_accounts(wallet_id).await
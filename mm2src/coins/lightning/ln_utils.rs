@@ -145,7 +145,7 @@ pub async fn init_channel_manager(
 
     let rpc_client = match &platform.coin.as_ref().rpc_client {
         UtxoRpcClientEnum::Electrum(c) => c.clone(),
-        UtxoRpcClientEnum::Native(_) => {
+        _ => {
             return MmError::err(EnableLightningError::UnsupportedMode(
                 "Lightning network".into(),
                 "electrum".into(),
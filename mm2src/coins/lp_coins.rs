@@ -34,6 +34,7 @@
 
 use async_trait::async_trait;
 use base58::FromBase58Error;
+use common::executor::Timer;
 use common::mm_metrics::MetricsWeak;
 use common::{calc_total_pages, now_ms, ten, HttpStatusCode};
 use crypto::{Bip32Error, CryptoCtx, DerivationPath};
@@ -44,9 +45,11 @@ use futures::{FutureExt, TryFutureExt};
 use futures01::Future;
 use http::{Response, StatusCode};
 use keys::{AddressFormat as UtxoAddressFormat, KeyPair, NetworkPrefix as CashAddrPrefix};
-use mm2_core::mm_ctx::{from_ctx, MmArc};
+use mm2_core::mm_ctx::{from_ctx, MmArc, MmWeak};
 use mm2_err_handle::prelude::*;
-use mm2_number::bigdecimal::{BigDecimal, ParseBigDecimalError, Zero};
+#[cfg(not(target_arch = "wasm32"))]
+use mm2_net::socks5::Socks5Proxy;
+use mm2_number::bigdecimal::{BigDecimal, ParseBigDecimalError, ToPrimitive, Zero};
 use mm2_number::MmNumber;
 use rpc::v1::types::{Bytes as BytesJson, H256 as H256Json};
 use serde::{Deserialize, Deserializer, Serialize};
@@ -75,6 +78,7 @@ cfg_native! {
 cfg_wasm32! {
     use mm2_db::indexed_db::{ConstructibleDb, DbLocked, SharedDb};
     use hd_wallet_storage::HDWalletDb;
+    use utxo::utxo_indexedb_block_header_storage::BlockHeadersDb;
     use tx_history_storage::wasm::{clear_tx_history, load_tx_history, save_tx_history, TxHistoryDb};
 
     pub type TxHistoryDbLocked<'a> = DbLocked<'a, TxHistoryDb>;
@@ -237,6 +241,10 @@ pub mod solana;
 pub use solana::spl::SplToken;
 #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
 pub use solana::{solana_coin_from_conf_and_params, SolanaActivationParams, SolanaCoin, SolanaFeeDetails};
+#[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
+use solana::{SolanaDelegationRequest, SolanaStakingInfosDetails};
+
+pub mod tendermint;
 
 pub mod utxo;
 #[cfg(not(target_arch = "wasm32"))] pub mod z_coin;
@@ -249,9 +257,12 @@ use qtum::{Qrc20AddressError, ScriptHashTypeNotSupported};
 use rpc_command::init_create_account::{CreateAccountTaskManager, CreateAccountTaskManagerShared};
 use rpc_command::init_scan_for_new_addresses::{ScanAddressesTaskManager, ScanAddressesTaskManagerShared};
 use rpc_command::init_withdraw::{WithdrawTaskManager, WithdrawTaskManagerShared};
+use rpc_task::RpcTaskInfo;
+use tendermint::{CosmosTransaction, TendermintCoin, TendermintFeeDetails, TendermintProtocolInfo, TendermintToken,
+                 TendermintTokenProtocolInfo};
 use utxo::bch::{bch_coin_from_conf_and_params, BchActivationRequest, BchCoin};
 use utxo::qtum::{self, qtum_coin_with_priv_key, QtumCoin};
-use utxo::qtum::{QtumDelegationOps, QtumDelegationRequest, QtumStakingInfosDetails};
+use utxo::qtum::{QtumDelegationRequest, QtumStakingInfosDetails};
 use utxo::rpc_clients::UtxoRpcError;
 use utxo::slp::SlpToken;
 use utxo::slp::{slp_addr_from_pubkey_str, SlpFeeDetails};
@@ -269,6 +280,8 @@ pub type StakingInfosResult = Result<StakingInfos, MmError<StakingInfosError>>;
 pub type StakingInfosFut = Box<dyn Future<Item = StakingInfos, Error = MmError<StakingInfosError>> + Send>;
 pub type DelegationResult = Result<TransactionDetails, MmError<DelegationError>>;
 pub type DelegationFut = Box<dyn Future<Item = TransactionDetails, Error = MmError<DelegationError>> + Send>;
+pub type StakingValidatorsFut = Box<dyn Future<Item = StakingValidators, Error = MmError<StakingInfosError>> + Send>;
+pub type StakingRewardsFut = Box<dyn Future<Item = StakingRewards, Error = MmError<StakingInfosError>> + Send>;
 pub type WithdrawResult = Result<TransactionDetails, MmError<WithdrawError>>;
 pub type WithdrawFut = Box<dyn Future<Item = TransactionDetails, Error = MmError<WithdrawError>> + Send>;
 pub type TradePreimageResult<T> = Result<T, MmError<TradePreimageError>>;
@@ -373,11 +386,13 @@ pub enum TransactionEnum {
     SignedEthTx(SignedEthTx),
     #[cfg(not(target_arch = "wasm32"))]
     ZTransaction(ZTransaction),
+    CosmosTransaction(CosmosTransaction),
 }
 ifrom!(TransactionEnum, UtxoTx);
 ifrom!(TransactionEnum, SignedEthTx);
 #[cfg(not(target_arch = "wasm32"))]
 ifrom!(TransactionEnum, ZTransaction);
+ifrom!(TransactionEnum, CosmosTransaction);
 
 // NB: When stable and groked by IDEs, `enum_dispatch` can be used instead of `Deref` to speed things up.
 impl Deref for TransactionEnum {
@@ -388,6 +403,7 @@ impl Deref for TransactionEnum {
             TransactionEnum::SignedEthTx(ref t) => t,
             #[cfg(not(target_arch = "wasm32"))]
             TransactionEnum::ZTransaction(ref t) => t,
+            TransactionEnum::CosmosTransaction(ref t) => t,
         }
     }
 }
@@ -457,6 +473,17 @@ pub struct ValidatePaymentInput {
     pub unique_swap_data: Vec<u8>,
 }
 
+/// The input of [`SwapOps::gen_unbroadcast_swap_payment`].
+#[derive(Clone, Debug)]
+pub struct UnbroadcastSwapPaymentInput {
+    pub time_lock: u32,
+    pub other_pub: Vec<u8>,
+    pub secret_hash: Vec<u8>,
+    pub amount: BigDecimal,
+    pub swap_contract_address: Option<BytesJson>,
+    pub swap_unique_data: Vec<u8>,
+}
+
 pub struct SearchForSwapTxSpendInput<'a> {
     pub time_lock: u32,
     pub other_pub: &'a [u8],
@@ -586,7 +613,29 @@ pub trait SwapOps {
         other_side_address: Option<&[u8]>,
     ) -> Result<Option<BytesJson>, MmError<NegotiateSwapContractAddrErr>>;
 
+    /// Returns the address of the second version of the swap contract if the coin supports it.
+    /// Unlike [`MmCoin::swap_contract_address`], it's used only if both sides of the swap advertise the same address.
+    fn swap_v2_contract_address(&self) -> Option<BytesJson> { None }
+
     fn derive_htlc_key_pair(&self, swap_unique_data: &[u8]) -> KeyPair;
+
+    /// Generates and signs the swap payment transaction without broadcasting it.
+    /// Used by the swap simulation to check that the coin is able to build the swap payment.
+    fn gen_unbroadcast_swap_payment(&self, _input: UnbroadcastSwapPaymentInput) -> TransactionFut {
+        Box::new(futures01::future::err(TransactionErr::Plain(ERRL!(
+            "Swap simulation is not supported"
+        ))))
+    }
+
+    /// Validates the payment generated by [`SwapOps::gen_unbroadcast_swap_payment`] as the counterparty would.
+    /// Unlike `validate_maker_payment` and `validate_taker_payment`, the transaction is not requested from the RPC
+    /// since it's not broadcasted. Used only by the swap simulation.
+    fn validate_unbroadcast_swap_payment(
+        &self,
+        _input: ValidatePaymentInput,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        Box::new(futures01::future::err(ERRL!("Swap simulation is not supported")))
+    }
 }
 
 /// Operations that coins have independently from the MarketMaker.
@@ -681,6 +730,11 @@ pub enum WithdrawFee {
         gas_limit: u64,
         gas_price: u64,
     },
+    CosmosGas {
+        gas_limit: u64,
+        /// in the platform coin denom units
+        gas_price: f64,
+    },
 }
 
 pub struct WithdrawSenderAddress<Address, Pubkey> {
@@ -741,6 +795,8 @@ pub struct WithdrawRequest {
 #[serde(tag = "type")]
 pub enum StakingDetails {
     Qtum(QtumDelegationRequest),
+    #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
+    Solana(SolanaDelegationRequest),
 }
 
 #[allow(dead_code)]
@@ -754,6 +810,10 @@ pub struct AddDelegateRequest {
 #[derive(Deserialize)]
 pub struct RemoveDelegateRequest {
     pub coin: String,
+    /// Undelegates the stake of this validator only if set.
+    /// Ignored by the coins that can delegate to a single staker at once.
+    #[serde(default)]
+    pub validator: Option<String>,
 }
 
 #[derive(Deserialize)]
@@ -761,6 +821,14 @@ pub struct GetStakingInfosRequest {
     pub coin: String,
 }
 
+#[derive(Deserialize)]
+pub struct ClaimStakingRewardsRequest {
+    pub coin: String,
+    /// Claims the rewards of this validator only if set.
+    #[serde(default)]
+    pub validator: Option<String>,
+}
+
 #[derive(Serialize, Deserialize)]
 pub struct SignatureRequest {
     coin: String,
@@ -813,17 +881,86 @@ impl WithdrawRequest {
 #[serde(tag = "type")]
 pub enum StakingInfosDetails {
     Qtum(QtumStakingInfosDetails),
+    #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
+    Solana(SolanaStakingInfosDetails),
 }
 
 impl From<QtumStakingInfosDetails> for StakingInfosDetails {
     fn from(qtum_staking_infos: QtumStakingInfosDetails) -> Self { StakingInfosDetails::Qtum(qtum_staking_infos) }
 }
 
+#[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
+impl From<SolanaStakingInfosDetails> for StakingInfosDetails {
+    fn from(solana_staking_infos: SolanaStakingInfosDetails) -> Self {
+        StakingInfosDetails::Solana(solana_staking_infos)
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct StakingInfos {
     pub staking_infos_details: StakingInfosDetails,
 }
 
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StakingValidator {
+    pub address: String,
+    /// The commission taken by the validator from the rewards, in percents.
+    pub commission: BigDecimal,
+    /// The total amount delegated to the validator.
+    pub total_stake: BigDecimal,
+    /// Whether the validator participates in the consensus at the moment.
+    pub is_active: bool,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StakingValidators {
+    pub validators: Vec<StakingValidator>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StakingReward {
+    pub validator: String,
+    pub amount: BigDecimal,
+    /// The epoch the reward was credited at, if the rewards are credited per epoch.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub epoch: Option<u64>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct StakingRewards {
+    pub rewards: Vec<StakingReward>,
+    pub total: BigDecimal,
+}
+
+impl StakingRewards {
+    pub fn new(rewards: Vec<StakingReward>) -> StakingRewards {
+        let total = rewards
+            .iter()
+            .fold(BigDecimal::from(0), |total, reward| total + &reward.amount);
+        StakingRewards { rewards, total }
+    }
+}
+
+/// The operations of the coins that can delegate their balance to the validators (stakers).
+/// The transactions are returned signed but not broadcasted, like the `withdraw` ones.
+pub trait StakingOps {
+    /// The validators the balance can be delegated to.
+    fn validators(&self) -> StakingValidatorsFut;
+
+    fn delegate(&self, details: StakingDetails) -> DelegationFut;
+
+    fn undelegate(&self, validator: Option<String>) -> DelegationFut;
+
+    /// Moves the rewards that can be claimed to the spendable balance.
+    /// The coins compounding the rewards into the stake, like Solana, withdraw the deactivated stake with the rewards.
+    fn claim_rewards(&self, validator: Option<String>) -> DelegationFut;
+
+    /// The rewards that aren't spendable yet.
+    fn pending_rewards(&self) -> StakingRewardsFut;
+
+    fn staking_infos(&self) -> StakingInfosFut;
+}
+
 #[derive(Serialize)]
 pub struct SignatureResponse {
     signature: String,
@@ -845,6 +982,7 @@ pub enum TxFeeDetails {
     Slp(SlpFeeDetails),
     #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
     Solana(SolanaFeeDetails),
+    Tendermint(TendermintFeeDetails),
 }
 
 /// Deserialize the TxFeeDetails as an untagged enum.
@@ -856,6 +994,8 @@ impl<'de> Deserialize<'de> for TxFeeDetails {
         #[derive(Deserialize)]
         #[serde(untagged)]
         enum TxFeeDetailsUnTagged {
+            // Goes first since the Utxo fee details would match the Tendermint ones otherwise.
+            Tendermint(TendermintFeeDetails),
             Utxo(UtxoFeeDetails),
             Eth(EthTxFeeDetails),
             Qrc20(Qrc20FeeDetails),
@@ -864,6 +1004,7 @@ impl<'de> Deserialize<'de> for TxFeeDetails {
         }
 
         match Deserialize::deserialize(deserializer)? {
+            TxFeeDetailsUnTagged::Tendermint(f) => Ok(TxFeeDetails::Tendermint(f)),
             TxFeeDetailsUnTagged::Utxo(f) => Ok(TxFeeDetails::Utxo(f)),
             TxFeeDetailsUnTagged::Eth(f) => Ok(TxFeeDetails::Eth(f)),
             TxFeeDetailsUnTagged::Qrc20(f) => Ok(TxFeeDetails::Qrc20(f)),
@@ -890,6 +1031,10 @@ impl From<SolanaFeeDetails> for TxFeeDetails {
     fn from(solana_details: SolanaFeeDetails) -> Self { TxFeeDetails::Solana(solana_details) }
 }
 
+impl From<TendermintFeeDetails> for TxFeeDetails {
+    fn from(tendermint_details: TendermintFeeDetails) -> Self { TxFeeDetails::Tendermint(tendermint_details) }
+}
+
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct KmdRewardsDetails {
     amount: BigDecimal,
@@ -909,6 +1054,10 @@ impl KmdRewardsDetails {
 pub enum TransactionType {
     StakingDelegation,
     RemoveDelegation,
+    ClaimDelegationRewards,
+    /// The withdrawal of the deactivated stake, the principal and the compounded rewards together.
+    WithdrawStake,
+    ClaimKmdRewards,
     StandardTransfer,
     TokenTransfer(BytesJson),
 }
@@ -954,6 +1103,10 @@ pub struct TransactionDetails {
     /// Type of transactions, default is StandardTransfer
     #[serde(default)]
     transaction_type: TransactionType,
+    /// Whether the transaction is included into a block validated by the SPV mode.
+    /// It's `None` if the SPV mode is disabled or the transaction is not confirmed yet.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    spv_verified: Option<bool>,
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -1206,6 +1359,8 @@ pub enum StakingInfosError {
     NoSuchCoin { coin: String },
     #[display(fmt = "Derivation method is not supported: {}", _0)]
     UnexpectedDerivationMethod(String),
+    #[display(fmt = "Staking operation is not supported, reason: {}", reason)]
+    StakingOpsNotSupported { reason: String },
     #[display(fmt = "Transport error: {}", _0)]
     Transport(String),
     #[display(fmt = "Internal error: {}", _0)]
@@ -1244,7 +1399,8 @@ impl HttpStatusCode for StakingInfosError {
         match self {
             StakingInfosError::NoSuchCoin { .. }
             | StakingInfosError::CoinDoesntSupportStakingInfos { .. }
-            | StakingInfosError::UnexpectedDerivationMethod(_) => StatusCode::BAD_REQUEST,
+            | StakingInfosError::UnexpectedDerivationMethod(_)
+            | StakingInfosError::StakingOpsNotSupported { .. } => StatusCode::BAD_REQUEST,
             StakingInfosError::Transport(_) | StakingInfosError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
@@ -1286,6 +1442,14 @@ pub enum DelegationError {
     AlreadyDelegating(String),
     #[display(fmt = "Delegation is not supported, reason: {}", reason)]
     DelegationOpsNotSupported { reason: String },
+    #[display(fmt = "Invalid staking details: {}", _0)]
+    InvalidStakingDetails(String),
+    #[display(fmt = "There is no delegation to undelegate")]
+    NothingToUndelegate,
+    #[display(fmt = "There are no rewards to claim")]
+    NoRewardsToClaim,
+    #[display(fmt = "There is no deactivated stake to withdraw")]
+    NothingToWithdraw,
     #[display(fmt = "Transport error: {}", _0)]
     Transport(String),
     #[display(fmt = "Internal error: {}", _0)]
@@ -1312,7 +1476,8 @@ impl From<StakingInfosError> for DelegationError {
             },
             StakingInfosError::NoSuchCoin { coin } => DelegationError::NoSuchCoin { coin },
             StakingInfosError::Transport(e) => DelegationError::Transport(e),
-            StakingInfosError::UnexpectedDerivationMethod(reason) => {
+            StakingInfosError::UnexpectedDerivationMethod(reason)
+            | StakingInfosError::StakingOpsNotSupported { reason } => {
                 DelegationError::DelegationOpsNotSupported { reason }
             },
             StakingInfosError::Internal(e) => DelegationError::InternalError(e),
@@ -1730,8 +1895,10 @@ pub trait MmCoin: SwapOps + MarketCoinOps + fmt::Debug + Send + Sync + 'static {
     }
 
     /// Loads existing tx history from file, returns empty vector if file is not found
-    /// Cleans the existing file if deserialization fails
-    fn load_history_from_file(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+    /// Cleans the existing file if deserialization fails.
+    /// The coins that keep the history within [`TxHistoryStorage`](my_tx_history_v2::TxHistoryStorage)
+    /// load it from the storage instead.
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
         load_history_from_file_impl(self, ctx)
     }
 
@@ -1739,6 +1906,11 @@ pub trait MmCoin: SwapOps + MarketCoinOps + fmt::Debug + Send + Sync + 'static {
         save_history_to_file_impl(self, ctx, history)
     }
 
+    /// Removes the tx history file left from the time the coin didn't keep the history
+    /// within [`TxHistoryStorage`](my_tx_history_v2::TxHistoryStorage).
+    /// Does nothing if the file is not found.
+    fn remove_history_file(&self, ctx: &MmArc) -> TxHistoryFut<()> { remove_history_file_impl(self, ctx) }
+
     /// Transaction history background sync status
     fn history_sync_status(&self) -> HistorySyncState;
 
@@ -1802,6 +1974,8 @@ pub enum MmCoinEnum {
     SolanaCoin(SolanaCoin),
     #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
     SplToken(SplToken),
+    Tendermint(TendermintCoin),
+    TendermintToken(TendermintToken),
     #[cfg(not(target_arch = "wasm32"))]
     LightningCoin(LightningCoin),
     Test(TestCoin),
@@ -1829,6 +2003,14 @@ impl From<SplToken> for MmCoinEnum {
     fn from(c: SplToken) -> MmCoinEnum { MmCoinEnum::SplToken(c) }
 }
 
+impl From<TendermintCoin> for MmCoinEnum {
+    fn from(c: TendermintCoin) -> MmCoinEnum { MmCoinEnum::Tendermint(c) }
+}
+
+impl From<TendermintToken> for MmCoinEnum {
+    fn from(c: TendermintToken) -> MmCoinEnum { MmCoinEnum::TendermintToken(c) }
+}
+
 impl From<QtumCoin> for MmCoinEnum {
     fn from(coin: QtumCoin) -> Self { MmCoinEnum::QtumCoin(coin) }
 }
@@ -1875,6 +2057,8 @@ impl Deref for MmCoinEnum {
             MmCoinEnum::SolanaCoin(ref c) => c,
             #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
             MmCoinEnum::SplToken(ref c) => c,
+            MmCoinEnum::Tendermint(ref c) => c,
+            MmCoinEnum::TendermintToken(ref c) => c,
         }
     }
 }
@@ -1899,11 +2083,37 @@ pub trait BalanceTradeFeeUpdatedHandler {
     async fn balance_updated(&self, coin: &MmCoinEnum, new_balance: &BigDecimal);
 }
 
+/// The amounts of the coin reserved by the trading activity.
+#[derive(Clone, Debug, Default)]
+pub struct ReservedAmounts {
+    /// The amount locked by the active swaps.
+    pub locked_by_swaps: BigDecimal,
+    /// The available volumes of the open maker orders selling the coin.
+    pub maker_order_volumes: Vec<BigDecimal>,
+}
+
+impl ReservedAmounts {
+    /// The total amount that must stay spendable.
+    pub fn total(&self) -> BigDecimal {
+        self.maker_order_volumes
+            .iter()
+            .fold(self.locked_by_swaps.clone(), |total, volume| total + volume)
+    }
+}
+
+/// Provides the coins with the amounts reserved by the swaps and the orders,
+/// so the wallet maintenance like the UTXO merging doesn't spend them.
+#[async_trait]
+pub trait ReservedAmountsProvider {
+    async fn reserved_amounts(&self, ticker: &str) -> ReservedAmounts;
+}
+
 pub struct CoinsContext {
     /// A map from a currency ticker symbol to the corresponding coin.
     /// Similar to `LP_coins`.
     coins: AsyncMutex<HashMap<String, MmCoinEnum>>,
     balance_update_handlers: AsyncMutex<Vec<Box<dyn BalanceTradeFeeUpdatedHandler + Send + Sync>>>,
+    reserved_amounts_provider: AsyncMutex<Option<Box<dyn ReservedAmountsProvider + Send + Sync>>>,
     withdraw_task_manager: WithdrawTaskManagerShared,
     create_account_manager: CreateAccountTaskManagerShared,
     scan_addresses_manager: ScanAddressesTaskManagerShared,
@@ -1911,6 +2121,8 @@ pub struct CoinsContext {
     tx_history_db: SharedDb<TxHistoryDb>,
     #[cfg(target_arch = "wasm32")]
     hd_wallet_db: SharedDb<HDWalletDb>,
+    #[cfg(target_arch = "wasm32")]
+    pub(crate) block_headers_db: SharedDb<BlockHeadersDb>,
 }
 
 #[derive(Debug)]
@@ -1930,6 +2142,7 @@ impl CoinsContext {
             Ok(CoinsContext {
                 coins: AsyncMutex::new(HashMap::new()),
                 balance_update_handlers: AsyncMutex::new(vec![]),
+                reserved_amounts_provider: AsyncMutex::new(None),
                 withdraw_task_manager: WithdrawTaskManager::new_shared(),
                 create_account_manager: CreateAccountTaskManager::new_shared(),
                 scan_addresses_manager: ScanAddressesTaskManager::new_shared(),
@@ -1937,10 +2150,21 @@ impl CoinsContext {
                 tx_history_db: ConstructibleDb::new_shared(ctx),
                 #[cfg(target_arch = "wasm32")]
                 hd_wallet_db: ConstructibleDb::new_shared(ctx),
+                #[cfg(target_arch = "wasm32")]
+                block_headers_db: ConstructibleDb::new_shared(ctx),
             })
         })))
     }
 
+    /// Returns the short descriptions of all RPC tasks spawned by this crate.
+    pub fn rpc_tasks_info(&self) -> Result<Vec<RpcTaskInfo>, String> {
+        let mut tasks = Vec::new();
+        tasks.extend(try_s!(self.withdraw_task_manager.lock()).tasks_info("init_withdraw"));
+        tasks.extend(try_s!(self.create_account_manager.lock()).tasks_info("init_create_new_account"));
+        tasks.extend(try_s!(self.scan_addresses_manager.lock()).tasks_info("init_scan_for_new_addresses"));
+        Ok(tasks)
+    }
+
     pub async fn add_coin(&self, coin: MmCoinEnum) -> Result<(), MmError<CoinIsAlreadyActivatedErr>> {
         let mut coins = self.coins.lock().await;
         if coins.contains_key(coin.ticker()) {
@@ -2118,6 +2342,8 @@ pub enum CoinProtocol {
     },
     #[cfg(not(target_arch = "wasm32"))]
     ZHTLC(ZcoinProtocolInfo),
+    TENDERMINT(TendermintProtocolInfo),
+    TENDERMINTTOKEN(TendermintTokenProtocolInfo),
 }
 
 pub type RpcTransportEventHandlerShared = Arc<dyn RpcTransportEventHandler + Send + Sync + 'static>;
@@ -2131,6 +2357,9 @@ pub trait RpcTransportEventHandler {
     fn on_incoming_response(&self, data: &[u8]);
 
     fn on_connected(&self, address: String) -> Result<(), String>;
+
+    /// Called when a request to the `server` is completed or failed (including the timeout expiration).
+    fn on_request_completed(&self, server: &str, latency: Duration, success: bool);
 }
 
 impl fmt::Debug for dyn RpcTransportEventHandler + Send + Sync {
@@ -2145,6 +2374,10 @@ impl RpcTransportEventHandler for RpcTransportEventHandlerShared {
     fn on_incoming_response(&self, data: &[u8]) { self.as_ref().on_incoming_response(data) }
 
     fn on_connected(&self, address: String) -> Result<(), String> { self.as_ref().on_connected(address) }
+
+    fn on_request_completed(&self, server: &str, latency: Duration, success: bool) {
+        self.as_ref().on_request_completed(server, latency, success)
+    }
 }
 
 impl<T: RpcTransportEventHandler> RpcTransportEventHandler for Vec<T> {
@@ -2171,6 +2404,12 @@ impl<T: RpcTransportEventHandler> RpcTransportEventHandler for Vec<T> {
         }
         Ok(())
     }
+
+    fn on_request_completed(&self, server: &str, latency: Duration, success: bool) {
+        for handler in self {
+            handler.on_request_completed(server, latency, success)
+        }
+    }
 }
 
 pub enum RpcClientType {
@@ -2233,6 +2472,17 @@ impl RpcTransportEventHandler for CoinTransportMetrics {
         // Now just return the Ok
         Ok(())
     }
+
+    fn on_request_completed(&self, server: &str, latency: Duration, success: bool) {
+        mm_timing!(self.metrics, "rpc_client.server.request.timing", 0, latency.as_nanos() as u64,
+            "coin" => self.ticker.clone(), "client" => self.client.clone(), "server" => server.to_owned());
+        mm_counter!(self.metrics, "rpc_client.server.request.count", 1,
+            "coin" => self.ticker.clone(), "client" => self.client.clone(), "server" => server.to_owned());
+        if !success {
+            mm_counter!(self.metrics, "rpc_client.server.error.count", 1,
+                "coin" => self.ticker.clone(), "client" => self.client.clone(), "server" => server.to_owned());
+        }
+    }
 }
 
 #[async_trait]
@@ -2372,6 +2622,16 @@ pub async fn lp_coininit(ctx: &MmArc, ticker: &str, req: &Json) -> Result<MmCoin
         CoinProtocol::SPLTOKEN { .. } => {
             return ERR!("SplToken protocol is not supported by lp_coininit - use enable_spl instead")
         },
+        CoinProtocol::TENDERMINT(_) => {
+            return ERR!(
+                "Tendermint protocol is not supported by lp_coininit - use enable_tendermint_with_assets instead"
+            )
+        },
+        CoinProtocol::TENDERMINTTOKEN(_) => {
+            return ERR!(
+                "TendermintToken protocol is not supported by lp_coininit - use enable_tendermint_token instead"
+            )
+        },
     };
 
     let register_params = RegisterCoinParams {
@@ -2438,6 +2698,18 @@ fn lp_spawn_tx_history(ctx: MmArc, coin: MmCoinEnum) -> Result<(), String> {
     Ok(())
 }
 
+/// Returns the SOCKS5 proxy the coin RPC connections are established through.
+/// The `proxy` of the coin config overrides the global `proxy` config option.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn coin_proxy(ctx: &MmArc, coin_conf: &Json) -> Result<Option<Socks5Proxy>, String> {
+    let proxy = if coin_conf["proxy"].is_null() {
+        &ctx.conf["proxy"]
+    } else {
+        &coin_conf["proxy"]
+    };
+    json::from_value(proxy.clone()).map_err(|e| ERRL!("Error parsing 'proxy': {}", e))
+}
+
 /// NB: Returns only the enabled (aka active) coins.
 pub async fn lp_coinfind(ctx: &MmArc, ticker: &str) -> Result<Option<MmCoinEnum>, String> {
     let cctx = try_s!(CoinsContext::from_ctx(ctx));
@@ -2445,6 +2717,35 @@ pub async fn lp_coinfind(ctx: &MmArc, ticker: &str) -> Result<Option<MmCoinEnum>
     Ok(coins.get(ticker).cloned())
 }
 
+/// How often the balances of the enabled coins are reported to the metrics, in seconds.
+const BALANCE_METRICS_INTERVAL: f64 = 60.;
+
+/// Periodically reports the spendable balances of the enabled coins by the `coin.balance` gauges.
+/// The gauges are integers, so the balances are reported in 10^-8 units of the coins.
+pub async fn balance_metrics_loop(ctx: MmWeak) {
+    let units_per_coin = BigDecimal::from(100_000_000);
+    loop {
+        Timer::sleep(BALANCE_METRICS_INTERVAL).await;
+        let ctx = match MmArc::from_weak(&ctx) {
+            Some(ctx) => ctx,
+            None => return,
+        };
+        let coins: Vec<MmCoinEnum> = match CoinsContext::from_ctx(&ctx) {
+            Ok(coins_ctx) => coins_ctx.coins.lock().await.values().cloned().collect(),
+            Err(_) => return,
+        };
+
+        for coin in coins {
+            let balance = match coin.my_spendable_balance().compat().await {
+                Ok(balance) => balance,
+                Err(_) => continue,
+            };
+            let units = (balance * &units_per_coin).to_i64().unwrap_or(i64::MAX);
+            mm_gauge!(ctx.metrics, "coin.balance", units, "coin" => coin.ticker().to_owned());
+        }
+    }
+}
+
 /// Attempts to find a pair of active coins returning None if one is not enabled
 pub async fn find_pair(ctx: &MmArc, base: &str, rel: &str) -> Result<Option<(MmCoinEnum, MmCoinEnum)>, String> {
     let fut_base = lp_coinfind(ctx, base);
@@ -2567,44 +2868,67 @@ pub async fn verify_message(ctx: MmArc, req: VerificationRequest) -> Verificatio
     Ok(VerificationResponse { is_valid })
 }
 
-pub async fn remove_delegation(ctx: MmArc, req: RemoveDelegateRequest) -> DelegationResult {
-    let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
+fn coin_staking_ops(coin: &MmCoinEnum) -> Option<&dyn StakingOps> {
     match coin {
-        MmCoinEnum::QtumCoin(qtum) => qtum.remove_delegation().compat().await,
-        _ => {
-            return MmError::err(DelegationError::CoinDoesntSupportDelegation {
-                coin: coin.ticker().to_string(),
-            })
-        },
+        MmCoinEnum::QtumCoin(qtum) => Some(qtum),
+        #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
+        MmCoinEnum::SolanaCoin(solana) => Some(solana),
+        _ => None,
     }
 }
 
+fn delegation_ops_or_err(coin: &MmCoinEnum) -> Result<&dyn StakingOps, MmError<DelegationError>> {
+    coin_staking_ops(coin).or_mm_err(|| DelegationError::CoinDoesntSupportDelegation {
+        coin: coin.ticker().to_string(),
+    })
+}
+
+fn staking_infos_ops_or_err(coin: &MmCoinEnum) -> Result<&dyn StakingOps, MmError<StakingInfosError>> {
+    coin_staking_ops(coin).or_mm_err(|| StakingInfosError::CoinDoesntSupportStakingInfos {
+        coin: coin.ticker().to_string(),
+    })
+}
+
+pub async fn remove_delegation(ctx: MmArc, req: RemoveDelegateRequest) -> DelegationResult {
+    let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
+    delegation_ops_or_err(&coin)?.undelegate(req.validator).compat().await
+}
+
 pub async fn get_staking_infos(ctx: MmArc, req: GetStakingInfosRequest) -> StakingInfosResult {
     let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
-    match coin {
-        MmCoinEnum::QtumCoin(qtum) => qtum.get_delegation_infos().compat().await,
-        _ => {
-            return MmError::err(StakingInfosError::CoinDoesntSupportStakingInfos {
-                coin: coin.ticker().to_string(),
-            })
-        },
-    }
+    staking_infos_ops_or_err(&coin)?.staking_infos().compat().await
 }
 
 pub async fn add_delegation(ctx: MmArc, req: AddDelegateRequest) -> DelegationResult {
     let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
-    // Need to find a way to do a proper dispatch
-    let coin_concrete = match coin {
-        MmCoinEnum::QtumCoin(qtum) => qtum,
-        _ => {
-            return MmError::err(DelegationError::CoinDoesntSupportDelegation {
-                coin: coin.ticker().to_string(),
-            })
-        },
-    };
-    match req.staking_details {
-        StakingDetails::Qtum(qtum_staking) => coin_concrete.add_delegation(qtum_staking).compat().await,
-    }
+    delegation_ops_or_err(&coin)?
+        .delegate(req.staking_details)
+        .compat()
+        .await
+}
+
+pub async fn get_staking_validators(
+    ctx: MmArc,
+    req: GetStakingInfosRequest,
+) -> Result<StakingValidators, MmError<StakingInfosError>> {
+    let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
+    staking_infos_ops_or_err(&coin)?.validators().compat().await
+}
+
+pub async fn get_pending_staking_rewards(
+    ctx: MmArc,
+    req: GetStakingInfosRequest,
+) -> Result<StakingRewards, MmError<StakingInfosError>> {
+    let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
+    staking_infos_ops_or_err(&coin)?.pending_rewards().compat().await
+}
+
+pub async fn claim_staking_rewards(ctx: MmArc, req: ClaimStakingRewardsRequest) -> DelegationResult {
+    let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
+    delegation_ops_or_err(&coin)?
+        .claim_rewards(req.validator)
+        .compat()
+        .await
 }
 
 pub async fn send_raw_transaction(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
@@ -2652,7 +2976,7 @@ pub async fn my_tx_history(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, S
         Err(err) => return ERR!("!lp_coinfind({}): {}", request.coin, err),
     };
 
-    let history = try_s!(coin.load_history_from_file(&ctx).compat().await);
+    let history = try_s!(coin.load_history(&ctx).compat().await);
     let total_records = history.len();
     let limit = if request.max { total_records } else { request.limit };
 
@@ -2826,6 +3150,22 @@ pub async fn register_balance_update_handler(
     coins_ctx.balance_update_handlers.lock().await.push(handler);
 }
 
+pub async fn register_reserved_amounts_provider(ctx: MmArc, provider: Box<dyn ReservedAmountsProvider + Send + Sync>) {
+    let coins_ctx = CoinsContext::from_ctx(&ctx).unwrap();
+    *coins_ctx.reserved_amounts_provider.lock().await = Some(provider);
+}
+
+/// Returns the amounts of the `ticker` coin reserved by the trading activity,
+/// nothing is reserved if no [`ReservedAmountsProvider`] is registered.
+pub async fn reserved_amounts(ctx: &MmArc, ticker: &str) -> ReservedAmounts {
+    let coins_ctx = CoinsContext::from_ctx(ctx).unwrap();
+    let provider = coins_ctx.reserved_amounts_provider.lock().await;
+    match provider.as_ref() {
+        Some(provider) => provider.reserved_amounts(ticker).await,
+        None => ReservedAmounts::default(),
+    }
+}
+
 pub fn update_coins_config(mut config: Json) -> Result<Json, String> {
     let coins = match config.as_array_mut() {
         Some(c) => c,
@@ -2931,6 +3271,22 @@ pub fn address_by_coin_conf_and_pubkey_str(
         },
         #[cfg(not(target_arch = "wasm32"))]
         CoinProtocol::ZHTLC { .. } => ERR!("address_by_coin_conf_and_pubkey_str is not supported for ZHTLC protocol!"),
+        CoinProtocol::TENDERMINT(protocol_info) => {
+            tendermint::account_id_from_pubkey_hex(&protocol_info.account_prefix, pubkey)
+        },
+        CoinProtocol::TENDERMINTTOKEN(protocol_info) => {
+            let platform_conf = coin_conf(ctx, &protocol_info.platform);
+            if platform_conf.is_null() {
+                return ERR!("platform {} conf is null", protocol_info.platform);
+            }
+            let platform_protocol: CoinProtocol = try_s!(json::from_value(platform_conf["protocol"].clone()));
+            match platform_protocol {
+                CoinProtocol::TENDERMINT(platform_info) => {
+                    tendermint::account_id_from_pubkey_hex(&platform_info.account_prefix, pubkey)
+                },
+                _ => ERR!("Platform protocol {:?} is not TENDERMINT", platform_protocol),
+            }
+        },
     }
 }
 
@@ -3008,6 +3364,44 @@ where
     Box::new(fut.boxed().compat())
 }
 
+#[cfg(target_arch = "wasm32")]
+fn remove_history_file_impl<T>(coin: &T, ctx: &MmArc) -> TxHistoryFut<()>
+where
+    T: MmCoin + ?Sized,
+{
+    let ctx = ctx.clone();
+    let ticker = coin.ticker().to_owned();
+    let my_address = try_f!(coin.my_address().map_to_mm(TxHistoryError::InternalError));
+
+    let fut = async move {
+        let coins_ctx = CoinsContext::from_ctx(&ctx).unwrap();
+        let db = coins_ctx.tx_history_db().await?;
+        clear_tx_history(&db, &ticker, &my_address).await?;
+        Ok(())
+    };
+    Box::new(fut.boxed().compat())
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn remove_history_file_impl<T>(coin: &T, ctx: &MmArc) -> TxHistoryFut<()>
+where
+    T: MmCoin + ?Sized,
+{
+    let history_path = coin.tx_history_path(ctx);
+
+    let fut = async move {
+        match fs::remove_file(&history_path).await {
+            Ok(()) => Ok(()),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(err) => {
+                let error = format!("Error '{}' removing the history file {}", err, history_path.display());
+                MmError::err(TxHistoryError::ErrorClearing(error))
+            },
+        }
+    };
+    Box::new(fut.boxed().compat())
+}
+
 #[cfg(target_arch = "wasm32")]
 fn save_history_to_file_impl<T>(coin: &T, ctx: &MmArc, mut history: Vec<TransactionDetails>) -> TxHistoryFut<()>
 where
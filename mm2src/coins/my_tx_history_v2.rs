@@ -1,17 +1,19 @@
 use crate::tx_history_storage::{CreateTxHistoryStorageError, GetTxHistoryFilters, TxHistoryStorageBuilder, WalletId};
 use crate::{lp_coinfind_or_err, BlockHeightAndTime, CoinFindError, HistorySyncState, MmCoin, MmCoinEnum, Transaction,
-            TransactionDetails, TransactionType, TxFeeDetails, UtxoRpcError};
+            TransactionDetails, TransactionType, TxFeeDetails, TxHistoryError, TxHistoryFut, UtxoRpcError};
 use async_trait::async_trait;
 use bitcrypto::sha256;
 use common::{calc_total_pages, ten, HttpStatusCode, PagingOptionsEnum, StatusCode};
 use derive_more::Display;
 use futures::compat::Future01CompatExt;
+use futures::{FutureExt, TryFutureExt};
 use keys::{Address, CashAddress};
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
 use mm2_number::BigDecimal;
 use rpc::v1::types::{Bytes as BytesJson, ToTxHash};
 use std::collections::HashSet;
+use std::num::NonZeroUsize;
 
 #[derive(Debug)]
 pub enum RemoveTxResult {
@@ -205,6 +207,9 @@ impl<'a, Addr: Clone + DisplayAddress + Eq + std::hash::Hash, Tx: Transaction> T
             },
             TransactionType::StakingDelegation
             | TransactionType::RemoveDelegation
+            | TransactionType::ClaimDelegationRewards
+            | TransactionType::WithdrawStake
+            | TransactionType::ClaimKmdRewards
             | TransactionType::StandardTransfer => tx_hash.clone(),
         };
 
@@ -223,6 +228,7 @@ impl<'a, Addr: Clone + DisplayAddress + Eq + std::hash::Hash, Tx: Transaction> T
             fee_details: self.tx_fee,
             internal_id,
             kmd_rewards: None,
+            spv_verified: None,
             transaction_type: self.transaction_type,
         }
     }
@@ -304,10 +310,57 @@ impl From<UtxoRpcError> for MyTxHistoryErrorV2 {
     fn from(err: UtxoRpcError) -> Self { MyTxHistoryErrorV2::RpcError(err.to_string()) }
 }
 
+#[async_trait]
 pub trait CoinWithTxHistoryV2 {
     fn history_wallet_id(&self) -> WalletId;
 
-    fn get_tx_history_filters(&self) -> GetTxHistoryFilters;
+    /// Returns the filters that select the coin's transactions within the `history_wallet_id` history.
+    /// For example, HD wallet coins filter the history by the known addresses.
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2>;
+}
+
+/// Loads every transaction that matches the `filters` from the storage.
+pub async fn load_all_history<Storage: TxHistoryStorage>(
+    storage: &Storage,
+    wallet_id: &WalletId,
+    filters: GetTxHistoryFilters,
+) -> MmResult<Vec<TransactionDetails>, Storage::Error> {
+    let paging_options = PagingOptionsEnum::PageNumber(NonZeroUsize::new(1).expect("1 is not zero"));
+    let limit = u32::MAX as usize;
+    let history = storage.get_history(wallet_id, filters, paging_options, limit).await?;
+    Ok(history.transactions)
+}
+
+/// Loads the coin's history from [`TxHistoryStorage`].
+/// It's used to implement [`MmCoin::load_history`] for the coins that keep their history within the storage,
+/// so the legacy `my_tx_history` RPC keeps working.
+pub fn load_history_from_storage<Coin>(coin: &Coin, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>>
+where
+    Coin: CoinWithTxHistoryV2 + Clone + Send + Sync + 'static,
+{
+    let coin = coin.clone();
+    let ctx = ctx.clone();
+    let fut = async move {
+        let storage = TxHistoryStorageBuilder::new(&ctx)
+            .build()
+            .mm_err(|e| TxHistoryError::ErrorLoading(e.to_string()))?;
+        let wallet_id = coin.history_wallet_id();
+        let is_initialized = storage
+            .is_initialized_for(&wallet_id)
+            .await
+            .mm_err(|e| TxHistoryError::ErrorLoading(format!("{:?}", e)))?;
+        if !is_initialized {
+            return Ok(Vec::new());
+        }
+        let filters = coin
+            .get_tx_history_filters()
+            .await
+            .mm_err(|e| TxHistoryError::ErrorLoading(e.to_string()))?;
+        load_all_history(&storage, &wallet_id, filters)
+            .await
+            .mm_err(|e| TxHistoryError::ErrorLoading(format!("{:?}", e)))
+    };
+    Box::new(fut.boxed().compat())
 }
 
 /// According to the [comment](https://github.com/KomodoPlatform/atomicDEX-API/pull/1285#discussion_r888410390),
@@ -319,6 +372,12 @@ pub async fn my_tx_history_v2_rpc(
     match lp_coinfind_or_err(&ctx, &request.coin).await? {
         MmCoinEnum::Bch(bch) => my_tx_history_v2_impl(ctx, &bch, request).await,
         MmCoinEnum::SlpToken(slp_token) => my_tx_history_v2_impl(ctx, &slp_token, request).await,
+        MmCoinEnum::UtxoCoin(utxo) => my_tx_history_v2_impl(ctx, &utxo, request).await,
+        MmCoinEnum::QtumCoin(qtum) => my_tx_history_v2_impl(ctx, &qtum, request).await,
+        MmCoinEnum::Qrc20Coin(qrc20) => my_tx_history_v2_impl(ctx, &qrc20, request).await,
+        MmCoinEnum::EthCoin(eth) => my_tx_history_v2_impl(ctx, &eth, request).await,
+        MmCoinEnum::Tendermint(tendermint) => my_tx_history_v2_impl(ctx, &tendermint, request).await,
+        MmCoinEnum::TendermintToken(token) => my_tx_history_v2_impl(ctx, &token, request).await,
         other => MmError::err(MyTxHistoryErrorV2::NotSupportedFor(other.ticker().to_owned())),
     }
 }
@@ -345,7 +404,7 @@ where
         .await
         .map_to_mm(MyTxHistoryErrorV2::RpcError)?;
 
-    let filters = coin.get_tx_history_filters();
+    let filters = coin.get_tx_history_filters().await?;
     let history = tx_history_storage
         .get_history(&wallet_id, filters, request.paging_options.clone(), request.limit)
         .await?;
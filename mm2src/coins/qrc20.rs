@@ -1,6 +1,8 @@
 use crate::eth::{self, u256_to_big_decimal, wei_from_big_decimal, TryToAddress};
+use crate::my_tx_history_v2::{load_history_from_storage, CoinWithTxHistoryV2, MyTxHistoryErrorV2, TxHistoryStorage};
 use crate::qrc20::rpc_clients::{LogEntry, Qrc20ElectrumOps, Qrc20NativeOps, Qrc20RpcOps, TopicFilter, TxReceipt,
                                 ViewContractCallType};
+use crate::tx_history_storage::{GetTxHistoryFilters, TxHistoryStorageBuilder, WalletId};
 use crate::utxo::qtum::QtumBasedCoin;
 use crate::utxo::rpc_clients::{ElectrumClient, NativeClient, UnspentInfo, UtxoRpcClientEnum, UtxoRpcClientOps,
                                UtxoRpcError, UtxoRpcFut, UtxoRpcResult};
@@ -17,8 +19,9 @@ use crate::{BalanceError, BalanceFut, CoinBalance, FeeApproxStage, FoundSwapTxSp
             MmCoin, NegotiateSwapContractAddrErr, PrivKeyNotAllowed, RawTransactionFut, RawTransactionRequest,
             SearchForSwapTxSpendInput, SignatureResult, SwapOps, TradeFee, TradePreimageError, TradePreimageFut,
             TradePreimageResult, TradePreimageValue, TransactionDetails, TransactionEnum, TransactionErr,
-            TransactionFut, TransactionType, UnexpectedDerivationMethod, ValidateAddressResult, ValidatePaymentInput,
-            VerificationResult, WithdrawError, WithdrawFee, WithdrawFut, WithdrawRequest, WithdrawResult};
+            TransactionFut, TransactionType, TxHistoryFut, UnexpectedDerivationMethod, ValidateAddressResult,
+            ValidatePaymentInput, VerificationResult, WithdrawError, WithdrawFee, WithdrawFut, WithdrawRequest,
+            WithdrawResult};
 use async_trait::async_trait;
 use bitcrypto::{dhash160, sha256};
 use chain::TransactionOutput;
@@ -1133,6 +1136,8 @@ impl MarketCoinOps for Qrc20Coin {
 impl MmCoin for Qrc20Coin {
     fn is_asset_chain(&self) -> bool { utxo_common::is_asset_chain(&self.utxo) }
 
+    fn wallet_only(&self, ctx: &MmArc) -> bool { utxo_common::wallet_only(&self.utxo, ctx) }
+
     fn withdraw(&self, req: WithdrawRequest) -> WithdrawFut {
         Box::new(qrc20_withdraw(self.clone(), req).boxed().compat())
     }
@@ -1153,6 +1158,10 @@ impl MmCoin for Qrc20Coin {
         Box::new(self.clone().history_loop(ctx).map(|_| Ok(())).boxed().compat())
     }
 
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+        load_history_from_storage(self, ctx)
+    }
+
     fn history_sync_status(&self) -> HistorySyncState { utxo_common::history_sync_status(&self.utxo) }
 
     /// This method is called to check our QTUM balance.
@@ -1296,6 +1305,17 @@ impl MmCoin for Qrc20Coin {
     }
 }
 
+#[async_trait]
+impl CoinWithTxHistoryV2 for Qrc20Coin {
+    /// QRC20 transfers are stored separately from the platform coin's history
+    /// since the platform coin considers a transaction as processed if its tx hash is in the history already.
+    fn history_wallet_id(&self) -> WalletId { WalletId::new(self.ticker().to_owned()) }
+
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        Ok(GetTxHistoryFilters::new().with_token_id(hex::encode(self.contract_address.0)))
+    }
+}
+
 pub fn qrc20_swap_id(time_lock: u32, secret_hash: &[u8]) -> Vec<u8> {
     let mut input = vec![];
     input.extend_from_slice(&time_lock.to_le_bytes());
@@ -1411,6 +1431,7 @@ async fn qrc20_withdraw(coin: Qrc20Coin, req: WithdrawRequest) -> WithdrawResult
         internal_id: vec![].into(),
         timestamp: now_ms() / 1000,
         kmd_rewards: None,
+        spv_verified: None,
         transaction_type: TransactionType::StandardTransfer,
     })
 }
@@ -74,13 +74,45 @@ enum ProcessCachedTransferMapResult {
 
 impl Qrc20Coin {
     pub async fn history_loop(self, ctx: MmArc) {
-        let mut history_map = match self.try_load_history_from_file(&ctx).await {
+        let storage = match TxHistoryStorageBuilder::new(&ctx).build() {
+            Ok(storage) => storage,
+            Err(e) => {
+                ctx.log.log(
+                    "😟",
+                    &[&"tx_history", &self.utxo.conf.ticker],
+                    &ERRL!("Error {} on creating the tx history storage, stop the history loop", e),
+                );
+                return;
+            },
+        };
+        let wallet_id = self.history_wallet_id();
+        if let Err(e) = storage.init(&wallet_id).await {
+            ctx.log.log(
+                "😟",
+                &[&"tx_history", &self.utxo.conf.ticker],
+                &ERRL!(
+                    "Error {:?} on the tx history storage initialization, stop the history loop",
+                    e
+                ),
+            );
+            return;
+        }
+
+        if let Err(e) = self.remove_history_file(&ctx).compat().await {
+            ctx.log.log(
+                "😟",
+                &[&"tx_history", &self.utxo.conf.ticker],
+                &ERRL!("Error {} on removing the legacy tx history file", e),
+            );
+        }
+
+        let mut history_map = match self.try_load_history_from_storage(&ctx).await {
             Ok(history) => history,
             Err(e) => {
                 ctx.log.log(
                     "😟",
                     &[&"tx_history", &self.utxo.conf.ticker],
-                    &ERRL!("Error {} on load history from file, stop the history loop", e),
+                    &ERRL!("Error {} on load history from storage, stop the history loop", e),
                 );
                 return;
             },
@@ -155,7 +187,20 @@ impl Qrc20Coin {
                 },
             };
 
-            let updated = self.process_tx_ids(&ctx, &mut history_map, tx_ids).await;
+            if let Err(e) = self
+                .process_tx_ids(&ctx, &storage, &wallet_id, &mut history_map, tx_ids)
+                .await
+            {
+                ctx.log.log(
+                    "",
+                    &[&"tx_history", &self.as_ref().conf.ticker],
+                    &ERRL!(
+                        "Error {:?} on saving the history to the storage, stop the history loop",
+                        e
+                    ),
+                );
+                return;
+            }
             if success_iteration == 0 {
                 ctx.log.log(
                     "😅",
@@ -166,25 +211,6 @@ impl Qrc20Coin {
 
             my_balance = Some(actual_balance);
             success_iteration += 1;
-
-            if !updated {
-                continue;
-            }
-
-            // `history_map` has been updated.
-            let to_write: Vec<TransactionDetails> = history_map
-                .iter()
-                .flat_map(|(_, value)| value)
-                .map(|(_tx_id, tx)| tx.clone())
-                .collect();
-            if let Err(e) = self.save_history_to_file(&ctx, to_write).compat().await {
-                ctx.log.log(
-                    "",
-                    &[&"tx_history", &self.as_ref().conf.ticker],
-                    &ERRL!("Error {} on 'save_history_to_file', stop the history loop", e),
-                );
-                return;
-            }
         }
     }
 
@@ -323,6 +349,8 @@ impl Qrc20Coin {
                 block_height,
                 fee_details: Some(fee_details.clone().into()),
                 internal_id: internal_id.clone().into(),
+                spv_verified: None,
+                transaction_type: TransactionType::TokenTransfer(BytesJson(self.contract_address.0.to_vec())),
                 ..qtum_details.clone()
             };
 
@@ -461,11 +489,27 @@ impl Qrc20Coin {
         }
     }
 
-    /// Returns true if the `history_map` has been updated.
-    async fn process_tx_ids(&self, ctx: &MmArc, history_map: &mut HistoryMapByHash, tx_ids: TxIds) -> bool {
+    /// Updates the `history_map` and writes the changes to the `storage`.
+    async fn process_tx_ids<Storage: TxHistoryStorage>(
+        &self,
+        ctx: &MmArc,
+        storage: &Storage,
+        wallet_id: &WalletId,
+        history_map: &mut HistoryMapByHash,
+        tx_ids: TxIds,
+    ) -> Result<(), MmError<Storage::Error>> {
         // Remove transactions in the history_map that are not in the requested transaction list anymore
         let requested_ids: HashSet<H256Json> = tx_ids.iter().map(|x| x.0).collect();
-        history_map.retain(|hash, _| requested_ids.contains(hash));
+        let removed_hashes: Vec<H256Json> = history_map
+            .keys()
+            .filter(|hash| !requested_ids.contains(hash))
+            .copied()
+            .collect();
+        for hash in removed_hashes {
+            if let Some(removed) = history_map.remove(&hash) {
+                self.remove_transfers_from_storage(storage, wallet_id, removed).await?;
+            }
+        }
 
         let mut transactions_left = if history_map.len() < tx_ids.len() {
             tx_ids.len() - history_map.len()
@@ -475,7 +519,6 @@ impl Qrc20Coin {
         *self.utxo.history_sync_state.lock().unwrap() =
             HistorySyncState::InProgress(json!({ "transactions_left": transactions_left }));
 
-        let mut updated = false;
         for (tx_hash, height) in tx_ids {
             // first check if the `transfer` details are initialized for the `tx_hash`
             if let Some(tx_hash_history) = history_map.get_mut(&tx_hash) {
@@ -485,7 +528,9 @@ impl Qrc20Coin {
                     .await
                 {
                     ProcessCachedTransferMapResult::Updated => {
-                        updated = true;
+                        for tx in tx_hash_history.values() {
+                            storage.update_tx_in_history(wallet_id, tx).await?;
+                        }
                         continue;
                     },
                     ProcessCachedTransferMapResult::UpdateIsNotNeeded => continue,
@@ -508,13 +553,17 @@ impl Qrc20Coin {
                 },
             };
 
-            if history_map.insert(tx_hash, tx_hash_history).is_some() {
+            if let Some(reloaded) = history_map.insert(tx_hash, tx_hash_history.clone()) {
                 ctx.log.log(
                     "😟",
                     &[&"tx_history", &self.utxo.conf.ticker],
                     &format!("'transfer' details of {:?} were reloaded", tx_hash),
                 );
+                self.remove_transfers_from_storage(storage, wallet_id, reloaded).await?;
             }
+            storage
+                .add_transactions_to_history(wallet_id, tx_hash_history.into_iter().map(|(_, tx)| tx))
+                .await?;
 
             mm_counter!(ctx.metrics, "tx.history.response.count", 1, "coin" => self.utxo.conf.ticker.clone(), "method" => "transfer_details_by_hash");
             if transactions_left > 0 {
@@ -522,16 +571,28 @@ impl Qrc20Coin {
                 *self.utxo.history_sync_state.lock().unwrap() =
                     HistorySyncState::InProgress(json!({ "transactions_left": transactions_left }));
             }
-
-            updated = true;
         }
 
         *self.utxo.history_sync_state.lock().unwrap() = HistorySyncState::Finished;
-        updated
+        Ok(())
+    }
+
+    async fn remove_transfers_from_storage<Storage: TxHistoryStorage>(
+        &self,
+        storage: &Storage,
+        wallet_id: &WalletId,
+        transfers: TxTransferMap,
+    ) -> Result<(), MmError<Storage::Error>> {
+        for (internal_id, _) in transfers {
+            storage
+                .remove_tx_from_history(wallet_id, &BytesJson::from(internal_id))
+                .await?;
+        }
+        Ok(())
     }
 
-    async fn try_load_history_from_file(&self, ctx: &MmArc) -> TxHistoryResult<HistoryMapByHash> {
-        let history = self.load_history_from_file(ctx).compat().await?;
+    async fn try_load_history_from_storage(&self, ctx: &MmArc) -> TxHistoryResult<HistoryMapByHash> {
+        let history = self.load_history(ctx).compat().await?;
         let mut history_map: HistoryMapByHash = HashMap::default();
 
         for tx in history {
@@ -541,7 +602,7 @@ impl Qrc20Coin {
                     ctx.log.log(
                         "😟",
                         &[&"tx_history", &self.utxo.conf.ticker],
-                        &ERRL!("Error {:?} on load history from file", e),
+                        &ERRL!("Error {:?} on load history from storage", e),
                     );
                     return Ok(HistoryMapByHash::default());
                 },
@@ -551,7 +612,7 @@ impl Qrc20Coin {
                 ctx.log.log(
                     "😟",
                     &[&"tx_history", &self.utxo.conf.ticker],
-                    &ERRL!("History file contains entries with the same 'internal_id'"),
+                    &ERRL!("History contains entries with the same 'internal_id'"),
                 );
                 return Ok(HistoryMapByHash::default());
             }
@@ -651,6 +712,10 @@ impl BuildTransferHistory for UtxoRpcClientEnum {
         match self {
             UtxoRpcClientEnum::Native(native) => native.build(params).await,
             UtxoRpcClientEnum::Electrum(electrum) => electrum.build(params).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(_) => MmError::err(UtxoRpcError::Internal(
+                "QRC20 transfer history isn't supported by the compact filters client".to_owned(),
+            )),
         }
     }
 
@@ -661,6 +726,10 @@ impl BuildTransferHistory for UtxoRpcClientEnum {
         match self {
             UtxoRpcClientEnum::Native(native) => native.build_tx_idents(params).await,
             UtxoRpcClientEnum::Electrum(electrum) => electrum.build_tx_idents(params).await,
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(_) => MmError::err(UtxoRpcError::Internal(
+                "QRC20 transfer history isn't supported by the compact filters client".to_owned(),
+            )),
         }
     }
 }
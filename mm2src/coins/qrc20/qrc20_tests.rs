@@ -510,7 +510,8 @@ fn test_transfer_details_by_hash() {
         .unwrap()
         .into(),
         kmd_rewards: None,
-        transaction_type: Default::default(),
+        spv_verified: None,
+        transaction_type: TransactionType::TokenTransfer(BytesJson(coin.contract_address.0.to_vec())),
     };
     assert_eq!(actual, expected);
 
@@ -534,7 +535,8 @@ fn test_transfer_details_by_hash() {
         .unwrap()
         .into(),
         kmd_rewards: None,
-        transaction_type: Default::default(),
+        spv_verified: None,
+        transaction_type: TransactionType::TokenTransfer(BytesJson(coin.contract_address.0.to_vec())),
     };
     assert_eq!(actual, expected);
 
@@ -558,7 +560,8 @@ fn test_transfer_details_by_hash() {
         .unwrap()
         .into(),
         kmd_rewards: None,
-        transaction_type: Default::default(),
+        spv_verified: None,
+        transaction_type: TransactionType::TokenTransfer(BytesJson(coin.contract_address.0.to_vec())),
     };
     assert_eq!(actual, expected);
 
@@ -582,7 +585,8 @@ fn test_transfer_details_by_hash() {
         .unwrap()
         .into(),
         kmd_rewards: None,
-        transaction_type: Default::default(),
+        spv_verified: None,
+        transaction_type: TransactionType::TokenTransfer(BytesJson(coin.contract_address.0.to_vec())),
     };
     assert_eq!(actual, expected);
 
@@ -606,7 +610,8 @@ fn test_transfer_details_by_hash() {
         .unwrap()
         .into(),
         kmd_rewards: None,
-        transaction_type: Default::default(),
+        spv_verified: None,
+        transaction_type: TransactionType::TokenTransfer(BytesJson(coin.contract_address.0.to_vec())),
     };
     assert_eq!(actual, expected);
     assert!(it.next().is_none());
@@ -2,6 +2,9 @@ use super::*;
 use crate::utxo::rpc_clients::{UtxoRpcError, UtxoRpcFut};
 use rpc::v1::types::H256;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::utxo::compact_filters::rpc_error;
+
 impl From<ethabi::Error> for UtxoRpcError {
     fn from(e: ethabi::Error) -> Self {
         // Currently, we use the `ethabi` crate to work with a smart contract ABI known at compile time.
@@ -383,6 +386,11 @@ impl Qrc20RpcOps for UtxoRpcClientEnum {
         match self {
             UtxoRpcClientEnum::Electrum(electrum) => electrum.blockchain_transaction_get_receipt(tx_hash),
             UtxoRpcClientEnum::Native(native) => native.get_transaction_receipt(tx_hash),
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(_) => Box::new(futures01::future::err(rpc_error(
+                "blockchain.transaction.get_receipt",
+                "QRC20 isn't supported by the compact filters client".to_owned(),
+            ))),
         }
     }
 
@@ -403,6 +411,11 @@ impl Qrc20RpcOps for UtxoRpcClientEnum {
                 UtxoRpcClientEnum::Electrum(electrum) => {
                     electrum.blockchain_contract_call(&contract_addr, params.into())
                 },
+                #[cfg(not(target_arch = "wasm32"))]
+                UtxoRpcClientEnum::CompactFilters(_) => Box::new(futures01::future::err(rpc_error(
+                    "blockchain.contract.call",
+                    "QRC20 isn't supported by the compact filters client".to_owned(),
+                ))),
             };
             let result = fut.compat().await?;
             let decoded = function.decode_output(&result.execution_result.output)?;
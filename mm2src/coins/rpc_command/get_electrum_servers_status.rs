@@ -0,0 +1,72 @@
+use crate::utxo::electrum_health::ElectrumServerStatus;
+use crate::utxo::rpc_clients::UtxoRpcClientEnum;
+use crate::utxo::UtxoCoinFields;
+use crate::{lp_coinfind_or_err, CoinFindError, MmCoinEnum};
+use common::{HttpStatusCode, StatusCode};
+use derive_more::Display;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+
+#[derive(Deserialize)]
+pub struct ElectrumServersStatusRequest {
+    coin: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ElectrumServersStatusResponse {
+    pub coin: String,
+    /// The servers in the order they're configured.
+    pub servers: Vec<ElectrumServerStatus>,
+}
+
+#[derive(Debug, Display, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum ElectrumServersStatusError {
+    #[display(fmt = "No such coin {}", coin)]
+    NoSuchCoin { coin: String },
+    #[display(fmt = "Coin {} is not activated with electrum servers", coin)]
+    NotElectrumCoin { coin: String },
+}
+
+impl HttpStatusCode for ElectrumServersStatusError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ElectrumServersStatusError::NoSuchCoin { .. } | ElectrumServersStatusError::NotElectrumCoin { .. } => {
+                StatusCode::BAD_REQUEST
+            },
+        }
+    }
+}
+
+impl From<CoinFindError> for ElectrumServersStatusError {
+    fn from(e: CoinFindError) -> Self {
+        match e {
+            CoinFindError::NoSuchCoin { coin } => ElectrumServersStatusError::NoSuchCoin { coin },
+        }
+    }
+}
+
+/// Returns the health scores of the electrum servers of the given UTXO coin.
+pub async fn get_electrum_servers_status(
+    ctx: MmArc,
+    req: ElectrumServersStatusRequest,
+) -> MmResult<ElectrumServersStatusResponse, ElectrumServersStatusError> {
+    let coin = lp_coinfind_or_err(&ctx, &req.coin).await?;
+    let utxo_fields: &UtxoCoinFields = match coin {
+        MmCoinEnum::UtxoCoin(ref coin) => coin.as_ref(),
+        MmCoinEnum::QtumCoin(ref coin) => coin.as_ref(),
+        MmCoinEnum::Qrc20Coin(ref coin) => coin.as_ref(),
+        MmCoinEnum::Bch(ref coin) => coin.as_ref(),
+        MmCoinEnum::SlpToken(ref coin) => coin.as_ref(),
+        _ => return MmError::err(ElectrumServersStatusError::NotElectrumCoin { coin: req.coin }),
+    };
+    let electrum = match utxo_fields.rpc_client {
+        UtxoRpcClientEnum::Electrum(ref electrum) => electrum,
+        _ => return MmError::err(ElectrumServersStatusError::NotElectrumCoin { coin: req.coin }),
+    };
+
+    Ok(ElectrumServersStatusResponse {
+        servers: electrum.servers_status().await,
+        coin: req.coin,
+    })
+}
@@ -5,16 +5,19 @@ use common::SuccessResponse;
 use crypto::hw_rpc_task::{HwRpcTaskAwaitingStatus, HwRpcTaskUserAction, HwRpcTaskUserActionRequest};
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
-use rpc_task::rpc_common::{InitRpcTaskResponse, RpcTaskStatusError, RpcTaskStatusRequest, RpcTaskUserActionError};
+use rpc_task::rpc_common::{CancelRpcTaskError, CancelRpcTaskRequest, InitRpcTaskResponse, RpcTaskStatusError,
+                           RpcTaskStatusRequest, RpcTaskUserActionError};
 use rpc_task::{RpcTask, RpcTaskHandle, RpcTaskManager, RpcTaskManagerShared, RpcTaskStatusAlias, RpcTaskTypes};
 
 pub type WithdrawAwaitingStatus = HwRpcTaskAwaitingStatus;
 pub type WithdrawUserAction = HwRpcTaskUserAction;
 pub type WithdrawStatusError = RpcTaskStatusError;
 pub type WithdrawUserActionError = RpcTaskUserActionError;
+pub type WithdrawCancelError = CancelRpcTaskError;
 pub type InitWithdrawResponse = InitRpcTaskResponse;
 pub type WithdrawStatusRequest = RpcTaskStatusRequest;
 pub type WithdrawUserActionRequest = HwRpcTaskUserActionRequest;
+pub type WithdrawCancelRequest = CancelRpcTaskRequest;
 pub type WithdrawTaskManager = RpcTaskManager<WithdrawTask>;
 pub type WithdrawTaskManagerShared = RpcTaskManagerShared<WithdrawTask>;
 pub type WithdrawTaskHandle = RpcTaskHandle<WithdrawTask>;
@@ -82,6 +85,19 @@ pub async fn withdraw_user_action(
     Ok(SuccessResponse::new())
 }
 
+pub async fn withdraw_cancel(
+    ctx: MmArc,
+    req: WithdrawCancelRequest,
+) -> Result<SuccessResponse, MmError<WithdrawCancelError>> {
+    let coins_ctx = CoinsContext::from_ctx(&ctx).map_to_mm(WithdrawCancelError::Internal)?;
+    let mut task_manager = coins_ctx
+        .withdraw_task_manager
+        .lock()
+        .map_to_mm(|e| WithdrawCancelError::Internal(e.to_string()))?;
+    task_manager.cancel_task(req.task_id)?;
+    Ok(SuccessResponse::new())
+}
+
 #[async_trait]
 pub trait InitWithdrawCoin {
     async fn init_withdraw(
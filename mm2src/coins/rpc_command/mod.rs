@@ -1,4 +1,5 @@
 pub mod account_balance;
+pub mod get_electrum_servers_status;
 pub mod hd_account_balance_rpc_error;
 pub mod init_create_account;
 pub mod init_scan_for_new_addresses;
@@ -39,6 +39,7 @@ use std::{convert::TryFrom,
 pub mod solana_common;
 #[cfg(test)] mod solana_common_tests;
 mod solana_decode_tx_helpers;
+mod solana_staking;
 #[cfg(test)] mod solana_tests;
 pub mod spl;
 #[cfg(test)] mod spl_tests;
@@ -229,6 +230,39 @@ pub struct SolanaFeeDetails {
     pub amount: BigDecimal,
 }
 
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SolanaDelegationRequest {
+    /// The vote account of the validator.
+    pub validator_address: String,
+    pub amount: BigDecimal,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SolanaStakeActivation {
+    Activating,
+    Active,
+    Deactivating,
+    Inactive,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SolanaStakeAccount {
+    pub address: String,
+    /// The vote account the stake is delegated to, `None` if the stake isn't delegated.
+    pub validator: Option<String>,
+    pub balance: BigDecimal,
+    pub active_stake: BigDecimal,
+    pub activation: SolanaStakeActivation,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SolanaStakingInfosDetails {
+    /// The total active stake.
+    pub amount: BigDecimal,
+    pub stake_accounts: Vec<SolanaStakeAccount>,
+}
+
 async fn withdraw_base_coin_impl(coin: SolanaCoin, req: WithdrawRequest) -> WithdrawResult {
     let (hash, fees) = coin.estimate_withdraw_fees().await?;
     let res = coin
@@ -264,6 +298,7 @@ async fn withdraw_base_coin_impl(coin: SolanaCoin, req: WithdrawRequest) -> With
         coin: coin.ticker.clone(),
         internal_id: vec![].into(),
         kmd_rewards: None,
+        spv_verified: None,
         transaction_type: TransactionType::StandardTransfer,
     })
 }
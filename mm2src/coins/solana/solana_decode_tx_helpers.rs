@@ -68,6 +68,7 @@ impl SolanaConfirmedTransaction {
                     coin: solana_coin.ticker.clone(),
                     internal_id: Default::default(),
                     kmd_rewards: None,
+                    spv_verified: None,
                     transaction_type: TransactionType::StandardTransfer,
                 };
                 transactions.push(tx);
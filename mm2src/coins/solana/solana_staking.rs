@@ -0,0 +1,487 @@
+//! Native SOL staking.
+//! The balance is delegated through the stake accounts derived from the wallet address with the `stake:<index>` seeds,
+//! so the wallet finds its stake accounts without scanning the whole stake program.
+
+use super::solana_common::{lamports_to_sol, sol_to_lamports};
+use super::{SolanaCoin, SolanaCommonOps, SolanaDelegationRequest, SolanaFeeDetails, SolanaStakeAccount,
+            SolanaStakeActivation, SolanaStakingInfosDetails};
+use crate::{DelegationError, DelegationFut, DelegationResult, MarketCoinOps, StakingDetails, StakingInfos,
+            StakingInfosError, StakingInfosFut, StakingInfosResult, StakingOps, StakingReward, StakingRewards,
+            StakingRewardsFut, StakingValidator, StakingValidators, StakingValidatorsFut, TransactionDetails,
+            TransactionType};
+use bincode::{deserialize, serialize};
+use common::{async_blocking, now_ms};
+use futures::compat::Future01CompatExt;
+use futures::{FutureExt, TryFutureExt};
+use mm2_err_handle::prelude::*;
+use mm2_number::BigDecimal;
+use solana_client::client_error::{ClientError, ClientErrorKind};
+use solana_client::rpc_response::{RpcVoteAccountInfo, StakeActivationState};
+use solana_sdk::instruction::Instruction;
+use solana_sdk::pubkey::{Pubkey, PubkeyError};
+use solana_sdk::signature::Signer;
+use solana_sdk::stake::instruction as stake_instruction;
+use solana_sdk::stake::program as stake_program;
+use solana_sdk::stake::state::{Authorized, Lockup, StakeState};
+use solana_sdk::transaction::Transaction;
+use std::str::FromStr;
+
+/// The max number of the stake accounts of the wallet.
+/// A new stake account is created on every delegation and closed once its balance is withdrawn.
+const MAX_STAKE_ACCOUNTS: u32 = 16;
+
+impl From<ClientError> for DelegationError {
+    fn from(e: ClientError) -> Self {
+        match e.kind {
+            ClientErrorKind::Io(e) => DelegationError::Transport(e.to_string()),
+            ClientErrorKind::Reqwest(e) => DelegationError::Transport(e.to_string()),
+            ClientErrorKind::RpcError(e) => DelegationError::Transport(format!("{:?}", e)),
+            ClientErrorKind::SerdeJson(e) => DelegationError::Transport(e.to_string()),
+            ClientErrorKind::Custom(e) => DelegationError::InternalError(e),
+            ClientErrorKind::SigningError(_)
+            | ClientErrorKind::TransactionError(_)
+            | ClientErrorKind::FaucetError(_) => DelegationError::InternalError("not_reacheable".to_string()),
+        }
+    }
+}
+
+impl From<ClientError> for StakingInfosError {
+    fn from(e: ClientError) -> Self {
+        match e.kind {
+            ClientErrorKind::Io(e) => StakingInfosError::Transport(e.to_string()),
+            ClientErrorKind::Reqwest(e) => StakingInfosError::Transport(e.to_string()),
+            ClientErrorKind::RpcError(e) => StakingInfosError::Transport(format!("{:?}", e)),
+            ClientErrorKind::SerdeJson(e) => StakingInfosError::Transport(e.to_string()),
+            ClientErrorKind::Custom(e) => StakingInfosError::Internal(e),
+            ClientErrorKind::SigningError(_)
+            | ClientErrorKind::TransactionError(_)
+            | ClientErrorKind::FaucetError(_) => StakingInfosError::Internal("not_reacheable".to_string()),
+        }
+    }
+}
+
+impl From<PubkeyError> for StakingInfosError {
+    fn from(e: PubkeyError) -> Self { StakingInfosError::Internal(e.to_string()) }
+}
+
+impl From<StakeActivationState> for SolanaStakeActivation {
+    fn from(state: StakeActivationState) -> Self {
+        match state {
+            StakeActivationState::Activating => SolanaStakeActivation::Activating,
+            StakeActivationState::Active => SolanaStakeActivation::Active,
+            StakeActivationState::Deactivating => SolanaStakeActivation::Deactivating,
+            StakeActivationState::Inactive => SolanaStakeActivation::Inactive,
+        }
+    }
+}
+
+struct StakeAccount {
+    address: Pubkey,
+    lamports: u64,
+    state: StakeState,
+}
+
+impl StakeAccount {
+    fn validator(&self) -> Option<Pubkey> { self.state.delegation().map(|delegation| delegation.voter_pubkey) }
+
+    /// Whether the stake is delegated to the given validator, any delegated stake matches if the validator isn't set.
+    fn is_delegated_to(&self, validator: Option<&Pubkey>) -> bool {
+        match (self.validator(), validator) {
+            (Some(actual), Some(expected)) => actual == *expected,
+            (Some(_), None) => true,
+            (None, _) => false,
+        }
+    }
+}
+
+fn stake_account_seed(index: u32) -> String { format!("stake:{}", index) }
+
+fn validator_from_str(validator: Option<String>) -> Result<Option<Pubkey>, MmError<DelegationError>> {
+    validator
+        .map(|validator| Pubkey::from_str(&validator))
+        .transpose()
+        .map_to_mm(|e| DelegationError::AddressError(format!("{:?}", e)))
+}
+
+impl StakingOps for SolanaCoin {
+    fn validators(&self) -> StakingValidatorsFut {
+        let coin = self.clone();
+        let fut = async move { coin.validators_impl().await };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn delegate(&self, details: StakingDetails) -> DelegationFut {
+        let coin = self.clone();
+        let fut = async move {
+            match details {
+                StakingDetails::Solana(request) => coin.delegate_impl(request).await,
+                StakingDetails::Qtum(_) => MmError::err(DelegationError::InvalidStakingDetails(format!(
+                    "{} expects the 'Solana' staking details",
+                    coin.ticker()
+                ))),
+            }
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn undelegate(&self, validator: Option<String>) -> DelegationFut {
+        let coin = self.clone();
+        let fut = async move { coin.undelegate_impl(validator).await };
+        Box::new(fut.boxed().compat())
+    }
+
+    /// Solana compounds the rewards into the delegated stake at the epoch boundaries, so they can't be claimed alone.
+    /// Instead, the whole balance of the fully deactivated stake accounts is withdrawn: the principal and the rewards.
+    /// The transaction is of the [`TransactionType::WithdrawStake`] type.
+    fn claim_rewards(&self, validator: Option<String>) -> DelegationFut {
+        let coin = self.clone();
+        let fut = async move { coin.withdraw_inactive_stake_impl(validator).await };
+        Box::new(fut.boxed().compat())
+    }
+
+    /// Returns the rewards credited to the delegated stake accounts at the last epoch only, with the epoch set.
+    /// The rewards of the earlier epochs are compounded into the stake and aren't accounted separately.
+    fn pending_rewards(&self) -> StakingRewardsFut {
+        let coin = self.clone();
+        let fut = async move { coin.last_epoch_rewards_impl().await };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn staking_infos(&self) -> StakingInfosFut {
+        let coin = self.clone();
+        let fut = async move { coin.staking_infos_impl().await };
+        Box::new(fut.boxed().compat())
+    }
+}
+
+impl SolanaCoin {
+    fn stake_account_address(&self, index: u32) -> Result<Pubkey, PubkeyError> {
+        Pubkey::create_with_seed(
+            &self.key_pair.pubkey(),
+            &stake_account_seed(index),
+            &stake_program::id(),
+        )
+    }
+
+    /// Returns the stake accounts of the wallet by their seed indexes, `None` if the account with the index doesn't exist.
+    async fn stake_accounts(&self) -> Result<Vec<Option<StakeAccount>>, MmError<StakingInfosError>> {
+        let addresses = (0..MAX_STAKE_ACCOUNTS)
+            .map(|index| self.stake_account_address(index))
+            .collect::<Result<Vec<_>, _>>()?;
+        let accounts = async_blocking({
+            let coin = self.clone();
+            let addresses = addresses.clone();
+            move || coin.rpc().get_multiple_accounts(&addresses)
+        })
+        .await?;
+
+        addresses
+            .into_iter()
+            .zip(accounts)
+            .map(|(address, account)| {
+                let account = match account {
+                    Some(account) => account,
+                    None => return Ok(None),
+                };
+                let state: StakeState =
+                    deserialize(&account.data).map_to_mm(|e| StakingInfosError::Internal(e.to_string()))?;
+                Ok(Some(StakeAccount {
+                    address,
+                    lamports: account.lamports,
+                    state,
+                }))
+            })
+            .collect()
+    }
+
+    /// Returns the activation state of the stake account and its active stake in lamports.
+    async fn stake_activation(
+        &self,
+        account: &StakeAccount,
+    ) -> Result<(SolanaStakeActivation, u64), MmError<StakingInfosError>> {
+        // The activation of the stake that isn't delegated can't be requested.
+        if account.state.delegation().is_none() {
+            return Ok((SolanaStakeActivation::Inactive, 0));
+        }
+        let activation = async_blocking({
+            let coin = self.clone();
+            let address = account.address;
+            move || coin.rpc().get_stake_activation(address, None)
+        })
+        .await?;
+        Ok((activation.state.into(), activation.active))
+    }
+
+    async fn validators_impl(&self) -> Result<StakingValidators, MmError<StakingInfosError>> {
+        let vote_accounts = async_blocking({
+            let coin = self.clone();
+            move || coin.rpc().get_vote_accounts()
+        })
+        .await?;
+
+        let to_validator = |info: RpcVoteAccountInfo, is_active: bool| StakingValidator {
+            address: info.vote_pubkey,
+            commission: BigDecimal::from(info.commission),
+            total_stake: lamports_to_sol(info.activated_stake),
+            is_active,
+        };
+        let current = vote_accounts.current.into_iter().map(|info| to_validator(info, true));
+        let delinquent = vote_accounts
+            .delinquent
+            .into_iter()
+            .map(|info| to_validator(info, false));
+        Ok(StakingValidators {
+            validators: current.chain(delinquent).collect(),
+        })
+    }
+
+    async fn staking_infos_impl(&self) -> StakingInfosResult {
+        let mut amount = BigDecimal::from(0);
+        let mut stake_accounts = Vec::new();
+        for account in self.stake_accounts().await?.into_iter().flatten() {
+            let (activation, active_lamports) = self.stake_activation(&account).await?;
+            let active_stake = lamports_to_sol(active_lamports);
+            amount += &active_stake;
+            stake_accounts.push(SolanaStakeAccount {
+                address: account.address.to_string(),
+                validator: account.validator().map(|validator| validator.to_string()),
+                balance: lamports_to_sol(account.lamports),
+                active_stake,
+                activation,
+            });
+        }
+        Ok(StakingInfos {
+            staking_infos_details: SolanaStakingInfosDetails { amount, stake_accounts }.into(),
+        })
+    }
+
+    async fn last_epoch_rewards_impl(&self) -> Result<StakingRewards, MmError<StakingInfosError>> {
+        let delegated: Vec<_> = self
+            .stake_accounts()
+            .await?
+            .into_iter()
+            .flatten()
+            .filter_map(|account| account.validator().map(|validator| (account.address, validator)))
+            .collect();
+        if delegated.is_empty() {
+            return Ok(StakingRewards::new(Vec::new()));
+        }
+
+        let addresses: Vec<_> = delegated.iter().map(|(address, _)| *address).collect();
+        let inflation_rewards = async_blocking({
+            let coin = self.clone();
+            move || coin.rpc().get_inflation_reward(&addresses, None)
+        })
+        .await?;
+
+        let rewards = delegated
+            .into_iter()
+            .zip(inflation_rewards)
+            .filter_map(|((_, validator), reward)| {
+                reward.map(|reward| StakingReward {
+                    validator: validator.to_string(),
+                    amount: lamports_to_sol(reward.amount),
+                    epoch: Some(reward.epoch),
+                })
+            })
+            .collect();
+        Ok(StakingRewards::new(rewards))
+    }
+
+    async fn delegate_impl(&self, request: SolanaDelegationRequest) -> DelegationResult {
+        let vote_pubkey = Pubkey::from_str(&request.validator_address)
+            .map_to_mm(|e| DelegationError::AddressError(format!("{:?}", e)))?;
+        let stake_lamports =
+            sol_to_lamports(&request.amount).mm_err(|e| DelegationError::InternalError(e.to_string()))?;
+        if stake_lamports == 0 {
+            return MmError::err(DelegationError::AmountTooLow {
+                amount: request.amount,
+                threshold: lamports_to_sol(1),
+            });
+        }
+
+        let index = self
+            .stake_accounts()
+            .await?
+            .iter()
+            .position(Option::is_none)
+            .or_mm_err(|| DelegationError::DelegationOpsNotSupported {
+                reason: format!("The wallet can't have more than {} stake accounts", MAX_STAKE_ACCOUNTS),
+            })? as u32;
+        let stake_address = self
+            .stake_account_address(index)
+            .map_to_mm(|e| DelegationError::InternalError(e.to_string()))?;
+        let rent_exempt_reserve = async_blocking({
+            let coin = self.clone();
+            move || coin.rpc().get_minimum_balance_for_rent_exemption(StakeState::size_of())
+        })
+        .await?;
+
+        let my_pubkey = self.key_pair.pubkey();
+        let instructions = stake_instruction::create_account_with_seed_and_delegate_stake(
+            &my_pubkey,
+            &stake_address,
+            &my_pubkey,
+            &stake_account_seed(index),
+            &vote_pubkey,
+            &Authorized::auto(&my_pubkey),
+            &Lockup::default(),
+            stake_lamports + rent_exempt_reserve,
+        );
+        let (tx, fee) = self.sign_staking_transaction(instructions).await?;
+        let spent_by_me = lamports_to_sol(stake_lamports + rent_exempt_reserve + fee);
+        self.check_staking_balance(&spent_by_me).await?;
+        self.staking_tx_details(
+            tx,
+            fee,
+            vec![stake_address.to_string()],
+            spent_by_me,
+            BigDecimal::from(0),
+            TransactionType::StakingDelegation,
+        )
+    }
+
+    async fn undelegate_impl(&self, validator: Option<String>) -> DelegationResult {
+        let validator = validator_from_str(validator)?;
+        let my_pubkey = self.key_pair.pubkey();
+        let mut instructions = Vec::new();
+        let mut to = Vec::new();
+        for account in self.stake_accounts().await?.into_iter().flatten() {
+            let is_deactivated = account
+                .state
+                .delegation()
+                .map_or(true, |delegation| delegation.deactivation_epoch != u64::MAX);
+            if is_deactivated || !account.is_delegated_to(validator.as_ref()) {
+                continue;
+            }
+            instructions.push(stake_instruction::deactivate_stake(&account.address, &my_pubkey));
+            to.push(account.address.to_string());
+        }
+        if instructions.is_empty() {
+            return MmError::err(DelegationError::NothingToUndelegate);
+        }
+
+        let (tx, fee) = self.sign_staking_transaction(instructions).await?;
+        let spent_by_me = lamports_to_sol(fee);
+        self.check_staking_balance(&spent_by_me).await?;
+        self.staking_tx_details(
+            tx,
+            fee,
+            to,
+            spent_by_me,
+            BigDecimal::from(0),
+            TransactionType::RemoveDelegation,
+        )
+    }
+
+    /// Withdraws the whole balance of the fully deactivated stake accounts, closing them.
+    async fn withdraw_inactive_stake_impl(&self, validator: Option<String>) -> DelegationResult {
+        let validator = validator_from_str(validator)?;
+        let my_pubkey = self.key_pair.pubkey();
+        let mut instructions = Vec::new();
+        let mut to = Vec::new();
+        let mut withdrawn_lamports = 0;
+        for account in self.stake_accounts().await?.into_iter().flatten() {
+            // The stake that has never been delegated is withdrawn only if the validator isn't specified.
+            if validator.is_some() && !account.is_delegated_to(validator.as_ref()) {
+                continue;
+            }
+            let (activation, _) = self.stake_activation(&account).await?;
+            if activation != SolanaStakeActivation::Inactive {
+                continue;
+            }
+            instructions.push(stake_instruction::withdraw(
+                &account.address,
+                &my_pubkey,
+                &my_pubkey,
+                account.lamports,
+                None,
+            ));
+            to.push(account.address.to_string());
+            withdrawn_lamports += account.lamports;
+        }
+        if instructions.is_empty() {
+            return MmError::err(DelegationError::NothingToWithdraw);
+        }
+
+        let (tx, fee) = self.sign_staking_transaction(instructions).await?;
+        let spent_by_me = lamports_to_sol(fee);
+        self.check_staking_balance(&spent_by_me).await?;
+        self.staking_tx_details(
+            tx,
+            fee,
+            to,
+            spent_by_me,
+            lamports_to_sol(withdrawn_lamports),
+            TransactionType::WithdrawStake,
+        )
+    }
+
+    /// Signs the transaction paid by the wallet and returns it with its fee in lamports.
+    async fn sign_staking_transaction(
+        &self,
+        instructions: Vec<Instruction>,
+    ) -> Result<(Transaction, u64), MmError<DelegationError>> {
+        let coin = self.clone();
+        async_blocking(move || {
+            let hash = coin.rpc().get_latest_blockhash()?;
+            let my_pubkey = coin.key_pair.pubkey();
+            let tx = Transaction::new_signed_with_payer(&instructions, Some(&my_pubkey), &[&coin.key_pair], hash);
+            let fee = coin.rpc().get_fee_for_message(tx.message())?;
+            Ok((tx, fee))
+        })
+        .await
+    }
+
+    async fn check_staking_balance(&self, required: &BigDecimal) -> Result<(), MmError<DelegationError>> {
+        let available = self.my_balance().compat().await?.spendable;
+        if &available < required {
+            return MmError::err(DelegationError::NotSufficientBalance {
+                coin: self.ticker().to_owned(),
+                available,
+                required: required.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    fn staking_tx_details(
+        &self,
+        tx: Transaction,
+        fee: u64,
+        to: Vec<String>,
+        spent_by_me: BigDecimal,
+        received_by_me: BigDecimal,
+        transaction_type: TransactionType,
+    ) -> DelegationResult {
+        let tx_hex = serialize(&tx).map_to_mm(|e| DelegationError::InternalError(e.to_string()))?;
+        let total_amount = if received_by_me > spent_by_me {
+            received_by_me.clone()
+        } else {
+            spent_by_me.clone()
+        };
+        Ok(TransactionDetails {
+            tx_hex: tx_hex.into(),
+            tx_hash: tx.signatures[0].to_string(),
+            from: vec![self.my_address.clone()],
+            to,
+            total_amount,
+            my_balance_change: &received_by_me - &spent_by_me,
+            spent_by_me,
+            received_by_me,
+            block_height: 0,
+            timestamp: now_ms() / 1000,
+            fee_details: Some(
+                SolanaFeeDetails {
+                    amount: lamports_to_sol(fee),
+                }
+                .into(),
+            ),
+            coin: self.ticker.clone(),
+            internal_id: vec![].into(),
+            kmd_rewards: None,
+            spv_verified: None,
+            transaction_type,
+        })
+    }
+}
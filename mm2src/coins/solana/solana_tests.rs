@@ -2,7 +2,8 @@ use super::*;
 use crate::solana::solana_common_tests::{generate_key_pair_from_iguana_seed, generate_key_pair_from_seed,
                                          solana_coin_for_test, SolanaNet};
 use crate::solana::solana_decode_tx_helpers::SolanaConfirmedTransaction;
-use crate::MarketCoinOps;
+use crate::utxo::qtum::QtumDelegationRequest;
+use crate::{DelegationError, MarketCoinOps, StakingDetails, StakingInfosDetails, StakingOps};
 use base58::ToBase58;
 use common::{block_on, Future01CompatExt};
 use solana_client::rpc_request::TokenAccountsFilter;
@@ -326,3 +327,43 @@ fn solana_test_tx_history() {
     }
     println!("{}", serde_json::to_string(&history).unwrap());
 }
+
+#[test]
+#[cfg(not(target_arch = "wasm32"))]
+fn solana_staking_validators_and_infos() {
+    let passphrase = "federal stay trigger hour exist success game vapor become comfort action phone bright ill target wild nasty crumble dune close rare fabric hen iron".to_string();
+    let (_, sol_coin) = solana_coin_for_test(passphrase.clone(), SolanaNet::Devnet);
+    let validators = block_on(sol_coin.validators().compat()).unwrap();
+    assert!(validators.validators.iter().any(|validator| validator.is_active));
+
+    let infos = block_on(sol_coin.staking_infos().compat()).unwrap();
+    match infos.staking_infos_details {
+        StakingInfosDetails::Solana(details) => assert!(details.stake_accounts.len() <= 16),
+        details => panic!("Unexpected staking infos: {:?}", details),
+    }
+}
+
+#[test]
+#[cfg(not(target_arch = "wasm32"))]
+fn solana_delegate_invalid_details() {
+    let passphrase = "federal stay trigger hour exist success game vapor become comfort action phone bright ill target wild nasty crumble dune close rare fabric hen iron".to_string();
+    let (_, sol_coin) = solana_coin_for_test(passphrase.clone(), SolanaNet::Devnet);
+
+    let request = SolanaDelegationRequest {
+        validator_address: "invalid_validator".to_string(),
+        amount: BigDecimal::from(1),
+    };
+    let err = block_on(sol_coin.delegate(StakingDetails::Solana(request)).compat())
+        .unwrap_err()
+        .into_inner();
+    assert!(matches!(err, DelegationError::AddressError(_)));
+
+    let request = QtumDelegationRequest {
+        address: "qcyBHeSct7Wr4mAw18iuQ1zW5mMFYmtmBE".to_string(),
+        fee: None,
+    };
+    let err = block_on(sol_coin.delegate(StakingDetails::Qtum(request)).compat())
+        .unwrap_err()
+        .into_inner();
+    assert!(matches!(err, DelegationError::InvalidStakingDetails(_)));
+}
@@ -148,6 +148,7 @@ async fn withdraw_spl_token_impl(coin: SplToken, req: WithdrawRequest) -> Withdr
         coin: coin.conf.ticker.clone(),
         internal_id: vec![].into(),
         kmd_rewards: None,
+        spv_verified: None,
         transaction_type: TransactionType::StandardTransfer,
     })
 }
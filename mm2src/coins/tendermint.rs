@@ -0,0 +1,1644 @@
+//! The Tendermint (Cosmos SDK) platform coin, e.g. IRIS.
+//! The transactions are built and signed locally and broadcasted via the Tendermint RPC,
+//! the atomic swaps rely on the IRISmod HTLC module: https://www.irisnet.org/docs/features/htlc.html
+
+use super::{CoinBalance, HistorySyncState, MarketCoinOps, MmCoin, SwapOps, TradeFee, Transaction, TransactionEnum};
+use crate::my_tx_history_v2::{load_history_from_storage, CoinWithTxHistoryV2, MyTxHistoryErrorV2};
+use crate::tx_history_storage::{GetTxHistoryFilters, WalletId};
+use crate::utxo::sat_from_big_decimal;
+use crate::utxo::utxo_common::big_decimal_from_sat_unsigned;
+use crate::{BalanceError, BalanceFut, CanRefundHtlc, FeeApproxStage, FoundSwapTxSpend, NegotiateSwapContractAddrErr,
+            NumConversError, RawTransactionError, RawTransactionFut, RawTransactionRequest, RawTransactionRes,
+            SearchForSwapTxSpendInput, SignatureError, SignatureResult, TradePreimageError, TradePreimageFut,
+            TradePreimageResult, TradePreimageValue, TransactionDetails, TransactionErr, TransactionFut,
+            TransactionType, TxFeeDetails, TxHistoryFut, UnexpectedDerivationMethod, ValidateAddressResult,
+            ValidatePaymentInput, VerificationError, VerificationResult, WithdrawError, WithdrawFee, WithdrawFut,
+            WithdrawRequest};
+use ::rpc::v1::types::Bytes as BytesJson;
+use async_trait::async_trait;
+use bech32::{FromBase32, ToBase32, Variant};
+use bitcrypto::{dhash160, sha256};
+use common::executor::Timer;
+use common::log::warn;
+use common::{now_ms, Future01CompatExt};
+use crypto::privkey::key_pair_from_secret;
+use derive_more::Display;
+use futures::{FutureExt, TryFutureExt};
+use futures01::Future;
+use keys::KeyPair;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::bigdecimal::{BigDecimal, Zero};
+use mm2_number::MmNumber;
+use prost::{DecodeError, Message};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::fmt;
+use std::ops::Deref;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering as AtomicOrdering};
+use std::sync::{Arc, Mutex};
+
+pub mod rpc;
+#[allow(clippy::all)]
+#[rustfmt::skip]
+#[path = "tendermint/tendermint_pb.rs"]
+mod tendermint_pb;
+#[cfg(test)] mod tendermint_tests;
+pub mod tendermint_token;
+mod tendermint_tx_history;
+
+use self::rpc::{TendermintRpcClient, TendermintRpcError, TxResponse};
+use tendermint_pb::{mode_info, Any, AuthInfo, BaseAccount, Coin, Fee, Htlc, HtlcState, ModeInfo, MsgClaimHtlc,
+                    MsgCreateHtlc, MsgSend, PubKey, QueryAccountRequest, QueryAccountResponse, QueryBalanceRequest,
+                    QueryBalanceResponse, QueryHtlcRequest, QueryHtlcResponse, SignDoc, SignerInfo, SimulateRequest,
+                    SimulateResponse, TxBody, TxRaw};
+pub use tendermint_token::{TendermintToken, TendermintTokenProtocolInfo};
+pub use tendermint_tx_history::tendermint_history_loop;
+
+const ACCOUNT_QUERY_PATH: &str = "/cosmos.auth.v1beta1.Query/Account";
+const BALANCE_QUERY_PATH: &str = "/cosmos.bank.v1beta1.Query/Balance";
+const SIMULATE_PATH: &str = "/cosmos.tx.v1beta1.Service/Simulate";
+const HTLC_QUERY_PATH: &str = "/irismod.htlc.Query/HTLC";
+
+const BASE_ACCOUNT_TYPE_URL: &str = "/cosmos.auth.v1beta1.BaseAccount";
+const PUBKEY_TYPE_URL: &str = "/cosmos.crypto.secp256k1.PubKey";
+const MSG_SEND_TYPE_URL: &str = "/cosmos.bank.v1beta1.MsgSend";
+const MSG_CREATE_HTLC_TYPE_URL: &str = "/irismod.htlc.MsgCreateHTLC";
+const MSG_CLAIM_HTLC_TYPE_URL: &str = "/irismod.htlc.MsgClaimHTLC";
+
+/// cosmos.tx.signing.v1beta1.SignMode.SIGN_MODE_DIRECT
+const SIGN_MODE_DIRECT: i32 = 1;
+
+const DEFAULT_GAS_PRICE: f64 = 0.25;
+const DEFAULT_AVG_BLOCKTIME: u64 = 5;
+/// The simulated gas is multiplied by this value to cover the gas usage deviations.
+const GAS_LIMIT_MULTIPLIER: f64 = 1.5;
+
+/// The gas limits used to estimate the trade fees without simulating the transactions.
+const SEND_GAS_LIMIT_ESTIMATE: u64 = 125_000;
+const HTLC_CREATE_GAS_LIMIT_ESTIMATE: u64 = 200_000;
+const HTLC_CLAIM_GAS_LIMIT_ESTIMATE: u64 = 200_000;
+
+/// The time lock bounds (in blocks) allowed by the IRISmod HTLC module.
+const HTLC_MIN_TIME_LOCK: u64 = 50;
+const HTLC_MAX_TIME_LOCK: u64 = 25480;
+/// The HTLC expiration height may deviate from the swap time lock by this number of blocks
+/// since the time lock is converted to blocks using the average block time.
+const HTLC_EXPIRATION_DRIFT_BLOCKS: u64 = 10;
+/// How often the HTLC state is polled by the swap operations, in seconds.
+const HTLC_CHECK_INTERVAL: f64 = 10.;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TendermintProtocolInfo {
+    decimals: u8,
+    denom: String,
+    /// The bech32 account address prefix, e.g. `iaa` for IRISnet.
+    pub account_prefix: String,
+    chain_id: String,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TendermintActivationParams {
+    rpc_urls: Vec<String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct TendermintFeeDetails {
+    pub coin: String,
+    pub amount: BigDecimal,
+    pub gas_limit: u64,
+}
+
+/// The signed Cosmos SDK transaction.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CosmosTransaction {
+    pub data: TxRaw,
+}
+
+impl Transaction for CosmosTransaction {
+    fn tx_hex(&self) -> Vec<u8> { self.data.encode_to_vec() }
+
+    fn tx_hash(&self) -> BytesJson { sha256(&self.tx_hex()).to_vec().into() }
+}
+
+#[derive(Debug, Display)]
+pub enum TendermintInitError {
+    #[display(fmt = "'rpc_urls' can't be empty")]
+    EmptyRpcUrls,
+    #[display(fmt = "Invalid private key: {}", _0)]
+    InvalidPrivKey(String),
+    #[display(fmt = "Error on account address generation: {}", _0)]
+    CouldNotGenerateAccountId(String),
+    #[display(fmt = "Invalid coin config: {}", _0)]
+    InvalidConf(String),
+}
+
+#[derive(Debug, Display)]
+pub enum TendermintCoinRpcError {
+    Rpc(TendermintRpcError),
+    #[display(fmt = "Protobuf decoding error: {}", _0)]
+    Prost(DecodeError),
+    #[display(fmt = "Invalid response: {}", _0)]
+    InvalidResponse(String),
+    #[display(fmt = "Internal error: {}", _0)]
+    Internal(String),
+}
+
+impl From<TendermintRpcError> for TendermintCoinRpcError {
+    fn from(e: TendermintRpcError) -> Self { TendermintCoinRpcError::Rpc(e) }
+}
+
+impl From<DecodeError> for TendermintCoinRpcError {
+    fn from(e: DecodeError) -> Self { TendermintCoinRpcError::Prost(e) }
+}
+
+impl From<NumConversError> for TendermintCoinRpcError {
+    fn from(e: NumConversError) -> Self { TendermintCoinRpcError::Internal(e.to_string()) }
+}
+
+impl From<TendermintCoinRpcError> for BalanceError {
+    fn from(e: TendermintCoinRpcError) -> Self {
+        match e {
+            TendermintCoinRpcError::Rpc(TendermintRpcError::Transport(e)) => BalanceError::Transport(e),
+            TendermintCoinRpcError::Rpc(e) => BalanceError::InvalidResponse(e.to_string()),
+            TendermintCoinRpcError::Prost(e) => BalanceError::InvalidResponse(e.to_string()),
+            TendermintCoinRpcError::InvalidResponse(e) => BalanceError::InvalidResponse(e),
+            TendermintCoinRpcError::Internal(e) => BalanceError::Internal(e),
+        }
+    }
+}
+
+impl From<TendermintCoinRpcError> for WithdrawError {
+    fn from(e: TendermintCoinRpcError) -> Self {
+        match e {
+            TendermintCoinRpcError::Rpc(TendermintRpcError::Transport(e)) => WithdrawError::Transport(e),
+            e => WithdrawError::InternalError(e.to_string()),
+        }
+    }
+}
+
+impl From<TendermintCoinRpcError> for TradePreimageError {
+    fn from(e: TendermintCoinRpcError) -> Self {
+        match e {
+            TendermintCoinRpcError::Rpc(TendermintRpcError::Transport(e)) => TradePreimageError::Transport(e),
+            e => TradePreimageError::InternalError(e.to_string()),
+        }
+    }
+}
+
+/// Generates the bech32 account address from the compressed secp256k1 public key.
+pub fn account_id_from_pubkey(prefix: &str, pubkey: &[u8]) -> Result<String, String> {
+    bech32::encode(prefix, dhash160(pubkey).as_slice().to_base32(), Variant::Bech32).map_err(|e| ERRL!("{}", e))
+}
+
+/// Generates the bech32 account address from the hex encoded compressed secp256k1 public key.
+pub fn account_id_from_pubkey_hex(prefix: &str, pubkey: &str) -> Result<String, String> {
+    let pubkey = try_s!(hex::decode(pubkey));
+    if pubkey.len() != 33 {
+        return ERR!("Expected a compressed public key, found {} bytes", pubkey.len());
+    }
+    account_id_from_pubkey(prefix, &pubkey)
+}
+
+/// Decodes the bech32 account address into the account id bytes.
+fn account_id_bytes(address: &str) -> Result<Vec<u8>, String> {
+    let (_prefix, data, _variant) = try_s!(bech32::decode(address));
+    let bytes = try_s!(Vec::<u8>::from_base32(&data));
+    if bytes.len() != 20 {
+        return ERR!("Invalid account id length {}", bytes.len());
+    }
+    Ok(bytes)
+}
+
+/// Formats the coins the same way as the Cosmos SDK `Coins.String()`, e.g. `100uatom,5uiris`.
+fn coins_to_string(coins: &[Coin]) -> String {
+    let mut coins: Vec<_> = coins.iter().collect();
+    coins.sort_by(|a, b| a.denom.cmp(&b.denom));
+    coins
+        .iter()
+        .map(|coin| format!("{}{}", coin.amount, coin.denom))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+/// Parses the coins string of the `transfer` event, e.g. `100uiris,5ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2`.
+/// Returns the `(denom, amount)` pairs, the amounts are in the denom units.
+fn parse_coins(coins: &str) -> Result<Vec<(String, BigDecimal)>, String> {
+    coins
+        .split(',')
+        .filter(|coin| !coin.is_empty())
+        .map(|coin| {
+            let denom_pos = coin
+                .find(|c: char| !c.is_ascii_digit())
+                .ok_or_else(|| ERRL!("No denom in '{}'", coin))?;
+            let amount = try_s!(BigDecimal::from_str(&coin[..denom_pos]));
+            Ok((coin[denom_pos..].to_owned(), amount))
+        })
+        .collect()
+}
+
+fn decode_any<M: Message + Default>(any: &Any) -> Result<M, DecodeError> { M::decode(any.value.as_slice()) }
+
+fn encode_any<M: Message>(type_url: &str, msg: &M) -> Any {
+    Any {
+        type_url: type_url.to_owned(),
+        value: msg.encode_to_vec(),
+    }
+}
+
+/// Returns the first message of the `type_url` type within the transaction.
+fn find_tx_msg<M: Message + Default>(tx: &TxRaw, type_url: &str) -> Result<Option<M>, DecodeError> {
+    let body = TxBody::decode(tx.body_bytes.as_slice())?;
+    body.messages
+        .iter()
+        .find(|msg| msg.type_url == type_url)
+        .map(decode_any)
+        .transpose()
+}
+
+fn create_htlc_msg_from_tx_bytes(tx: &[u8]) -> Result<MsgCreateHtlc, String> {
+    let tx = try_s!(TxRaw::decode(tx));
+    match try_s!(find_tx_msg(&tx, MSG_CREATE_HTLC_TYPE_URL)) {
+        Some(msg) => Ok(msg),
+        None => ERR!("The transaction doesn't contain {}", MSG_CREATE_HTLC_TYPE_URL),
+    }
+}
+
+/// Computes the HTLC id the same way as IRISmod does: `sha256(hash_lock ‖ sender ‖ to ‖ amount)`.
+fn htlc_id(msg: &MsgCreateHtlc) -> Result<String, String> {
+    let mut preimage = try_s!(hex::decode(&msg.hash_lock));
+    preimage.extend(try_s!(account_id_bytes(&msg.sender)));
+    preimage.extend(try_s!(account_id_bytes(&msg.to)));
+    preimage.extend(coins_to_string(&msg.amount).into_bytes());
+    Ok(hex::encode_upper(sha256(&preimage).as_slice()))
+}
+
+/// The swap secret can't be used to claim the HTLC with the `timestamp` set since its hash lock is
+/// `sha256(secret ‖ timestamp)` then, and the HTLC with the `transfer` flag is an HTLT to another chain.
+fn check_htlc_claimable_by_secret(timestamp: u64, transfer: bool) -> Result<(), String> {
+    if timestamp != 0 {
+        return ERR!("HTLC timestamp {} is not allowed", timestamp);
+    }
+    if transfer {
+        return ERR!("HTLC transfer to another chain is not allowed");
+    }
+    Ok(())
+}
+
+/// Validates the HTLC creation message of the other side of the swap.
+fn validate_create_htlc_msg(
+    msg: &MsgCreateHtlc,
+    expected_sender: &str,
+    expected_to: &str,
+    secret_hash: &[u8],
+    expected_amount: &[Coin],
+) -> Result<(), String> {
+    if msg.sender != expected_sender {
+        return ERR!("Invalid HTLC sender {}, expected {}", msg.sender, expected_sender);
+    }
+    if msg.to != expected_to {
+        return ERR!("Invalid HTLC receiver {}, expected {}", msg.to, expected_to);
+    }
+    if try_s!(hex::decode(&msg.hash_lock)) != secret_hash {
+        return ERR!(
+            "Invalid HTLC hash lock {}, expected {}",
+            msg.hash_lock,
+            hex::encode(secret_hash)
+        );
+    }
+    if msg.amount != expected_amount {
+        return ERR!("Invalid HTLC amount {:?}, expected {:?}", msg.amount, expected_amount);
+    }
+    check_htlc_claimable_by_secret(msg.timestamp, msg.transfer)
+}
+
+/// Validates the HTLC stored on chain against the already validated creation message.
+fn validate_htlc_state(htlc: &Htlc, msg: &MsgCreateHtlc) -> Result<(), String> {
+    if htlc.state != HtlcState::Open as i32 {
+        return ERR!("HTLC {} is not open, state {}", htlc.id, htlc.state);
+    }
+    if htlc.sender != msg.sender || htlc.to != msg.to || htlc.amount != msg.amount {
+        return ERR!("HTLC {} doesn't match the payment transaction", htlc.id);
+    }
+    if !htlc.hash_lock.eq_ignore_ascii_case(&msg.hash_lock) {
+        return ERR!(
+            "HTLC {} hash lock {} doesn't match {}",
+            htlc.id,
+            htlc.hash_lock,
+            msg.hash_lock
+        );
+    }
+    check_htlc_claimable_by_secret(htlc.timestamp, htlc.transfer)
+}
+
+/// Estimates the timestamp the HTLC expires at.
+/// The expiration may come [`HTLC_EXPIRATION_DRIFT_BLOCKS`] earlier than the average block time implies,
+/// so the drift is subtracted to stay on the safe side of the receiver.
+fn htlc_expires_at(expiration_height: u64, current_block: u64, now: u64, avg_blocktime: u64) -> u64 {
+    let blocks_left = expiration_height
+        .saturating_sub(current_block)
+        .saturating_sub(HTLC_EXPIRATION_DRIFT_BLOCKS);
+    now + blocks_left * avg_blocktime
+}
+
+fn tx_from_response(response: &TxResponse) -> Result<CosmosTransaction, String> {
+    let bytes = try_s!(response.tx_bytes());
+    let data = try_s!(TxRaw::decode(bytes.as_slice()));
+    Ok(CosmosTransaction { data })
+}
+
+/// The info of the activated token kept by the platform coin.
+#[derive(Clone, Debug)]
+pub struct ActivatedTokenInfo {
+    pub ticker: String,
+    pub decimals: u8,
+}
+
+/// pImpl idiom.
+pub struct TendermintCoinImpl {
+    ticker: String,
+    /// The bech32 account address prefix, e.g. `iaa` for IRISnet.
+    account_prefix: String,
+    denom: String,
+    decimals: u8,
+    chain_id: String,
+    /// The price of a gas unit in the `denom` units.
+    gas_price: f64,
+    /// The average block time in seconds, used to convert the swap time locks to blocks.
+    avg_blocktime: u64,
+    key_pair: KeyPair,
+    my_address: String,
+    rpc_client: TendermintRpcClient,
+    /// The activated tokens by their denoms.
+    tokens_info: Mutex<HashMap<String, ActivatedTokenInfo>>,
+    history_sync_state: Mutex<HistorySyncState>,
+    required_confirmations: AtomicU64,
+}
+
+impl fmt::Debug for TendermintCoinImpl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result { f.write_str(&self.ticker) }
+}
+
+#[derive(Clone, Debug)]
+pub struct TendermintCoin(Arc<TendermintCoinImpl>);
+
+impl Deref for TendermintCoin {
+    type Target = TendermintCoinImpl;
+    fn deref(&self) -> &TendermintCoinImpl { &self.0 }
+}
+
+pub fn tendermint_coin_from_conf_and_params(
+    ctx: &MmArc,
+    ticker: &str,
+    conf: &Json,
+    protocol_info: TendermintProtocolInfo,
+    params: TendermintActivationParams,
+    priv_key: &[u8],
+) -> MmResult<TendermintCoin, TendermintInitError> {
+    if params.rpc_urls.is_empty() {
+        return MmError::err(TendermintInitError::EmptyRpcUrls);
+    }
+    let key_pair = key_pair_from_secret(priv_key).mm_err(|e| TendermintInitError::InvalidPrivKey(e.to_string()))?;
+    let my_address = account_id_from_pubkey(&protocol_info.account_prefix, key_pair.public_slice())
+        .map_to_mm(TendermintInitError::CouldNotGenerateAccountId)?;
+
+    #[cfg(not(target_arch = "wasm32"))]
+    let rpc_client = {
+        let proxy = crate::coin_proxy(ctx, conf).map_to_mm(TendermintInitError::InvalidConf)?;
+        TendermintRpcClient::new(params.rpc_urls, proxy)
+    };
+    #[cfg(target_arch = "wasm32")]
+    let rpc_client = {
+        let _ = ctx;
+        TendermintRpcClient::new(params.rpc_urls)
+    };
+
+    let gas_price = conf["gas_price"].as_f64().unwrap_or(DEFAULT_GAS_PRICE);
+    let avg_blocktime = conf["avg_blocktime"].as_u64().unwrap_or(DEFAULT_AVG_BLOCKTIME);
+    if avg_blocktime == 0 {
+        return MmError::err(TendermintInitError::InvalidConf(
+            "'avg_blocktime' can't be 0".to_owned(),
+        ));
+    }
+    let required_confirmations = conf["required_confirmations"].as_u64().unwrap_or(1);
+
+    Ok(TendermintCoin(Arc::new(TendermintCoinImpl {
+        ticker: ticker.to_owned(),
+        account_prefix: protocol_info.account_prefix,
+        denom: protocol_info.denom,
+        decimals: protocol_info.decimals,
+        chain_id: protocol_info.chain_id,
+        gas_price,
+        avg_blocktime,
+        key_pair,
+        my_address,
+        rpc_client,
+        tokens_info: Mutex::new(HashMap::new()),
+        history_sync_state: Mutex::new(HistorySyncState::NotEnabled),
+        required_confirmations: AtomicU64::new(required_confirmations),
+    })))
+}
+
+impl TendermintCoin {
+    pub fn rpc_client(&self) -> &TendermintRpcClient { &self.rpc_client }
+
+    pub fn denom(&self) -> &str { &self.denom }
+
+    pub fn add_activated_token_info(&self, denom: String, info: ActivatedTokenInfo) {
+        self.tokens_info.lock().unwrap().insert(denom, info);
+    }
+
+    pub fn get_activated_tokens_info(&self) -> HashMap<String, ActivatedTokenInfo> {
+        self.tokens_info.lock().unwrap().clone()
+    }
+
+    pub(crate) fn set_history_sync_state(&self, new_state: HistorySyncState) {
+        *self.history_sync_state.lock().unwrap() = new_state;
+    }
+
+    fn account_id_from_pubkey(&self, pubkey: &[u8]) -> Result<String, String> {
+        account_id_from_pubkey(&self.account_prefix, pubkey)
+    }
+
+    async fn my_account_info(&self) -> MmResult<BaseAccount, TendermintCoinRpcError> {
+        let request = QueryAccountRequest {
+            address: self.my_address.clone(),
+        };
+        let response = self
+            .rpc_client
+            .abci_query(ACCOUNT_QUERY_PATH, request.encode_to_vec())
+            .await?;
+        let account = QueryAccountResponse::decode(response.as_slice())?
+            .account
+            .or_mm_err(|| TendermintCoinRpcError::InvalidResponse(format!("Account {} not found", self.my_address)))?;
+        if account.type_url != BASE_ACCOUNT_TYPE_URL {
+            return MmError::err(TendermintCoinRpcError::InvalidResponse(format!(
+                "Unsupported account type {}",
+                account.type_url
+            )));
+        }
+        Ok(decode_any(&account)?)
+    }
+
+    /// Returns the balance of the `denom` in the coin units.
+    pub async fn balance_for_denom(&self, denom: &str, decimals: u8) -> MmResult<BigDecimal, TendermintCoinRpcError> {
+        let request = QueryBalanceRequest {
+            address: self.my_address.clone(),
+            denom: denom.to_owned(),
+        };
+        let response = self
+            .rpc_client
+            .abci_query(BALANCE_QUERY_PATH, request.encode_to_vec())
+            .await?;
+        let amount = match QueryBalanceResponse::decode(response.as_slice())?.balance {
+            Some(balance) => BigDecimal::from_str(&balance.amount)
+                .map_to_mm(|e| TendermintCoinRpcError::InvalidResponse(e.to_string()))?,
+            None => BigDecimal::zero(),
+        };
+        Ok(amount / BigDecimal::from(10u64.pow(decimals as u32)))
+    }
+
+    fn fee_for_gas_limit(&self, gas_limit: u64) -> Fee {
+        let amount = (gas_limit as f64 * self.gas_price).ceil() as u64;
+        Fee {
+            amount: vec![Coin {
+                denom: self.denom.clone(),
+                amount: amount.to_string(),
+            }],
+            gas_limit,
+            payer: String::new(),
+            granter: String::new(),
+        }
+    }
+
+    /// Returns the fee amount in the platform coin units.
+    fn fee_amount(&self, fee: &Fee) -> BigDecimal {
+        fee.amount
+            .iter()
+            .filter(|coin| coin.denom == self.denom)
+            .filter_map(|coin| BigDecimal::from_str(&coin.amount).ok())
+            .fold(BigDecimal::zero(), |total, amount| total + amount)
+            / BigDecimal::from(10u64.pow(self.decimals as u32))
+    }
+
+    fn fee_details(&self, fee: &Fee) -> TxFeeDetails {
+        TxFeeDetails::Tendermint(TendermintFeeDetails {
+            coin: self.ticker.clone(),
+            amount: self.fee_amount(fee),
+            gas_limit: fee.gas_limit,
+        })
+    }
+
+    /// Estimates the fee in the platform coin units by the given gas limit.
+    fn estimated_fee(&self, gas_limit: u64) -> BigDecimal { self.fee_amount(&self.fee_for_gas_limit(gas_limit)) }
+
+    /// Builds and signs the transaction using the `SIGN_MODE_DIRECT` mode.
+    fn sign_tx(
+        &self,
+        messages: Vec<Any>,
+        memo: String,
+        fee: Fee,
+        account: &BaseAccount,
+    ) -> MmResult<TxRaw, TendermintCoinRpcError> {
+        let body = TxBody {
+            messages,
+            memo,
+            timeout_height: 0,
+        };
+        let public_key = PubKey {
+            key: self.key_pair.public_slice().to_vec(),
+        };
+        let signer_info = SignerInfo {
+            public_key: Some(encode_any(PUBKEY_TYPE_URL, &public_key)),
+            mode_info: Some(ModeInfo {
+                single: Some(mode_info::Single { mode: SIGN_MODE_DIRECT }),
+            }),
+            sequence: account.sequence,
+        };
+        let auth_info = AuthInfo {
+            signer_infos: vec![signer_info],
+            fee: Some(fee),
+        };
+        let body_bytes = body.encode_to_vec();
+        let auth_info_bytes = auth_info.encode_to_vec();
+        let sign_doc = SignDoc {
+            body_bytes: body_bytes.clone(),
+            auth_info_bytes: auth_info_bytes.clone(),
+            chain_id: self.chain_id.clone(),
+            account_number: account.account_number,
+        };
+
+        let sign_doc_hash = sha256(&sign_doc.encode_to_vec());
+        let message = secp256k1::Message::from_slice(sign_doc_hash.as_slice())
+            .map_to_mm(|e| TendermintCoinRpcError::Internal(e.to_string()))?;
+        let secret_key = secp256k1::SecretKey::from_slice(self.key_pair.private_ref())
+            .map_to_mm(|e| TendermintCoinRpcError::Internal(e.to_string()))?;
+        let signature = keys::SECP_SIGN.sign(&message, &secret_key).serialize_compact();
+
+        Ok(TxRaw {
+            body_bytes,
+            auth_info_bytes,
+            signatures: vec![signature.to_vec()],
+        })
+    }
+
+    /// Simulates the transaction to calculate the fee.
+    async fn calc_fee(&self, msg: Any, memo: String, account: &BaseAccount) -> MmResult<Fee, TendermintCoinRpcError> {
+        // The fee isn't checked by the simulation.
+        let fee = Fee {
+            amount: Vec::new(),
+            gas_limit: 0,
+            payer: String::new(),
+            granter: String::new(),
+        };
+        let tx = self.sign_tx(vec![msg], memo, fee, account)?;
+        let request = SimulateRequest {
+            tx_bytes: tx.encode_to_vec(),
+        };
+        let response = self
+            .rpc_client
+            .abci_query(SIMULATE_PATH, request.encode_to_vec())
+            .await?;
+        let gas_used = SimulateResponse::decode(response.as_slice())?
+            .gas_info
+            .map(|gas_info| gas_info.gas_used)
+            .or_mm_err(|| {
+                TendermintCoinRpcError::InvalidResponse("No 'gas_info' in the simulation result".to_owned())
+            })?;
+        let gas_limit = (gas_used as f64 * GAS_LIMIT_MULTIPLIER).ceil() as u64;
+        Ok(self.fee_for_gas_limit(gas_limit))
+    }
+
+    /// Builds, signs and broadcasts the transaction with the single `msg`.
+    async fn send_msg(&self, msg: Any, memo: String) -> MmResult<CosmosTransaction, TendermintCoinRpcError> {
+        let account = self.my_account_info().await?;
+        let fee = self.calc_fee(msg.clone(), memo.clone(), &account).await?;
+        let data = self.sign_tx(vec![msg], memo, fee, &account)?;
+        self.rpc_client.broadcast_tx_sync(&data.encode_to_vec()).await?;
+        Ok(CosmosTransaction { data })
+    }
+
+    async fn query_htlc(&self, id: &str) -> MmResult<Option<Htlc>, TendermintCoinRpcError> {
+        let request = QueryHtlcRequest { id: id.to_owned() };
+        match self
+            .rpc_client
+            .abci_query(HTLC_QUERY_PATH, request.encode_to_vec())
+            .await
+        {
+            Ok(response) => Ok(QueryHtlcResponse::decode(response.as_slice())?.htlc),
+            // The HTLC isn't created yet.
+            Err(e) if matches!(e.get_inner(), TendermintRpcError::AbciQuery { .. }) => Ok(None),
+            Err(e) => Err(e.map(TendermintCoinRpcError::from)),
+        }
+    }
+
+    /// Converts the swap `time_lock` timestamp to the HTLC time lock in blocks.
+    fn htlc_time_lock_blocks(&self, time_lock: u32) -> u64 {
+        let lock_duration = (time_lock as u64).saturating_sub(now_ms() / 1000);
+        (lock_duration / self.avg_blocktime).clamp(HTLC_MIN_TIME_LOCK, HTLC_MAX_TIME_LOCK)
+    }
+
+    pub(crate) fn send_htlc_for_denom(
+        &self,
+        time_lock: u32,
+        other_pub: &[u8],
+        secret_hash: &[u8],
+        amount: BigDecimal,
+        denom: String,
+        decimals: u8,
+    ) -> TransactionFut {
+        let to = try_tx_fus!(self.account_id_from_pubkey(other_pub));
+        let amount = try_tx_fus!(sat_from_big_decimal(&amount, decimals));
+        let msg = MsgCreateHtlc {
+            sender: self.my_address.clone(),
+            to,
+            receiver_on_other_chain: String::new(),
+            sender_on_other_chain: String::new(),
+            amount: vec![Coin {
+                denom,
+                amount: amount.to_string(),
+            }],
+            hash_lock: hex::encode(secret_hash),
+            timestamp: 0,
+            time_lock: self.htlc_time_lock_blocks(time_lock),
+            transfer: false,
+        };
+        let coin = self.clone();
+        let fut = async move {
+            let tx = try_tx_s!(
+                coin.send_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &msg), String::new())
+                    .await
+            );
+            Ok(tx.into())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn claim_htlc(&self, payment_tx: &[u8], secret: &[u8]) -> TransactionFut {
+        let create_msg = try_tx_fus!(create_htlc_msg_from_tx_bytes(payment_tx));
+        let msg = MsgClaimHtlc {
+            sender: self.my_address.clone(),
+            id: try_tx_fus!(htlc_id(&create_msg)),
+            secret: hex::encode(secret),
+        };
+        let coin = self.clone();
+        let fut = async move {
+            let tx = try_tx_s!(
+                coin.send_msg(encode_any(MSG_CLAIM_HTLC_TYPE_URL, &msg), String::new())
+                    .await
+            );
+            Ok(tx.into())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    /// The HTLC module refunds the expired HTLCs automatically.
+    /// So the refund operation waits for the HTLC to be refunded and returns the payment transaction.
+    fn wait_for_htlc_refund(&self, payment_tx: &[u8]) -> TransactionFut {
+        let payment = try_tx_fus!(TxRaw::decode(payment_tx));
+        let create_msg = try_tx_fus!(create_htlc_msg_from_tx_bytes(payment_tx));
+        let id = try_tx_fus!(htlc_id(&create_msg));
+        let coin = self.clone();
+        let fut = async move {
+            loop {
+                let htlc = try_tx_s!(coin.query_htlc(&id).await);
+                let htlc = match htlc {
+                    Some(htlc) => htlc,
+                    None => return TX_PLAIN_ERR!("HTLC {} is not found", id),
+                };
+                match HtlcState::from_i32(htlc.state) {
+                    Some(HtlcState::Refunded) => return Ok(CosmosTransaction { data: payment }.into()),
+                    Some(HtlcState::Completed) => return TX_PLAIN_ERR!("HTLC {} is already claimed", id),
+                    Some(HtlcState::Open) => (),
+                    None => return TX_PLAIN_ERR!("Unknown HTLC {} state {}", id, htlc.state),
+                }
+                let current_block = try_tx_s!(coin.rpc_client.latest_block_height().await);
+                if current_block > htlc.expiration_height + HTLC_EXPIRATION_DRIFT_BLOCKS {
+                    return TX_PLAIN_ERR!(
+                        "HTLC {} is not refunded at {} block, expiration height {}",
+                        id,
+                        current_block,
+                        htlc.expiration_height
+                    );
+                }
+                Timer::sleep(HTLC_CHECK_INTERVAL).await;
+            }
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) fn validate_htlc_payment_for_denom(
+        &self,
+        input: ValidatePaymentInput,
+        denom: String,
+        decimals: u8,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        let coin = self.clone();
+        let fut = async move {
+            let msg = try_s!(create_htlc_msg_from_tx_bytes(&input.payment_tx));
+            let expected_sender = try_s!(coin.account_id_from_pubkey(&input.other_pub));
+            let expected_amount = vec![Coin {
+                denom,
+                amount: try_s!(sat_from_big_decimal(&input.amount, decimals)).to_string(),
+            }];
+            try_s!(validate_create_htlc_msg(
+                &msg,
+                &expected_sender,
+                &coin.my_address,
+                &input.secret_hash,
+                &expected_amount
+            ));
+
+            let id = try_s!(htlc_id(&msg));
+            let htlc = loop {
+                if let Some(htlc) = try_s!(coin.query_htlc(&id).await) {
+                    break htlc;
+                }
+                if now_ms() / 1000 > input.try_spv_proof_until {
+                    return ERR!("HTLC {} is not found", id);
+                }
+                Timer::sleep(HTLC_CHECK_INTERVAL).await;
+            };
+            try_s!(validate_htlc_state(&htlc, &msg));
+
+            // Check that the HTLC doesn't expire before the swap time lock.
+            let current_block = try_s!(coin.rpc_client.latest_block_height().await);
+            let expires_at = htlc_expires_at(
+                htlc.expiration_height,
+                current_block,
+                now_ms() / 1000,
+                coin.avg_blocktime,
+            );
+            if expires_at < input.time_lock as u64 {
+                return ERR!(
+                    "HTLC {} expires at {} block that is earlier than the time lock {}",
+                    id,
+                    htlc.expiration_height,
+                    input.time_lock
+                );
+            }
+            Ok(())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) fn check_if_my_htlc_sent(
+        &self,
+        other_pub: &[u8],
+        secret_hash: &[u8],
+    ) -> Box<dyn Future<Item = Option<TransactionEnum>, Error = String> + Send> {
+        let to = try_fus!(self.account_id_from_pubkey(other_pub));
+        let hash_lock = hex::encode(secret_hash);
+        let coin = self.clone();
+        let fut = async move {
+            let query = format!("create_htlc.sender='{}'", coin.my_address);
+            let mut page = 1;
+            loop {
+                let response = try_s!(coin.rpc_client.tx_search(&query, page).await);
+                if response.txs.is_empty() {
+                    return Ok(None);
+                }
+                for tx_response in response.txs.iter() {
+                    let tx = try_s!(tx_from_response(tx_response));
+                    let msg: Option<MsgCreateHtlc> = try_s!(find_tx_msg(&tx.data, MSG_CREATE_HTLC_TYPE_URL));
+                    if let Some(msg) = msg {
+                        if msg.to == to && msg.hash_lock.eq_ignore_ascii_case(&hash_lock) {
+                            return Ok(Some(tx.into()));
+                        }
+                    }
+                }
+                page += 1;
+            }
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) async fn search_for_htlc_spend(
+        &self,
+        input: SearchForSwapTxSpendInput<'_>,
+    ) -> Result<Option<FoundSwapTxSpend>, String> {
+        let payment = try_s!(TxRaw::decode(input.tx));
+        let msg = try_s!(create_htlc_msg_from_tx_bytes(input.tx));
+        let id = try_s!(htlc_id(&msg));
+        let htlc = match try_s!(self.query_htlc(&id).await) {
+            Some(htlc) => htlc,
+            None => return Ok(None),
+        };
+        match HtlcState::from_i32(htlc.state) {
+            Some(HtlcState::Open) => Ok(None),
+            Some(HtlcState::Completed) => {
+                let query = format!("claim_htlc.id='{}'", id);
+                let response = try_s!(self.rpc_client.tx_search(&query, 1).await);
+                match response.txs.first() {
+                    Some(tx_response) => Ok(Some(FoundSwapTxSpend::Spent(
+                        try_s!(tx_from_response(tx_response)).into(),
+                    ))),
+                    None => ERR!("HTLC {} is claimed, but the claim transaction is not found", id),
+                }
+            },
+            Some(HtlcState::Refunded) => Ok(Some(FoundSwapTxSpend::Refunded(
+                CosmosTransaction { data: payment }.into(),
+            ))),
+            None => ERR!("Unknown HTLC {} state {}", id, htlc.state),
+        }
+    }
+
+    pub(crate) fn wait_for_htlc_spend(&self, payment_tx: &[u8], wait_until: u64) -> TransactionFut {
+        let coin = self.clone();
+        let payment_tx = payment_tx.to_vec();
+        let fut = async move {
+            loop {
+                let input = SearchForSwapTxSpendInput {
+                    time_lock: 0,
+                    other_pub: &[],
+                    secret_hash: &[],
+                    tx: &payment_tx,
+                    search_from_block: 0,
+                    swap_contract_address: &None,
+                    swap_unique_data: &[],
+                };
+                match coin.search_for_htlc_spend(input).await {
+                    Ok(Some(FoundSwapTxSpend::Spent(tx))) => return Ok(tx),
+                    Ok(Some(FoundSwapTxSpend::Refunded(_))) => return TX_PLAIN_ERR!("The HTLC is refunded"),
+                    Ok(None) => (),
+                    Err(e) => warn!("Error on searching for the HTLC spend: {}", e),
+                }
+                if now_ms() / 1000 > wait_until {
+                    return TX_PLAIN_ERR!("Waited too long until {} for the HTLC to be spent", wait_until);
+                }
+                Timer::sleep(HTLC_CHECK_INTERVAL).await;
+            }
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) fn send_taker_fee_for_denom(
+        &self,
+        fee_addr: &[u8],
+        amount: BigDecimal,
+        denom: String,
+        decimals: u8,
+        uuid: &[u8],
+    ) -> TransactionFut {
+        let to_address = try_tx_fus!(self.account_id_from_pubkey(fee_addr));
+        let amount = try_tx_fus!(sat_from_big_decimal(&amount, decimals));
+        let msg = MsgSend {
+            from_address: self.my_address.clone(),
+            to_address,
+            amount: vec![Coin {
+                denom,
+                amount: amount.to_string(),
+            }],
+        };
+        let memo = hex::encode(uuid);
+        let coin = self.clone();
+        let fut = async move {
+            let tx = try_tx_s!(coin.send_msg(encode_any(MSG_SEND_TYPE_URL, &msg), memo).await);
+            Ok(tx.into())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) fn validate_fee_for_denom(
+        &self,
+        fee_tx: &TransactionEnum,
+        expected_sender: &[u8],
+        fee_addr: &[u8],
+        amount: &BigDecimal,
+        min_block_number: u64,
+        denom: String,
+        decimals: u8,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        let tx = match fee_tx {
+            TransactionEnum::CosmosTransaction(tx) => tx.clone(),
+            _ => return Box::new(futures01::future::err(ERRL!("Expected a Cosmos transaction"))),
+        };
+        let expected_sender = try_fus!(self.account_id_from_pubkey(expected_sender));
+        let fee_address = try_fus!(self.account_id_from_pubkey(fee_addr));
+        let expected_amount = try_fus!(sat_from_big_decimal(amount, decimals));
+        let coin = self.clone();
+        let fut = async move {
+            let msg: MsgSend = match try_s!(find_tx_msg(&tx.data, MSG_SEND_TYPE_URL)) {
+                Some(msg) => msg,
+                None => return ERR!("The fee transaction doesn't contain {}", MSG_SEND_TYPE_URL),
+            };
+            if msg.from_address != expected_sender {
+                return ERR!("Invalid fee sender {}, expected {}", msg.from_address, expected_sender);
+            }
+            if msg.to_address != fee_address {
+                return ERR!("Invalid fee receiver {}, expected {}", msg.to_address, fee_address);
+            }
+            let sent_amount = msg
+                .amount
+                .iter()
+                .find(|coin| coin.denom == denom)
+                .and_then(|coin| coin.amount.parse::<u64>().ok())
+                .unwrap_or_default();
+            if sent_amount < expected_amount {
+                return ERR!(
+                    "Invalid fee amount {:?}, expected {}{}",
+                    msg.amount,
+                    expected_amount,
+                    denom
+                );
+            }
+
+            let tx_response = match try_s!(coin.rpc_client.tx(&tx.tx_hash()).await) {
+                Some(tx_response) => tx_response,
+                None => return ERR!("The fee transaction {:02x} is not found", tx.tx_hash()),
+            };
+            if tx_response.tx_result.code != 0 {
+                return ERR!("The fee transaction failed: {}", tx_response.tx_result.log);
+            }
+            if tx_response.height < min_block_number {
+                return ERR!(
+                    "The fee transaction is mined at {} block that is less than the minimal {} block",
+                    tx_response.height,
+                    min_block_number
+                );
+            }
+            Ok(())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn wait_for_tx_confirmations(
+        &self,
+        tx: &[u8],
+        confirmations: u64,
+        wait_until: u64,
+        check_every: u64,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        let coin = self.clone();
+        let tx_hash = sha256(tx);
+        let fut = async move {
+            loop {
+                match coin.rpc_client.tx(tx_hash.as_slice()).await {
+                    Ok(Some(tx_response)) => {
+                        if tx_response.tx_result.code != 0 {
+                            return ERR!("Transaction {} failed: {}", tx_response.hash, tx_response.tx_result.log);
+                        }
+                        let current_block = try_s!(coin.rpc_client.latest_block_height().await);
+                        if current_block + 1 >= tx_response.height + confirmations {
+                            return Ok(());
+                        }
+                    },
+                    Ok(None) => (),
+                    Err(e) => warn!("Error on getting the {} transaction: {}", coin.ticker, e),
+                }
+                if now_ms() / 1000 > wait_until {
+                    return ERR!(
+                        "Waited too long until {} for the transaction {:02x} to be confirmed",
+                        wait_until,
+                        BytesJson::from(tx_hash.to_vec())
+                    );
+                }
+                Timer::sleep(check_every as f64).await;
+            }
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    /// Checks that the platform balance is enough to pay the `fee` and to send the `amount` of the platform coin.
+    async fn check_platform_balance_for_fee(
+        &self,
+        fee: &BigDecimal,
+        amount: &BigDecimal,
+    ) -> MmResult<(), TradePreimageError> {
+        let available = self.balance_for_denom(&self.denom, self.decimals).await?;
+        let required = fee + amount;
+        if available < required {
+            return MmError::err(TradePreimageError::NotSufficientBalance {
+                coin: self.ticker.clone(),
+                available,
+                required,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the sender trade fee and checks the balances.
+    /// The `value` is the `denom` amount to be sent, the fee is always paid in the platform coin.
+    pub(crate) async fn get_sender_trade_fee_for_denom(
+        &self,
+        ticker: &str,
+        value: TradePreimageValue,
+        denom: &str,
+        decimals: u8,
+    ) -> TradePreimageResult<TradeFee> {
+        let fee = self.estimated_fee(HTLC_CREATE_GAS_LIMIT_ESTIMATE);
+        let amount = match value {
+            TradePreimageValue::Exact(amount) | TradePreimageValue::UpperBound(amount) => amount,
+        };
+        if denom == self.denom {
+            self.check_platform_balance_for_fee(&fee, &amount).await?;
+        } else {
+            self.check_platform_balance_for_fee(&fee, &BigDecimal::zero()).await?;
+            let available = self.balance_for_denom(denom, decimals).await?;
+            if available < amount {
+                return MmError::err(TradePreimageError::NotSufficientBalance {
+                    coin: ticker.to_owned(),
+                    available,
+                    required: amount,
+                });
+            }
+        }
+        Ok(TradeFee {
+            coin: self.ticker.clone(),
+            amount: fee.into(),
+            paid_from_trading_vol: false,
+        })
+    }
+
+    pub(crate) async fn get_fee_to_send_taker_fee_for_denom(
+        &self,
+        ticker: &str,
+        dex_fee_amount: BigDecimal,
+        denom: &str,
+        decimals: u8,
+    ) -> TradePreimageResult<TradeFee> {
+        let fee = self.estimated_fee(SEND_GAS_LIMIT_ESTIMATE);
+        if denom == self.denom {
+            self.check_platform_balance_for_fee(&fee, &dex_fee_amount).await?;
+        } else {
+            self.check_platform_balance_for_fee(&fee, &BigDecimal::zero()).await?;
+            let available = self.balance_for_denom(denom, decimals).await?;
+            if available < dex_fee_amount {
+                return MmError::err(TradePreimageError::NotSufficientBalance {
+                    coin: ticker.to_owned(),
+                    available,
+                    required: dex_fee_amount,
+                });
+            }
+        }
+        Ok(TradeFee {
+            coin: self.ticker.clone(),
+            amount: fee.into(),
+            paid_from_trading_vol: false,
+        })
+    }
+
+    pub(crate) fn get_receiver_trade_fee_impl(&self) -> TradePreimageFut<TradeFee> {
+        let fee = TradeFee {
+            coin: self.ticker.clone(),
+            amount: self.estimated_fee(HTLC_CLAIM_GAS_LIMIT_ESTIMATE).into(),
+            paid_from_trading_vol: false,
+        };
+        Box::new(futures01::future::ok(fee))
+    }
+
+    pub(crate) fn validate_address_impl(&self, address: &str) -> ValidateAddressResult {
+        let result = bech32::decode(address)
+            .map_err(|e| e.to_string())
+            .and_then(|(prefix, _, _)| {
+                if prefix != self.account_prefix {
+                    return Err(format!(
+                        "Expected '{}' address prefix, found '{}'",
+                        self.account_prefix, prefix
+                    ));
+                }
+                account_id_bytes(address).map(|_| ())
+            });
+        match result {
+            Ok(()) => ValidateAddressResult {
+                is_valid: true,
+                reason: None,
+            },
+            Err(e) => ValidateAddressResult {
+                is_valid: false,
+                reason: Some(e),
+            },
+        }
+    }
+
+    pub(crate) fn get_raw_transaction_impl(&self, req: RawTransactionRequest) -> RawTransactionFut {
+        let coin = self.clone();
+        let fut = async move {
+            let hash = hex::decode(&req.tx_hash).map_to_mm(|e| RawTransactionError::InvalidHashError(e.to_string()))?;
+            let tx_response = coin
+                .rpc_client
+                .tx(&hash)
+                .await
+                .mm_err(|e| RawTransactionError::Transport(e.to_string()))?
+                .or_mm_err(|| RawTransactionError::HashNotExist(req.tx_hash.clone()))?;
+            let tx_hex = tx_response
+                .tx_bytes()
+                .mm_err(|e| RawTransactionError::InternalError(e.to_string()))?;
+            Ok(RawTransactionRes { tx_hex: tx_hex.into() })
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) fn send_raw_tx_bytes_impl(&self, tx: &[u8]) -> Box<dyn Future<Item = String, Error = String> + Send> {
+        let coin = self.clone();
+        let tx = tx.to_vec();
+        let fut = async move {
+            let hash = try_s!(coin.rpc_client.broadcast_tx_sync(&tx).await);
+            Ok(hash.to_lowercase())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    pub(crate) fn current_block_impl(&self) -> Box<dyn Future<Item = u64, Error = String> + Send> {
+        let coin = self.clone();
+        let fut = async move { coin.rpc_client.latest_block_height().await.map_err(|e| ERRL!("{}", e)) };
+        Box::new(fut.boxed().compat())
+    }
+
+    /// Withdraws the `denom`, the fee is paid in the platform coin.
+    pub(crate) async fn withdraw_denom(
+        &self,
+        ticker: String,
+        denom: String,
+        decimals: u8,
+        req: WithdrawRequest,
+    ) -> MmResult<TransactionDetails, WithdrawError> {
+        let validate_address_result = self.validate_address_impl(&req.to);
+        if !validate_address_result.is_valid {
+            return MmError::err(WithdrawError::InvalidAddress(
+                validate_address_result.reason.unwrap_or_else(|| "Unknown".to_string()),
+            ));
+        }
+        let is_platform = denom == self.denom;
+        let memo = req.memo.clone().unwrap_or_default();
+        let balance = self.balance_for_denom(&denom, decimals).await?;
+        if req.max && balance.is_zero() {
+            return MmError::err(WithdrawError::ZeroBalanceToWithdrawMax);
+        }
+
+        let msg_send = |amount: u64| MsgSend {
+            from_address: self.my_address.clone(),
+            to_address: req.to.clone(),
+            amount: vec![Coin {
+                denom: denom.clone(),
+                amount: amount.to_string(),
+            }],
+        };
+        let account = self.my_account_info().await?;
+        let fee = match req.fee {
+            Some(WithdrawFee::CosmosGas { gas_limit, gas_price }) => {
+                let amount = (gas_limit as f64 * gas_price).ceil() as u64;
+                Fee {
+                    amount: vec![Coin {
+                        denom: self.denom.clone(),
+                        amount: amount.to_string(),
+                    }],
+                    gas_limit,
+                    payer: String::new(),
+                    granter: String::new(),
+                }
+            },
+            Some(ref fee_policy) => {
+                let error = format!("Expected 'CosmosGas' fee type, found {:?}", fee_policy);
+                return MmError::err(WithdrawError::InvalidFeePolicy(error));
+            },
+            None => {
+                // The gas usage of the transfer doesn't depend on the amount,
+                // so the minimal amount is simulated to avoid the insufficient balance error on max withdrawal.
+                let msg = encode_any(MSG_SEND_TYPE_URL, &msg_send(1));
+                self.calc_fee(msg, memo.clone(), &account).await?
+            },
+        };
+        let fee_amount = self.fee_amount(&fee);
+
+        let amount = match (req.max, is_platform) {
+            (true, true) => &balance - &fee_amount,
+            (true, false) => balance.clone(),
+            (false, _) => req.amount.clone(),
+        };
+        if amount <= BigDecimal::zero() {
+            return MmError::err(WithdrawError::AmountTooLow {
+                amount,
+                threshold: fee_amount,
+            });
+        }
+        let required = if is_platform {
+            &amount + &fee_amount
+        } else {
+            amount.clone()
+        };
+        if balance < required {
+            return MmError::err(WithdrawError::NotSufficientBalance {
+                coin: ticker,
+                available: balance,
+                required,
+            });
+        }
+        if !is_platform {
+            let platform_balance = self.balance_for_denom(&self.denom, self.decimals).await?;
+            if platform_balance < fee_amount {
+                return MmError::err(WithdrawError::NotSufficientBalance {
+                    coin: self.ticker.clone(),
+                    available: platform_balance,
+                    required: fee_amount,
+                });
+            }
+        }
+
+        let amount_sat = sat_from_big_decimal(&amount, decimals)?;
+        let msg = encode_any(MSG_SEND_TYPE_URL, &msg_send(amount_sat));
+        let fee_details = self.fee_details(&fee);
+        let tx = CosmosTransaction {
+            data: self.sign_tx(vec![msg], memo, fee, &account)?,
+        };
+
+        let received_by_me = if req.to == self.my_address {
+            amount.clone()
+        } else {
+            BigDecimal::zero()
+        };
+        let spent_by_me = if is_platform {
+            &amount + &fee_amount
+        } else {
+            amount.clone()
+        };
+        let transaction_type = if is_platform {
+            TransactionType::StandardTransfer
+        } else {
+            TransactionType::TokenTransfer(denom.into_bytes().into())
+        };
+        let tx_hash = tx.tx_hash();
+        Ok(TransactionDetails {
+            tx_hex: tx.tx_hex().into(),
+            tx_hash: format!("{:02x}", tx_hash),
+            from: vec![self.my_address.clone()],
+            to: vec![req.to],
+            total_amount: spent_by_me.clone(),
+            my_balance_change: &received_by_me - &spent_by_me,
+            spent_by_me,
+            received_by_me,
+            block_height: 0,
+            timestamp: now_ms() / 1000,
+            fee_details: Some(fee_details),
+            coin: ticker,
+            internal_id: tx_hash,
+            kmd_rewards: None,
+            spv_verified: None,
+            transaction_type,
+        })
+    }
+}
+
+impl MarketCoinOps for TendermintCoin {
+    fn ticker(&self) -> &str { &self.ticker }
+
+    fn my_address(&self) -> Result<String, String> { Ok(self.my_address.clone()) }
+
+    fn get_public_key(&self) -> Result<String, MmError<UnexpectedDerivationMethod>> {
+        Ok(hex::encode(self.key_pair.public_slice()))
+    }
+
+    fn sign_message_hash(&self, _message: &str) -> Option<[u8; 32]> { None }
+
+    fn sign_message(&self, _message: &str) -> SignatureResult<String> {
+        MmError::err(SignatureError::InvalidRequest(
+            "Message signing is not supported by the given coin type".to_string(),
+        ))
+    }
+
+    fn verify_message(&self, _signature: &str, _message: &str, _address: &str) -> VerificationResult<bool> {
+        MmError::err(VerificationError::InvalidRequest(
+            "Message verification is not supported by the given coin type".to_string(),
+        ))
+    }
+
+    fn my_balance(&self) -> BalanceFut<CoinBalance> {
+        let coin = self.clone();
+        let fut = async move {
+            let spendable = coin.balance_for_denom(&coin.denom, coin.decimals).await?;
+            Ok(CoinBalance {
+                spendable,
+                unspendable: BigDecimal::zero(),
+            })
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn base_coin_balance(&self) -> BalanceFut<BigDecimal> {
+        Box::new(self.my_balance().map(|balance| balance.spendable))
+    }
+
+    fn platform_ticker(&self) -> &str { &self.ticker }
+
+    fn send_raw_tx(&self, tx: &str) -> Box<dyn Future<Item = String, Error = String> + Send> {
+        let tx = try_fus!(hex::decode(tx));
+        self.send_raw_tx_bytes_impl(&tx)
+    }
+
+    fn send_raw_tx_bytes(&self, tx: &[u8]) -> Box<dyn Future<Item = String, Error = String> + Send> {
+        self.send_raw_tx_bytes_impl(tx)
+    }
+
+    fn wait_for_confirmations(
+        &self,
+        tx: &[u8],
+        confirmations: u64,
+        _requires_nota: bool,
+        wait_until: u64,
+        check_every: u64,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.wait_for_tx_confirmations(tx, confirmations, wait_until, check_every)
+    }
+
+    fn wait_for_tx_spend(
+        &self,
+        transaction: &[u8],
+        wait_until: u64,
+        _from_block: u64,
+        _swap_contract_address: &Option<BytesJson>,
+    ) -> TransactionFut {
+        self.wait_for_htlc_spend(transaction, wait_until)
+    }
+
+    fn tx_enum_from_bytes(&self, bytes: &[u8]) -> Result<TransactionEnum, String> {
+        let data = try_s!(TxRaw::decode(bytes));
+        Ok(CosmosTransaction { data }.into())
+    }
+
+    fn current_block(&self) -> Box<dyn Future<Item = u64, Error = String> + Send> { self.current_block_impl() }
+
+    fn display_priv_key(&self) -> Result<String, String> { Ok(hex::encode(self.key_pair.private_ref())) }
+
+    fn min_tx_amount(&self) -> BigDecimal { big_decimal_from_sat_unsigned(1, self.decimals) }
+
+    fn min_trading_vol(&self) -> MmNumber { big_decimal_from_sat_unsigned(1, self.decimals).into() }
+}
+
+#[async_trait]
+impl SwapOps for TendermintCoin {
+    fn send_taker_fee(&self, fee_addr: &[u8], amount: BigDecimal, uuid: &[u8]) -> TransactionFut {
+        self.send_taker_fee_for_denom(fee_addr, amount, self.denom.clone(), self.decimals, uuid)
+    }
+
+    fn send_maker_payment(
+        &self,
+        time_lock: u32,
+        taker_pub: &[u8],
+        secret_hash: &[u8],
+        amount: BigDecimal,
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.send_htlc_for_denom(
+            time_lock,
+            taker_pub,
+            secret_hash,
+            amount,
+            self.denom.clone(),
+            self.decimals,
+        )
+    }
+
+    fn send_taker_payment(
+        &self,
+        time_lock: u32,
+        maker_pub: &[u8],
+        secret_hash: &[u8],
+        amount: BigDecimal,
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.send_htlc_for_denom(
+            time_lock,
+            maker_pub,
+            secret_hash,
+            amount,
+            self.denom.clone(),
+            self.decimals,
+        )
+    }
+
+    fn send_maker_spends_taker_payment(
+        &self,
+        taker_payment_tx: &[u8],
+        _time_lock: u32,
+        _taker_pub: &[u8],
+        secret: &[u8],
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.claim_htlc(taker_payment_tx, secret)
+    }
+
+    fn send_taker_spends_maker_payment(
+        &self,
+        maker_payment_tx: &[u8],
+        _time_lock: u32,
+        _maker_pub: &[u8],
+        secret: &[u8],
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.claim_htlc(maker_payment_tx, secret)
+    }
+
+    fn send_taker_refunds_payment(
+        &self,
+        taker_payment_tx: &[u8],
+        _time_lock: u32,
+        _maker_pub: &[u8],
+        _secret_hash: &[u8],
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.wait_for_htlc_refund(taker_payment_tx)
+    }
+
+    fn send_maker_refunds_payment(
+        &self,
+        maker_payment_tx: &[u8],
+        _time_lock: u32,
+        _taker_pub: &[u8],
+        _secret_hash: &[u8],
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.wait_for_htlc_refund(maker_payment_tx)
+    }
+
+    fn validate_fee(
+        &self,
+        fee_tx: &TransactionEnum,
+        expected_sender: &[u8],
+        fee_addr: &[u8],
+        amount: &BigDecimal,
+        min_block_number: u64,
+        _uuid: &[u8],
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.validate_fee_for_denom(
+            fee_tx,
+            expected_sender,
+            fee_addr,
+            amount,
+            min_block_number,
+            self.denom.clone(),
+            self.decimals,
+        )
+    }
+
+    fn validate_maker_payment(&self, input: ValidatePaymentInput) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.validate_htlc_payment_for_denom(input, self.denom.clone(), self.decimals)
+    }
+
+    fn validate_taker_payment(&self, input: ValidatePaymentInput) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.validate_htlc_payment_for_denom(input, self.denom.clone(), self.decimals)
+    }
+
+    fn check_if_my_payment_sent(
+        &self,
+        _time_lock: u32,
+        other_pub: &[u8],
+        secret_hash: &[u8],
+        _search_from_block: u64,
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> Box<dyn Future<Item = Option<TransactionEnum>, Error = String> + Send> {
+        self.check_if_my_htlc_sent(other_pub, secret_hash)
+    }
+
+    async fn search_for_swap_tx_spend_my(
+        &self,
+        input: SearchForSwapTxSpendInput<'_>,
+    ) -> Result<Option<FoundSwapTxSpend>, String> {
+        self.search_for_htlc_spend(input).await
+    }
+
+    async fn search_for_swap_tx_spend_other(
+        &self,
+        input: SearchForSwapTxSpendInput<'_>,
+    ) -> Result<Option<FoundSwapTxSpend>, String> {
+        self.search_for_htlc_spend(input).await
+    }
+
+    fn extract_secret(&self, secret_hash: &[u8], spend_tx: &[u8]) -> Result<Vec<u8>, String> {
+        let tx = try_s!(TxRaw::decode(spend_tx));
+        let msg: MsgClaimHtlc = match try_s!(find_tx_msg(&tx, MSG_CLAIM_HTLC_TYPE_URL)) {
+            Some(msg) => msg,
+            None => return ERR!("The transaction doesn't contain {}", MSG_CLAIM_HTLC_TYPE_URL),
+        };
+        let secret = try_s!(hex::decode(&msg.secret));
+        if sha256(&secret).as_slice() != secret_hash {
+            return ERR!(
+                "The secret hash doesn't match the expected {}",
+                hex::encode(secret_hash)
+            );
+        }
+        Ok(secret)
+    }
+
+    /// The HTLC module refunds the payment automatically at the expiration height.
+    fn can_refund_htlc(&self, _locktime: u64) -> Box<dyn Future<Item = CanRefundHtlc, Error = String> + Send + '_> {
+        Box::new(futures01::future::ok(CanRefundHtlc::CanRefundNow))
+    }
+
+    fn negotiate_swap_contract_addr(
+        &self,
+        _other_side_address: Option<&[u8]>,
+    ) -> Result<Option<BytesJson>, MmError<NegotiateSwapContractAddrErr>> {
+        Ok(None)
+    }
+
+    fn derive_htlc_key_pair(&self, _swap_unique_data: &[u8]) -> KeyPair { self.key_pair }
+}
+
+#[async_trait]
+impl MmCoin for TendermintCoin {
+    fn is_asset_chain(&self) -> bool { false }
+
+    fn withdraw(&self, req: WithdrawRequest) -> WithdrawFut {
+        let coin = self.clone();
+        let fut = async move {
+            coin.withdraw_denom(coin.ticker.clone(), coin.denom.clone(), coin.decimals, req)
+                .await
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_raw_transaction(&self, req: RawTransactionRequest) -> RawTransactionFut {
+        self.get_raw_transaction_impl(req)
+    }
+
+    fn decimals(&self) -> u8 { self.decimals }
+
+    fn convert_to_address(&self, _from: &str, _to_address_format: Json) -> Result<String, String> {
+        ERR!("Address conversion is not available for {}", self.ticker)
+    }
+
+    fn validate_address(&self, address: &str) -> ValidateAddressResult { self.validate_address_impl(address) }
+
+    fn process_history_loop(&self, ctx: MmArc) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        ctx.log.log(
+            "🤔",
+            &[&"tx_history", &self.ticker],
+            &ERRL!("The history is fetched on 'enable_tendermint_with_assets' with 'tx_history' enabled"),
+        );
+        Box::new(futures01::future::ok(()))
+    }
+
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+        load_history_from_storage(self, ctx)
+    }
+
+    fn history_sync_status(&self) -> HistorySyncState { self.history_sync_state.lock().unwrap().clone() }
+
+    fn get_trade_fee(&self) -> Box<dyn Future<Item = TradeFee, Error = String> + Send> {
+        Box::new(futures01::future::ok(TradeFee {
+            coin: self.ticker.clone(),
+            amount: self.estimated_fee(HTLC_CREATE_GAS_LIMIT_ESTIMATE).into(),
+            paid_from_trading_vol: false,
+        }))
+    }
+
+    async fn get_sender_trade_fee(
+        &self,
+        value: TradePreimageValue,
+        _stage: FeeApproxStage,
+    ) -> TradePreimageResult<TradeFee> {
+        self.get_sender_trade_fee_for_denom(&self.ticker, value, &self.denom, self.decimals)
+            .await
+    }
+
+    fn get_receiver_trade_fee(&self, _stage: FeeApproxStage) -> TradePreimageFut<TradeFee> {
+        self.get_receiver_trade_fee_impl()
+    }
+
+    async fn get_fee_to_send_taker_fee(
+        &self,
+        dex_fee_amount: BigDecimal,
+        _stage: FeeApproxStage,
+    ) -> TradePreimageResult<TradeFee> {
+        self.get_fee_to_send_taker_fee_for_denom(&self.ticker, dex_fee_amount, &self.denom, self.decimals)
+            .await
+    }
+
+    fn required_confirmations(&self) -> u64 { self.required_confirmations.load(AtomicOrdering::Relaxed) }
+
+    fn requires_notarization(&self) -> bool { false }
+
+    fn set_required_confirmations(&self, confirmations: u64) {
+        self.required_confirmations
+            .store(confirmations, AtomicOrdering::Relaxed);
+    }
+
+    fn set_requires_notarization(&self, _requires_nota: bool) {
+        warn!("set_requires_notarization doesn't take any effect on {}", self.ticker);
+    }
+
+    fn swap_contract_address(&self) -> Option<BytesJson> { None }
+
+    fn mature_confirmations(&self) -> Option<u32> { None }
+
+    fn coin_protocol_info(&self) -> Vec<u8> { Vec::new() }
+
+    fn is_coin_protocol_supported(&self, _info: &Option<Vec<u8>>) -> bool { true }
+}
+
+#[async_trait]
+impl CoinWithTxHistoryV2 for TendermintCoin {
+    fn history_wallet_id(&self) -> WalletId { WalletId::new(self.ticker.clone()) }
+
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        Ok(GetTxHistoryFilters::new())
+    }
+}
@@ -0,0 +1,364 @@
+//! The Tendermint JSON-RPC client.
+//! https://docs.tendermint.com/v0.34/rpc/
+
+use async_trait::async_trait;
+use chrono::DateTime;
+use common::log::warn;
+use derive_more::Display;
+use http::StatusCode;
+use mm2_err_handle::prelude::*;
+#[cfg(not(target_arch = "wasm32"))]
+use mm2_net::socks5::Socks5Proxy;
+use mm2_net::transport::SlurpResult;
+use serde::de::{DeserializeOwned, Deserializer};
+use serde::Deserialize;
+use serde_json::{self as json, Value as Json};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+#[cfg(test)] use mocktopus::macros::*;
+
+/// The `tx_search` page size, 100 is the maximum allowed by Tendermint.
+pub const TX_SEARCH_PER_PAGE: u32 = 100;
+
+#[derive(Debug, Display)]
+pub enum TendermintRpcError {
+    #[display(fmt = "Transport error: {}", _0)]
+    Transport(String),
+    #[display(fmt = "Invalid response: {}", _0)]
+    InvalidResponse(String),
+    #[display(fmt = "RPC error {}: {}", code, message)]
+    Rpc { code: i64, message: String },
+    #[display(fmt = "ABCI query '{}' failed with {} code: {}", path, code, log)]
+    AbciQuery { path: String, code: u32, log: String },
+    #[display(fmt = "Transaction is rejected with {} code: {}", code, log)]
+    TxRejected { code: u32, log: String },
+}
+
+fn u64_from_str<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(serde::de::Error::custom)
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcError {
+    code: i64,
+    message: String,
+    #[serde(default)]
+    data: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct JsonRpcResponse {
+    #[serde(default)]
+    result: Option<Json>,
+    #[serde(default)]
+    error: Option<JsonRpcError>,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbciQueryResponse {
+    response: AbciQueryResult,
+}
+
+#[derive(Debug, Deserialize)]
+struct AbciQueryResult {
+    #[serde(default)]
+    code: u32,
+    #[serde(default)]
+    log: String,
+    /// Base64 encoded protobuf response.
+    #[serde(default)]
+    value: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct BroadcastTxResult {
+    code: u32,
+    #[serde(default)]
+    log: String,
+    hash: String,
+}
+
+#[derive(Debug, Deserialize)]
+struct StatusResult {
+    sync_info: SyncInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct SyncInfo {
+    #[serde(deserialize_with = "u64_from_str")]
+    latest_block_height: u64,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockResult {
+    block: Block,
+}
+
+#[derive(Debug, Deserialize)]
+struct Block {
+    header: BlockHeader,
+}
+
+#[derive(Debug, Deserialize)]
+struct BlockHeader {
+    time: String,
+}
+
+/// The event attribute, its key and value are base64 encoded by Tendermint 0.34 and plain since 0.35.
+#[derive(Clone, Debug, Deserialize)]
+pub struct EventAttribute {
+    key: String,
+    #[serde(default)]
+    value: Option<String>,
+}
+
+impl EventAttribute {
+    /// Returns the attribute value if the attribute has the given `key`.
+    fn value_of(&self, key: &str) -> Option<String> {
+        let value = self.value.clone().unwrap_or_default();
+        if self.key == key {
+            return Some(value);
+        }
+        if self.key == base64::encode(key) {
+            return base64::decode(&value)
+                .ok()
+                .and_then(|bytes| String::from_utf8(bytes).ok());
+        }
+        None
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct Event {
+    #[serde(rename = "type")]
+    pub kind: String,
+    #[serde(default)]
+    pub attributes: Vec<EventAttribute>,
+}
+
+impl Event {
+    /// Returns the value of the first attribute with the given `key`.
+    pub fn attribute(&self, key: &str) -> Option<String> { self.attributes.iter().find_map(|attr| attr.value_of(key)) }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TxResult {
+    #[serde(default)]
+    pub code: u32,
+    #[serde(default)]
+    pub log: String,
+    #[serde(default)]
+    pub events: Vec<Event>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TxResponse {
+    /// Uppercase hex of the transaction hash.
+    pub hash: String,
+    #[serde(deserialize_with = "u64_from_str")]
+    pub height: u64,
+    pub tx_result: TxResult,
+    /// Base64 encoded `TxRaw`.
+    pub tx: String,
+}
+
+impl TxResponse {
+    pub fn tx_bytes(&self) -> Result<Vec<u8>, MmError<TendermintRpcError>> {
+        base64::decode(&self.tx).map_to_mm(|e| TendermintRpcError::InvalidResponse(e.to_string()))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TxSearchResponse {
+    pub txs: Vec<TxResponse>,
+    #[serde(deserialize_with = "u64_from_str")]
+    pub total_count: u64,
+}
+
+/// pImpl idiom.
+#[derive(Debug)]
+pub struct TendermintRpcClientImpl {
+    rpc_urls: Vec<String>,
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<Socks5Proxy>,
+    next_id: AtomicU64,
+}
+
+#[derive(Clone, Debug)]
+pub struct TendermintRpcClient(Arc<TendermintRpcClientImpl>);
+
+impl TendermintRpcClient {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn new(rpc_urls: Vec<String>, proxy: Option<Socks5Proxy>) -> TendermintRpcClient {
+        TendermintRpcClient(Arc::new(TendermintRpcClientImpl {
+            rpc_urls,
+            proxy,
+            next_id: AtomicU64::new(0),
+        }))
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    pub fn new(rpc_urls: Vec<String>) -> TendermintRpcClient {
+        TendermintRpcClient(Arc::new(TendermintRpcClientImpl {
+            rpc_urls,
+            next_id: AtomicU64::new(0),
+        }))
+    }
+
+    #[cfg(not(target_arch = "wasm32"))]
+    async fn post_json(&self, url: &str, body: String) -> SlurpResult {
+        use http::header::{HeaderValue, CONTENT_TYPE};
+        use http::Request;
+        use mm2_net::transport::slurp_req_via_proxy;
+
+        let request = Request::builder()
+            .method("POST")
+            .uri(url)
+            .header(CONTENT_TYPE, HeaderValue::from_static("application/json"))
+            .body(body.into_bytes())?;
+        slurp_req_via_proxy(request, self.0.proxy.as_ref()).await
+    }
+
+    #[cfg(target_arch = "wasm32")]
+    async fn post_json(&self, url: &str, body: String) -> SlurpResult {
+        mm2_net::transport::slurp_post_json(url, body).await
+    }
+
+    async fn request<T: DeserializeOwned>(&self, method: &str, params: Json) -> MmResult<T, TendermintRpcError> {
+        let result = self.rpc_request(method, params).await?;
+        json::from_value(result)
+            .map_to_mm(|e| TendermintRpcError::InvalidResponse(format!("'{}' result: {}", method, e)))
+    }
+
+    /// Performs the ABCI query, the `data` is the protobuf encoded request.
+    /// Returns the protobuf encoded response.
+    pub async fn abci_query(&self, path: &str, data: Vec<u8>) -> MmResult<Vec<u8>, TendermintRpcError> {
+        let params = json!({
+            "path": path,
+            "data": hex::encode(data),
+            "prove": false,
+        });
+        let response: AbciQueryResponse = self.request("abci_query", params).await?;
+        if response.response.code != 0 {
+            return MmError::err(TendermintRpcError::AbciQuery {
+                path: path.to_owned(),
+                code: response.response.code,
+                log: response.response.log,
+            });
+        }
+        let value = response.response.value.unwrap_or_default();
+        base64::decode(&value).map_to_mm(|e| TendermintRpcError::InvalidResponse(e.to_string()))
+    }
+
+    pub async fn latest_block_height(&self) -> MmResult<u64, TendermintRpcError> {
+        let status: StatusResult = self.request("status", json!({})).await?;
+        Ok(status.sync_info.latest_block_height)
+    }
+
+    /// Returns the timestamp of the block at the given `height`.
+    pub async fn block_timestamp(&self, height: u64) -> MmResult<u64, TendermintRpcError> {
+        let block: BlockResult = self.request("block", json!({ "height": height.to_string() })).await?;
+        let time = DateTime::parse_from_rfc3339(&block.block.header.time)
+            .map_to_mm(|e| TendermintRpcError::InvalidResponse(format!("Invalid block time: {}", e)))?;
+        Ok(time.timestamp() as u64)
+    }
+
+    /// Broadcasts the transaction and waits for it to pass `CheckTx`, returns the uppercase hex of the transaction hash.
+    pub async fn broadcast_tx_sync(&self, tx: &[u8]) -> MmResult<String, TendermintRpcError> {
+        let result: BroadcastTxResult = self
+            .request("broadcast_tx_sync", json!({ "tx": base64::encode(tx) }))
+            .await?;
+        if result.code != 0 {
+            return MmError::err(TendermintRpcError::TxRejected {
+                code: result.code,
+                log: result.log,
+            });
+        }
+        Ok(result.hash)
+    }
+
+    /// Returns the transaction by its hash or `None` if it's not included into a block yet.
+    pub async fn tx(&self, hash: &[u8]) -> MmResult<Option<TxResponse>, TendermintRpcError> {
+        let params = json!({
+            "hash": base64::encode(hash),
+            "prove": false,
+        });
+        match self.request("tx", params).await {
+            Ok(tx) => Ok(Some(tx)),
+            Err(e) => match e.get_inner() {
+                TendermintRpcError::Rpc { message, .. } if message.contains("not found") => Ok(None),
+                _ => Err(e),
+            },
+        }
+    }
+
+    /// Searches the transactions by the events `query`, the newest transactions go first.
+    /// https://docs.tendermint.com/v0.34/rpc/#/Info/tx_search
+    pub async fn tx_search(&self, query: &str, page: u32) -> MmResult<TxSearchResponse, TendermintRpcError> {
+        let params = json!({
+            "query": query,
+            "prove": false,
+            "page": page.to_string(),
+            "per_page": TX_SEARCH_PER_PAGE.to_string(),
+            "order_by": "desc",
+        });
+        self.request("tx_search", params).await
+    }
+}
+
+#[async_trait]
+pub trait TendermintRpcTransport {
+    /// Sends the JSON-RPC request, returns the `result` of the response.
+    async fn rpc_request(&self, method: &str, params: Json) -> MmResult<Json, TendermintRpcError>;
+}
+
+#[async_trait]
+#[cfg_attr(test, mockable)]
+impl TendermintRpcTransport for TendermintRpcClient {
+    /// Sends the JSON-RPC request to the RPC servers one by one until one of them responds.
+    async fn rpc_request(&self, method: &str, params: Json) -> MmResult<Json, TendermintRpcError> {
+        let id = self.0.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = json::to_string(&json!({
+            "jsonrpc": "2.0",
+            "id": id,
+            "method": method,
+            "params": params,
+        }))
+        .map_to_mm(|e| TendermintRpcError::InvalidResponse(e.to_string()))?;
+
+        let mut errors = Vec::new();
+        for url in self.0.rpc_urls.iter() {
+            let (status, _headers, response) = match self.post_json(url, body.clone()).await {
+                Ok(response) => response,
+                Err(e) => {
+                    warn!("Error on '{}' request to {}: {}", method, url, e);
+                    errors.push(e.to_string());
+                    continue;
+                },
+            };
+            if status != StatusCode::OK && response.is_empty() {
+                errors.push(format!("{} responded with {} status", url, status));
+                continue;
+            }
+            let response: JsonRpcResponse = json::from_slice(&response)
+                .map_to_mm(|e| TendermintRpcError::InvalidResponse(format!("{} from {}", e, url)))?;
+            if let Some(error) = response.error {
+                let message = match error.data {
+                    Some(data) => format!("{}: {}", error.message, data),
+                    None => error.message,
+                };
+                return MmError::err(TendermintRpcError::Rpc {
+                    code: error.code,
+                    message,
+                });
+            }
+            return Ok(response.result.unwrap_or(Json::Null));
+        }
+        MmError::err(TendermintRpcError::Transport(format!(
+            "All the RPC servers failed on '{}': {:?}",
+            method, errors
+        )))
+    }
+}
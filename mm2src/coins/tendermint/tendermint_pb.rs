@@ -0,0 +1,261 @@
+/// google.protobuf.Any
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Any {
+    #[prost(string, tag="1")]
+    pub type_url: ::prost::alloc::string::String,
+    #[prost(bytes="vec", tag="2")]
+    pub value: ::prost::alloc::vec::Vec<u8>,
+}
+/// cosmos.base.v1beta1.Coin
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Coin {
+    #[prost(string, tag="1")]
+    pub denom: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub amount: ::prost::alloc::string::String,
+}
+/// cosmos.crypto.secp256k1.PubKey
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct PubKey {
+    #[prost(bytes="vec", tag="1")]
+    pub key: ::prost::alloc::vec::Vec<u8>,
+}
+/// cosmos.bank.v1beta1.MsgSend
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgSend {
+    #[prost(string, tag="1")]
+    pub from_address: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub to_address: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag="3")]
+    pub amount: ::prost::alloc::vec::Vec<Coin>,
+}
+/// cosmos.tx.v1beta1.Tx
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Tx {
+    #[prost(message, optional, tag="1")]
+    pub body: ::core::option::Option<TxBody>,
+    #[prost(message, optional, tag="2")]
+    pub auth_info: ::core::option::Option<AuthInfo>,
+    #[prost(bytes="vec", repeated, tag="3")]
+    pub signatures: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// cosmos.tx.v1beta1.TxRaw
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxRaw {
+    #[prost(bytes="vec", tag="1")]
+    pub body_bytes: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes="vec", tag="2")]
+    pub auth_info_bytes: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes="vec", repeated, tag="3")]
+    pub signatures: ::prost::alloc::vec::Vec<::prost::alloc::vec::Vec<u8>>,
+}
+/// cosmos.tx.v1beta1.SignDoc
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SignDoc {
+    #[prost(bytes="vec", tag="1")]
+    pub body_bytes: ::prost::alloc::vec::Vec<u8>,
+    #[prost(bytes="vec", tag="2")]
+    pub auth_info_bytes: ::prost::alloc::vec::Vec<u8>,
+    #[prost(string, tag="3")]
+    pub chain_id: ::prost::alloc::string::String,
+    #[prost(uint64, tag="4")]
+    pub account_number: u64,
+}
+/// cosmos.tx.v1beta1.TxBody
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct TxBody {
+    #[prost(message, repeated, tag="1")]
+    pub messages: ::prost::alloc::vec::Vec<Any>,
+    #[prost(string, tag="2")]
+    pub memo: ::prost::alloc::string::String,
+    #[prost(uint64, tag="3")]
+    pub timeout_height: u64,
+}
+/// cosmos.tx.v1beta1.AuthInfo
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct AuthInfo {
+    #[prost(message, repeated, tag="1")]
+    pub signer_infos: ::prost::alloc::vec::Vec<SignerInfo>,
+    #[prost(message, optional, tag="2")]
+    pub fee: ::core::option::Option<Fee>,
+}
+/// cosmos.tx.v1beta1.SignerInfo
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SignerInfo {
+    #[prost(message, optional, tag="1")]
+    pub public_key: ::core::option::Option<Any>,
+    #[prost(message, optional, tag="2")]
+    pub mode_info: ::core::option::Option<ModeInfo>,
+    #[prost(uint64, tag="3")]
+    pub sequence: u64,
+}
+/// cosmos.tx.v1beta1.ModeInfo, only the single signer mode is supported.
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct ModeInfo {
+    #[prost(message, optional, tag="1")]
+    pub single: ::core::option::Option<mode_info::Single>,
+}
+/// Nested message and enum types in `ModeInfo`.
+pub mod mode_info {
+    #[derive(Clone, PartialEq, ::prost::Message)]
+    pub struct Single {
+        /// cosmos.tx.signing.v1beta1.SignMode
+        #[prost(int32, tag="1")]
+        pub mode: i32,
+    }
+}
+/// cosmos.tx.v1beta1.Fee
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Fee {
+    #[prost(message, repeated, tag="1")]
+    pub amount: ::prost::alloc::vec::Vec<Coin>,
+    #[prost(uint64, tag="2")]
+    pub gas_limit: u64,
+    #[prost(string, tag="3")]
+    pub payer: ::prost::alloc::string::String,
+    #[prost(string, tag="4")]
+    pub granter: ::prost::alloc::string::String,
+}
+/// cosmos.auth.v1beta1.BaseAccount
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct BaseAccount {
+    #[prost(string, tag="1")]
+    pub address: ::prost::alloc::string::String,
+    #[prost(message, optional, tag="2")]
+    pub pub_key: ::core::option::Option<Any>,
+    #[prost(uint64, tag="3")]
+    pub account_number: u64,
+    #[prost(uint64, tag="4")]
+    pub sequence: u64,
+}
+/// cosmos.auth.v1beta1.QueryAccountRequest
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryAccountRequest {
+    #[prost(string, tag="1")]
+    pub address: ::prost::alloc::string::String,
+}
+/// cosmos.auth.v1beta1.QueryAccountResponse
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryAccountResponse {
+    #[prost(message, optional, tag="1")]
+    pub account: ::core::option::Option<Any>,
+}
+/// cosmos.bank.v1beta1.QueryBalanceRequest
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryBalanceRequest {
+    #[prost(string, tag="1")]
+    pub address: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub denom: ::prost::alloc::string::String,
+}
+/// cosmos.bank.v1beta1.QueryBalanceResponse
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryBalanceResponse {
+    #[prost(message, optional, tag="1")]
+    pub balance: ::core::option::Option<Coin>,
+}
+/// cosmos.tx.v1beta1.SimulateRequest
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SimulateRequest {
+    #[prost(bytes="vec", tag="2")]
+    pub tx_bytes: ::prost::alloc::vec::Vec<u8>,
+}
+/// cosmos.base.abci.v1beta1.GasInfo
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct GasInfo {
+    #[prost(uint64, tag="1")]
+    pub gas_wanted: u64,
+    #[prost(uint64, tag="2")]
+    pub gas_used: u64,
+}
+/// cosmos.tx.v1beta1.SimulateResponse
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct SimulateResponse {
+    #[prost(message, optional, tag="1")]
+    pub gas_info: ::core::option::Option<GasInfo>,
+}
+/// irismod.htlc.MsgCreateHTLC
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgCreateHtlc {
+    #[prost(string, tag="1")]
+    pub sender: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub to: ::prost::alloc::string::String,
+    #[prost(string, tag="3")]
+    pub receiver_on_other_chain: ::prost::alloc::string::String,
+    #[prost(string, tag="4")]
+    pub sender_on_other_chain: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag="5")]
+    pub amount: ::prost::alloc::vec::Vec<Coin>,
+    #[prost(string, tag="6")]
+    pub hash_lock: ::prost::alloc::string::String,
+    #[prost(uint64, tag="7")]
+    pub timestamp: u64,
+    #[prost(uint64, tag="8")]
+    pub time_lock: u64,
+    #[prost(bool, tag="9")]
+    pub transfer: bool,
+}
+/// irismod.htlc.MsgClaimHTLC
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MsgClaimHtlc {
+    #[prost(string, tag="1")]
+    pub sender: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag="3")]
+    pub secret: ::prost::alloc::string::String,
+}
+/// irismod.htlc.HTLC
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct Htlc {
+    #[prost(string, tag="1")]
+    pub id: ::prost::alloc::string::String,
+    #[prost(string, tag="2")]
+    pub sender: ::prost::alloc::string::String,
+    #[prost(string, tag="3")]
+    pub to: ::prost::alloc::string::String,
+    #[prost(string, tag="4")]
+    pub receiver_on_other_chain: ::prost::alloc::string::String,
+    #[prost(string, tag="5")]
+    pub sender_on_other_chain: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag="6")]
+    pub amount: ::prost::alloc::vec::Vec<Coin>,
+    #[prost(string, tag="7")]
+    pub hash_lock: ::prost::alloc::string::String,
+    #[prost(string, tag="8")]
+    pub secret: ::prost::alloc::string::String,
+    #[prost(uint64, tag="9")]
+    pub timestamp: u64,
+    #[prost(uint64, tag="10")]
+    pub expiration_height: u64,
+    #[prost(enumeration="HtlcState", tag="11")]
+    pub state: i32,
+    #[prost(uint64, tag="12")]
+    pub closed_block: u64,
+    #[prost(bool, tag="13")]
+    pub transfer: bool,
+    #[prost(int32, tag="14")]
+    pub direction: i32,
+}
+/// irismod.htlc.QueryHTLCRequest
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryHtlcRequest {
+    #[prost(string, tag="1")]
+    pub id: ::prost::alloc::string::String,
+}
+/// irismod.htlc.QueryHTLCResponse
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct QueryHtlcResponse {
+    #[prost(message, optional, tag="1")]
+    pub htlc: ::core::option::Option<Htlc>,
+}
+/// irismod.htlc.HTLCState
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, ::prost::Enumeration)]
+#[repr(i32)]
+pub enum HtlcState {
+    Open = 0,
+    Completed = 1,
+    Refunded = 2,
+}
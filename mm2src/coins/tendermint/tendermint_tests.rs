@@ -0,0 +1,662 @@
+use super::rpc::TendermintRpcTransport;
+use super::tendermint_pb::GasInfo;
+use super::*;
+use common::block_on;
+use mm2_core::mm_ctx::MmCtxBuilder;
+use mocktopus::mocking::*;
+
+const PUBKEY: &str = "02a7bd40ea7b8d6c4bc6dd4f0f7cbd5a2bb3bfe26be4eecd4a9f9d6a7a9f5b1a3c";
+
+#[test]
+fn test_account_id_from_pubkey() {
+    let pubkey = hex::decode(PUBKEY).unwrap();
+    assert_eq!(
+        account_id_from_pubkey("iaa", &pubkey).unwrap(),
+        "iaa1msatzdv4r7d85kd38as789ecpe8fegmm5wj5h9"
+    );
+    assert_eq!(
+        account_id_from_pubkey("cosmos", &pubkey).unwrap(),
+        "cosmos1msatzdv4r7d85kd38as789ecpe8fegmmpvj945"
+    );
+    assert_eq!(
+        account_id_from_pubkey_hex("iaa", PUBKEY).unwrap(),
+        "iaa1msatzdv4r7d85kd38as789ecpe8fegmm5wj5h9"
+    );
+
+    // Uncompressed public keys are not allowed.
+    account_id_from_pubkey_hex("iaa", &PUBKEY[2..]).unwrap_err();
+}
+
+#[test]
+fn test_account_id_bytes() {
+    let bytes = account_id_bytes("iaa1msatzdv4r7d85kd38as789ecpe8fegmm5wj5h9").unwrap();
+    assert_eq!(hex::encode(bytes), "dc3ab135951f9a7a59b13f61e397380e4e9ca37b");
+
+    // Invalid checksum.
+    account_id_bytes("iaa1msatzdv4r7d85kd38as789ecpe8fegmm5wj5h8").unwrap_err();
+}
+
+#[test]
+fn test_coins_string() {
+    let ibc_denom = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2";
+    let coins = vec![
+        Coin {
+            denom: "uiris".to_owned(),
+            amount: "100".to_owned(),
+        },
+        Coin {
+            denom: ibc_denom.to_owned(),
+            amount: "5".to_owned(),
+        },
+    ];
+    let coins_str = coins_to_string(&coins);
+    assert_eq!(coins_str, format!("5{},100uiris", ibc_denom));
+
+    let parsed = parse_coins(&coins_str).unwrap();
+    let expected = vec![
+        (ibc_denom.to_owned(), BigDecimal::from(5)),
+        ("uiris".to_owned(), BigDecimal::from(100)),
+    ];
+    assert_eq!(parsed, expected);
+
+    assert!(parse_coins("").unwrap().is_empty());
+    parse_coins("100").unwrap_err();
+}
+
+#[test]
+fn test_htlc_id() {
+    let msg = MsgCreateHtlc {
+        sender: "iaa1msatzdv4r7d85kd38as789ecpe8fegmm5wj5h9".to_owned(),
+        to: "iaa1qqqsyqcyq5rqwzqfpg9scrgwpugpzysnk53tng".to_owned(),
+        receiver_on_other_chain: String::new(),
+        sender_on_other_chain: String::new(),
+        amount: vec![Coin {
+            denom: "uiris".to_owned(),
+            amount: "100".to_owned(),
+        }],
+        hash_lock: "6ab7c6a5cfba1e1b4ed1d3e4ab2bd0f3aa1e9cee8b0cc5bb8ac4c8dc5d3b1f0a".to_owned(),
+        timestamp: 0,
+        time_lock: 50,
+        transfer: false,
+    };
+    assert_eq!(
+        htlc_id(&msg).unwrap(),
+        "EA054F3D0C550E05773B6D76E6CA4178353E6DE8A0DF758F3A6AD3145DEC06E0"
+    );
+}
+
+const DENOM: &str = "uiris";
+const MY_PRIV_KEY: [u8; 32] = [1; 32];
+const OTHER_PRIV_KEY: [u8; 32] = [2; 32];
+const FEE_PRIV_KEY: [u8; 32] = [4; 32];
+const SECRET: [u8; 32] = [3; 32];
+
+fn tendermint_coin_for_test() -> TendermintCoin {
+    let ctx = MmCtxBuilder::new().into_mm_arc();
+    let protocol_info = TendermintProtocolInfo {
+        decimals: 6,
+        denom: DENOM.to_owned(),
+        account_prefix: "iaa".to_owned(),
+        chain_id: "nyancat-9".to_owned(),
+    };
+    let params = TendermintActivationParams {
+        rpc_urls: vec!["http://127.0.0.1:26657".to_owned()],
+    };
+    tendermint_coin_from_conf_and_params(&ctx, "IRIS", &json!({}), protocol_info, params, &MY_PRIV_KEY).unwrap()
+}
+
+fn pubkey_of(priv_key: &[u8]) -> Vec<u8> { key_pair_from_secret(priv_key).unwrap().public_slice().to_vec() }
+
+fn address_of(priv_key: &[u8]) -> String { account_id_from_pubkey("iaa", &pubkey_of(priv_key)).unwrap() }
+
+fn uiris(amount: &str) -> Vec<Coin> {
+    vec![Coin {
+        denom: DENOM.to_owned(),
+        amount: amount.to_owned(),
+    }]
+}
+
+fn tx_with_msg(msg: Any) -> TxRaw {
+    let body = TxBody {
+        messages: vec![msg],
+        memo: String::new(),
+        timeout_height: 0,
+    };
+    TxRaw {
+        body_bytes: body.encode_to_vec(),
+        auth_info_bytes: Vec::new(),
+        signatures: Vec::new(),
+    }
+}
+
+/// The HTLC created by the other side of the swap for the test coin owner.
+fn create_htlc_msg_for_test() -> MsgCreateHtlc {
+    MsgCreateHtlc {
+        sender: address_of(&OTHER_PRIV_KEY),
+        to: address_of(&MY_PRIV_KEY),
+        receiver_on_other_chain: String::new(),
+        sender_on_other_chain: String::new(),
+        amount: uiris("1500000"),
+        hash_lock: hex::encode(sha256(&SECRET).as_slice()),
+        timestamp: 0,
+        time_lock: 1000,
+        transfer: false,
+    }
+}
+
+fn htlc_from_msg(msg: &MsgCreateHtlc, expiration_height: u64, state: HtlcState) -> Htlc {
+    Htlc {
+        id: htlc_id(msg).unwrap(),
+        sender: msg.sender.clone(),
+        to: msg.to.clone(),
+        amount: msg.amount.clone(),
+        hash_lock: msg.hash_lock.to_uppercase(),
+        timestamp: msg.timestamp,
+        expiration_height,
+        state: state as i32,
+        transfer: msg.transfer,
+        ..Default::default()
+    }
+}
+
+fn abci_response<M: Message>(msg: &M) -> Json {
+    json!({
+        "response": {
+            "code": 0,
+            "log": "",
+            "value": base64::encode(msg.encode_to_vec()),
+        }
+    })
+}
+
+fn abci_not_found() -> Json {
+    json!({
+        "response": {
+            "code": 38,
+            "log": "not found",
+        }
+    })
+}
+
+fn tx_response(tx: &TxRaw, height: u64, code: u32) -> Json {
+    json!({
+        "hash": hex::encode_upper(sha256(&tx.encode_to_vec()).as_slice()),
+        "height": height.to_string(),
+        "tx_result": {
+            "code": code,
+            "log": "",
+            "events": [],
+        },
+        "tx": base64::encode(tx.encode_to_vec()),
+    })
+}
+
+/// Mocks the Tendermint RPC, the `handler` receives the method and params and returns the `result` of the response.
+fn mock_rpc<F>(handler: F)
+where
+    F: Fn(&str, &Json) -> Result<Json, TendermintRpcError> + 'static,
+{
+    TendermintRpcClient::rpc_request.mock_safe(move |_, method, params| {
+        let result = handler(method, &params).map_err(MmError::new);
+        MockResult::Return(Box::pin(futures::future::ready(result)))
+    });
+}
+
+/// Mocks the HTLC query returning the `htlc` and the `status` returning the `latest_block`.
+fn mock_htlc_query(htlc: Option<Htlc>, latest_block: u64) {
+    mock_rpc(move |method, params| match method {
+        "abci_query" if params["path"] == HTLC_QUERY_PATH => Ok(match htlc {
+            Some(ref htlc) => abci_response(&QueryHtlcResponse {
+                htlc: Some(htlc.clone()),
+            }),
+            None => abci_not_found(),
+        }),
+        "status" => Ok(json!({ "sync_info": { "latest_block_height": latest_block.to_string() } })),
+        _ => panic!("Unexpected '{}' request", method),
+    });
+}
+
+fn validate_payment_input(payment: &TxRaw, time_lock: u32) -> ValidatePaymentInput {
+    ValidatePaymentInput {
+        payment_tx: payment.encode_to_vec(),
+        time_lock,
+        other_pub: pubkey_of(&OTHER_PRIV_KEY),
+        secret_hash: sha256(&SECRET).to_vec(),
+        amount: "1.5".parse().unwrap(),
+        swap_contract_address: None,
+        try_spv_proof_until: 0,
+        confirmations: 1,
+        unique_swap_data: Vec::new(),
+    }
+}
+
+#[test]
+fn test_validate_htlc_payment() {
+    let coin = tendermint_coin_for_test();
+    let msg = create_htlc_msg_for_test();
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &msg));
+    let now = (now_ms() / 1000) as u32;
+
+    // The HTLC expires in (1000 - 100 - HTLC_EXPIRATION_DRIFT_BLOCKS) * 5 = 4450 seconds.
+    mock_htlc_query(Some(htlc_from_msg(&msg, 1000, HtlcState::Open)), 100);
+    coin.validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap();
+
+    // The drift must be subtracted from the time left.
+    let error = coin
+        .validate_maker_payment(validate_payment_input(&payment, now + 4500))
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("earlier than the time lock"), "{}", error);
+
+    // The HTLC is already claimed.
+    mock_htlc_query(Some(htlc_from_msg(&msg, 1000, HtlcState::Completed)), 100);
+    let error = coin
+        .validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("is not open"), "{}", error);
+
+    // The HTLC is not created.
+    mock_htlc_query(None, 100);
+    let error = coin
+        .validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("is not found"), "{}", error);
+
+    // The queried HTLC has the timestamp set.
+    let mut htlc = htlc_from_msg(&msg, 1000, HtlcState::Open);
+    htlc.timestamp = 1;
+    mock_htlc_query(Some(htlc), 100);
+    let error = coin
+        .validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("timestamp 1 is not allowed"), "{}", error);
+
+    // The queried HTLC is an HTLT to another chain.
+    let mut htlc = htlc_from_msg(&msg, 1000, HtlcState::Open);
+    htlc.transfer = true;
+    mock_htlc_query(Some(htlc), 100);
+    let error = coin
+        .validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("transfer to another chain"), "{}", error);
+}
+
+#[test]
+fn test_validate_create_htlc_msg_malicious() {
+    let msg = create_htlc_msg_for_test();
+    let sender = address_of(&OTHER_PRIV_KEY);
+    let to = address_of(&MY_PRIV_KEY);
+    let secret_hash = sha256(&SECRET);
+    let amount = uiris("1500000");
+    validate_create_htlc_msg(&msg, &sender, &to, secret_hash.as_slice(), &amount).unwrap();
+
+    // The hash lock is sha256(secret ‖ timestamp), so the swap secret doesn't unlock it.
+    let mut malicious = msg.clone();
+    malicious.timestamp = now_ms() / 1000;
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("timestamp"), "{}", error);
+
+    let mut malicious = msg.clone();
+    malicious.transfer = true;
+    malicious.receiver_on_other_chain = "0x0000000000000000000000000000000000000000".to_owned();
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("transfer"), "{}", error);
+
+    let mut malicious = msg.clone();
+    malicious.amount = uiris("1499999");
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("Invalid HTLC amount"), "{}", error);
+
+    let mut malicious = msg.clone();
+    malicious.amount[0].denom = "ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2".to_owned();
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("Invalid HTLC amount"), "{}", error);
+
+    let mut malicious = msg.clone();
+    malicious.to = address_of(&FEE_PRIV_KEY);
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("Invalid HTLC receiver"), "{}", error);
+
+    let mut malicious = msg.clone();
+    malicious.sender = address_of(&FEE_PRIV_KEY);
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("Invalid HTLC sender"), "{}", error);
+
+    let mut malicious = msg;
+    malicious.hash_lock = hex::encode(sha256(&[5; 32]).as_slice());
+    let error = validate_create_htlc_msg(&malicious, &sender, &to, secret_hash.as_slice(), &amount).unwrap_err();
+    assert!(error.contains("Invalid HTLC hash lock"), "{}", error);
+}
+
+#[test]
+fn test_validate_htlc_payment_malicious_msg() {
+    let coin = tendermint_coin_for_test();
+    let now = (now_ms() / 1000) as u32;
+    let msg = create_htlc_msg_for_test();
+    // The HTLC query must not be reached.
+    mock_rpc(|method, _| panic!("Unexpected '{}' request", method));
+
+    let mut malicious = msg.clone();
+    malicious.timestamp = 1;
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &malicious));
+    coin.validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+
+    let mut malicious = msg.clone();
+    malicious.transfer = true;
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &malicious));
+    coin.validate_taker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+
+    let mut malicious = msg.clone();
+    malicious.amount = uiris("1000000");
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &malicious));
+    coin.validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+
+    let mut malicious = msg;
+    malicious.to = address_of(&OTHER_PRIV_KEY);
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &malicious));
+    coin.validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+
+    // Not an HTLC at all.
+    let send = MsgSend {
+        from_address: address_of(&OTHER_PRIV_KEY),
+        to_address: address_of(&MY_PRIV_KEY),
+        amount: uiris("1500000"),
+    };
+    let payment = tx_with_msg(encode_any(MSG_SEND_TYPE_URL, &send));
+    coin.validate_maker_payment(validate_payment_input(&payment, now + 3600))
+        .wait()
+        .unwrap_err();
+}
+
+#[test]
+fn test_htlc_expires_at() {
+    // 1000 - 100 - 10 blocks of 5 seconds.
+    assert_eq!(htlc_expires_at(1000, 100, 1_000_000, 5), 1_004_450);
+    // Expires within the drift.
+    assert_eq!(htlc_expires_at(1000, 995, 1_000_000, 5), 1_000_000);
+    // Already expired.
+    assert_eq!(htlc_expires_at(1000, 1100, 1_000_000, 5), 1_000_000);
+}
+
+#[test]
+fn test_extract_secret() {
+    let coin = tendermint_coin_for_test();
+    let msg = create_htlc_msg_for_test();
+    let claim = MsgClaimHtlc {
+        sender: address_of(&MY_PRIV_KEY),
+        id: htlc_id(&msg).unwrap(),
+        secret: hex::encode(SECRET),
+    };
+    let claim_tx = tx_with_msg(encode_any(MSG_CLAIM_HTLC_TYPE_URL, &claim)).encode_to_vec();
+
+    let secret_hash = sha256(&SECRET);
+    assert_eq!(coin.extract_secret(secret_hash.as_slice(), &claim_tx).unwrap(), SECRET);
+
+    let other_hash = sha256(&[5; 32]);
+    let error = coin.extract_secret(other_hash.as_slice(), &claim_tx).unwrap_err();
+    assert!(error.contains("doesn't match"), "{}", error);
+
+    // The payment doesn't contain the secret.
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &msg)).encode_to_vec();
+    let error = coin.extract_secret(secret_hash.as_slice(), &payment).unwrap_err();
+    assert!(error.contains(MSG_CLAIM_HTLC_TYPE_URL), "{}", error);
+}
+
+#[test]
+fn test_search_for_htlc_spend() {
+    let coin = tendermint_coin_for_test();
+    let msg = create_htlc_msg_for_test();
+    let id = htlc_id(&msg).unwrap();
+    let payment = tx_with_msg(encode_any(MSG_CREATE_HTLC_TYPE_URL, &msg));
+    let payment_bytes = payment.encode_to_vec();
+    let claim = MsgClaimHtlc {
+        sender: msg.to.clone(),
+        id: id.clone(),
+        secret: hex::encode(SECRET),
+    };
+    let claim_tx = tx_with_msg(encode_any(MSG_CLAIM_HTLC_TYPE_URL, &claim));
+    let search_input = || SearchForSwapTxSpendInput {
+        time_lock: 0,
+        other_pub: &[],
+        secret_hash: &[],
+        tx: &payment_bytes,
+        search_from_block: 0,
+        swap_contract_address: &None,
+        swap_unique_data: &[],
+    };
+
+    mock_htlc_query(None, 100);
+    assert_eq!(
+        block_on(coin.search_for_swap_tx_spend_my(search_input())).unwrap(),
+        None
+    );
+
+    mock_htlc_query(Some(htlc_from_msg(&msg, 1000, HtlcState::Open)), 100);
+    assert_eq!(
+        block_on(coin.search_for_swap_tx_spend_my(search_input())).unwrap(),
+        None
+    );
+
+    mock_htlc_query(Some(htlc_from_msg(&msg, 1000, HtlcState::Refunded)), 1100);
+    match block_on(coin.search_for_swap_tx_spend_other(search_input())).unwrap() {
+        Some(FoundSwapTxSpend::Refunded(tx)) => assert_eq!(tx.tx_hex(), payment_bytes),
+        found => panic!("Expected the refunded HTLC, found {:?}", found),
+    }
+
+    let htlc = htlc_from_msg(&msg, 1000, HtlcState::Completed);
+    let expected_query = format!("claim_htlc.id='{}'", id);
+    let claim_response = tx_response(&claim_tx, 150, 0);
+    mock_rpc(move |method, params| match method {
+        "abci_query" => Ok(abci_response(&QueryHtlcResponse {
+            htlc: Some(htlc.clone()),
+        })),
+        "tx_search" => {
+            assert_eq!(params["query"], expected_query);
+            Ok(json!({ "txs": [claim_response.clone()], "total_count": "1" }))
+        },
+        _ => panic!("Unexpected '{}' request", method),
+    });
+    match block_on(coin.search_for_swap_tx_spend_my(search_input())).unwrap() {
+        Some(FoundSwapTxSpend::Spent(tx)) => assert_eq!(tx.tx_hex(), claim_tx.encode_to_vec()),
+        found => panic!("Expected the spent HTLC, found {:?}", found),
+    }
+
+    // The HTLC is claimed, but the claim transaction is not indexed.
+    let htlc = htlc_from_msg(&msg, 1000, HtlcState::Completed);
+    mock_rpc(move |method, _| match method {
+        "abci_query" => Ok(abci_response(&QueryHtlcResponse {
+            htlc: Some(htlc.clone()),
+        })),
+        "tx_search" => Ok(json!({ "txs": [], "total_count": "0" })),
+        _ => panic!("Unexpected '{}' request", method),
+    });
+    block_on(coin.search_for_swap_tx_spend_my(search_input())).unwrap_err();
+}
+
+#[test]
+fn test_validate_fee() {
+    let coin = tendermint_coin_for_test();
+    let sender_pub = pubkey_of(&OTHER_PRIV_KEY);
+    let fee_pub = pubkey_of(&FEE_PRIV_KEY);
+    let fee_msg = MsgSend {
+        from_address: address_of(&OTHER_PRIV_KEY),
+        to_address: address_of(&FEE_PRIV_KEY),
+        amount: uiris("1000"),
+    };
+    let fee_tx = tx_with_msg(encode_any(MSG_SEND_TYPE_URL, &fee_msg));
+    let fee_tx_enum: TransactionEnum = CosmosTransaction { data: fee_tx.clone() }.into();
+    let amount: BigDecimal = "0.001".parse().unwrap();
+
+    let mock_fee_tx = |height: u64, code: u32| {
+        let response = tx_response(&fee_tx, height, code);
+        mock_rpc(move |method, _| match method {
+            "tx" => Ok(response.clone()),
+            _ => panic!("Unexpected '{}' request", method),
+        });
+    };
+
+    mock_fee_tx(50, 0);
+    coin.validate_fee(&fee_tx_enum, &sender_pub, &fee_pub, &amount, 40, &[])
+        .wait()
+        .unwrap();
+
+    let error = coin
+        .validate_fee(&fee_tx_enum, &sender_pub, &fee_pub, &amount, 60, &[])
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("less than the minimal"), "{}", error);
+
+    let error = coin
+        .validate_fee(&fee_tx_enum, &fee_pub, &fee_pub, &amount, 40, &[])
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("Invalid fee sender"), "{}", error);
+
+    let error = coin
+        .validate_fee(&fee_tx_enum, &sender_pub, &sender_pub, &amount, 40, &[])
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("Invalid fee receiver"), "{}", error);
+
+    let greater_amount: BigDecimal = "0.0011".parse().unwrap();
+    let error = coin
+        .validate_fee(&fee_tx_enum, &sender_pub, &fee_pub, &greater_amount, 40, &[])
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("Invalid fee amount"), "{}", error);
+
+    mock_fee_tx(50, 5);
+    let error = coin
+        .validate_fee(&fee_tx_enum, &sender_pub, &fee_pub, &amount, 40, &[])
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("The fee transaction failed"), "{}", error);
+
+    mock_rpc(|method, _| match method {
+        "tx" => Err(TendermintRpcError::Rpc {
+            code: -32603,
+            message: "Internal error: tx not found".to_owned(),
+        }),
+        _ => panic!("Unexpected '{}' request", method),
+    });
+    let error = coin
+        .validate_fee(&fee_tx_enum, &sender_pub, &fee_pub, &amount, 40, &[])
+        .wait()
+        .unwrap_err();
+    assert!(error.contains("is not found"), "{}", error);
+}
+
+/// Mocks the account with 10 IRIS and the simulation using 100000 gas.
+fn mock_withdraw_queries() {
+    mock_rpc(|method, params| {
+        assert_eq!(method, "abci_query");
+        let response = match params["path"].as_str() {
+            Some(ACCOUNT_QUERY_PATH) => {
+                let account = BaseAccount {
+                    address: address_of(&MY_PRIV_KEY),
+                    pub_key: None,
+                    account_number: 5,
+                    sequence: 3,
+                };
+                abci_response(&QueryAccountResponse {
+                    account: Some(encode_any(BASE_ACCOUNT_TYPE_URL, &account)),
+                })
+            },
+            Some(BALANCE_QUERY_PATH) => abci_response(&QueryBalanceResponse {
+                balance: uiris("10000000").pop(),
+            }),
+            Some(SIMULATE_PATH) => abci_response(&SimulateResponse {
+                gas_info: Some(GasInfo {
+                    gas_wanted: 0,
+                    gas_used: 100_000,
+                }),
+            }),
+            path => panic!("Unexpected {:?} query", path),
+        };
+        Ok(response)
+    });
+}
+
+#[test]
+fn test_withdraw() {
+    let coin = tendermint_coin_for_test();
+    let to = address_of(&OTHER_PRIV_KEY);
+    mock_withdraw_queries();
+
+    let req = WithdrawRequest::new(
+        "IRIS".to_owned(),
+        None,
+        to.clone(),
+        1.into(),
+        false,
+        None,
+        Some("memo".to_owned()),
+    );
+    let details = coin.withdraw(req).wait().unwrap();
+    // The gas limit is 100000 * 1.5, the fee is 150000 * 0.25 uiris.
+    let fee: BigDecimal = "0.0375".parse().unwrap();
+    assert_eq!(details.spent_by_me, BigDecimal::from(1) + &fee);
+    assert_eq!(details.received_by_me, BigDecimal::zero());
+    assert_eq!(details.my_balance_change, -(BigDecimal::from(1) + &fee));
+    assert_eq!(details.to, vec![to.clone()]);
+    let expected_fee = TendermintFeeDetails {
+        coin: "IRIS".to_owned(),
+        amount: fee.clone(),
+        gas_limit: 150_000,
+    };
+    assert_eq!(details.fee_details, Some(TxFeeDetails::Tendermint(expected_fee)));
+
+    let tx = TxRaw::decode(details.tx_hex.0.as_slice()).unwrap();
+    let body = TxBody::decode(tx.body_bytes.as_slice()).unwrap();
+    assert_eq!(body.memo, "memo");
+    let msg: MsgSend = find_tx_msg(&tx, MSG_SEND_TYPE_URL).unwrap().unwrap();
+    assert_eq!(msg.from_address, address_of(&MY_PRIV_KEY));
+    assert_eq!(msg.to_address, to);
+    assert_eq!(msg.amount, uiris("1000000"));
+    let auth_info = AuthInfo::decode(tx.auth_info_bytes.as_slice()).unwrap();
+    assert_eq!(auth_info.signer_infos[0].sequence, 3);
+    assert_eq!(auth_info.fee.unwrap().amount, uiris("37500"));
+    assert_eq!(tx.signatures.len(), 1);
+
+    // The fee is deducted from the max amount.
+    let details = coin
+        .withdraw(WithdrawRequest::new_max("IRIS".to_owned(), to.clone()))
+        .wait()
+        .unwrap();
+    assert_eq!(details.spent_by_me, BigDecimal::from(10));
+    let msg: MsgSend = find_tx_msg(&TxRaw::decode(details.tx_hex.0.as_slice()).unwrap(), MSG_SEND_TYPE_URL)
+        .unwrap()
+        .unwrap();
+    assert_eq!(msg.amount, uiris("9962500"));
+
+    let req = WithdrawRequest::new("IRIS".to_owned(), None, to, 10.into(), false, None, None);
+    match coin.withdraw(req).wait().unwrap_err().into_inner() {
+        WithdrawError::NotSufficientBalance {
+            available, required, ..
+        } => {
+            assert_eq!(available, BigDecimal::from(10));
+            assert_eq!(required, BigDecimal::from(10) + fee);
+        },
+        e => panic!("Expected NotSufficientBalance, found {:?}", e),
+    }
+
+    let req = WithdrawRequest::new_max("IRIS".to_owned(), "cosmos1invalid".to_owned());
+    match coin.withdraw(req).wait().unwrap_err().into_inner() {
+        WithdrawError::InvalidAddress(_) => (),
+        e => panic!("Expected InvalidAddress, found {:?}", e),
+    }
+}
@@ -0,0 +1,442 @@
+//! The IBC denom tokens (e.g. `ibc/27394FB092D2ECCD56123C74F36E4C1F926001CEADA9CA97EA622B25F41E5EB2`)
+//! held on the Tendermint platform coin account. The fees are paid in the platform coin.
+
+use super::TendermintCoin;
+use crate::my_tx_history_v2::{load_history_from_storage, CoinWithTxHistoryV2, MyTxHistoryErrorV2};
+use crate::tx_history_storage::{GetTxHistoryFilters, WalletId};
+use crate::utxo::utxo_common::big_decimal_from_sat_unsigned;
+use crate::{BalanceFut, CanRefundHtlc, CoinBalance, FeeApproxStage, FoundSwapTxSpend, HistorySyncState, MarketCoinOps,
+            MmCoin, NegotiateSwapContractAddrErr, RawTransactionFut, RawTransactionRequest, SearchForSwapTxSpendInput,
+            SignatureResult, SwapOps, TradeFee, TradePreimageFut, TradePreimageResult, TradePreimageValue,
+            TransactionDetails, TransactionEnum, TransactionFut, TxHistoryFut, UnexpectedDerivationMethod,
+            ValidateAddressResult, ValidatePaymentInput, VerificationResult, WithdrawFut, WithdrawRequest};
+use async_trait::async_trait;
+use futures::{FutureExt, TryFutureExt};
+use futures01::Future;
+use keys::KeyPair;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::bigdecimal::{BigDecimal, Zero};
+use mm2_number::MmNumber;
+use rpc::v1::types::Bytes as BytesJson;
+use serde_json::Value as Json;
+use std::ops::Deref;
+use std::sync::Arc;
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct TendermintTokenProtocolInfo {
+    pub platform: String,
+    pub decimals: u8,
+    pub denom: String,
+}
+
+/// pImpl idiom.
+#[derive(Debug)]
+pub struct TendermintTokenImpl {
+    ticker: String,
+    platform_coin: TendermintCoin,
+    decimals: u8,
+    denom: String,
+}
+
+#[derive(Clone, Debug)]
+pub struct TendermintToken(Arc<TendermintTokenImpl>);
+
+impl Deref for TendermintToken {
+    type Target = TendermintTokenImpl;
+    fn deref(&self) -> &TendermintTokenImpl { &self.0 }
+}
+
+impl TendermintToken {
+    pub fn new(ticker: String, platform_coin: TendermintCoin, decimals: u8, denom: String) -> TendermintToken {
+        TendermintToken(Arc::new(TendermintTokenImpl {
+            ticker,
+            platform_coin,
+            decimals,
+            denom,
+        }))
+    }
+
+    pub fn platform_coin(&self) -> &TendermintCoin { &self.platform_coin }
+
+    pub fn denom(&self) -> &str { &self.denom }
+}
+
+impl MarketCoinOps for TendermintToken {
+    fn ticker(&self) -> &str { &self.ticker }
+
+    fn my_address(&self) -> Result<String, String> { self.platform_coin.my_address() }
+
+    fn get_public_key(&self) -> Result<String, MmError<UnexpectedDerivationMethod>> {
+        self.platform_coin.get_public_key()
+    }
+
+    fn sign_message_hash(&self, message: &str) -> Option<[u8; 32]> { self.platform_coin.sign_message_hash(message) }
+
+    fn sign_message(&self, message: &str) -> SignatureResult<String> { self.platform_coin.sign_message(message) }
+
+    fn verify_message(&self, signature: &str, message: &str, address: &str) -> VerificationResult<bool> {
+        self.platform_coin.verify_message(signature, message, address)
+    }
+
+    fn my_balance(&self) -> BalanceFut<CoinBalance> {
+        let token = self.clone();
+        let fut = async move {
+            let spendable = token
+                .platform_coin
+                .balance_for_denom(&token.denom, token.decimals)
+                .await?;
+            Ok(CoinBalance {
+                spendable,
+                unspendable: BigDecimal::zero(),
+            })
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn base_coin_balance(&self) -> BalanceFut<BigDecimal> { self.platform_coin.my_spendable_balance() }
+
+    fn platform_ticker(&self) -> &str { self.platform_coin.ticker() }
+
+    fn send_raw_tx(&self, tx: &str) -> Box<dyn Future<Item = String, Error = String> + Send> {
+        self.platform_coin.send_raw_tx(tx)
+    }
+
+    fn send_raw_tx_bytes(&self, tx: &[u8]) -> Box<dyn Future<Item = String, Error = String> + Send> {
+        self.platform_coin.send_raw_tx_bytes(tx)
+    }
+
+    fn wait_for_confirmations(
+        &self,
+        tx: &[u8],
+        confirmations: u64,
+        requires_nota: bool,
+        wait_until: u64,
+        check_every: u64,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.platform_coin
+            .wait_for_confirmations(tx, confirmations, requires_nota, wait_until, check_every)
+    }
+
+    fn wait_for_tx_spend(
+        &self,
+        transaction: &[u8],
+        wait_until: u64,
+        from_block: u64,
+        swap_contract_address: &Option<BytesJson>,
+    ) -> TransactionFut {
+        self.platform_coin
+            .wait_for_tx_spend(transaction, wait_until, from_block, swap_contract_address)
+    }
+
+    fn tx_enum_from_bytes(&self, bytes: &[u8]) -> Result<TransactionEnum, String> {
+        self.platform_coin.tx_enum_from_bytes(bytes)
+    }
+
+    fn current_block(&self) -> Box<dyn Future<Item = u64, Error = String> + Send> { self.platform_coin.current_block() }
+
+    fn display_priv_key(&self) -> Result<String, String> { self.platform_coin.display_priv_key() }
+
+    fn min_tx_amount(&self) -> BigDecimal { big_decimal_from_sat_unsigned(1, self.decimals) }
+
+    fn min_trading_vol(&self) -> MmNumber { big_decimal_from_sat_unsigned(1, self.decimals).into() }
+}
+
+#[async_trait]
+impl SwapOps for TendermintToken {
+    fn send_taker_fee(&self, fee_addr: &[u8], amount: BigDecimal, uuid: &[u8]) -> TransactionFut {
+        self.platform_coin
+            .send_taker_fee_for_denom(fee_addr, amount, self.denom.clone(), self.decimals, uuid)
+    }
+
+    fn send_maker_payment(
+        &self,
+        time_lock: u32,
+        taker_pub: &[u8],
+        secret_hash: &[u8],
+        amount: BigDecimal,
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.platform_coin.send_htlc_for_denom(
+            time_lock,
+            taker_pub,
+            secret_hash,
+            amount,
+            self.denom.clone(),
+            self.decimals,
+        )
+    }
+
+    fn send_taker_payment(
+        &self,
+        time_lock: u32,
+        maker_pub: &[u8],
+        secret_hash: &[u8],
+        amount: BigDecimal,
+        _swap_contract_address: &Option<BytesJson>,
+        _swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.platform_coin.send_htlc_for_denom(
+            time_lock,
+            maker_pub,
+            secret_hash,
+            amount,
+            self.denom.clone(),
+            self.decimals,
+        )
+    }
+
+    fn send_maker_spends_taker_payment(
+        &self,
+        taker_payment_tx: &[u8],
+        time_lock: u32,
+        taker_pub: &[u8],
+        secret: &[u8],
+        swap_contract_address: &Option<BytesJson>,
+        swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.platform_coin.send_maker_spends_taker_payment(
+            taker_payment_tx,
+            time_lock,
+            taker_pub,
+            secret,
+            swap_contract_address,
+            swap_unique_data,
+        )
+    }
+
+    fn send_taker_spends_maker_payment(
+        &self,
+        maker_payment_tx: &[u8],
+        time_lock: u32,
+        maker_pub: &[u8],
+        secret: &[u8],
+        swap_contract_address: &Option<BytesJson>,
+        swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.platform_coin.send_taker_spends_maker_payment(
+            maker_payment_tx,
+            time_lock,
+            maker_pub,
+            secret,
+            swap_contract_address,
+            swap_unique_data,
+        )
+    }
+
+    fn send_taker_refunds_payment(
+        &self,
+        taker_payment_tx: &[u8],
+        time_lock: u32,
+        maker_pub: &[u8],
+        secret_hash: &[u8],
+        swap_contract_address: &Option<BytesJson>,
+        swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.platform_coin.send_taker_refunds_payment(
+            taker_payment_tx,
+            time_lock,
+            maker_pub,
+            secret_hash,
+            swap_contract_address,
+            swap_unique_data,
+        )
+    }
+
+    fn send_maker_refunds_payment(
+        &self,
+        maker_payment_tx: &[u8],
+        time_lock: u32,
+        taker_pub: &[u8],
+        secret_hash: &[u8],
+        swap_contract_address: &Option<BytesJson>,
+        swap_unique_data: &[u8],
+    ) -> TransactionFut {
+        self.platform_coin.send_maker_refunds_payment(
+            maker_payment_tx,
+            time_lock,
+            taker_pub,
+            secret_hash,
+            swap_contract_address,
+            swap_unique_data,
+        )
+    }
+
+    fn validate_fee(
+        &self,
+        fee_tx: &TransactionEnum,
+        expected_sender: &[u8],
+        fee_addr: &[u8],
+        amount: &BigDecimal,
+        min_block_number: u64,
+        _uuid: &[u8],
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.platform_coin.validate_fee_for_denom(
+            fee_tx,
+            expected_sender,
+            fee_addr,
+            amount,
+            min_block_number,
+            self.denom.clone(),
+            self.decimals,
+        )
+    }
+
+    fn validate_maker_payment(&self, input: ValidatePaymentInput) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.platform_coin
+            .validate_htlc_payment_for_denom(input, self.denom.clone(), self.decimals)
+    }
+
+    fn validate_taker_payment(&self, input: ValidatePaymentInput) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        self.platform_coin
+            .validate_htlc_payment_for_denom(input, self.denom.clone(), self.decimals)
+    }
+
+    fn check_if_my_payment_sent(
+        &self,
+        time_lock: u32,
+        other_pub: &[u8],
+        secret_hash: &[u8],
+        search_from_block: u64,
+        swap_contract_address: &Option<BytesJson>,
+        swap_unique_data: &[u8],
+    ) -> Box<dyn Future<Item = Option<TransactionEnum>, Error = String> + Send> {
+        self.platform_coin.check_if_my_payment_sent(
+            time_lock,
+            other_pub,
+            secret_hash,
+            search_from_block,
+            swap_contract_address,
+            swap_unique_data,
+        )
+    }
+
+    async fn search_for_swap_tx_spend_my(
+        &self,
+        input: SearchForSwapTxSpendInput<'_>,
+    ) -> Result<Option<FoundSwapTxSpend>, String> {
+        self.platform_coin.search_for_htlc_spend(input).await
+    }
+
+    async fn search_for_swap_tx_spend_other(
+        &self,
+        input: SearchForSwapTxSpendInput<'_>,
+    ) -> Result<Option<FoundSwapTxSpend>, String> {
+        self.platform_coin.search_for_htlc_spend(input).await
+    }
+
+    fn extract_secret(&self, secret_hash: &[u8], spend_tx: &[u8]) -> Result<Vec<u8>, String> {
+        self.platform_coin.extract_secret(secret_hash, spend_tx)
+    }
+
+    fn can_refund_htlc(&self, locktime: u64) -> Box<dyn Future<Item = CanRefundHtlc, Error = String> + Send + '_> {
+        self.platform_coin.can_refund_htlc(locktime)
+    }
+
+    fn negotiate_swap_contract_addr(
+        &self,
+        other_side_address: Option<&[u8]>,
+    ) -> Result<Option<BytesJson>, MmError<NegotiateSwapContractAddrErr>> {
+        self.platform_coin.negotiate_swap_contract_addr(other_side_address)
+    }
+
+    fn derive_htlc_key_pair(&self, swap_unique_data: &[u8]) -> KeyPair {
+        self.platform_coin.derive_htlc_key_pair(swap_unique_data)
+    }
+}
+
+#[async_trait]
+impl MmCoin for TendermintToken {
+    fn is_asset_chain(&self) -> bool { false }
+
+    fn withdraw(&self, req: WithdrawRequest) -> WithdrawFut {
+        let token = self.clone();
+        let fut = async move {
+            token
+                .platform_coin
+                .withdraw_denom(token.ticker.clone(), token.denom.clone(), token.decimals, req)
+                .await
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_raw_transaction(&self, req: RawTransactionRequest) -> RawTransactionFut {
+        self.platform_coin.get_raw_transaction(req)
+    }
+
+    fn decimals(&self) -> u8 { self.decimals }
+
+    fn convert_to_address(&self, from: &str, to_address_format: Json) -> Result<String, String> {
+        self.platform_coin.convert_to_address(from, to_address_format)
+    }
+
+    fn validate_address(&self, address: &str) -> ValidateAddressResult { self.platform_coin.validate_address(address) }
+
+    fn process_history_loop(&self, ctx: MmArc) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        self.platform_coin.process_history_loop(ctx)
+    }
+
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+        load_history_from_storage(self, ctx)
+    }
+
+    fn history_sync_status(&self) -> HistorySyncState { self.platform_coin.history_sync_status() }
+
+    fn get_trade_fee(&self) -> Box<dyn Future<Item = TradeFee, Error = String> + Send> {
+        self.platform_coin.get_trade_fee()
+    }
+
+    async fn get_sender_trade_fee(
+        &self,
+        value: TradePreimageValue,
+        _stage: FeeApproxStage,
+    ) -> TradePreimageResult<TradeFee> {
+        self.platform_coin
+            .get_sender_trade_fee_for_denom(&self.ticker, value, &self.denom, self.decimals)
+            .await
+    }
+
+    fn get_receiver_trade_fee(&self, _stage: FeeApproxStage) -> TradePreimageFut<TradeFee> {
+        self.platform_coin.get_receiver_trade_fee_impl()
+    }
+
+    async fn get_fee_to_send_taker_fee(
+        &self,
+        dex_fee_amount: BigDecimal,
+        _stage: FeeApproxStage,
+    ) -> TradePreimageResult<TradeFee> {
+        self.platform_coin
+            .get_fee_to_send_taker_fee_for_denom(&self.ticker, dex_fee_amount, &self.denom, self.decimals)
+            .await
+    }
+
+    fn required_confirmations(&self) -> u64 { self.platform_coin.required_confirmations() }
+
+    fn requires_notarization(&self) -> bool { self.platform_coin.requires_notarization() }
+
+    fn set_required_confirmations(&self, confirmations: u64) {
+        self.platform_coin.set_required_confirmations(confirmations)
+    }
+
+    fn set_requires_notarization(&self, requires_nota: bool) {
+        self.platform_coin.set_requires_notarization(requires_nota)
+    }
+
+    fn swap_contract_address(&self) -> Option<BytesJson> { None }
+
+    fn mature_confirmations(&self) -> Option<u32> { None }
+
+    fn coin_protocol_info(&self) -> Vec<u8> { Vec::new() }
+
+    fn is_coin_protocol_supported(&self, _info: &Option<Vec<u8>>) -> bool { true }
+}
+
+#[async_trait]
+impl CoinWithTxHistoryV2 for TendermintToken {
+    fn history_wallet_id(&self) -> WalletId { self.platform_coin.history_wallet_id() }
+
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        Ok(GetTxHistoryFilters::new().with_token_id(hex::encode(&self.denom)))
+    }
+}
@@ -0,0 +1,260 @@
+//! Fetches the transaction history of the Tendermint platform coin and its activated tokens into [`TxHistoryStorage`].
+//! The history is built from the `transfer` events, so it includes the HTLC transfers as well.
+
+use super::rpc::{TxResponse, TX_SEARCH_PER_PAGE};
+use super::tendermint_pb::{AuthInfo, TxRaw};
+use super::{coins_to_string, parse_coins, TendermintCoin, TendermintFeeDetails};
+use crate::my_tx_history_v2::{CoinWithTxHistoryV2, TxHistoryStorage};
+use crate::tx_history_storage::WalletId;
+use crate::{HistorySyncState, MarketCoinOps, TransactionDetails, TransactionType};
+use bitcrypto::sha256;
+use common::executor::Timer;
+use common::log::error;
+use common::mm_metrics::MetricsArc;
+use mm2_number::bigdecimal::{BigDecimal, Zero};
+use prost::Message;
+use std::collections::HashMap;
+
+/// How often the new transactions are requested, in seconds.
+const HISTORY_SYNC_INTERVAL: f64 = 30.;
+const TRANSFER_EVENT: &str = "transfer";
+
+/// The transfers of a single denom within the transaction.
+#[derive(Default)]
+struct DenomTransfers {
+    spent_by_me: BigDecimal,
+    received_by_me: BigDecimal,
+    from: Vec<String>,
+    to: Vec<String>,
+}
+
+fn push_unique(addresses: &mut Vec<String>, address: &str) {
+    if !addresses.iter().any(|a| a == address) {
+        addresses.push(address.to_owned());
+    }
+}
+
+/// Runs until the returned future is aborted.
+pub async fn tendermint_history_loop(coin: TendermintCoin, storage: impl TxHistoryStorage, metrics: MetricsArc) {
+    let wallet_id = coin.history_wallet_id();
+    if let Err(e) = storage.init(&wallet_id).await {
+        error!(
+            "Error {:?} on the {} tx history storage initialization",
+            e,
+            coin.ticker()
+        );
+        coin.set_history_sync_state(HistorySyncState::Error(json!({ "message": format!("{:?}", e) })));
+        return;
+    }
+    coin.set_history_sync_state(HistorySyncState::NotStarted);
+
+    let mut block_timestamps = HashMap::new();
+    let mut synced_once = false;
+    loop {
+        if !synced_once {
+            coin.set_history_sync_state(HistorySyncState::InProgress(json!({})));
+        }
+        match fetch_new_transactions(
+            &coin,
+            &storage,
+            &wallet_id,
+            metrics.clone(),
+            &mut block_timestamps,
+            synced_once,
+        )
+        .await
+        {
+            Ok(()) => {
+                synced_once = true;
+                coin.set_history_sync_state(HistorySyncState::Finished);
+            },
+            Err(e) => {
+                error!("Error on fetching the {} tx history: {}", coin.ticker(), e);
+                coin.set_history_sync_state(HistorySyncState::Error(json!({ "message": e })));
+            },
+        }
+        Timer::sleep(HISTORY_SYNC_INTERVAL).await;
+    }
+}
+
+/// Requests the transactions sent and received by the coin address.
+/// The transactions are sorted from the newest, so once the history is fully synced,
+/// the pages are requested until one of them contains the known transactions only.
+async fn fetch_new_transactions(
+    coin: &TendermintCoin,
+    storage: &impl TxHistoryStorage,
+    wallet_id: &WalletId,
+    metrics: MetricsArc,
+    block_timestamps: &mut HashMap<u64, u64>,
+    synced_once: bool,
+) -> Result<(), String> {
+    let queries = [
+        format!("{}.sender='{}'", TRANSFER_EVENT, coin.my_address),
+        format!("{}.recipient='{}'", TRANSFER_EVENT, coin.my_address),
+    ];
+    for query in queries.iter() {
+        let mut page = 1;
+        loop {
+            mm_counter!(metrics, "tx.history.request.count", 1,
+                "coin" => coin.ticker().to_owned(), "client" => "tendermint", "method" => "tx_search");
+            let response = try_s!(coin.rpc_client.tx_search(query, page).await);
+            mm_counter!(metrics, "tx.history.response.count", 1,
+                "coin" => coin.ticker().to_owned(), "client" => "tendermint", "method" => "tx_search");
+
+            let mut new_txs = 0;
+            for tx in response.txs.iter() {
+                if try_s!(storage.history_has_tx_hash(wallet_id, &tx.hash.to_lowercase()).await) {
+                    continue;
+                }
+                new_txs += 1;
+                let timestamp = match block_timestamps.get(&tx.height) {
+                    Some(timestamp) => *timestamp,
+                    None => {
+                        let timestamp = try_s!(coin.rpc_client.block_timestamp(tx.height).await);
+                        block_timestamps.insert(tx.height, timestamp);
+                        timestamp
+                    },
+                };
+                let details = try_s!(tx_details_from_response(coin, tx, timestamp));
+                if !details.is_empty() {
+                    try_s!(storage.add_transactions_to_history(wallet_id, details).await);
+                }
+            }
+
+            let fetched = page as u64 * TX_SEARCH_PER_PAGE as u64;
+            if (synced_once && new_txs == 0) || fetched >= response.total_count || response.txs.is_empty() {
+                break;
+            }
+            page += 1;
+        }
+    }
+    Ok(())
+}
+
+/// Builds the history records of the platform coin and the activated tokens transferred by the transaction.
+fn tx_details_from_response(
+    coin: &TendermintCoin,
+    response: &TxResponse,
+    timestamp: u64,
+) -> Result<Vec<TransactionDetails>, String> {
+    // The failed transactions don't transfer the coins.
+    if response.tx_result.code != 0 {
+        return Ok(Vec::new());
+    }
+    let tx_bytes = try_s!(response.tx_bytes());
+    let tx = try_s!(TxRaw::decode(tx_bytes.as_slice()));
+    let auth_info = try_s!(AuthInfo::decode(tx.auth_info_bytes.as_slice()));
+    let fee = auth_info.fee.unwrap_or_default();
+    let fee_coins = coins_to_string(&fee.amount);
+
+    let mut transfers: HashMap<String, DenomTransfers> = HashMap::new();
+    let mut fee_paid_by_me = false;
+    for event in response
+        .tx_result
+        .events
+        .iter()
+        .filter(|event| event.kind == TRANSFER_EVENT)
+    {
+        let (sender, recipient, amount) = match (
+            event.attribute("sender"),
+            event.attribute("recipient"),
+            event.attribute("amount"),
+        ) {
+            (Some(sender), Some(recipient), Some(amount)) => (sender, recipient, amount),
+            _ => continue,
+        };
+        let is_sent = sender == coin.my_address;
+        let is_received = recipient == coin.my_address;
+        if !is_sent && !is_received {
+            continue;
+        }
+        // The fee is transferred to the fee collector module before the messages are executed.
+        if is_sent && !fee_paid_by_me && !fee_coins.is_empty() && amount == fee_coins {
+            fee_paid_by_me = true;
+            continue;
+        }
+        for (denom, amount) in try_s!(parse_coins(&amount)) {
+            let denom_transfers = transfers.entry(denom).or_default();
+            if is_sent {
+                denom_transfers.spent_by_me += &amount;
+            }
+            if is_received {
+                denom_transfers.received_by_me += &amount;
+            }
+            push_unique(&mut denom_transfers.from, &sender);
+            push_unique(&mut denom_transfers.to, &recipient);
+        }
+    }
+
+    let fee_details = if fee_paid_by_me {
+        let amount = coin.fee_amount(&fee);
+        let platform_transfers = transfers.entry(coin.denom.clone()).or_default();
+        // The fee amount is in the platform coin units, so it's converted back to the denom units.
+        platform_transfers.spent_by_me += &amount * BigDecimal::from(10u64.pow(coin.decimals as u32));
+        push_unique(&mut platform_transfers.from, &coin.my_address);
+        Some(
+            TendermintFeeDetails {
+                coin: coin.ticker().to_owned(),
+                amount,
+                gas_limit: fee.gas_limit,
+            }
+            .into(),
+        )
+    } else {
+        None
+    };
+
+    let tx_hash = try_s!(hex::decode(&response.hash));
+    let tokens_info = coin.get_activated_tokens_info();
+    let mut details = Vec::with_capacity(transfers.len());
+    for (denom, transfers) in transfers {
+        let (ticker, decimals, transaction_type, internal_id) = if denom == coin.denom {
+            (
+                coin.ticker().to_owned(),
+                coin.decimals,
+                TransactionType::StandardTransfer,
+                tx_hash.clone(),
+            )
+        } else if let Some(token_info) = tokens_info.get(&denom) {
+            let mut internal_id_preimage = tx_hash.clone();
+            internal_id_preimage.extend(denom.as_bytes());
+            (
+                token_info.ticker.clone(),
+                token_info.decimals,
+                TransactionType::TokenTransfer(denom.into_bytes().into()),
+                sha256(&internal_id_preimage).to_vec(),
+            )
+        } else {
+            // The denom of a token that isn't activated.
+            continue;
+        };
+
+        let denominator = BigDecimal::from(10u64.pow(decimals as u32));
+        let spent_by_me = transfers.spent_by_me / &denominator;
+        let received_by_me = transfers.received_by_me / &denominator;
+        let total_amount = if spent_by_me.is_zero() {
+            received_by_me.clone()
+        } else {
+            spent_by_me.clone()
+        };
+        details.push(TransactionDetails {
+            tx_hex: tx_bytes.clone().into(),
+            tx_hash: response.hash.to_lowercase(),
+            from: transfers.from,
+            to: transfers.to,
+            total_amount,
+            my_balance_change: &received_by_me - &spent_by_me,
+            spent_by_me,
+            received_by_me,
+            block_height: response.height,
+            timestamp,
+            fee_details: fee_details.clone(),
+            coin: ticker,
+            internal_id: internal_id.into(),
+            kmd_rewards: None,
+            spv_verified: None,
+            transaction_type,
+        });
+    }
+    Ok(details)
+}
@@ -1,8 +1,9 @@
 use super::{CoinBalance, HistorySyncState, MarketCoinOps, MmCoin, RawTransactionFut, RawTransactionRequest, SwapOps,
             TradeFee, TransactionEnum, TransactionFut};
 use crate::{BalanceFut, FeeApproxStage, FoundSwapTxSpend, NegotiateSwapContractAddrErr, SearchForSwapTxSpendInput,
-            SignatureResult, TradePreimageFut, TradePreimageResult, TradePreimageValue, UnexpectedDerivationMethod,
-            ValidateAddressResult, ValidatePaymentInput, VerificationResult, WithdrawFut, WithdrawRequest};
+            SignatureResult, TradePreimageFut, TradePreimageResult, TradePreimageValue, UnbroadcastSwapPaymentInput,
+            UnexpectedDerivationMethod, ValidateAddressResult, ValidatePaymentInput, VerificationResult, WithdrawFut,
+            WithdrawRequest};
 use async_trait::async_trait;
 use futures01::Future;
 use keys::KeyPair;
@@ -227,7 +228,18 @@ impl SwapOps for TestCoin {
         unimplemented!()
     }
 
+    fn swap_v2_contract_address(&self) -> Option<BytesJson> { unimplemented!() }
+
     fn derive_htlc_key_pair(&self, _swap_unique_data: &[u8]) -> KeyPair { unimplemented!() }
+
+    fn gen_unbroadcast_swap_payment(&self, input: UnbroadcastSwapPaymentInput) -> TransactionFut { unimplemented!() }
+
+    fn validate_unbroadcast_swap_payment(
+        &self,
+        input: ValidatePaymentInput,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        unimplemented!()
+    }
 }
 
 #[async_trait]
@@ -122,7 +122,7 @@ impl WalletId {
     }
 }
 
-#[derive(Debug, Default)]
+#[derive(Clone, Debug, Default)]
 pub struct GetTxHistoryFilters {
     token_id: Option<String>,
     for_addresses: Option<FilteringAddresses>,
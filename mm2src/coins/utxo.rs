@@ -22,8 +22,9 @@
 //
 
 pub mod bch;
-pub mod bch_and_slp_tx_history;
 mod bchd_grpc;
+pub mod electrum_health;
+pub mod kmd_rewards_claim;
 #[allow(clippy::all)]
 #[rustfmt::skip]
 #[path = "utxo/pb.rs"]
@@ -34,7 +35,9 @@ pub mod slp;
 pub mod utxo_block_header_storage;
 pub mod utxo_builder;
 pub mod utxo_common;
+pub mod utxo_spv;
 pub mod utxo_standard;
+pub mod utxo_tx_history_v2;
 pub mod utxo_withdraw;
 
 use async_trait::async_trait;
@@ -82,6 +85,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 use std::sync::atomic::{AtomicBool, AtomicU64};
 use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use utxo_builder::UtxoConfBuilder;
 use utxo_common::{big_decimal_from_sat, UtxoTxBuilder};
 use utxo_signer::with_key_pair::sign_tx;
@@ -105,6 +109,10 @@ use crate::utxo::utxo_block_header_storage::BlockHeaderStorageError;
 use crate::TransactionErr;
 use utxo_block_header_storage::BlockHeaderStorage;
 
+#[cfg(not(target_arch = "wasm32"))]
+use self::compact_filters::CompactFiltersClient;
+
+#[cfg(not(target_arch = "wasm32"))] pub mod compact_filters;
 pub mod tx_cache;
 #[cfg(target_arch = "wasm32")]
 pub mod utxo_indexedb_block_header_storage;
@@ -697,8 +705,12 @@ pub trait UtxoTxGenerationOps {
 /// right on `UtxoAddressBalanceScanner` initialization.
 /// See [`NativeClientImpl::list_transactions`].
 pub enum UtxoAddressScanner {
-    Native { non_empty_addresses: HashSet<String> },
+    Native {
+        non_empty_addresses: HashSet<String>,
+    },
     Electrum(ElectrumClient),
+    #[cfg(not(target_arch = "wasm32"))]
+    CompactFilters(CompactFiltersClient),
 }
 
 #[async_trait]
@@ -719,6 +731,15 @@ impl HDAddressBalanceScanner for UtxoAddressScanner {
 
                 !electrum_history.is_empty()
             },
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoAddressScanner::CompactFilters(client) => {
+                let script = output_script(address, ScriptType::P2PKH).to_vec();
+                let history = client
+                    .script_history(vec![script])
+                    .await
+                    .map_to_mm(BalanceError::Transport)?;
+                !history.is_empty()
+            },
         };
         Ok(is_used)
     }
@@ -729,6 +750,8 @@ impl UtxoAddressScanner {
         match rpc_client {
             UtxoRpcClientEnum::Native(native) => UtxoAddressScanner::init_with_native_client(&native).await,
             UtxoRpcClientEnum::Electrum(electrum) => Ok(UtxoAddressScanner::Electrum(electrum)),
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(client) => Ok(UtxoAddressScanner::CompactFilters(client)),
         }
     }
 
@@ -941,8 +964,6 @@ pub trait UtxoStandardOps {
         input_transactions: &mut HistoryUtxoTxMap,
     ) -> Result<TransactionDetails, String>;
 
-    async fn request_tx_history(&self, metrics: MetricsArc) -> RequestTxHistoryResult;
-
     /// Calculate the KMD rewards and re-calculate the transaction fee
     /// if the specified `tx_details` was generated without considering the KMD rewards.
     /// Please note, this method has to be used for KMD transactions only.
@@ -1180,15 +1201,40 @@ impl RpcTransportEventHandler for ElectrumProtoVerifier {
         try_s!(self.on_connect_tx.unbounded_send(address));
         Ok(())
     }
+
+    fn on_request_completed(&self, _server: &str, _latency: Duration, _success: bool) {}
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
 pub struct UtxoMergeParams {
+    /// The unspent outputs are merged once their number reaches this value.
+    /// In the [`UtxoMergeMode::Split`] mode, the outputs are not split further once their number reaches this value.
     pub merge_at: usize,
     #[serde(default = "common::ten_f64")]
     pub check_every: f64,
+    /// The max number of the inputs of the consolidation transaction.
     #[serde(default = "common::one_hundred")]
     pub max_merge_at_once: usize,
+    /// The consolidation is postponed while the fee rate exceeds this value, in satoshis per kB.
+    /// The fee rate isn't limited by default, since the reasonable limit differs from coin to coin.
+    #[serde(default)]
+    pub max_fee_per_kb: Option<u64>,
+    #[serde(default)]
+    pub mode: UtxoMergeMode,
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum UtxoMergeMode {
+    /// Merge the unspent outputs into one.
+    Merge,
+    /// Split the unspent outputs into the ones sized to the open maker orders of the coin,
+    /// so the swaps don't wait for the change confirmations.
+    Split,
+}
+
+impl Default for UtxoMergeMode {
+    fn default() -> Self { UtxoMergeMode::Merge }
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -1197,6 +1243,18 @@ pub struct UtxoBlockHeaderVerificationParams {
     pub constant_difficulty: bool,
     pub blocks_limit_to_check: NonZeroU64,
     pub check_every: f64,
+    /// Whether the unspent outputs and the history transactions returned by the electrum server
+    /// are verified against the stored block headers. See [`utxo_spv`].
+    #[serde(default)]
+    pub spv_mode: bool,
+    /// The trusted block header the stored headers chain is pinned to.
+    pub checkpoint: Option<BlockHeaderCheckpoint>,
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct BlockHeaderCheckpoint {
+    pub height: u64,
+    pub hash: H256Json,
 }
 
 #[derive(Clone, Debug, Deserialize, Serialize)]
@@ -1281,7 +1339,16 @@ impl UtxoActivationParams {
 #[serde(tag = "rpc", content = "rpc_data")]
 pub enum UtxoRpcMode {
     Native,
-    Electrum { servers: Vec<ElectrumRpcRequest> },
+    Electrum {
+        servers: Vec<ElectrumRpcRequest>,
+    },
+    /// Syncs the BIP157/158 compact block filters from the Bitcoin P2P `peers` starting from the `sync_from` block.
+    /// The wallet transactions mined before the `sync_from` block aren't found.
+    /// The coin is wallet only in this mode since the unconfirmed transactions of the counterparty aren't visible.
+    CompactFilters {
+        peers: Vec<String>,
+        sync_from: BlockHeaderCheckpoint,
+    },
 }
 
 #[derive(Debug)]
@@ -1577,6 +1644,21 @@ where
     generate_and_send_tx(&coin, unspents, None, FeePolicy::SendExact, recently_sent_txs, outputs).await
 }
 
+/// Generates and signs tx from my address without broadcasting it.
+/// Note the spent unspents are not added to the recently_spent since the tx is not expected to be broadcasted.
+async fn sign_outputs_from_my_address_impl<T>(
+    coin: T,
+    outputs: Vec<TransactionOutput>,
+) -> Result<UtxoTx, TransactionErr>
+where
+    T: UtxoCommonOps + GetUtxoListOps,
+{
+    let my_address = try_tx_s!(coin.as_ref().derivation_method.iguana_or_err());
+    let (unspents, _recently_sent_txs) = try_tx_s!(coin.get_unspent_ordered_list(my_address).await);
+    let (signed, _spent_unspents) = generate_and_sign_tx(&coin, unspents, None, FeePolicy::SendExact, outputs).await?;
+    Ok(signed)
+}
+
 /// Generates and sends tx using unspents and outputs adding new record to the recently_spent in case of success
 async fn generate_and_send_tx<T>(
     coin: &T,
@@ -1588,6 +1670,27 @@ async fn generate_and_send_tx<T>(
 ) -> Result<UtxoTx, TransactionErr>
 where
     T: AsRef<UtxoCoinFields> + UtxoTxGenerationOps + UtxoTxBroadcastOps,
+{
+    let (signed, spent_unspents) = generate_and_sign_tx(coin, unspents, required_inputs, fee_policy, outputs).await?;
+
+    try_tx_s!(coin.broadcast_tx(&signed).await, signed);
+
+    recently_spent.add_spent(spent_unspents, signed.hash(), signed.outputs.clone());
+
+    Ok(signed)
+}
+
+/// Generates and signs tx using unspents and outputs.
+/// Returns the signed tx and the unspents spent by it.
+async fn generate_and_sign_tx<T>(
+    coin: &T,
+    unspents: Vec<UnspentInfo>,
+    required_inputs: Option<Vec<UnspentInfo>>,
+    fee_policy: FeePolicy,
+    outputs: Vec<TransactionOutput>,
+) -> Result<(UtxoTx, Vec<UnspentInfo>), TransactionErr>
+where
+    T: AsRef<UtxoCoinFields> + UtxoTxGenerationOps,
 {
     let my_address = try_tx_s!(coin.as_ref().derivation_method.iguana_or_err());
     let key_pair = try_tx_s!(coin.as_ref().priv_key_policy.key_pair_or_err());
@@ -1625,11 +1728,7 @@ where
         coin.as_ref().conf.fork_id
     ));
 
-    try_tx_s!(coin.broadcast_tx(&signed).await, signed);
-
-    recently_spent.add_spent(spent_unspents, signed.hash(), signed.outputs.clone());
-
-    Ok(signed)
+    Ok((signed, spent_unspents))
 }
 
 pub fn output_script(address: &Address, script_type: ScriptType) -> Script {
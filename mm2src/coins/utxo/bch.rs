@@ -1,15 +1,18 @@
 use super::*;
-use crate::my_tx_history_v2::{CoinWithTxHistoryV2, TxDetailsBuilder, TxHistoryStorage, TxHistoryStorageError};
+use crate::my_tx_history_v2::{load_history_from_storage, CoinWithTxHistoryV2, MyTxHistoryErrorV2, TxDetailsBuilder,
+                              TxHistoryStorage, TxHistoryStorageError};
 use crate::tx_history_storage::{GetTxHistoryFilters, WalletId};
 use crate::utxo::rpc_clients::UtxoRpcFut;
 use crate::utxo::slp::{parse_slp_script, ParseSlpScriptError, SlpGenesisParams, SlpTokenInfo, SlpTransaction,
                        SlpUnspent};
 use crate::utxo::utxo_builder::{UtxoArcBuilder, UtxoCoinBuilder};
 use crate::utxo::utxo_common::big_decimal_from_sat_unsigned;
+use crate::utxo::utxo_tx_history_v2::{process_history_loop_v2, UtxoMyAddressesHistoryError, UtxoTxHistoryOps};
 use crate::{BlockHeightAndTime, CanRefundHtlc, CoinBalance, CoinProtocol, NegotiateSwapContractAddrErr,
             PrivKeyBuildPolicy, RawTransactionFut, RawTransactionRequest, SearchForSwapTxSpendInput, SignatureResult,
-            SwapOps, TradePreimageValue, TransactionFut, TransactionType, TxFeeDetails, UnexpectedDerivationMethod,
-            ValidateAddressResult, ValidatePaymentInput, VerificationResult, WithdrawFut};
+            SwapOps, TradePreimageValue, TransactionFut, TransactionType, TxFeeDetails, TxHistoryFut,
+            UnbroadcastSwapPaymentInput, UnexpectedDerivationMethod, ValidateAddressResult, ValidatePaymentInput,
+            VerificationResult, WithdrawFut};
 use common::log::warn;
 use common::mm_metrics::MetricsArc;
 use derive_more::Display;
@@ -383,18 +386,7 @@ impl BchCoin {
         tx_hash: &H256Json,
         storage: &T,
     ) -> Result<UtxoTx, MmError<GetTxDetailsError<T::Error>>> {
-        let tx_hash_str = format!("{:02x}", tx_hash);
-        let wallet_id = self.history_wallet_id();
-        let tx_bytes = match storage.tx_bytes_from_cache(&wallet_id, &tx_hash_str).await? {
-            Some(tx_bytes) => tx_bytes,
-            None => {
-                let tx_bytes = self.as_ref().rpc_client.get_transaction_bytes(tx_hash).compat().await?;
-                storage.add_tx_to_cache(&wallet_id, &tx_hash_str, &tx_bytes).await?;
-                tx_bytes
-            },
-        };
-        let tx = deserialize(tx_bytes.0.as_slice())?;
-        Ok(tx)
+        utxo_common::tx_from_storage_or_rpc(self, tx_hash, storage).await
     }
 
     /// Returns multiple details by tx hash if token transfers also occurred in the transaction
@@ -1044,6 +1036,17 @@ impl SwapOps for BchCoin {
     fn derive_htlc_key_pair(&self, swap_unique_data: &[u8]) -> KeyPair {
         utxo_common::derive_htlc_key_pair(self.as_ref(), swap_unique_data)
     }
+
+    fn gen_unbroadcast_swap_payment(&self, input: UnbroadcastSwapPaymentInput) -> TransactionFut {
+        utxo_common::gen_unbroadcast_swap_payment(self.clone(), input)
+    }
+
+    fn validate_unbroadcast_swap_payment(
+        &self,
+        input: ValidatePaymentInput,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        utxo_common::validate_unbroadcast_swap_payment(self, input)
+    }
 }
 
 fn total_unspent_value<'a>(unspents: impl IntoIterator<Item = &'a UnspentInfo>) -> u64 {
@@ -1155,10 +1158,6 @@ impl UtxoStandardOps for BchCoin {
         utxo_common::tx_details_by_hash(self, hash, input_transactions).await
     }
 
-    async fn request_tx_history(&self, metrics: MetricsArc) -> RequestTxHistoryResult {
-        utxo_common::request_tx_history(self, metrics).await
-    }
-
     async fn update_kmd_rewards(
         &self,
         tx_details: &mut TransactionDetails,
@@ -1172,6 +1171,8 @@ impl UtxoStandardOps for BchCoin {
 impl MmCoin for BchCoin {
     fn is_asset_chain(&self) -> bool { utxo_common::is_asset_chain(&self.utxo_arc) }
 
+    fn wallet_only(&self, ctx: &MmArc) -> bool { utxo_common::wallet_only(&self.utxo_arc, ctx) }
+
     fn get_raw_transaction(&self, req: RawTransactionRequest) -> RawTransactionFut {
         Box::new(utxo_common::get_raw_transaction(&self.utxo_arc, req).boxed().compat())
     }
@@ -1190,13 +1191,17 @@ impl MmCoin for BchCoin {
 
     fn process_history_loop(&self, ctx: MmArc) -> Box<dyn Future<Item = (), Error = ()> + Send> {
         Box::new(
-            utxo_common::process_history_loop(self.clone(), ctx)
+            process_history_loop_v2(self.clone(), ctx)
                 .map(|_| Ok(()))
                 .boxed()
                 .compat(),
         )
     }
 
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+        load_history_from_storage(self, ctx)
+    }
+
     fn history_sync_status(&self) -> HistorySyncState { utxo_common::history_sync_status(&self.utxo_arc) }
 
     fn get_trade_fee(&self) -> Box<dyn Future<Item = TradeFee, Error = String> + Send> {
@@ -1246,11 +1251,57 @@ impl MmCoin for BchCoin {
     }
 }
 
+#[async_trait]
 impl CoinWithTxHistoryV2 for BchCoin {
-    fn history_wallet_id(&self) -> WalletId { WalletId::new(self.ticker().to_owned()) }
+    fn history_wallet_id(&self) -> WalletId { utxo_common::history_wallet_id(self.as_ref()) }
+
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        utxo_common::get_tx_history_filters(self).await
+    }
+}
+
+#[async_trait]
+impl UtxoTxHistoryOps for BchCoin {
+    async fn my_addresses(&self) -> MmResult<HashSet<Address>, UtxoMyAddressesHistoryError> {
+        utxo_common::my_addresses(self).await
+    }
+
+    async fn request_tx_history(
+        &self,
+        metrics: MetricsArc,
+        for_addresses: &HashSet<Address>,
+    ) -> RequestTxHistoryResult {
+        utxo_common::request_tx_history(self, metrics, for_addresses).await
+    }
+
+    async fn get_block_timestamp(&self, height: u64) -> MmResult<u64, UtxoRpcError> {
+        self.as_ref().rpc_client.get_block_timestamp(height).await
+    }
 
-    /// There are not specific filters for `BchCoin`.
-    fn get_tx_history_filters(&self) -> GetTxHistoryFilters { GetTxHistoryFilters::new() }
+    /// `BchCoin` is activated with an iguana private key only,
+    /// so its transactions and SLP token transfers are built for the iguana address.
+    async fn tx_details_by_hash<Storage: TxHistoryStorage>(
+        &self,
+        tx_hash: &H256Json,
+        block_height_and_time: Option<BlockHeightAndTime>,
+        storage: &Storage,
+        _my_addresses: &HashSet<Address>,
+    ) -> MmResult<Vec<TransactionDetails>, GetTxDetailsError<Storage::Error>> {
+        self.transaction_details_with_token_transfers(tx_hash, block_height_and_time, storage)
+            .await
+    }
+
+    async fn spv_verify_tx(
+        &self,
+        tx_hash: &H256Json,
+        height: u64,
+    ) -> MmResult<Option<bool>, utxo_spv::SpvVerificationError> {
+        utxo_spv::spv_verify_tx(self.as_ref(), tx_hash, height).await
+    }
+
+    fn set_history_sync_state(&self, new_state: HistorySyncState) {
+        *self.as_ref().history_sync_state.lock().unwrap() = new_state;
+    }
 }
 
 // testnet
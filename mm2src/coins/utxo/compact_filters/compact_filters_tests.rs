@@ -0,0 +1,447 @@
+use super::p2p::{read_message, VersionMessage, MSG_TX, NODE_COMPACT_FILTERS, NODE_NETWORK, NODE_WITNESS,
+                 PROTOCOL_VERSION};
+use super::*;
+use chain::{BlockHeaderNonce, TransactionInput, TransactionOutput};
+use common::{block_on, now_ms};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{TcpListener, TcpStream};
+
+const TICKER: &str = "BTC-regtest";
+const REGTEST_MAGIC: u32 = 0xDAB5_BFFA;
+/// The regtest genesis block header, it's the checkpoint the client syncs from.
+const REGTEST_GENESIS_HEADER: &str = "0100000000000000000000000000000000000000000000000000000000000000000000003ba3edfd7a7b12b27ac72c3e67768f617fc81bc3888a51323a9fb8aa4b1e5e4adae5494dffff7f2002000000";
+const PEER_FEE_RATE: u64 = 2000;
+
+/// The regtest chain served by the test peer, `blocks[i]` is at the `i` height.
+#[derive(Clone)]
+struct TestChain {
+    blocks: Vec<Block>,
+    filters: Vec<Vec<u8>>,
+    filter_headers: Vec<H256>,
+    /// Whether the peer rejects the transactions sent to it.
+    reject_txs: bool,
+}
+
+impl TestChain {
+    fn new() -> TestChain {
+        TestChain {
+            blocks: vec![Block::new(REGTEST_GENESIS_HEADER.into(), Vec::new())],
+            filters: vec![Vec::new()],
+            filter_headers: vec![H256::default()],
+            reject_txs: false,
+        }
+    }
+
+    fn tip_height(&self) -> u64 { self.blocks.len() as u64 - 1 }
+
+    /// Mines the block with the coinbase paying to the `coinbase_script` followed by the `txs`.
+    fn mine_block(&mut self, coinbase_script: &[u8], txs: Vec<UtxoTx>) -> Block {
+        let height = self.blocks.len() as u32;
+        let coinbase = UtxoTx {
+            version: 1,
+            inputs: vec![TransactionInput::coinbase(height.to_le_bytes().to_vec().into())],
+            outputs: vec![TransactionOutput {
+                value: 50 * 100_000_000,
+                script_pubkey: coinbase_script.to_vec().into(),
+            }],
+            ..Default::default()
+        };
+        let prev_header = &self.blocks.last().unwrap().block_header;
+        let mut header = prev_header.clone();
+        header.previous_header_hash = prev_header.hash();
+        header.time = prev_header.time + 600;
+        let mut block = Block::new(header, std::iter::once(coinbase).chain(txs).collect());
+        block.block_header.merkle_root_hash = block.merkle_root();
+        for nonce in 0.. {
+            block.block_header.nonce = BlockHeaderNonce::U32(nonce);
+            if validate_headers(vec![block.block_header.clone()], true, true).is_ok() {
+                break;
+            }
+        }
+
+        let filter = gcs::build_basic_filter(&block.hash(), &self.filter_items(&block));
+        let prev_filter_header = self.filter_headers.last().unwrap().clone();
+        let mut preimage = dhash256(&filter).to_vec();
+        preimage.extend_from_slice(&*prev_filter_header);
+        self.filter_headers.push(dhash256(&preimage));
+        self.filters.push(filter);
+        self.blocks.push(block.clone());
+        block
+    }
+
+    /// The output scripts of the block and the scripts of the outputs it spends.
+    fn filter_items(&self, block: &Block) -> Vec<Vec<u8>> {
+        let mut items: Vec<Vec<u8>> = block
+            .transactions
+            .iter()
+            .flat_map(|tx| tx.outputs.iter().map(|output| output.script_pubkey.to_vec()))
+            .collect();
+        for input in block.transactions.iter().flat_map(|tx| tx.inputs.iter()) {
+            let prev_output = self
+                .blocks
+                .iter()
+                .flat_map(|block| block.transactions.iter())
+                .find(|tx| tx.hash() == input.previous_output.hash)
+                .and_then(|tx| tx.outputs.get(input.previous_output.index as usize));
+            if let Some(prev_output) = prev_output {
+                items.push(prev_output.script_pubkey.to_vec());
+            }
+        }
+        items
+    }
+
+    fn height_of(&self, hash: &H256) -> Option<usize> { self.blocks.iter().position(|block| block.hash() == *hash) }
+
+    fn respond(&self, message: NetworkMessage, txs: &mpsc::UnboundedSender<UtxoTx>) -> Vec<NetworkMessage> {
+        match message {
+            NetworkMessage::Version(_) => vec![
+                NetworkMessage::Version(VersionMessage {
+                    version: PROTOCOL_VERSION,
+                    services: NODE_NETWORK | NODE_WITNESS | NODE_COMPACT_FILTERS,
+                    user_agent: "/Satoshi:24.0.1/".to_owned(),
+                    start_height: self.tip_height() as i32,
+                }),
+                NetworkMessage::Verack,
+                NetworkMessage::FeeFilter(PEER_FEE_RATE),
+            ],
+            NetworkMessage::GetHeaders { locator, .. } => {
+                let fork_height = locator.iter().find_map(|hash| self.height_of(hash)).unwrap_or_default();
+                let headers = self.blocks[fork_height + 1..]
+                    .iter()
+                    .map(|block| block.block_header.clone())
+                    .collect();
+                vec![NetworkMessage::Headers(headers)]
+            },
+            NetworkMessage::GetCFHeaders {
+                start_height,
+                stop_hash,
+            } => {
+                let start_height = start_height as usize;
+                let stop_height = self.height_of(&stop_hash).unwrap();
+                vec![NetworkMessage::CFHeaders {
+                    stop_hash,
+                    previous_filter_header: self.filter_headers[start_height - 1].clone(),
+                    filter_hashes: self.filters[start_height..=stop_height]
+                        .iter()
+                        .map(|filter| dhash256(filter))
+                        .collect(),
+                }]
+            },
+            NetworkMessage::GetCFilters {
+                start_height,
+                stop_hash,
+            } => {
+                let stop_height = self.height_of(&stop_hash).unwrap();
+                (start_height as usize..=stop_height)
+                    .map(|height| NetworkMessage::CFilter {
+                        block_hash: self.blocks[height].hash(),
+                        filter: self.filters[height].clone(),
+                    })
+                    .collect()
+            },
+            NetworkMessage::GetData(items) => items
+                .iter()
+                .filter(|item| item.inv_type & MSG_BLOCK != 0)
+                .filter_map(|item| self.height_of(&item.hash))
+                .map(|height| NetworkMessage::Block(self.blocks[height].clone()))
+                .collect(),
+            NetworkMessage::Ping(nonce) => vec![NetworkMessage::Pong(nonce)],
+            NetworkMessage::Tx(tx) if self.reject_txs => vec![NetworkMessage::Reject {
+                message: "tx".to_owned(),
+                code: 0x42,
+                reason: "insufficient fee".to_owned(),
+                hash: Some(tx.hash()),
+            }],
+            NetworkMessage::Tx(tx) => {
+                txs.unbounded_send(tx).unwrap();
+                Vec::new()
+            },
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// Serves the `chain` to every connected client and forwards the transactions they send to `txs`.
+async fn serve_peer(listener: TcpListener, chain: Arc<TestChain>, txs: mpsc::UnboundedSender<UtxoTx>) {
+    async fn serve_connection(stream: TcpStream, chain: Arc<TestChain>, txs: mpsc::UnboundedSender<UtxoTx>) {
+        let (mut reader, mut writer) = stream.into_split();
+        while let Ok(message) = read_message(&mut reader, REGTEST_MAGIC, TICKER).await {
+            for response in chain.respond(message, &txs) {
+                writer.write_all(&response.to_bytes(REGTEST_MAGIC)).await.unwrap();
+            }
+        }
+    }
+
+    loop {
+        let (stream, _) = listener.accept().await.unwrap();
+        tokio::spawn(serve_connection(stream, chain.clone(), txs.clone()));
+    }
+}
+
+/// Spawns the test peers serving the `chains` and returns their addresses.
+async fn spawn_peers(chains: Vec<TestChain>) -> (Vec<String>, mpsc::UnboundedReceiver<UtxoTx>) {
+    let (txs_tx, txs_rx) = mpsc::unbounded();
+    let mut peers = Vec::new();
+    for chain in chains {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        peers.push(listener.local_addr().unwrap().to_string());
+        tokio::spawn(serve_peer(listener, Arc::new(chain), txs_tx.clone()));
+    }
+    (peers, txs_rx)
+}
+
+fn regtest_client(peers: Vec<String>, chain: &TestChain, chain_path: Option<PathBuf>) -> CompactFiltersClient {
+    let checkpoint = BlockHeaderCheckpoint {
+        height: 0,
+        hash: chain.blocks[0].hash().reversed().into(),
+    };
+    CompactFiltersClient::new(
+        TICKER.to_owned(),
+        peers,
+        checkpoint,
+        REGTEST_MAGIC,
+        8,
+        true,
+        None,
+        chain_path,
+    )
+}
+
+fn spend_tx(prev_tx: &UtxoTx, index: u32, outputs: Vec<TransactionOutput>) -> UtxoTx {
+    UtxoTx {
+        version: 1,
+        inputs: vec![TransactionInput {
+            previous_output: OutPoint {
+                hash: prev_tx.hash(),
+                index,
+            },
+            script_sig: vec![0x51].into(),
+            sequence: u32::MAX,
+            script_witness: Vec::new(),
+        }],
+        outputs,
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_compact_filters_client_list_unspent_and_send() {
+    let address = Address::from("R9o9xTocqr6CeEDGDH6mEYpwLoMz6jNjMW");
+    let my_script = output_script(&address, ScriptType::P2PKH).to_vec();
+    let other_script = vec![0x51];
+
+    let mut chain = TestChain::new();
+    chain.mine_block(&other_script, Vec::new());
+    let received = chain.mine_block(&my_script, Vec::new()).transactions[0].clone();
+    let spent = spend_tx(&received, 0, vec![
+        TransactionOutput {
+            value: 10 * 100_000_000,
+            script_pubkey: other_script.clone().into(),
+        },
+        TransactionOutput {
+            value: 39 * 100_000_000,
+            script_pubkey: my_script.clone().into(),
+        },
+    ]);
+    chain.mine_block(&other_script, vec![spent.clone()]);
+    let checkpoint = BlockHeaderCheckpoint {
+        height: 0,
+        hash: chain.blocks[0].hash().reversed().into(),
+    };
+    let chain = Arc::new(chain);
+
+    block_on(async move {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let peer = listener.local_addr().unwrap().to_string();
+        let (txs_tx, mut txs_rx) = mpsc::unbounded();
+        tokio::spawn(serve_peer(listener, chain, txs_tx));
+
+        let client = CompactFiltersClient::new(
+            TICKER.to_owned(),
+            vec![peer],
+            checkpoint,
+            REGTEST_MAGIC,
+            8,
+            true,
+            None,
+            None,
+        );
+
+        let unspents = client.list_unspent(&address, 8).compat().await.unwrap();
+        let expected = vec![UnspentInfo {
+            outpoint: OutPoint {
+                hash: spent.hash(),
+                index: 1,
+            },
+            value: 39 * 100_000_000,
+            height: Some(3),
+        }];
+        assert_eq!(unspents, expected);
+        assert_eq!(client.get_block_count().compat().await.unwrap(), 3);
+        assert_eq!(
+            client.get_relay_fee().compat().await.unwrap(),
+            big_decimal_from_sat_unsigned(PEER_FEE_RATE, 8)
+        );
+
+        let spent_txid: H256Json = spent.hash().reversed().into();
+        let verbose = client.get_verbose_transaction(&spent_txid).compat().await.unwrap();
+        assert_eq!(verbose.height, Some(3));
+        assert_eq!(verbose.confirmations, 1);
+        let history = client.script_history(vec![my_script.clone()]).await.unwrap();
+        let expected_history = vec![(spent_txid, 3), (received.hash().reversed().into(), 2)];
+        assert_eq!(history, expected_history);
+
+        let new_tx = spend_tx(&spent, 1, vec![TransactionOutput {
+            value: 38 * 100_000_000,
+            script_pubkey: my_script.clone().into(),
+        }]);
+        let new_txid = client.send_transaction(&new_tx).compat().await.unwrap();
+        assert_eq!(new_txid, H256Json::from(new_tx.hash().reversed()));
+        let relayed = Box::pin(txs_rx.next()).timeout_secs(30.).await.unwrap().unwrap();
+        assert_eq!(relayed, new_tx);
+
+        let unspents = client.list_unspent(&address, 8).compat().await.unwrap();
+        let expected = vec![UnspentInfo {
+            outpoint: OutPoint {
+                hash: new_tx.hash(),
+                index: 0,
+            },
+            value: 38 * 100_000_000,
+            height: None,
+        }];
+        assert_eq!(unspents, expected);
+    });
+}
+
+#[test]
+fn test_network_message_roundtrip() {
+    let mut chain = TestChain::new();
+    let block = chain.mine_block(&[0x51], Vec::new());
+    let messages = vec![
+        NetworkMessage::Headers(vec![block.block_header.clone()]),
+        NetworkMessage::Block(block.clone()),
+        NetworkMessage::GetData(vec![InvVect {
+            inv_type: MSG_TX,
+            hash: block.transactions[0].hash(),
+        }]),
+        NetworkMessage::Reject {
+            message: "tx".to_owned(),
+            code: 0x42,
+            reason: "insufficient fee".to_owned(),
+            hash: Some(block.transactions[0].hash()),
+        },
+    ];
+    for message in messages {
+        let bytes = message.to_bytes(REGTEST_MAGIC);
+        let parsed = NetworkMessage::from_payload(message.command(), &bytes[24..], TICKER).unwrap();
+        assert_eq!(format!("{:?}", parsed), format!("{:?}", message));
+    }
+}
+
+#[test]
+fn test_compact_filters_client_send_rejected() {
+    let address = Address::from("R9o9xTocqr6CeEDGDH6mEYpwLoMz6jNjMW");
+    let my_script = output_script(&address, ScriptType::P2PKH).to_vec();
+
+    let mut chain = TestChain::new();
+    let received = chain.mine_block(&my_script, Vec::new()).transactions[0].clone();
+    chain.reject_txs = true;
+
+    block_on(async move {
+        let (peers, _txs_rx) = spawn_peers(vec![chain.clone()]).await;
+        let client = regtest_client(peers, &chain, None);
+        let unspents = client.list_unspent(&address, 8).compat().await.unwrap();
+        assert_eq!(unspents.len(), 1);
+
+        let new_tx = spend_tx(&received, 0, vec![TransactionOutput {
+            value: 49 * 100_000_000,
+            script_pubkey: my_script.clone().into(),
+        }]);
+        let error = client.send_transaction(&new_tx).compat().await.unwrap_err();
+        assert!(error.to_string().contains("insufficient fee"), "{}", error);
+
+        // The rejected transaction doesn't spend the wallet unspents.
+        let unspents = client.list_unspent(&address, 8).compat().await.unwrap();
+        assert_eq!(unspents[0].outpoint.hash, received.hash());
+    });
+}
+
+#[test]
+fn test_compact_filters_client_rescans_new_scripts_only() {
+    let address = Address::from("R9o9xTocqr6CeEDGDH6mEYpwLoMz6jNjMW");
+    let my_script = output_script(&address, ScriptType::P2PKH).to_vec();
+    let other_script = vec![0x51];
+
+    let mut chain = TestChain::new();
+    chain.mine_block(&other_script, Vec::new());
+    chain.mine_block(&my_script, Vec::new());
+
+    block_on(async move {
+        // Both peers serve the same filters, so the cross-check passes.
+        let (peers, _txs_rx) = spawn_peers(vec![chain.clone(), chain.clone()]).await;
+        let client = regtest_client(peers, &chain, None);
+        let unspents = client.list_unspent(&address, 8).compat().await.unwrap();
+        assert_eq!(unspents.len(), 1);
+        assert_eq!(client.state.lock().filter_headers, chain.filter_headers[1..].to_vec());
+
+        client.watch_scripts(vec![my_script.clone(), other_script.clone()]);
+        {
+            let state = client.state.lock();
+            assert_eq!(state.scripts[&my_script], 2);
+            assert_eq!(state.scripts[&other_script], 0);
+        }
+        let history = client.script_history(vec![other_script.clone()]).await.unwrap();
+        let expected: Vec<(H256Json, u64)> = vec![(chain.blocks[1].transactions[0].hash().reversed().into(), 1)];
+        assert_eq!(history, expected);
+        assert_eq!(client.state.lock().scanned_height(), 2);
+    });
+}
+
+#[test]
+fn test_compact_filters_client_cross_check_mismatch() {
+    let mut chain = TestChain::new();
+    chain.mine_block(&[0x51], Vec::new());
+    chain.mine_block(&[0x52], Vec::new());
+    let mut forged = chain.clone();
+    forged.filters[2] = gcs::build_basic_filter(&chain.blocks[2].hash(), &[vec![0x53]]);
+
+    block_on(async move {
+        let (peers, _txs_rx) = spawn_peers(vec![chain.clone(), forged]).await;
+        let client = regtest_client(peers, &chain, None);
+        client.watch_scripts(std::iter::once(vec![0x51]));
+        Timer::sleep(3.).await;
+
+        let state = client.state.lock();
+        assert_eq!(state.tip_height(), 2);
+        assert!(state.filter_headers.is_empty());
+        assert!(!state.synced);
+    });
+}
+
+#[test]
+fn test_compact_filters_client_saves_chain() {
+    let address = Address::from("R9o9xTocqr6CeEDGDH6mEYpwLoMz6jNjMW");
+    let my_script = output_script(&address, ScriptType::P2PKH).to_vec();
+    let mut chain = TestChain::new();
+    chain.mine_block(&my_script, Vec::new());
+    chain.mine_block(&[0x51], Vec::new());
+    let chain_path = std::env::temp_dir().join(format!("compact_filters_test_{}", now_ms()));
+
+    block_on(async move {
+        let (peers, _txs_rx) = spawn_peers(vec![chain.clone()]).await;
+        let client = regtest_client(peers, &chain, Some(chain_path.clone()));
+        client.list_unspent(&address, 8).compat().await.unwrap();
+
+        // The client without reachable peers starts from the saved chain.
+        let restored = regtest_client(Vec::new(), &chain, Some(chain_path.clone()));
+        let state = restored.state.lock();
+        assert_eq!(state.tip_height(), 2);
+        assert_eq!(state.header_hashes, vec![
+            chain.blocks[1].hash(),
+            chain.blocks[2].hash()
+        ]);
+        assert_eq!(state.checkpoint_filter_header, Some(chain.filter_headers[0].clone()));
+        assert_eq!(state.filter_headers, chain.filter_headers[1..].to_vec());
+        std::fs::remove_file(&chain_path).ok();
+    });
+}
@@ -0,0 +1,170 @@
+//! The BIP158 basic block filters encoded as Golomb-coded sets.
+//! https://github.com/bitcoin/bips/blob/master/bip-0158.mediawiki
+
+use bitcrypto::siphash24;
+use byteorder::{ByteOrder, LittleEndian};
+use keys::hash::H256;
+use serialization::{parse_compact_int, CompactInteger};
+
+/// The Golomb-Rice coding parameter of the basic filter.
+const BASIC_FILTER_P: u8 = 19;
+/// The inverse of the basic filter false positive rate.
+const BASIC_FILTER_M: u64 = 784931;
+
+/// The SipHash keys are the first 16 bytes of the block hash in the internal byte order.
+fn siphash_keys(block_hash: &H256) -> (u64, u64) {
+    let k0 = LittleEndian::read_u64(&block_hash[0..8]);
+    let k1 = LittleEndian::read_u64(&block_hash[8..16]);
+    (k0, k1)
+}
+
+/// Maps the `item` hash uniformly onto the `[0, range)` interval.
+fn hash_to_range(k0: u64, k1: u64, range: u64, item: &[u8]) -> u64 {
+    ((siphash24(k0, k1, item) as u128 * range as u128) >> 64) as u64
+}
+
+struct BitReader<'a> {
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> BitReader<'a> { BitReader { data, offset: 0 } }
+
+    fn read_bit(&mut self) -> Result<bool, String> {
+        let byte = self
+            .data
+            .get(self.offset / 8)
+            .ok_or_else(|| ERRL!("Unexpected end of the filter"))?;
+        let bit = byte & (0x80 >> (self.offset % 8)) != 0;
+        self.offset += 1;
+        Ok(bit)
+    }
+
+    fn read_bits(&mut self, count: u8) -> Result<u64, String> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = (value << 1) | self.read_bit()? as u64;
+        }
+        Ok(value)
+    }
+
+    /// Reads the next Golomb-Rice coded value: the unary quotient followed by the `P` bits remainder.
+    fn read_golomb_rice(&mut self) -> Result<u64, String> {
+        let mut quotient = 0u64;
+        while self.read_bit()? {
+            quotient += 1;
+        }
+        let remainder = self.read_bits(BASIC_FILTER_P)?;
+        Ok((quotient << BASIC_FILTER_P) + remainder)
+    }
+}
+
+/// Checks whether the basic `filter` of the block with the given `block_hash` matches any of the `scripts`.
+/// The false positive rate is about 1/784931 per script, so the matched block still has to be checked.
+pub fn filter_matches_any(block_hash: &H256, filter: &[u8], scripts: &[Vec<u8>]) -> Result<bool, String> {
+    if scripts.is_empty() || filter.is_empty() {
+        return Ok(false);
+    }
+    let items_count: u64 = try_s!(parse_compact_int(filter)).into();
+    if items_count == 0 {
+        return Ok(false);
+    }
+    let data_offset = 1 + CompactInteger::data_length(filter[0]) as usize;
+
+    let (k0, k1) = siphash_keys(block_hash);
+    let range = items_count * BASIC_FILTER_M;
+    let mut queries: Vec<u64> = scripts
+        .iter()
+        .map(|script| hash_to_range(k0, k1, range, script))
+        .collect();
+    queries.sort_unstable();
+
+    let mut reader = BitReader::new(&filter[data_offset..]);
+    let mut queries = queries.into_iter().peekable();
+    let mut value = 0u64;
+    for _ in 0..items_count {
+        value += reader.read_golomb_rice()?;
+        while let Some(query) = queries.peek() {
+            if *query == value {
+                return Ok(true);
+            }
+            if *query > value {
+                break;
+            }
+            queries.next();
+        }
+        if queries.peek().is_none() {
+            break;
+        }
+    }
+    Ok(false)
+}
+
+/// Builds the basic filter of the given `items`, the peers do that for the P2P `cfilter` messages.
+#[cfg(test)]
+pub fn build_basic_filter(block_hash: &H256, items: &[Vec<u8>]) -> Vec<u8> {
+    let mut items = items.to_vec();
+    items.sort();
+    items.dedup();
+
+    let (k0, k1) = siphash_keys(block_hash);
+    let range = items.len() as u64 * BASIC_FILTER_M;
+    let mut values: Vec<u64> = items.iter().map(|item| hash_to_range(k0, k1, range, item)).collect();
+    values.sort_unstable();
+
+    let mut bits = Vec::new();
+    let mut last = 0;
+    for value in values {
+        let delta = value - last;
+        last = value;
+        bits.extend(std::iter::repeat(true).take((delta >> BASIC_FILTER_P) as usize));
+        bits.push(false);
+        bits.extend((0..BASIC_FILTER_P).rev().map(|i| (delta >> i) & 1 == 1));
+    }
+
+    let mut filter = serialization::serialize(&CompactInteger::from(items.len())).take();
+    filter.extend(bits.chunks(8).map(|chunk| {
+        chunk
+            .iter()
+            .enumerate()
+            .fold(0u8, |byte, (i, bit)| byte | ((*bit as u8) << (7 - i)))
+    }));
+    filter
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// https://github.com/bitcoin/bips/blob/master/bip-0158/testnet-19.json
+    const TESTNET_GENESIS_HASH: &str = "000000000933ea01ad0ee984209779baaec3ced90fa3f408719526f8d77f4943";
+    const TESTNET_GENESIS_COINBASE_SCRIPT: &str = "4104678afdb0fe5548271967f1a67130b7105cd6a828e03909a67962e0ea1f61deb649f6bc3f4cef38c4f35504e51ec112de5c384df7ba0b8d578a4c702b6bf11d5fac";
+    const TESTNET_GENESIS_FILTER: &str = "019dfca8";
+
+    #[test]
+    fn test_bip158_testnet_genesis_filter() {
+        let block_hash = H256::from(TESTNET_GENESIS_HASH).reversed();
+        let script = hex::decode(TESTNET_GENESIS_COINBASE_SCRIPT).unwrap();
+        let filter = hex::decode(TESTNET_GENESIS_FILTER).unwrap();
+
+        assert_eq!(build_basic_filter(&block_hash, &[script.clone()]), filter);
+        assert!(filter_matches_any(&block_hash, &filter, &[script]).unwrap());
+        assert!(!filter_matches_any(&block_hash, &filter, &[vec![0x51]]).unwrap());
+    }
+
+    #[test]
+    fn test_filter_matches_any() {
+        let block_hash = H256::from([7; 32]);
+        let items: Vec<Vec<u8>> = (0..100u32).map(|i| i.to_le_bytes().to_vec()).collect();
+        let filter = build_basic_filter(&block_hash, &items);
+
+        for item in items.iter() {
+            assert!(filter_matches_any(&block_hash, &filter, &[item.clone()]).unwrap());
+        }
+        let missing = vec![b"missing".to_vec(), b"another missing".to_vec()];
+        assert!(!filter_matches_any(&block_hash, &filter, &missing).unwrap());
+        assert!(filter_matches_any(&block_hash, &filter, &[missing[0].clone(), items[50].clone()]).unwrap());
+        assert!(!filter_matches_any(&block_hash, &filter, &[]).unwrap());
+    }
+}
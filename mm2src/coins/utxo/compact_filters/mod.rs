@@ -0,0 +1,1435 @@
+//! The light client backend speaking the Bitcoin P2P protocol directly instead of trusting an electrum server.
+//!
+//! The client syncs the block headers starting from a trusted checkpoint and checks every header's proof of work.
+//! The BIP157 compact filters of the new blocks are matched against the scripts of the wallet,
+//! so only the blocks paying to or spending from the wallet are downloaded.
+//! The wallet unspents and transactions are collected from these blocks after checking their merkle roots,
+//! the transactions of the wallet are broadcast to the connected peer directly.
+//! The new filter headers are cross-checked with another peer if there is one,
+//! and the synced headers are saved to be loaded on the next start.
+
+use crate::big_decimal_from_sat_unsigned;
+use crate::utxo::rpc_clients::{BlockHashOrHeight, EstimateFeeMethod, EstimateFeeMode, SpentOutputInfo, UnspentInfo,
+                               UnspentMap, UtxoRpcClientOps, UtxoRpcError, UtxoRpcFut, UtxoRpcResult};
+use crate::utxo::{output_script, BlockHeaderCheckpoint};
+use async_trait::async_trait;
+use bitcrypto::dhash256;
+use chain::{Block, BlockHeader, OutPoint, Transaction as UtxoTx};
+use common::custom_futures::FutureTimerExt;
+use common::executor::{spawn, Timer};
+use common::jsonrpc_client::{JsonRpcError, JsonRpcErrorType, JsonRpcRequest, JsonRpcRequestEnum, RpcRes};
+use common::log::{debug, info, warn};
+use common::median;
+use futures::channel::{mpsc, oneshot};
+use futures::compat::Future01CompatExt;
+use futures::{select, FutureExt, StreamExt, TryFutureExt};
+use keys::hash::H256;
+use keys::{Address, Type as ScriptType};
+use mm2_err_handle::prelude::*;
+use mm2_net::socks5::Socks5Proxy;
+use mm2_number::BigDecimal;
+use p2p::{tx_bytes, InvVect, NetworkMessage, PeerConnection, MAX_FILTERS_PER_REQUEST, MAX_HEADERS_PER_MESSAGE,
+          MSG_BLOCK, MSG_WITNESS_FLAG};
+use parking_lot::Mutex as PaMutex;
+use rpc::v1::types::{Bytes as BytesJson, CoinbaseTransactionInput, SignedTransactionInput, SignedTransactionOutput,
+                     Transaction as RpcTransaction, TransactionInputEnum, TransactionInputScript,
+                     TransactionOutputScript, H256 as H256Json};
+use script::Script;
+use serialization::{coin_variant_by_ticker, deserialize, CoinVariant, Reader, Stream};
+use spv_validation::helpers_validation::validate_headers;
+use std::collections::{HashMap, HashSet};
+use std::num::NonZeroU64;
+use std::ops::Deref;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+
+mod gcs;
+mod p2p;
+
+#[cfg(test)] mod compact_filters_tests;
+
+const RECONNECT_INTERVAL_S: f64 = 10.;
+const REQUEST_TIMEOUT_S: f64 = 60.;
+/// The headers are requested again if the peer hasn't announced a new block for so long.
+const IDLE_TIMEOUT_S: f64 = 10. * 60.;
+/// The wallet requests waiting for the headers and the filters to be synced fail if the sync doesn't progress for so long.
+const SYNC_TIMEOUT_S: f64 = 120.;
+/// How long the sent transactions wait for a peer to be connected and to accept them.
+const SEND_TIMEOUT_S: f64 = 60.;
+/// The relay fee per kilobyte in satoshis if the peer hasn't sent its `feefilter`.
+const DEFAULT_RELAY_FEE_SAT: u64 = 1000;
+/// The number of the block headers stepped over one by one before the locator steps grow exponentially.
+const LOCATOR_DENSE_PART: usize = 10;
+
+#[derive(Clone, Debug)]
+struct WalletTx {
+    tx: UtxoTx,
+    /// The height of the block the transaction is mined in, `None` if it isn't mined yet.
+    height: Option<u64>,
+    block_hash: H256,
+    block_time: u32,
+}
+
+#[derive(Debug)]
+struct SyncState {
+    checkpoint_height: u64,
+    checkpoint_hash: H256,
+    /// The headers following the checkpoint, `headers[i]` is at the `checkpoint_height + 1 + i` height.
+    headers: Vec<BlockHeader>,
+    header_hashes: Vec<H256>,
+    /// The filter header of the checkpoint block. It's cross-checked with another peer along with the following ones.
+    checkpoint_filter_header: Option<H256>,
+    /// The filter headers aligned with `headers`. They must be the same on every peer.
+    filter_headers: Vec<H256>,
+    /// The watched scripts along with the heights up to which the filters are checked against them,
+    /// so only the new scripts are scanned for in the blocks scanned before.
+    scripts: HashMap<Vec<u8>, u64>,
+    txs: HashMap<H256, WalletTx>,
+    /// The waiters of the transactions which aren't sent to a peer yet.
+    send_waiters: HashMap<H256, Vec<oneshot::Sender<Result<(), String>>>>,
+    /// The minimum fee rate per kilobyte the peer relays the transactions with.
+    relay_fee: Option<u64>,
+    /// Whether the headers and the filters are synced up to the peer's tip.
+    synced: bool,
+    sync_waiters: Vec<oneshot::Sender<()>>,
+    /// Whether the headers or the filter headers are changed since they were saved.
+    chain_changed: bool,
+}
+
+impl SyncState {
+    fn tip_height(&self) -> u64 { self.checkpoint_height + self.headers.len() as u64 }
+
+    /// The height up to which the filters are checked against all the watched scripts.
+    fn scanned_height(&self) -> u64 {
+        self.scripts
+            .values()
+            .min()
+            .copied()
+            .unwrap_or_else(|| self.tip_height())
+    }
+
+    fn hash_at(&self, height: u64) -> Option<&H256> {
+        if height == self.checkpoint_height {
+            return Some(&self.checkpoint_hash);
+        }
+        let index = height.checked_sub(self.checkpoint_height + 1)?;
+        self.header_hashes.get(index as usize)
+    }
+
+    fn header_at(&self, height: u64) -> Option<&BlockHeader> {
+        let index = height.checked_sub(self.checkpoint_height + 1)?;
+        self.headers.get(index as usize)
+    }
+
+    fn height_of(&self, hash: &H256) -> Option<u64> {
+        if *hash == self.checkpoint_hash {
+            return Some(self.checkpoint_height);
+        }
+        let index = self.header_hashes.iter().rposition(|known| known == hash)?;
+        Some(self.checkpoint_height + 1 + index as u64)
+    }
+
+    /// The hashes of the known blocks from the tip back to the checkpoint, dense at the tip and sparse further.
+    fn locator(&self) -> Vec<H256> {
+        let mut locator = Vec::new();
+        let mut index = self.header_hashes.len();
+        let mut step = 1;
+        while index > 0 {
+            locator.push(self.header_hashes[index - 1].clone());
+            if locator.len() >= LOCATOR_DENSE_PART {
+                step *= 2;
+            }
+            index = index.saturating_sub(step);
+        }
+        locator.push(self.checkpoint_hash.clone());
+        locator
+    }
+
+    /// Forgets the blocks above the `height`, their transactions become unconfirmed.
+    fn rollback_to(&mut self, height: u64) {
+        let len = (height - self.checkpoint_height) as usize;
+        self.headers.truncate(len);
+        self.header_hashes.truncate(len);
+        self.filter_headers.truncate(len);
+        for scanned_height in self.scripts.values_mut() {
+            *scanned_height = (*scanned_height).min(height);
+        }
+        for wallet_tx in self.txs.values_mut() {
+            if wallet_tx.height.map_or(false, |tx_height| tx_height > height) {
+                wallet_tx.height = None;
+            }
+        }
+        self.synced = false;
+        self.chain_changed = true;
+    }
+
+    fn is_wallet_output(&self, outpoint: &OutPoint) -> bool {
+        self.txs
+            .get(&outpoint.hash)
+            .and_then(|wallet_tx| wallet_tx.tx.outputs.get(outpoint.index as usize))
+            .map_or(false, |output| self.scripts.contains_key(&output.script_pubkey[..]))
+    }
+
+    fn unspents(&self, script: &[u8]) -> Vec<UnspentInfo> {
+        let spent: HashSet<&OutPoint> = self
+            .txs
+            .values()
+            .flat_map(|wallet_tx| wallet_tx.tx.inputs.iter().map(|input| &input.previous_output))
+            .collect();
+        let mut unspents = Vec::new();
+        for (tx_hash, wallet_tx) in self.txs.iter() {
+            for (index, output) in wallet_tx.tx.outputs.iter().enumerate() {
+                let outpoint = OutPoint {
+                    hash: tx_hash.clone(),
+                    index: index as u32,
+                };
+                if output.script_pubkey[..] == *script && !spent.contains(&outpoint) {
+                    unspents.push(UnspentInfo {
+                        outpoint,
+                        value: output.value,
+                        height: wallet_tx.height,
+                    });
+                }
+            }
+        }
+        unspents
+    }
+
+    fn notify_synced(&mut self) {
+        self.synced = true;
+        for waiter in self.sync_waiters.drain(..) {
+            waiter.send(()).ok();
+        }
+    }
+
+    /// Serializes the checkpoint, the headers and the filter headers following it.
+    fn chain_bytes(&self) -> Vec<u8> {
+        let mut stream = Stream::new();
+        stream
+            .append(&self.checkpoint_height)
+            .append(&self.checkpoint_hash)
+            .append(&self.checkpoint_filter_header.is_some())
+            .append(&self.checkpoint_filter_header.clone().unwrap_or_default())
+            .append_list(&self.headers)
+            .append_list(&self.filter_headers);
+        stream.out().take()
+    }
+
+    /// Restores the chain saved by [`SyncState::chain_bytes`] if it starts from the same checkpoint.
+    /// The headers are validated again, the filter headers are trusted since they're cross-checked before saving.
+    fn restore_chain(&mut self, bytes: &[u8], ticker: &str, difficulty_check: bool) -> Result<(), String> {
+        let mut reader = Reader::new_with_coin_variant(bytes, coin_variant_by_ticker(ticker));
+        let mut read = || -> Result<_, serialization::Error> {
+            let checkpoint_height: u64 = reader.read()?;
+            let checkpoint_hash: H256 = reader.read()?;
+            let has_checkpoint_filter_header: bool = reader.read()?;
+            let checkpoint_filter_header: H256 = reader.read()?;
+            let headers: Vec<BlockHeader> = reader.read_list()?;
+            let filter_headers: Vec<H256> = reader.read_list()?;
+            Ok((
+                checkpoint_height,
+                checkpoint_hash,
+                Some(checkpoint_filter_header).filter(|_| has_checkpoint_filter_header),
+                headers,
+                filter_headers,
+            ))
+        };
+        let (checkpoint_height, checkpoint_hash, checkpoint_filter_header, headers, mut filter_headers) =
+            try_s!(read().map_err(|e| ERRL!("Error parsing the saved chain: {:?}", e)));
+        if checkpoint_height != self.checkpoint_height || checkpoint_hash != self.checkpoint_hash {
+            return ERR!(
+                "The saved chain starts from another checkpoint {} at {}",
+                checkpoint_hash.reversed(),
+                checkpoint_height
+            );
+        }
+        let header_hashes = try_s!(validate_headers_chain(&checkpoint_hash, &headers, difficulty_check));
+        if checkpoint_filter_header.is_none() {
+            filter_headers.clear();
+        }
+        filter_headers.truncate(headers.len());
+
+        self.headers = headers;
+        self.header_hashes = header_hashes;
+        self.checkpoint_filter_header = checkpoint_filter_header;
+        self.filter_headers = filter_headers;
+        Ok(())
+    }
+}
+
+/// Writes the chain to a temporary file first, so the saved chain is never partially written.
+fn write_chain(path: &Path, bytes: &[u8]) -> Result<(), String> {
+    if let Some(dir) = path.parent() {
+        try_s!(std::fs::create_dir_all(dir));
+    }
+    let tmp_path = PathBuf::from(format!("{}.tmp", path.display()));
+    try_s!(std::fs::write(&tmp_path, bytes));
+    try_s!(std::fs::rename(&tmp_path, path));
+    Ok(())
+}
+
+/// Checks that the `headers` follow each other starting from the `prev_hash` and meet their own targets.
+/// The difficulty adjustment rules aren't checked, they're trusted to the checkpoint and the longest chain.
+fn validate_headers_chain(
+    prev_hash: &H256,
+    headers: &[BlockHeader],
+    difficulty_check: bool,
+) -> Result<Vec<H256>, String> {
+    let mut prev_hash = prev_hash.clone();
+    let mut hashes = Vec::with_capacity(headers.len());
+    for header in headers {
+        if header.previous_header_hash != prev_hash {
+            return ERR!(
+                "Header {} doesn't follow {}",
+                header.hash().reversed(),
+                prev_hash.reversed()
+            );
+        }
+        try_s!(validate_headers(vec![header.clone()], difficulty_check, true).map_err(|e| ERRL!("{:?}", e)));
+        prev_hash = header.hash();
+        hashes.push(prev_hash.clone());
+    }
+    Ok(hashes)
+}
+
+#[derive(Debug)]
+pub struct CompactFiltersClientImpl {
+    ticker: String,
+    peers: Vec<String>,
+    magic: u32,
+    decimals: u8,
+    difficulty_check: bool,
+    proxy: Option<Socks5Proxy>,
+    /// The file the synced chain is saved to, it's not saved if `None`.
+    chain_path: Option<PathBuf>,
+    state: PaMutex<SyncState>,
+    /// Wakes up the sync loop when there are new scripts to scan for or new transactions to broadcast.
+    events: mpsc::UnboundedSender<()>,
+}
+
+#[derive(Clone, Debug)]
+pub struct CompactFiltersClient(pub Arc<CompactFiltersClientImpl>);
+
+impl Deref for CompactFiltersClient {
+    type Target = CompactFiltersClientImpl;
+    fn deref(&self) -> &CompactFiltersClientImpl { &*self.0 }
+}
+
+impl CompactFiltersClient {
+    /// Creates the client and spawns the loop syncing the chain from the `sync_from` checkpoint through the `peers`.
+    /// The `magic` is the network magic of the P2P messages.
+    /// The chain saved to the `chain_path` before is loaded, so it's synced from the saved tip.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new(
+        ticker: String,
+        peers: Vec<String>,
+        sync_from: BlockHeaderCheckpoint,
+        magic: u32,
+        decimals: u8,
+        difficulty_check: bool,
+        proxy: Option<Socks5Proxy>,
+        chain_path: Option<PathBuf>,
+    ) -> CompactFiltersClient {
+        let (events, events_rx) = mpsc::unbounded();
+        let mut state = SyncState {
+            checkpoint_height: sync_from.height,
+            checkpoint_hash: sync_from.hash.reversed().into(),
+            headers: Vec::new(),
+            header_hashes: Vec::new(),
+            checkpoint_filter_header: None,
+            filter_headers: Vec::new(),
+            scripts: HashMap::new(),
+            txs: HashMap::new(),
+            send_waiters: HashMap::new(),
+            relay_fee: None,
+            synced: false,
+            sync_waiters: Vec::new(),
+            chain_changed: false,
+        };
+        if let Some(path) = chain_path.as_ref() {
+            match mm2_io::fs::safe_slurp(path) {
+                Ok(bytes) if bytes.is_empty() => (),
+                Ok(bytes) => match state.restore_chain(&bytes, &ticker, difficulty_check) {
+                    Ok(()) => info!("{} loaded {} saved headers", ticker, state.headers.len()),
+                    Err(e) => warn!("{} error loading the saved chain: {}", ticker, e),
+                },
+                Err(e) => warn!("{} error reading the saved chain: {}", ticker, e),
+            }
+        }
+        if peers.len() < 2 {
+            warn!(
+                "{} compact filters can't be cross-checked with a single peer, configure more peers",
+                ticker
+            );
+        }
+        let client = Arc::new(CompactFiltersClientImpl {
+            ticker,
+            peers,
+            magic,
+            decimals,
+            difficulty_check,
+            proxy,
+            chain_path,
+            state: PaMutex::new(state),
+            events,
+        });
+        spawn(sync_loop(Arc::downgrade(&client), events_rx));
+        CompactFiltersClient(client)
+    }
+}
+
+impl CompactFiltersClientImpl {
+    /// Adds the scripts to scan the blocks for. The blocks are rescanned from the checkpoint for the new ones only.
+    pub fn watch_scripts<I>(&self, scripts: I)
+    where
+        I: IntoIterator<Item = Vec<u8>>,
+    {
+        let mut state = self.state.lock();
+        let checkpoint_height = state.checkpoint_height;
+        let mut is_new = false;
+        for script in scripts {
+            if !state.scripts.contains_key(&script) {
+                state.scripts.insert(script, checkpoint_height);
+                is_new = true;
+            }
+        }
+        if is_new {
+            state.synced = false;
+            drop(state);
+            self.events.unbounded_send(()).ok();
+        }
+    }
+
+    /// Waits for the headers and the filters to be synced as long as the sync makes progress,
+    /// so the initial sync from an old checkpoint doesn't fail the requests.
+    async fn wait_for_sync(&self) -> Result<(), String> {
+        let mut last_progress = None;
+        loop {
+            let (synced, progress) = {
+                let mut state = self.state.lock();
+                if state.synced {
+                    return Ok(());
+                }
+                let (tx, rx) = oneshot::channel();
+                state.sync_waiters.push(tx);
+                (rx, (state.tip_height(), state.scanned_height()))
+            };
+            if last_progress == Some(progress) {
+                return ERR!(
+                    "{} compact filters sync hasn't progressed in {} seconds",
+                    self.ticker,
+                    SYNC_TIMEOUT_S
+                );
+            }
+            last_progress = Some(progress);
+            if synced.timeout_secs(SYNC_TIMEOUT_S).await.is_ok() {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn synced_unspents(&self, script: Vec<u8>) -> Result<Vec<UnspentInfo>, String> {
+        self.watch_scripts(std::iter::once(script.clone()));
+        try_s!(self.wait_for_sync().await);
+        Ok(self.state.lock().unspents(&script))
+    }
+
+    /// Returns the transactions paying to or spending from the `scripts` along with their heights,
+    /// where the unconfirmed transactions have the zero height like in the electrum history.
+    /// The most recent transactions come first.
+    pub async fn script_history(&self, scripts: Vec<Vec<u8>>) -> Result<Vec<(H256Json, u64)>, String> {
+        self.watch_scripts(scripts.clone());
+        try_s!(self.wait_for_sync().await);
+
+        let state = self.state.lock();
+        let pays_to_scripts = |outpoint: &OutPoint| {
+            state
+                .txs
+                .get(&outpoint.hash)
+                .and_then(|wallet_tx| wallet_tx.tx.outputs.get(outpoint.index as usize))
+                .map_or(false, |output| {
+                    scripts.iter().any(|script| output.script_pubkey[..] == script[..])
+                })
+        };
+        let mut history: Vec<(H256Json, u64)> = state
+            .txs
+            .iter()
+            .filter(|(_, wallet_tx)| {
+                wallet_tx
+                    .tx
+                    .outputs
+                    .iter()
+                    .any(|output| scripts.iter().any(|script| output.script_pubkey[..] == script[..]))
+                    || wallet_tx
+                        .tx
+                        .inputs
+                        .iter()
+                        .any(|input| pays_to_scripts(&input.previous_output))
+            })
+            .map(|(tx_hash, wallet_tx)| (tx_hash.reversed().into(), wallet_tx.height.unwrap_or_default()))
+            .collect();
+        history.sort_by_key(|(_, height)| match height {
+            0 => std::cmp::Reverse(u64::MAX),
+            height => std::cmp::Reverse(*height),
+        });
+        Ok(history)
+    }
+
+    /// Returns the wallet transaction waiting for the sync if it's not found yet.
+    async fn wallet_tx(&self, txid: &H256Json) -> UtxoRpcResult<WalletTx> {
+        let hash: H256 = txid.reversed().into();
+        if let Some(wallet_tx) = self.state.lock().txs.get(&hash) {
+            return Ok(wallet_tx.clone());
+        }
+        if let Err(e) = self.wait_for_sync().await {
+            debug!("{}", e);
+        }
+        self.state.lock().txs.get(&hash).cloned().or_mm_err(|| {
+            UtxoRpcError::InvalidResponse(format!("Transaction {} isn't found in the wallet blocks", txid))
+        })
+    }
+
+    fn verbose_tx(&self, wallet_tx: &WalletTx) -> RpcTransaction {
+        let tx = &wallet_tx.tx;
+        let bytes = tx_bytes(tx);
+        let confirmations = match wallet_tx.height {
+            Some(height) => (self.state.lock().tip_height() + 1).saturating_sub(height) as u32,
+            None => 0,
+        };
+        let vin = tx
+            .inputs
+            .iter()
+            .map(|input| {
+                if input.previous_output.is_null() {
+                    TransactionInputEnum::Coinbase(CoinbaseTransactionInput {
+                        coinbase: input.script_sig.clone().into(),
+                        sequence: input.sequence,
+                    })
+                } else {
+                    TransactionInputEnum::Signed(SignedTransactionInput {
+                        txid: input.previous_output.hash.reversed().into(),
+                        vout: input.previous_output.index,
+                        script_sig: TransactionInputScript {
+                            asm: String::new(),
+                            hex: input.script_sig.clone().into(),
+                        },
+                        sequence: input.sequence,
+                        txinwitness: None,
+                    })
+                }
+            })
+            .collect();
+        let vout = tx
+            .outputs
+            .iter()
+            .enumerate()
+            .map(|(n, output)| {
+                let script = Script::from(output.script_pubkey.clone());
+                SignedTransactionOutput {
+                    value: Some(output.value as f64 / 10f64.powi(self.decimals as i32)),
+                    n: n as u32,
+                    script: TransactionOutputScript {
+                        asm: String::new(),
+                        hex: output.script_pubkey.clone().into(),
+                        req_sigs: 0,
+                        script_type: script.script_type().into(),
+                        addresses: Vec::new(),
+                    },
+                }
+            })
+            .collect();
+        RpcTransaction {
+            hex: bytes.clone().into(),
+            txid: tx.hash().reversed().into(),
+            hash: Some(tx.witness_hash().reversed().into()),
+            size: Some(bytes.len()),
+            vsize: None,
+            version: tx.version,
+            locktime: tx.lock_time,
+            vin,
+            vout,
+            blockhash: wallet_tx.block_hash.reversed().into(),
+            confirmations,
+            rawconfirmations: None,
+            time: wallet_tx.block_time,
+            blocktime: wallet_tx.block_time,
+            height: wallet_tx.height,
+        }
+    }
+
+    /// Adds the transaction to the wallet, it's sent to the peers by the sync loop.
+    /// The returned receiver is notified once the transaction is accepted or rejected by a peer.
+    fn broadcast(&self, tx: UtxoTx) -> oneshot::Receiver<Result<(), String>> {
+        let (waiter, sent) = oneshot::channel();
+        let hash = tx.hash();
+        let mut state = self.state.lock();
+        if state.txs.contains_key(&hash) && !state.send_waiters.contains_key(&hash) {
+            // The transaction is mined or sent already.
+            waiter.send(Ok(())).ok();
+            return sent;
+        }
+        state.txs.entry(hash.clone()).or_insert(WalletTx {
+            tx,
+            height: None,
+            block_hash: H256::default(),
+            block_time: 0,
+        });
+        state.send_waiters.entry(hash).or_default().push(waiter);
+        drop(state);
+        self.events.unbounded_send(()).ok();
+        sent
+    }
+
+    /// Sends the transaction to a peer and waits for the peer to accept it.
+    async fn send_tx(&self, tx: UtxoTx) -> UtxoRpcResult<H256Json> {
+        let hash = tx.hash();
+        let txid: H256Json = hash.reversed().into();
+        match self.broadcast(tx).timeout_secs(SEND_TIMEOUT_S).await {
+            Ok(Ok(Ok(()))) => Ok(txid),
+            Ok(Ok(Err(reason))) => MmError::err(UtxoRpcError::Internal(format!(
+                "Transaction {} is rejected: {}",
+                txid, reason
+            ))),
+            Ok(Err(_canceled)) => MmError::err(UtxoRpcError::Internal(format!(
+                "Transaction {} isn't sent, the client is stopped",
+                txid
+            ))),
+            Err(_timeout) => {
+                // No peer has got the transaction, so it's forgotten not to be spent by the next transactions.
+                let mut state = self.state.lock();
+                state.send_waiters.remove(&hash);
+                if state
+                    .txs
+                    .get(&hash)
+                    .map_or(false, |wallet_tx| wallet_tx.height.is_none())
+                {
+                    state.txs.remove(&hash);
+                }
+                MmError::err(UtxoRpcError::Internal(format!(
+                    "Transaction {} isn't sent to any peer in {} seconds",
+                    txid, SEND_TIMEOUT_S
+                )))
+            },
+        }
+    }
+
+    /// Notifies the waiters of the transaction sent to a peer.
+    /// The rejected transaction is removed from the wallet unless it's mined already.
+    fn on_tx_sent(&self, hash: &H256, result: Result<(), String>) {
+        let mut state = self.state.lock();
+        if let Err(reason) = &result {
+            warn!(
+                "{} transaction {} is rejected: {}",
+                self.ticker,
+                hash.reversed(),
+                reason
+            );
+            if state
+                .txs
+                .get(hash)
+                .map_or(false, |wallet_tx| wallet_tx.height.is_none())
+            {
+                state.txs.remove(hash);
+            }
+        }
+        for waiter in state.send_waiters.remove(hash).unwrap_or_default() {
+            waiter.send(result.clone()).ok();
+        }
+    }
+
+    /// Saves the headers and the filter headers if they're changed since the last time.
+    fn save_chain(&self) {
+        let path = match self.chain_path.as_ref() {
+            Some(path) => path,
+            None => return,
+        };
+        let bytes = {
+            let mut state = self.state.lock();
+            if !state.chain_changed {
+                return;
+            }
+            state.chain_changed = false;
+            state.chain_bytes()
+        };
+        if let Err(e) = write_chain(path, &bytes) {
+            warn!("{} error saving the chain to {}: {}", self.ticker, path.display(), e);
+        }
+    }
+
+    /// Connects the `headers` received from a peer to the known chain.
+    /// Switches to the peer's branch if it's longer than the known one.
+    /// Returns whether the chain is extended.
+    fn connect_headers(&self, headers: Vec<BlockHeader>) -> Result<bool, String> {
+        let mut state = self.state.lock();
+        let prev_hash = match headers.first() {
+            Some(header) => header.previous_header_hash.clone(),
+            None => return Ok(false),
+        };
+        let fork_height = match state.height_of(&prev_hash) {
+            Some(height) => height,
+            None => return ERR!("Headers don't connect to the known chain at {}", prev_hash.reversed()),
+        };
+        let hashes = try_s!(validate_headers_chain(&prev_hash, &headers, self.difficulty_check));
+
+        let known = hashes
+            .iter()
+            .enumerate()
+            .take_while(|(i, hash)| state.hash_at(fork_height + 1 + *i as u64) == Some(*hash))
+            .count();
+        if known == hashes.len() {
+            return Ok(false);
+        }
+        let fork_height = fork_height + known as u64;
+        if fork_height < state.tip_height() {
+            let new_tip_height = fork_height + (hashes.len() - known) as u64;
+            if new_tip_height <= state.tip_height() {
+                return Ok(false);
+            }
+            warn!(
+                "{} chain reorganization: the blocks above {} are replaced",
+                self.ticker, fork_height
+            );
+            state.rollback_to(fork_height);
+        }
+        state.headers.extend(headers.into_iter().skip(known));
+        state.header_hashes.extend(hashes.into_iter().skip(known));
+        state.synced = false;
+        state.chain_changed = true;
+        Ok(true)
+    }
+
+    /// Checks the filter headers of the blocks starting from the `start_height` against the known ones
+    /// and remembers the new ones. The filters of every peer have to be the same.
+    fn connect_filter_headers(
+        &self,
+        start_height: u64,
+        previous_filter_header: H256,
+        filter_hashes: &[H256],
+    ) -> Result<(), String> {
+        let mut state = self.state.lock();
+        let known_previous = if start_height == state.checkpoint_height + 1 {
+            state
+                .checkpoint_filter_header
+                .get_or_insert_with(|| previous_filter_header.clone())
+                .clone()
+        } else {
+            match state
+                .filter_headers
+                .get((start_height - state.checkpoint_height - 2) as usize)
+            {
+                Some(header) => header.clone(),
+                None => return ERR!("Unknown filter header at {}", start_height - 1),
+            }
+        };
+        if known_previous != previous_filter_header {
+            return ERR!("Filter header at {} differs from the known one", start_height - 1);
+        }
+
+        let mut filter_header = previous_filter_header;
+        for (i, filter_hash) in filter_hashes.iter().enumerate() {
+            let mut preimage = filter_hash.to_vec();
+            preimage.extend_from_slice(&*filter_header);
+            filter_header = dhash256(&preimage);
+
+            let index = (start_height - state.checkpoint_height - 1) as usize + i;
+            match state.filter_headers.get(index) {
+                Some(known) if *known != filter_header => {
+                    return ERR!(
+                        "Filter header at {} differs from the known one",
+                        start_height + i as u64
+                    )
+                },
+                Some(_) => (),
+                None => {
+                    state.filter_headers.push(filter_header.clone());
+                    state.chain_changed = true;
+                },
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects the transactions paying to or spending from the wallet.
+    fn process_block(&self, height: u64, block: Block) {
+        let mut state = self.state.lock();
+        let block_hash = block.hash();
+        if state.hash_at(height) != Some(&block_hash) {
+            // The block is reorganized out while being downloaded.
+            return;
+        }
+        let block_time = block.block_header.time;
+        for tx in block.transactions {
+            let tx_hash = tx.hash();
+            let spends_wallet = tx
+                .inputs
+                .iter()
+                .any(|input| state.is_wallet_output(&input.previous_output));
+            let pays_wallet = tx
+                .outputs
+                .iter()
+                .any(|output| state.scripts.contains_key(&output.script_pubkey[..]));
+            if !spends_wallet && !pays_wallet {
+                continue;
+            }
+
+            // The unconfirmed transactions double spent by the mined one will never be mined.
+            let spent: HashSet<&OutPoint> = tx.inputs.iter().map(|input| &input.previous_output).collect();
+            state.txs.retain(|hash, wallet_tx| {
+                wallet_tx.height.is_some()
+                    || *hash == tx_hash
+                    || !wallet_tx
+                        .tx
+                        .inputs
+                        .iter()
+                        .any(|input| spent.contains(&input.previous_output))
+            });
+            info!(
+                "{} found wallet transaction {} at {}",
+                self.ticker,
+                tx_hash.reversed(),
+                height
+            );
+            state.txs.insert(tx_hash, WalletTx {
+                tx,
+                height: Some(height),
+                block_hash: block_hash.clone(),
+                block_time,
+            });
+        }
+    }
+}
+
+async fn sync_loop(weak: Weak<CompactFiltersClientImpl>, mut events: mpsc::UnboundedReceiver<()>) {
+    let (ticker, peers, magic, proxy) = match weak.upgrade() {
+        Some(client) => (
+            client.ticker.clone(),
+            client.peers.clone(),
+            client.magic,
+            client.proxy.clone(),
+        ),
+        None => return,
+    };
+    for peer in peers.iter().cycle() {
+        if weak.strong_count() == 0 {
+            break;
+        }
+        match PeerConnection::connect(&ticker, peer, magic, proxy.as_ref()).await {
+            Ok(connection) => {
+                info!(
+                    "{} connected to peer {} {}",
+                    ticker, peer, connection.peer_version.user_agent
+                );
+                let mut session = SyncSession {
+                    weak: weak.clone(),
+                    connection,
+                    witness: None,
+                    broadcast: HashSet::new(),
+                };
+                if let Err(e) = session.run(&mut events).await {
+                    warn!("{} sync with peer {} is interrupted: {}", ticker, peer, e);
+                }
+            },
+            Err(e) => warn!("{} error connecting to peer {}: {}", ticker, peer, e),
+        }
+        Timer::sleep(RECONNECT_INTERVAL_S).await;
+    }
+    info!("{} compact filters sync stopped", ticker);
+}
+
+struct SyncSession {
+    weak: Weak<CompactFiltersClientImpl>,
+    connection: PeerConnection,
+    /// The connection to another peer the new filter headers are cross-checked with.
+    witness: Option<PeerConnection>,
+    /// The transactions already sent to the peer.
+    broadcast: HashSet<H256>,
+}
+
+impl SyncSession {
+    fn client(&self) -> Result<Arc<CompactFiltersClientImpl>, String> {
+        self.weak.upgrade().ok_or_else(|| ERRL!("The client is dropped"))
+    }
+
+    async fn run(&mut self, events: &mut mpsc::UnboundedReceiver<()>) -> Result<(), String> {
+        loop {
+            try_s!(self.broadcast_txs().await);
+            try_s!(self.sync_headers().await);
+            try_s!(self.sync_filters().await);
+            {
+                let client = try_s!(self.client());
+                client.save_chain();
+                let mut state = client.state.lock();
+                if state.scanned_height() == state.tip_height() {
+                    state.notify_synced();
+                }
+            }
+            try_s!(self.wait_for_news(events).await);
+        }
+    }
+
+    async fn broadcast_txs(&mut self) -> Result<(), String> {
+        let txs: Vec<UtxoTx> = {
+            let client = try_s!(self.client());
+            let state = client.state.lock();
+            state
+                .txs
+                .iter()
+                .filter(|(hash, wallet_tx)| wallet_tx.height.is_none() && !self.broadcast.contains(*hash))
+                .map(|(_, wallet_tx)| wallet_tx.tx.clone())
+                .collect()
+        };
+        for tx in txs {
+            let hash = tx.hash();
+            debug!("Sending transaction {} to {}", hash.reversed(), self.connection.address);
+            self.broadcast.insert(hash.clone());
+            try_s!(self.connection.send(&NetworkMessage::Tx(tx)).await);
+            // The peer handles the messages in order, so it rejects the transaction before answering the ping.
+            let nonce = rand::random();
+            try_s!(self.connection.send(&NetworkMessage::Ping(nonce)).await);
+            let result = try_s!(
+                self.expect(|message| match message {
+                    NetworkMessage::Reject {
+                        message,
+                        reason,
+                        hash: Some(rejected),
+                        ..
+                    } if message == "tx" && rejected == hash => Some(Err(reason)),
+                    NetworkMessage::Pong(pong) if pong == nonce => Some(Ok(())),
+                    _ => None,
+                })
+                .await
+            );
+            try_s!(self.client()).on_tx_sent(&hash, result);
+        }
+        Ok(())
+    }
+
+    async fn sync_headers(&mut self) -> Result<(), String> {
+        loop {
+            let locator = try_s!(self.client()).state.lock().locator();
+            let get_headers = NetworkMessage::GetHeaders {
+                locator,
+                stop_hash: H256::default(),
+            };
+            try_s!(self.connection.send(&get_headers).await);
+            let headers = try_s!(
+                self.expect(|message| match message {
+                    NetworkMessage::Headers(headers) => Some(headers),
+                    _ => None,
+                })
+                .await
+            );
+            let is_full = headers.len() == MAX_HEADERS_PER_MESSAGE;
+            let is_extended = try_s!(try_s!(self.client()).connect_headers(headers));
+            if !is_full || !is_extended {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn sync_filters(&mut self) -> Result<(), String> {
+        loop {
+            let client = try_s!(self.client());
+            let (start_height, block_hashes, scripts, is_new) = {
+                let state = client.state.lock();
+                let tip_height = state.tip_height();
+                let start_height = state.scanned_height() + 1;
+                if start_height > tip_height {
+                    return Ok(());
+                }
+                let stop_height = tip_height.min(start_height + MAX_FILTERS_PER_REQUEST as u64 - 1);
+                let block_hashes: Vec<H256> = (start_height..=stop_height)
+                    .filter_map(|height| state.hash_at(height).cloned())
+                    .collect();
+                // The scripts along with their scanned heights, every block is matched against the scripts
+                // which aren't scanned for in it yet.
+                let scripts: Vec<(Vec<u8>, u64)> = state
+                    .scripts
+                    .iter()
+                    .map(|(script, scanned_height)| (script.clone(), *scanned_height))
+                    .collect();
+                let is_new = stop_height > state.checkpoint_height + state.filter_headers.len() as u64;
+                (start_height, block_hashes, scripts, is_new)
+            };
+            drop(client);
+            let stop_hash = block_hashes.last().cloned().unwrap_or_default();
+
+            let get_cfheaders = NetworkMessage::GetCFHeaders {
+                start_height: start_height as u32,
+                stop_hash: stop_hash.clone(),
+            };
+            try_s!(self.connection.send(&get_cfheaders).await);
+            let (previous_filter_header, filter_hashes) = try_s!(
+                self.expect(|message| match message {
+                    NetworkMessage::CFHeaders {
+                        stop_hash: cf_stop_hash,
+                        previous_filter_header,
+                        filter_hashes,
+                    } if cf_stop_hash == stop_hash => Some((previous_filter_header, filter_hashes)),
+                    _ => None,
+                })
+                .await
+            );
+            if filter_hashes.len() != block_hashes.len() {
+                return ERR!(
+                    "Expected {} filter hashes, received {}",
+                    block_hashes.len(),
+                    filter_hashes.len()
+                );
+            }
+            if is_new {
+                try_s!(
+                    self.cross_check_filter_headers(start_height, &stop_hash, &previous_filter_header, &filter_hashes)
+                        .await
+                );
+            }
+            try_s!(try_s!(self.client()).connect_filter_headers(start_height, previous_filter_header, &filter_hashes));
+
+            let get_cfilters = NetworkMessage::GetCFilters {
+                start_height: start_height as u32,
+                stop_hash: stop_hash.clone(),
+            };
+            try_s!(self.connection.send(&get_cfilters).await);
+            let mut matched = Vec::new();
+            for (i, (block_hash, filter_hash)) in block_hashes.iter().zip(filter_hashes.iter()).enumerate() {
+                let filter = try_s!(
+                    self.expect(|message| match message {
+                        NetworkMessage::CFilter {
+                            block_hash: cf_block_hash,
+                            filter,
+                        } if cf_block_hash == *block_hash => Some(filter),
+                        _ => None,
+                    })
+                    .await
+                );
+                if dhash256(&filter) != *filter_hash {
+                    return ERR!("Filter of {} doesn't match its filter header", block_hash.reversed());
+                }
+                let height = start_height + i as u64;
+                let unscanned: Vec<Vec<u8>> = scripts
+                    .iter()
+                    .filter(|(_, scanned_height)| *scanned_height < height)
+                    .map(|(script, _)| script.clone())
+                    .collect();
+                if try_s!(gcs::filter_matches_any(block_hash, &filter, &unscanned)) {
+                    matched.push((height, block_hash.clone()));
+                }
+            }
+
+            for (height, block_hash) in matched {
+                let block = try_s!(self.get_block(&block_hash).await);
+                try_s!(self.client()).process_block(height, block);
+            }
+
+            let client = try_s!(self.client());
+            let mut state = client.state.lock();
+            let stop_height = start_height + block_hashes.len() as u64 - 1;
+            if state.hash_at(stop_height) == Some(&stop_hash) {
+                for (script, _) in scripts {
+                    if let Some(scanned_height) = state.scripts.get_mut(&script) {
+                        *scanned_height = (*scanned_height).max(stop_height);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Checks the new filter headers with another peer, so a single peer can't hide the wallet transactions
+    /// serving the filters which don't match the blocks. The check is skipped if there is no other peer.
+    async fn cross_check_filter_headers(
+        &mut self,
+        start_height: u64,
+        stop_hash: &H256,
+        previous_filter_header: &H256,
+        filter_hashes: &[H256],
+    ) -> Result<(), String> {
+        let client = try_s!(self.client());
+        let witnesses: Vec<String> = client
+            .peers
+            .iter()
+            .filter(|peer| **peer != self.connection.address)
+            .cloned()
+            .collect();
+        if witnesses.is_empty() {
+            return Ok(());
+        }
+
+        let mut last_error = String::new();
+        // The idle witness connection may be closed by the peer, so it's reconnected once.
+        for _attempt in 0..2 {
+            let mut witness = match self.witness.take() {
+                Some(witness) => witness,
+                None => try_s!(connect_any(&client, &witnesses).await),
+            };
+            match request_filter_headers(&mut witness, start_height, stop_hash).await {
+                Ok((witness_previous, witness_hashes)) => {
+                    let witness_address = witness.address.clone();
+                    self.witness = Some(witness);
+                    if witness_previous != *previous_filter_header || witness_hashes != filter_hashes {
+                        return ERR!(
+                            "Peers {} and {} serve different filter headers of the blocks from {}",
+                            self.connection.address,
+                            witness_address,
+                            start_height
+                        );
+                    }
+                    return Ok(());
+                },
+                Err(e) => last_error = e,
+            }
+        }
+        ERR!("Error cross-checking the filter headers: {}", last_error)
+    }
+
+    async fn get_block(&mut self, block_hash: &H256) -> Result<Block, String> {
+        let inv_type = if self.connection.supports_witness() {
+            MSG_BLOCK | MSG_WITNESS_FLAG
+        } else {
+            MSG_BLOCK
+        };
+        let inventory = vec![InvVect {
+            inv_type,
+            hash: block_hash.clone(),
+        }];
+        try_s!(self.connection.send(&NetworkMessage::GetData(inventory)).await);
+        let block = try_s!(
+            self.expect(|message| match message {
+                NetworkMessage::Block(block) if block.hash() == *block_hash => Some(Ok(block)),
+                NetworkMessage::NotFound(items) if items.iter().any(|item| item.hash == *block_hash) => {
+                    Some(ERR!("Peer doesn't have block {}", block_hash.reversed()))
+                },
+                _ => None,
+            })
+            .await
+        )?;
+        if block.merkle_root() != block.block_header.merkle_root_hash {
+            return ERR!("Block {} has invalid merkle root", block_hash.reversed());
+        }
+        Ok(block)
+    }
+
+    /// Waits for the message `extract` returns `Some` for, answering the peer's requests meanwhile.
+    async fn expect<T, F>(&mut self, mut extract: F) -> Result<T, String>
+    where
+        F: FnMut(NetworkMessage) -> Option<T>,
+    {
+        loop {
+            let message = match Box::pin(self.connection.receive())
+                .timeout_secs(REQUEST_TIMEOUT_S)
+                .await
+            {
+                Ok(message) => try_s!(message),
+                Err(_) => return ERR!("Peer {} hasn't responded in time", self.connection.address),
+            };
+            if let Some(message) = try_s!(self.handle_common(message).await) {
+                if let Some(result) = extract(message) {
+                    return Ok(result);
+                }
+            }
+        }
+    }
+
+    /// Handles the messages which aren't responses to the client requests.
+    /// Returns the message back if it's not one of them.
+    async fn handle_common(&mut self, message: NetworkMessage) -> Result<Option<NetworkMessage>, String> {
+        match message {
+            NetworkMessage::Ping(nonce) => try_s!(self.connection.send(&NetworkMessage::Pong(nonce)).await),
+            NetworkMessage::FeeFilter(fee_rate) => try_s!(self.client()).state.lock().relay_fee = Some(fee_rate),
+            NetworkMessage::GetData(items) => {
+                let txs: Vec<UtxoTx> = {
+                    let client = try_s!(self.client());
+                    let state = client.state.lock();
+                    items
+                        .iter()
+                        .filter_map(|item| state.txs.get(&item.hash))
+                        .map(|wallet_tx| wallet_tx.tx.clone())
+                        .collect()
+                };
+                for tx in txs {
+                    try_s!(self.connection.send(&NetworkMessage::Tx(tx)).await);
+                }
+            },
+            message => return Ok(Some(message)),
+        }
+        Ok(None)
+    }
+
+    /// Waits for a new block announcement, a new script to scan for or a new transaction to broadcast.
+    async fn wait_for_news(&mut self, events: &mut mpsc::UnboundedReceiver<()>) -> Result<(), String> {
+        loop {
+            let message = select! {
+                message = self.connection.receive().fuse() => try_s!(message),
+                _event = events.next() => return Ok(()),
+                _timeout = Timer::sleep(IDLE_TIMEOUT_S).fuse() => return Ok(()),
+            };
+            match try_s!(self.handle_common(message).await) {
+                Some(NetworkMessage::Inv(items)) if items.iter().any(|item| item.inv_type & MSG_BLOCK != 0) => {
+                    return Ok(())
+                },
+                Some(NetworkMessage::Headers(_)) => return Ok(()),
+                _ => (),
+            }
+        }
+    }
+}
+
+/// Connects to the first reachable peer of the `peers`.
+async fn connect_any(client: &CompactFiltersClientImpl, peers: &[String]) -> Result<PeerConnection, String> {
+    let mut errors = Vec::new();
+    for peer in peers {
+        match PeerConnection::connect(&client.ticker, peer, client.magic, client.proxy.as_ref()).await {
+            Ok(connection) => return Ok(connection),
+            Err(e) => errors.push(e),
+        }
+    }
+    ERR!("No peer is reachable: {:?}", errors)
+}
+
+/// Requests the filter headers of the blocks from the `start_height` to the `stop_hash`.
+/// Returns the filter header preceding them and the filter hashes of the blocks.
+async fn request_filter_headers(
+    connection: &mut PeerConnection,
+    start_height: u64,
+    stop_hash: &H256,
+) -> Result<(H256, Vec<H256>), String> {
+    let get_cfheaders = NetworkMessage::GetCFHeaders {
+        start_height: start_height as u32,
+        stop_hash: stop_hash.clone(),
+    };
+    try_s!(connection.send(&get_cfheaders).await);
+    loop {
+        let message = match Box::pin(connection.receive()).timeout_secs(REQUEST_TIMEOUT_S).await {
+            Ok(message) => try_s!(message),
+            Err(_) => return ERR!("Peer {} hasn't responded in time", connection.address),
+        };
+        match message {
+            NetworkMessage::Ping(nonce) => try_s!(connection.send(&NetworkMessage::Pong(nonce)).await),
+            NetworkMessage::CFHeaders {
+                stop_hash: cf_stop_hash,
+                previous_filter_header,
+                filter_hashes,
+            } if cf_stop_hash == *stop_hash => return Ok((previous_filter_header, filter_hashes)),
+            _ => (),
+        }
+    }
+}
+
+/// Wraps the `error` of the locally served `method` into the transport error of the JSON-RPC clients.
+pub fn rpc_error(method: &str, error: String) -> JsonRpcError {
+    JsonRpcError {
+        client_info: "CompactFiltersClient".to_owned(),
+        request: JsonRpcRequestEnum::Single(JsonRpcRequest {
+            jsonrpc: "2.0".to_owned(),
+            id: String::new(),
+            method: method.to_owned(),
+            params: Vec::new(),
+        }),
+        error: JsonRpcErrorType::Transport(error),
+    }
+}
+
+#[async_trait]
+impl UtxoRpcClientOps for CompactFiltersClient {
+    fn list_unspent(&self, address: &Address, _decimals: u8) -> UtxoRpcFut<Vec<UnspentInfo>> {
+        let script = output_script(address, ScriptType::P2PKH).to_vec();
+        let client = self.clone();
+        let fut = async move { client.synced_unspents(script).await.map_to_mm(UtxoRpcError::Internal) };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn list_unspent_group(&self, addresses: Vec<Address>, _decimals: u8) -> UtxoRpcFut<UnspentMap> {
+        let client = self.clone();
+        let fut = async move {
+            let scripts: Vec<Vec<u8>> = addresses
+                .iter()
+                .map(|address| output_script(address, ScriptType::P2PKH).to_vec())
+                .collect();
+            client.watch_scripts(scripts.clone());
+            client.wait_for_sync().await.map_to_mm(UtxoRpcError::Internal)?;
+
+            let state = client.state.lock();
+            Ok(addresses
+                .into_iter()
+                .zip(scripts)
+                .map(|(address, script)| (address, state.unspents(&script)))
+                .collect())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn send_transaction(&self, tx: &UtxoTx) -> UtxoRpcFut<H256Json> {
+        let client = self.clone();
+        let tx = tx.clone();
+        let fut = async move { client.send_tx(tx).await };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn send_raw_transaction(&self, tx: BytesJson) -> UtxoRpcFut<H256Json> {
+        let tx: UtxoTx = match deserialize(tx.as_slice()) {
+            Ok(tx) => tx,
+            Err(e) => {
+                return Box::new(futures01::future::err(
+                    UtxoRpcError::Internal(format!("Error parsing transaction: {:?}", e)).into(),
+                ))
+            },
+        };
+        let client = self.clone();
+        let fut = async move { client.send_tx(tx).await };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_transaction_bytes(&self, txid: &H256Json) -> UtxoRpcFut<BytesJson> {
+        let client = self.clone();
+        let txid = *txid;
+        let fut = async move {
+            let wallet_tx = client.wallet_tx(&txid).await?;
+            Ok(tx_bytes(&wallet_tx.tx).into())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_verbose_transaction(&self, txid: &H256Json) -> UtxoRpcFut<RpcTransaction> {
+        let client = self.clone();
+        let txid = *txid;
+        let fut = async move {
+            let wallet_tx = client.wallet_tx(&txid).await?;
+            Ok(client.verbose_tx(&wallet_tx))
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_verbose_transactions(&self, tx_ids: &[H256Json]) -> UtxoRpcFut<Vec<RpcTransaction>> {
+        let client = self.clone();
+        let tx_ids = tx_ids.to_vec();
+        let fut = async move {
+            let mut txs = Vec::with_capacity(tx_ids.len());
+            for txid in tx_ids.iter() {
+                let wallet_tx = client.wallet_tx(txid).await?;
+                txs.push(client.verbose_tx(&wallet_tx));
+            }
+            Ok(txs)
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_block_count(&self) -> UtxoRpcFut<u64> { Box::new(futures01::future::ok(self.state.lock().tip_height())) }
+
+    fn display_balance(&self, address: Address, decimals: u8) -> RpcRes<BigDecimal> {
+        let script = output_script(&address, ScriptType::P2PKH).to_vec();
+        let client = self.clone();
+        let fut = async move {
+            let unspents = client
+                .synced_unspents(script)
+                .await
+                .map_err(|e| rpc_error("display_balance", e))?;
+            let balance = unspents.iter().map(|unspent| unspent.value).sum();
+            Ok(big_decimal_from_sat_unsigned(balance, decimals))
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn display_balances(&self, addresses: Vec<Address>, decimals: u8) -> UtxoRpcFut<Vec<(Address, BigDecimal)>> {
+        let client = self.clone();
+        let fut = async move {
+            let unspents = client.list_unspent_group(addresses.clone(), decimals).compat().await?;
+            Ok(addresses
+                .into_iter()
+                .map(|address| {
+                    let balance = unspents
+                        .get(&address)
+                        .map(|unspents| unspents.iter().map(|unspent| unspent.value).sum())
+                        .unwrap_or_default();
+                    (address, big_decimal_from_sat_unsigned(balance, decimals))
+                })
+                .collect())
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn estimate_fee_sat(
+        &self,
+        _decimals: u8,
+        _fee_method: &EstimateFeeMethod,
+        _mode: &Option<EstimateFeeMode>,
+        _n_blocks: u32,
+    ) -> UtxoRpcFut<u64> {
+        Box::new(futures01::future::err(
+            UtxoRpcError::Internal(
+                "Fee estimation isn't supported by the compact filters client, set 'txfee' in the coin config".into(),
+            )
+            .into(),
+        ))
+    }
+
+    fn get_relay_fee(&self) -> RpcRes<BigDecimal> {
+        let relay_fee = self.state.lock().relay_fee.unwrap_or(DEFAULT_RELAY_FEE_SAT);
+        Box::new(futures01::future::ok(big_decimal_from_sat_unsigned(
+            relay_fee,
+            self.decimals,
+        )))
+    }
+
+    fn find_output_spend(
+        &self,
+        tx_hash: H256,
+        script_pubkey: &[u8],
+        vout: usize,
+        _from_block: BlockHashOrHeight,
+    ) -> Box<dyn futures01::Future<Item = Option<SpentOutputInfo>, Error = String> + Send> {
+        let client = self.clone();
+        let script_pubkey = script_pubkey.to_vec();
+        let fut = async move {
+            client.watch_scripts(std::iter::once(script_pubkey));
+            try_s!(client.wait_for_sync().await);
+
+            let state = client.state.lock();
+            for wallet_tx in state.txs.values() {
+                let input_index = wallet_tx.tx.inputs.iter().position(|input| {
+                    input.previous_output.hash == tx_hash && input.previous_output.index == vout as u32
+                });
+                if let Some(input_index) = input_index {
+                    return Ok(Some(SpentOutputInfo {
+                        spending_tx: wallet_tx.tx.clone(),
+                        input_index,
+                        spent_in_block: BlockHashOrHeight::Height(wallet_tx.height.unwrap_or_default() as i64),
+                    }));
+                }
+            }
+            Ok(None)
+        };
+        Box::new(fut.boxed().compat())
+    }
+
+    fn get_median_time_past(
+        &self,
+        starting_block: u64,
+        count: NonZeroU64,
+        _coin_variant: CoinVariant,
+    ) -> UtxoRpcFut<u32> {
+        let state = self.state.lock();
+        let from = (starting_block + 1).saturating_sub(count.get());
+        let timestamps: Option<Vec<u32>> = (from..=starting_block)
+            .map(|height| state.header_at(height).map(|header| header.time))
+            .collect();
+        let result = match timestamps {
+            Some(mut timestamps) => median(timestamps.as_mut_slice())
+                .or_mm_err(|| UtxoRpcError::InvalidResponse("No headers to get the median time of".into())),
+            None => MmError::err(UtxoRpcError::InvalidResponse(format!(
+                "Headers {}..={} aren't synced",
+                from, starting_block
+            ))),
+        };
+        Box::new(futures01::future::result(result))
+    }
+
+    async fn get_block_timestamp(&self, height: u64) -> Result<u64, MmError<UtxoRpcError>> {
+        let state = self.state.lock();
+        match state.header_at(height) {
+            Some(header) => Ok(header.time as u64),
+            None => MmError::err(UtxoRpcError::InvalidResponse(format!(
+                "Header at {} isn't synced",
+                height
+            ))),
+        }
+    }
+}
@@ -0,0 +1,520 @@
+//! The subset of the Bitcoin P2P protocol the compact filters client speaks:
+//! the handshake, the headers, the BIP157 compact filters, the blocks and the transactions relay.
+//! https://developer.bitcoin.org/reference/p2p_networking.html
+
+use crate::utxo::rpc_clients::host_and_port;
+use bitcrypto::dhash256;
+use byteorder::{ByteOrder, LittleEndian};
+use chain::{deserialize_tx, Block, BlockHeader, Transaction as UtxoTx, TxType};
+use common::custom_futures::FutureTimerExt;
+use common::executor::spawn;
+use common::now_ms;
+use futures::channel::mpsc;
+use futures::future::{abortable, AbortHandle};
+use futures::StreamExt;
+use keys::hash::H256;
+use mm2_net::socks5::{socks5_connect, Socks5Proxy};
+use primitives::bytes::Bytes;
+use serialization::{coin_variant_by_ticker, deserialize, serialize, serialize_with_flags, CompactInteger,
+                    Deserializable, Error as SerError, Reader, Serializable, Stream, SERIALIZE_TRANSACTION_WITNESS};
+use std::io;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+
+pub const PROTOCOL_VERSION: u32 = 70016;
+/// The peer can serve the full blocks.
+pub const NODE_NETWORK: u64 = 1;
+/// The peer can serve the witness data of the blocks and the transactions.
+pub const NODE_WITNESS: u64 = 1 << 3;
+/// The peer can serve the BIP157 compact filters.
+pub const NODE_COMPACT_FILTERS: u64 = 1 << 6;
+/// The BIP158 basic filter type.
+pub const BASIC_FILTER_TYPE: u8 = 0;
+pub const MSG_TX: u32 = 1;
+pub const MSG_BLOCK: u32 = 2;
+pub const MSG_WITNESS_FLAG: u32 = 1 << 30;
+/// The peers send at most 2000 headers in one `headers` message.
+pub const MAX_HEADERS_PER_MESSAGE: usize = 2000;
+/// The peers serve at most 1000 filters for one `getcfilters` request.
+pub const MAX_FILTERS_PER_REQUEST: usize = 1000;
+
+const MESSAGE_HEADER_SIZE: usize = 24;
+const COMMAND_SIZE: usize = 12;
+/// The messages are limited by the size of the biggest blocks.
+const MAX_MESSAGE_SIZE: usize = 32 * 1024 * 1024;
+const HANDSHAKE_TIMEOUT_S: f64 = 30.;
+const USER_AGENT: &str = "/mm2:compact_filters/";
+
+/// The inventory item of the `inv`, `getdata` and `notfound` messages.
+#[derive(Clone, Debug, PartialEq)]
+pub struct InvVect {
+    pub inv_type: u32,
+    pub hash: H256,
+}
+
+impl Serializable for InvVect {
+    fn serialize(&self, s: &mut Stream) { s.append(&self.inv_type).append(&self.hash); }
+}
+
+impl Deserializable for InvVect {
+    fn deserialize<T: io::Read>(reader: &mut Reader<T>) -> Result<Self, SerError> {
+        Ok(InvVect {
+            inv_type: reader.read()?,
+            hash: reader.read()?,
+        })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct VersionMessage {
+    pub version: u32,
+    pub services: u64,
+    pub user_agent: String,
+    pub start_height: i32,
+}
+
+#[derive(Debug)]
+pub enum NetworkMessage {
+    Version(VersionMessage),
+    Verack,
+    Ping(u64),
+    Pong(u64),
+    GetHeaders {
+        locator: Vec<H256>,
+        stop_hash: H256,
+    },
+    Headers(Vec<BlockHeader>),
+    GetCFHeaders {
+        start_height: u32,
+        stop_hash: H256,
+    },
+    CFHeaders {
+        stop_hash: H256,
+        previous_filter_header: H256,
+        filter_hashes: Vec<H256>,
+    },
+    GetCFilters {
+        start_height: u32,
+        stop_hash: H256,
+    },
+    CFilter {
+        block_hash: H256,
+        filter: Vec<u8>,
+    },
+    Inv(Vec<InvVect>),
+    GetData(Vec<InvVect>),
+    NotFound(Vec<InvVect>),
+    Block(Block),
+    Tx(UtxoTx),
+    FeeFilter(u64),
+    /// The peer rejected the `message`, e.g. a `tx` which is invalid or doesn't meet its relay policy.
+    /// The `hash` of the rejected transaction or block is set if there is one.
+    Reject {
+        message: String,
+        code: u8,
+        reason: String,
+        hash: Option<H256>,
+    },
+    /// The messages the client doesn't need, like `addr` or `sendcmpct`.
+    Unknown(String),
+}
+
+/// Serializes the transaction with the witness data if there is any.
+pub fn tx_bytes(tx: &UtxoTx) -> Vec<u8> {
+    if tx.has_witness() {
+        serialize_with_flags(tx, SERIALIZE_TRANSACTION_WITNESS).take()
+    } else {
+        serialize(tx).take()
+    }
+}
+
+impl NetworkMessage {
+    pub fn command(&self) -> &str {
+        match self {
+            NetworkMessage::Version(_) => "version",
+            NetworkMessage::Verack => "verack",
+            NetworkMessage::Ping(_) => "ping",
+            NetworkMessage::Pong(_) => "pong",
+            NetworkMessage::GetHeaders { .. } => "getheaders",
+            NetworkMessage::Headers(_) => "headers",
+            NetworkMessage::GetCFHeaders { .. } => "getcfheaders",
+            NetworkMessage::CFHeaders { .. } => "cfheaders",
+            NetworkMessage::GetCFilters { .. } => "getcfilters",
+            NetworkMessage::CFilter { .. } => "cfilter",
+            NetworkMessage::Inv(_) => "inv",
+            NetworkMessage::GetData(_) => "getdata",
+            NetworkMessage::NotFound(_) => "notfound",
+            NetworkMessage::Block(_) => "block",
+            NetworkMessage::Tx(_) => "tx",
+            NetworkMessage::FeeFilter(_) => "feefilter",
+            NetworkMessage::Reject { .. } => "reject",
+            NetworkMessage::Unknown(command) => command,
+        }
+    }
+
+    fn payload(&self) -> Vec<u8> {
+        let mut stream = Stream::new();
+        match self {
+            NetworkMessage::Version(version) => {
+                stream
+                    .append(&version.version)
+                    .append(&version.services)
+                    .append(&((now_ms() / 1000) as i64));
+                // The receiving and the sending node addresses aren't used by the modern nodes.
+                for _ in 0..2 {
+                    stream.append(&0u64).append_slice(&[0; 16]).append_slice(&[0; 2]);
+                }
+                stream
+                    .append(&rand::random::<u64>())
+                    .append(&version.user_agent)
+                    .append(&version.start_height)
+                    // Don't relay the unconfirmed transactions of the other users to us.
+                    .append(&false);
+            },
+            NetworkMessage::Verack | NetworkMessage::Unknown(_) => (),
+            NetworkMessage::Ping(nonce) | NetworkMessage::Pong(nonce) => {
+                stream.append(nonce);
+            },
+            NetworkMessage::GetHeaders { locator, stop_hash } => {
+                stream.append(&PROTOCOL_VERSION).append_list(locator).append(stop_hash);
+            },
+            NetworkMessage::Headers(headers) => {
+                stream.append(&CompactInteger::from(headers.len()));
+                for header in headers {
+                    // Every header is followed by the always empty transactions list.
+                    stream.append(header).append(&CompactInteger::from(0u8));
+                }
+            },
+            NetworkMessage::GetCFHeaders {
+                start_height,
+                stop_hash,
+            }
+            | NetworkMessage::GetCFilters {
+                start_height,
+                stop_hash,
+            } => {
+                stream.append(&BASIC_FILTER_TYPE).append(start_height).append(stop_hash);
+            },
+            NetworkMessage::CFHeaders {
+                stop_hash,
+                previous_filter_header,
+                filter_hashes,
+            } => {
+                stream
+                    .append(&BASIC_FILTER_TYPE)
+                    .append(stop_hash)
+                    .append(previous_filter_header)
+                    .append_list(filter_hashes);
+            },
+            NetworkMessage::CFilter { block_hash, filter } => {
+                stream
+                    .append(&BASIC_FILTER_TYPE)
+                    .append(block_hash)
+                    .append(&Bytes::from(filter.clone()));
+            },
+            NetworkMessage::Inv(items) | NetworkMessage::GetData(items) | NetworkMessage::NotFound(items) => {
+                stream.append_list(items);
+            },
+            NetworkMessage::Block(block) => {
+                stream
+                    .append(&block.block_header)
+                    .append(&CompactInteger::from(block.transactions.len()));
+                for tx in block.transactions.iter() {
+                    stream.append_slice(&tx_bytes(tx));
+                }
+            },
+            NetworkMessage::Tx(tx) => {
+                stream.append_slice(&tx_bytes(tx));
+            },
+            NetworkMessage::FeeFilter(fee_rate) => {
+                stream.append(fee_rate);
+            },
+            NetworkMessage::Reject {
+                message,
+                code,
+                reason,
+                hash,
+            } => {
+                stream.append(message).append(code).append(reason);
+                if let Some(hash) = hash {
+                    stream.append(hash);
+                }
+            },
+        }
+        stream.out().take()
+    }
+
+    /// Serializes the message along with the header: the network magic, the command, the length and the checksum.
+    pub fn to_bytes(&self, magic: u32) -> Vec<u8> {
+        let payload = self.payload();
+        let mut command = [0u8; COMMAND_SIZE];
+        command[..self.command().len()].copy_from_slice(self.command().as_bytes());
+
+        let mut stream = Stream::new();
+        stream
+            .append(&magic)
+            .append_slice(&command)
+            .append(&(payload.len() as u32))
+            .append_slice(&dhash256(&payload)[..4])
+            .append_slice(&payload);
+        stream.out().take()
+    }
+
+    /// Parses the message `payload`. The headers and the blocks are parsed according to the `ticker` coin variant.
+    pub fn from_payload(command: &str, payload: &[u8], ticker: &str) -> Result<NetworkMessage, SerError> {
+        let mut reader = Reader::new_with_coin_variant(payload, coin_variant_by_ticker(ticker));
+        let message = match command {
+            "version" => {
+                let version = reader.read()?;
+                let services = reader.read()?;
+                let _timestamp: i64 = reader.read()?;
+                let mut addresses = [0u8; 2 * 26];
+                reader.read_slice(&mut addresses)?;
+                let _nonce: u64 = reader.read()?;
+                NetworkMessage::Version(VersionMessage {
+                    version,
+                    services,
+                    user_agent: reader.read()?,
+                    start_height: reader.read()?,
+                })
+            },
+            "verack" => NetworkMessage::Verack,
+            "ping" => NetworkMessage::Ping(reader.read()?),
+            "pong" => NetworkMessage::Pong(reader.read()?),
+            "getheaders" => {
+                let _version: u32 = reader.read()?;
+                NetworkMessage::GetHeaders {
+                    locator: reader.read_list()?,
+                    stop_hash: reader.read()?,
+                }
+            },
+            "headers" => {
+                let count: usize = reader.read::<CompactInteger>()?.into();
+                if count > MAX_HEADERS_PER_MESSAGE {
+                    return Err(SerError::MalformedData);
+                }
+                let mut headers = Vec::with_capacity(count);
+                for _ in 0..count {
+                    headers.push(reader.read()?);
+                    let _tx_count: CompactInteger = reader.read()?;
+                }
+                NetworkMessage::Headers(headers)
+            },
+            "getcfheaders" | "getcfilters" => {
+                let _filter_type: u8 = reader.read()?;
+                let start_height = reader.read()?;
+                let stop_hash = reader.read()?;
+                if command == "getcfheaders" {
+                    NetworkMessage::GetCFHeaders {
+                        start_height,
+                        stop_hash,
+                    }
+                } else {
+                    NetworkMessage::GetCFilters {
+                        start_height,
+                        stop_hash,
+                    }
+                }
+            },
+            "cfheaders" => {
+                let _filter_type: u8 = reader.read()?;
+                NetworkMessage::CFHeaders {
+                    stop_hash: reader.read()?,
+                    previous_filter_header: reader.read()?,
+                    filter_hashes: reader.read_list()?,
+                }
+            },
+            "cfilter" => {
+                let _filter_type: u8 = reader.read()?;
+                let block_hash = reader.read()?;
+                let filter: Bytes = reader.read()?;
+                NetworkMessage::CFilter {
+                    block_hash,
+                    filter: filter.take(),
+                }
+            },
+            "inv" => NetworkMessage::Inv(reader.read_list()?),
+            "getdata" => NetworkMessage::GetData(reader.read_list()?),
+            "notfound" => NetworkMessage::NotFound(reader.read_list()?),
+            "block" => {
+                let block_header = reader.read()?;
+                let count: usize = reader.read::<CompactInteger>()?.into();
+                // `Transaction` deserializes the whole remaining buffer, so the block transactions are parsed one by one.
+                let mut transactions = Vec::with_capacity(count.min(MAX_MESSAGE_SIZE / 60));
+                for _ in 0..count {
+                    transactions.push(deserialize_tx(&mut reader, TxType::StandardWithWitness)?);
+                }
+                NetworkMessage::Block(Block::new(block_header, transactions))
+            },
+            "tx" => return Ok(NetworkMessage::Tx(deserialize(payload)?)),
+            "feefilter" => NetworkMessage::FeeFilter(reader.read()?),
+            "reject" => {
+                let message = reader.read()?;
+                let code = reader.read()?;
+                let reason = reader.read()?;
+                let hash = if reader.is_finished() {
+                    None
+                } else {
+                    Some(reader.read()?)
+                };
+                NetworkMessage::Reject {
+                    message,
+                    code,
+                    reason,
+                    hash,
+                }
+            },
+            _ => return Ok(NetworkMessage::Unknown(command.to_owned())),
+        };
+        Ok(message)
+    }
+}
+
+/// Reads the messages of the peer until the connection is closed or the receiver is dropped.
+async fn read_loop(
+    mut reader: OwnedReadHalf,
+    magic: u32,
+    ticker: String,
+    incoming: mpsc::UnboundedSender<Result<NetworkMessage, String>>,
+) {
+    loop {
+        let message = read_message(&mut reader, magic, &ticker).await;
+        let is_err = message.is_err();
+        if incoming.unbounded_send(message).is_err() || is_err {
+            break;
+        }
+    }
+}
+
+/// Reads the next message checking its network `magic` and checksum.
+pub async fn read_message(reader: &mut OwnedReadHalf, magic: u32, ticker: &str) -> Result<NetworkMessage, String> {
+    let mut header = [0u8; MESSAGE_HEADER_SIZE];
+    try_s!(reader.read_exact(&mut header).await);
+    if LittleEndian::read_u32(&header[0..4]) != magic {
+        return ERR!("Unexpected network magic {}", hex::encode(&header[0..4]));
+    }
+    let command = String::from_utf8_lossy(&header[4..16])
+        .trim_end_matches('\0')
+        .to_owned();
+    let length = LittleEndian::read_u32(&header[16..20]) as usize;
+    if length > MAX_MESSAGE_SIZE {
+        return ERR!("'{}' message is too large: {} bytes", command, length);
+    }
+
+    let mut payload = vec![0u8; length];
+    try_s!(reader.read_exact(&mut payload).await);
+    if dhash256(&payload)[..4] != header[20..24] {
+        return ERR!("Invalid '{}' message checksum", command);
+    }
+    NetworkMessage::from_payload(&command, &payload, ticker).map_err(|e| ERRL!("Error parsing '{}': {:?}", command, e))
+}
+
+/// The handshaked connection to a peer.
+/// The incoming messages are read by a separate task, so waiting for them can be safely interrupted.
+pub struct PeerConnection {
+    pub address: String,
+    pub peer_version: VersionMessage,
+    magic: u32,
+    writer: OwnedWriteHalf,
+    incoming: mpsc::UnboundedReceiver<Result<NetworkMessage, String>>,
+    read_loop_handle: AbortHandle,
+}
+
+impl Drop for PeerConnection {
+    fn drop(&mut self) { self.read_loop_handle.abort(); }
+}
+
+impl PeerConnection {
+    /// Connects to the peer through the SOCKS5 `proxy` if it's set and performs the version handshake.
+    /// The peer has to serve the blocks and the compact filters.
+    pub async fn connect(
+        ticker: &str,
+        address: &str,
+        magic: u32,
+        proxy: Option<&Socks5Proxy>,
+    ) -> Result<PeerConnection, String> {
+        let stream = match proxy {
+            Some(proxy) => {
+                let (host, port) = try_s!(host_and_port(address));
+                try_s!(socks5_connect(proxy, &host, port).await)
+            },
+            None => try_s!(TcpStream::connect(address).await),
+        };
+        let (reader, writer) = stream.into_split();
+        let (incoming_tx, incoming) = mpsc::unbounded();
+        let (read_loop, read_loop_handle) = abortable(read_loop(reader, magic, ticker.to_owned(), incoming_tx));
+        spawn(async move {
+            read_loop.await.ok();
+        });
+
+        let mut connection = PeerConnection {
+            address: address.to_owned(),
+            peer_version: VersionMessage {
+                version: 0,
+                services: 0,
+                user_agent: String::new(),
+                start_height: 0,
+            },
+            magic,
+            writer,
+            incoming,
+            read_loop_handle,
+        };
+        match Box::pin(connection.handshake()).timeout_secs(HANDSHAKE_TIMEOUT_S).await {
+            Ok(Ok(())) => (),
+            Ok(Err(e)) => return ERR!("Handshake with {} failed: {}", address, e),
+            Err(e) => return ERR!("Handshake with {} failed: {}", address, e),
+        }
+
+        let required_services = NODE_NETWORK | NODE_COMPACT_FILTERS;
+        if connection.peer_version.services & required_services != required_services {
+            return ERR!(
+                "Peer {} doesn't serve the blocks and the compact filters, services {:#x}",
+                address,
+                connection.peer_version.services
+            );
+        }
+        Ok(connection)
+    }
+
+    async fn handshake(&mut self) -> Result<(), String> {
+        let version = VersionMessage {
+            version: PROTOCOL_VERSION,
+            services: 0,
+            user_agent: USER_AGENT.to_owned(),
+            start_height: 0,
+        };
+        try_s!(self.send(&NetworkMessage::Version(version)).await);
+
+        let (mut version_received, mut verack_received) = (false, false);
+        while !(version_received && verack_received) {
+            match try_s!(self.receive().await) {
+                NetworkMessage::Version(version) => {
+                    self.peer_version = version;
+                    version_received = true;
+                    try_s!(self.send(&NetworkMessage::Verack).await);
+                },
+                NetworkMessage::Verack => verack_received = true,
+                _ => (),
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the peer serves the witness data, so the witness blocks can be requested.
+    pub fn supports_witness(&self) -> bool { self.peer_version.services & NODE_WITNESS != 0 }
+
+    pub async fn send(&mut self, message: &NetworkMessage) -> Result<(), String> {
+        try_s!(self.writer.write_all(&message.to_bytes(self.magic)).await);
+        Ok(())
+    }
+
+    /// Waits for the next message. It's safe to drop the returned future, no message is lost.
+    pub async fn receive(&mut self) -> Result<NetworkMessage, String> {
+        match self.incoming.next().await {
+            Some(message) => message,
+            None => ERR!("Connection to {} is closed", self.address),
+        }
+    }
+}
@@ -0,0 +1,322 @@
+//! Health scoring of the electrum servers.
+//!
+//! The latency and the error rate of every server are collected from the completed requests,
+//! the tip of every server is polled by [`ElectrumClient::check_servers_tips`].
+//! The requests are sent to the servers with the highest score first,
+//! the servers lagging behind the tip or following another chain are quarantined for a while.
+//!
+//! [`ElectrumClient::check_servers_tips`]: super::rpc_clients::ElectrumClient::check_servers_tips
+
+use crate::RpcTransportEventHandler;
+use common::log::warn;
+use common::now_ms;
+use parking_lot::Mutex as PaMutex;
+use rpc::v1::types::H256 as H256Json;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The weight of the latest sample in the moving averages of the latency and the error rate.
+const EWMA_WEIGHT: f64 = 0.2;
+/// The latency of the servers that haven't responded yet, in milliseconds.
+/// The score of a server is halved when its latency reaches this value.
+const REFERENCE_LATENCY_MS: f64 = 1000.;
+/// The servers whose tip differs from the best known tip by more blocks are quarantined.
+const MAX_TIP_LAG: u64 = 2;
+/// How long the unhealthy servers aren't used for the requests.
+const QUARANTINE_DURATION_MS: u64 = 5 * 60 * 1000;
+
+fn ewma(prev: Option<f64>, sample: f64) -> f64 {
+    match prev {
+        Some(prev) => prev + EWMA_WEIGHT * (sample - prev),
+        None => sample,
+    }
+}
+
+#[derive(Clone, Debug)]
+struct Quarantine {
+    until_ms: u64,
+    reason: String,
+}
+
+#[derive(Clone, Debug, Default)]
+struct ElectrumServerHealth {
+    /// The moving average of the successful requests latency in milliseconds.
+    latency_ms: Option<f64>,
+    /// The moving average of the failed requests share, from 0 to 1.
+    error_rate: f64,
+    requests: u64,
+    errors: u64,
+    protocol_version: Option<f32>,
+    tip_height: Option<u64>,
+    tip_hash: Option<H256Json>,
+    quarantine: Option<Quarantine>,
+}
+
+impl ElectrumServerHealth {
+    fn on_request_completed(&mut self, latency: Duration, success: bool) {
+        self.requests += 1;
+        if success {
+            self.latency_ms = Some(ewma(self.latency_ms, latency.as_secs_f64() * 1000.));
+            self.error_rate = ewma(Some(self.error_rate), 0.);
+        } else {
+            self.errors += 1;
+            self.error_rate = ewma(Some(self.error_rate), 1.);
+        }
+    }
+
+    fn active_quarantine(&self, now_ms: u64) -> Option<&Quarantine> {
+        self.quarantine
+            .as_ref()
+            .filter(|quarantine| quarantine.until_ms > now_ms)
+    }
+
+    /// The score from 0 to 100, the higher the better. The quarantined servers have zero score.
+    fn score(&self, now_ms: u64) -> f64 {
+        if self.active_quarantine(now_ms).is_some() {
+            return 0.;
+        }
+        let latency_ms = self.latency_ms.unwrap_or(REFERENCE_LATENCY_MS);
+        100. * (1. - self.error_rate) * REFERENCE_LATENCY_MS / (REFERENCE_LATENCY_MS + latency_ms)
+    }
+}
+
+/// The health of a server as it's returned by the `get_electrum_servers_status` RPC.
+#[derive(Debug, Serialize)]
+pub struct ElectrumServerStatus {
+    pub url: String,
+    pub connected: bool,
+    pub protocol_version: Option<f32>,
+    pub score: f64,
+    pub latency_ms: Option<f64>,
+    pub error_rate: f64,
+    pub requests: u64,
+    pub errors: u64,
+    pub tip_height: Option<u64>,
+    pub quarantined: bool,
+    pub quarantine_reason: Option<String>,
+    /// The timestamp in seconds when the quarantine is over.
+    pub quarantined_until: Option<u64>,
+}
+
+/// The health of the servers of an electrum client.
+/// It's also an [`RpcTransportEventHandler`] collecting the latency and the errors of the requests.
+#[derive(Debug)]
+pub struct ElectrumServersHealth {
+    coin_ticker: String,
+    servers: PaMutex<HashMap<String, ElectrumServerHealth>>,
+}
+
+impl ElectrumServersHealth {
+    pub fn new(coin_ticker: String) -> ElectrumServersHealth {
+        ElectrumServersHealth {
+            coin_ticker,
+            servers: PaMutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn set_protocol_version(&self, server: &str, version: f32) {
+        self.servers
+            .lock()
+            .entry(server.to_owned())
+            .or_default()
+            .protocol_version = Some(version);
+    }
+
+    pub fn remove_server(&self, server: &str) { self.servers.lock().remove(server); }
+
+    /// Orders the `items` by the score of their servers descending.
+    /// The quarantined servers are left out unless all servers are quarantined.
+    pub fn order_by_score<T, F>(&self, items: Vec<T>, server_of: F) -> Vec<T>
+    where
+        F: Fn(&T) -> &str,
+    {
+        let now = now_ms();
+        let servers = self.servers.lock();
+        let (mut healthy, mut quarantined): (Vec<_>, Vec<_>) = items
+            .into_iter()
+            .map(|item| {
+                let health = servers.get(server_of(&item)).cloned().unwrap_or_default();
+                (health.score(now), health.active_quarantine(now).is_some(), item)
+            })
+            .partition(|(_, is_quarantined, _)| !is_quarantined);
+        drop(servers);
+
+        let by_score_desc = |a: &(f64, bool, T), b: &(f64, bool, T)| b.0.partial_cmp(&a.0).unwrap_or(Ordering::Equal);
+        if healthy.is_empty() {
+            // The quarantined servers are all scored zero, so the configured order is preserved.
+            quarantined.sort_by(by_score_desc);
+            return quarantined.into_iter().map(|(_, _, item)| item).collect();
+        }
+        healthy.sort_by(by_score_desc);
+        healthy.into_iter().map(|(_, _, item)| item).collect()
+    }
+
+    /// Records the tips of the servers and quarantines the servers which tip is inconsistent with the others.
+    pub fn update_tips(&self, tips: Vec<(String, u64, H256Json)>) {
+        let now = now_ms();
+        let mut servers = self.servers.lock();
+        for (server, height, hash) in tips {
+            let health = servers.entry(server).or_default();
+            health.tip_height = Some(height);
+            health.tip_hash = Some(hash);
+        }
+        for (server, reason) in find_inconsistent_tips(&servers, now) {
+            warn!("Quarantine {} electrum server {}: {}", self.coin_ticker, server, reason);
+            if let Some(health) = servers.get_mut(&server) {
+                health.quarantine = Some(Quarantine {
+                    until_ms: now + QUARANTINE_DURATION_MS,
+                    reason,
+                });
+            }
+        }
+    }
+
+    pub fn status(&self, server: &str, connected: bool) -> ElectrumServerStatus {
+        let now = now_ms();
+        let health = self.servers.lock().get(server).cloned().unwrap_or_default();
+        let quarantine = health.active_quarantine(now);
+        ElectrumServerStatus {
+            url: server.to_owned(),
+            connected,
+            protocol_version: health.protocol_version,
+            score: health.score(now),
+            latency_ms: health.latency_ms,
+            error_rate: health.error_rate,
+            requests: health.requests,
+            errors: health.errors,
+            tip_height: health.tip_height,
+            quarantined: quarantine.is_some(),
+            quarantine_reason: quarantine.map(|q| q.reason.clone()),
+            quarantined_until: quarantine.map(|q| q.until_ms / 1000),
+        }
+    }
+}
+
+/// Returns the servers to quarantine along with the reasons.
+///
+/// The best tip height is the median of the heights reported by the healthy servers,
+/// so a single server can't push the others into quarantine by reporting a fake tip.
+/// The servers at the best height must agree on the tip hash with the majority of them.
+fn find_inconsistent_tips(servers: &HashMap<String, ElectrumServerHealth>, now_ms: u64) -> Vec<(String, String)> {
+    let tips: Vec<(&String, u64, &H256Json)> = servers
+        .iter()
+        .filter(|(_, health)| health.active_quarantine(now_ms).is_none())
+        .filter_map(|(server, health)| Some((server, health.tip_height?, health.tip_hash.as_ref()?)))
+        .collect();
+    if tips.len() < 2 {
+        return Vec::new();
+    }
+
+    let mut heights: Vec<u64> = tips.iter().map(|(_, height, _)| *height).collect();
+    heights.sort_unstable_by(|a, b| b.cmp(a));
+    let best_height = heights[(heights.len() - 1) / 2];
+
+    let mut hash_votes: HashMap<&H256Json, usize> = HashMap::new();
+    for (_, _, hash) in tips.iter().filter(|(_, height, _)| *height == best_height) {
+        *hash_votes.entry(*hash).or_insert(0) += 1;
+    }
+    let max_votes = hash_votes.values().copied().max().unwrap_or_default();
+    // Don't judge the servers if there is no single majority hash.
+    let mut majority_hashes = hash_votes.iter().filter(|(_, votes)| **votes == max_votes);
+    let majority_hash = match (majority_hashes.next(), majority_hashes.next()) {
+        (Some((hash, _)), None) => Some(*hash),
+        _ => None,
+    };
+
+    let mut inconsistent = Vec::new();
+    for (server, height, hash) in tips {
+        let reason = if height + MAX_TIP_LAG < best_height {
+            format!("tip {} lags behind the best known tip {}", height, best_height)
+        } else if height > best_height + MAX_TIP_LAG {
+            format!("tip {} is ahead of the best known tip {}", height, best_height)
+        } else if height == best_height && majority_hash.map_or(false, |majority| majority != hash) {
+            format!("tip {} at height {} differs from the majority of servers", hash, height)
+        } else {
+            continue;
+        };
+        inconsistent.push((server.clone(), reason));
+    }
+    inconsistent
+}
+
+impl RpcTransportEventHandler for ElectrumServersHealth {
+    fn debug_info(&self) -> String { "ElectrumServersHealth".into() }
+
+    fn on_outgoing_request(&self, _data: &[u8]) {}
+
+    fn on_incoming_response(&self, _data: &[u8]) {}
+
+    fn on_connected(&self, _address: String) -> Result<(), String> { Ok(()) }
+
+    fn on_request_completed(&self, server: &str, latency: Duration, success: bool) {
+        self.servers
+            .lock()
+            .entry(server.to_owned())
+            .or_default()
+            .on_request_completed(latency, success)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn hash(byte: u8) -> H256Json { H256Json::from([byte; 32]) }
+
+    #[test]
+    fn test_order_by_score() {
+        let health = ElectrumServersHealth::new("RICK".into());
+        health.on_request_completed("slow", Duration::from_millis(900), true);
+        health.on_request_completed("fast", Duration::from_millis(100), true);
+        health.on_request_completed("failing", Duration::from_millis(100), false);
+
+        let ordered = health.order_by_score(vec!["unknown", "failing", "slow", "fast"], |server| *server);
+        assert_eq!(ordered, vec!["fast", "slow", "unknown", "failing"]);
+    }
+
+    #[test]
+    fn test_quarantine_inconsistent_tips() {
+        let health = ElectrumServersHealth::new("RICK".into());
+        health.update_tips(vec![
+            ("a".into(), 100, hash(1)),
+            ("b".into(), 100, hash(1)),
+            ("forked".into(), 100, hash(2)),
+            ("lagging".into(), 90, hash(3)),
+            ("behind_by_one".into(), 99, hash(4)),
+        ]);
+
+        for server in ["a", "b", "behind_by_one"] {
+            assert!(
+                !health.status(server, true).quarantined,
+                "{} must not be quarantined",
+                server
+            );
+        }
+        for server in ["forked", "lagging"] {
+            let status = health.status(server, true);
+            assert!(status.quarantined, "{} must be quarantined", server);
+            assert_eq!(status.score, 0.);
+        }
+
+        let ordered = health.order_by_score(vec!["forked", "lagging", "a"], |server| *server);
+        assert_eq!(ordered, vec!["a"]);
+        // The quarantined servers are used if there are no other ones.
+        let ordered = health.order_by_score(vec!["forked", "lagging"], |server| *server);
+        assert_eq!(ordered, vec!["forked", "lagging"]);
+    }
+
+    #[test]
+    fn test_single_fake_tip_does_not_quarantine_others() {
+        let health = ElectrumServersHealth::new("RICK".into());
+        health.update_tips(vec![
+            ("a".into(), 100, hash(1)),
+            ("b".into(), 101, hash(2)),
+            ("fake".into(), 1_000_000, hash(3)),
+        ]);
+
+        assert!(!health.status("a", true).quarantined);
+        assert!(!health.status("b", true).quarantined);
+        assert!(health.status("fake", true).quarantined);
+    }
+}
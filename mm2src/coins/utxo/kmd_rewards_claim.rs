@@ -0,0 +1,307 @@
+//! Claiming the KMD rewards (interest) by sending the rewarded unspent outputs back to the wallet address.
+
+use super::rpc_clients::UtxoRpcError;
+use super::utxo_common::{big_decimal_from_sat_unsigned, UtxoTxBuilder};
+use super::{kmd_interest, kmd_interest_accrue_stop_at, output_script, sat_from_big_decimal, BroadcastTxErr, FeePolicy,
+            GenerateTxError, GetUtxoListOps, UnspentInfo, UtxoCommonOps, UtxoFeeDetails, UtxoTxBroadcastOps, UTXO_LOCK};
+use crate::{MarketCoinOps, NumConversError, PrivKeyNotAllowed, TransactionDetails, TransactionType,
+            UnexpectedDerivationMethod};
+use chain::TransactionOutput;
+use common::now_ms;
+use derive_more::Display;
+use futures::compat::Future01CompatExt;
+use keys::Type as ScriptType;
+use mm2_err_handle::prelude::*;
+use mm2_number::BigDecimal;
+use rpc::v1::types::{ToTxHash, H256 as H256Json};
+use script::Builder;
+use serialization::serialize;
+use std::collections::HashSet;
+use utxo_signer::with_key_pair::{sign_tx, UtxoSignWithKeyPairError};
+
+/// The rewards stop accruing 31 days after the output is created, so they're claimed 3 days before by default.
+const DEFAULT_EXPIRY_MARGIN: u64 = 3 * 24 * 60 * 60;
+
+pub type KmdRewardsClaimResult<T> = Result<T, MmError<KmdRewardsClaimError>>;
+
+#[derive(Debug, Display)]
+pub enum KmdRewardsClaimError {
+    #[display(fmt = "KMD rewards claiming is not supported: {}", _0)]
+    NotSupported(String),
+    #[display(fmt = "Error generating the transaction: {}", _0)]
+    GenerateTxError(GenerateTxError),
+    #[display(fmt = "Transport error: {}", _0)]
+    Transport(String),
+    #[display(fmt = "Internal error: {}", _0)]
+    Internal(String),
+}
+
+impl From<UtxoRpcError> for KmdRewardsClaimError {
+    fn from(e: UtxoRpcError) -> Self {
+        match e {
+            UtxoRpcError::Internal(e) => KmdRewardsClaimError::Internal(e),
+            e => KmdRewardsClaimError::Transport(e.to_string()),
+        }
+    }
+}
+
+impl From<UnexpectedDerivationMethod> for KmdRewardsClaimError {
+    fn from(e: UnexpectedDerivationMethod) -> Self { KmdRewardsClaimError::NotSupported(e.to_string()) }
+}
+
+impl From<PrivKeyNotAllowed> for KmdRewardsClaimError {
+    fn from(e: PrivKeyNotAllowed) -> Self { KmdRewardsClaimError::NotSupported(e.to_string()) }
+}
+
+impl From<NumConversError> for KmdRewardsClaimError {
+    fn from(e: NumConversError) -> Self { KmdRewardsClaimError::Internal(e.to_string()) }
+}
+
+impl From<GenerateTxError> for KmdRewardsClaimError {
+    fn from(e: GenerateTxError) -> Self { KmdRewardsClaimError::GenerateTxError(e) }
+}
+
+impl From<UtxoSignWithKeyPairError> for KmdRewardsClaimError {
+    fn from(e: UtxoSignWithKeyPairError) -> Self { KmdRewardsClaimError::Internal(format!("Error signing: {}", e)) }
+}
+
+impl From<BroadcastTxErr> for KmdRewardsClaimError {
+    fn from(e: BroadcastTxErr) -> Self { KmdRewardsClaimError::Transport(e.to_string()) }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct KmdRewardsClaimParams {
+    /// The min amount of the accrued rewards worth claiming.
+    #[serde(default = "default_min_rewards")]
+    pub min_rewards: BigDecimal,
+    /// The rewards are claimed regardless of the [`KmdRewardsClaimParams::min_rewards`]
+    /// if any rewarded output stops accruing within this number of seconds.
+    #[serde(default = "default_expiry_margin")]
+    pub expiry_margin: u64,
+}
+
+fn default_min_rewards() -> BigDecimal { "0.1".parse().expect("valid decimal") }
+
+fn default_expiry_margin() -> u64 { DEFAULT_EXPIRY_MARGIN }
+
+impl Default for KmdRewardsClaimParams {
+    fn default() -> Self {
+        KmdRewardsClaimParams {
+            min_rewards: default_min_rewards(),
+            expiry_margin: default_expiry_margin(),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct ClaimedKmdRewards {
+    /// The hash of the broadcast claiming transaction.
+    pub tx_hash: String,
+    /// The claimed rewards amount.
+    pub amount: BigDecimal,
+    pub tx_details: TransactionDetails,
+}
+
+#[derive(Debug)]
+pub(crate) struct RewardedUnspent {
+    pub(crate) unspent: UnspentInfo,
+    /// The accrued rewards in satoshis.
+    pub(crate) rewards: u64,
+    /// The rewards stop accruing at this time.
+    pub(crate) stop_at: u64,
+}
+
+/// Selects the rewarded outputs to claim leaving the outputs worth at least `locked` satoshis untouched.
+/// The not rewarded outputs are left untouched first, then the outputs with the least rewards.
+/// The selected outputs are ordered by value in ascending order as [`UtxoTxBuilder`] expects.
+pub(crate) fn select_unspents_to_claim(
+    mut rewarded: Vec<RewardedUnspent>,
+    not_rewarded_value: u64,
+    locked: u64,
+) -> Vec<RewardedUnspent> {
+    rewarded.sort_by_key(|rewarded| rewarded.rewards);
+    let mut untouched_value = not_rewarded_value;
+    let untouched_number = rewarded
+        .iter()
+        .take_while(|rewarded| {
+            let is_required = untouched_value < locked;
+            untouched_value += rewarded.unspent.value;
+            is_required
+        })
+        .count();
+
+    let mut to_claim = rewarded.split_off(untouched_number);
+    to_claim.sort_by_key(|rewarded| rewarded.unspent.value);
+    to_claim
+}
+
+/// Keeps the evaluated `rewarded` outputs that are still in the `current` unspents,
+/// and returns the total value of the other `current` unspents.
+/// The unspents that weren't evaluated are considered not rewarded, so they're left untouched.
+pub(crate) fn retain_current_rewarded(
+    mut rewarded: Vec<RewardedUnspent>,
+    current: &[UnspentInfo],
+) -> (Vec<RewardedUnspent>, u64) {
+    let current_outpoints: HashSet<_> = current.iter().map(|unspent| unspent.outpoint).collect();
+    rewarded.retain(|rewarded| current_outpoints.contains(&rewarded.unspent.outpoint));
+
+    let rewarded_outpoints: HashSet<_> = rewarded.iter().map(|rewarded| rewarded.unspent.outpoint).collect();
+    let not_rewarded_value = current
+        .iter()
+        .filter(|unspent| !rewarded_outpoints.contains(&unspent.outpoint))
+        .map(|unspent| unspent.value)
+        .sum();
+    (rewarded, not_rewarded_value)
+}
+
+/// Evaluates the rewards accrued by the `unspents` at the `current_time`. Returns the rewarded outputs only.
+/// Requests the verbose transaction of every unspent, so it shouldn't be called under the [`UTXO_LOCK`].
+async fn evaluate_rewards<T>(
+    coin: &T,
+    unspents: Vec<UnspentInfo>,
+    current_time: u64,
+) -> KmdRewardsClaimResult<Vec<RewardedUnspent>>
+where
+    T: UtxoCommonOps,
+{
+    let mut rewarded = Vec::new();
+    for unspent in unspents {
+        let tx_hash: H256Json = unspent.outpoint.hash.reversed().into();
+        let tx = coin
+            .as_ref()
+            .rpc_client
+            .get_verbose_transaction(&tx_hash)
+            .compat()
+            .await?;
+        let locktime = tx.locktime as u64;
+        if let (Ok(rewards), Some(height)) = (
+            kmd_interest(tx.height, unspent.value, locktime, current_time),
+            tx.height,
+        ) {
+            rewarded.push(RewardedUnspent {
+                unspent,
+                rewards,
+                stop_at: kmd_interest_accrue_stop_at(height, locktime),
+            });
+        }
+    }
+    Ok(rewarded)
+}
+
+/// Whether the rewards are worth claiming or some of them are about to stop accruing.
+pub(crate) fn should_claim_rewards(to_claim: &[RewardedUnspent], min_rewards: u64, expiry_deadline: u64) -> bool {
+    let total_rewards: u64 = to_claim.iter().map(|rewarded| rewarded.rewards).sum();
+    total_rewards > 0
+        && (total_rewards >= min_rewards || to_claim.iter().any(|rewarded| rewarded.stop_at <= expiry_deadline))
+}
+
+/// Claims the accrued rewards and broadcasts the claiming transaction if the rewards are worth claiming
+/// according to the `params`, returns `None` otherwise.
+/// The outputs worth at least `locked_amount` are left untouched, so the funds locked by the active swaps stay spendable.
+pub async fn claim_kmd_rewards_if_required<T>(
+    coin: &T,
+    params: &KmdRewardsClaimParams,
+    locked_amount: &BigDecimal,
+) -> KmdRewardsClaimResult<Option<ClaimedKmdRewards>>
+where
+    T: UtxoCommonOps + GetUtxoListOps + UtxoTxBroadcastOps + MarketCoinOps,
+{
+    let utxo = coin.as_ref();
+    let ticker = utxo.conf.ticker.clone();
+    if ticker != "KMD" {
+        return MmError::err(KmdRewardsClaimError::NotSupported(format!(
+            "{} doesn't accrue the rewards",
+            ticker
+        )));
+    }
+    let decimals = utxo.decimals;
+    let my_address = utxo.derivation_method.iguana_or_err()?;
+    let key_pair = utxo.priv_key_policy.key_pair_or_err()?;
+
+    // The rewards are evaluated before taking the lock, so the other transactions aren't blocked by the RPC calls.
+    let (unspents, _) = coin.get_unspent_ordered_list(my_address).await?;
+    let current_time = coin.get_current_mtp().await? as u64;
+    let rewarded = evaluate_rewards(coin, unspents, current_time).await?;
+
+    let _utxo_lock = UTXO_LOCK.lock().await;
+    // The unspents could be spent while the rewards were being evaluated.
+    let (unspents, mut recently_spent) = coin.get_unspent_ordered_list(my_address).await?;
+    let (rewarded, not_rewarded_value) = retain_current_rewarded(rewarded, &unspents);
+
+    let locked = sat_from_big_decimal(locked_amount, decimals)?;
+    let to_claim = select_unspents_to_claim(rewarded, not_rewarded_value, locked);
+    let min_rewards = sat_from_big_decimal(&params.min_rewards, decimals)?;
+    if !should_claim_rewards(&to_claim, min_rewards, current_time + params.expiry_margin) {
+        return Ok(None);
+    }
+
+    let value = to_claim.iter().map(|rewarded| rewarded.unspent.value).sum();
+    let script_pubkey = output_script(my_address, ScriptType::P2PKH).to_bytes();
+    // The rewards are added to the output by the builder.
+    let (unsigned, data) = UtxoTxBuilder::new(coin)
+        .add_available_inputs(to_claim.into_iter().map(|rewarded| rewarded.unspent))
+        .add_outputs(vec![TransactionOutput { value, script_pubkey }])
+        .with_fee_policy(FeePolicy::DeductFromOutput(0))
+        .build()
+        .await?;
+    let spent_unspents = unsigned
+        .inputs
+        .iter()
+        .map(|input| UnspentInfo {
+            outpoint: input.previous_output,
+            value: input.amount,
+            height: None,
+        })
+        .collect();
+
+    let prev_script = Builder::build_p2pkh(&my_address.hash);
+    let signed = sign_tx(
+        unsigned,
+        key_pair,
+        prev_script,
+        utxo.conf.signature_version,
+        utxo.conf.fork_id,
+    )?;
+    coin.broadcast_tx(&signed).await?;
+    recently_spent.add_spent(spent_unspents, signed.hash(), signed.outputs.clone());
+
+    let my_address = coin.my_address().map_to_mm(KmdRewardsClaimError::Internal)?;
+    let tx_hash = signed.hash().reversed().to_vec().to_tx_hash();
+    let amount = data
+        .kmd_rewards
+        .as_ref()
+        .map(|rewards| rewards.amount.clone())
+        .unwrap_or_default();
+    let fee_amount = data.fee_amount + data.unused_change.unwrap_or_default();
+    let tx_details = TransactionDetails {
+        tx_hex: serialize(&signed).into(),
+        tx_hash: tx_hash.clone(),
+        from: vec![my_address.clone()],
+        to: vec![my_address],
+        total_amount: big_decimal_from_sat_unsigned(data.spent_by_me, decimals),
+        spent_by_me: big_decimal_from_sat_unsigned(data.spent_by_me, decimals),
+        received_by_me: big_decimal_from_sat_unsigned(data.received_by_me, decimals),
+        // The rewards are usually greater than the fee, so the balance is increased.
+        my_balance_change: big_decimal_from_sat_unsigned(data.received_by_me, decimals)
+            - big_decimal_from_sat_unsigned(data.spent_by_me, decimals),
+        block_height: 0,
+        timestamp: now_ms() / 1000,
+        fee_details: Some(
+            UtxoFeeDetails {
+                coin: Some(ticker.clone()),
+                amount: big_decimal_from_sat_unsigned(fee_amount, decimals),
+            }
+            .into(),
+        ),
+        coin: ticker,
+        internal_id: signed.hash().reversed().to_vec().into(),
+        kmd_rewards: data.kmd_rewards,
+        spv_verified: None,
+        transaction_type: TransactionType::ClaimKmdRewards,
+    };
+    Ok(Some(ClaimedKmdRewards {
+        tx_hash,
+        amount,
+        tx_details,
+    }))
+}
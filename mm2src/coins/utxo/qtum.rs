@@ -6,18 +6,23 @@ use crate::hd_wallet::{self, AccountUpdatingError, AddressDerivingError, GetNewH
                        GetNewHDAddressResponse, HDAccountMut, HDWalletRpcError, HDWalletRpcOps,
                        NewAccountCreatingError};
 use crate::hd_wallet_storage::HDWalletCoinWithStorageOps;
+use crate::my_tx_history_v2::{load_history_from_storage, CoinWithTxHistoryV2, MyTxHistoryErrorV2, TxHistoryStorage};
 use crate::rpc_command::account_balance::{self, AccountBalanceParams, AccountBalanceRpcOps, HDAccountBalanceResponse};
 use crate::rpc_command::hd_account_balance_rpc_error::HDAccountBalanceRpcError;
 use crate::rpc_command::init_create_account::{self, CreateNewAccountParams, InitCreateHDAccountRpcOps};
 use crate::rpc_command::init_scan_for_new_addresses::{self, InitScanAddressesRpcOps, ScanAddressesParams,
                                                       ScanAddressesResponse};
 use crate::rpc_command::init_withdraw::{InitWithdrawCoin, WithdrawTaskHandle};
+use crate::tx_history_storage::{GetTxHistoryFilters, WalletId};
+use crate::utxo::bch::GetTxDetailsError;
 use crate::utxo::utxo_builder::{MergeUtxoArcOps, UtxoCoinBuildError, UtxoCoinBuilder, UtxoCoinBuilderCommonOps,
                                 UtxoFieldsWithHardwareWalletBuilder, UtxoFieldsWithIguanaPrivKeyBuilder};
-use crate::{eth, CanRefundHtlc, CoinBalance, CoinWithDerivationMethod, DelegationError, DelegationFut,
-            GetWithdrawSenderAddress, NegotiateSwapContractAddrErr, PrivKeyBuildPolicy, SearchForSwapTxSpendInput,
-            SignatureResult, StakingInfosFut, SwapOps, TradePreimageValue, TransactionFut, UnexpectedDerivationMethod,
-            ValidateAddressResult, ValidatePaymentInput, VerificationResult, WithdrawFut, WithdrawSenderAddress};
+use crate::utxo::utxo_tx_history_v2::{process_history_loop_v2, UtxoMyAddressesHistoryError, UtxoTxHistoryOps};
+use crate::{eth, BlockHeightAndTime, CanRefundHtlc, CoinBalance, CoinWithDerivationMethod, DelegationError,
+            DelegationFut, GetWithdrawSenderAddress, NegotiateSwapContractAddrErr, PrivKeyBuildPolicy,
+            SearchForSwapTxSpendInput, SignatureResult, StakingInfosFut, SwapOps, TradePreimageValue, TransactionFut,
+            TxHistoryFut, UnbroadcastSwapPaymentInput, UnexpectedDerivationMethod, ValidateAddressResult,
+            ValidatePaymentInput, VerificationResult, WithdrawFut, WithdrawSenderAddress};
 use common::mm_metrics::MetricsArc;
 use crypto::trezor::utxo::TrezorUtxoCoin;
 use crypto::Bip44Chain;
@@ -481,10 +486,6 @@ impl UtxoStandardOps for QtumCoin {
         utxo_common::tx_details_by_hash(self, hash, input_transactions).await
     }
 
-    async fn request_tx_history(&self, metrics: MetricsArc) -> RequestTxHistoryResult {
-        utxo_common::request_tx_history(self, metrics).await
-    }
-
     async fn update_kmd_rewards(
         &self,
         tx_details: &mut TransactionDetails,
@@ -494,6 +495,58 @@ impl UtxoStandardOps for QtumCoin {
     }
 }
 
+#[async_trait]
+impl CoinWithTxHistoryV2 for QtumCoin {
+    fn history_wallet_id(&self) -> WalletId { utxo_common::history_wallet_id(self.as_ref()) }
+
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        utxo_common::get_tx_history_filters(self).await
+    }
+}
+
+#[async_trait]
+impl UtxoTxHistoryOps for QtumCoin {
+    async fn my_addresses(&self) -> MmResult<HashSet<Address>, UtxoMyAddressesHistoryError> {
+        utxo_common::my_addresses(self).await
+    }
+
+    async fn request_tx_history(
+        &self,
+        metrics: MetricsArc,
+        for_addresses: &HashSet<Address>,
+    ) -> RequestTxHistoryResult {
+        utxo_common::request_tx_history(self, metrics, for_addresses).await
+    }
+
+    async fn get_block_timestamp(&self, height: u64) -> MmResult<u64, UtxoRpcError> {
+        self.as_ref().rpc_client.get_block_timestamp(height).await
+    }
+
+    async fn tx_details_by_hash<Storage: TxHistoryStorage>(
+        &self,
+        tx_hash: &H256Json,
+        block_height_and_time: Option<BlockHeightAndTime>,
+        storage: &Storage,
+        my_addresses: &HashSet<Address>,
+    ) -> MmResult<Vec<TransactionDetails>, GetTxDetailsError<Storage::Error>> {
+        let tx_details =
+            utxo_common::tx_details_with_storage(self, tx_hash, block_height_and_time, storage, my_addresses).await?;
+        Ok(vec![tx_details])
+    }
+
+    async fn spv_verify_tx(
+        &self,
+        tx_hash: &H256Json,
+        height: u64,
+    ) -> MmResult<Option<bool>, utxo_spv::SpvVerificationError> {
+        utxo_spv::spv_verify_tx(self.as_ref(), tx_hash, height).await
+    }
+
+    fn set_history_sync_state(&self, new_state: HistorySyncState) {
+        *self.as_ref().history_sync_state.lock().unwrap() = new_state;
+    }
+}
+
 #[async_trait]
 impl SwapOps for QtumCoin {
     fn send_taker_fee(&self, fee_addr: &[u8], amount: BigDecimal, _uuid: &[u8]) -> TransactionFut {
@@ -695,6 +748,17 @@ impl SwapOps for QtumCoin {
     fn derive_htlc_key_pair(&self, swap_unique_data: &[u8]) -> KeyPair {
         utxo_common::derive_htlc_key_pair(self.as_ref(), swap_unique_data)
     }
+
+    fn gen_unbroadcast_swap_payment(&self, input: UnbroadcastSwapPaymentInput) -> TransactionFut {
+        utxo_common::gen_unbroadcast_swap_payment(self.clone(), input)
+    }
+
+    fn validate_unbroadcast_swap_payment(
+        &self,
+        input: ValidatePaymentInput,
+    ) -> Box<dyn Future<Item = (), Error = String> + Send> {
+        utxo_common::validate_unbroadcast_swap_payment(self, input)
+    }
 }
 
 impl MarketCoinOps for QtumCoin {
@@ -788,6 +852,8 @@ impl MarketCoinOps for QtumCoin {
 impl MmCoin for QtumCoin {
     fn is_asset_chain(&self) -> bool { utxo_common::is_asset_chain(&self.utxo_arc) }
 
+    fn wallet_only(&self, ctx: &MmArc) -> bool { utxo_common::wallet_only(&self.utxo_arc, ctx) }
+
     fn get_raw_transaction(&self, req: RawTransactionRequest) -> RawTransactionFut {
         Box::new(utxo_common::get_raw_transaction(&self.utxo_arc, req).boxed().compat())
     }
@@ -807,13 +873,17 @@ impl MmCoin for QtumCoin {
 
     fn process_history_loop(&self, ctx: MmArc) -> Box<dyn Future<Item = (), Error = ()> + Send> {
         Box::new(
-            utxo_common::process_history_loop(self.clone(), ctx)
+            process_history_loop_v2(self.clone(), ctx)
                 .map(|_| Ok(()))
                 .boxed()
                 .compat(),
         )
     }
 
+    fn load_history(&self, ctx: &MmArc) -> TxHistoryFut<Vec<TransactionDetails>> {
+        load_history_from_storage(self, ctx)
+    }
+
     fn history_sync_status(&self) -> HistorySyncState { utxo_common::history_sync_status(&self.utxo_arc) }
 
     fn get_trade_fee(&self) -> Box<dyn Future<Item = TradeFee, Error = String> + Send> {
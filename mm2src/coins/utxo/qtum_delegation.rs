@@ -7,8 +7,9 @@ use crate::utxo::rpc_clients::UtxoRpcClientEnum;
 use crate::utxo::utxo_common::{big_decimal_from_sat_unsigned, UtxoTxBuilder};
 use crate::utxo::{qtum, utxo_common, Address, GetUtxoListOps, UtxoCommonOps};
 use crate::utxo::{PrivKeyNotAllowed, UTXO_LOCK};
-use crate::{DelegationError, DelegationFut, DelegationResult, MarketCoinOps, StakingInfos, StakingInfosError,
-            StakingInfosFut, StakingInfosResult, TransactionDetails, TransactionType};
+use crate::{DelegationError, DelegationFut, DelegationResult, MarketCoinOps, StakingDetails, StakingInfos,
+            StakingInfosError, StakingInfosFut, StakingInfosResult, StakingOps, StakingRewardsFut,
+            StakingValidatorsFut, TransactionDetails, TransactionType};
 use bitcrypto::dhash256;
 use common::now_ms;
 use derive_more::Display;
@@ -114,6 +115,48 @@ impl QtumDelegationOps for QtumCoin {
     }
 }
 
+/// Qtum delegates the whole balance to a single staker that can be any Qtum address,
+/// and the staker pays the rewards to the delegator address directly.
+impl StakingOps for QtumCoin {
+    fn validators(&self) -> StakingValidatorsFut {
+        Box::new(futures01::future::err(MmError::new(
+            StakingInfosError::StakingOpsNotSupported {
+                reason: "Qtum can delegate to any staker address, there is no validators set".to_owned(),
+            },
+        )))
+    }
+
+    fn delegate(&self, details: StakingDetails) -> DelegationFut {
+        match details {
+            StakingDetails::Qtum(request) => self.add_delegation(request),
+            #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
+            StakingDetails::Solana(_) => Box::new(futures01::future::err(MmError::new(
+                DelegationError::InvalidStakingDetails(format!("{} expects the 'Qtum' staking details", self.ticker())),
+            ))),
+        }
+    }
+
+    fn undelegate(&self, _validator: Option<String>) -> DelegationFut { self.remove_delegation() }
+
+    fn claim_rewards(&self, _validator: Option<String>) -> DelegationFut {
+        Box::new(futures01::future::err(MmError::new(
+            DelegationError::DelegationOpsNotSupported {
+                reason: "Qtum staking rewards are paid to the delegator address directly".to_owned(),
+            },
+        )))
+    }
+
+    fn pending_rewards(&self) -> StakingRewardsFut {
+        Box::new(futures01::future::err(MmError::new(
+            StakingInfosError::StakingOpsNotSupported {
+                reason: "Qtum staking rewards are paid to the delegator address directly".to_owned(),
+            },
+        )))
+    }
+
+    fn staking_infos(&self) -> StakingInfosFut { self.get_delegation_infos() }
+}
+
 impl QtumCoin {
     async fn remove_delegation_impl(&self) -> DelegationResult {
         if self.addr_format().is_segwit() {
@@ -137,10 +180,8 @@ impl QtumCoin {
         let utxo = self.as_ref();
         let contract_address = contract_addr_into_rpc_format(&QTUM_DELEGATE_CONTRACT_ADDRESS);
         let client = match &utxo.rpc_client {
-            UtxoRpcClientEnum::Native(_) => {
-                return MmError::err(StakingInfosError::Internal("Native not supported".to_string()))
-            },
             UtxoRpcClientEnum::Electrum(electrum) => electrum,
+            _ => return MmError::err(StakingInfosError::Internal("Only electrum is supported".to_string())),
         };
         let address = self.my_addr_as_contract_addr()?;
         let address_rpc = contract_addr_into_rpc_format(&address);
@@ -339,6 +380,7 @@ impl QtumCoin {
             coin: self.ticker().to_string(),
             internal_id: vec![].into(),
             kmd_rewards: None,
+            spv_verified: None,
             transaction_type,
         })
     }
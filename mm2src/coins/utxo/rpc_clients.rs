@@ -1,6 +1,7 @@
 #![cfg_attr(target_arch = "wasm32", allow(unused_macros))]
 #![cfg_attr(target_arch = "wasm32", allow(dead_code))]
 
+use crate::utxo::electrum_health::{ElectrumServerStatus, ElectrumServersHealth};
 use crate::utxo::{output_script, sat_from_big_decimal};
 use crate::{big_decimal_from_sat_unsigned, NumConversError, RpcTransportEventHandler, RpcTransportEventHandlerShared};
 use async_trait::async_trait;
@@ -16,7 +17,7 @@ use common::{median, now_float, now_ms, OrdRange};
 use derive_more::Display;
 use futures::channel::oneshot as async_oneshot;
 use futures::compat::{Future01CompatExt, Stream01CompatExt};
-use futures::future::{select as select_func, FutureExt, TryFutureExt};
+use futures::future::{join_all, select as select_func, FutureExt, TryFutureExt};
 use futures::lock::Mutex as AsyncMutex;
 use futures::{select, StreamExt};
 use futures01::future::select_ok;
@@ -46,10 +47,11 @@ use std::sync::Arc;
 use std::time::Duration;
 
 cfg_native! {
-    use futures::future::Either;
+    use crate::utxo::compact_filters::CompactFiltersClient;
     use futures::io::Error;
     use http::header::AUTHORIZATION;
     use http::{Request, StatusCode};
+    use mm2_net::socks5::{is_onion_host, socks5_connect, Socks5Proxy};
     use rustls::client::ServerCertVerified;
     use rustls::{Certificate, ClientConfig, ServerName, OwnedTrustAnchor, RootCertStore};
     use std::convert::TryFrom;
@@ -99,6 +101,8 @@ impl rustls::client::ServerCertVerifier for NoCertificateVerification {
 pub enum UtxoRpcClientEnum {
     Native(NativeClient),
     Electrum(ElectrumClient),
+    #[cfg(not(target_arch = "wasm32"))]
+    CompactFilters(CompactFiltersClient),
 }
 
 impl From<ElectrumClient> for UtxoRpcClientEnum {
@@ -109,12 +113,19 @@ impl From<NativeClient> for UtxoRpcClientEnum {
     fn from(client: NativeClient) -> UtxoRpcClientEnum { UtxoRpcClientEnum::Native(client) }
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+impl From<CompactFiltersClient> for UtxoRpcClientEnum {
+    fn from(client: CompactFiltersClient) -> UtxoRpcClientEnum { UtxoRpcClientEnum::CompactFilters(client) }
+}
+
 impl Deref for UtxoRpcClientEnum {
     type Target = dyn UtxoRpcClientOps;
     fn deref(&self) -> &dyn UtxoRpcClientOps {
         match self {
             UtxoRpcClientEnum::Native(ref c) => c,
             UtxoRpcClientEnum::Electrum(ref c) => c,
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(ref c) => c,
         }
     }
 }
@@ -124,6 +135,8 @@ impl Clone for UtxoRpcClientEnum {
         match self {
             UtxoRpcClientEnum::Native(c) => UtxoRpcClientEnum::Native(c.clone()),
             UtxoRpcClientEnum::Electrum(c) => UtxoRpcClientEnum::Electrum(c.clone()),
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(c) => UtxoRpcClientEnum::CompactFilters(c.clone()),
         }
     }
 }
@@ -199,6 +212,8 @@ impl UtxoRpcClientEnum {
         match self {
             UtxoRpcClientEnum::Native(_) => true,
             UtxoRpcClientEnum::Electrum(_) => false,
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(_) => false,
         }
     }
 }
@@ -1349,8 +1364,23 @@ pub struct ElectrumRpcRequest {
 #[cfg(not(target_arch = "wasm32"))]
 #[derive(Clone, Debug, Serialize)]
 enum ElectrumConfig {
-    TCP,
-    SSL { dns_name: String, skip_validation: bool },
+    TCP {
+        proxy: Option<Socks5Proxy>,
+    },
+    SSL {
+        dns_name: String,
+        skip_validation: bool,
+        proxy: Option<Socks5Proxy>,
+    },
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl ElectrumConfig {
+    fn proxy(&self) -> Option<&Socks5Proxy> {
+        match self {
+            ElectrumConfig::TCP { proxy } | ElectrumConfig::SSL { proxy, .. } => proxy.as_ref(),
+        }
+    }
 }
 
 /// Electrum client configuration
@@ -1372,26 +1402,43 @@ fn addr_to_socket_addr(input: &str) -> Result<SocketAddr, String> {
     }
 }
 
-/// Attempts to process the request (parse url, etc), build up the config and create new electrum connection
+/// Splits the server `addr` like `electrum1.cipig.net:10001` into the host and the port.
+#[cfg(not(target_arch = "wasm32"))]
+pub fn host_and_port(addr: &str) -> Result<(String, u16), String> {
+    let uri: Uri = try_s!(addr.parse());
+    let host = uri.host().ok_or(ERRL!("Couldn't retrieve host from addr {}", addr))?;
+    let port = uri
+        .port_u16()
+        .ok_or(ERRL!("Couldn't retrieve port from addr {}", addr))?;
+    Ok((host.to_owned(), port))
+}
+
+/// Attempts to process the request (parse url, etc), build up the config and create new electrum connection.
+/// The connection is established through the SOCKS5 `proxy` if it's set, it's required for the `.onion` servers.
 #[cfg(not(target_arch = "wasm32"))]
 pub fn spawn_electrum(
     req: &ElectrumRpcRequest,
+    proxy: Option<Socks5Proxy>,
     event_handlers: Vec<RpcTransportEventHandlerShared>,
 ) -> Result<ElectrumConnection, String> {
+    let (host, _port) = try_s!(host_and_port(&req.url));
+    if proxy.is_none() && is_onion_host(&host) {
+        return ERR!(
+            "'.onion' electrum server {} can be reached through the 'proxy' only",
+            req.url
+        );
+    }
+
     let config = match req.protocol {
-        ElectrumProtocol::TCP => ElectrumConfig::TCP,
+        ElectrumProtocol::TCP => ElectrumConfig::TCP { proxy },
         ElectrumProtocol::SSL => {
-            let uri: Uri = try_s!(req.url.parse());
-            let host = uri
-                .host()
-                .ok_or(ERRL!("Couldn't retrieve host from addr {}", req.url))?;
-
             // check the dns name
-            try_s!(DnsNameRef::try_from_ascii_str(host));
+            try_s!(DnsNameRef::try_from_ascii_str(&host));
 
             ElectrumConfig::SSL {
-                dns_name: host.into(),
+                dns_name: host,
                 skip_validation: req.disable_cert_verification,
+                proxy,
             }
         },
         ElectrumProtocol::WS | ElectrumProtocol::WSS => {
@@ -1539,52 +1586,68 @@ pub struct ElectrumClientImpl {
     protocol_version: OrdRange<f32>,
     get_balance_concurrent_map: ConcurrentRequestMap<String, ElectrumBalance>,
     list_unspent_concurrent_map: ConcurrentRequestMap<String, Vec<ElectrumUnspent>>,
+    /// The health of the servers used to choose the servers the requests are sent to.
+    health: Arc<ElectrumServersHealth>,
+    /// The SOCKS5 proxy the connections are established through.
+    #[cfg(not(target_arch = "wasm32"))]
+    proxy: Option<Socks5Proxy>,
 }
 
 async fn electrum_request_multi(
     client: ElectrumClient,
     request: JsonRpcRequestEnum,
 ) -> Result<(JsonRpcRemoteAddr, JsonRpcResponseEnum), String> {
-    let mut futures = vec![];
     let connections = client.connections.lock().await;
-    for (i, connection) in connections.iter().enumerate() {
-        let connection_addr = connection.addr.clone();
-        match &*connection.tx.lock().await {
-            Some(tx) => {
-                let fut = electrum_request(
-                    request.clone(),
-                    tx.clone(),
-                    connection.responses.clone(),
-                    ELECTRUM_TIMEOUT / (connections.len() - i) as u64,
-                )
-                .map(|response| (JsonRpcRemoteAddr(connection_addr), response));
-                futures.push(fut)
-            },
-            None => (),
+    let mut connected = vec![];
+    for connection in connections.iter() {
+        if let Some(tx) = &*connection.tx.lock().await {
+            connected.push((connection, tx.clone()));
         }
     }
-    drop(connections);
-    if futures.is_empty() {
+    if connected.is_empty() {
         return ERR!("All electrums are currently disconnected");
     }
 
-    match request {
-        JsonRpcRequestEnum::Single(single) if single.method == "server.ping" => {
-            // server.ping must be sent to all servers to keep all connections alive
-            return select_ok(futures)
-                .map(|(result, _)| result)
-                .map_err(|e| ERRL!("{:?}", e))
-                .compat()
-                .await;
-        },
-        _ => (),
+    // server.ping must be sent to all servers to keep all connections alive
+    let is_ping = matches!(&request, JsonRpcRequestEnum::Single(single) if single.method == "server.ping");
+    if !is_ping {
+        connected = client
+            .health
+            .order_by_score(connected, |(connection, _)| connection.addr.as_str());
+    }
+
+    let connected_len = connected.len();
+    let futures: Vec<_> = connected
+        .into_iter()
+        .enumerate()
+        .map(|(i, (connection, tx))| {
+            let connection_addr = connection.addr.clone();
+            electrum_request(
+                request.clone(),
+                tx,
+                connection.responses.clone(),
+                ELECTRUM_TIMEOUT / (connected_len - i) as u64,
+                connection_addr.clone(),
+                client.event_handlers.clone(),
+            )
+            .map(|response| (JsonRpcRemoteAddr(connection_addr), response))
+        })
+        .collect();
+    drop(connections);
+
+    if is_ping {
+        return select_ok(futures)
+            .map(|(result, _)| result)
+            .map_err(|e| ERRL!("{:?}", e))
+            .compat()
+            .await;
     }
 
-    let (res, no_of_failed_requests) = select_ok_sequential(futures)
+    // The failed servers are moved down by their error rate, so the number of the failed requests isn't needed.
+    let (res, _) = select_ok_sequential(futures)
         .compat()
         .await
         .map_err(|e| ERRL!("{:?}", e))?;
-    client.rotate_servers(no_of_failed_requests).await;
     Ok(res)
 }
 
@@ -1610,9 +1673,16 @@ async fn electrum_request_to(
     };
 
     let response = try_s!(
-        electrum_request(request.clone(), tx, responses, ELECTRUM_TIMEOUT)
-            .compat()
-            .await
+        electrum_request(
+            request.clone(),
+            tx,
+            responses,
+            ELECTRUM_TIMEOUT,
+            to_addr.clone(),
+            client.event_handlers.clone()
+        )
+        .compat()
+        .await
     );
     Ok((JsonRpcRemoteAddr(to_addr.to_owned()), response))
 }
@@ -1620,6 +1690,9 @@ async fn electrum_request_to(
 impl ElectrumClientImpl {
     /// Create an Electrum connection and spawn a green thread actor to handle it.
     pub async fn add_server(&self, req: &ElectrumRpcRequest) -> Result<(), String> {
+        #[cfg(not(target_arch = "wasm32"))]
+        let connection = try_s!(spawn_electrum(req, self.proxy.clone(), self.event_handlers.clone()));
+        #[cfg(target_arch = "wasm32")]
         let connection = try_s!(spawn_electrum(req, self.event_handlers.clone()));
         self.connections.lock().await.push(connection);
         Ok(())
@@ -1635,15 +1708,10 @@ impl ElectrumClientImpl {
             .ok_or(ERRL!("Unknown electrum address {}", server_addr))?;
         // shutdown_tx will be closed immediately on the connection drop
         connections.remove(pos);
+        self.health.remove_server(server_addr);
         Ok(())
     }
 
-    /// Moves the Electrum servers that fail in a multi request to the end.
-    pub async fn rotate_servers(&self, no_of_rotations: usize) {
-        let mut connections = self.connections.lock().await;
-        connections.rotate_left(no_of_rotations);
-    }
-
     /// Check if one of the spawned connections is connected.
     pub async fn is_connected(&self) -> bool {
         for connection in self.connections.lock().await.iter() {
@@ -1674,9 +1742,20 @@ impl ElectrumClientImpl {
             .find(|con| con.addr == server_addr)
             .ok_or(ERRL!("Unknown electrum address {}", server_addr))?;
         con.set_protocol_version(version).await;
+        self.health.set_protocol_version(server_addr, version);
         Ok(())
     }
 
+    /// Returns the health of every server in the configured order.
+    pub async fn servers_status(&self) -> Vec<ElectrumServerStatus> {
+        let mut statuses = vec![];
+        for connection in self.connections.lock().await.iter() {
+            let connected = connection.is_connected().await;
+            statuses.push(self.health.status(&connection.addr, connected));
+        }
+        statuses
+    }
+
     /// Get available protocol versions.
     pub fn protocol_version(&self) -> &OrdRange<f32> { &self.protocol_version }
 }
@@ -1809,6 +1888,33 @@ impl ElectrumClient {
         rpc_func!(self, "blockchain.headers.subscribe")
     }
 
+    /// The same as [`ElectrumClient::blockchain_headers_subscribe`], but the request is sent to the specified server.
+    pub fn blockchain_headers_subscribe_from(&self, server_address: &str) -> RpcRes<ElectrumBlockHeader> {
+        rpc_func_from!(self, server_address, "blockchain.headers.subscribe")
+    }
+
+    /// Requests the tip of every connected server and quarantines the servers which tip is inconsistent with the others.
+    pub async fn check_servers_tips(&self) {
+        let mut servers = vec![];
+        for connection in self.connections.lock().await.iter() {
+            if connection.is_connected().await {
+                servers.push(connection.addr.clone());
+            }
+        }
+
+        let tip_futures = servers.into_iter().map(|server| {
+            self.blockchain_headers_subscribe_from(&server)
+                .compat()
+                .map(move |res| {
+                    res.ok()
+                        .map(|header| (server, header.block_height(), header.block_hash()))
+                })
+        });
+        // The failed requests are already counted by the health event handler.
+        let tips = join_all(tip_futures).await.into_iter().flatten().collect();
+        self.health.update_tips(tips);
+    }
+
     /// https://electrumx.readthedocs.io/en/latest/protocol-methods.html#blockchain-transaction-broadcast
     pub fn blockchain_transaction_broadcast(&self, tx: BytesJson) -> RpcRes<H256Json> {
         rpc_func!(self, "blockchain.transaction.broadcast", tx)
@@ -1826,7 +1932,10 @@ impl ElectrumClient {
             None => Box::new(rpc_func!(self, "blockchain.estimatefee", n_blocks).map_to_mm_fut(UtxoRpcError::from)),
         }
     }
+}
 
+#[cfg_attr(test, mockable)]
+impl ElectrumClient {
     /// https://electrumx.readthedocs.io/en/latest/protocol-methods.html#blockchain-block-header
     pub fn blockchain_block_header(&self, height: u64) -> RpcRes<BytesJson> {
         rpc_func!(self, "blockchain.block.header", height)
@@ -2117,8 +2226,10 @@ impl UtxoRpcClientOps for ElectrumClient {
 
 #[cfg_attr(test, mockable)]
 impl ElectrumClientImpl {
-    pub fn new(coin_ticker: String, event_handlers: Vec<RpcTransportEventHandlerShared>) -> ElectrumClientImpl {
+    pub fn new(coin_ticker: String, mut event_handlers: Vec<RpcTransportEventHandlerShared>) -> ElectrumClientImpl {
         let protocol_version = OrdRange::new(1.2, 1.4).unwrap();
+        let health = Arc::new(ElectrumServersHealth::new(coin_ticker.clone()));
+        event_handlers.push(health.clone());
         ElectrumClientImpl {
             coin_ticker,
             connections: AsyncMutex::new(vec![]),
@@ -2127,9 +2238,16 @@ impl ElectrumClientImpl {
             protocol_version,
             get_balance_concurrent_map: ConcurrentRequestMap::new(),
             list_unspent_concurrent_map: ConcurrentRequestMap::new(),
+            health,
+            #[cfg(not(target_arch = "wasm32"))]
+            proxy: None,
         }
     }
 
+    /// Sets the SOCKS5 proxy the servers added afterwards are connected through.
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn set_proxy(&mut self, proxy: Option<Socks5Proxy>) { self.proxy = proxy; }
+
     #[cfg(test)]
     pub fn with_protocol_version(
         coin_ticker: String,
@@ -2348,13 +2466,24 @@ async fn connect_loop(
             Timer::sleep(current_delay as f64).await;
         };
 
-        let socket_addr = try_loop!(addr_to_socket_addr(&addr), addr, delay);
+        // The host name is resolved by the proxy, so the DNS requests don't leak.
+        let tcp_stream = match config.proxy() {
+            Some(proxy) => {
+                let (host, port) = try_loop!(host_and_port(&addr), addr, delay);
+                try_loop!(socks5_connect(proxy, &host, port).await, addr, delay)
+            },
+            None => {
+                let socket_addr = try_loop!(addr_to_socket_addr(&addr), addr, delay);
+                try_loop!(TcpStream::connect(&socket_addr).await, addr, delay)
+            },
+        };
 
-        let connect_f = match config.clone() {
-            ElectrumConfig::TCP => Either::Left(TcpStream::connect(&socket_addr).map_ok(ElectrumStream::Tcp)),
+        let stream = match config.clone() {
+            ElectrumConfig::TCP { .. } => ElectrumStream::Tcp(tcp_stream),
             ElectrumConfig::SSL {
                 dns_name,
                 skip_validation,
+                ..
             } => {
                 let tls_connector = if skip_validation {
                     TlsConnector::from(UNSAFE_TLS_CONFIG.clone())
@@ -2362,17 +2491,14 @@ async fn connect_loop(
                     TlsConnector::from(SAFE_TLS_CONFIG.clone())
                 };
 
-                Either::Right(TcpStream::connect(&socket_addr).and_then(move |stream| {
-                    // Can use `unwrap` cause `dns_name` is pre-checked.
-                    let dns = ServerName::try_from(dns_name.as_str())
-                        .map_err(|e| format!("{:?}", e))
-                        .unwrap();
-                    tls_connector.connect(dns, stream).map_ok(ElectrumStream::Tls)
-                }))
+                // Can use `unwrap` cause `dns_name` is pre-checked.
+                let dns = ServerName::try_from(dns_name.as_str())
+                    .map_err(|e| format!("{:?}", e))
+                    .unwrap();
+                ElectrumStream::Tls(try_loop!(tls_connector.connect(dns, tcp_stream).await, addr, delay))
             },
         };
 
-        let stream = try_loop!(connect_f.await, addr, delay);
         try_loop!(stream.as_ref().set_nodelay(true), addr, delay);
         info!("Electrum client connected to {}", addr);
         try_loop!(event_handlers.on_connected(addr.clone()), addr, delay);
@@ -2584,11 +2710,14 @@ fn electrum_connect(
     }
 }
 
+/// Sends the request to the `server_addr` connection and reports the latency and the result to the `event_handlers`.
 fn electrum_request(
     request: JsonRpcRequestEnum,
     tx: mpsc::Sender<Vec<u8>>,
     responses: JsonRpcPendingRequestsShared,
     timeout: u64,
+    server_addr: String,
+    event_handlers: Vec<RpcTransportEventHandlerShared>,
 ) -> Box<dyn Future<Item = JsonRpcResponseEnum, Error = String> + Send + 'static> {
     let send_fut = async move {
         let mut json = try_s!(json::to_string(&request));
@@ -2605,16 +2734,20 @@ fn electrum_request(
         let resps = try_s!(resp_rx.await);
         Ok(resps)
     };
-    let send_fut = send_fut
-        .boxed()
-        .timeout(Duration::from_secs(timeout))
-        .compat()
-        .then(|res| match res {
+    // The timer is started right away, but the latency is measured from the first poll
+    // since the requests can be sent to the servers sequentially.
+    let send_fut = send_fut.boxed().timeout(Duration::from_secs(timeout));
+    let send_fut = async move {
+        let start = now_ms();
+        let result = match send_fut.await {
             Ok(response) => response,
             Err(timeout_error) => ERR!("{}", timeout_error),
-        })
-        .map_err(|e| ERRL!("{}", e));
-    Box::new(send_fut)
+        };
+        let latency = Duration::from_millis(now_ms().saturating_sub(start));
+        event_handlers.on_request_completed(&server_addr, latency, result.is_ok());
+        result.map_err(|e| ERRL!("{}", e))
+    };
+    Box::new(send_fut.boxed().compat())
 }
 
 fn address_balance_from_unspent_map(address: &Address, unspent_map: &UnspentMap, decimals: u8) -> BigDecimal {
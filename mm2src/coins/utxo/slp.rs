@@ -3,12 +3,13 @@
 //! Tracking issue: https://github.com/KomodoPlatform/atomicDEX-API/issues/701
 //! More info about the protocol and implementation guides can be found at https://slp.dev/
 
-use crate::my_tx_history_v2::CoinWithTxHistoryV2;
+use crate::my_tx_history_v2::{CoinWithTxHistoryV2, MyTxHistoryErrorV2};
 use crate::tx_history_storage::{GetTxHistoryFilters, WalletId};
 use crate::utxo::bch::BchCoin;
 use crate::utxo::bchd_grpc::{check_slp_transaction, validate_slp_utxos, ValidateSlpUtxosErr};
 use crate::utxo::rpc_clients::{UnspentInfo, UtxoRpcClientEnum, UtxoRpcError, UtxoRpcResult};
-use crate::utxo::utxo_common::{self, big_decimal_from_sat_unsigned, payment_script, UtxoTxBuilder};
+use crate::utxo::utxo_common::{self, big_decimal_from_sat_unsigned, payment_script, payment_script_for_secret,
+                               UtxoTxBuilder};
 use crate::utxo::{generate_and_send_tx, sat_from_big_decimal, ActualTxFee, AdditionalTxData, BroadcastTxErr,
                   FeePolicy, GenerateTxError, RecentlySpentOutPointsGuard, UtxoCoinConf, UtxoCoinFields,
                   UtxoCommonOps, UtxoTx, UtxoTxBroadcastOps, UtxoTxGenerationOps};
@@ -562,7 +563,13 @@ impl SlpToken {
         let slp_tx: SlpTxDetails = deserialize(tx.outputs[0].script_pubkey.as_slice())?;
 
         let other_pub = Public::from_slice(other_pub)?;
-        let redeem = payment_script(time_lock, &*dhash160(secret), &other_pub, keypair.public());
+        let redeem = payment_script_for_secret(
+            time_lock,
+            secret,
+            &other_pub,
+            keypair.public(),
+            &tx.outputs[SLP_SWAP_VOUT].script_pubkey,
+        );
 
         let slp_amount = match slp_tx.transaction {
             SlpTransaction::Send { token_id, amounts } => {
@@ -1587,6 +1594,7 @@ impl MmCoin for SlpToken {
                 fee_details: Some(fee_details.into()),
                 coin: coin.ticker().into(),
                 kmd_rewards: None,
+                spv_verified: None,
                 transaction_type: Default::default(),
             };
             Ok(details)
@@ -1743,11 +1751,13 @@ impl MmCoin for SlpToken {
     fn is_coin_protocol_supported(&self, _info: &Option<Vec<u8>>) -> bool { true }
 }
 
+#[async_trait]
 impl CoinWithTxHistoryV2 for SlpToken {
-    fn history_wallet_id(&self) -> WalletId { WalletId::new(self.platform_ticker().to_owned()) }
+    /// SLP token transfers are stored within the platform coin's history.
+    fn history_wallet_id(&self) -> WalletId { self.platform_coin.history_wallet_id() }
 
-    fn get_tx_history_filters(&self) -> GetTxHistoryFilters {
-        GetTxHistoryFilters::new().with_token_id(self.token_id().to_string())
+    async fn get_tx_history_filters(&self) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2> {
+        Ok(GetTxHistoryFilters::new().with_token_id(self.token_id().to_string()))
     }
 }
 
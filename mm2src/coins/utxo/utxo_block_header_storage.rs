@@ -9,6 +9,8 @@ use chain::BlockHeader;
 use derive_more::Display;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
+use parking_lot::Mutex as PaMutex;
+use rpc::v1::types::H256 as H256Json;
 use std::collections::HashMap;
 use std::fmt::{Debug, Formatter};
 
@@ -18,6 +20,8 @@ pub enum BlockHeaderStorageError {
     AddToStorageError { ticker: String, reason: String },
     #[display(fmt = "Can't get from the storage for {} - reason: {}", ticker, reason)]
     GetFromStorageError { ticker: String, reason: String },
+    #[display(fmt = "Can't remove from the storage for {} - reason: {}", ticker, reason)]
+    RemoveFromStorageError { ticker: String, reason: String },
     #[display(
         fmt = "Can't retrieve the table from the storage for {} - reason: {}",
         ticker,
@@ -35,6 +39,29 @@ pub enum BlockHeaderStorageError {
 pub struct BlockHeaderStorage {
     pub inner: Box<dyn BlockHeaderStorageOps>,
     pub params: UtxoBlockHeaderVerificationParams,
+    /// The transactions verified by the SPV mode along with the heights of the blocks they're included into.
+    verified_txs: PaMutex<HashMap<H256Json, u64>>,
+}
+
+impl BlockHeaderStorage {
+    pub fn new(inner: Box<dyn BlockHeaderStorageOps>, params: UtxoBlockHeaderVerificationParams) -> BlockHeaderStorage {
+        BlockHeaderStorage {
+            inner,
+            params,
+            verified_txs: PaMutex::new(HashMap::new()),
+        }
+    }
+
+    pub fn is_tx_verified(&self, tx_hash: &H256Json, height: u64) -> bool {
+        self.verified_txs.lock().get(tx_hash) == Some(&height)
+    }
+
+    pub fn mark_tx_verified(&self, tx_hash: H256Json, height: u64) { self.verified_txs.lock().insert(tx_hash, height); }
+
+    /// Forgets the verified transactions from the blocks that are removed by a chain reorganization.
+    pub fn forget_verified_txs_from_height(&self, from_height: u64) {
+        self.verified_txs.lock().retain(|_, height| *height < from_height);
+    }
 }
 
 impl Debug for BlockHeaderStorage {
@@ -85,23 +112,30 @@ pub trait BlockHeaderStorageOps: Send + Sync + 'static {
         for_coin: &str,
         height: u64,
     ) -> Result<Option<String>, MmError<BlockHeaderStorageError>>;
+
+    /// Gets the height of the highest block header stored for the selected coin
+    async fn get_last_block_height(&self, for_coin: &str) -> Result<Option<u64>, MmError<BlockHeaderStorageError>>;
+
+    /// Removes the block headers starting from the given height, e.g. on a chain reorganization
+    async fn remove_block_headers_from_height(
+        &self,
+        for_coin: &str,
+        from_height: u64,
+    ) -> Result<(), MmError<BlockHeaderStorageError>>;
 }
 
 impl InitBlockHeaderStorageOps for BlockHeaderStorage {
     #[cfg(not(target_arch = "wasm32"))]
     fn new_from_ctx(ctx: MmArc, params: UtxoBlockHeaderVerificationParams) -> Option<BlockHeaderStorage> {
-        ctx.sqlite_connection.as_option().map(|connection| BlockHeaderStorage {
-            inner: Box::new(SqliteBlockHeadersStorage(connection.clone())),
-            params,
-        })
+        ctx.sqlite_connection
+            .as_option()
+            .map(|connection| BlockHeaderStorage::new(Box::new(SqliteBlockHeadersStorage(connection.clone())), params))
     }
 
     #[cfg(target_arch = "wasm32")]
-    fn new_from_ctx(_ctx: MmArc, params: UtxoBlockHeaderVerificationParams) -> Option<BlockHeaderStorage> {
-        Some(BlockHeaderStorage {
-            inner: Box::new(IndexedDBBlockHeadersStorage {}),
-            params,
-        })
+    fn new_from_ctx(ctx: MmArc, params: UtxoBlockHeaderVerificationParams) -> Option<BlockHeaderStorage> {
+        let storage = IndexedDBBlockHeadersStorage::new(&ctx).ok()?;
+        Some(BlockHeaderStorage::new(Box::new(storage), params))
     }
 }
 
@@ -148,4 +182,16 @@ impl BlockHeaderStorageOps for BlockHeaderStorage {
     ) -> Result<Option<String>, MmError<BlockHeaderStorageError>> {
         self.inner.get_block_header_raw(for_coin, height).await
     }
+
+    async fn get_last_block_height(&self, for_coin: &str) -> Result<Option<u64>, MmError<BlockHeaderStorageError>> {
+        self.inner.get_last_block_height(for_coin).await
+    }
+
+    async fn remove_block_headers_from_height(
+        &self,
+        for_coin: &str,
+        from_height: u64,
+    ) -> Result<(), MmError<BlockHeaderStorageError>> {
+        self.inner.remove_block_headers_from_height(for_coin, from_height).await
+    }
 }
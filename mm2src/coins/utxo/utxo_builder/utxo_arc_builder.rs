@@ -120,13 +120,7 @@ pub trait MergeUtxoArcOps<T: UtxoCommonOps + GetUtxoListOps>: UtxoCoinBuilderCom
         F: Fn(UtxoArc) -> T + Send + Sync + 'static,
     {
         if let Some(ref merge_params) = self.activation_params().utxo_merge_params {
-            let fut = merge_utxo_loop(
-                weak,
-                merge_params.merge_at,
-                merge_params.check_every,
-                merge_params.max_merge_at_once,
-                constructor,
-            );
+            let fut = merge_utxo_loop(weak, self.ctx().weak(), merge_params.clone(), constructor);
             info!("Starting UTXO merge loop for coin {}", self.ticker());
             spawn(fut);
         }
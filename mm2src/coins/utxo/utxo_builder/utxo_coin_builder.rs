@@ -1,3 +1,4 @@
+#[cfg(not(target_arch = "wasm32"))] use crate::coin_proxy;
 use crate::hd_wallet::{HDAccountsMap, HDAccountsMutex};
 use crate::hd_wallet_storage::{HDWalletCoinStorage, HDWalletStorageError};
 use crate::utxo::rpc_clients::{ElectrumClient, ElectrumClientImpl, ElectrumRpcRequest, EstimateFeeMethod,
@@ -33,6 +34,8 @@ use std::sync::{Arc, Mutex, Weak};
 
 cfg_native! {
     use crate::utxo::coin_daemon_data_dir;
+    use crate::utxo::compact_filters::CompactFiltersClient;
+    use crate::utxo::{BlockHeaderCheckpoint, UtxoBlockHeaderVerificationParams};
     use crate::utxo::rpc_clients::{ConcurrentRequestMap, NativeClient, NativeClientImpl};
     use dirs::home_dir;
     use std::path::{Path, PathBuf};
@@ -45,6 +48,8 @@ pub enum UtxoCoinBuildError {
     ConfError(UtxoConfError),
     #[display(fmt = "Native RPC client is only supported in native mode")]
     NativeRpcNotSupportedInWasm,
+    #[display(fmt = "Compact filters client is only supported in native mode")]
+    CompactFiltersNotSupportedInWasm,
     ErrorReadingNativeModeConf(String),
     #[display(fmt = "Rpc port is not set neither in `coins` file nor in native daemon config")]
     RpcPortIsNotSet,
@@ -61,6 +66,8 @@ pub enum UtxoCoinBuildError {
         seconds: u64,
     },
     ElectrumProtocolVersionCheckError(String),
+    #[display(fmt = "Invalid 'proxy' config: {}", _0)]
+    InvalidProxyConfig(String),
     #[display(fmt = "Can not detect the user home directory")]
     CantDetectUserHome,
     #[display(fmt = "Unexpected derivation method: {}", _0)]
@@ -364,6 +371,8 @@ pub trait UtxoCoinBuilderCommonOps {
             Some(0) => {
                 let fee_method = match &rpc_client {
                     UtxoRpcClientEnum::Electrum(_) => EstimateFeeMethod::Standard,
+                    #[cfg(not(target_arch = "wasm32"))]
+                    UtxoRpcClientEnum::CompactFilters(_) => EstimateFeeMethod::Standard,
                     UtxoRpcClientEnum::Native(client) => client
                         .detect_fee_method()
                         .compat()
@@ -402,9 +411,51 @@ pub trait UtxoCoinBuilderCommonOps {
                 let electrum = self.electrum_client(ElectrumBuilderArgs::default(), servers).await?;
                 Ok(UtxoRpcClientEnum::Electrum(electrum))
             },
+            #[cfg(target_arch = "wasm32")]
+            UtxoRpcMode::CompactFilters { .. } => MmError::err(UtxoCoinBuildError::CompactFiltersNotSupportedInWasm),
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcMode::CompactFilters { peers, sync_from } => {
+                let client = self.compact_filters_client(peers, sync_from)?;
+                Ok(UtxoRpcClientEnum::CompactFilters(client))
+            },
         }
     }
 
+    #[cfg(not(target_arch = "wasm32"))]
+    fn compact_filters_client(
+        &self,
+        peers: Vec<String>,
+        sync_from: BlockHeaderCheckpoint,
+    ) -> UtxoCoinBuildResult<CompactFiltersClient> {
+        let conf = self.conf();
+        // The magic of the Bitcoin networks is used if the coin config doesn't override it.
+        let magic = match conf["p2p_magic"].as_u64() {
+            Some(magic) => magic as u32,
+            None => match self.network()? {
+                BlockchainNetwork::Mainnet => 0xD9B4_BEF9,
+                BlockchainNetwork::Testnet => 0x0709_110B,
+                BlockchainNetwork::Regtest => 0xDAB5_BFFA,
+            },
+        };
+        let decimals = conf["decimals"].as_u64().unwrap_or(8) as u8;
+        let params: Option<UtxoBlockHeaderVerificationParams> =
+            json::from_value(conf["block_header_params"].clone())
+                .map_to_mm(|e| UtxoConfError::InvalidBlockHeaderParams(e.to_string()))?;
+        let difficulty_check = params.map_or(true, |params| params.difficulty_check);
+        let proxy = coin_proxy(self.ctx(), conf).map_to_mm(UtxoCoinBuildError::InvalidProxyConfig)?;
+
+        Ok(CompactFiltersClient::new(
+            self.ticker().to_owned(),
+            peers,
+            sync_from,
+            magic,
+            decimals,
+            difficulty_check,
+            proxy,
+            Some(self.ctx().dbdir().join("COMPACT_FILTERS").join(self.ticker())),
+        ))
+    }
+
     async fn electrum_client(
         &self,
         args: ElectrumBuilderArgs,
@@ -426,7 +477,10 @@ pub trait UtxoCoinBuilderCommonOps {
 
         let mut rng = small_rng();
         servers.as_mut_slice().shuffle(&mut rng);
-        let client = ElectrumClientImpl::new(ticker, event_handlers);
+        #[allow(unused_mut)]
+        let mut client = ElectrumClientImpl::new(ticker, event_handlers);
+        #[cfg(not(target_arch = "wasm32"))]
+        client.set_proxy(coin_proxy(ctx, self.conf()).map_to_mm(UtxoCoinBuildError::InvalidProxyConfig)?);
         for server in servers.iter() {
             match client.add_server(server).await {
                 Ok(_) => (),
@@ -621,14 +675,17 @@ fn read_native_mode_conf(
 /// Ping the electrum servers every 30 seconds to prevent them from disconnecting us.
 /// According to docs server can do it if there are no messages in ~10 minutes.
 /// https://electrumx.readthedocs.io/en/latest/protocol-methods.html?highlight=keep#server-ping
+/// The tips of the servers are checked at the same time to update their health.
 /// Weak reference will allow to stop the thread if client is dropped.
 fn spawn_electrum_ping_loop(weak_client: Weak<ElectrumClientImpl>, servers: Vec<ElectrumRpcRequest>) {
     spawn(async move {
         loop {
             if let Some(client) = weak_client.upgrade() {
-                if let Err(e) = ElectrumClient(client).server_ping().compat().await {
+                let client = ElectrumClient(client);
+                if let Err(e) = client.server_ping().compat().await {
                     error!("Electrum servers {:?} ping error: {}", servers, e);
                 }
+                client.check_servers_tips().await;
             } else {
                 info!("Electrum servers {:?} ping loop stopped", servers);
                 break;
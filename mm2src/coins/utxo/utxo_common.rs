@@ -5,16 +5,20 @@ use crate::hd_pubkey::{ExtractExtendedPubkey, HDExtractPubkeyError, HDXPubExtrac
 use crate::hd_wallet::{AccountUpdatingError, AddressDerivingError, HDAccountMut, HDAccountsMap,
                        NewAccountCreatingError};
 use crate::hd_wallet_storage::{HDWalletCoinWithStorageOps, HDWalletStorageResult};
+use crate::my_tx_history_v2::{DisplayAddress, MyTxHistoryErrorV2, TxDetailsBuilder, TxHistoryStorage};
 use crate::rpc_command::init_withdraw::WithdrawTaskHandle;
+use crate::tx_history_storage::{GetTxHistoryFilters, WalletId};
+use crate::utxo::bch::GetTxDetailsError;
 use crate::utxo::rpc_clients::{electrum_script_hash, BlockHashOrHeight, UnspentInfo, UnspentMap, UtxoRpcClientEnum,
                                UtxoRpcClientOps, UtxoRpcResult};
 use crate::utxo::tx_cache::TxCacheResult;
+use crate::utxo::utxo_tx_history_v2::UtxoMyAddressesHistoryError;
 use crate::utxo::utxo_withdraw::{InitUtxoWithdraw, StandardUtxoWithdraw, UtxoWithdraw};
-use crate::{CanRefundHtlc, CoinBalance, CoinWithDerivationMethod, GetWithdrawSenderAddress, HDAddressId,
-            RawTransactionError, RawTransactionRequest, RawTransactionRes, SearchForSwapTxSpendInput, SignatureError,
-            SignatureResult, SwapOps, TradePreimageValue, TransactionFut, TxFeeDetails, ValidateAddressResult,
-            ValidatePaymentInput, VerificationError, VerificationResult, WithdrawFrom, WithdrawResult,
-            WithdrawSenderAddress};
+use crate::{reserved_amounts, BlockHeightAndTime, CanRefundHtlc, CoinBalance, CoinWithDerivationMethod,
+            GetWithdrawSenderAddress, HDAddressId, RawTransactionError, RawTransactionRequest, RawTransactionRes,
+            SearchForSwapTxSpendInput, SignatureError, SignatureResult, SwapOps, TradePreimageValue, TransactionFut,
+            TransactionType, TxFeeDetails, UnbroadcastSwapPaymentInput, ValidateAddressResult, ValidatePaymentInput,
+            VerificationError, VerificationResult, WithdrawFrom, WithdrawResult, WithdrawSenderAddress};
 use bitcrypto::dhash256;
 pub use bitcrypto::{dhash160, sha256, ChecksumType};
 use chain::constants::SEQUENCE_FINAL;
@@ -23,7 +27,7 @@ use common::executor::Timer;
 use common::jsonrpc_client::JsonRpcErrorType;
 use common::log::{debug, error, info, warn};
 use common::mm_metrics::MetricsArc;
-use common::{now_ms, one_hundred, ten_f64};
+use common::now_ms;
 use crypto::{Bip32DerPathOps, Bip44Chain, Bip44DerPathError, Bip44DerivationPath, RpcDerivationPath};
 use futures::compat::Future01CompatExt;
 use futures::future::{FutureExt, TryFutureExt};
@@ -32,7 +36,7 @@ use itertools::Itertools;
 use keys::bytes::Bytes;
 use keys::{Address, AddressFormat as UtxoAddressFormat, AddressHashEnum, CompactSignature, Public, SegwitAddress,
            Type as ScriptType};
-use mm2_core::mm_ctx::MmArc;
+use mm2_core::mm_ctx::{MmArc, MmWeak};
 use mm2_err_handle::prelude::*;
 use mm2_number::{BigDecimal, MmNumber};
 use primitives::hash::H512;
@@ -42,7 +46,6 @@ use secp256k1::{PublicKey, Signature};
 use serde_json::{self as json};
 use serialization::{deserialize, serialize, serialize_list, serialize_with_flags, CoinVariant, CompactInteger,
                     Serializable, Stream, SERIALIZE_TRANSACTION_WITNESS};
-use spv_validation::helpers_validation::validate_headers;
 use spv_validation::helpers_validation::SPVError;
 use spv_validation::spv_proof::{SPVProof, TRY_SPV_PROOF_INTERVAL};
 use std::cmp::Ordering;
@@ -57,6 +60,9 @@ pub use chain::Transaction as UtxoTx;
 
 pub const DEFAULT_FEE_VOUT: usize = 0;
 pub const DEFAULT_SWAP_TX_SPEND_SIZE: u64 = 305;
+/// The maker payment spending one P2PKH input to the P2SH payment output and the P2PKH change output
+/// is about 250 bytes as of now.
+const MAKER_PAYMENT_TX_SIZE: u64 = 250;
 pub const DEFAULT_SWAP_VOUT: usize = 0;
 const MIN_BTC_TRADING_VOL: &str = "0.00777";
 pub const NO_TX_ERROR_CODE: &str = "'code': -5";
@@ -78,15 +84,6 @@ lazy_static! {
 
 pub const HISTORY_TOO_LARGE_ERR_CODE: i64 = -1;
 
-#[derive(Clone, Debug, Deserialize, Serialize)]
-pub struct UtxoMergeParams {
-    merge_at: usize,
-    #[serde(default = "ten_f64")]
-    check_every: f64,
-    #[serde(default = "one_hundred")]
-    max_merge_at_once: usize,
-}
-
 pub async fn get_tx_fee(coin: &UtxoCoinFields) -> UtxoRpcResult<ActualTxFee> {
     let conf = &coin.conf;
     match &coin.tx_fee {
@@ -393,6 +390,16 @@ pub async fn address_balance<T>(coin: &T, address: &Address) -> BalanceResult<Co
 where
     T: UtxoCommonOps + GetUtxoListOps + MarketCoinOps,
 {
+    if utxo_spv::is_spv_mode_enabled(coin.as_ref()) {
+        let unspents = coin
+            .as_ref()
+            .rpc_client
+            .list_unspent(address, coin.as_ref().decimals)
+            .compat()
+            .await?;
+        return spv_unspents_balance(coin, unspents).await;
+    }
+
     if coin.as_ref().check_utxo_maturity {
         let (unspents, _) = coin.get_mature_unspent_ordered_list(address).await?;
         return Ok(unspents.to_coin_balance(coin.as_ref().decimals));
@@ -417,6 +424,22 @@ pub async fn addresses_balances<T>(coin: &T, addresses: Vec<Address>) -> Balance
 where
     T: UtxoCommonOps + GetUtxoMapOps + MarketCoinOps,
 {
+    if utxo_spv::is_spv_mode_enabled(coin.as_ref()) {
+        let mut unspents_map = coin
+            .as_ref()
+            .rpc_client
+            .list_unspent_group(addresses.clone(), coin.as_ref().decimals)
+            .compat()
+            .await?;
+        let mut result = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let unspents = unspents_map.remove(&address).unwrap_or_default();
+            let balance = spv_unspents_balance(coin, unspents).await?;
+            result.push((address, balance));
+        }
+        return Ok(result);
+    }
+
     if coin.as_ref().check_utxo_maturity {
         let (unspents_map, _) = coin.get_mature_unspent_ordered_map(addresses.clone()).await?;
         addresses
@@ -447,6 +470,32 @@ where
     }
 }
 
+/// Calculates the balance of the `unspents` in the SPV mode.
+/// The unverified unspents are considered unspendable along with the immature ones.
+async fn spv_unspents_balance<T>(coin: &T, unspents: Vec<UnspentInfo>) -> BalanceResult<CoinBalance>
+where
+    T: UtxoCommonOps,
+{
+    let decimals = coin.as_ref().decimals;
+    let (verified, unverified) = utxo_spv::partition_unspents_by_spv(coin.as_ref(), unspents).await;
+    let mut balance = if coin.as_ref().check_utxo_maturity {
+        identify_mature_unspents(coin, verified)
+            .await?
+            .to_coin_balance(decimals)
+    } else {
+        CoinBalance {
+            spendable: verified.iter().fold(BigDecimal::from(0), |acc, unspent| {
+                acc + big_decimal_from_sat_unsigned(unspent.value, decimals)
+            }),
+            unspendable: BigDecimal::from(0),
+        }
+    };
+    for unspent in unverified {
+        balance.unspendable += big_decimal_from_sat_unsigned(unspent.value, decimals);
+    }
+    Ok(balance)
+}
+
 pub fn derivation_method(coin: &UtxoCoinFields) -> &DerivationMethod<Address, UtxoHDWallet> { &coin.derivation_method }
 
 pub async fn extract_extended_pubkey<XPubExtractor>(
@@ -1079,6 +1128,14 @@ where
     ));
     let send_fut = match &coin.as_ref().rpc_client {
         UtxoRpcClientEnum::Electrum(_) => Either::A(send_outputs_from_my_address(coin, outputs)),
+        #[cfg(not(target_arch = "wasm32"))]
+        UtxoRpcClientEnum::CompactFilters(client) => {
+            // The blocks spending the payment have to be scanned for the secret or the refund.
+            client.watch_scripts(std::iter::once(
+                output_script(&payment_address, ScriptType::P2SH).to_vec(),
+            ));
+            Either::A(send_outputs_from_my_address(coin, outputs))
+        },
         UtxoRpcClientEnum::Native(client) => {
             let addr_string = try_tx_fus!(payment_address.display_address());
             Either::B(
@@ -1118,6 +1175,14 @@ where
 
     let send_fut = match &coin.as_ref().rpc_client {
         UtxoRpcClientEnum::Electrum(_) => Either::A(send_outputs_from_my_address(coin, outputs)),
+        #[cfg(not(target_arch = "wasm32"))]
+        UtxoRpcClientEnum::CompactFilters(client) => {
+            // The blocks spending the payment have to be scanned for the secret or the refund.
+            client.watch_scripts(std::iter::once(
+                output_script(&payment_address, ScriptType::P2SH).to_vec(),
+            ));
+            Either::A(send_outputs_from_my_address(coin, outputs))
+        },
         UtxoRpcClientEnum::Native(client) => {
             let addr_string = try_tx_fus!(payment_address.display_address());
             Either::B(
@@ -1131,6 +1196,25 @@ where
     Box::new(send_fut)
 }
 
+/// Generates and signs the swap payment without broadcasting it.
+/// The payment script is the same for the maker and the taker payments.
+pub fn gen_unbroadcast_swap_payment<T>(coin: T, input: UnbroadcastSwapPaymentInput) -> TransactionFut
+where
+    T: UtxoCommonOps + GetUtxoListOps + SwapOps,
+{
+    let my_htlc_key_pair = coin.derive_htlc_key_pair(&input.swap_unique_data);
+    let SwapPaymentOutputsResult { outputs, .. } = try_tx_fus!(generate_swap_payment_outputs(
+        &coin,
+        input.time_lock,
+        my_htlc_key_pair.public_slice(),
+        &input.other_pub,
+        &input.secret_hash,
+        input.amount
+    ));
+    let fut = sign_outputs_from_my_address_impl(coin, outputs);
+    Box::new(fut.boxed().compat().map(|tx| tx.into()))
+}
+
 pub fn send_maker_spends_taker_payment<T: UtxoCommonOps + SwapOps>(
     coin: T,
     taker_payment_tx: &[u8],
@@ -1148,11 +1232,12 @@ pub fn send_maker_spends_taker_payment<T: UtxoCommonOps + SwapOps>(
         .push_data(secret)
         .push_opcode(Opcode::OP_0)
         .into_script();
-    let redeem_script = payment_script(
+    let redeem_script = payment_script_for_secret(
         time_lock,
-        &*dhash160(secret),
+        secret,
         &try_tx_fus!(Public::from_slice(taker_pub)),
         key_pair.public(),
+        &prev_transaction.outputs[0].script_pubkey,
     )
     .into();
     let fut = async move {
@@ -1200,11 +1285,12 @@ pub fn send_taker_spends_maker_payment<T: UtxoCommonOps + SwapOps>(
         .push_data(secret)
         .push_opcode(Opcode::OP_0)
         .into_script();
-    let redeem_script = payment_script(
+    let redeem_script = payment_script_for_secret(
         time_lock,
-        &*dhash160(secret),
+        secret,
         &try_tx_fus!(Public::from_slice(maker_pub)),
         key_pair.public(),
+        &prev_transaction.outputs[0].script_pubkey,
     )
     .into();
     let fut = async move {
@@ -1535,6 +1621,27 @@ pub fn validate_taker_payment<T: UtxoCommonOps + SwapOps>(
     )
 }
 
+/// Validates the payment generated by [`gen_unbroadcast_swap_payment`].
+/// Unlike [`validate_payment`], the transaction is not requested from the RPC as it's not broadcasted.
+pub fn validate_unbroadcast_swap_payment<T: UtxoCommonOps + SwapOps>(
+    coin: &T,
+    input: ValidatePaymentInput,
+) -> Box<dyn Future<Item = (), Error = String> + Send> {
+    let mut tx: UtxoTx = try_fus!(deserialize(input.payment_tx.as_slice()).map_err(|e| ERRL!("{:?}", e)));
+    tx.tx_hash_algo = coin.as_ref().tx_hash_algo;
+
+    let htlc_keypair = coin.derive_htlc_key_pair(&input.unique_swap_data);
+    let amount = try_fus!(sat_from_big_decimal(&input.amount, coin.as_ref().decimals));
+    let expected_redeem = payment_script(
+        input.time_lock,
+        &input.secret_hash,
+        &try_fus!(Public::from_slice(&input.other_pub)),
+        htlc_keypair.public(),
+    );
+    let result = validate_payment_output(&tx, DEFAULT_SWAP_VOUT, &expected_redeem, amount);
+    Box::new(futures01::future::result(result))
+}
+
 pub fn check_if_my_payment_sent<T: UtxoCommonOps + SwapOps>(
     coin: T,
     time_lock: u32,
@@ -1566,6 +1673,19 @@ pub fn check_if_my_payment_sent<T: UtxoCommonOps + SwapOps>(
                     None => Ok(None),
                 }
             },
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(client) => {
+                let history = try_s!(client.script_history(vec![p2sh.to_vec()]).await);
+                match history.last() {
+                    Some((tx_hash, _)) => {
+                        let tx_bytes = try_s!(client.get_transaction_bytes(tx_hash).compat().await);
+                        let mut tx: UtxoTx = try_s!(deserialize(tx_bytes.0.as_slice()).map_err(|e| ERRL!("{:?}", e)));
+                        tx.tx_hash_algo = coin.as_ref().tx_hash_algo;
+                        Ok(Some(tx.into()))
+                    },
+                    None => Ok(None),
+                }
+            },
             UtxoRpcClientEnum::Native(client) => {
                 let target_addr = Address {
                     t_addr_prefix: coin.as_ref().conf.p2sh_t_addr_prefix,
@@ -1668,10 +1788,14 @@ pub fn extract_secret(secret_hash: &[u8], spend_tx: &[u8]) -> Result<Vec<u8>, St
             },
         };
 
-        let actual_secret_hash = &*dhash160(&secret);
+        let actual_secret_hash = if secret_hash.len() == 32 {
+            sha256(&secret).to_vec()
+        } else {
+            dhash160(&secret).to_vec()
+        };
         if actual_secret_hash != secret_hash {
             warn!(
-                "Invalid 'dhash160(secret)' {:?}, expected {:?}",
+                "Invalid secret hash {:?}, expected {:?}",
                 actual_secret_hash, secret_hash
             );
             continue;
@@ -1857,6 +1981,16 @@ pub fn min_trading_vol(coin: &UtxoCoinFields) -> MmNumber {
 
 pub fn is_asset_chain(coin: &UtxoCoinFields) -> bool { coin.conf.asset_chain }
 
+/// The compact filters client finds the transactions of the watched scripts only once they're mined,
+/// so it can't validate the taker fee and the counterparty payments in the swap time frame.
+pub fn wallet_only(coin: &UtxoCoinFields, ctx: &MmArc) -> bool {
+    match coin.rpc_client {
+        #[cfg(not(target_arch = "wasm32"))]
+        UtxoRpcClientEnum::CompactFilters(_) => true,
+        _ => crate::is_wallet_only_ticker(ctx, &coin.conf.ticker),
+    }
+}
+
 pub async fn get_raw_transaction(coin: &UtxoCoinFields, req: RawTransactionRequest) -> RawTransactionResult {
     let hash = H256Json::from_str(&req.tx_hash).map_to_mm(|e| RawTransactionError::InvalidHashError(e.to_string()))?;
     let hex = coin
@@ -2033,247 +2167,78 @@ pub fn validate_address<T: UtxoCommonOps>(coin: &T, address: &str) -> ValidateAd
     }
 }
 
-#[allow(clippy::cognitive_complexity)]
-pub async fn process_history_loop<T>(coin: T, ctx: MmArc)
-where
-    T: UtxoStandardOps + UtxoCommonOps + MmCoin + MarketCoinOps,
-{
-    let mut my_balance: Option<CoinBalance> = None;
-    let history = match coin.load_history_from_file(&ctx).compat().await {
-        Ok(history) => history,
-        Err(e) => {
-            log_tag!(
-                ctx,
-                "",
-                "tx_history",
-                "coin" => coin.as_ref().conf.ticker;
-                fmt = "Error {} on 'load_history_from_file', stop the history loop", e
-            );
-            return;
+pub fn history_wallet_id(coin: &UtxoCoinFields) -> WalletId {
+    let wallet_id = WalletId::new(coin.conf.ticker.clone());
+    match coin.derivation_method {
+        DerivationMethod::Iguana(_) => wallet_id,
+        DerivationMethod::HDWallet(ref hd_wallet) => {
+            wallet_id.with_hd_wallet_rmd160(hd_wallet.hd_wallet_storage.hd_wallet_rmd160())
         },
-    };
-
-    let mut history_map: HashMap<H256Json, TransactionDetails> = history
-        .into_iter()
-        .filter_map(|tx| {
-            let tx_hash = H256Json::from_str(&tx.tx_hash).ok()?;
-            Some((tx_hash, tx))
-        })
-        .collect();
-
-    let mut success_iteration = 0i32;
-    loop {
-        if ctx.is_stopping() {
-            break;
-        };
-        {
-            let coins_ctx = CoinsContext::from_ctx(&ctx).unwrap();
-            let coins = coins_ctx.coins.lock().await;
-            if !coins.contains_key(&coin.as_ref().conf.ticker) {
-                log_tag!(ctx, "", "tx_history", "coin" => coin.as_ref().conf.ticker; fmt = "Loop stopped");
-                break;
-            };
-        }
-
-        let actual_balance = match coin.my_balance().compat().await {
-            Ok(actual_balance) => Some(actual_balance),
-            Err(err) => {
-                log_tag!(
-                    ctx,
-                    "",
-                    "tx_history",
-                    "coin" => coin.as_ref().conf.ticker;
-                    fmt = "Error {:?} on getting balance", err
-                );
-                None
-            },
-        };
-
-        let need_update = history_map.iter().any(|(_, tx)| tx.should_update());
-        match (&my_balance, &actual_balance) {
-            (Some(prev_balance), Some(actual_balance)) if prev_balance == actual_balance && !need_update => {
-                // my balance hasn't been changed, there is no need to reload tx_history
-                Timer::sleep(30.).await;
-                continue;
-            },
-            _ => (),
-        }
-
-        let metrics = ctx.metrics.clone();
-        let tx_ids = match coin.request_tx_history(metrics).await {
-            RequestTxHistoryResult::Ok(tx_ids) => tx_ids,
-            RequestTxHistoryResult::Retry { error } => {
-                log_tag!(
-                    ctx,
-                    "",
-                    "tx_history",
-                    "coin" => coin.as_ref().conf.ticker;
-                    fmt = "{}, retrying", error
-                );
-                Timer::sleep(10.).await;
-                continue;
-            },
-            RequestTxHistoryResult::HistoryTooLarge => {
-                log_tag!(
-                    ctx,
-                    "",
-                    "tx_history",
-                    "coin" => coin.as_ref().conf.ticker;
-                    fmt = "Got `history too large`, stopping further attempts to retrieve it"
-                );
-                *coin.as_ref().history_sync_state.lock().unwrap() = HistorySyncState::Error(json!({
-                    "code": HISTORY_TOO_LARGE_ERR_CODE,
-                    "message": "Got `history too large` error from Electrum server. History is not available",
-                }));
-                break;
-            },
-            RequestTxHistoryResult::CriticalError(e) => {
-                log_tag!(
-                    ctx,
-                    "",
-                    "tx_history",
-                    "coin" => coin.as_ref().conf.ticker;
-                    fmt = "{}, stopping futher attempts to retreive it", e
-                );
-                break;
-            },
-        };
-
-        // Remove transactions in the history_map that are not in the requested transaction list anymore
-        let history_length = history_map.len();
-        let requested_ids: HashSet<H256Json> = tx_ids.iter().map(|x| x.0).collect();
-        history_map.retain(|hash, _| requested_ids.contains(hash));
-
-        if history_map.len() < history_length {
-            let to_write: Vec<TransactionDetails> = history_map.iter().map(|(_, value)| value.clone()).collect();
-            if let Err(e) = coin.save_history_to_file(&ctx, to_write).compat().await {
-                log_tag!(
-                    ctx,
-                    "",
-                    "tx_history",
-                    "coin" => coin.as_ref().conf.ticker;
-                    fmt = "Error {} on 'save_history_to_file', stop the history loop", e
-                );
-                return;
-            };
-        }
-
-        let mut transactions_left = if tx_ids.len() > history_map.len() {
-            *coin.as_ref().history_sync_state.lock().unwrap() = HistorySyncState::InProgress(json!({
-                "transactions_left": tx_ids.len() - history_map.len()
-            }));
-            tx_ids.len() - history_map.len()
-        } else {
-            *coin.as_ref().history_sync_state.lock().unwrap() = HistorySyncState::InProgress(json!({
-                "transactions_left": 0
-            }));
-            0
-        };
-
-        // This is the cache of the already requested transactions.
-        let mut input_transactions = HistoryUtxoTxMap::default();
-        for (txid, height) in tx_ids {
-            let mut updated = false;
-            match history_map.entry(txid) {
-                Entry::Vacant(e) => {
-                    mm_counter!(ctx.metrics, "tx.history.request.count", 1, "coin" => coin.as_ref().conf.ticker.clone(), "method" => "tx_detail_by_hash");
-
-                    match coin.tx_details_by_hash(&txid.0, &mut input_transactions).await {
-                        Ok(mut tx_details) => {
-                            mm_counter!(ctx.metrics, "tx.history.response.count", 1, "coin" => coin.as_ref().conf.ticker.clone(), "method" => "tx_detail_by_hash");
-
-                            if tx_details.block_height == 0 && height > 0 {
-                                tx_details.block_height = height;
-                            }
+    }
+}
 
-                            e.insert(tx_details);
-                            if transactions_left > 0 {
-                                transactions_left -= 1;
-                                *coin.as_ref().history_sync_state.lock().unwrap() =
-                                    HistorySyncState::InProgress(json!({ "transactions_left": transactions_left }));
-                            }
-                            updated = true;
-                        },
-                        Err(e) => log_tag!(
-                            ctx,
-                            "",
-                            "tx_history",
-                            "coin" => coin.as_ref().conf.ticker;
-                            fmt = "Error {:?} on getting the details of {:?}, skipping the tx", e, txid
-                        ),
-                    }
-                },
-                Entry::Occupied(mut e) => {
-                    // update block height for previously unconfirmed transaction
-                    if e.get().should_update_block_height() && height > 0 {
-                        e.get_mut().block_height = height;
-                        updated = true;
-                    }
-                    if e.get().should_update_timestamp() || e.get().firo_negative_fee() {
-                        mm_counter!(ctx.metrics, "tx.history.request.count", 1, "coin" => coin.as_ref().conf.ticker.clone(), "method" => "tx_detail_by_hash");
-
-                        if let Ok(tx_details) = coin.tx_details_by_hash(&txid.0, &mut input_transactions).await {
-                            mm_counter!(ctx.metrics, "tx.history.response.count", 1, "coin" => coin.as_ref().conf.ticker.clone(), "method" => "tx_detail_by_hash");
-                            // replace with new tx details in case we need to update any data
-                            e.insert(tx_details);
-                            updated = true;
-                        }
+/// Returns the addresses the transaction history is fetched for:
+/// the iguana address or the known addresses of every activated HD account.
+pub async fn my_addresses<T>(coin: &T) -> MmResult<HashSet<Address>, UtxoMyAddressesHistoryError>
+where
+    T: UtxoCommonOps,
+{
+    match coin.as_ref().derivation_method {
+        DerivationMethod::Iguana(ref my_address) => Ok(std::iter::once(my_address.clone()).collect()),
+        DerivationMethod::HDWallet(ref hd_wallet) => {
+            let hd_accounts = hd_wallet.get_accounts().await;
+
+            let mut all_addresses = HashSet::new();
+            for (_, hd_account) in hd_accounts {
+                for chain in [Bip44Chain::External, Bip44Chain::Internal].iter() {
+                    let known_addresses_number = hd_account.known_addresses_number(*chain)?;
+                    for address_id in 0..known_addresses_number {
+                        let hd_address = derive_address(coin, &hd_account, *chain, address_id)?;
+                        all_addresses.insert(hd_address.address);
                     }
-                },
-            }
-            if updated {
-                let to_write: Vec<TransactionDetails> = history_map.iter().map(|(_, value)| value.clone()).collect();
-                if let Err(e) = coin.save_history_to_file(&ctx, to_write).compat().await {
-                    log_tag!(
-                        ctx,
-                        "",
-                        "tx_history",
-                        "coin" => coin.as_ref().conf.ticker;
-                        fmt = "Error {} on 'save_history_to_file', stop the history loop", e
-                    );
-                    return;
-                };
+                }
             }
-        }
-        *coin.as_ref().history_sync_state.lock().unwrap() = HistorySyncState::Finished;
-
-        if success_iteration == 0 {
-            log_tag!(
-                ctx,
-                "😅",
-                "tx_history",
-                "coin" => coin.as_ref().conf.ticker;
-                fmt = "history has been loaded successfully"
-            );
-        }
-
-        my_balance = actual_balance;
-        success_iteration += 1;
-        Timer::sleep(30.).await;
+            Ok(all_addresses)
+        },
     }
 }
 
-pub async fn request_tx_history<T>(coin: &T, metrics: MetricsArc) -> RequestTxHistoryResult
+/// HD wallet transactions are stored within the same [`WalletId`] history,
+/// so they need to be filtered by the known addresses.
+pub async fn get_tx_history_filters<T>(coin: &T) -> MmResult<GetTxHistoryFilters, MyTxHistoryErrorV2>
 where
-    T: UtxoCommonOps + MmCoin + MarketCoinOps,
+    T: UtxoCommonOps,
 {
-    let my_address = match coin.my_address() {
-        Ok(addr) => addr,
-        Err(e) => {
-            return RequestTxHistoryResult::CriticalError(ERRL!(
-                "Error on getting self address: {}. Stop tx history",
-                e
-            ))
+    match coin.as_ref().derivation_method {
+        DerivationMethod::Iguana(_) => Ok(GetTxHistoryFilters::new()),
+        DerivationMethod::HDWallet(_) => {
+            let addresses = my_addresses(coin)
+                .await
+                .mm_err(|e| MyTxHistoryErrorV2::Internal(e.to_string()))?;
+            let for_addresses = addresses.iter().map(DisplayAddress::display_address);
+            Ok(GetTxHistoryFilters::new().with_for_addresses(for_addresses))
         },
-    };
+    }
+}
 
+pub async fn request_tx_history<T>(
+    coin: &T,
+    metrics: MetricsArc,
+    for_addresses: &HashSet<Address>,
+) -> RequestTxHistoryResult
+where
+    T: UtxoCommonOps + MmCoin + MarketCoinOps,
+{
+    let ticker = coin.ticker();
     let tx_ids = match &coin.as_ref().rpc_client {
         UtxoRpcClientEnum::Native(client) => {
+            let for_addresses: HashSet<String> = for_addresses.iter().map(DisplayAddress::display_address).collect();
+
             let mut from = 0;
             let mut all_transactions = vec![];
             loop {
                 mm_counter!(metrics, "tx.history.request.count", 1,
-                    "coin" => coin.as_ref().conf.ticker.clone(), "client" => "native", "method" => "listtransactions");
+                    "coin" => ticker.to_owned(), "client" => "native", "method" => "listtransactions");
 
                 let transactions = match client.list_transactions(100, from).compat().await {
                     Ok(value) => value,
@@ -2285,7 +2250,7 @@ where
                 };
 
                 mm_counter!(metrics, "tx.history.response.count", 1,
-                    "coin" => coin.as_ref().conf.ticker.clone(), "client" => "native", "method" => "listtransactions");
+                    "coin" => ticker.to_owned(), "client" => "native", "method" => "listtransactions");
 
                 if transactions.is_empty() {
                     break;
@@ -2295,12 +2260,14 @@ where
             }
 
             mm_counter!(metrics, "tx.history.response.total_length", all_transactions.len() as u64,
-                "coin" => coin.as_ref().conf.ticker.clone(), "client" => "native", "method" => "listtransactions");
+                "coin" => ticker.to_owned(), "client" => "native", "method" => "listtransactions");
 
+            // A transaction can be listed several times if it's related to several of our addresses.
+            let mut unique_tx_ids = HashSet::new();
             all_transactions
                 .into_iter()
                 .filter_map(|item| {
-                    if item.address == my_address {
+                    if for_addresses.contains(&item.address) && unique_tx_ids.insert(item.txid) {
                         Some((item.txid, item.blockindex))
                     } else {
                         None
@@ -2309,58 +2276,171 @@ where
                 .collect()
         },
         UtxoRpcClientEnum::Electrum(client) => {
-            let my_address = match coin.as_ref().derivation_method.iguana_or_err() {
-                Ok(my_address) => my_address,
-                Err(e) => return RequestTxHistoryResult::CriticalError(e.to_string()),
-            };
-            let script = output_script(my_address, ScriptType::P2PKH);
-            let script_hash = electrum_script_hash(&script);
-
-            mm_counter!(metrics, "tx.history.request.count", 1,
-                "coin" => coin.as_ref().conf.ticker.clone(), "client" => "electrum", "method" => "blockchain.scripthash.get_history");
-
-            let electrum_history = match client.scripthash_get_history(&hex::encode(script_hash)).compat().await {
-                Ok(value) => value,
-                Err(e) => match &e.error {
-                    JsonRpcErrorType::InvalidRequest(e)
-                    | JsonRpcErrorType::Transport(e)
-                    | JsonRpcErrorType::Parse(_, e) => {
-                        return RequestTxHistoryResult::Retry {
-                            error: ERRL!("Error {} on scripthash_get_history", e),
-                        };
-                    },
-                    JsonRpcErrorType::Response(_addr, err) => {
-                        if HISTORY_TOO_LARGE_ERROR.eq(err) {
-                            return RequestTxHistoryResult::HistoryTooLarge;
-                        } else {
+            let mut tx_ids_with_height = HashMap::new();
+            for address in for_addresses {
+                let script = output_script(address, ScriptType::P2PKH);
+                let script_hash = electrum_script_hash(&script);
+
+                mm_counter!(metrics, "tx.history.request.count", 1,
+                    "coin" => ticker.to_owned(), "client" => "electrum", "method" => "blockchain.scripthash.get_history");
+
+                let electrum_history = match client.scripthash_get_history(&hex::encode(script_hash)).compat().await {
+                    Ok(value) => value,
+                    Err(e) => match &e.error {
+                        JsonRpcErrorType::InvalidRequest(e)
+                        | JsonRpcErrorType::Transport(e)
+                        | JsonRpcErrorType::Parse(_, e) => {
                             return RequestTxHistoryResult::Retry {
-                                error: ERRL!("Error {:?} on scripthash_get_history", e),
+                                error: ERRL!("Error {} on scripthash_get_history", e),
                             };
-                        }
+                        },
+                        JsonRpcErrorType::Response(_addr, err) => {
+                            if HISTORY_TOO_LARGE_ERROR.eq(err) {
+                                return RequestTxHistoryResult::HistoryTooLarge;
+                            } else {
+                                return RequestTxHistoryResult::Retry {
+                                    error: ERRL!("Error {:?} on scripthash_get_history", e),
+                                };
+                            }
+                        },
                     },
-                },
-            };
-            mm_counter!(metrics, "tx.history.response.count", 1,
-                "coin" => coin.as_ref().conf.ticker.clone(), "client" => "electrum", "method" => "blockchain.scripthash.get_history");
+                };
+                mm_counter!(metrics, "tx.history.response.count", 1,
+                    "coin" => ticker.to_owned(), "client" => "electrum", "method" => "blockchain.scripthash.get_history");
 
-            mm_counter!(metrics, "tx.history.response.total_length", electrum_history.len() as u64,
-                "coin" => coin.as_ref().conf.ticker.clone(), "client" => "electrum", "method" => "blockchain.scripthash.get_history");
+                mm_counter!(metrics, "tx.history.response.total_length", electrum_history.len() as u64,
+                    "coin" => ticker.to_owned(), "client" => "electrum", "method" => "blockchain.scripthash.get_history");
 
-            // electrum returns the most recent transactions in the end but we need to
-            // process them first so rev is required
-            electrum_history
-                .into_iter()
-                .rev()
-                .map(|item| {
+                for item in electrum_history {
                     let height = if item.height < 0 { 0 } else { item.height as u64 };
-                    (item.tx_hash, height)
-                })
-                .collect()
+                    tx_ids_with_height.insert(item.tx_hash, height);
+                }
+            }
+
+            // The most recent transactions should be processed first,
+            // where unconfirmed transactions (with the zero height) are the most recent ones.
+            let mut tx_ids: Vec<(H256Json, u64)> = tx_ids_with_height.into_iter().collect();
+            tx_ids.sort_by_key(|(_, height)| match height {
+                0 => std::cmp::Reverse(u64::MAX),
+                height => std::cmp::Reverse(*height),
+            });
+            tx_ids
+        },
+        #[cfg(not(target_arch = "wasm32"))]
+        UtxoRpcClientEnum::CompactFilters(client) => {
+            let scripts = for_addresses
+                .iter()
+                .map(|address| output_script(address, ScriptType::P2PKH).to_vec())
+                .collect();
+            match client.script_history(scripts).await {
+                Ok(tx_ids) => tx_ids,
+                Err(e) => {
+                    return RequestTxHistoryResult::Retry {
+                        error: ERRL!("Error {} on compact filters history", e),
+                    }
+                },
+            }
         },
     };
     RequestTxHistoryResult::Ok(tx_ids)
 }
 
+/// Gets the transaction from the `storage` cache or requests it from the RPC and caches it.
+pub async fn tx_from_storage_or_rpc<T, Storage>(
+    coin: &T,
+    tx_hash: &H256Json,
+    storage: &Storage,
+) -> MmResult<UtxoTx, GetTxDetailsError<Storage::Error>>
+where
+    T: AsRef<UtxoCoinFields>,
+    Storage: TxHistoryStorage,
+{
+    let tx_hash_str = format!("{:02x}", tx_hash);
+    let wallet_id = history_wallet_id(coin.as_ref());
+    let tx_bytes = match storage.tx_bytes_from_cache(&wallet_id, &tx_hash_str).await? {
+        Some(tx_bytes) => tx_bytes,
+        None => {
+            let tx_bytes = coin.as_ref().rpc_client.get_transaction_bytes(tx_hash).compat().await?;
+            storage.add_tx_to_cache(&wallet_id, &tx_hash_str, &tx_bytes).await?;
+            tx_bytes
+        },
+    };
+    let mut tx: UtxoTx = deserialize(tx_bytes.0.as_slice())?;
+    tx.tx_hash_algo = coin.as_ref().tx_hash_algo;
+    Ok(tx)
+}
+
+/// Builds the transaction details considering every address of the `my_addresses` set as our own.
+pub async fn tx_details_with_storage<T, Storage>(
+    coin: &T,
+    tx_hash: &H256Json,
+    block_height_and_time: Option<BlockHeightAndTime>,
+    storage: &Storage,
+    my_addresses: &HashSet<Address>,
+) -> MmResult<TransactionDetails, GetTxDetailsError<Storage::Error>>
+where
+    T: UtxoCommonOps + MarketCoinOps,
+    Storage: TxHistoryStorage,
+{
+    let tx = tx_from_storage_or_rpc(coin, tx_hash, storage).await?;
+    let decimals = coin.as_ref().decimals;
+    let mut tx_builder = TxDetailsBuilder::new(
+        coin.ticker().to_owned(),
+        &tx,
+        block_height_and_time,
+        my_addresses.iter().cloned(),
+    );
+
+    let mut total_input = 0;
+    for input in tx.inputs.iter() {
+        // input transaction is zero if the tx is the coinbase transaction
+        if input.previous_output.hash.is_zero() {
+            continue;
+        }
+
+        let index = input.previous_output.index as usize;
+        let prev_tx = tx_from_storage_or_rpc(coin, &input.previous_output.hash.reversed().into(), storage).await?;
+        let prev_output = match prev_tx.outputs.get(index) {
+            Some(output) => output,
+            None => {
+                let msg = format!("{} tx {:02x} spends a non-existent output", coin.ticker(), tx_hash);
+                return MmError::err(GetTxDetailsError::AddressesFromScriptError(msg));
+            },
+        };
+        total_input += prev_output.value;
+
+        let addresses = coin
+            .addresses_from_script(&prev_output.script_pubkey.clone().into())
+            .map_to_mm(GetTxDetailsError::AddressesFromScriptError)?;
+        let amount = big_decimal_from_sat_unsigned(prev_output.value, decimals);
+        for address in addresses {
+            tx_builder.transferred_from(address, &amount);
+        }
+    }
+
+    let mut total_output = 0;
+    for output in tx.outputs.iter() {
+        total_output += output.value;
+        let addresses = match coin.addresses_from_script(&output.script_pubkey.clone().into()) {
+            Ok(addresses) => addresses,
+            Err(_) => continue,
+        };
+        let amount = big_decimal_from_sat_unsigned(output.value, decimals);
+        for address in addresses {
+            tx_builder.transferred_to(address, &amount);
+        }
+    }
+
+    // The fee can't be calculated for coinbase transactions.
+    if total_input >= total_output {
+        tx_builder.set_tx_fee(Some(TxFeeDetails::Utxo(UtxoFeeDetails {
+            coin: Some(coin.ticker().to_owned()),
+            amount: big_decimal_from_sat_unsigned(total_input - total_output, decimals),
+        })));
+    }
+    Ok(tx_builder.build())
+}
+
 pub async fn tx_details_by_hash<T: UtxoCommonOps>(
     coin: &T,
     hash: &[u8],
@@ -2498,6 +2578,7 @@ pub async fn tx_details_by_hash<T: UtxoCommonOps>(
         internal_id: tx.hash().reversed().to_vec().into(),
         timestamp: verbose_tx.time.into(),
         kmd_rewards,
+        spv_verified: None,
         transaction_type: Default::default(),
     })
 }
@@ -2568,6 +2649,14 @@ where
 
     let my_address = &coin.my_address().map_to_mm(UtxoRpcError::Internal)?;
     let claimed_by_me = tx_details.from.iter().all(|from| from == my_address) && tx_details.to.contains(my_address);
+    // The rewards claiming transaction sends all the funds back to "my" address.
+    let is_rewards_claim = claimed_by_me
+        && kmd_rewards > BigDecimal::from(0)
+        && tx_details.to.iter().all(|to| to == my_address)
+        && tx_details.transaction_type == TransactionType::StandardTransfer;
+    if is_rewards_claim {
+        tx_details.transaction_type = TransactionType::ClaimKmdRewards;
+    }
 
     tx_details.kmd_rewards = Some(KmdRewardsDetails {
         amount: kmd_rewards,
@@ -3085,6 +3174,9 @@ pub async fn validate_spv_proof<T: UtxoCommonOps>(
 ) -> Result<(), MmError<SPVError>> {
     let client = match &coin.as_ref().rpc_client {
         UtxoRpcClientEnum::Native(_) => return Ok(()),
+        // The wallet transactions are taken from the blocks with the checked merkle roots already.
+        #[cfg(not(target_arch = "wasm32"))]
+        UtxoRpcClientEnum::CompactFilters(_) => return Ok(()),
         UtxoRpcClientEnum::Electrum(electrum_client) => electrum_client,
     };
     if tx.outputs.is_empty() {
@@ -3254,19 +3346,7 @@ pub fn validate_payment<T: UtxoCommonOps>(
                 );
             }
 
-            let expected_output = TransactionOutput {
-                value: amount,
-                script_pubkey: Builder::build_p2sh(&dhash160(&expected_redeem).into()).into(),
-            };
-
-            let actual_output = tx.outputs.get(output_index);
-            if actual_output != Some(&expected_output) {
-                return ERR!(
-                    "Provided payment tx output doesn't match expected {:?} {:?}",
-                    actual_output,
-                    expected_output
-                );
-            }
+            try_s!(validate_payment_output(&tx, output_index, &expected_redeem, amount));
 
             if !coin.as_ref().conf.enable_spv_proof {
                 return Ok(());
@@ -3283,6 +3363,29 @@ pub fn validate_payment<T: UtxoCommonOps>(
     Box::new(fut.boxed().compat())
 }
 
+/// Checks if the `output_index` output of the payment `tx` pays `amount` to the P2SH of the `expected_redeem` script.
+fn validate_payment_output(
+    tx: &UtxoTx,
+    output_index: usize,
+    expected_redeem: &Script,
+    amount: u64,
+) -> Result<(), String> {
+    let expected_output = TransactionOutput {
+        value: amount,
+        script_pubkey: Builder::build_p2sh(&dhash160(expected_redeem).into()).into(),
+    };
+
+    let actual_output = tx.outputs.get(output_index);
+    if actual_output != Some(&expected_output) {
+        return ERR!(
+            "Provided payment tx output doesn't match expected {:?} {:?}",
+            actual_output,
+            expected_output
+        );
+    }
+    Ok(())
+}
+
 #[allow(clippy::too_many_arguments)]
 async fn search_for_swap_output_spend(
     coin: &UtxoCoinFields,
@@ -3405,7 +3508,14 @@ where
     Ok(result)
 }
 
+/// The HTLC is locked with `sha256(secret)` if the `secret_hash` is 32 bytes long, with `dhash160(secret)` otherwise.
+/// The SHA256 hash lock is used if the other coin of the swap supports only SHA256, e.g. Tendermint.
 pub fn payment_script(time_lock: u32, secret_hash: &[u8], pub_0: &Public, pub_1: &Public) -> Script {
+    let hash_opcode = if secret_hash.len() == 32 {
+        Opcode::OP_SHA256
+    } else {
+        Opcode::OP_HASH160
+    };
     let builder = Builder::default();
     builder
         .push_opcode(Opcode::OP_IF)
@@ -3418,7 +3528,7 @@ pub fn payment_script(time_lock: u32, secret_hash: &[u8], pub_0: &Public, pub_1:
         .push_opcode(Opcode::OP_SIZE)
         .push_bytes(&[32])
         .push_opcode(Opcode::OP_EQUALVERIFY)
-        .push_opcode(Opcode::OP_HASH160)
+        .push_opcode(hash_opcode)
         .push_bytes(secret_hash)
         .push_opcode(Opcode::OP_EQUALVERIFY)
         .push_bytes(pub_1)
@@ -3427,6 +3537,23 @@ pub fn payment_script(time_lock: u32, secret_hash: &[u8], pub_0: &Public, pub_1:
         .into_script()
 }
 
+/// Returns the redeem script of the HTLC spent by revealing the `secret`.
+/// The HTLC paid to the `htlc_script_pubkey` is locked either with `sha256(secret)` or with `dhash160(secret)`.
+pub fn payment_script_for_secret(
+    time_lock: u32,
+    secret: &[u8],
+    pub_0: &Public,
+    pub_1: &Public,
+    htlc_script_pubkey: &[u8],
+) -> Script {
+    let sha256_script = payment_script(time_lock, &*sha256(secret), pub_0, pub_1);
+    let sha256_script_pubkey = Builder::build_p2sh(&dhash160(&sha256_script).into()).to_bytes();
+    if sha256_script_pubkey.as_slice() == htlc_script_pubkey {
+        return sha256_script;
+    }
+    payment_script(time_lock, &*dhash160(secret), pub_0, pub_1)
+}
+
 pub fn dex_fee_script(uuid: [u8; 16], time_lock: u32, watcher_pub: &Public, sender_pub: &Public) -> Script {
     let builder = Builder::default();
     builder
@@ -3505,6 +3632,8 @@ pub async fn get_all_unspent_ordered_list<'a, T: UtxoCommonOps>(
         .list_unspent(address, decimals)
         .compat()
         .await?;
+    // The unverified unspents are left out in the SPV mode, so they can't be spent.
+    let (unspents, _unverified) = utxo_spv::partition_unspents_by_spv(coin.as_ref(), unspents).await;
     let recently_spent = coin.as_ref().recently_spent_outpoints.lock().await;
     let unordered_unspents = recently_spent.replace_spent_outputs_with_cache(unspents.into_iter().collect());
     let ordered_unspents = sort_dedup_unspents(unordered_unspents);
@@ -3525,6 +3654,11 @@ pub async fn get_all_unspent_ordered_map<T: UtxoCommonOps>(
         .list_unspent_group(addresses, decimals)
         .compat()
         .await?;
+    for (_address, unspents) in unspents_map.iter_mut() {
+        let (verified, _unverified) =
+            utxo_spv::partition_unspents_by_spv(coin.as_ref(), std::mem::take(unspents)).await;
+        *unspents = verified;
+    }
     let recently_spent = coin.as_ref().recently_spent_outpoints.lock().await;
     for (_address, unspents) in unspents_map.iter_mut() {
         let unordered_unspents = recently_spent.replace_spent_outputs_with_cache(unspents.iter().cloned().collect());
@@ -3641,6 +3775,15 @@ pub async fn block_header_utxo_loop<T: UtxoCommonOps>(weak: UtxoWeak, constructo
             },
             Err(_e) => return,
         };
+        if let UtxoRpcClientEnum::Electrum(ref client) = coin.as_ref().rpc_client {
+            if let Err(e) = utxo_spv::store_checkpoint_header(ticker, client, storage).await {
+                error!(
+                    "Couldn't store the checkpoint header - aborting the block_header_utxo_loop: {}",
+                    e
+                );
+                return;
+            }
+        }
     }
     while let Some(arc) = weak.upgrade() {
         let coin = constructor(arc);
@@ -3648,32 +3791,25 @@ pub async fn block_header_utxo_loop<T: UtxoCommonOps>(weak: UtxoWeak, constructo
             None => break,
             Some(storage) => storage,
         };
-        let params = storage.params.clone();
-        let (check_every, blocks_limit_to_check, difficulty_check, constant_difficulty) = (
-            params.check_every,
-            params.blocks_limit_to_check,
-            params.difficulty_check,
-            params.constant_difficulty,
-        );
+        let check_every = storage.params.check_every;
         let height =
             ok_or_continue_after_sleep!(coin.as_ref().rpc_client.get_block_count().compat().await, check_every);
         let client = match &coin.as_ref().rpc_client {
             UtxoRpcClientEnum::Native(_) => break,
+            // The compact filters client validates the headers chain itself.
+            #[cfg(not(target_arch = "wasm32"))]
+            UtxoRpcClientEnum::CompactFilters(_) => break,
             UtxoRpcClientEnum::Electrum(client) => client,
         };
-        let (block_registry, block_headers) = ok_or_continue_after_sleep!(
-            client
-                .retrieve_last_headers(blocks_limit_to_check, height)
-                .compat()
-                .await,
+        let ticker = coin.as_ref().conf.ticker.as_str();
+        let block_registry = ok_or_continue_after_sleep!(
+            utxo_spv::retrieve_linked_headers(ticker, client, storage, height).await,
             check_every
         );
         ok_or_continue_after_sleep!(
-            validate_headers(block_headers, difficulty_check, constant_difficulty),
+            utxo_spv::handle_chain_reorg(ticker, client, storage, &block_registry).await,
             check_every
         );
-
-        let ticker = coin.as_ref().conf.ticker.as_str();
         ok_or_continue_after_sleep!(
             storage.add_block_headers_to_storage(ticker, block_registry).await,
             check_every
@@ -3683,22 +3819,27 @@ pub async fn block_header_utxo_loop<T: UtxoCommonOps>(weak: UtxoWeak, constructo
     }
 }
 
+/// Consolidates the unspent outputs according to the [`UtxoMergeParams::mode`].
+/// The outputs covering the amounts reserved by the swaps and the orders are left untouched.
 pub async fn merge_utxo_loop<T>(
     weak: UtxoWeak,
-    merge_at: usize,
-    check_every: f64,
-    max_merge_at_once: usize,
+    ctx_weak: MmWeak,
+    merge_params: UtxoMergeParams,
     constructor: impl Fn(UtxoArc) -> T,
 ) where
     T: UtxoCommonOps + GetUtxoListOps,
 {
     loop {
-        Timer::sleep(check_every).await;
+        Timer::sleep(merge_params.check_every).await;
 
         let coin = match weak.upgrade() {
             Some(arc) => constructor(arc),
             None => break,
         };
+        let ctx = match MmArc::from_weak(&ctx_weak) {
+            Some(ctx) => ctx,
+            None => break,
+        };
 
         let my_address = match coin.as_ref().derivation_method {
             DerivationMethod::Iguana(ref my_address) => my_address,
@@ -3709,6 +3850,41 @@ pub async fn merge_utxo_loop<T>(
         };
 
         let ticker = &coin.as_ref().conf.ticker;
+        let fee_per_kb = match coin.get_tx_fee().await {
+            Ok(ActualTxFee::Dynamic(fee) | ActualTxFee::FixedPerKb(fee)) => fee,
+            Err(e) => {
+                error!("Error {} on get_tx_fee of coin {}", e, ticker);
+                continue;
+            },
+        };
+        if let Some(max_fee_per_kb) = merge_params.max_fee_per_kb {
+            if fee_per_kb > max_fee_per_kb {
+                debug!(
+                    "UTXO consolidation of coin {} is postponed: fee {} per kB exceeds {}",
+                    ticker, fee_per_kb, max_fee_per_kb
+                );
+                continue;
+            }
+        }
+
+        let decimals = coin.as_ref().decimals;
+        let reserved = reserved_amounts(&ctx, ticker).await;
+        let locked_by_swaps = sat_from_big_decimal(&reserved.locked_by_swaps, decimals);
+        let total_reserved = sat_from_big_decimal(&reserved.total(), decimals);
+        let maker_order_volumes: Result<Vec<_>, _> = reserved
+            .maker_order_volumes
+            .iter()
+            .map(|volume| sat_from_big_decimal(volume, decimals))
+            .collect();
+        let (locked_by_swaps, total_reserved, maker_order_volumes) =
+            match (locked_by_swaps, total_reserved, maker_order_volumes) {
+                (Ok(locked), Ok(total), Ok(volumes)) => (locked, total, volumes),
+                _ => {
+                    error!("Error converting the reserved amounts of coin {}", ticker);
+                    continue;
+                },
+            };
+
         let (unspents, recently_spent) = match coin.get_unspent_ordered_list(my_address).await {
             Ok((unspents, recently_spent)) => (unspents, recently_spent),
             Err(e) => {
@@ -3716,30 +3892,135 @@ pub async fn merge_utxo_loop<T>(
                 continue;
             },
         };
-        if unspents.len() >= merge_at {
-            let unspents: Vec<_> = unspents.into_iter().take(max_merge_at_once).collect();
-            info!("Trying to merge {} UTXOs of coin {}", unspents.len(), ticker);
-            let value = unspents.iter().fold(0, |sum, unspent| sum + unspent.value);
-            let script_pubkey = Builder::build_p2pkh(&my_address.hash).to_bytes();
-            let output = TransactionOutput { value, script_pubkey };
-            let merge_tx_fut = generate_and_send_tx(
-                &coin,
-                unspents,
-                None,
-                FeePolicy::DeductFromOutput(0),
-                recently_spent,
-                vec![output],
-            );
-            match merge_tx_fut.await {
-                Ok(tx) => info!(
-                    "UTXO merge successful for coin {}, tx_hash {:?}",
-                    ticker,
-                    tx.hash().reversed()
-                ),
-                Err(e) => error!("Error {:?} on UTXO merge attempt for coin {}", e, ticker),
-            }
+        let unspents_number = unspents.len();
+        let script_pubkey = Builder::build_p2pkh(&my_address.hash).to_bytes();
+
+        let (unspents, fee_policy, outputs) = match merge_params.mode {
+            UtxoMergeMode::Merge => {
+                if unspents_number < merge_params.merge_at {
+                    continue;
+                }
+                let (_reserved, free) = split_off_reserved_unspents(unspents, total_reserved);
+                let unspents: Vec<_> = free.into_iter().take(merge_params.max_merge_at_once).collect();
+                if unspents.len() < 2 {
+                    debug!(
+                        "There are no UTXOs of coin {} to merge besides the reserved ones",
+                        ticker
+                    );
+                    continue;
+                }
+                info!("Trying to merge {} UTXOs of coin {}", unspents.len(), ticker);
+                let value = unspents.iter().fold(0, |sum, unspent| sum + unspent.value);
+                let output = TransactionOutput { value, script_pubkey };
+                (unspents, FeePolicy::DeductFromOutput(0), vec![output])
+            },
+            UtxoMergeMode::Split => {
+                let (_reserved, mut free) = split_off_reserved_unspents(unspents, locked_by_swaps);
+                // Every output should also cover the fee of the maker payment.
+                let maker_payment_fee = match coin.get_htlc_spend_fee(MAKER_PAYMENT_TX_SIZE).await {
+                    Ok(fee) => fee,
+                    Err(e) => {
+                        error!("Error {} on estimating the maker payment fee of coin {}", e, ticker);
+                        continue;
+                    },
+                };
+                let required_values = maker_order_volumes
+                    .into_iter()
+                    .map(|volume| volume + maker_payment_fee)
+                    .collect();
+                let values = maker_order_outputs_to_create(&mut free, required_values);
+                if values.is_empty() {
+                    continue;
+                }
+                if unspents_number + values.len() > merge_params.merge_at {
+                    debug!(
+                        "Not splitting UTXOs of coin {}: the number of UTXOs would exceed {}",
+                        ticker, merge_params.merge_at
+                    );
+                    continue;
+                }
+                // Spend the largest unspents to create the outputs.
+                let unspents: Vec<_> = free.into_iter().rev().take(merge_params.max_merge_at_once).collect();
+                let available = unspents.iter().fold(0, |sum, unspent| sum + unspent.value);
+                let required = values.iter().sum::<u64>();
+                if available <= required {
+                    debug!(
+                        "Not enough free UTXOs of coin {} to split: available {}, required {}",
+                        ticker, available, required
+                    );
+                    continue;
+                }
+                info!("Trying to split UTXOs of coin {} into {} outputs", ticker, values.len());
+                let outputs = values
+                    .into_iter()
+                    .map(|value| TransactionOutput {
+                        value,
+                        script_pubkey: script_pubkey.clone(),
+                    })
+                    .collect();
+                (unspents, FeePolicy::SendExact, outputs)
+            },
+        };
+
+        let action = match merge_params.mode {
+            UtxoMergeMode::Merge => "merge",
+            UtxoMergeMode::Split => "split",
+        };
+        let merge_tx_fut = generate_and_send_tx(&coin, unspents, None, fee_policy, recently_spent, outputs);
+        match merge_tx_fut.await {
+            Ok(tx) => info!(
+                "UTXO {} successful for coin {}, tx_hash {:?}",
+                action,
+                ticker,
+                tx.hash().reversed()
+            ),
+            Err(e) => error!("Error {:?} on UTXO {} attempt for coin {}", e, action, ticker),
+        }
+    }
+}
+
+/// Splits the `unspents` into the ones left untouched to cover the `reserved` amount and the free ones.
+/// The largest unspents are reserved, so the smallest ones are consolidated.
+/// The free unspents are ordered by value in ascending order.
+pub(crate) fn split_off_reserved_unspents(
+    mut unspents: Vec<UnspentInfo>,
+    reserved: u64,
+) -> (Vec<UnspentInfo>, Vec<UnspentInfo>) {
+    unspents.sort_by(|a, b| b.value.cmp(&a.value));
+    let mut reserved_value = 0;
+    let reserved_number = unspents
+        .iter()
+        .take_while(|unspent| {
+            let is_required = reserved_value < reserved;
+            reserved_value += unspent.value;
+            is_required
+        })
+        .count();
+
+    let mut free = unspents.split_off(reserved_number);
+    free.reverse();
+    (unspents, free)
+}
+
+/// Returns the values of the outputs to create for the `required_values` not covered by the `unspents` yet.
+/// Every value is covered by the smallest unspent worth at least this value,
+/// such unspents are removed from the `unspents`.
+/// The `unspents` must be ordered by value in ascending order.
+pub(crate) fn maker_order_outputs_to_create(
+    unspents: &mut Vec<UnspentInfo>,
+    mut required_values: Vec<u64>,
+) -> Vec<u64> {
+    required_values.sort_unstable_by(|a, b| b.cmp(a));
+    let mut to_create = Vec::new();
+    for required in required_values {
+        match unspents.iter().position(|unspent| unspent.value >= required) {
+            Some(idx) => {
+                unspents.remove(idx);
+            },
+            None => to_create.push(required),
         }
     }
+    to_create
 }
 
 pub async fn can_refund_htlc<T>(coin: &T, locktime: u64) -> Result<CanRefundHtlc, MmError<UtxoRpcError>>
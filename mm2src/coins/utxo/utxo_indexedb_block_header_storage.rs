@@ -1,13 +1,123 @@
 use crate::utxo::rpc_clients::ElectrumBlockHeader;
 use crate::utxo::utxo_block_header_storage::BlockHeaderStorageError;
 use crate::utxo::utxo_block_header_storage::BlockHeaderStorageOps;
+use crate::CoinsContext;
 use async_trait::async_trait;
 use chain::BlockHeader;
+use mm2_core::mm_ctx::MmArc;
+use mm2_db::indexed_db::cursor_prelude::*;
+use mm2_db::indexed_db::{DbIdentifier, DbInstance, DbLocked, DbUpgrader, IndexedDb, IndexedDbBuilder, InitDbResult,
+                         MultiIndex, OnUpgradeResult, SharedDb, TableSignature};
 use mm2_err_handle::prelude::*;
+use serialization::deserialize;
 use std::collections::HashMap;
 
-#[derive(Debug)]
-pub struct IndexedDBBlockHeadersStorage {}
+const DB_NAME: &str = "block_headers_cache";
+const DB_VERSION: u32 = 1;
+/// A **unique** index of the `BlockHeaderStorageTable` table that consists of the `ticker` and the `height`.
+const TICKER_HEIGHT_INDEX: &str = "ticker_height";
+
+pub type BlockHeadersDbLocked<'a> = DbLocked<'a, BlockHeadersDb>;
+
+pub struct BlockHeadersDb {
+    inner: IndexedDb,
+}
+
+#[async_trait]
+impl DbInstance for BlockHeadersDb {
+    fn db_name() -> &'static str { DB_NAME }
+
+    async fn init(db_id: DbIdentifier) -> InitDbResult<Self> {
+        let inner = IndexedDbBuilder::new(db_id)
+            .with_version(DB_VERSION)
+            .with_table::<BlockHeaderStorageTable>()
+            .build()
+            .await?;
+        Ok(BlockHeadersDb { inner })
+    }
+}
+
+impl BlockHeadersDb {
+    pub fn get_inner(&self) -> &IndexedDb { &self.inner }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct BlockHeaderStorageTable {
+    ticker: String,
+    height: u32,
+    raw_header: String,
+}
+
+impl TableSignature for BlockHeaderStorageTable {
+    fn table_name() -> &'static str { "block_header_storage_cache_table" }
+
+    fn on_upgrade_needed(upgrader: &DbUpgrader, old_version: u32, new_version: u32) -> OnUpgradeResult<()> {
+        if let (0, 1) = (old_version, new_version) {
+            let table = upgrader.create_table(Self::table_name())?;
+            table.create_multi_index(TICKER_HEIGHT_INDEX, &["ticker", "height"], true)?;
+            table.create_index("ticker", false)?;
+        }
+        Ok(())
+    }
+}
+
+pub struct IndexedDBBlockHeadersStorage {
+    db: SharedDb<BlockHeadersDb>,
+}
+
+impl IndexedDBBlockHeadersStorage {
+    pub fn new(ctx: &MmArc) -> Result<Self, String> {
+        let coins_ctx = try_s!(CoinsContext::from_ctx(ctx));
+        Ok(IndexedDBBlockHeadersStorage {
+            db: coins_ctx.block_headers_db.clone(),
+        })
+    }
+
+    async fn lock_db(&self, for_coin: &str) -> Result<BlockHeadersDbLocked<'_>, MmError<BlockHeaderStorageError>> {
+        self.db
+            .get_or_initialize()
+            .await
+            .mm_err(|e| BlockHeaderStorageError::InitializationError {
+                ticker: for_coin.to_owned(),
+                reason: e.to_string(),
+            })
+    }
+
+    /// Adds the `(height, raw_header)` pairs replacing the headers stored at the same heights.
+    async fn add_raw_headers(
+        &self,
+        for_coin: &str,
+        headers: Vec<(u64, String)>,
+    ) -> Result<(), MmError<BlockHeaderStorageError>> {
+        let add_err = |e: &dyn std::fmt::Display| BlockHeaderStorageError::AddToStorageError {
+            ticker: for_coin.to_owned(),
+            reason: e.to_string(),
+        };
+        let locked_db = self.lock_db(for_coin).await?;
+        let db_transaction = locked_db.get_inner().transaction().await.mm_err(|e| add_err(&e))?;
+        let table = db_transaction
+            .table::<BlockHeaderStorageTable>()
+            .await
+            .mm_err(|e| add_err(&e))?;
+
+        for (height, raw_header) in headers {
+            let item = BlockHeaderStorageTable {
+                ticker: for_coin.to_owned(),
+                height: height as u32,
+                raw_header,
+            };
+            let index_keys = MultiIndex::new(TICKER_HEIGHT_INDEX)
+                .with_value(&item.ticker)
+                .and_then(|index| index.with_value(item.height))
+                .mm_err(|e| add_err(&e))?;
+            table
+                .replace_item_by_unique_multi_index(index_keys, &item)
+                .await
+                .mm_err(|e| add_err(&e))?;
+        }
+        Ok(())
+    }
+}
 
 #[async_trait]
 impl BlockHeaderStorageOps for IndexedDBBlockHeadersStorage {
@@ -17,33 +127,157 @@ impl BlockHeaderStorageOps for IndexedDBBlockHeadersStorage {
 
     async fn add_electrum_block_headers_to_storage(
         &self,
-        _for_coin: &str,
-        _headers: Vec<ElectrumBlockHeader>,
+        for_coin: &str,
+        headers: Vec<ElectrumBlockHeader>,
     ) -> Result<(), MmError<BlockHeaderStorageError>> {
-        Ok(())
+        let headers = headers
+            .into_iter()
+            .map(|header| match header {
+                ElectrumBlockHeader::V12(h) => (h.block_height, h.as_hex()),
+                ElectrumBlockHeader::V14(h) => (h.height, format!("{:02x}", h.hex)),
+            })
+            .collect();
+        self.add_raw_headers(for_coin, headers).await
     }
 
     async fn add_block_headers_to_storage(
         &self,
-        _for_coin: &str,
-        _headers: HashMap<u64, BlockHeader>,
+        for_coin: &str,
+        headers: HashMap<u64, BlockHeader>,
     ) -> Result<(), MmError<BlockHeaderStorageError>> {
-        Ok(())
+        let headers = headers
+            .into_iter()
+            .map(|(height, header)| (height, hex::encode(header.raw())))
+            .collect();
+        self.add_raw_headers(for_coin, headers).await
     }
 
     async fn get_block_header(
         &self,
-        _for_coin: &str,
-        _height: u64,
+        for_coin: &str,
+        height: u64,
     ) -> Result<Option<BlockHeader>, MmError<BlockHeaderStorageError>> {
-        Ok(None)
+        let header_raw = match self.get_block_header_raw(for_coin, height).await? {
+            Some(header_raw) => header_raw,
+            None => return Ok(None),
+        };
+        let decode_err = |reason: String| BlockHeaderStorageError::DecodeError {
+            ticker: for_coin.to_owned(),
+            reason,
+        };
+        let header_bytes = hex::decode(header_raw).map_to_mm(|e| decode_err(e.to_string()))?;
+        let header: BlockHeader = deserialize(header_bytes.as_slice()).map_to_mm(|e| decode_err(e.to_string()))?;
+        Ok(Some(header))
     }
 
     async fn get_block_header_raw(
         &self,
-        _for_coin: &str,
-        _height: u64,
+        for_coin: &str,
+        height: u64,
     ) -> Result<Option<String>, MmError<BlockHeaderStorageError>> {
-        Ok(None)
+        let get_err = |e: &dyn std::fmt::Display| BlockHeaderStorageError::GetFromStorageError {
+            ticker: for_coin.to_owned(),
+            reason: e.to_string(),
+        };
+        let locked_db = self.lock_db(for_coin).await?;
+        let db_transaction = locked_db.get_inner().transaction().await.mm_err(|e| get_err(&e))?;
+        let table = db_transaction
+            .table::<BlockHeaderStorageTable>()
+            .await
+            .mm_err(|e| get_err(&e))?;
+        let index_keys = MultiIndex::new(TICKER_HEIGHT_INDEX)
+            .with_value(for_coin)
+            .and_then(|index| index.with_value(height as u32))
+            .mm_err(|e| get_err(&e))?;
+        let item = table
+            .get_item_by_unique_multi_index(index_keys)
+            .await
+            .mm_err(|e| get_err(&e))?;
+        Ok(item.map(|(_item_id, item)| item.raw_header))
+    }
+
+    async fn get_last_block_height(&self, for_coin: &str) -> Result<Option<u64>, MmError<BlockHeaderStorageError>> {
+        let get_err = |e: &dyn std::fmt::Display| BlockHeaderStorageError::GetFromStorageError {
+            ticker: for_coin.to_owned(),
+            reason: e.to_string(),
+        };
+        let locked_db = self.lock_db(for_coin).await?;
+        let db_transaction = locked_db.get_inner().transaction().await.mm_err(|e| get_err(&e))?;
+        let table = db_transaction
+            .table::<BlockHeaderStorageTable>()
+            .await
+            .mm_err(|e| get_err(&e))?;
+        let items = table.get_items("ticker", for_coin).await.mm_err(|e| get_err(&e))?;
+        Ok(items.into_iter().map(|(_item_id, item)| item.height as u64).max())
+    }
+
+    async fn remove_block_headers_from_height(
+        &self,
+        for_coin: &str,
+        from_height: u64,
+    ) -> Result<(), MmError<BlockHeaderStorageError>> {
+        let remove_err = |e: &dyn std::fmt::Display| BlockHeaderStorageError::RemoveFromStorageError {
+            ticker: for_coin.to_owned(),
+            reason: e.to_string(),
+        };
+        let locked_db = self.lock_db(for_coin).await?;
+        let db_transaction = locked_db.get_inner().transaction().await.mm_err(|e| remove_err(&e))?;
+        let table = db_transaction
+            .table::<BlockHeaderStorageTable>()
+            .await
+            .mm_err(|e| remove_err(&e))?;
+
+        let items = table
+            .open_cursor(TICKER_HEIGHT_INDEX)
+            .await
+            .mm_err(|e| remove_err(&e))?
+            .only("ticker", for_coin)
+            .mm_err(|e| remove_err(&e))?
+            .bound("height", from_height as u32, u32::MAX)
+            .collect()
+            .await
+            .mm_err(|e| remove_err(&e))?;
+        for (item_id, _item) in items {
+            table.delete_item(item_id).await.mm_err(|e| remove_err(&e))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use mm2_test_helpers::for_tests::mm_ctx_with_custom_db;
+    use wasm_bindgen_test::*;
+
+    wasm_bindgen_test_configure!(run_in_browser);
+
+    const HEADER_HEX: &str = "0000002076d41d3e4b0bfd4c0d3b30aa69fdff3ed35d85829efd04000000000000000000b386498b583390959d9bac72346986e3015e83ac0b54bc7747a11a494ac35c94bb3ce65a53fb45177f7e311c";
+
+    #[wasm_bindgen_test]
+    async fn test_remove_block_headers_from_height() {
+        const FOR_COIN: &str = "RICK";
+        let ctx = mm_ctx_with_custom_db();
+        let storage = IndexedDBBlockHeadersStorage::new(&ctx).unwrap();
+        let header: BlockHeader = deserialize(hex::decode(HEADER_HEX).unwrap().as_slice()).unwrap();
+        let headers: HashMap<u64, BlockHeader> = (100..105).map(|height| (height, header.clone())).collect();
+        storage.add_block_headers_to_storage(FOR_COIN, headers).await.unwrap();
+        storage
+            .add_block_headers_to_storage("MORTY", std::iter::once((103, header.clone())).collect())
+            .await
+            .unwrap();
+
+        assert_eq!(storage.get_last_block_height(FOR_COIN).await.unwrap(), Some(104));
+
+        storage.remove_block_headers_from_height(FOR_COIN, 102).await.unwrap();
+        assert_eq!(storage.get_last_block_height(FOR_COIN).await.unwrap(), Some(101));
+        assert_eq!(
+            storage.get_block_header(FOR_COIN, 101).await.unwrap(),
+            Some(header.clone())
+        );
+        assert_eq!(storage.get_block_header(FOR_COIN, 102).await.unwrap(), None);
+        assert_eq!(storage.get_block_header(FOR_COIN, 104).await.unwrap(), None);
+        // The headers of the other coins are kept.
+        assert_eq!(storage.get_block_header("MORTY", 103).await.unwrap(), Some(header));
     }
 }
@@ -0,0 +1,798 @@
+//! The wallet-wide SPV mode of the electrum-backed UTXO coins.
+//!
+//! The confirmed unspent outputs and history transactions returned by the electrum server are checked
+//! by their merkle proofs against the block headers that are validated locally by [`validate_headers`]
+//! and kept in the [`BlockHeaderStorage`].
+//! The stored headers chain is pinned to the optional checkpoint and is rolled back on chain reorganizations.
+
+use crate::utxo::rpc_clients::{ElectrumClient, UnspentInfo, UtxoRpcClientEnum, UtxoRpcError};
+use crate::utxo::utxo_block_header_storage::{BlockHeaderStorage, BlockHeaderStorageError, BlockHeaderStorageOps};
+use crate::utxo::{BlockHeaderCheckpoint, UtxoCoinFields};
+use chain::BlockHeader;
+use common::jsonrpc_client::JsonRpcError;
+use common::log::{debug, info, warn};
+use derive_more::Display;
+use futures::compat::Future01CompatExt;
+use mm2_err_handle::prelude::*;
+use primitives::hash::H256;
+use rpc::v1::types::H256 as H256Json;
+use serialization::deserialize;
+use spv_validation::helpers_validation::{merkle_prove, validate_headers};
+use std::collections::HashMap;
+use std::num::NonZeroU64;
+
+/// The maximum number of blocks a chain reorganization can roll the stored headers back.
+const MAX_REORG_DEPTH: u64 = 100;
+/// The maximum number of headers requested by one `blockchain.block.headers` call.
+const MAX_HEADERS_PER_REQUEST: u64 = 2016;
+/// The maximum distance between the requested header and the stored chain if there is no checkpoint.
+const MAX_UNLINKED_HEADERS: u64 = 10 * MAX_HEADERS_PER_REQUEST;
+
+#[derive(Debug, Display)]
+pub enum SpvVerificationError {
+    /// The data returned by the electrum server doesn't match the validated block headers.
+    #[display(fmt = "Unverifiable: {}", _0)]
+    Unverifiable(String),
+    /// The verification has been interrupted and can be retried later.
+    #[display(fmt = "Verification interrupted: {}", _0)]
+    Interrupted(String),
+}
+
+impl From<JsonRpcError> for SpvVerificationError {
+    fn from(e: JsonRpcError) -> Self { SpvVerificationError::Interrupted(e.to_string()) }
+}
+
+impl From<UtxoRpcError> for SpvVerificationError {
+    fn from(e: UtxoRpcError) -> Self { SpvVerificationError::Interrupted(e.to_string()) }
+}
+
+impl From<BlockHeaderStorageError> for SpvVerificationError {
+    fn from(e: BlockHeaderStorageError) -> Self { SpvVerificationError::Interrupted(e.to_string()) }
+}
+
+impl From<serialization::Error> for SpvVerificationError {
+    fn from(e: serialization::Error) -> Self { SpvVerificationError::Unverifiable(format!("{:?}", e)) }
+}
+
+/// Returns the electrum client and the block headers storage if the SPV mode is enabled for the coin.
+pub fn spv_client_and_storage(coin: &UtxoCoinFields) -> Option<(&ElectrumClient, &BlockHeaderStorage)> {
+    let storage = coin
+        .block_headers_storage
+        .as_ref()
+        .filter(|storage| storage.params.spv_mode)?;
+    match coin.rpc_client {
+        UtxoRpcClientEnum::Electrum(ref client) => Some((client, storage)),
+        _ => None,
+    }
+}
+
+#[inline]
+pub fn is_spv_mode_enabled(coin: &UtxoCoinFields) -> bool { spv_client_and_storage(coin).is_some() }
+
+fn block_hash(header: &BlockHeader) -> H256Json { header.hash().reversed().into() }
+
+/// Checks that the transaction is included into the block at the given `height`
+/// by the merkle proof requested from the electrum server and the locally validated block header.
+pub async fn verify_tx_inclusion(
+    ticker: &str,
+    client: &ElectrumClient,
+    storage: &BlockHeaderStorage,
+    tx_hash: H256Json,
+    height: u64,
+) -> Result<(), MmError<SpvVerificationError>> {
+    if storage.is_tx_verified(&tx_hash, height) {
+        return Ok(());
+    }
+
+    let merkle_branch = client
+        .blockchain_transaction_get_merkle(tx_hash.clone(), height)
+        .compat()
+        .await?;
+    if merkle_branch.block_height != height {
+        let error = format!(
+            "merkle branch of {:?} is for the block {} instead of {}",
+            tx_hash, merkle_branch.block_height, height
+        );
+        return MmError::err(SpvVerificationError::Unverifiable(error));
+    }
+
+    let header = validated_block_header(ticker, client, storage, height).await?;
+    let intermediate_nodes: Vec<H256> = merkle_branch
+        .merkle
+        .into_iter()
+        .map(|hash| hash.reversed().into())
+        .collect();
+    merkle_prove(
+        tx_hash.reversed().into(),
+        header.merkle_root_hash,
+        intermediate_nodes,
+        merkle_branch.pos as u64,
+    )
+    .map_to_mm(|e| SpvVerificationError::Unverifiable(format!("{:?} of {:?}", e, tx_hash)))?;
+
+    storage.mark_tx_verified(tx_hash, height);
+    Ok(())
+}
+
+/// Verifies the transaction if the SPV mode is enabled for the coin.
+/// Returns `None` if the SPV mode is disabled or the transaction is not confirmed yet.
+pub async fn spv_verify_tx(
+    coin: &UtxoCoinFields,
+    tx_hash: &H256Json,
+    height: u64,
+) -> Result<Option<bool>, MmError<SpvVerificationError>> {
+    let (client, storage) = match spv_client_and_storage(coin) {
+        Some(client_and_storage) if height > 0 => client_and_storage,
+        _ => return Ok(None),
+    };
+    match verify_tx_inclusion(&coin.conf.ticker, client, storage, tx_hash.clone(), height).await {
+        Ok(()) => Ok(Some(true)),
+        Err(e) => match e.get_inner() {
+            SpvVerificationError::Unverifiable(reason) => {
+                warn!(
+                    "{} transaction {:?} is not verified: {}",
+                    coin.conf.ticker, tx_hash, reason
+                );
+                Ok(Some(false))
+            },
+            SpvVerificationError::Interrupted(_) => Err(e),
+        },
+    }
+}
+
+/// Splits the `unspents` into the verified and the unverified ones if the SPV mode is enabled for the coin.
+/// The unconfirmed outputs can't be verified, so they're considered verified as they are by the non-SPV wallets.
+pub async fn partition_unspents_by_spv(
+    coin: &UtxoCoinFields,
+    unspents: Vec<UnspentInfo>,
+) -> (Vec<UnspentInfo>, Vec<UnspentInfo>) {
+    let (client, storage) = match spv_client_and_storage(coin) {
+        Some(client_and_storage) => client_and_storage,
+        None => return (unspents, Vec::new()),
+    };
+
+    let mut verified = Vec::with_capacity(unspents.len());
+    let mut unverified = Vec::new();
+    for unspent in unspents {
+        let height = match unspent.height {
+            Some(height) if height > 0 => height,
+            _ => {
+                verified.push(unspent);
+                continue;
+            },
+        };
+        let tx_hash: H256Json = unspent.outpoint.hash.reversed().into();
+        match verify_tx_inclusion(&coin.conf.ticker, client, storage, tx_hash.clone(), height).await {
+            Ok(()) => verified.push(unspent),
+            Err(e) => {
+                warn!(
+                    "{} unspent output {:?}:{} is not verified: {}",
+                    coin.conf.ticker, tx_hash, unspent.outpoint.index, e
+                );
+                unverified.push(unspent);
+            },
+        }
+    }
+    (verified, unverified)
+}
+
+/// Returns the header at the given `height` from the storage,
+/// or requests the headers up to the `height` linked to the stored chain and validates them before storing.
+async fn validated_block_header(
+    ticker: &str,
+    client: &ElectrumClient,
+    storage: &BlockHeaderStorage,
+    height: u64,
+) -> Result<BlockHeader, MmError<SpvVerificationError>> {
+    if let Some(header) = storage.get_block_header(ticker, height).await? {
+        return Ok(header);
+    }
+
+    let headers_registry = retrieve_linked_headers(ticker, client, storage, height).await?;
+    check_links_with_stored_headers(ticker, storage, &headers_registry).await?;
+
+    let header = headers_registry.get(&height).cloned().or_mm_err(|| {
+        SpvVerificationError::Unverifiable(format!("electrum server didn't return the header at {}", height))
+    })?;
+    storage.add_block_headers_to_storage(ticker, headers_registry).await?;
+    Ok(header)
+}
+
+/// Requests the headers between the `height` and the last stored header (or the checkpoint if it's higher than
+/// the `height` or nothing is stored yet),
+/// so the returned chain links to the stored one. If the stored header doesn't match the electrum server one,
+/// the chain is linked to the highest matching stored header within [`MAX_REORG_DEPTH`] blocks.
+/// The first headers stored for a coin without a checkpoint are trusted as they are.
+/// Returns the validated headers registry that includes the `height`.
+pub async fn retrieve_linked_headers(
+    ticker: &str,
+    client: &ElectrumClient,
+    storage: &BlockHeaderStorage,
+    height: u64,
+) -> Result<HashMap<u64, BlockHeader>, MmError<SpvVerificationError>> {
+    let checkpoint_height = storage.params.checkpoint.as_ref().map(|checkpoint| checkpoint.height);
+    let anchor_height = match checkpoint_height {
+        Some(checkpoint_height) if height < checkpoint_height => Some(checkpoint_height),
+        _ => storage.get_last_block_height(ticker).await?.or(checkpoint_height),
+    };
+    let (from, to) = match anchor_height {
+        Some(anchor_height) => (height.min(anchor_height), height.max(anchor_height)),
+        None => (
+            (height + 1).saturating_sub(storage.params.blocks_limit_to_check.get()),
+            height,
+        ),
+    };
+    if checkpoint_height.is_none() && to - from >= MAX_UNLINKED_HEADERS {
+        let error = format!(
+            "header at {} is more than {} blocks away from the stored chain",
+            height, MAX_UNLINKED_HEADERS
+        );
+        return MmError::err(SpvVerificationError::Unverifiable(error));
+    }
+    let mut headers_registry = retrieve_headers_range(client, from, to).await?;
+
+    if let Some(anchor_height) = anchor_height {
+        if !is_linked_at(ticker, storage, &headers_registry, anchor_height).await? {
+            let reorg_from = anchor_height.saturating_sub(MAX_REORG_DEPTH);
+            if reorg_from < from {
+                let lower_headers = retrieve_headers_range(client, reorg_from, from - 1).await?;
+                headers_registry.extend(lower_headers);
+            }
+            let mut linked = false;
+            for link_height in (reorg_from..anchor_height).rev() {
+                if is_linked_at(ticker, storage, &headers_registry, link_height).await? {
+                    linked = true;
+                    break;
+                }
+            }
+            if !linked {
+                let error = format!(
+                    "headers up to {} don't link to the stored chain within {} blocks",
+                    to, MAX_REORG_DEPTH
+                );
+                return MmError::err(SpvVerificationError::Unverifiable(error));
+            }
+        }
+    }
+
+    let mut heights: Vec<u64> = headers_registry.keys().copied().collect();
+    heights.sort_unstable();
+    let headers = heights.iter().map(|height| headers_registry[height].clone()).collect();
+    validate_headers_chain(storage, &headers_registry, headers)?;
+    Ok(headers_registry)
+}
+
+/// Checks whether the `headers_registry` links to the stored chain at the `height`:
+/// the header at the `height` is either the checkpoint one (that is checked by [`validate_headers_chain`])
+/// or the stored one.
+async fn is_linked_at(
+    ticker: &str,
+    storage: &BlockHeaderStorage,
+    headers_registry: &HashMap<u64, BlockHeader>,
+    height: u64,
+) -> Result<bool, MmError<SpvVerificationError>> {
+    let header = match headers_registry.get(&height) {
+        Some(header) => header,
+        None => return Ok(false),
+    };
+    if storage.params.checkpoint.as_ref().map(|checkpoint| checkpoint.height) == Some(height) {
+        return Ok(true);
+    }
+    Ok(storage
+        .get_block_header(ticker, height)
+        .await?
+        .map_or(false, |stored| stored.hash() == header.hash()))
+}
+
+/// Requests the headers from `from` to `to` inclusively by [`MAX_HEADERS_PER_REQUEST`] at once.
+async fn retrieve_headers_range(
+    client: &ElectrumClient,
+    from: u64,
+    to: u64,
+) -> Result<HashMap<u64, BlockHeader>, MmError<SpvVerificationError>> {
+    let mut headers_registry = HashMap::new();
+    let mut chunk_from = from;
+    while chunk_from <= to {
+        let chunk_to = to.min(chunk_from + MAX_HEADERS_PER_REQUEST - 1);
+        let count = NonZeroU64::new(chunk_to - chunk_from + 1).expect("chunk_to >= chunk_from");
+        let (chunk, _) = client.retrieve_last_headers(count, chunk_to + 1).compat().await?;
+        headers_registry.extend(chunk);
+        chunk_from = chunk_to + 1;
+    }
+    if let Some(missing) = (from..=to).find(|height| !headers_registry.contains_key(height)) {
+        let error = format!("electrum server didn't return the header at {}", missing);
+        return MmError::err(SpvVerificationError::Interrupted(error));
+    }
+    Ok(headers_registry)
+}
+
+/// Validates the `headers` chain by [`validate_headers`] and checks it against the configured checkpoint.
+pub fn validate_headers_chain(
+    storage: &BlockHeaderStorage,
+    headers_registry: &HashMap<u64, BlockHeader>,
+    headers: Vec<BlockHeader>,
+) -> Result<(), MmError<SpvVerificationError>> {
+    let params = &storage.params;
+    validate_headers(headers, params.difficulty_check, params.constant_difficulty)
+        .map_to_mm(|e| SpvVerificationError::Unverifiable(format!("invalid headers chain: {:?}", e)))?;
+
+    if let Some(BlockHeaderCheckpoint { height, hash }) = params.checkpoint.as_ref() {
+        if let Some(header) = headers_registry.get(height) {
+            if block_hash(header) != *hash {
+                let error = format!("header at {} doesn't match the checkpoint {:?}", height, hash);
+                return MmError::err(SpvVerificationError::Unverifiable(error));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Checks that the `headers_registry` is a part of the stored headers chain.
+async fn check_links_with_stored_headers(
+    ticker: &str,
+    storage: &BlockHeaderStorage,
+    headers_registry: &HashMap<u64, BlockHeader>,
+) -> Result<(), MmError<SpvVerificationError>> {
+    let (lowest, highest) = match (headers_registry.keys().min(), headers_registry.keys().max()) {
+        (Some(lowest), Some(highest)) => (*lowest, *highest),
+        _ => return Ok(()),
+    };
+
+    if lowest > 0 {
+        if let Some(prev) = storage.get_block_header(ticker, lowest - 1).await? {
+            if headers_registry[&lowest].previous_header_hash != prev.hash() {
+                let error = format!("header at {} doesn't follow the stored chain", lowest);
+                return MmError::err(SpvVerificationError::Unverifiable(error));
+            }
+        }
+    }
+    if let Some(next) = storage.get_block_header(ticker, highest + 1).await? {
+        if next.previous_header_hash != headers_registry[&highest].hash() {
+            let error = format!(
+                "stored header at {} doesn't follow the header at {}",
+                highest + 1,
+                highest
+            );
+            return MmError::err(SpvVerificationError::Unverifiable(error));
+        }
+    }
+    Ok(())
+}
+
+/// Stores the configured checkpoint header once it's confirmed by the electrum server.
+/// The headers chains that don't match the checkpoint are rejected afterwards.
+pub async fn store_checkpoint_header(
+    ticker: &str,
+    client: &ElectrumClient,
+    storage: &BlockHeaderStorage,
+) -> Result<(), MmError<SpvVerificationError>> {
+    let BlockHeaderCheckpoint { height, hash } = match storage.params.checkpoint {
+        Some(ref checkpoint) => checkpoint,
+        None => return Ok(()),
+    };
+    if let Some(stored) = storage.get_block_header(ticker, *height).await? {
+        if block_hash(&stored) == *hash {
+            return Ok(());
+        }
+    }
+
+    let bytes = client.blockchain_block_header(*height).compat().await?;
+    let header: BlockHeader = deserialize(bytes.0.as_slice())?;
+    if block_hash(&header) != *hash {
+        let error = format!(
+            "electrum server header at {} doesn't match the checkpoint {:?}",
+            height, hash
+        );
+        return MmError::err(SpvVerificationError::Unverifiable(error));
+    }
+    let mut headers = HashMap::new();
+    headers.insert(*height, header);
+    storage.add_block_headers_to_storage(ticker, headers).await?;
+    info!(
+        "{} block headers are pinned to the checkpoint {:?} at {}",
+        ticker, hash, height
+    );
+    Ok(())
+}
+
+/// Detects a chain reorganization by comparing the validated `headers_registry` with the stored headers.
+/// The stored headers above the fork point are removed along with the transactions verified within them.
+pub async fn handle_chain_reorg(
+    ticker: &str,
+    client: &ElectrumClient,
+    storage: &BlockHeaderStorage,
+    headers_registry: &HashMap<u64, BlockHeader>,
+) -> Result<(), MmError<SpvVerificationError>> {
+    let mut heights: Vec<u64> = headers_registry.keys().copied().collect();
+    heights.sort_unstable();
+    let lowest = match heights.first() {
+        Some(lowest) => *lowest,
+        None => return Ok(()),
+    };
+
+    let mut diverged_at = None;
+    for height in heights {
+        if let Some(stored) = storage.get_block_header(ticker, height).await? {
+            if stored.hash() != headers_registry[&height].hash() {
+                diverged_at = Some(height);
+                break;
+            }
+        }
+    }
+
+    let fork_height = match diverged_at {
+        Some(height) if height > lowest => height,
+        Some(_) => find_fork_height(ticker, client, storage, lowest).await?,
+        None => {
+            let prev = match lowest.checked_sub(1) {
+                Some(prev_height) => storage.get_block_header(ticker, prev_height).await?,
+                None => None,
+            };
+            match prev {
+                Some(prev) if headers_registry[&lowest].previous_header_hash != prev.hash() => {
+                    find_fork_height(ticker, client, storage, lowest).await?
+                },
+                _ => return Ok(()),
+            }
+        },
+    };
+
+    warn!(
+        "{} chain reorganization detected, removing the stored headers from {}",
+        ticker, fork_height
+    );
+    storage.remove_block_headers_from_height(ticker, fork_height).await?;
+    storage.forget_verified_txs_from_height(fork_height);
+    Ok(())
+}
+
+/// Walks the stored headers back from the `height` the chain is known to differ at
+/// and returns the lowest height the stored header differs from the electrum server one at.
+async fn find_fork_height(
+    ticker: &str,
+    client: &ElectrumClient,
+    storage: &BlockHeaderStorage,
+    mut height: u64,
+) -> Result<u64, MmError<SpvVerificationError>> {
+    let checkpoint_height = storage.params.checkpoint.as_ref().map(|checkpoint| checkpoint.height);
+    let min_height = height.saturating_sub(MAX_REORG_DEPTH);
+    while height > min_height {
+        if checkpoint_height == Some(height) {
+            let error = format!("chain reorganization below the checkpoint at {}", height);
+            return MmError::err(SpvVerificationError::Unverifiable(error));
+        }
+        let prev_height = height - 1;
+        let stored = match storage.get_block_header(ticker, prev_height).await? {
+            Some(stored) => stored,
+            None => return Ok(height),
+        };
+        let bytes = client.blockchain_block_header(prev_height).compat().await?;
+        let actual: BlockHeader = deserialize(bytes.0.as_slice())?;
+        if actual.hash() == stored.hash() {
+            return Ok(height);
+        }
+        debug!("{} stored header at {} is reorganized", ticker, prev_height);
+        height = prev_height;
+    }
+    let error = format!("chain reorganization is deeper than {} blocks", MAX_REORG_DEPTH);
+    MmError::err(SpvVerificationError::Unverifiable(error))
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::utxo::rpc_clients::{ElectrumClientImpl, TxMerkleBranch};
+    use crate::utxo::utxo_sql_block_header_storage::SqliteBlockHeadersStorage;
+    use crate::utxo::utxo_tests::utxo_coin_fields_for_test;
+    use crate::utxo::UtxoBlockHeaderVerificationParams;
+    use bitcrypto::dhash256;
+    use chain::OutPoint;
+    use common::block_on;
+    use mocktopus::mocking::*;
+    use rpc::v1::types::Bytes as BytesJson;
+    use serialization::serialize;
+    use std::num::NonZeroU64;
+    use std::str::FromStr;
+    use std::sync::Arc;
+
+    const HEADER_HEIGHT: u64 = 520481;
+    const HEADER_HEX: &str = "0000002076d41d3e4b0bfd4c0d3b30aa69fdff3ed35d85829efd04000000000000000000b386498b583390959d9bac72346986e3015e83ac0b54bc7747a11a494ac35c94bb3ce65a53fb45177f7e311c";
+    const HEADER_HASH: &str = "0000000000000000002e31d0714a5ab23100945ff87ba2d856cd566a3c9344ec";
+    const TICKER: &str = "RICK";
+
+    fn storage_with_checkpoint(checkpoint_hash: &str) -> BlockHeaderStorage {
+        let params = UtxoBlockHeaderVerificationParams {
+            difficulty_check: true,
+            constant_difficulty: false,
+            blocks_limit_to_check: NonZeroU64::new(1).unwrap(),
+            check_every: 10.,
+            spv_mode: true,
+            checkpoint: Some(BlockHeaderCheckpoint {
+                height: HEADER_HEIGHT,
+                hash: H256Json::from_str(checkpoint_hash).unwrap(),
+            }),
+        };
+        BlockHeaderStorage::new(Box::new(SqliteBlockHeadersStorage::in_memory()), params)
+    }
+
+    /// Returns the storage of the test chains built by [`build_chain`] that don't meet the difficulty.
+    fn storage_without_checkpoint() -> BlockHeaderStorage {
+        let params = UtxoBlockHeaderVerificationParams {
+            difficulty_check: false,
+            constant_difficulty: false,
+            blocks_limit_to_check: NonZeroU64::new(2).unwrap(),
+            check_every: 10.,
+            spv_mode: true,
+            checkpoint: None,
+        };
+        let storage = BlockHeaderStorage::new(Box::new(SqliteBlockHeadersStorage::in_memory()), params);
+        block_on(storage.init(TICKER)).unwrap();
+        storage
+    }
+
+    fn header_registry() -> (HashMap<u64, BlockHeader>, Vec<BlockHeader>) {
+        let header: BlockHeader = deserialize(hex::decode(HEADER_HEX).unwrap().as_slice()).unwrap();
+        let mut registry = HashMap::new();
+        registry.insert(HEADER_HEIGHT, header.clone());
+        (registry, vec![header])
+    }
+
+    /// Builds the chain of the headers following the `base` header at the `base_height`
+    /// with the given merkle roots. The result includes the `base` header.
+    fn build_chain(base: BlockHeader, base_height: u64, merkle_roots: Vec<H256>) -> HashMap<u64, BlockHeader> {
+        let mut chain = HashMap::new();
+        let mut prev = base;
+        chain.insert(base_height, prev.clone());
+        for (height, merkle_root) in (base_height + 1..).zip(merkle_roots) {
+            let mut header = prev.clone();
+            header.previous_header_hash = prev.hash();
+            header.merkle_root_hash = merkle_root;
+            chain.insert(height, header.clone());
+            prev = header;
+        }
+        chain
+    }
+
+    fn test_chain(root_byte: u8, len: usize) -> HashMap<u64, BlockHeader> {
+        let (mut registry, _) = header_registry();
+        let mut base = registry.remove(&HEADER_HEIGHT).unwrap();
+        base.merkle_root_hash = H256::from([root_byte; 32]);
+        build_chain(base, 0, vec![H256::from([root_byte; 32]); len])
+    }
+
+    fn mock_electrum_chain(chain: HashMap<u64, BlockHeader>) {
+        let chain = Arc::new(chain);
+        let headers_chain = chain.clone();
+        ElectrumClient::retrieve_last_headers.mock_safe(move |_, count, height| {
+            let registry: HashMap<u64, BlockHeader> = (height - count.get()..height)
+                .filter_map(|height| headers_chain.get(&height).map(|header| (height, header.clone())))
+                .collect();
+            let headers = registry.values().cloned().collect();
+            MockResult::Return(Box::new(futures01::future::ok((registry, headers))))
+        });
+        ElectrumClient::blockchain_block_header.mock_safe(move |_, height| {
+            let bytes = BytesJson::from(serialize(&chain[&height]));
+            MockResult::Return(Box::new(futures01::future::ok(bytes)))
+        });
+    }
+
+    fn mock_merkle_branches(branches: HashMap<H256Json, TxMerkleBranch>) {
+        ElectrumClient::blockchain_transaction_get_merkle.mock_safe(move |_, tx_hash, _| {
+            let branch = branches[&tx_hash].clone();
+            MockResult::Return(Box::new(futures01::future::ok(branch)))
+        });
+    }
+
+    fn electrum_client() -> ElectrumClient {
+        ElectrumClient(Arc::new(ElectrumClientImpl::new(TICKER.into(), Vec::new())))
+    }
+
+    fn store_headers(storage: &BlockHeaderStorage, chain: &HashMap<u64, BlockHeader>, heights: &[u64]) {
+        let headers = heights.iter().map(|height| (*height, chain[height].clone())).collect();
+        block_on(storage.add_block_headers_to_storage(TICKER, headers)).unwrap();
+    }
+
+    /// Returns the `(tx_1, tx_2)` hashes of the block with two transactions and its merkle root.
+    fn two_txs_block() -> (H256, H256, H256) {
+        let tx_1 = H256::from([1; 32]);
+        let tx_2 = H256::from([2; 32]);
+        let mut concat = tx_1.take().to_vec();
+        concat.extend_from_slice(&tx_2.take());
+        (tx_1, tx_2, dhash256(&concat))
+    }
+
+    fn merkle_branch(sibling: &H256, block_height: u64, pos: usize) -> TxMerkleBranch {
+        TxMerkleBranch {
+            merkle: vec![sibling.reversed().into()],
+            block_height,
+            pos,
+        }
+    }
+
+    #[test]
+    fn test_validate_headers_chain_checkpoint() {
+        let storage = storage_with_checkpoint(HEADER_HASH);
+        let (registry, headers) = header_registry();
+        validate_headers_chain(&storage, &registry, headers).unwrap();
+
+        let storage = storage_with_checkpoint("00000000000000000001a0a448d6cf2546b06801389cc030b2b18c6491266815");
+        let (registry, headers) = header_registry();
+        let error = validate_headers_chain(&storage, &registry, headers).unwrap_err();
+        assert!(
+            matches!(error.get_inner(), SpvVerificationError::Unverifiable(_)),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_forget_verified_txs_from_height() {
+        let storage = storage_with_checkpoint(HEADER_HASH);
+        let tx_1 = H256Json::from([1; 32]);
+        let tx_2 = H256Json::from([2; 32]);
+        storage.mark_tx_verified(tx_1.clone(), 100);
+        storage.mark_tx_verified(tx_2.clone(), 101);
+
+        storage.forget_verified_txs_from_height(101);
+        assert!(storage.is_tx_verified(&tx_1, 100));
+        assert!(!storage.is_tx_verified(&tx_2, 101));
+        // The transaction is verified for the specific block only.
+        assert!(!storage.is_tx_verified(&tx_1, 101));
+    }
+
+    #[test]
+    fn test_verify_tx_inclusion_merkle() {
+        let (tx_1, tx_2, merkle_root) = two_txs_block();
+        let mut merkle_roots = vec![H256::from([7; 32]); 10];
+        merkle_roots[4] = merkle_root;
+        let chain = build_chain(test_chain(7, 0)[&0].clone(), 100, merkle_roots);
+        mock_electrum_chain(chain.clone());
+
+        let (tx_1_json, tx_2_json): (H256Json, H256Json) = (tx_1.reversed().into(), tx_2.reversed().into());
+        let unknown_tx: H256Json = H256::from([3; 32]).reversed().into();
+        let mut branches = HashMap::new();
+        branches.insert(tx_1_json.clone(), merkle_branch(&tx_2, 105, 0));
+        branches.insert(tx_2_json.clone(), merkle_branch(&tx_1, 105, 1));
+        branches.insert(unknown_tx.clone(), merkle_branch(&tx_1, 105, 1));
+        mock_merkle_branches(branches);
+
+        let client = electrum_client();
+        let storage = storage_without_checkpoint();
+        block_on(verify_tx_inclusion(TICKER, &client, &storage, tx_1_json.clone(), 105)).unwrap();
+        assert!(storage.is_tx_verified(&tx_1_json, 105));
+        // The first headers of the coin without a checkpoint are stored as they are.
+        assert_eq!(
+            block_on(storage.get_block_header(TICKER, 105)).unwrap(),
+            Some(chain[&105].clone())
+        );
+        block_on(verify_tx_inclusion(TICKER, &client, &storage, tx_2_json, 105)).unwrap();
+
+        let error = block_on(verify_tx_inclusion(TICKER, &client, &storage, unknown_tx.clone(), 105)).unwrap_err();
+        assert!(
+            matches!(error.get_inner(), SpvVerificationError::Unverifiable(_)),
+            "{}",
+            error
+        );
+        // The merkle branch must be of the requested block.
+        let error = block_on(verify_tx_inclusion(TICKER, &client, &storage, unknown_tx, 106)).unwrap_err();
+        assert!(
+            matches!(error.get_inner(), SpvVerificationError::Unverifiable(_)),
+            "{}",
+            error
+        );
+    }
+
+    #[test]
+    fn test_retrieve_linked_headers() {
+        let chain = test_chain(1, 120);
+        let fake_chain = test_chain(2, 120);
+        let client = electrum_client();
+        let storage = storage_without_checkpoint();
+        store_headers(&storage, &chain, &[110]);
+
+        // The fake chain doesn't link to the stored tip.
+        mock_electrum_chain(fake_chain);
+        let error = block_on(validated_block_header(TICKER, &client, &storage, 115)).unwrap_err();
+        assert!(
+            matches!(error.get_inner(), SpvVerificationError::Unverifiable(_)),
+            "{}",
+            error
+        );
+        assert_eq!(block_on(storage.get_block_header(TICKER, 115)).unwrap(), None);
+
+        mock_electrum_chain(chain.clone());
+        let header = block_on(validated_block_header(TICKER, &client, &storage, 115)).unwrap();
+        assert_eq!(header, chain[&115]);
+        // The headers between the stored tip and the requested one are stored too.
+        assert_eq!(
+            block_on(storage.get_block_header(TICKER, 112)).unwrap(),
+            Some(chain[&112].clone())
+        );
+
+        // The headers below the stored tip are linked to it.
+        let header = block_on(validated_block_header(TICKER, &client, &storage, 105)).unwrap();
+        assert_eq!(header, chain[&105]);
+    }
+
+    #[test]
+    fn test_chain_reorg() {
+        let chain = test_chain(1, 120);
+        let mut reorg_chain = build_chain(chain[&105].clone(), 105, vec![H256::from([2; 32]); 15]);
+        reorg_chain.extend((0..105).map(|height| (height, chain[&height].clone())));
+        let client = electrum_client();
+        let storage = storage_without_checkpoint();
+        store_headers(&storage, &chain, &(100..=110).collect::<Vec<_>>());
+        let reorganized_tx = H256Json::from([1; 32]);
+        storage.mark_tx_verified(reorganized_tx.clone(), 107);
+        mock_electrum_chain(reorg_chain.clone());
+
+        // The reorganized chain is linked to the last common header.
+        let registry = block_on(retrieve_linked_headers(TICKER, &client, &storage, 115)).unwrap();
+        assert_eq!(registry[&115], reorg_chain[&115]);
+
+        block_on(handle_chain_reorg(TICKER, &client, &storage, &registry)).unwrap();
+        assert_eq!(
+            block_on(storage.get_block_header(TICKER, 105)).unwrap(),
+            Some(chain[&105].clone())
+        );
+        assert_eq!(block_on(storage.get_block_header(TICKER, 106)).unwrap(), None);
+        assert!(!storage.is_tx_verified(&reorganized_tx, 107));
+    }
+
+    #[test]
+    fn test_find_fork_height() {
+        let chain = test_chain(1, 120);
+        let mut reorg_chain = build_chain(chain[&105].clone(), 105, vec![H256::from([2; 32]); 15]);
+        reorg_chain.extend((0..105).map(|height| (height, chain[&height].clone())));
+        let client = electrum_client();
+        let storage = storage_without_checkpoint();
+        store_headers(&storage, &chain, &(100..=110).collect::<Vec<_>>());
+        mock_electrum_chain(reorg_chain.clone());
+
+        // The registry diverges from the stored chain at its lowest header,
+        // so the fork point is looked for below it.
+        let registry: HashMap<u64, BlockHeader> = (108..=110)
+            .map(|height| (height, reorg_chain[&height].clone()))
+            .collect();
+        block_on(handle_chain_reorg(TICKER, &client, &storage, &registry)).unwrap();
+        assert_eq!(
+            block_on(storage.get_block_header(TICKER, 105)).unwrap(),
+            Some(chain[&105].clone())
+        );
+        assert_eq!(block_on(storage.get_block_header(TICKER, 106)).unwrap(), None);
+    }
+
+    #[test]
+    fn test_partition_unspents_by_spv() {
+        let (tx_1, tx_2, merkle_root) = two_txs_block();
+        let mut merkle_roots = vec![H256::from([7; 32]); 10];
+        merkle_roots[4] = merkle_root;
+        mock_electrum_chain(build_chain(test_chain(7, 0)[&0].clone(), 100, merkle_roots));
+
+        let unknown_tx = H256::from([3; 32]);
+        let mut branches = HashMap::new();
+        branches.insert(tx_1.reversed().into(), merkle_branch(&tx_2, 105, 0));
+        branches.insert(unknown_tx.reversed().into(), merkle_branch(&tx_1, 105, 1));
+        mock_merkle_branches(branches);
+
+        let mut fields = utxo_coin_fields_for_test(UtxoRpcClientEnum::Electrum(electrum_client()), None, false);
+        fields.conf.ticker = TICKER.to_owned();
+        fields.block_headers_storage = Some(storage_without_checkpoint());
+        let unspent = |hash: H256, height: Option<u64>| UnspentInfo {
+            outpoint: OutPoint { hash, index: 0 },
+            value: 1000,
+            height,
+        };
+        let unconfirmed = unspent(H256::from([4; 32]), None);
+        let confirmed = unspent(tx_1, Some(105));
+        let fake = unspent(unknown_tx, Some(105));
+
+        let (verified, unverified) = block_on(partition_unspents_by_spv(&fields, vec![
+            unconfirmed.clone(),
+            confirmed.clone(),
+            fake.clone(),
+        ]));
+        assert_eq!(verified, vec![unconfirmed, confirmed]);
+        assert_eq!(unverified, vec![fake]);
+    }
+}
@@ -54,6 +54,20 @@ fn get_block_header_by_height(for_coin: &str) -> Result<String, MmError<BlockHea
     Ok(sql)
 }
 
+fn get_last_block_height_sql(for_coin: &str) -> Result<String, MmError<BlockHeaderStorageError>> {
+    let table_name = get_table_name_and_validate(for_coin)?;
+    let sql = format!("SELECT MAX(block_height) FROM {};", table_name);
+
+    Ok(sql)
+}
+
+fn remove_block_headers_from_height_sql(for_coin: &str) -> Result<String, MmError<BlockHeaderStorageError>> {
+    let table_name = get_table_name_and_validate(for_coin)?;
+    let sql = format!("DELETE FROM {} WHERE block_height >= ?1;", table_name);
+
+    Ok(sql)
+}
+
 #[derive(Clone, Debug)]
 pub struct SqliteBlockHeadersStorage(pub Arc<Mutex<Connection>>);
 
@@ -234,6 +248,46 @@ impl BlockHeaderStorageOps for SqliteBlockHeadersStorage {
             })
         })
     }
+
+    async fn get_last_block_height(&self, for_coin: &str) -> Result<Option<u64>, MmError<BlockHeaderStorageError>> {
+        let sql = get_last_block_height_sql(for_coin)?;
+        let selfi = self.clone();
+
+        let height = async_blocking(move || {
+            let conn = selfi.0.lock().unwrap();
+            query_single_row(&conn, &sql, NO_PARAMS, |row| row.get::<_, Option<i64>>(0))
+        })
+        .await
+        .map_err(|e| {
+            MmError::new(BlockHeaderStorageError::GetFromStorageError {
+                ticker: for_coin.to_string(),
+                reason: e.into_inner().to_string(),
+            })
+        })?;
+        Ok(height.flatten().map(|height| height as u64))
+    }
+
+    async fn remove_block_headers_from_height(
+        &self,
+        for_coin: &str,
+        from_height: u64,
+    ) -> Result<(), MmError<BlockHeaderStorageError>> {
+        let params = [from_height.to_string()];
+        let sql = remove_block_headers_from_height_sql(for_coin)?;
+        let ticker = for_coin.to_owned();
+        let selfi = self.clone();
+
+        async_blocking(move || {
+            let conn = selfi.0.lock().unwrap();
+            conn.execute(&sql, params).map(|_| ()).map_err(|e| {
+                MmError::new(BlockHeaderStorageError::RemoveFromStorageError {
+                    ticker,
+                    reason: e.to_string(),
+                })
+            })
+        })
+        .await
+    }
 }
 
 #[cfg(test)]
@@ -321,4 +375,30 @@ mod sql_block_headers_storage_tests {
             H256::from_reversed_str("0000000000000000002e31d0714a5ab23100945ff87ba2d856cd566a3c9344ec")
         )
     }
+
+    #[test]
+    fn test_remove_block_headers_from_height() {
+        let for_coin = "remove";
+        let storage = SqliteBlockHeadersStorage::in_memory();
+        block_on(storage.init(for_coin)).unwrap();
+
+        let block_header = ElectrumBlockHeaderV14 {
+            height: 520481,
+            hex: "0000002076d41d3e4b0bfd4c0d3b30aa69fdff3ed35d85829efd04000000000000000000b386498b583390959d9bac72346986e3015e83ac0b54bc7747a11a494ac35c94bb3ce65a53fb45177f7e311c".into(),
+        }.into();
+        let headers = vec![ElectrumBlockHeader::V14(block_header)];
+        block_on(storage.add_electrum_block_headers_to_storage(for_coin, headers)).unwrap();
+
+        block_on(storage.remove_block_headers_from_height(for_coin, 520482)).unwrap();
+        assert!(block_on(storage.get_block_header_raw(for_coin, 520481))
+            .unwrap()
+            .is_some());
+        assert_eq!(block_on(storage.get_last_block_height(for_coin)).unwrap(), Some(520481));
+
+        block_on(storage.remove_block_headers_from_height(for_coin, 520481)).unwrap();
+        assert!(block_on(storage.get_block_header_raw(for_coin, 520481))
+            .unwrap()
+            .is_none());
+        assert_eq!(block_on(storage.get_last_block_height(for_coin)).unwrap(), None);
+    }
 }
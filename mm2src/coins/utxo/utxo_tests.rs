@@ -5,6 +5,9 @@ use crate::hd_wallet_storage::{HDWalletMockStorage, HDWalletStorageInternalOps};
 use crate::rpc_command::account_balance::{AccountBalanceParams, AccountBalanceRpcOps, HDAccountBalanceResponse};
 use crate::rpc_command::init_scan_for_new_addresses::{InitScanAddressesRpcOps, ScanAddressesParams,
                                                       ScanAddressesResponse};
+use crate::tx_history_storage::TxHistoryStorageBuilder;
+use crate::utxo::kmd_rewards_claim::{retain_current_rewarded, select_unspents_to_claim, should_claim_rewards,
+                                     RewardedUnspent};
 use crate::utxo::qtum::{qtum_coin_with_priv_key, QtumCoin, QtumDelegationOps, QtumDelegationRequest};
 use crate::utxo::rpc_clients::{BlockHashOrHeight, ElectrumBalance, ElectrumClient, ElectrumClientImpl,
                                GetAddressInfoRes, ListSinceBlockRes, ListTransactionsItem, NativeClient,
@@ -13,13 +16,14 @@ use crate::utxo::rpc_clients::{BlockHashOrHeight, ElectrumBalance, ElectrumClien
 use crate::utxo::tx_cache::dummy_tx_cache::DummyVerboseCache;
 use crate::utxo::tx_cache::UtxoVerboseCacheOps;
 use crate::utxo::utxo_builder::{UtxoArcBuilder, UtxoCoinBuilderCommonOps};
-use crate::utxo::utxo_common::UtxoTxBuilder;
+use crate::utxo::utxo_common::{maker_order_outputs_to_create, split_off_reserved_unspents, UtxoTxBuilder};
 use crate::utxo::utxo_common_tests;
 use crate::utxo::utxo_standard::{utxo_standard_coin_with_priv_key, UtxoStandardCoin};
+use crate::utxo::utxo_tx_history_v2::{utxo_history_loop, UtxoTxHistoryOps};
 #[cfg(not(target_arch = "wasm32"))] use crate::WithdrawFee;
 use crate::{CoinBalance, PrivKeyBuildPolicy, SearchForSwapTxSpendInput, StakingInfosDetails, SwapOps,
-            TradePreimageValue, TxFeeDetails};
-use chain::OutPoint;
+            TradePreimageValue, TransactionType, TxFeeDetails};
+use chain::{OutPoint, TransactionInput};
 use common::executor::Timer;
 use common::{block_on, now_ms, OrdRange, PagingOptionsEnum, DEX_FEE_ADDR_RAW_PUBKEY};
 use crypto::{privkey::key_pair_from_seed, Bip44Chain, RpcDerivationPath};
@@ -27,6 +31,7 @@ use futures::future::join_all;
 use futures::TryFutureExt;
 use mm2_core::mm_ctx::MmCtxBuilder;
 use mm2_number::bigdecimal::{BigDecimal, Signed};
+use mm2_test_helpers::for_tests::mm_ctx_with_custom_db;
 use mocktopus::mocking::*;
 use rpc::v1::types::H256 as H256Json;
 use serialization::{deserialize, CoinVariant};
@@ -76,7 +81,7 @@ pub fn electrum_client_for_test(servers: &[&str]) -> ElectrumClient {
 #[cfg(not(target_arch = "wasm32"))]
 fn native_client_for_test() -> NativeClient { NativeClient(Arc::new(NativeClientImpl::default())) }
 
-fn utxo_coin_fields_for_test(
+pub(super) fn utxo_coin_fields_for_test(
     rpc_client: UtxoRpcClientEnum,
     force_seed: Option<&str>,
     is_segwit_coin: bool,
@@ -197,6 +202,36 @@ fn test_extract_secret() {
     assert_eq!(secret, expected_secret);
 }
 
+#[test]
+fn test_extract_secret_sha256() {
+    let client = electrum_client_for_test(RICK_ELECTRUM_ADDRS);
+    let coin = utxo_coin_for_test(client.into(), None, false);
+
+    let tx_hex = hex::decode("0100000001de7aa8d29524906b2b54ee2e0281f3607f75662cbc9080df81d1047b78e21dbc00000000d7473044022079b6c50820040b1fbbe9251ced32ab334d33830f6f8d0bf0a40c7f1336b67d5b0220142ccf723ddabb34e542ed65c395abc1fbf5b6c3e730396f15d25c49b668a1a401209da937e5609680cb30bff4a7661364ca1d1851c2506fa80c443f00a3d3bf7365004c6b6304f62b0e5cb175210270e75970bb20029b3879ec76c4acd320a8d0589e003636264d01a7d566504bfbac6782012088a9142fb610d856c19fd57f2d0cffe8dff689074b3d8a882103f368228456c940ac113e53dad5c104cf209f2f102a409207269383b6ab9b03deac68ffffffff01d0dc9800000000001976a9146d9d2b554d768232320587df75c4338ecc8bf37d88ac40280e5c").unwrap();
+    let expected_secret = hex::decode("9da937e5609680cb30bff4a7661364ca1d1851c2506fa80c443f00a3d3bf7365").unwrap();
+    let secret_hash = &*sha256(&expected_secret);
+    let secret = coin.extract_secret(secret_hash, &tx_hex).unwrap();
+    assert_eq!(secret, expected_secret);
+}
+
+#[test]
+fn test_payment_script_for_secret() {
+    let pub_0 =
+        Public::from_slice(&hex::decode("03f368228456c940ac113e53dad5c104cf209f2f102a409207269383b6ab9b03de").unwrap())
+            .unwrap();
+    let pub_1 =
+        Public::from_slice(&hex::decode("0270e75970bb20029b3879ec76c4acd320a8d0589e003636264d01a7d566504bfb").unwrap())
+            .unwrap();
+    let secret = [1; 32];
+
+    for secret_hash in vec![dhash160(&secret).to_vec(), sha256(&secret).to_vec()] {
+        let script = utxo_common::payment_script(1000, &secret_hash, &pub_0, &pub_1);
+        let script_pubkey = Builder::build_p2sh(&dhash160(&script).into()).to_bytes();
+        let actual = utxo_common::payment_script_for_secret(1000, &secret, &pub_0, &pub_1, &script_pubkey);
+        assert_eq!(actual, script);
+    }
+}
+
 #[test]
 fn test_send_maker_spends_taker_payment_recoverable_tx() {
     let client = electrum_client_for_test(RICK_ELECTRUM_ADDRS);
@@ -389,6 +424,163 @@ fn test_kmd_interest_accrue_stop_at() {
     assert_eq!(expected, actual);
 }
 
+fn rewarded_unspent_for_test(index: u32, value: u64, rewards: u64, stop_at: u64) -> RewardedUnspent {
+    RewardedUnspent {
+        unspent: UnspentInfo {
+            outpoint: OutPoint {
+                hash: Default::default(),
+                index,
+            },
+            value,
+            height: Some(1000001),
+        },
+        rewards,
+        stop_at,
+    }
+}
+
+#[test]
+fn test_select_unspents_to_claim_kmd_rewards() {
+    let rewarded = vec![
+        rewarded_unspent_for_test(0, 3_000_000_000, 300, 0),
+        rewarded_unspent_for_test(1, 1_000_000_000, 100, 0),
+        rewarded_unspent_for_test(2, 2_000_000_000, 200, 0),
+    ];
+    let to_claim = select_unspents_to_claim(rewarded, 0, 0);
+    let actual: Vec<_> = to_claim
+        .iter()
+        .map(|rewarded| rewarded.unspent.outpoint.index)
+        .collect();
+    // The unspents are ordered by value.
+    assert_eq!(actual, vec![1, 2, 0]);
+
+    // The least rewarded unspents are left for the locked amount.
+    let rewarded = vec![
+        rewarded_unspent_for_test(0, 3_000_000_000, 300, 0),
+        rewarded_unspent_for_test(1, 1_000_000_000, 100, 0),
+        rewarded_unspent_for_test(2, 2_000_000_000, 200, 0),
+    ];
+    let to_claim = select_unspents_to_claim(rewarded, 500_000_000, 1_500_000_001);
+    let actual: Vec<_> = to_claim
+        .iter()
+        .map(|rewarded| rewarded.unspent.outpoint.index)
+        .collect();
+    assert_eq!(actual, vec![0]);
+
+    // The not rewarded unspents cover the locked amount.
+    let rewarded = vec![
+        rewarded_unspent_for_test(0, 3_000_000_000, 300, 0),
+        rewarded_unspent_for_test(1, 1_000_000_000, 100, 0),
+    ];
+    let to_claim = select_unspents_to_claim(rewarded, 1_000_000_000, 1_000_000_000);
+    assert_eq!(to_claim.len(), 2);
+
+    // Nothing can be claimed if everything is locked.
+    let rewarded = vec![rewarded_unspent_for_test(0, 3_000_000_000, 300, 0)];
+    let to_claim = select_unspents_to_claim(rewarded, 0, 5_000_000_000);
+    assert!(to_claim.is_empty());
+}
+
+#[test]
+fn test_should_claim_kmd_rewards() {
+    let deadline = 1_000_000;
+    let to_claim = vec![
+        rewarded_unspent_for_test(0, 1_000_000_000, 100, deadline + 1),
+        rewarded_unspent_for_test(1, 2_000_000_000, 200, deadline + 1),
+    ];
+    assert!(should_claim_rewards(&to_claim, 300, deadline));
+    assert!(!should_claim_rewards(&to_claim, 301, deadline));
+
+    // One of the unspents stops accruing the rewards soon.
+    let to_claim = vec![
+        rewarded_unspent_for_test(0, 1_000_000_000, 100, deadline + 1),
+        rewarded_unspent_for_test(1, 2_000_000_000, 200, deadline),
+    ];
+    assert!(should_claim_rewards(&to_claim, 301, deadline));
+
+    assert!(!should_claim_rewards(&[], 0, deadline));
+}
+
+#[test]
+fn test_retain_current_kmd_rewarded() {
+    let rewarded = vec![
+        rewarded_unspent_for_test(0, 1_000_000_000, 100, 0),
+        rewarded_unspent_for_test(1, 2_000_000_000, 200, 0),
+    ];
+    // The unspent 1 was spent and the unspent 2 appeared while the rewards were being evaluated.
+    let current = vec![
+        rewarded_unspent_for_test(0, 1_000_000_000, 100, 0).unspent,
+        rewarded_unspent_for_test(2, 3_000_000_000, 300, 0).unspent,
+    ];
+    let (rewarded, not_rewarded_value) = retain_current_rewarded(rewarded, &current);
+    let actual: Vec<_> = rewarded
+        .iter()
+        .map(|rewarded| rewarded.unspent.outpoint.index)
+        .collect();
+    assert_eq!(actual, vec![0]);
+    assert_eq!(not_rewarded_value, 3_000_000_000);
+}
+
+fn unspents_for_test(values: &[u64]) -> Vec<UnspentInfo> {
+    values
+        .iter()
+        .enumerate()
+        .map(|(index, value)| UnspentInfo {
+            outpoint: OutPoint {
+                hash: Default::default(),
+                index: index as u32,
+            },
+            value: *value,
+            height: Some(1),
+        })
+        .collect()
+}
+
+fn unspent_values(unspents: &[UnspentInfo]) -> Vec<u64> { unspents.iter().map(|unspent| unspent.value).collect() }
+
+#[test]
+fn test_split_off_reserved_unspents() {
+    let (reserved, free) = split_off_reserved_unspents(unspents_for_test(&[300, 100, 500, 200]), 0);
+    assert!(reserved.is_empty());
+    assert_eq!(unspent_values(&free), vec![100, 200, 300, 500]);
+
+    // The largest unspents are reserved.
+    let (reserved, free) = split_off_reserved_unspents(unspents_for_test(&[300, 100, 500, 200]), 501);
+    assert_eq!(unspent_values(&reserved), vec![500, 300]);
+    assert_eq!(unspent_values(&free), vec![100, 200]);
+
+    let (reserved, free) = split_off_reserved_unspents(unspents_for_test(&[300, 100]), 1000);
+    assert_eq!(unspent_values(&reserved), vec![300, 100]);
+    assert!(free.is_empty());
+}
+
+#[test]
+fn test_maker_order_outputs_to_create() {
+    let mut unspents = unspents_for_test(&[100, 250, 1000]);
+    let to_create = maker_order_outputs_to_create(&mut unspents, vec![200, 300, 200]);
+    // 300 is covered by 1000 and one of 200 is covered by 250.
+    assert_eq!(to_create, vec![200]);
+    assert_eq!(unspent_values(&unspents), vec![100]);
+
+    let mut unspents = unspents_for_test(&[100]);
+    let to_create = maker_order_outputs_to_create(&mut unspents, vec![]);
+    assert!(to_create.is_empty());
+    assert_eq!(unspent_values(&unspents), vec![100]);
+}
+
+#[test]
+fn test_utxo_merge_params_deserialize() {
+    let params: UtxoMergeParams = json::from_value(json!({"merge_at": 50})).unwrap();
+    assert_eq!(params.mode, UtxoMergeMode::Merge);
+    assert_eq!(params.max_fee_per_kb, None);
+    assert_eq!(params.max_merge_at_once, 100);
+
+    let params: UtxoMergeParams =
+        json::from_value(json!({"merge_at": 10, "max_fee_per_kb": 2000, "mode": "split"})).unwrap();
+    assert_eq!(params.mode, UtxoMergeMode::Split);
+    assert_eq!(params.max_fee_per_kb, Some(2000));
+}
+
 #[test]
 fn test_sat_from_big_decimal() {
     let amount = "0.000001".parse().unwrap();
@@ -2983,6 +3175,7 @@ fn test_update_kmd_rewards() {
         claimed_by_me: true,
     };
     assert_eq!(tx_details.kmd_rewards, Some(expected_rewards));
+    assert_eq!(tx_details.transaction_type, TransactionType::ClaimKmdRewards);
 
     let expected_fee_details = TxFeeDetails::Utxo(UtxoFeeDetails {
         coin: Some("KMD".into()),
@@ -3015,6 +3208,7 @@ fn test_update_kmd_rewards_claimed_not_by_me() {
         claimed_by_me: false,
     };
     assert_eq!(tx_details.kmd_rewards, Some(expected_rewards));
+    assert_eq!(tx_details.transaction_type, TransactionType::StandardTransfer);
 
     let expected_fee_details = TxFeeDetails::Utxo(UtxoFeeDetails {
         coin: Some("KMD".into()),
@@ -4013,3 +4207,133 @@ fn test_sign_verify_message_segwit() {
         .unwrap();
     assert!(is_valid);
 }
+
+/// Runs [`utxo_history_loop`] for the `coin` connected to the mocked native client
+/// and checks that the transaction listed by the client is saved to the tx history storage
+/// and is loaded from there by [`MmCoin::load_history`].
+fn test_utxo_history_loop_v2_impl<Coin>(coin: Coin)
+where
+    Coin: UtxoTxHistoryOps + UtxoCommonOps + MmCoin + Clone,
+{
+    const BLOCK_HEIGHT: u64 = 100;
+    const BLOCK_TIMESTAMP: u64 = 1650000000;
+
+    let my_address = coin.as_ref().derivation_method.iguana_or_err().unwrap().clone();
+    let my_script_pubkey = Builder::build_p2pkh(&my_address.hash).to_bytes();
+    let other_script_pubkey = Builder::build_p2pkh(&AddressHashEnum::AddressHash([1; 20].into())).to_bytes();
+
+    let prev_tx = UtxoTx {
+        version: 1,
+        inputs: vec![TransactionInput::default()],
+        outputs: vec![TransactionOutput {
+            value: 100000000,
+            script_pubkey: my_script_pubkey.clone(),
+        }],
+        ..UtxoTx::default()
+    };
+    let tx = UtxoTx {
+        version: 1,
+        inputs: vec![TransactionInput {
+            previous_output: OutPoint {
+                hash: prev_tx.hash(),
+                index: 0,
+            },
+            ..TransactionInput::default()
+        }],
+        outputs: vec![
+            TransactionOutput {
+                value: 40000000,
+                script_pubkey: other_script_pubkey,
+            },
+            TransactionOutput {
+                value: 59999000,
+                script_pubkey: my_script_pubkey,
+            },
+        ],
+        ..UtxoTx::default()
+    };
+    let tx_id: H256Json = tx.hash().reversed().into();
+
+    let raw_txs: HashMap<H256Json, BytesJson> = vec![
+        (prev_tx.hash().reversed().into(), serialize(&prev_tx).take().into()),
+        (tx_id.clone(), serialize(&tx).take().into()),
+    ]
+    .into_iter()
+    .collect();
+    NativeClientImpl::get_raw_transaction_bytes.mock_safe(move |_, txid| {
+        let bytes = raw_txs.get(txid).expect("Unexpected transaction").clone();
+        MockResult::Return(Box::new(futures01::future::ok(bytes)))
+    });
+
+    let my_address_str = my_address.to_string();
+    let listed_tx_id = tx_id.clone();
+    NativeClientImpl::list_transactions.mock_safe(move |_, _, from| {
+        // The transactions are requested by pages until an empty one is returned.
+        let transactions = if from == 0 {
+            vec![ListTransactionsItem {
+                address: my_address_str.clone(),
+                txid: listed_tx_id.clone(),
+                blockindex: BLOCK_HEIGHT,
+                ..ListTransactionsItem::default()
+            }]
+        } else {
+            Vec::new()
+        };
+        MockResult::Return(Box::new(futures01::future::ok(transactions)))
+    });
+
+    NativeClient::get_block_timestamp.mock_safe(|_, height| {
+        assert_eq!(height, BLOCK_HEIGHT);
+        MockResult::Return(Box::pin(futures::future::ok(BLOCK_TIMESTAMP)))
+    });
+
+    let ctx = mm_ctx_with_custom_db();
+    let storage = TxHistoryStorageBuilder::new(&ctx).build().unwrap();
+    let history_fut = Box::pin(utxo_history_loop(
+        coin.clone(),
+        storage,
+        ctx.metrics.clone(),
+        BigDecimal::from(0),
+    ));
+    let wait_fut = Box::pin(async {
+        for _ in 0..100 {
+            if let HistorySyncState::Finished = coin.history_sync_status() {
+                return;
+            }
+            Timer::sleep(0.1).await;
+        }
+        panic!("{} tx history hasn't been fetched in time", coin.ticker());
+    });
+    block_on(futures::future::select(history_fut, wait_fut));
+    assert!(matches!(coin.history_sync_status(), HistorySyncState::Finished));
+
+    let history = block_on(coin.load_history(&ctx).compat()).unwrap();
+    assert_eq!(history.len(), 1);
+    let details = &history[0];
+    assert_eq!(details.tx_hash, format!("{:02x}", tx_id));
+    assert_eq!(details.block_height, BLOCK_HEIGHT);
+    assert_eq!(details.timestamp, BLOCK_TIMESTAMP);
+    assert_eq!(details.spent_by_me, BigDecimal::from(1));
+    assert_eq!(details.received_by_me, "0.59999".parse().unwrap());
+    assert_eq!(details.my_balance_change, "-0.40001".parse().unwrap());
+    assert_eq!(details.from, vec![my_address.to_string()]);
+
+    let expected_fee = Some(TxFeeDetails::Utxo(UtxoFeeDetails {
+        coin: Some(coin.ticker().to_owned()),
+        amount: "0.00001".parse().unwrap(),
+    }));
+    assert_eq!(details.fee_details, expected_fee);
+}
+
+#[test]
+fn test_utxo_history_loop_v2() {
+    let coin = utxo_coin_for_test(UtxoRpcClientEnum::Native(native_client_for_test()), None, false);
+    test_utxo_history_loop_v2_impl(coin);
+}
+
+#[test]
+fn test_qtum_history_loop_v2() {
+    let coin_fields = utxo_coin_fields_for_test(UtxoRpcClientEnum::Native(native_client_for_test()), None, false);
+    let arc: UtxoArc = coin_fields.into();
+    test_utxo_history_loop_v2_impl(QtumCoin::from(arc));
+}
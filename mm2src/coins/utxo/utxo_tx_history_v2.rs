@@ -0,0 +1,586 @@
+//! The UTXO transaction history state machine that keeps the history within [`TxHistoryStorage`].
+//! It is shared by every UTXO-based coin including BCH with its SLP tokens.
+
+use super::RequestTxHistoryResult;
+use crate::hd_wallet::{AddressDerivingError, InvalidBip44ChainError};
+use crate::my_tx_history_v2::{CoinWithTxHistoryV2, TxHistoryStorage};
+use crate::tx_history_storage::TxHistoryStorageBuilder;
+use crate::utxo::bch::GetTxDetailsError;
+use crate::utxo::utxo_common;
+use crate::utxo::utxo_spv::SpvVerificationError;
+use crate::utxo::UtxoRpcError;
+use crate::{BlockHeightAndTime, CoinsContext, HistorySyncState, MarketCoinOps, MmCoin, TransactionDetails};
+use async_trait::async_trait;
+use common::executor::Timer;
+use common::log::{error, info};
+use common::mm_metrics::MetricsArc;
+use common::now_ms;
+use common::state_machine::prelude::*;
+use derive_more::Display;
+use futures::compat::Future01CompatExt;
+use futures::future::select;
+use keys::Address;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::BigDecimal;
+use rpc::v1::types::H256 as H256Json;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
+
+#[derive(Debug, Display)]
+pub enum UtxoMyAddressesHistoryError {
+    AddressDerivingError(String),
+    InvalidBip44Chain(String),
+}
+
+impl From<AddressDerivingError> for UtxoMyAddressesHistoryError {
+    fn from(e: AddressDerivingError) -> Self { UtxoMyAddressesHistoryError::AddressDerivingError(e.to_string()) }
+}
+
+impl From<InvalidBip44ChainError> for UtxoMyAddressesHistoryError {
+    fn from(e: InvalidBip44ChainError) -> Self { UtxoMyAddressesHistoryError::InvalidBip44Chain(e.to_string()) }
+}
+
+/// The operations required by the UTXO transaction history state machine.
+#[async_trait]
+pub trait UtxoTxHistoryOps: CoinWithTxHistoryV2 + MarketCoinOps + Send + Sync + 'static {
+    /// Returns the addresses the transaction history is fetched for:
+    /// the iguana address or the known addresses of the HD wallet.
+    async fn my_addresses(&self) -> MmResult<HashSet<Address>, UtxoMyAddressesHistoryError>;
+
+    /// Requests the ids and heights of the transactions related to the given `for_addresses`.
+    async fn request_tx_history(&self, metrics: MetricsArc, for_addresses: &HashSet<Address>)
+        -> RequestTxHistoryResult;
+
+    async fn get_block_timestamp(&self, height: u64) -> MmResult<u64, UtxoRpcError>;
+
+    /// Returns the details of the transaction.
+    /// There can be several details if tokens were transferred within the transaction.
+    async fn tx_details_by_hash<Storage: TxHistoryStorage>(
+        &self,
+        tx_hash: &H256Json,
+        block_height_and_time: Option<BlockHeightAndTime>,
+        storage: &Storage,
+        my_addresses: &HashSet<Address>,
+    ) -> MmResult<Vec<TransactionDetails>, GetTxDetailsError<Storage::Error>>;
+
+    /// Verifies the transaction against the locally validated block headers if the SPV mode is enabled.
+    /// Returns `None` if the SPV mode is disabled or the transaction is not confirmed yet.
+    async fn spv_verify_tx(&self, tx_hash: &H256Json, height: u64) -> MmResult<Option<bool>, SpvVerificationError>;
+
+    fn set_history_sync_state(&self, new_state: HistorySyncState);
+}
+
+struct UtxoTxHistoryCtx<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> {
+    coin: Coin,
+    storage: Storage,
+    metrics: MetricsArc,
+    current_balance: BigDecimal,
+    /// The addresses the history has been requested for the last time.
+    my_addresses: HashSet<Address>,
+    /// The last time in seconds the history was known to be in sync.
+    /// It's the time the loop is started at until the history is fetched for the first time.
+    synced_at: u64,
+}
+
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> UtxoTxHistoryCtx<Coin, Storage> {
+    fn mark_synced(&mut self) {
+        self.synced_at = now_ms() / 1000;
+        self.update_sync_lag_metric();
+    }
+
+    /// Reports how many seconds have passed since the history was known to be in sync.
+    fn update_sync_lag_metric(&self) {
+        let lag = (now_ms() / 1000).saturating_sub(self.synced_at);
+        mm_gauge!(self.metrics, "tx.history.sync_lag", lag as i64, "coin" => self.coin.ticker().to_owned());
+    }
+}
+
+// States have to be generic over coin and storage types because UtxoTxHistoryCtx is generic over them
+struct Init<Coin, Storage> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+}
+
+impl<Coin, Storage> Init<Coin, Storage> {
+    fn new() -> Self {
+        Init {
+            phantom: Default::default(),
+        }
+    }
+}
+
+impl<Coin, Storage, E> TransitionFrom<Init<Coin, Storage>> for Stopped<Coin, Storage, E> {}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> State for Init<Coin, Storage> {
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> StateResult<Self::Ctx, Self::Result> {
+        ctx.coin.set_history_sync_state(HistorySyncState::NotStarted);
+
+        if let Err(e) = ctx.storage.init(&ctx.coin.history_wallet_id()).await {
+            return Self::change_state(Stopped::storage_error(e));
+        }
+
+        Self::change_state(FetchingTxHashes::new())
+    }
+}
+
+// States have to be generic over coin and storage types because UtxoTxHistoryCtx is generic over them
+struct FetchingTxHashes<Coin, Storage> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+}
+
+impl<Coin, Storage> FetchingTxHashes<Coin, Storage> {
+    fn new() -> Self {
+        FetchingTxHashes {
+            phantom: Default::default(),
+        }
+    }
+}
+
+impl<Coin, Storage> TransitionFrom<Init<Coin, Storage>> for FetchingTxHashes<Coin, Storage> {}
+impl<Coin, Storage> TransitionFrom<OnIoErrorCooldown<Coin, Storage>> for FetchingTxHashes<Coin, Storage> {}
+impl<Coin, Storage> TransitionFrom<WaitForHistoryUpdateTrigger<Coin, Storage>> for FetchingTxHashes<Coin, Storage> {}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> State for FetchingTxHashes<Coin, Storage> {
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> StateResult<Self::Ctx, Self::Result> {
+        let wallet_id = ctx.coin.history_wallet_id();
+        if let Err(e) = ctx.storage.init(&wallet_id).await {
+            return Self::change_state(Stopped::storage_error(e));
+        }
+
+        ctx.my_addresses = match ctx.coin.my_addresses().await {
+            Ok(addresses) => addresses,
+            Err(e) => {
+                error!("Error {} on getting my addresses of {}", e, ctx.coin.ticker());
+                return Self::change_state(OnIoErrorCooldown::new());
+            },
+        };
+
+        let maybe_tx_ids = ctx
+            .coin
+            .request_tx_history(ctx.metrics.clone(), &ctx.my_addresses)
+            .await;
+        match maybe_tx_ids {
+            RequestTxHistoryResult::Ok(all_tx_ids_with_height) => {
+                let in_storage = match ctx.storage.unique_tx_hashes_num_in_history(&wallet_id).await {
+                    Ok(num) => num,
+                    Err(e) => return Self::change_state(Stopped::storage_error(e)),
+                };
+                if all_tx_ids_with_height.len() > in_storage {
+                    let txes_left = all_tx_ids_with_height.len() - in_storage;
+                    ctx.coin.set_history_sync_state(HistorySyncState::InProgress(json!({
+                        "transactions_left": txes_left
+                    })));
+                }
+
+                Self::change_state(UpdatingUnconfirmedTxes::new(all_tx_ids_with_height))
+            },
+            RequestTxHistoryResult::HistoryTooLarge => {
+                Self::change_state(Stopped::<Coin, Storage, Storage::Error>::history_too_large())
+            },
+            RequestTxHistoryResult::Retry { error } => {
+                error!("Error {} on requesting tx history for {}", error, ctx.coin.ticker());
+                Self::change_state(OnIoErrorCooldown::new())
+            },
+            RequestTxHistoryResult::CriticalError(e) => {
+                error!(
+                    "Critical error {} on requesting tx history for {}",
+                    e,
+                    ctx.coin.ticker()
+                );
+                Self::change_state(Stopped::<Coin, Storage, Storage::Error>::unknown(e))
+            },
+        }
+    }
+}
+
+// States have to be generic over coin and storage types because UtxoTxHistoryCtx is generic over them
+struct OnIoErrorCooldown<Coin, Storage> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+}
+
+impl<Coin, Storage> OnIoErrorCooldown<Coin, Storage> {
+    fn new() -> Self {
+        OnIoErrorCooldown {
+            phantom: Default::default(),
+        }
+    }
+}
+
+impl<Coin, Storage> TransitionFrom<FetchingTxHashes<Coin, Storage>> for OnIoErrorCooldown<Coin, Storage> {}
+impl<Coin, Storage> TransitionFrom<FetchingTransactionsData<Coin, Storage>> for OnIoErrorCooldown<Coin, Storage> {}
+impl<Coin, Storage> TransitionFrom<UpdatingUnconfirmedTxes<Coin, Storage>> for OnIoErrorCooldown<Coin, Storage> {}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> State for OnIoErrorCooldown<Coin, Storage> {
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> StateResult<Self::Ctx, Self::Result> {
+        ctx.update_sync_lag_metric();
+        Timer::sleep(30.).await;
+        Self::change_state(FetchingTxHashes::new())
+    }
+}
+
+// States have to be generic over coin and storage types because UtxoTxHistoryCtx is generic over them
+struct WaitForHistoryUpdateTrigger<Coin, Storage> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+}
+
+impl<Coin, Storage> WaitForHistoryUpdateTrigger<Coin, Storage> {
+    fn new() -> Self {
+        WaitForHistoryUpdateTrigger {
+            phantom: Default::default(),
+        }
+    }
+}
+
+impl<Coin, Storage> TransitionFrom<FetchingTransactionsData<Coin, Storage>>
+    for WaitForHistoryUpdateTrigger<Coin, Storage>
+{
+}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> State for WaitForHistoryUpdateTrigger<Coin, Storage> {
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> StateResult<Self::Ctx, Self::Result> {
+        let wallet_id = ctx.coin.history_wallet_id();
+        loop {
+            Timer::sleep(30.).await;
+            match ctx.storage.history_contains_unconfirmed_txes(&wallet_id).await {
+                Ok(contains) => {
+                    if contains {
+                        return Self::change_state(FetchingTxHashes::new());
+                    }
+                },
+                Err(e) => return Self::change_state(Stopped::storage_error(e)),
+            }
+
+            match ctx.coin.my_balance().compat().await {
+                Ok(balance) => {
+                    let total_balance = balance.into_total();
+                    if ctx.current_balance != total_balance {
+                        ctx.current_balance = total_balance;
+                        return Self::change_state(FetchingTxHashes::new());
+                    }
+                },
+                Err(e) => {
+                    error!("Error {} on balance fetching for the coin {}", e, ctx.coin.ticker());
+                },
+            }
+
+            // New addresses could be generated (HD wallet), so their history has to be fetched.
+            match ctx.coin.my_addresses().await {
+                Ok(addresses) => {
+                    if ctx.my_addresses != addresses {
+                        return Self::change_state(FetchingTxHashes::new());
+                    }
+                },
+                Err(e) => {
+                    error!("Error {} on getting my addresses of {}", e, ctx.coin.ticker());
+                },
+            }
+
+            ctx.mark_synced();
+        }
+    }
+}
+
+// States have to be generic over coin and storage types because UtxoTxHistoryCtx is generic over them
+struct UpdatingUnconfirmedTxes<Coin, Storage> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+    all_tx_ids_with_height: Vec<(H256Json, u64)>,
+}
+
+impl<Coin, Storage> UpdatingUnconfirmedTxes<Coin, Storage> {
+    fn new(all_tx_ids_with_height: Vec<(H256Json, u64)>) -> Self {
+        UpdatingUnconfirmedTxes {
+            phantom: Default::default(),
+            all_tx_ids_with_height,
+        }
+    }
+}
+
+impl<Coin, Storage> TransitionFrom<FetchingTxHashes<Coin, Storage>> for UpdatingUnconfirmedTxes<Coin, Storage> {}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> State for UpdatingUnconfirmedTxes<Coin, Storage> {
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> StateResult<Self::Ctx, Self::Result> {
+        let wallet_id = ctx.coin.history_wallet_id();
+        match ctx.storage.get_unconfirmed_txes_from_history(&wallet_id).await {
+            Ok(unconfirmed) => {
+                let txs_with_height: HashMap<H256Json, u64> = self.all_tx_ids_with_height.clone().into_iter().collect();
+                for mut tx in unconfirmed {
+                    let found = match H256Json::from_str(&tx.tx_hash) {
+                        Ok(unconfirmed_tx_hash) => txs_with_height.get_key_value(&unconfirmed_tx_hash),
+                        Err(_) => None,
+                    };
+
+                    match found {
+                        Some((tx_hash, height)) => {
+                            if *height > 0 {
+                                match ctx.coin.get_block_timestamp(*height).await {
+                                    Ok(time) => tx.timestamp = time,
+                                    Err(_) => return Self::change_state(OnIoErrorCooldown::new()),
+                                };
+                                tx.spv_verified = match ctx.coin.spv_verify_tx(tx_hash, *height).await {
+                                    Ok(verified) => verified,
+                                    Err(e) => {
+                                        error!("Error {} on verifying {} tx {}", e, ctx.coin.ticker(), tx.tx_hash);
+                                        return Self::change_state(OnIoErrorCooldown::new());
+                                    },
+                                };
+                                tx.block_height = *height;
+                                if let Err(e) = ctx.storage.update_tx_in_history(&wallet_id, &tx).await {
+                                    return Self::change_state(Stopped::storage_error(e));
+                                }
+                            }
+                        },
+                        None => {
+                            // This can potentially happen when unconfirmed tx is removed from mempool for some reason.
+                            // Or if the hash is undecodable. We should remove it from storage too.
+                            if let Err(e) = ctx.storage.remove_tx_from_history(&wallet_id, &tx.internal_id).await {
+                                return Self::change_state(Stopped::storage_error(e));
+                            }
+                        },
+                    }
+                }
+                Self::change_state(FetchingTransactionsData::new(self.all_tx_ids_with_height))
+            },
+            Err(e) => Self::change_state(Stopped::storage_error(e)),
+        }
+    }
+}
+
+// States have to be generic over coin and storage types because UtxoTxHistoryCtx is generic over them
+struct FetchingTransactionsData<Coin, Storage> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+    all_tx_ids_with_height: Vec<(H256Json, u64)>,
+}
+
+impl<Coin, Storage> TransitionFrom<UpdatingUnconfirmedTxes<Coin, Storage>> for FetchingTransactionsData<Coin, Storage> {}
+
+impl<Coin, Storage> FetchingTransactionsData<Coin, Storage> {
+    fn new(all_tx_ids_with_height: Vec<(H256Json, u64)>) -> Self {
+        FetchingTransactionsData {
+            phantom: Default::default(),
+            all_tx_ids_with_height,
+        }
+    }
+}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage> State for FetchingTransactionsData<Coin, Storage> {
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> StateResult<Self::Ctx, Self::Result> {
+        let wallet_id = ctx.coin.history_wallet_id();
+        for (tx_hash, height) in self.all_tx_ids_with_height {
+            let tx_hash_string = format!("{:02x}", tx_hash);
+            match ctx.storage.history_has_tx_hash(&wallet_id, &tx_hash_string).await {
+                Ok(true) => continue,
+                Ok(false) => (),
+                Err(e) => return Self::change_state(Stopped::storage_error(e)),
+            }
+
+            let block_height_and_time = if height > 0 {
+                let timestamp = match ctx.coin.get_block_timestamp(height).await {
+                    Ok(time) => time,
+                    Err(_) => return Self::change_state(OnIoErrorCooldown::new()),
+                };
+                Some(BlockHeightAndTime { height, timestamp })
+            } else {
+                None
+            };
+            let mut tx_details = match ctx
+                .coin
+                .tx_details_by_hash(&tx_hash, block_height_and_time, &ctx.storage, &ctx.my_addresses)
+                .await
+            {
+                Ok(tx) => tx,
+                Err(e) => {
+                    error!(
+                        "Error {:?} on getting {} tx details for hash {:02x}",
+                        e,
+                        ctx.coin.ticker(),
+                        tx_hash
+                    );
+                    return Self::change_state(OnIoErrorCooldown::new());
+                },
+            };
+
+            let spv_verified = match ctx.coin.spv_verify_tx(&tx_hash, height).await {
+                Ok(verified) => verified,
+                Err(e) => {
+                    error!("Error {} on verifying {} tx {:02x}", e, ctx.coin.ticker(), tx_hash);
+                    return Self::change_state(OnIoErrorCooldown::new());
+                },
+            };
+            for tx in tx_details.iter_mut() {
+                tx.spv_verified = spv_verified;
+            }
+
+            if let Err(e) = ctx.storage.add_transactions_to_history(&wallet_id, tx_details).await {
+                return Self::change_state(Stopped::storage_error(e));
+            }
+
+            ctx.update_sync_lag_metric();
+            // wait for for one second to reduce the number of requests to electrum servers
+            Timer::sleep(1.).await;
+        }
+        info!("Tx history fetching finished for {}", ctx.coin.ticker());
+        ctx.coin.set_history_sync_state(HistorySyncState::Finished);
+        ctx.mark_synced();
+        Self::change_state(WaitForHistoryUpdateTrigger::new())
+    }
+}
+
+#[derive(Debug)]
+enum StopReason<E> {
+    HistoryTooLarge,
+    StorageError(E),
+    UnknownError(String),
+}
+
+struct Stopped<Coin, Storage, E> {
+    phantom: std::marker::PhantomData<(Coin, Storage)>,
+    stop_reason: StopReason<E>,
+}
+
+impl<Coin, Storage, E> Stopped<Coin, Storage, E> {
+    fn history_too_large() -> Self {
+        Stopped {
+            phantom: Default::default(),
+            stop_reason: StopReason::HistoryTooLarge,
+        }
+    }
+
+    fn storage_error(e: E) -> Self {
+        Stopped {
+            phantom: Default::default(),
+            stop_reason: StopReason::StorageError(e),
+        }
+    }
+
+    fn unknown(e: String) -> Self {
+        Stopped {
+            phantom: Default::default(),
+            stop_reason: StopReason::UnknownError(e),
+        }
+    }
+}
+
+impl<Coin, Storage, E> TransitionFrom<FetchingTxHashes<Coin, Storage>> for Stopped<Coin, Storage, E> {}
+impl<Coin, Storage, E> TransitionFrom<UpdatingUnconfirmedTxes<Coin, Storage>> for Stopped<Coin, Storage, E> {}
+impl<Coin, Storage, E> TransitionFrom<WaitForHistoryUpdateTrigger<Coin, Storage>> for Stopped<Coin, Storage, E> {}
+impl<Coin, Storage, E> TransitionFrom<FetchingTransactionsData<Coin, Storage>> for Stopped<Coin, Storage, E> {}
+
+#[async_trait]
+impl<Coin: UtxoTxHistoryOps, Storage: TxHistoryStorage, E: std::fmt::Debug + Send + 'static> LastState
+    for Stopped<Coin, Storage, E>
+{
+    type Ctx = UtxoTxHistoryCtx<Coin, Storage>;
+    type Result = ();
+
+    async fn on_changed(self: Box<Self>, ctx: &mut Self::Ctx) -> Self::Result {
+        info!(
+            "Stopping tx history fetching for {}. Reason: {:?}",
+            ctx.coin.ticker(),
+            self.stop_reason
+        );
+        let new_state_json = match self.stop_reason {
+            StopReason::HistoryTooLarge => json!({
+                "code": utxo_common::HISTORY_TOO_LARGE_ERR_CODE,
+                "message": "Got `history too large` error from Electrum server. History is not available",
+            }),
+            reason => json!({
+                "message": format!("{:?}", reason),
+            }),
+        };
+        ctx.coin.set_history_sync_state(HistorySyncState::Error(new_state_json));
+    }
+}
+
+pub async fn utxo_history_loop<Coin, Storage>(
+    coin: Coin,
+    storage: Storage,
+    metrics: MetricsArc,
+    current_balance: BigDecimal,
+) where
+    Coin: UtxoTxHistoryOps,
+    Storage: TxHistoryStorage,
+{
+    let ctx = UtxoTxHistoryCtx {
+        coin,
+        storage,
+        metrics,
+        current_balance,
+        my_addresses: HashSet::new(),
+        synced_at: now_ms() / 1000,
+    };
+    let state_machine: StateMachine<_, ()> = StateMachine::from_ctx(ctx);
+    state_machine.run(Init::new()).await;
+}
+
+/// Creates the [`TxHistoryStorage`] and runs [`utxo_history_loop`] until the coin is disabled or MM is stopping.
+/// The legacy history file is removed beforehand since the history is refetched into the storage.
+/// Intended to be used within [`MmCoin::process_history_loop`].
+pub async fn process_history_loop_v2<Coin>(coin: Coin, ctx: MmArc)
+where
+    Coin: UtxoTxHistoryOps + MmCoin + Clone,
+{
+    let ticker = coin.ticker().to_owned();
+    if let Err(e) = coin.remove_history_file(&ctx).compat().await {
+        error!("Error {} on removing the legacy tx history file of {}", e, ticker);
+    }
+    let storage = match TxHistoryStorageBuilder::new(&ctx).build() {
+        Ok(storage) => storage,
+        Err(e) => {
+            error!("Error {} on creating the tx history storage for {}", e, ticker);
+            return;
+        },
+    };
+    let current_balance = match coin.my_balance().compat().await {
+        Ok(balance) => balance.into_total(),
+        Err(e) => {
+            error!("Error {} on balance fetching for the coin {}", e, ticker);
+            return;
+        },
+    };
+    let coins_ctx = match CoinsContext::from_ctx(&ctx) {
+        Ok(coins_ctx) => coins_ctx,
+        Err(e) => {
+            error!("Error {} on getting CoinsContext", e);
+            return;
+        },
+    };
+
+    let history_fut = Box::pin(utxo_history_loop(coin, storage, ctx.metrics.clone(), current_balance));
+    let stop_fut = Box::pin(async move {
+        loop {
+            if ctx.is_stopping() {
+                break;
+            }
+            if !coins_ctx.coins.lock().await.contains_key(&ticker) {
+                info!("Tx history loop stopped for {}", ticker);
+                break;
+            }
+            Timer::sleep(10.).await;
+        }
+    });
+    select(history_fut, stop_fut).await;
+}
@@ -224,6 +224,7 @@ where
             internal_id: vec![].into(),
             timestamp: now_ms() / 1000,
             kmd_rewards: data.kmd_rewards,
+            spv_verified: None,
             transaction_type: Default::default(),
         })
     }
@@ -2,26 +2,27 @@ use crate::my_tx_history_v2::{MyTxHistoryErrorV2, MyTxHistoryRequestV2, MyTxHist
 use crate::rpc_command::init_withdraw::{InitWithdrawCoin, WithdrawInProgressStatus, WithdrawTaskHandle};
 use crate::utxo::rpc_clients::{ElectrumRpcRequest, UnspentInfo, UtxoRpcClientEnum, UtxoRpcError, UtxoRpcFut,
                                UtxoRpcResult};
-use crate::utxo::utxo_builder::{UtxoCoinBuilderCommonOps, UtxoCoinWithIguanaPrivKeyBuilder,
+use crate::utxo::utxo_builder::{UtxoCoinBuildError, UtxoCoinBuilderCommonOps, UtxoCoinWithIguanaPrivKeyBuilder,
                                 UtxoFieldsWithIguanaPrivKeyBuilder};
 use crate::utxo::utxo_common::{addresses_from_script, big_decimal_from_sat, big_decimal_from_sat_unsigned,
-                               payment_script};
+                               payment_script, payment_script_for_secret};
 use crate::utxo::{sat_from_big_decimal, utxo_common, ActualTxFee, AdditionalTxData, Address, BroadcastTxErr,
                   FeePolicy, GetUtxoListOps, HistoryUtxoTx, HistoryUtxoTxMap, MatureUnspentList,
                   RecentlySpentOutPointsGuard, UtxoActivationParams, UtxoAddressFormat, UtxoArc, UtxoCoinFields,
                   UtxoCommonOps, UtxoFeeDetails, UtxoRpcMode, UtxoTxBroadcastOps, UtxoTxGenerationOps,
                   VerboseTransactionFrom};
-use crate::{BalanceError, BalanceFut, CoinBalance, FeeApproxStage, FoundSwapTxSpend, HistorySyncState, MarketCoinOps,
-            MmCoin, NegotiateSwapContractAddrErr, NumConversError, PrivKeyActivationPolicy, RawTransactionFut,
-            RawTransactionRequest, SearchForSwapTxSpendInput, SignatureError, SignatureResult, SwapOps, TradeFee,
-            TradePreimageFut, TradePreimageResult, TradePreimageValue, TransactionDetails, TransactionEnum,
-            TransactionFut, TxFeeDetails, UnexpectedDerivationMethod, ValidateAddressResult, ValidatePaymentInput,
-            VerificationError, VerificationResult, WithdrawFut, WithdrawRequest};
-use crate::{Transaction, WithdrawError};
+use crate::{coin_proxy, Transaction, WithdrawError};
+use crate::{BalanceError, BalanceFut, CoinBalance, CoinsContext, FeeApproxStage, FoundSwapTxSpend, HistorySyncState,
+            MarketCoinOps, MmCoin, NegotiateSwapContractAddrErr, NumConversError, PrivKeyActivationPolicy,
+            RawTransactionFut, RawTransactionRequest, SearchForSwapTxSpendInput, SignatureError, SignatureResult,
+            SwapOps, TradeFee, TradePreimageFut, TradePreimageResult, TradePreimageValue, TransactionDetails,
+            TransactionEnum, TransactionFut, TxFeeDetails, UnexpectedDerivationMethod, ValidateAddressResult,
+            ValidatePaymentInput, VerificationError, VerificationResult, WithdrawFut, WithdrawRequest};
 use async_trait::async_trait;
-use bitcrypto::{dhash160, dhash256};
+use bitcrypto::dhash256;
 use chain::constants::SEQUENCE_FINAL;
 use chain::{Transaction as UtxoTx, TransactionOutput};
+use common::executor::Timer;
 use common::{async_blocking, calc_total_pages, log, PagingOptionsEnum};
 use crypto::privkey::{key_pair_from_secret, secp_privkey_from_hash};
 use db_common::sqlite::offset_by_id;
@@ -44,9 +45,11 @@ use script::{Builder as ScriptBuilder, Opcode, Script, TransactionInputSigner};
 use serde_json::Value as Json;
 use serialization::CoinVariant;
 use std::collections::{HashMap, HashSet};
+use std::convert::TryFrom;
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::sync::Arc;
+use zcash_client_backend::data_api::wallet::decrypt_and_store_transaction;
 use zcash_client_backend::data_api::WalletRead;
 use zcash_client_backend::encoding::{decode_payment_address, encode_extended_spending_key, encode_payment_address};
 use zcash_client_backend::wallet::{AccountId, SpendableNote};
@@ -55,7 +58,7 @@ use zcash_client_sqlite::error::SqliteClientError;
 use zcash_client_sqlite::wallet::get_balance;
 use zcash_client_sqlite::wallet::transact::get_spendable_notes;
 use zcash_primitives::consensus::{BlockHeight, NetworkUpgrade, Parameters, H0};
-use zcash_primitives::memo::MemoBytes;
+use zcash_primitives::memo::{Memo, MemoBytes};
 use zcash_primitives::sapling::keys::OutgoingViewingKey;
 use zcash_primitives::sapling::note_encryption::try_sapling_output_recovery;
 use zcash_primitives::transaction::builder::Builder as ZTxBuilder;
@@ -101,6 +104,7 @@ const DEX_FEE_OVK: OutgoingViewingKey = OutgoingViewingKey([7; 32]);
 const DEX_FEE_Z_ADDR: &str = "zs1rp6426e9r6jkq2nsanl66tkd34enewrmr0uvj0zelhkcwmsy0uvxz2fhm9eu9rl3ukxvgzy2v9f";
 const TRANSACTIONS_TABLE: &str = "transactions";
 const BLOCKS_TABLE: &str = "blocks";
+const RECEIVED_NOTES_TABLE: &str = "received_notes";
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct ZcoinConsensusParams {
@@ -209,10 +213,13 @@ pub struct ZOutput {
 struct ZCoinSqlTxHistoryItem {
     tx_hash: Vec<u8>,
     internal_id: i64,
-    height: i64,
-    timestamp: i64,
+    /// `None` if the transaction is not mined yet.
+    height: Option<i64>,
+    /// The block time, `None` if the transaction is not mined yet.
+    timestamp: Option<i64>,
     received_amount: i64,
     spent_amount: i64,
+    received_notes: Vec<ZCoinSqlReceivedNote>,
 }
 
 impl ZCoinSqlTxHistoryItem {
@@ -226,6 +233,26 @@ impl ZCoinSqlTxHistoryItem {
             timestamp: row.get(3)?,
             received_amount: row.get(4)?,
             spent_amount: row.get(5)?,
+            received_notes: Vec::new(),
+        })
+    }
+}
+
+struct ZCoinSqlReceivedNote {
+    output_index: u32,
+    value: i64,
+    is_change: bool,
+    /// The memo is set only after the full transaction is decrypted and stored by the history loop
+    memo: Option<Vec<u8>>,
+}
+
+impl ZCoinSqlReceivedNote {
+    fn try_from_sql_row(row: &Row<'_>) -> Result<Self, SqlError> {
+        Ok(ZCoinSqlReceivedNote {
+            output_index: row.get(0)?,
+            value: row.get(1)?,
+            is_change: row.get(2)?,
+            memo: row.get(3)?,
         })
     }
 }
@@ -260,6 +287,23 @@ pub struct ZcoinTxDetails {
     coin: String,
     /// Internal MM2 id used for internal transaction identification, for some coins it might be equal to transaction hash
     internal_id: i64,
+    /// Shielded notes received by "my" address, including the change
+    received_notes: Vec<ZcoinNoteDetails>,
+    /// Shielded notes sent from "my" address, including the change
+    sent_notes: Vec<ZcoinNoteDetails>,
+}
+
+#[derive(Serialize)]
+pub struct ZcoinNoteDetails {
+    /// Index of the shielded output within the transaction
+    output_index: u32,
+    /// The recipient shielded address
+    address: String,
+    amount: BigDecimal,
+    /// Whether the note is the change returned to "my" address
+    is_change: bool,
+    /// The text memo or the hex-encoded (0x-prefixed) arbitrary memo data, if any
+    memo: Option<String>,
 }
 
 impl ZCoin {
@@ -509,11 +553,27 @@ impl ZCoin {
                 .sql()
                 .expect("valid query");
 
-            let sql_items = conn
+            let mut sql_items = conn
                 .prepare(&sql)?
                 .query_map(NO_PARAMS, ZCoinSqlTxHistoryItem::try_from_sql_row)?
                 .collect::<Result<Vec<_>, _>>()?;
 
+            let notes_sql = SqlBuilder::select_from(RECEIVED_NOTES_TABLE)
+                .field("output_index")
+                .field("value")
+                .field("is_change")
+                .field("memo")
+                .and_where("tx = ?1")
+                .order_by("output_index", false)
+                .sql()
+                .expect("valid SQL");
+            let mut notes_stmt = conn.prepare(&notes_sql)?;
+            for item in sql_items.iter_mut() {
+                item.received_notes = notes_stmt
+                    .query_map([item.internal_id], ZCoinSqlReceivedNote::try_from_sql_row)?
+                    .collect::<Result<_, _>>()?;
+            }
+
             Ok(SqlTxHistoryRes {
                 transactions: sql_items,
                 total_tx_count,
@@ -538,6 +598,54 @@ impl ZCoin {
             .map_to_mm(|e| UtxoRpcError::InvalidResponse(e.to_string()))
     }
 
+    /// Decrypts the transactions that were imported to the light wallet DB from the compact blocks only
+    /// (the full transaction data is not stored yet) and stores their memos and sent notes.
+    /// Reports the number of the transactions left by the history sync state.
+    async fn store_decrypted_txs(&self) -> Result<(), String> {
+        let wallet_db = self.z_fields.light_wallet_db.clone();
+        let tx_hashes: HashSet<H256Json> = try_s!(
+            async_blocking(move || {
+                let db_guard = wallet_db.lock();
+                let sql = SqlBuilder::select_from(TRANSACTIONS_TABLE)
+                    .field("txid")
+                    .and_where_is_null("raw")
+                    .sql()
+                    .expect("valid SQL");
+                let mut stmt = db_guard.sql_conn().prepare(&sql)?;
+                let hashes = stmt
+                    .query_map(NO_PARAMS, |row| {
+                        let mut tx_hash: Vec<u8> = row.get(0)?;
+                        tx_hash.reverse();
+                        Ok(H256Json::from(tx_hash.as_slice()))
+                    })?
+                    .collect::<Result<_, SqlError>>();
+                hashes
+            })
+            .await
+        );
+        if tx_hashes.is_empty() {
+            return Ok(());
+        }
+        *self.utxo_arc.history_sync_state.lock().unwrap() =
+            HistorySyncState::InProgress(json!({ "transactions_left": tx_hashes.len() }));
+
+        let transactions = try_s!(self.z_transactions_from_cache_or_rpc(tx_hashes).await);
+        let wallet_db = self.z_fields.light_wallet_db.clone();
+        let consensus_params = self.consensus_params();
+        try_s!(
+            async_blocking(move || {
+                let db_guard = wallet_db.lock();
+                let mut wallet_ops = db_guard.get_update_ops().expect("get_update_ops always returns Ok");
+                for tx in transactions.values() {
+                    decrypt_and_store_transaction(&consensus_params, &mut wallet_ops, tx)?;
+                }
+                Ok::<_, ZcashClientError>(())
+            })
+            .await
+        );
+        Ok(())
+    }
+
     fn tx_details_from_sql_item(
         &self,
         sql_item: ZCoinSqlTxHistoryItem,
@@ -547,10 +655,10 @@ impl ZCoin {
     ) -> Result<ZcoinTxDetails, MmError<NoInfoAboutTx>> {
         let mut from = HashSet::new();
 
-        let mut confirmations = current_block as i64 - sql_item.height + 1;
-        if confirmations < 0 {
-            confirmations = 0;
-        }
+        let confirmations = match sql_item.height {
+            Some(height) => (current_block as i64 - height + 1).max(0),
+            None => 0,
+        };
 
         let mut transparent_input_amount = Amount::zero();
         let hash = H256Json::from(sql_item.tx_hash.as_slice());
@@ -589,25 +697,41 @@ impl ZCoin {
             to.insert(self.my_z_address_encoded());
         }
 
-        for z_out in z_tx.shielded_outputs.iter() {
-            if let Some((_, address, _)) = try_sapling_output_recovery(
+        let change_indexes: HashSet<_> = sql_item
+            .received_notes
+            .iter()
+            .filter(|note| note.is_change)
+            .map(|note| note.output_index)
+            .collect();
+        // The note plaintext rules depend on the height the transaction is mined at,
+        // an unmined transaction is expected to be mined in the next block.
+        let tx_height = match sql_item.height {
+            Some(height) => BlockHeight::from_u32(height as u32),
+            None => BlockHeight::from_u32(current_block as u32 + 1),
+        };
+        let mut sent_notes = Vec::new();
+        for (output_index, z_out) in z_tx.shielded_outputs.iter().enumerate() {
+            if let Some((note, address, memo)) = try_sapling_output_recovery(
                 self.consensus_params_ref(),
-                BlockHeight::from_u32(current_block as u32),
+                tx_height,
                 &self.z_fields.evk.fvk.ovk,
                 z_out,
             ) {
-                to.insert(encode_payment_address(
-                    self.consensus_params_ref().hrp_sapling_payment_address(),
-                    &address,
-                ));
+                let address =
+                    encode_payment_address(self.consensus_params_ref().hrp_sapling_payment_address(), &address);
+                to.insert(address.clone());
+                sent_notes.push(ZcoinNoteDetails {
+                    output_index: output_index as u32,
+                    address,
+                    amount: big_decimal_from_sat_unsigned(note.value, self.decimals()),
+                    is_change: change_indexes.contains(&(output_index as u32)),
+                    memo: memo_to_string(&memo),
+                });
             }
 
-            if let Some((_, address, _)) = try_sapling_output_recovery(
-                self.consensus_params_ref(),
-                BlockHeight::from_u32(current_block as u32),
-                &DEX_FEE_OVK,
-                z_out,
-            ) {
+            if let Some((_, address, _)) =
+                try_sapling_output_recovery(self.consensus_params_ref(), tx_height, &DEX_FEE_OVK, z_out)
+            {
                 to.insert(encode_payment_address(
                     self.consensus_params_ref().hrp_sapling_payment_address(),
                     &address,
@@ -615,6 +739,21 @@ impl ZCoin {
             }
         }
 
+        let received_notes = sql_item
+            .received_notes
+            .into_iter()
+            .map(|note| ZcoinNoteDetails {
+                output_index: note.output_index,
+                address: self.my_z_address_encoded(),
+                amount: big_decimal_from_sat(note.value, self.decimals()),
+                is_change: note.is_change,
+                memo: note
+                    .memo
+                    .and_then(|bytes| MemoBytes::from_bytes(&bytes).ok())
+                    .and_then(|memo| memo_to_string(&memo)),
+            })
+            .collect();
+
         let spent_by_me = big_decimal_from_sat(sql_item.spent_amount, self.decimals());
         let received_by_me = big_decimal_from_sat(sql_item.received_amount, self.decimals());
         Ok(ZcoinTxDetails {
@@ -624,12 +763,14 @@ impl ZCoin {
             my_balance_change: &received_by_me - &spent_by_me,
             spent_by_me,
             received_by_me,
-            block_height: sql_item.height,
+            block_height: sql_item.height.unwrap_or(0),
             confirmations,
-            timestamp: sql_item.timestamp,
+            timestamp: sql_item.timestamp.unwrap_or(0),
             transaction_fee: big_decimal_from_sat(fee_amount.into(), self.decimals()),
             coin: self.ticker().into(),
             internal_id: sql_item.internal_id,
+            received_notes,
+            sent_notes,
         })
     }
 
@@ -673,8 +814,9 @@ impl ZCoin {
             coin: self.ticker().into(),
             current_block,
             transactions,
-            // Zcoin is activated only after the state is synced
-            sync_status: HistorySyncState::Finished,
+            // The transactions are listed once the blocks are scanned,
+            // but their memos and sent notes are stored by the history loop.
+            sync_status: self.history_sync_status(),
             limit: request.limit,
             skipped: sql_result.skipped,
             total: sql_result.total_tx_count as usize,
@@ -698,11 +840,14 @@ pub enum ZcoinRpcMode {
     },
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, Serialize)]
 pub struct ZcoinActivationParams {
     pub mode: ZcoinRpcMode,
     pub required_confirmations: Option<u64>,
     pub requires_notarization: Option<bool>,
+    /// Runs the history loop that stores the memos and the sent notes of the wallet transactions.
+    #[serde(default)]
+    pub tx_history: bool,
 }
 
 pub async fn z_coin_from_conf_and_params(
@@ -792,6 +937,8 @@ impl<'a> UtxoCoinWithIguanaPrivKeyBuilder for ZCoinBuilder<'a> {
                         .or_mm_err(|| ZCoinBuildError::EmptyLightwalletdUris)?,
                 )?;
 
+                let proxy = coin_proxy(self.ctx, self.conf).map_to_mm(UtxoCoinBuildError::InvalidProxyConfig)?;
+
                 init_light_client(
                     uri,
                     cache_db_path,
@@ -799,6 +946,7 @@ impl<'a> UtxoCoinWithIguanaPrivKeyBuilder for ZCoinBuilder<'a> {
                     self.protocol_info.consensus_params.clone(),
                     self.protocol_info.check_point_block,
                     evk,
+                    proxy,
                 )
                 .await?
             },
@@ -846,7 +994,7 @@ impl<'a> ZCoinBuilder<'a> {
         let utxo_params = UtxoActivationParams {
             mode: utxo_mode,
             utxo_merge_params: None,
-            tx_history: false,
+            tx_history: z_coin_params.tx_history,
             required_confirmations: z_coin_params.required_confirmations,
             requires_notarization: z_coin_params.requires_notarization,
             address_format: None,
@@ -1096,11 +1244,12 @@ impl SwapOps for ZCoin {
     ) -> TransactionFut {
         let tx = try_tx_fus!(ZTransaction::read(taker_payment_tx));
         let key_pair = self.derive_htlc_key_pair(swap_unique_data);
-        let redeem_script = payment_script(
+        let redeem_script = payment_script_for_secret(
             time_lock,
-            &*dhash160(secret),
+            secret,
             &try_tx_fus!(Public::from_slice(taker_pub)),
             key_pair.public(),
+            &tx.vout[0].script_pubkey.0,
         );
         let script_data = ScriptBuilder::default()
             .push_data(secret)
@@ -1134,11 +1283,12 @@ impl SwapOps for ZCoin {
     ) -> TransactionFut {
         let tx = try_tx_fus!(ZTransaction::read(maker_payment_tx));
         let key_pair = self.derive_htlc_key_pair(swap_unique_data);
-        let redeem_script = payment_script(
+        let redeem_script = payment_script_for_secret(
             time_lock,
-            &*dhash160(secret),
+            secret,
             &try_tx_fus!(Public::from_slice(maker_pub)),
             key_pair.public(),
+            &tx.vout[0].script_pubkey.0,
         );
         let script_data = ScriptBuilder::default()
             .push_data(secret)
@@ -1407,12 +1557,11 @@ impl MmCoin for ZCoin {
         }
     }
 
-    fn process_history_loop(&self, _ctx: MmArc) -> Box<dyn Future<Item = (), Error = ()> + Send> {
-        log::warn!("process_history_loop is not implemented for ZCoin yet!");
-        Box::new(futures01::future::err(()))
+    fn process_history_loop(&self, ctx: MmArc) -> Box<dyn Future<Item = (), Error = ()> + Send> {
+        Box::new(z_coin_history_loop(self.clone(), ctx).unit_error().boxed().compat())
     }
 
-    fn history_sync_status(&self) -> HistorySyncState { HistorySyncState::NotEnabled }
+    fn history_sync_status(&self) -> HistorySyncState { utxo_common::history_sync_status(&self.utxo_arc) }
 
     fn get_trade_fee(&self) -> Box<dyn Future<Item = TradeFee, Error = String> + Send> {
         utxo_common::get_trade_fee(self.clone())
@@ -1655,7 +1804,7 @@ impl InitWithdrawCoin for ZCoin {
             to_addr,
             amount: Amount::from_u64(satoshi)
                 .map_to_mm(|_| NumConversError(format!("Failed to get ZCash amount from {}", amount)))?,
-            // TODO add optional viewing_key and memo fields to the WithdrawRequest
+            // TODO add optional viewing_key field to the WithdrawRequest
             viewing_key: Some(self.z_fields.evk.fvk.ovk),
             memo,
         };
@@ -1688,11 +1837,59 @@ impl InitWithdrawCoin for ZCoin {
             coin: self.ticker().to_owned(),
             internal_id: tx_hash.into(),
             kmd_rewards: None,
+            spv_verified: None,
             transaction_type: Default::default(),
         })
     }
 }
 
+/// The shielded history is built from the light wallet DB that is filled by the blocks sync loop.
+/// Compact blocks contain neither memos nor the outputs sent to other addresses,
+/// so this loop fetches the full transactions and stores the decrypted data to the light wallet DB.
+async fn z_coin_history_loop(coin: ZCoin, ctx: MmArc) {
+    let coins_ctx = match CoinsContext::from_ctx(&ctx) {
+        Ok(coins_ctx) => coins_ctx,
+        Err(e) => {
+            log::error!("Error {} on getting CoinsContext", e);
+            return;
+        },
+    };
+
+    loop {
+        if ctx.is_stopping() {
+            break;
+        }
+        if !coins_ctx.coins.lock().await.contains_key(coin.ticker()) {
+            log::info!("Tx history loop stopped for {}", coin.ticker());
+            break;
+        }
+
+        let sync_state = match coin.store_decrypted_txs().await {
+            Ok(()) => HistorySyncState::Finished,
+            Err(e) => {
+                log::error!("Error {} on storing the decrypted {} transactions", e, coin.ticker());
+                HistorySyncState::Error(json!({ "error": e }))
+            },
+        };
+        *coin.utxo_arc.history_sync_state.lock().unwrap() = sync_state;
+        Timer::sleep(30.).await;
+    }
+}
+
+/// Decodes the memo into a string: text memos are returned as is,
+/// arbitrary data is returned hex-encoded with the "0x" prefix, so it can be passed back to `interpret_memo_string`.
+fn memo_to_string(memo_bytes: &MemoBytes) -> Option<String> {
+    match Memo::try_from(memo_bytes) {
+        Ok(Memo::Empty) => None,
+        Ok(Memo::Text(text)) => Some((*text).to_owned()),
+        _ => {
+            let bytes = memo_bytes.as_slice();
+            let len = bytes.iter().rposition(|byte| *byte != 0).map_or(0, |pos| pos + 1);
+            Some(format!("0x{}", hex::encode(&bytes[..len])))
+        },
+    }
+}
+
 /// Interpret a string or hex-encoded memo, and return a Memo object.
 /// Inspired by https://github.com/adityapk00/zecwallet-light-cli/blob/v1.7.20/lib/src/lightwallet/utils.rs#L23
 pub fn interpret_memo_string(memo_str: &str) -> MmResult<MemoBytes, WithdrawError> {
@@ -1759,3 +1956,14 @@ fn test_interpret_memo_string() {
     let expected = MemoBytes::from_bytes(&hex::decode("68656c6c6f207a63617368").unwrap()).unwrap();
     assert_eq!(actual, expected);
 }
+
+#[test]
+fn test_memo_to_string() {
+    let memo = interpret_memo_string("A custom memo").unwrap();
+    assert_eq!(memo_to_string(&memo), Some("A custom memo".to_owned()));
+
+    let memo = interpret_memo_string("0xff0102").unwrap();
+    assert_eq!(memo_to_string(&memo), Some("0xff0102".to_owned()));
+
+    assert_eq!(memo_to_string(&MemoBytes::empty()), None);
+}
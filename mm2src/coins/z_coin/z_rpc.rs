@@ -11,6 +11,7 @@ use futures::lock::{Mutex as AsyncMutex, MutexGuard as AsyncMutexGuard};
 use futures::StreamExt;
 use http::Uri;
 use mm2_err_handle::prelude::*;
+use mm2_net::socks5::{Socks5Connector, Socks5Proxy};
 use parking_lot::Mutex;
 use prost::Message;
 use protobuf::Message as ProtobufMessage;
@@ -150,6 +151,7 @@ pub(super) async fn init_light_client(
     consensus_params: ZcoinConsensusParams,
     check_point_block: Option<CheckPointBlockInfo>,
     evk: ExtendedFullViewingKey,
+    proxy: Option<Socks5Proxy>,
 ) -> Result<(AsyncMutex<SaplingSyncConnector>, WalletDbShared), MmError<ZcoinLightClientInitError>> {
     let blocks_db =
         async_blocking(|| BlockDb::for_path(cache_db_path).map_to_mm(ZcoinLightClientInitError::BlocksDbInitFailure))
@@ -179,12 +181,14 @@ pub(super) async fn init_light_client(
     })
     .await?;
 
-    let tonic_channel = Channel::builder(lightwalletd_url)
+    let endpoint = Channel::builder(lightwalletd_url)
         .tls_config(ClientTlsConfig::new())
-        .map_to_mm(ZcoinLightClientInitError::TlsConfigFailure)?
-        .connect()
-        .await
-        .map_to_mm(ZcoinLightClientInitError::ConnectionFailure)?;
+        .map_to_mm(ZcoinLightClientInitError::TlsConfigFailure)?;
+    let tonic_channel = match proxy {
+        Some(proxy) => endpoint.connect_with_connector(Socks5Connector::new(proxy)).await,
+        None => endpoint.connect().await,
+    }
+    .map_to_mm(ZcoinLightClientInitError::ConnectionFailure)?;
     let grpc_client = CompactTxStreamerClient::new(tonic_channel);
 
     let (sync_status_notifier, sync_watcher) = channel(1);
@@ -4,9 +4,9 @@ use crate::slp_token_activation::SlpActivationRequest;
 use async_trait::async_trait;
 use coins::my_tx_history_v2::TxHistoryStorage;
 use coins::utxo::bch::{bch_coin_from_conf_and_params, BchActivationRequest, BchCoin, CashAddrPrefix};
-use coins::utxo::bch_and_slp_tx_history::bch_and_slp_history_loop;
 use coins::utxo::rpc_clients::UtxoRpcError;
 use coins::utxo::slp::{SlpProtocolConf, SlpToken};
+use coins::utxo::utxo_tx_history_v2::utxo_history_loop;
 use coins::utxo::UtxoCommonOps;
 use coins::{CoinBalance, CoinProtocol, MarketCoinOps, MmCoin, PrivKeyNotAllowed, UnexpectedDerivationMethod};
 use common::executor::spawn;
@@ -275,15 +275,10 @@ impl PlatformWithTokensActivationOps for BchCoin {
         initial_balance: BigDecimal,
     ) -> AbortHandle {
         let ticker = self.ticker().to_owned();
-        let (fut, abort_handle) = abortable(bch_and_slp_history_loop(
-            self.clone(),
-            storage,
-            metrics,
-            initial_balance,
-        ));
+        let (fut, abort_handle) = abortable(utxo_history_loop(self.clone(), storage, metrics, initial_balance));
         spawn(async move {
             if let Err(e) = fut.await {
-                info!("bch_and_slp_history_loop stopped for {}, reason {}", ticker, e);
+                info!("utxo_history_loop stopped for {}, reason {}", ticker, e);
             }
         });
         abort_handle
@@ -2,7 +2,7 @@ use crate::utxo_activation::{QtumTaskManagerShared, UtxoStandardTaskManagerShare
 #[cfg(not(target_arch = "wasm32"))]
 use crate::z_coin_activation::ZcoinTaskManagerShared;
 use mm2_core::mm_ctx::{from_ctx, MmArc};
-use rpc_task::RpcTaskManager;
+use rpc_task::{RpcTaskInfo, RpcTaskManager};
 use std::sync::Arc;
 
 pub struct CoinsActivationContext {
@@ -24,4 +24,29 @@ impl CoinsActivationContext {
             })
         })
     }
+
+    /// Returns the short descriptions of all coin activation RPC tasks.
+    pub fn rpc_tasks_info(&self) -> Result<Vec<RpcTaskInfo>, String> {
+        let mut tasks = Vec::new();
+        tasks.extend(
+            self.init_utxo_standard_task_manager
+                .lock()
+                .map_err(|e| e.to_string())?
+                .tasks_info("init_utxo"),
+        );
+        tasks.extend(
+            self.init_qtum_task_manager
+                .lock()
+                .map_err(|e| e.to_string())?
+                .tasks_info("init_qtum"),
+        );
+        #[cfg(not(target_arch = "wasm32"))]
+        tasks.extend(
+            self.init_z_coin_task_manager
+                .lock()
+                .map_err(|e| e.to_string())?
+                .tasks_info("init_z_coin"),
+        );
+        Ok(tasks)
+    }
 }
@@ -10,11 +10,17 @@ mod solana_with_tokens_activation;
 #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
 mod spl_token_activation;
 mod standalone_coin;
+mod tendermint_token_activation;
+mod tendermint_with_assets_activation;
 mod token;
 mod utxo_activation;
 #[cfg(not(target_arch = "wasm32"))] mod z_coin_activation;
 
+pub use context::CoinsActivationContext;
 pub use l2::enable_l2;
 pub use platform_coin_with_tokens::enable_platform_coin_with_tokens;
-pub use standalone_coin::{init_standalone_coin, init_standalone_coin_status, init_standalone_coin_user_action};
+pub use standalone_coin::{init_standalone_coin, init_standalone_coin_cancel, init_standalone_coin_status,
+                          init_standalone_coin_user_action};
 pub use token::enable_token;
+#[cfg(not(target_arch = "wasm32"))]
+pub use z_coin_activation::resume_z_coin_activations;
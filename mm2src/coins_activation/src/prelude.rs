@@ -22,7 +22,7 @@ impl TxHistory for UtxoActivationParams {
 
 #[cfg(not(target_arch = "wasm32"))]
 impl TxHistory for ZcoinActivationParams {
-    fn tx_history(&self) -> bool { false }
+    fn tx_history(&self) -> bool { self.tx_history }
 }
 
 #[derive(Clone, Debug, Serialize)]
@@ -1,6 +1,7 @@
 use crate::context::CoinsActivationContext;
 use crate::prelude::*;
-use crate::standalone_coin::init_standalone_coin_error::{InitStandaloneCoinError, InitStandaloneCoinStatusError,
+use crate::standalone_coin::init_standalone_coin_error::{InitStandaloneCoinCancelError, InitStandaloneCoinError,
+                                                         InitStandaloneCoinStatusError,
                                                          InitStandaloneCoinUserActionError};
 use async_trait::async_trait;
 use coins::{lp_coinfind, lp_register_coin, MmCoinEnum, RegisterCoinError, RegisterCoinParams};
@@ -8,7 +9,7 @@ use common::{log, SuccessResponse};
 use crypto::trezor::trezor_rpc_task::RpcTaskHandle;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
-use rpc_task::rpc_common::{InitRpcTaskResponse, RpcTaskStatusRequest, RpcTaskUserActionRequest};
+use rpc_task::rpc_common::{CancelRpcTaskRequest, InitRpcTaskResponse, RpcTaskStatusRequest, RpcTaskUserActionRequest};
 use rpc_task::{RpcTask, RpcTaskManager, RpcTaskManagerShared, RpcTaskStatus, RpcTaskTypes};
 use serde_derive::Deserialize;
 use serde_json::Value as Json;
@@ -16,13 +17,14 @@ use serde_json::Value as Json;
 pub type InitStandaloneCoinResponse = InitRpcTaskResponse;
 pub type InitStandaloneCoinStatusRequest = RpcTaskStatusRequest;
 pub type InitStandaloneCoinUserActionRequest<UserAction> = RpcTaskUserActionRequest<UserAction>;
+pub type InitStandaloneCoinCancelRequest = CancelRpcTaskRequest;
 pub type InitStandaloneCoinTaskManagerShared<Standalone> = RpcTaskManagerShared<InitStandaloneCoinTask<Standalone>>;
 pub type InitStandaloneCoinTaskHandle<Standalone> = RpcTaskHandle<InitStandaloneCoinTask<Standalone>>;
 
 #[derive(Debug, Deserialize)]
 pub struct InitStandaloneCoinReq<T> {
-    ticker: String,
-    activation_params: T,
+    pub(crate) ticker: String,
+    pub(crate) activation_params: T,
 }
 
 #[async_trait]
@@ -132,6 +134,18 @@ pub async fn init_standalone_coin_user_action<Standalone: InitStandaloneCoinActi
     Ok(SuccessResponse::new())
 }
 
+pub async fn init_standalone_coin_cancel<Standalone: InitStandaloneCoinActivationOps>(
+    ctx: MmArc,
+    req: InitStandaloneCoinCancelRequest,
+) -> MmResult<SuccessResponse, InitStandaloneCoinCancelError> {
+    let coins_act_ctx = CoinsActivationContext::from_ctx(&ctx).map_to_mm(InitStandaloneCoinCancelError::Internal)?;
+    let mut task_manager = Standalone::rpc_task_manager(&coins_act_ctx)
+        .lock()
+        .map_to_mm(|poison| InitStandaloneCoinCancelError::Internal(poison.to_string()))?;
+    task_manager.cancel_task(req.task_id)?;
+    Ok(SuccessResponse::new())
+}
+
 pub struct InitStandaloneCoinTask<Standalone: InitStandaloneCoinActivationOps> {
     ctx: MmArc,
     request: InitStandaloneCoinReq<Standalone::ActivationRequest>,
@@ -1,8 +1,8 @@
 mod init_standalone_coin;
 mod init_standalone_coin_error;
 
-pub use init_standalone_coin::{init_standalone_coin, init_standalone_coin_status, init_standalone_coin_user_action,
-                               InitStandaloneCoinActivationOps, InitStandaloneCoinInitialStatus,
-                               InitStandaloneCoinTask, InitStandaloneCoinTaskHandle,
-                               InitStandaloneCoinTaskManagerShared};
+pub use init_standalone_coin::{init_standalone_coin, init_standalone_coin_cancel, init_standalone_coin_status,
+                               init_standalone_coin_user_action, InitStandaloneCoinActivationOps,
+                               InitStandaloneCoinInitialStatus, InitStandaloneCoinReq, InitStandaloneCoinTask,
+                               InitStandaloneCoinTaskHandle, InitStandaloneCoinTaskManagerShared};
 pub use init_standalone_coin_error::InitStandaloneCoinError;
@@ -0,0 +1,101 @@
+use crate::platform_coin_with_tokens::RegisterTokenInfo;
+use crate::prelude::{TryFromCoinProtocol, TryPlatformCoinFromMmCoinEnum};
+use crate::token::{EnableTokenError, TokenActivationOps, TokenProtocolParams};
+use async_trait::async_trait;
+use coins::tendermint::{TendermintCoin, TendermintToken, TendermintTokenProtocolInfo};
+use coins::{BalanceError, CoinBalance, CoinProtocol, MarketCoinOps, MmCoinEnum};
+use common::Future01CompatExt;
+use mm2_err_handle::prelude::*;
+use serde_derive::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+impl TryPlatformCoinFromMmCoinEnum for TendermintCoin {
+    fn try_from_mm_coin(coin: MmCoinEnum) -> Option<Self>
+    where
+        Self: Sized,
+    {
+        match coin {
+            MmCoinEnum::Tendermint(coin) => Some(coin),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TendermintTokenActivationParams {}
+
+impl TryFromCoinProtocol for TendermintTokenProtocolInfo {
+    fn try_from_coin_protocol(proto: CoinProtocol) -> Result<Self, MmError<CoinProtocol>>
+    where
+        Self: Sized,
+    {
+        match proto {
+            CoinProtocol::TENDERMINTTOKEN(info) => Ok(info),
+            proto => MmError::err(proto),
+        }
+    }
+}
+
+impl TokenProtocolParams for TendermintTokenProtocolInfo {
+    fn platform_coin_ticker(&self) -> &str { &self.platform }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TendermintTokenInitResult {
+    balances: HashMap<String, CoinBalance>,
+    platform_coin: String,
+}
+
+#[derive(Debug)]
+pub enum TendermintTokenInitError {
+    GetBalanceError(BalanceError),
+    MyAddressError(String),
+}
+
+impl From<TendermintTokenInitError> for EnableTokenError {
+    fn from(err: TendermintTokenInitError) -> Self {
+        match err {
+            TendermintTokenInitError::GetBalanceError(rpc_err) => rpc_err.into(),
+            TendermintTokenInitError::MyAddressError(e) => EnableTokenError::Internal(e),
+        }
+    }
+}
+
+#[async_trait]
+impl TokenActivationOps for TendermintToken {
+    type PlatformCoin = TendermintCoin;
+    type ActivationParams = TendermintTokenActivationParams;
+    type ProtocolInfo = TendermintTokenProtocolInfo;
+    type ActivationResult = TendermintTokenInitResult;
+    type ActivationError = TendermintTokenInitError;
+
+    async fn enable_token(
+        ticker: String,
+        platform_coin: Self::PlatformCoin,
+        _activation_params: Self::ActivationParams,
+        protocol_conf: Self::ProtocolInfo,
+    ) -> Result<(Self, Self::ActivationResult), MmError<Self::ActivationError>> {
+        let token = TendermintToken::new(
+            ticker,
+            platform_coin.clone(),
+            protocol_conf.decimals,
+            protocol_conf.denom,
+        );
+        let balance = token
+            .my_balance()
+            .compat()
+            .await
+            .map_err(|e| TendermintTokenInitError::GetBalanceError(e.into_inner()))?;
+        let my_address = token.my_address().map_to_mm(TendermintTokenInitError::MyAddressError)?;
+        // The platform coin needs the token info to include the token transfers into the history.
+        platform_coin.register_token_info(&token);
+
+        let mut balances = HashMap::new();
+        balances.insert(my_address, balance);
+        let init_result = TendermintTokenInitResult {
+            balances,
+            platform_coin: platform_coin.ticker().to_owned(),
+        };
+        Ok((token, init_result))
+    }
+}
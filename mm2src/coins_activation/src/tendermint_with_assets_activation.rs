@@ -0,0 +1,241 @@
+use crate::platform_coin_with_tokens::{EnablePlatformCoinWithTokensError, GetPlatformBalance,
+                                       PlatformWithTokensActivationOps, RegisterTokenInfo, TokenActivationParams,
+                                       TokenActivationRequest, TokenAsMmCoinInitializer, TokenInitializer, TokenOf};
+use crate::prelude::*;
+use crate::tendermint_token_activation::TendermintTokenActivationParams;
+use async_trait::async_trait;
+use coins::my_tx_history_v2::TxHistoryStorage;
+use coins::tendermint::rpc::TendermintRpcError;
+use coins::tendermint::{tendermint_coin_from_conf_and_params, tendermint_history_loop, ActivatedTokenInfo,
+                        TendermintActivationParams, TendermintCoin, TendermintCoinRpcError, TendermintProtocolInfo,
+                        TendermintToken, TendermintTokenProtocolInfo};
+use coins::{BalanceError, CoinBalance, CoinProtocol, MarketCoinOps, MmCoin};
+use common::executor::spawn;
+use common::log::info;
+use common::mm_metrics::MetricsArc;
+use common::Future01CompatExt;
+use futures::future::{abortable, AbortHandle};
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::BigDecimal;
+use serde_derive::{Deserialize, Serialize};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+
+pub struct TendermintTokenInitializer {
+    platform_coin: TendermintCoin,
+}
+
+impl TokenOf for TendermintToken {
+    type PlatformCoin = TendermintCoin;
+}
+
+#[async_trait]
+impl TokenInitializer for TendermintTokenInitializer {
+    type Token = TendermintToken;
+    type TokenActivationRequest = TendermintTokenActivationParams;
+    type TokenProtocol = TendermintTokenProtocolInfo;
+    type InitTokensError = std::convert::Infallible;
+
+    fn tokens_requests_from_platform_request(
+        platform_params: &TendermintActivationRequest,
+    ) -> Vec<TokenActivationRequest<Self::TokenActivationRequest>> {
+        platform_params.tokens_params.clone()
+    }
+
+    async fn enable_tokens(
+        &self,
+        activation_params: Vec<TokenActivationParams<TendermintTokenActivationParams, TendermintTokenProtocolInfo>>,
+    ) -> Result<Vec<TendermintToken>, MmError<std::convert::Infallible>> {
+        let tokens = activation_params
+            .into_iter()
+            .map(|params| {
+                TendermintToken::new(
+                    params.ticker,
+                    self.platform_coin.clone(),
+                    params.protocol.decimals,
+                    params.protocol.denom,
+                )
+            })
+            .collect();
+        Ok(tokens)
+    }
+
+    fn platform_coin(&self) -> &TendermintCoin { &self.platform_coin }
+}
+
+impl RegisterTokenInfo<TendermintToken> for TendermintCoin {
+    fn register_token_info(&self, token: &TendermintToken) {
+        self.add_activated_token_info(token.denom().to_owned(), ActivatedTokenInfo {
+            ticker: token.ticker().to_owned(),
+            decimals: token.decimals(),
+        })
+    }
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct TendermintActivationRequest {
+    #[serde(flatten)]
+    platform_request: TendermintActivationParams,
+    tokens_params: Vec<TokenActivationRequest<TendermintTokenActivationParams>>,
+    #[serde(default)]
+    tx_history: bool,
+}
+
+impl TxHistory for TendermintActivationRequest {
+    fn tx_history(&self) -> bool { self.tx_history }
+}
+
+#[derive(Debug, Serialize)]
+pub struct TendermintActivationResult {
+    ticker: String,
+    address: String,
+    current_block: u64,
+    balance: CoinBalance,
+    tokens_balances: HashMap<String, CoinBalance>,
+}
+
+impl GetPlatformBalance for TendermintActivationResult {
+    fn get_platform_balance(&self) -> BigDecimal { self.balance.spendable.clone() }
+}
+
+impl CurrentBlock for TendermintActivationResult {
+    fn current_block(&self) -> u64 { self.current_block }
+}
+
+#[derive(Debug)]
+pub enum TendermintActivationError {
+    PlatformCoinCreationError { ticker: String, error: String },
+    UnableToRetrieveMyAddress(String),
+    GetBalanceError(BalanceError),
+    Transport(String),
+    Internal(String),
+}
+
+impl From<TendermintActivationError> for EnablePlatformCoinWithTokensError {
+    fn from(e: TendermintActivationError) -> Self {
+        match e {
+            TendermintActivationError::PlatformCoinCreationError { ticker, error } => {
+                EnablePlatformCoinWithTokensError::PlatformCoinCreationError { ticker, error }
+            },
+            TendermintActivationError::UnableToRetrieveMyAddress(e) => EnablePlatformCoinWithTokensError::Internal(e),
+            TendermintActivationError::GetBalanceError(e) => {
+                EnablePlatformCoinWithTokensError::Internal(format!("{:?}", e))
+            },
+            TendermintActivationError::Transport(e) => EnablePlatformCoinWithTokensError::Transport(e),
+            TendermintActivationError::Internal(e) => EnablePlatformCoinWithTokensError::Internal(e),
+        }
+    }
+}
+
+impl From<BalanceError> for TendermintActivationError {
+    fn from(e: BalanceError) -> Self { TendermintActivationError::GetBalanceError(e) }
+}
+
+impl From<TendermintCoinRpcError> for TendermintActivationError {
+    fn from(e: TendermintCoinRpcError) -> Self {
+        match e {
+            TendermintCoinRpcError::Rpc(TendermintRpcError::Transport(e)) => TendermintActivationError::Transport(e),
+            e => TendermintActivationError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl TryFromCoinProtocol for TendermintProtocolInfo {
+    fn try_from_coin_protocol(proto: CoinProtocol) -> Result<Self, MmError<CoinProtocol>>
+    where
+        Self: Sized,
+    {
+        match proto {
+            CoinProtocol::TENDERMINT(info) => Ok(info),
+            protocol => MmError::err(protocol),
+        }
+    }
+}
+
+#[async_trait]
+impl PlatformWithTokensActivationOps for TendermintCoin {
+    type ActivationRequest = TendermintActivationRequest;
+    type PlatformProtocolInfo = TendermintProtocolInfo;
+    type ActivationResult = TendermintActivationResult;
+    type ActivationError = TendermintActivationError;
+
+    async fn enable_platform_coin(
+        ctx: MmArc,
+        ticker: String,
+        platform_conf: Json,
+        activation_request: Self::ActivationRequest,
+        protocol_conf: Self::PlatformProtocolInfo,
+        priv_key: &[u8],
+    ) -> Result<Self, MmError<Self::ActivationError>> {
+        tendermint_coin_from_conf_and_params(
+            &ctx,
+            &ticker,
+            &platform_conf,
+            protocol_conf,
+            activation_request.platform_request,
+            priv_key,
+        )
+        .mm_err(|e| TendermintActivationError::PlatformCoinCreationError {
+            ticker,
+            error: e.to_string(),
+        })
+    }
+
+    fn token_initializers(
+        &self,
+    ) -> Vec<Box<dyn TokenAsMmCoinInitializer<PlatformCoin = Self, ActivationRequest = Self::ActivationRequest>>> {
+        vec![Box::new(TendermintTokenInitializer {
+            platform_coin: self.clone(),
+        })]
+    }
+
+    async fn get_activation_result(&self) -> Result<Self::ActivationResult, MmError<Self::ActivationError>> {
+        let address = self
+            .my_address()
+            .map_to_mm(TendermintActivationError::UnableToRetrieveMyAddress)?;
+        let current_block = self
+            .current_block()
+            .compat()
+            .await
+            .map_to_mm(TendermintActivationError::Internal)?;
+        let balance = self
+            .my_balance()
+            .compat()
+            .await
+            .map_err(|e| TendermintActivationError::GetBalanceError(e.into_inner()))?;
+
+        let mut tokens_balances = HashMap::new();
+        for (denom, info) in self.get_activated_tokens_info() {
+            let spendable = self.balance_for_denom(&denom, info.decimals).await?;
+            tokens_balances.insert(info.ticker, CoinBalance {
+                spendable,
+                unspendable: BigDecimal::from(0),
+            });
+        }
+
+        Ok(TendermintActivationResult {
+            ticker: self.ticker().to_owned(),
+            address,
+            current_block,
+            balance,
+            tokens_balances,
+        })
+    }
+
+    fn start_history_background_fetching(
+        &self,
+        metrics: MetricsArc,
+        storage: impl TxHistoryStorage + Send + 'static,
+        _initial_balance: BigDecimal,
+    ) -> AbortHandle {
+        let ticker = self.ticker().to_owned();
+        let (fut, abort_handle) = abortable(tendermint_history_loop(self.clone(), storage, metrics));
+        spawn(async move {
+            if let Err(e) = fut.await {
+                info!("tendermint_history_loop stopped for {}, reason {}", ticker, e);
+            }
+        });
+        abort_handle
+    }
+}
@@ -1,13 +1,14 @@
 use crate::context::CoinsActivationContext;
 use crate::prelude::*;
-use crate::standalone_coin::{InitStandaloneCoinActivationOps, InitStandaloneCoinError,
-                             InitStandaloneCoinInitialStatus, InitStandaloneCoinTaskHandle,
+use crate::standalone_coin::{init_standalone_coin, InitStandaloneCoinActivationOps, InitStandaloneCoinError,
+                             InitStandaloneCoinInitialStatus, InitStandaloneCoinReq, InitStandaloneCoinTaskHandle,
                              InitStandaloneCoinTaskManagerShared};
 use async_trait::async_trait;
 use coins::coin_balance::{EnableCoinBalance, IguanaWalletBalance};
 use coins::z_coin::{z_coin_from_conf_and_params, BlockchainScanStopped, SyncStatus, ZCoin, ZCoinBuildError,
                     ZcoinActivationParams, ZcoinProtocolInfo};
 use coins::{BalanceError, CoinProtocol, MarketCoinOps, RegisterCoinError};
+use common::log::{error, info, warn};
 use crypto::hw_rpc_task::{HwRpcTaskAwaitingStatus, HwRpcTaskUserAction};
 use crypto::CryptoInitError;
 use derive_more::Display;
@@ -17,9 +18,12 @@ use mm2_err_handle::prelude::*;
 use rpc_task::RpcTaskError;
 use ser_error_derive::SerializeErrorType;
 use serde_derive::Serialize;
-use serde_json::Value as Json;
+use serde_json::{self as json, Value as Json};
+use std::path::PathBuf;
 use std::time::Duration;
 
+const PENDING_ACTIVATION_SUFFIX: &str = "_pending_activation.json";
+
 pub type ZcoinTaskManagerShared = InitStandaloneCoinTaskManagerShared<ZCoin>;
 pub type ZcoinRpcTaskHandle = InitStandaloneCoinTaskHandle<ZCoin>;
 pub type ZcoinAwaitingStatus = HwRpcTaskAwaitingStatus;
@@ -179,6 +183,8 @@ impl InitStandaloneCoinActivationOps for ZCoin {
         protocol_info: ZcoinProtocolInfo,
         task_handle: &ZcoinRpcTaskHandle,
     ) -> MmResult<Self, ZcoinInitError> {
+        let _pending_activation = PendingActivationGuard::new(ctx.clone(), &ticker, activation_request);
+
         let secp_privkey = ctx.secp256k1_key_pair().private().secret;
         let coin = z_coin_from_conf_and_params(
             &ctx,
@@ -240,3 +246,229 @@ impl InitStandaloneCoinActivationOps for ZCoin {
         })
     }
 }
+
+#[derive(Serialize)]
+struct PendingActivation<'a> {
+    ticker: &'a str,
+    activation_params: &'a ZcoinActivationParams,
+}
+
+fn pending_activation_path(ctx: &MmArc, ticker: &str) -> PathBuf {
+    ctx.dbdir().join(format!("{}{}", ticker, PENDING_ACTIVATION_SUFFIX))
+}
+
+/// Keeps the activation request on the disk while the blocks are being scanned,
+/// so the activation can be resumed by [`resume_z_coin_activations`] if the node is stopped meanwhile.
+/// The request is removed once the activation is finished, failed or cancelled.
+struct PendingActivationGuard {
+    ctx: MmArc,
+    path: PathBuf,
+}
+
+impl PendingActivationGuard {
+    fn new(ctx: MmArc, ticker: &str, activation_params: &ZcoinActivationParams) -> PendingActivationGuard {
+        let path = pending_activation_path(&ctx, ticker);
+        let pending = PendingActivation {
+            ticker,
+            activation_params,
+        };
+        let write_res = json::to_vec(&pending)
+            .map_err(|e| e.to_string())
+            .and_then(|content| std::fs::write(&path, content).map_err(|e| e.to_string()));
+        if let Err(e) = write_res {
+            warn!(
+                "Couldn't save the pending {} activation to {}: {}",
+                ticker,
+                path.display(),
+                e
+            );
+        }
+        PendingActivationGuard { ctx, path }
+    }
+}
+
+impl Drop for PendingActivationGuard {
+    fn drop(&mut self) {
+        // The activation should be resumed on the next start.
+        if self.ctx.is_stopping() {
+            return;
+        }
+        if let Err(e) = std::fs::remove_file(&self.path) {
+            warn!("Couldn't remove the pending activation {}: {}", self.path.display(), e);
+        }
+    }
+}
+
+/// Spawns the Z-coin activation tasks that had not been finished before the node stopped.
+/// The blocks cache and the light wallet DB are kept on the disk, so the scanning continues from the last scanned block.
+/// The new task ids can be found using the `list_rpc_tasks` RPC.
+pub async fn resume_z_coin_activations(ctx: MmArc) {
+    let dir_entries = match std::fs::read_dir(ctx.dbdir()) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!("Error {} on reading the DB directory", e);
+            return;
+        },
+    };
+
+    for entry in dir_entries.flatten() {
+        let path = entry.path();
+        let is_pending_activation = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .map_or(false, |name| name.ends_with(PENDING_ACTIVATION_SUFFIX));
+        if !is_pending_activation {
+            continue;
+        }
+
+        let request: InitStandaloneCoinReq<ZcoinActivationParams> = match std::fs::read(&path)
+            .map_err(|e| e.to_string())
+            .and_then(|content| json::from_slice(&content).map_err(|e| e.to_string()))
+        {
+            Ok(request) => request,
+            Err(e) => {
+                error!("Error {} on loading the pending activation {}", e, path.display());
+                std::fs::remove_file(&path).ok();
+                continue;
+            },
+        };
+
+        let ticker = request.ticker.clone();
+        match init_standalone_coin::<ZCoin>(ctx.clone(), request).await {
+            Ok(response) => info!("Resumed {} activation, task id {}", ticker, response.task_id),
+            Err(e) => {
+                error!("Error {} on resuming {} activation", e, ticker);
+                std::fs::remove_file(&path).ok();
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use coins::z_coin::ZcoinRpcMode;
+    use common::{block_on, now_ms};
+    use crypto::CryptoCtx;
+    use mm2_core::mm_ctx::MmCtxBuilder;
+    use serde_json::json;
+
+    const PASSPHRASE: &str = "z coin activation passphrase";
+
+    fn ctx_for_test(dbdir: &str) -> MmArc {
+        let conf = json!({
+            "dbdir": dbdir,
+            "coins": [{
+                "coin": "ZOMBIE",
+                "asset": "ZOMBIE",
+                "txversion": 4,
+                "overwintered": 1,
+                "mm2": 1,
+                "protocol": {
+                    "type": "ZHTLC",
+                    "protocol_data": {
+                        "consensus_params": {
+                            "overwinter_activation_height": 0,
+                            "sapling_activation_height": 1,
+                            "blossom_activation_height": null,
+                            "heartwood_activation_height": null,
+                            "canopy_activation_height": null,
+                            "coin_type": 133,
+                            "hrp_sapling_extended_spending_key": "secret-extended-key-main",
+                            "hrp_sapling_extended_full_viewing_key": "zxviews",
+                            "hrp_sapling_payment_address": "zs",
+                            "b58_pubkey_address_prefix": [28, 184],
+                            "b58_script_address_prefix": [28, 189]
+                        }
+                    }
+                },
+                "required_confirmations": 0
+            }]
+        });
+        let ctx = MmCtxBuilder::new().with_conf(conf).into_mm_arc();
+        CryptoCtx::init_with_iguana_passphrase(ctx.clone(), PASSPHRASE).unwrap();
+        std::fs::create_dir_all(ctx.dbdir()).unwrap();
+        ctx
+    }
+
+    fn activation_params() -> ZcoinActivationParams {
+        ZcoinActivationParams {
+            mode: ZcoinRpcMode::Native,
+            required_confirmations: None,
+            requires_notarization: None,
+            tx_history: false,
+        }
+    }
+
+    fn test_dbdir(name: &str) -> String {
+        let dbdir = std::env::temp_dir().join(format!("{}_{}", name, now_ms()));
+        dbdir.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_pending_activation_guard() {
+        let ctx = ctx_for_test(&test_dbdir("test_pending_activation_guard"));
+        let path = pending_activation_path(&ctx, "ZOMBIE");
+
+        let guard = PendingActivationGuard::new(ctx.clone(), "ZOMBIE", &activation_params());
+        let request: InitStandaloneCoinReq<ZcoinActivationParams> =
+            json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+        assert_eq!(request.ticker, "ZOMBIE");
+        assert!(matches!(request.activation_params.mode, ZcoinRpcMode::Native));
+
+        // The activation is finished, failed or cancelled, so it mustn't be resumed.
+        drop(guard);
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_resume_z_coin_activations() {
+        let dbdir = test_dbdir("test_resume_z_coin_activations");
+        let ctx = ctx_for_test(&dbdir);
+        let path = pending_activation_path(&ctx, "ZOMBIE");
+
+        // The node is stopped while the blocks are being scanned.
+        let guard = PendingActivationGuard::new(ctx.clone(), "ZOMBIE", &activation_params());
+        ctx.stop().unwrap();
+        drop(guard);
+        assert!(path.exists());
+
+        // A broken pending activation can't be resumed, so it's removed.
+        let broken_path = pending_activation_path(&ctx, "BROKEN");
+        std::fs::write(&broken_path, "not a json").unwrap();
+
+        let ctx = ctx_for_test(&dbdir);
+        block_on(resume_z_coin_activations(ctx.clone()));
+        assert!(!broken_path.exists());
+
+        let tasks = CoinsActivationContext::from_ctx(&ctx)
+            .unwrap()
+            .rpc_tasks_info()
+            .unwrap();
+        assert_eq!(tasks.len(), 1);
+        assert_eq!(tasks[0].kind, "init_z_coin");
+    }
+
+    #[test]
+    fn test_resume_z_coin_activations_unknown_coin() {
+        let dbdir = test_dbdir("test_resume_z_coin_activations_unknown_coin");
+        let ctx = ctx_for_test(&dbdir);
+
+        // The coin could be removed from the coins config since the node was stopped.
+        let path = pending_activation_path(&ctx, "UNKNOWN");
+        let guard = PendingActivationGuard::new(ctx.clone(), "UNKNOWN", &activation_params());
+        ctx.stop().unwrap();
+        drop(guard);
+        assert!(path.exists());
+
+        let ctx = ctx_for_test(&dbdir);
+        block_on(resume_z_coin_activations(ctx.clone()));
+        assert!(!path.exists());
+
+        let tasks = CoinsActivationContext::from_ctx(&ctx)
+            .unwrap()
+            .rpc_tasks_info()
+            .unwrap();
+        assert!(tasks.is_empty());
+    }
+}
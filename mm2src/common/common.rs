@@ -23,6 +23,7 @@
 #[macro_use] extern crate lazy_static;
 #[macro_use] pub extern crate serde_derive;
 #[macro_use] pub extern crate serde_json;
+#[macro_use] extern crate rpc_schema_derive;
 #[macro_use] extern crate ser_error_derive;
 
 /// Implements a `From` for `enum` with a variant name matching the name of the type stored.
@@ -546,6 +547,10 @@ impl Default for SuccessResponse {
     fn default() -> Self { SuccessResponse::new() }
 }
 
+impl rpc_schema::RpcSchema for SuccessResponse {
+    fn json_schema(_gen: &mut rpc_schema::SchemaGenerator) -> Json { json!({ "type": "string", "const": "success" }) }
+}
+
 #[derive(Serialize)]
 struct ErrResponse {
     error: String,
@@ -752,7 +757,7 @@ pub const fn one_hundred() -> usize { 100 }
 
 pub fn one() -> NonZeroUsize { NonZeroUsize::new(1).unwrap() }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, RpcSchema)]
 pub struct PagingOptions {
     #[serde(default = "ten")]
     pub limit: usize,
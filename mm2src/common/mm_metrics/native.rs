@@ -71,6 +71,10 @@ macro_rules! mm_timing {
 /// Default quantiles are "min" and "max"
 const QUANTILES: &[f64] = &[0.0, 1.0];
 
+/// The Prometheus summaries also contain the median and the tail quantiles
+/// to alert on the degraded latency of the RPC servers and the swap steps.
+const PROMETHEUS_QUANTILES: &[f64] = &[0.0, 0.5, 0.9, 0.99, 1.0];
+
 pub trait TrySink {
     fn try_sink(&self) -> Option<Sink>;
 }
@@ -161,7 +165,7 @@ impl Metrics {
         let receiver = try_s!(self.try_receiver());
         let controller = receiver.controller();
 
-        let mut observer = PrometheusBuilder::new().set_quantiles(QUANTILES).build();
+        let mut observer = PrometheusBuilder::new().set_quantiles(PROMETHEUS_QUANTILES).build();
         controller.observe(&mut observer);
 
         Ok(observer.drain())
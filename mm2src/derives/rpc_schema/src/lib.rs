@@ -0,0 +1,306 @@
+//! The JSON schemas of the RPC requests, responses and errors.
+//!
+//! The schemas follow the JSON Schema dialect of the OpenAPI 3.1 specification.
+//! Usually [`RpcSchema`] is implemented by `#[derive(RpcSchema)]` that takes the `serde` attributes into account.
+
+use bigdecimal::BigDecimal;
+use num_rational::BigRational;
+use serde_json::{json, Map, Value as Json};
+use std::any::type_name;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::num::{NonZeroU32, NonZeroU64, NonZeroUsize};
+use std::sync::Arc;
+use std::time::Duration;
+use uuid::Uuid;
+
+/// The location of the named schemas in an OpenAPI document.
+pub const REF_PREFIX: &str = "#/components/schemas/";
+
+pub trait RpcSchema {
+    /// The name of the schema in the [`SchemaGenerator::definitions`].
+    /// The schema is inlined into every place it's used if the name is `None`.
+    fn schema_name() -> Option<String> { None }
+
+    /// Returns the JSON schema of the type.
+    /// The schemas of the nested types must be obtained via [`SchemaGenerator::subschema_for`].
+    fn json_schema(gen: &mut SchemaGenerator) -> Json;
+}
+
+/// Collects the named schemas of the types and the nested types.
+#[derive(Default)]
+pub struct SchemaGenerator {
+    definitions: BTreeMap<String, Json>,
+    /// The definition names by the Rust type names.
+    /// Different types with the same [`RpcSchema::schema_name`] get unique definition names.
+    names: HashMap<&'static str, String>,
+}
+
+impl SchemaGenerator {
+    /// Returns the schema of the `T` type or the reference to it if the type has a name.
+    pub fn subschema_for<T: RpcSchema + ?Sized>(&mut self) -> Json {
+        let name = match T::schema_name() {
+            Some(name) => name,
+            None => return T::json_schema(self),
+        };
+        let type_name = type_name::<T>();
+        if let Some(name) = self.names.get(type_name) {
+            return schema_ref(name);
+        }
+
+        let name = self.unique_name(name);
+        self.names.insert(type_name, name.clone());
+        // Insert a placeholder first to stop the recursion if the type refers to itself.
+        self.definitions.insert(name.clone(), Json::Null);
+        let schema = T::json_schema(self);
+        self.definitions.insert(name.clone(), schema);
+        schema_ref(&name)
+    }
+
+    pub fn definitions(&self) -> &BTreeMap<String, Json> { &self.definitions }
+
+    pub fn into_definitions(self) -> BTreeMap<String, Json> { self.definitions }
+
+    fn unique_name(&self, name: String) -> String {
+        if !self.definitions.contains_key(&name) {
+            return name;
+        }
+        (2..)
+            .map(|idx| format!("{}{}", name, idx))
+            .find(|candidate| !self.definitions.contains_key(candidate))
+            .expect("An infinite iterator always finds a free name")
+    }
+}
+
+pub fn schema_ref(name: &str) -> Json { json!({ "$ref": format!("{}{}", REF_PREFIX, name) }) }
+
+/// Returns the schema of `T` without adding the definitions to any document.
+pub fn schema_for<T: RpcSchema + ?Sized>() -> Json { T::json_schema(&mut SchemaGenerator::default()) }
+
+macro_rules! impl_rpc_schema {
+    ($($ty:ty),+ => $schema:expr) => {
+        $(
+            impl RpcSchema for $ty {
+                fn json_schema(_gen: &mut SchemaGenerator) -> Json { $schema }
+            }
+        )+
+    };
+}
+
+impl_rpc_schema!(bool => json!({ "type": "boolean" }));
+impl_rpc_schema!(String, str, char => json!({ "type": "string" }));
+impl_rpc_schema!(u8, u16, u32, u64, u128, usize => json!({ "type": "integer", "minimum": 0 }));
+impl_rpc_schema!(NonZeroU32, NonZeroU64, NonZeroUsize => json!({ "type": "integer", "minimum": 1 }));
+impl_rpc_schema!(i8, i16, i32, i64, i128, isize => json!({ "type": "integer" }));
+impl_rpc_schema!(f32, f64 => json!({ "type": "number" }));
+impl_rpc_schema!(() => json!({ "type": "null" }));
+impl_rpc_schema!(Json => json!({}));
+impl_rpc_schema!(Uuid => json!({ "type": "string", "format": "uuid" }));
+impl_rpc_schema!(Ipv4Addr => json!({ "type": "string", "format": "ipv4" }));
+impl_rpc_schema!(Ipv6Addr => json!({ "type": "string", "format": "ipv6" }));
+impl_rpc_schema!(IpAddr => json!({ "type": "string", "anyOf": [{ "format": "ipv4" }, { "format": "ipv6" }] }));
+impl_rpc_schema!(SocketAddr => json!({ "type": "string", "description": "IP address and port" }));
+impl_rpc_schema!(BigDecimal => json!({ "type": "string", "description": "Decimal number, e.g. \"0.1\"" }));
+impl_rpc_schema!(BigRational => json!({
+    "type": "array",
+    "description": "Rational number in the [numerator, denominator] form of big integers",
+    "minItems": 2,
+    "maxItems": 2,
+}));
+
+impl_rpc_schema!(Duration => json!({
+    "type": "object",
+    "properties": {
+        "secs": { "type": "integer", "minimum": 0 },
+        "nanos": { "type": "integer", "minimum": 0 },
+    },
+    "required": ["secs", "nanos"],
+}));
+
+impl<T: RpcSchema> RpcSchema for Option<T> {
+    fn json_schema(gen: &mut SchemaGenerator) -> Json {
+        json!({ "anyOf": [gen.subschema_for::<T>(), { "type": "null" }] })
+    }
+}
+
+impl<T: RpcSchema + ?Sized> RpcSchema for &T {
+    fn schema_name() -> Option<String> { T::schema_name() }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Json { T::json_schema(gen) }
+}
+
+impl<T: RpcSchema + ?Sized> RpcSchema for Box<T> {
+    fn schema_name() -> Option<String> { T::schema_name() }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Json { T::json_schema(gen) }
+}
+
+impl<T: RpcSchema + ?Sized> RpcSchema for Arc<T> {
+    fn schema_name() -> Option<String> { T::schema_name() }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Json { T::json_schema(gen) }
+}
+
+impl<'a, T: RpcSchema + ToOwned + ?Sized> RpcSchema for Cow<'a, T> {
+    fn schema_name() -> Option<String> { T::schema_name() }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> Json { T::json_schema(gen) }
+}
+
+macro_rules! impl_array_schema {
+    ($($ty:ident),+ => unique_items: $unique:expr) => {
+        $(
+            impl<T: RpcSchema> RpcSchema for $ty<T> {
+                fn json_schema(gen: &mut SchemaGenerator) -> Json {
+                    let mut schema = json!({ "type": "array", "items": gen.subschema_for::<T>() });
+                    if $unique {
+                        schema["uniqueItems"] = Json::Bool(true);
+                    }
+                    schema
+                }
+            }
+        )+
+    };
+}
+
+impl_array_schema!(Vec, VecDeque => unique_items: false);
+impl_array_schema!(HashSet, BTreeSet => unique_items: true);
+
+impl<T: RpcSchema> RpcSchema for [T] {
+    fn json_schema(gen: &mut SchemaGenerator) -> Json { json!({ "type": "array", "items": gen.subschema_for::<T>() }) }
+}
+
+/// The keys of the maps are always serialized into the strings in JSON.
+impl<K, V: RpcSchema, S> RpcSchema for HashMap<K, V, S> {
+    fn json_schema(gen: &mut SchemaGenerator) -> Json {
+        json!({ "type": "object", "additionalProperties": gen.subschema_for::<V>() })
+    }
+}
+
+impl<K, V: RpcSchema> RpcSchema for BTreeMap<K, V> {
+    fn json_schema(gen: &mut SchemaGenerator) -> Json {
+        json!({ "type": "object", "additionalProperties": gen.subschema_for::<V>() })
+    }
+}
+
+macro_rules! impl_tuple_schema {
+    ($($name:ident),+) => {
+        impl<$($name: RpcSchema),+> RpcSchema for ($($name,)+) {
+            fn json_schema(gen: &mut SchemaGenerator) -> Json {
+                __private::tuple(vec![$(gen.subschema_for::<$name>()),+])
+            }
+        }
+    };
+}
+
+impl_tuple_schema!(A);
+impl_tuple_schema!(A, B);
+impl_tuple_schema!(A, B, C);
+impl_tuple_schema!(A, B, C, D);
+
+/// The helpers used by the code generated by `#[derive(RpcSchema)]`.
+#[doc(hidden)]
+pub mod __private {
+    use super::*;
+
+    pub use serde_json::Value as Json;
+
+    pub fn object(properties: Vec<(String, Json)>, required: Vec<String>) -> Json {
+        let mut schema = json!({
+            "type": "object",
+            "properties": properties.into_iter().collect::<Map<String, Json>>(),
+        });
+        if !required.is_empty() {
+            schema["required"] = required.into();
+        }
+        schema
+    }
+
+    pub fn all_of(schemas: Vec<Json>) -> Json {
+        if schemas.len() == 1 {
+            return schemas.into_iter().next().expect("Checked the length above");
+        }
+        json!({ "allOf": schemas })
+    }
+
+    pub fn one_of(schemas: Vec<Json>) -> Json {
+        if schemas.len() == 1 {
+            return schemas.into_iter().next().expect("Checked the length above");
+        }
+        json!({ "oneOf": schemas })
+    }
+
+    pub fn tuple(items: Vec<Json>) -> Json {
+        let len = items.len();
+        json!({ "type": "array", "prefixItems": items, "minItems": len, "maxItems": len })
+    }
+
+    pub fn const_str(value: &str) -> Json { json!({ "type": "string", "const": value }) }
+
+    pub fn string_enum(values: Vec<&str>) -> Json { json!({ "type": "string", "enum": values }) }
+
+    /// The schema of a value that is serialized by a custom function.
+    pub fn any() -> Json { json!({}) }
+
+    pub fn with_description(mut schema: Json, description: &str) -> Json {
+        if let Json::Object(ref mut map) = schema {
+            map.insert("description".to_owned(), description.into());
+        }
+        schema
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Recursive;
+
+    impl RpcSchema for Recursive {
+        fn schema_name() -> Option<String> { Some("Recursive".to_owned()) }
+
+        fn json_schema(gen: &mut SchemaGenerator) -> Json {
+            __private::object(
+                vec![("next".to_owned(), gen.subschema_for::<Option<Recursive>>())],
+                Vec::new(),
+            )
+        }
+    }
+
+    mod other {
+        use super::*;
+
+        pub struct Recursive;
+
+        impl RpcSchema for Recursive {
+            fn schema_name() -> Option<String> { Some("Recursive".to_owned()) }
+
+            fn json_schema(_gen: &mut SchemaGenerator) -> Json { json!({ "type": "string" }) }
+        }
+    }
+
+    #[test]
+    fn test_subschema_for_recursive_type() {
+        let mut gen = SchemaGenerator::default();
+        assert_eq!(gen.subschema_for::<Recursive>(), schema_ref("Recursive"));
+
+        let expected = json!({
+            "type": "object",
+            "properties": {
+                "next": { "anyOf": [{ "$ref": "#/components/schemas/Recursive" }, { "type": "null" }] },
+            },
+        });
+        assert_eq!(gen.definitions()["Recursive"], expected);
+    }
+
+    #[test]
+    fn test_subschema_for_same_names() {
+        let mut gen = SchemaGenerator::default();
+        assert_eq!(gen.subschema_for::<Recursive>(), schema_ref("Recursive"));
+        assert_eq!(gen.subschema_for::<other::Recursive>(), schema_ref("Recursive2"));
+        // The names are stable for the already known types.
+        assert_eq!(gen.subschema_for::<other::Recursive>(), schema_ref("Recursive2"));
+        assert_eq!(gen.definitions().len(), 2);
+    }
+}
@@ -0,0 +1,533 @@
+use proc_macro::{self, TokenStream};
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::Meta::{List, NameValue, Path};
+use syn::NestedMeta;
+use syn::{parse_macro_input, Attribute, Data, DataEnum, DeriveInput, Error, Fields, GenericParam, Type};
+
+const SERDE_IDENT: &str = "serde";
+const RPC_SCHEMA_IDENT: &str = "rpc_schema";
+const DOC_IDENT: &str = "doc";
+
+macro_rules! compile_err {
+    ($($arg:tt)*) => {
+        $crate::CompileError(format!($($arg)*))
+    };
+}
+
+struct CompileError(String);
+
+impl From<CompileError> for TokenStream {
+    fn from(e: CompileError) -> Self { TokenStream2::from(e).into() }
+}
+
+impl From<CompileError> for TokenStream2 {
+    fn from(e: CompileError) -> Self { Error::new(Span::call_site(), e.0).to_compile_error() }
+}
+
+/// Implements `rpc_schema::RpcSchema` using the same `serde` attributes as in the `serde-derive` crate,
+/// so the schema describes the JSON the type is serialized into.
+///
+/// The following `serde` attributes are taken into account:
+/// * container: `rename_all`, `tag`, `content`, `untagged`, `transparent`, `default`;
+/// * variant: `rename`, `skip`;
+/// * field: `rename`, `default`, `flatten`, `skip`, `skip_serializing_if`, `with`, `serialize_with`, `deserialize_with`.
+///
+/// The `#[rpc_schema(any)]` field attribute allows a field of any JSON value.
+/// Use it for the fields of the types that don't implement `RpcSchema`.
+///
+/// The doc comments become the descriptions of the schemas.
+#[proc_macro_derive(RpcSchema, attributes(serde, rpc_schema))]
+pub fn rpc_schema(input: TokenStream) -> TokenStream {
+    let input: DeriveInput = parse_macro_input!(input);
+    match impl_rpc_schema(input) {
+        Ok(output) => wrap_const(output),
+        Err(e) => e.into(),
+    }
+}
+
+fn impl_rpc_schema(mut input: DeriveInput) -> Result<TokenStream2, CompileError> {
+    let container = ContainerAttrs::parse(&input.attrs)?;
+    let schema = match input.data {
+        Data::Struct(ref data) => struct_schema(&container, &data.fields)?,
+        Data::Enum(ref data) => enum_schema(&container, data)?,
+        Data::Union(_) => return Err(compile_err!("'RpcSchema' cannot be implemented for a union")),
+    };
+    let schema = with_description(schema, &doc_comment(&input.attrs));
+
+    let ident = &input.ident;
+    let is_generic = input
+        .generics
+        .params
+        .iter()
+        .any(|param| matches!(param, GenericParam::Type(_)));
+    // The schemas of the generic types are inlined as the schema of every instance may differ.
+    let schema_name = if is_generic {
+        quote! { None }
+    } else {
+        let name = ident.to_string();
+        quote! { Some(#name.to_owned()) }
+    };
+
+    let type_params: Vec<_> = input.generics.type_params().map(|param| param.ident.clone()).collect();
+    let where_clause = input.generics.make_where_clause();
+    for param in type_params {
+        where_clause
+            .predicates
+            .push(syn::parse_quote! { #param: rpc_schema::RpcSchema });
+    }
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+
+    Ok(quote! {
+        #[automatically_derived]
+        impl #impl_generics rpc_schema::RpcSchema for #ident #type_generics #where_clause {
+            fn schema_name() -> Option<String> { #schema_name }
+
+            #[allow(unused_mut, unused_variables)]
+            fn json_schema(gen: &mut rpc_schema::SchemaGenerator) -> rpc_schema::__private::Json {
+                #schema
+            }
+        }
+    })
+}
+
+#[derive(Default)]
+struct ContainerAttrs {
+    rename_all: Option<RenameRule>,
+    tag: Option<String>,
+    content: Option<String>,
+    untagged: bool,
+    transparent: bool,
+    default: bool,
+}
+
+impl ContainerAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<ContainerAttrs, CompileError> {
+        let mut container = ContainerAttrs::default();
+        for meta_item in attrs.iter().flat_map(|attr| get_meta_items(attr, SERDE_IDENT)) {
+            match meta_item {
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("rename_all") => {
+                    container.rename_all = Some(RenameRule::from_str(&parse_lit_str("rename_all", m.lit)?)?)
+                },
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("tag") => {
+                    container.tag = Some(parse_lit_str("tag", m.lit)?)
+                },
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("content") => {
+                    container.content = Some(parse_lit_str("content", m.lit)?)
+                },
+                NestedMeta::Meta(Path(word)) if word.is_ident("untagged") => container.untagged = true,
+                NestedMeta::Meta(Path(word)) if word.is_ident("transparent") => container.transparent = true,
+                NestedMeta::Meta(Path(word)) if word.is_ident("default") => container.default = true,
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("default") => container.default = true,
+                _ => (),
+            }
+        }
+        Ok(container)
+    }
+}
+
+#[derive(Default)]
+struct FieldAttrs {
+    rename: Option<String>,
+    default: bool,
+    flatten: bool,
+    skip: bool,
+    skip_serializing_if: bool,
+    custom_serde: bool,
+    any: bool,
+}
+
+impl FieldAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<FieldAttrs, CompileError> {
+        let mut field = FieldAttrs::default();
+        for meta_item in attrs.iter().flat_map(|attr| get_meta_items(attr, SERDE_IDENT)) {
+            match meta_item {
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("rename") => {
+                    field.rename = Some(parse_lit_str("rename", m.lit)?)
+                },
+                NestedMeta::Meta(Path(word)) if word.is_ident("default") => field.default = true,
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("default") => field.default = true,
+                NestedMeta::Meta(Path(word)) if word.is_ident("flatten") => field.flatten = true,
+                NestedMeta::Meta(Path(word)) if word.is_ident("skip") => field.skip = true,
+                NestedMeta::Meta(Path(word)) if word.is_ident("skip_serializing") => field.skip_serializing_if = true,
+                NestedMeta::Meta(Path(word)) if word.is_ident("skip_deserializing") => field.default = true,
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("skip_serializing_if") => {
+                    field.skip_serializing_if = true
+                },
+                NestedMeta::Meta(NameValue(m))
+                    if m.path.is_ident("with")
+                        || m.path.is_ident("serialize_with")
+                        || m.path.is_ident("deserialize_with") =>
+                {
+                    field.custom_serde = true
+                },
+                _ => (),
+            }
+        }
+        for meta_item in attrs.iter().flat_map(|attr| get_meta_items(attr, RPC_SCHEMA_IDENT)) {
+            match meta_item {
+                NestedMeta::Meta(Path(word)) if word.is_ident("any") => field.any = true,
+                _ => {
+                    return Err(compile_err!(
+                        "unknown 'rpc_schema' attribute, expected `#[rpc_schema(any)]`"
+                    ))
+                },
+            }
+        }
+        Ok(field)
+    }
+}
+
+#[derive(Default)]
+struct VariantAttrs {
+    rename: Option<String>,
+    skip: bool,
+}
+
+impl VariantAttrs {
+    fn parse(attrs: &[Attribute]) -> Result<VariantAttrs, CompileError> {
+        let mut variant = VariantAttrs::default();
+        for meta_item in attrs.iter().flat_map(|attr| get_meta_items(attr, SERDE_IDENT)) {
+            match meta_item {
+                NestedMeta::Meta(NameValue(m)) if m.path.is_ident("rename") => {
+                    variant.rename = Some(parse_lit_str("rename", m.lit)?)
+                },
+                NestedMeta::Meta(Path(word)) if word.is_ident("skip") || word.is_ident("skip_deserializing") => {
+                    variant.skip = true
+                },
+                _ => (),
+            }
+        }
+        Ok(variant)
+    }
+}
+
+/// The same rules as in the `serde-derive` crate.
+#[derive(Clone, Copy)]
+enum RenameRule {
+    Lower,
+    Upper,
+    Pascal,
+    Camel,
+    Snake,
+    ScreamingSnake,
+    Kebab,
+    ScreamingKebab,
+}
+
+impl RenameRule {
+    fn from_str(rule: &str) -> Result<RenameRule, CompileError> {
+        match rule {
+            "lowercase" => Ok(RenameRule::Lower),
+            "UPPERCASE" => Ok(RenameRule::Upper),
+            "PascalCase" => Ok(RenameRule::Pascal),
+            "camelCase" => Ok(RenameRule::Camel),
+            "snake_case" => Ok(RenameRule::Snake),
+            "SCREAMING_SNAKE_CASE" => Ok(RenameRule::ScreamingSnake),
+            "kebab-case" => Ok(RenameRule::Kebab),
+            "SCREAMING-KEBAB-CASE" => Ok(RenameRule::ScreamingKebab),
+            _ => Err(compile_err!("unknown rename rule `rename_all = \"{}\"`", rule)),
+        }
+    }
+
+    /// Applies the rule to a variant name that is expected to be in the `PascalCase`.
+    fn apply_to_variant(self, variant: &str) -> String {
+        match self {
+            RenameRule::Pascal => variant.to_owned(),
+            RenameRule::Lower => variant.to_ascii_lowercase(),
+            RenameRule::Upper => variant.to_ascii_uppercase(),
+            RenameRule::Camel => variant[..1].to_ascii_lowercase() + &variant[1..],
+            RenameRule::Snake => {
+                let mut snake = String::new();
+                for (i, ch) in variant.char_indices() {
+                    if i > 0 && ch.is_uppercase() {
+                        snake.push('_');
+                    }
+                    snake.push(ch.to_ascii_lowercase());
+                }
+                snake
+            },
+            RenameRule::ScreamingSnake => RenameRule::Snake.apply_to_variant(variant).to_ascii_uppercase(),
+            RenameRule::Kebab => RenameRule::Snake.apply_to_variant(variant).replace('_', "-"),
+            RenameRule::ScreamingKebab => RenameRule::ScreamingSnake.apply_to_variant(variant).replace('_', "-"),
+        }
+    }
+
+    /// Applies the rule to a field name that is expected to be in the `snake_case`.
+    fn apply_to_field(self, field: &str) -> String {
+        match self {
+            RenameRule::Lower | RenameRule::Snake => field.to_owned(),
+            RenameRule::Upper | RenameRule::ScreamingSnake => field.to_ascii_uppercase(),
+            RenameRule::Pascal => {
+                let mut pascal = String::new();
+                let mut capitalize = true;
+                for ch in field.chars() {
+                    if ch == '_' {
+                        capitalize = true;
+                    } else if capitalize {
+                        pascal.push(ch.to_ascii_uppercase());
+                        capitalize = false;
+                    } else {
+                        pascal.push(ch);
+                    }
+                }
+                pascal
+            },
+            RenameRule::Camel => {
+                let pascal = RenameRule::Pascal.apply_to_field(field);
+                pascal[..1].to_ascii_lowercase() + &pascal[1..]
+            },
+            RenameRule::Kebab => field.replace('_', "-"),
+            RenameRule::ScreamingKebab => field.to_ascii_uppercase().replace('_', "-"),
+        }
+    }
+}
+
+fn struct_schema(container: &ContainerAttrs, fields: &Fields) -> Result<TokenStream2, CompileError> {
+    if container.transparent {
+        let field = fields
+            .iter()
+            .find(|field| !FieldAttrs::parse(&field.attrs).map(|attrs| attrs.skip).unwrap_or(false))
+            .ok_or_else(|| compile_err!("'RpcSchema': a transparent struct must have a field"))?;
+        return field_schema(&field.ty, &FieldAttrs::parse(&field.attrs)?);
+    }
+    match fields {
+        Fields::Named(_) => named_fields_schema(container.rename_all, container.default, fields, None),
+        Fields::Unnamed(_) => unnamed_fields_schema(fields),
+        // A unit struct is (de)serialized as `null`.
+        Fields::Unit => Ok(quote! { gen.subschema_for::<()>() }),
+    }
+}
+
+/// Generates the object schema of the named fields.
+/// The `tag` is a property with a constant value, added by the internally tagged enums.
+fn named_fields_schema(
+    rename_all: Option<RenameRule>,
+    default: bool,
+    fields: &Fields,
+    tag: Option<(&str, &str)>,
+) -> Result<TokenStream2, CompileError> {
+    let mut statements = Vec::new();
+    if let Some((tag, value)) = tag {
+        statements.push(quote! {
+            properties.push((#tag.to_owned(), rpc_schema::__private::const_str(#value)));
+            required.push(#tag.to_owned());
+        });
+    }
+
+    for field in fields.iter() {
+        let attrs = FieldAttrs::parse(&field.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ty = &field.ty;
+        if attrs.flatten {
+            statements.push(quote! { flattened.push(gen.subschema_for::<#ty>()); });
+            continue;
+        }
+
+        let ident = field
+            .ident
+            .as_ref()
+            .ok_or_else(|| compile_err!("'RpcSchema': expected a named field"))?
+            .to_string();
+        let ident = ident.trim_start_matches("r#");
+        let name = match (&attrs.rename, rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_field(ident),
+            (None, None) => ident.to_owned(),
+        };
+        let schema = with_description(field_schema(ty, &attrs)?, &doc_comment(&field.attrs));
+        statements.push(quote! { properties.push((#name.to_owned(), #schema)); });
+
+        let is_required = !default && !attrs.default && !attrs.skip_serializing_if && !is_option(ty);
+        if is_required {
+            statements.push(quote! { required.push(#name.to_owned()); });
+        }
+    }
+
+    Ok(quote! {{
+        let mut properties = Vec::new();
+        let mut required: Vec<String> = Vec::new();
+        let mut flattened = Vec::new();
+        #(#statements)*
+        let object = rpc_schema::__private::object(properties, required);
+        rpc_schema::__private::all_of(std::iter::once(object).chain(flattened).collect())
+    }})
+}
+
+fn unnamed_fields_schema(fields: &Fields) -> Result<TokenStream2, CompileError> {
+    let schemas = fields
+        .iter()
+        .map(|field| field_schema(&field.ty, &FieldAttrs::parse(&field.attrs)?))
+        .collect::<Result<Vec<_>, _>>()?;
+    if schemas.len() == 1 {
+        return Ok(schemas.into_iter().next().expect("Checked the length above"));
+    }
+    Ok(quote! { rpc_schema::__private::tuple(vec![#(#schemas),*]) })
+}
+
+fn field_schema(ty: &Type, attrs: &FieldAttrs) -> Result<TokenStream2, CompileError> {
+    if attrs.any || attrs.custom_serde {
+        return Ok(quote! { rpc_schema::__private::any() });
+    }
+    Ok(quote! { gen.subschema_for::<#ty>() })
+}
+
+fn enum_schema(container: &ContainerAttrs, data: &DataEnum) -> Result<TokenStream2, CompileError> {
+    let mut variants = Vec::new();
+    let mut unit_names = Vec::new();
+    for variant in data.variants.iter() {
+        let attrs = VariantAttrs::parse(&variant.attrs)?;
+        if attrs.skip {
+            continue;
+        }
+        let ident = variant.ident.to_string();
+        let name = match (&attrs.rename, container.rename_all) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rule)) => rule.apply_to_variant(&ident),
+            (None, None) => ident,
+        };
+        let content = match variant.fields {
+            Fields::Named(_) => Some(named_fields_schema(None, false, &variant.fields, None)?),
+            Fields::Unnamed(_) => Some(unnamed_fields_schema(&variant.fields)?),
+            Fields::Unit => None,
+        };
+
+        let schema = match (&container.tag, &container.content, container.untagged) {
+            (_, _, true) => content.unwrap_or_else(|| quote! { rpc_schema::__private::Json::Null }),
+            // Adjacently tagged.
+            (Some(tag), Some(content_name), false) => match content {
+                Some(content) => quote! {
+                    rpc_schema::__private::object(
+                        vec![
+                            (#tag.to_owned(), rpc_schema::__private::const_str(#name)),
+                            (#content_name.to_owned(), #content),
+                        ],
+                        vec![#tag.to_owned(), #content_name.to_owned()],
+                    )
+                },
+                None => quote! {
+                    rpc_schema::__private::object(
+                        vec![(#tag.to_owned(), rpc_schema::__private::const_str(#name))],
+                        vec![#tag.to_owned()],
+                    )
+                },
+            },
+            // Internally tagged.
+            (Some(tag), None, false) => match variant.fields {
+                Fields::Named(_) => {
+                    named_fields_schema(None, false, &variant.fields, Some((tag.as_str(), name.as_str())))?
+                },
+                Fields::Unnamed(_) => {
+                    let content = content.expect("Unnamed fields always have a schema");
+                    quote! {
+                        rpc_schema::__private::all_of(vec![
+                            rpc_schema::__private::object(
+                                vec![(#tag.to_owned(), rpc_schema::__private::const_str(#name))],
+                                vec![#tag.to_owned()],
+                            ),
+                            #content,
+                        ])
+                    }
+                },
+                Fields::Unit => quote! {
+                    rpc_schema::__private::object(
+                        vec![(#tag.to_owned(), rpc_schema::__private::const_str(#name))],
+                        vec![#tag.to_owned()],
+                    )
+                },
+            },
+            (None, Some(_), false) => {
+                return Err(compile_err!(
+                    "'RpcSchema': #[serde(content = \"...\")] requires #[serde(tag = \"...\")]"
+                ))
+            },
+            // Externally tagged.
+            (None, None, false) => match content {
+                Some(content) => quote! {{
+                    let mut schema = rpc_schema::__private::object(vec![(#name.to_owned(), #content)], vec![#name.to_owned()]);
+                    schema["additionalProperties"] = false.into();
+                    schema
+                }},
+                None => {
+                    // The unit variants are serialized as strings and are combined into a single `enum` schema below.
+                    unit_names.push(name);
+                    continue;
+                },
+            },
+        };
+        variants.push(with_description(schema, &doc_comment(&variant.attrs)));
+    }
+
+    if !unit_names.is_empty() {
+        variants.insert(0, quote! { rpc_schema::__private::string_enum(vec![#(#unit_names),*]) });
+    }
+    Ok(quote! { rpc_schema::__private::one_of(vec![#(#variants),*]) })
+}
+
+fn is_option(ty: &Type) -> bool {
+    match ty {
+        Type::Path(path) => path
+            .path
+            .segments
+            .last()
+            .map_or(false, |segment| segment.ident == "Option"),
+        _ => false,
+    }
+}
+
+fn with_description(schema: TokenStream2, description: &str) -> TokenStream2 {
+    if description.is_empty() {
+        return schema;
+    }
+    quote! { rpc_schema::__private::with_description(#schema, #description) }
+}
+
+/// Joins the lines of the `///` doc comments.
+fn doc_comment(attrs: &[Attribute]) -> String {
+    let lines: Vec<String> = attrs
+        .iter()
+        .filter(|attr| attr.path.is_ident(DOC_IDENT))
+        .filter_map(|attr| match attr.parse_meta() {
+            Ok(NameValue(m)) => match m.lit {
+                syn::Lit::Str(lit) => Some(lit.value().trim().to_owned()),
+                _ => None,
+            },
+            _ => None,
+        })
+        .collect();
+    lines.join("\n").trim().to_owned()
+}
+
+fn get_meta_items(attr: &Attribute, ident: &str) -> Vec<NestedMeta> {
+    if !attr.path.is_ident(ident) {
+        return Vec::new();
+    }
+
+    match attr.parse_meta() {
+        Ok(List(meta)) => meta.nested.into_iter().collect(),
+        _ => Vec::new(),
+    }
+}
+
+fn parse_lit_str(attr_ident: &str, lit: syn::Lit) -> Result<String, CompileError> {
+    match lit {
+        syn::Lit::Str(lit) => Ok(lit.value()),
+        _ => Err(compile_err!(
+            "expected serde '{}' attribute to be a string: `{} = \"...\"`",
+            attr_ident,
+            attr_ident
+        )),
+    }
+}
+
+fn wrap_const(code: TokenStream2) -> TokenStream {
+    let output = quote! {
+        const _: () = {
+            extern crate rpc_schema;
+            #code
+        };
+    };
+    output.into()
+}
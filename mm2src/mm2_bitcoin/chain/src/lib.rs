@@ -26,8 +26,8 @@ pub use primitives::{bytes, compact, hash, U256};
 pub use block::Block;
 pub use block_header::{BlockHeader, BlockHeaderBits, BlockHeaderNonce};
 pub use merkle_root::{merkle_node_hash, merkle_root};
-pub use transaction::{JoinSplit, OutPoint, ShieldedOutput, ShieldedSpend, Transaction, TransactionInput,
-                      TransactionOutput, TxHashAlgo};
+pub use transaction::{deserialize_tx, JoinSplit, OutPoint, ShieldedOutput, ShieldedSpend, Transaction,
+                      TransactionInput, TransactionOutput, TxHashAlgo, TxType};
 
 pub use read_and_hash::{HashedData, ReadAndHash};
 
@@ -1,5 +1,6 @@
 extern crate core;
 extern crate log;
+extern crate rpc_schema;
 extern crate rustc_hex as hex;
 extern crate serde;
 extern crate serde_json;
@@ -78,6 +78,12 @@ impl<'a> Visitor<'a> for BytesVisitor {
     }
 }
 
+impl ::rpc_schema::RpcSchema for Bytes {
+    fn json_schema(_gen: &mut ::rpc_schema::SchemaGenerator) -> ::serde_json::Value {
+        ::serde_json::json!({ "type": "string", "description": "Hex-encoded bytes" })
+    }
+}
+
 impl ops::Deref for Bytes {
     type Target = Vec<u8>;
 
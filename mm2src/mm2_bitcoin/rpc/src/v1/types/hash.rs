@@ -19,6 +19,15 @@ macro_rules! impl_hash {
             fn default() -> Self { $name([0; $size]) }
         }
 
+        impl ::rpc_schema::RpcSchema for $name {
+            fn json_schema(_gen: &mut ::rpc_schema::SchemaGenerator) -> ::serde_json::Value {
+                ::serde_json::json!({
+                    "type": "string",
+                    "description": format!("Hex-encoded {}-byte hash", $size),
+                })
+            }
+        }
+
         impl fmt::Display for $name {
             fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> { write!(f, "{:02x}", self) }
         }
@@ -18,9 +18,9 @@ pub use self::get_tx_out_response::GetTxOutResponse;
 pub use self::get_tx_out_set_info_response::GetTxOutSetInfoResponse;
 pub use self::hash::{H160, H256, H264};
 pub use self::script::ScriptType;
-pub use self::transaction::{GetRawTransactionResponse, RawTransaction, SignedTransactionInput,
-                            SignedTransactionOutput, Transaction, TransactionInput, TransactionInputEnum,
-                            TransactionInputScript, TransactionOutput, TransactionOutputScript,
+pub use self::transaction::{CoinbaseTransactionInput, GetRawTransactionResponse, RawTransaction,
+                            SignedTransactionInput, SignedTransactionOutput, Transaction, TransactionInput,
+                            TransactionInputEnum, TransactionInputScript, TransactionOutput, TransactionOutputScript,
                             TransactionOutputWithAddress, TransactionOutputWithScriptData, TransactionOutputs};
 pub use self::uint::U256;
 
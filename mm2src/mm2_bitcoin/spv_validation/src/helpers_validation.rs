@@ -273,12 +273,7 @@ fn verify_hash256_merkle(
 ///
 /// # Notes
 /// Wrapper around `bitcoin_spv::validatespv::prove`
-pub(crate) fn merkle_prove(
-    txid: H256,
-    merkle_root: H256,
-    intermediate_nodes: Vec<H256>,
-    index: u64,
-) -> Result<(), SPVError> {
+pub fn merkle_prove(txid: H256, merkle_root: H256, intermediate_nodes: Vec<H256>, index: u64) -> Result<(), SPVError> {
     if txid == merkle_root && index == 0 && intermediate_nodes.is_empty() {
         return Ok(());
     }
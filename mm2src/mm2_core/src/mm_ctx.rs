@@ -83,6 +83,8 @@ pub struct MmCtx {
     /// The context belonging to the `ordermatch` mod: `OrdermatchContext`.
     pub ordermatch_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
     pub rate_limit_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
+    /// The context belonging to the `api_keys` mod: `ApiKeysContext`.
+    pub api_keys_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
     pub simple_market_maker_bot_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
     pub dispatcher_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
     pub message_service_ctx: Mutex<Option<Arc<dyn Any + 'static + Send + Sync>>>,
@@ -128,6 +130,7 @@ impl MmCtx {
             stop_listeners: Mutex::new(Vec::new()),
             ordermatch_ctx: Mutex::new(None),
             rate_limit_ctx: Mutex::new(None),
+            api_keys_ctx: Mutex::new(None),
             simple_market_maker_bot_ctx: Mutex::new(None),
             dispatcher_ctx: Mutex::new(None),
             message_service_ctx: Mutex::new(None),
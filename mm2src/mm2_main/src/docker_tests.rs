@@ -26,6 +26,9 @@ extern crate serde_json;
 extern crate serde_derive;
 #[cfg(test)]
 #[macro_use]
+extern crate rpc_schema_derive;
+#[cfg(test)]
+#[macro_use]
 extern crate serialization_derive;
 #[cfg(test)]
 #[macro_use]
@@ -1611,6 +1614,84 @@ mod docker_tests {
         block_on(mm_alice.stop()).unwrap();
     }
 
+    #[test]
+    fn test_simulate_swap() {
+        let priv_key = SecretKey::new(&mut rand6::thread_rng());
+
+        let (_ctx, mycoin) = utxo_coin_from_privkey("MYCOIN", &priv_key[..]);
+        let my_address = mycoin.my_address().expect("!my_address");
+        fill_address(&mycoin, &my_address, 10.into(), 30);
+
+        let (_ctx, mycoin1) = utxo_coin_from_privkey("MYCOIN1", &priv_key[..]);
+        let my_address = mycoin1.my_address().expect("!my_address");
+        fill_address(&mycoin1, &my_address, 20.into(), 30);
+
+        let coins = json!([
+            {"coin":"MYCOIN","asset":"MYCOIN","txversion":4,"overwintered":1,"txfee":1000,"protocol":{"type":"UTXO"}},
+            {"coin":"MYCOIN1","asset":"MYCOIN1","txversion":4,"overwintered":1,"txfee":2000,"protocol":{"type":"UTXO"}},
+        ]);
+        let mm = MarketMakerIt::start(
+            json! ({
+                "gui": "nogui",
+                "netid": 9000,
+                "dht": "on",  // Enable DHT without delay.
+                "passphrase": format!("0x{}", hex::encode(&priv_key[..])),
+                "coins": coins,
+                "rpc_password": "pass",
+                "i_am_seed": true,
+            }),
+            "pass".to_string(),
+            None,
+        )
+        .unwrap();
+        let (_dump_log, _dump_dashboard) = mm_dump(&mm.log_path);
+
+        log!("{:?}", block_on(enable_native(&mm, "MYCOIN1", &[])));
+        log!("{:?}", block_on(enable_native(&mm, "MYCOIN", &[])));
+
+        let rc = block_on(mm.rpc(&json!({
+            "userpass": mm.userpass,
+            "mmrpc": "2.0",
+            "method": "simulate_swap",
+            "params": {
+                "base": "MYCOIN",
+                "rel": "MYCOIN1",
+                "price": 2,
+                "volume": 1,
+            },
+        })))
+        .unwrap();
+        assert!(rc.0.is_success(), "!simulate_swap: {}", rc.1);
+        let response: Json = json::from_str(&rc.1).unwrap();
+        let result = &response["result"];
+        assert_eq!(result["success"], Json::Bool(true), "{}", rc.1);
+        assert_eq!(result["maker"]["coin"], "MYCOIN");
+        assert_eq!(result["taker"]["coin"], "MYCOIN1");
+        assert!(result["maker"]["payment_tx_hex"].is_string());
+        assert!(result["taker"]["payment_tx_hex"].is_string());
+
+        // The simulation must not broadcast anything.
+        let balance = mycoin.my_balance().wait().unwrap();
+        assert_eq!(balance.spendable, 10.into());
+        let balance = mycoin1.my_balance().wait().unwrap();
+        assert_eq!(balance.spendable, 20.into());
+
+        let rc = block_on(mm.rpc(&json!({
+            "userpass": mm.userpass,
+            "mmrpc": "2.0",
+            "method": "simulate_swap",
+            "params": {
+                "base": "MYCOIN",
+                "rel": "MYCOIN",
+                "price": 1,
+                "volume": 1,
+            },
+        })))
+        .unwrap();
+        assert!(!rc.0.is_success(), "simulate_swap success, but should fail: {}", rc.1);
+        block_on(mm.stop()).unwrap();
+    }
+
     #[test]
     fn test_maker_trade_preimage() {
         let priv_key = SecretKey::new(&mut rand6::thread_rng());
@@ -1,25 +1,51 @@
+use crate::mm2::lp_kmd_rewards::KmdRewardsClaimed;
 use crate::mm2::lp_ordermatch::TradingBotEvent;
-use crate::mm2::lp_swap::MakerSwapStatusChanged;
+use crate::mm2::lp_swap::{MakerSwapStatusChanged, SwapFundsRecovered};
 use async_std::sync::RwLock;
+#[cfg(not(target_arch = "wasm32"))] use async_trait::async_trait;
+#[cfg(not(target_arch = "wasm32"))] use common::log::error;
+#[cfg(not(target_arch = "wasm32"))]
+use mm2_core::event_dispatcher::EventListener;
 use mm2_core::{event_dispatcher::{Dispatcher, EventUniqueId},
                mm_ctx::{from_ctx, MmArc}};
 use std::any::TypeId;
+#[cfg(not(target_arch = "wasm32"))] use std::ffi::CString;
+#[cfg(not(target_arch = "wasm32"))] use std::os::raw::c_char;
 use std::sync::Arc;
 
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct StopCtxEvent;
 
 impl StopCtxEvent {
     pub fn event_id() -> TypeId { TypeId::of::<StopCtxEvent>() }
 }
 
-#[derive(Clone)]
+/// The events are serialized as `{"type": "<variant>", "data": <event>}` when sent to the library host.
+#[derive(Clone, Serialize)]
+#[serde(tag = "type", content = "data")]
 pub enum LpEvents {
+    KmdRewardsClaimed(KmdRewardsClaimed),
     MakerSwapStatusChanged(MakerSwapStatusChanged),
     StopCtxEvent(StopCtxEvent),
+    SwapFundsRecovered(SwapFundsRecovered),
     TradingBotEvent(TradingBotEvent),
 }
 
+impl LpEvents {
+    /// The IDs of all the events, for the listeners interested in every event.
+    #[allow(dead_code)] // Used by the native library only.
+    pub fn event_ids() -> Vec<TypeId> {
+        let mut ids = vec![
+            KmdRewardsClaimed::event_id(),
+            MakerSwapStatusChanged::event_id(),
+            StopCtxEvent::event_id(),
+            SwapFundsRecovered::event_id(),
+        ];
+        ids.extend(TradingBotEvent::event_ids());
+        ids
+    }
+}
+
 impl From<TradingBotEvent> for LpEvents {
     fn from(evt: TradingBotEvent) -> Self { LpEvents::TradingBotEvent(evt) }
 }
@@ -31,8 +57,10 @@ impl From<StopCtxEvent> for LpEvents {
 impl EventUniqueId for LpEvents {
     fn event_id(&self) -> TypeId {
         match self {
+            LpEvents::KmdRewardsClaimed(_) => KmdRewardsClaimed::event_id(),
             LpEvents::MakerSwapStatusChanged(_) => MakerSwapStatusChanged::event_id(),
             LpEvents::StopCtxEvent(_) => StopCtxEvent::event_id(),
+            LpEvents::SwapFundsRecovered(_) => SwapFundsRecovered::event_id(),
             LpEvents::TradingBotEvent(event) => event.event_id(),
         }
     }
@@ -56,3 +84,87 @@ pub async fn dispatch_lp_event(ctx: MmArc, event: LpEvents) {
     let dispatcher_ctx = DispatcherContext::from_ctx(&ctx).unwrap();
     dispatcher_ctx.dispatcher.read().await.dispatch_async(ctx, event).await;
 }
+
+/// The callback of the library host, the `event` pointer is only valid during the callback call.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(dead_code)] // Used by the native library only.
+pub type FfiEventCallback = extern "C" fn(event: *const c_char);
+
+/// Forwards the `LpEvents` to the callback of the library host as `{"type": "<event>", "data": <event data>}` JSON.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(dead_code)] // Used by the native library only.
+pub struct FfiEventListener {
+    /// Returns the callback registered at the moment, the host can replace it while the listener is added.
+    get_callback: fn() -> Option<FfiEventCallback>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl FfiEventListener {
+    #[allow(dead_code)] // Used by the native library only.
+    pub fn new(get_callback: fn() -> Option<FfiEventCallback>) -> FfiEventListener { FfiEventListener { get_callback } }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[async_trait]
+impl EventListener for FfiEventListener {
+    type Event = LpEvents;
+
+    async fn process_event_async(&self, _ctx: MmArc, event: Self::Event) {
+        let callback = match (self.get_callback)() {
+            Some(callback) => callback,
+            None => return,
+        };
+        let event = match serde_json::to_string(&event).map(CString::new) {
+            Ok(Ok(event)) => event,
+            Ok(Err(e)) => {
+                error!("Event contains a nul byte: {}", e);
+                return;
+            },
+            Err(e) => {
+                error!("Error serializing event: {}", e);
+                return;
+            },
+        };
+        callback(event.as_ptr());
+    }
+
+    fn get_desired_events(&self) -> Vec<TypeId> { LpEvents::event_ids() }
+
+    fn listener_id(&self) -> &'static str { "ffi_event_listener" }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use common::block_on;
+    use mm2_core::mm_ctx::MmCtxBuilder;
+    use std::ffi::CStr;
+    use std::sync::Mutex;
+
+    lazy_static! {
+        static ref RECEIVED_EVENTS: Mutex<Vec<String>> = Mutex::new(Vec::new());
+    }
+
+    extern "C" fn record_event(event: *const c_char) {
+        let event = unsafe { CStr::from_ptr(event) }.to_str().unwrap().to_owned();
+        RECEIVED_EVENTS.lock().unwrap().push(event);
+    }
+
+    #[test]
+    fn test_ffi_event_listener() {
+        let ctx = MmCtxBuilder::default().into_mm_arc();
+        // No callback is registered yet.
+        block_on(FfiEventListener::new(|| None).process_event_async(ctx.clone(), StopCtxEvent.into()));
+        assert!(RECEIVED_EVENTS.lock().unwrap().is_empty());
+
+        let dispatcher_ctx = DispatcherContext::from_ctx(&ctx).unwrap();
+        block_on(async {
+            let mut dispatcher = dispatcher_ctx.dispatcher.write().await;
+            dispatcher.add_listener(FfiEventListener::new(|| Some(record_event)));
+        });
+        block_on(dispatch_lp_event(ctx, StopCtxEvent.into()));
+
+        let received = RECEIVED_EVENTS.lock().unwrap().clone();
+        assert_eq!(received, vec![r#"{"type":"StopCtxEvent","data":null}"#.to_owned()]);
+    }
+}
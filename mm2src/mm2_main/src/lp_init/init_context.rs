@@ -1,6 +1,6 @@
 use crate::mm2::lp_native_dex::init_hw::InitHwTaskManagerShared;
 use mm2_core::mm_ctx::{from_ctx, MmArc};
-use rpc_task::RpcTaskManager;
+use rpc_task::{RpcTaskInfo, RpcTaskManager};
 use std::sync::Arc;
 
 pub struct MmInitContext {
@@ -16,4 +16,10 @@ impl MmInitContext {
             })
         })
     }
+
+    /// Returns the short descriptions of all RPC tasks spawned on the node initialization.
+    pub fn rpc_tasks_info(&self) -> Result<Vec<RpcTaskInfo>, String> {
+        let task_manager = try_s!(self.init_hw_task_manager.lock());
+        Ok(task_manager.tasks_info("init_trezor"))
+    }
 }
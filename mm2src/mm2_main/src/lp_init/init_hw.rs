@@ -8,7 +8,8 @@ use derive_more::Display;
 use http::StatusCode;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
-use rpc_task::rpc_common::{InitRpcTaskResponse, RpcTaskStatusError, RpcTaskStatusRequest, RpcTaskUserActionError};
+use rpc_task::rpc_common::{CancelRpcTaskError, CancelRpcTaskRequest, InitRpcTaskResponse, RpcTaskStatusError,
+                           RpcTaskStatusRequest, RpcTaskUserActionError};
 use rpc_task::{RpcTask, RpcTaskError, RpcTaskHandle, RpcTaskManager, RpcTaskManagerShared, RpcTaskStatus, RpcTaskTypes};
 use std::time::Duration;
 
@@ -22,7 +23,7 @@ pub type InitHwTaskManagerShared = RpcTaskManagerShared<InitHwTask>;
 pub type InitHwStatus = RpcTaskStatus<SuccessResponse, InitHwError, InitHwInProgressStatus, InitHwAwaitingStatus>;
 type InitHwTaskHandle = RpcTaskHandle<InitHwTask>;
 
-#[derive(Clone, Display, Serialize, SerializeErrorType)]
+#[derive(Clone, Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum InitHwError {
     /* ----------- Trezor device errors ----------- */
@@ -138,7 +139,7 @@ impl RpcTask for InitHwTask {
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct InitTrezorRequest;
 
 pub async fn init_trezor(ctx: MmArc, _req: InitTrezorRequest) -> MmResult<InitRpcTaskResponse, InitHwError> {
@@ -174,3 +175,16 @@ pub async fn init_trezor_user_action(
     task_manager.on_user_action(req.task_id, req.user_action)?;
     Ok(SuccessResponse::new())
 }
+
+pub async fn init_trezor_cancel(
+    ctx: MmArc,
+    req: CancelRpcTaskRequest,
+) -> MmResult<SuccessResponse, CancelRpcTaskError> {
+    let init_ctx = MmInitContext::from_ctx(&ctx).map_to_mm(CancelRpcTaskError::Internal)?;
+    let mut task_manager = init_ctx
+        .init_hw_task_manager
+        .lock()
+        .map_to_mm(|e| CancelRpcTaskError::Internal(e.to_string()))?;
+    task_manager.cancel_task(req.task_id)?;
+    Ok(SuccessResponse::new())
+}
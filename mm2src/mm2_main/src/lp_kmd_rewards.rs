@@ -0,0 +1,145 @@
+//! The background service claiming the accrued KMD rewards.
+//!
+//! The service periodically checks the rewards accrued by the KMD unspent outputs and sends them back to the wallet
+//! address once the rewards exceed the configured threshold or some of them are about to stop accruing.
+//! The outputs covering the amount locked by the active swaps are never spent.
+//! Every claim is added to the transaction history and reported with the [`KmdRewardsClaimed`] event
+//! and the message service notification.
+
+use crate::mm2::lp_dispatcher::{dispatch_lp_event, LpEvents};
+use crate::mm2::lp_message_service::{MessageServiceContext, KMD_REWARDS_ROOM_ID};
+use crate::mm2::lp_swap::get_locked_amount;
+use coins::my_tx_history_v2::{CoinWithTxHistoryV2, TxHistoryStorage};
+use coins::tx_history_storage::TxHistoryStorageBuilder;
+use coins::utxo::kmd_rewards_claim::{claim_kmd_rewards_if_required, KmdRewardsClaimParams};
+use coins::utxo::utxo_standard::UtxoStandardCoin;
+use coins::{lp_coinfind, MmCoinEnum, TransactionDetails};
+use common::executor::Timer;
+use common::log::{error, info, warn};
+use mm2_core::mm_ctx::{MmArc, MmWeak};
+use mm2_number::BigDecimal;
+use serde_json::{self as json};
+use std::any::TypeId;
+
+const KMD: &str = "KMD";
+/// The default interval between the rewards checks, in seconds.
+const DEFAULT_KMD_REWARDS_CLAIM_INTERVAL: f64 = 3600.;
+
+/// The `kmd_rewards_claim` config object enabling the service.
+#[derive(Deserialize)]
+struct KmdRewardsClaimConf {
+    #[serde(flatten)]
+    params: KmdRewardsClaimParams,
+    #[serde(default = "default_interval")]
+    interval: f64,
+}
+
+fn default_interval() -> f64 { DEFAULT_KMD_REWARDS_CLAIM_INTERVAL }
+
+#[derive(Clone, Debug, Serialize)]
+pub struct KmdRewardsClaimed {
+    pub coin: String,
+    pub tx_hash: String,
+    pub amount: BigDecimal,
+}
+
+impl KmdRewardsClaimed {
+    pub fn event_id() -> TypeId { TypeId::of::<KmdRewardsClaimed>() }
+}
+
+/// Claims the KMD rewards until the context is stopped.
+/// The service is enabled by the `kmd_rewards_claim` config object:
+/// `{"min_rewards": "0.1", "expiry_margin": 259200, "interval": 3600}`, all the fields are optional.
+pub async fn kmd_rewards_claim_loop(ctx_weak: MmWeak) {
+    loop {
+        let interval = {
+            let ctx = match MmArc::from_weak(&ctx_weak) {
+                Some(ctx) => ctx,
+                None => return,
+            };
+            if ctx.is_stopping() {
+                break;
+            }
+            if ctx.conf["kmd_rewards_claim"].is_null() {
+                break;
+            }
+            let conf: KmdRewardsClaimConf = match json::from_value(ctx.conf["kmd_rewards_claim"].clone()) {
+                Ok(conf) => conf,
+                Err(e) => {
+                    error!("Invalid 'kmd_rewards_claim' config: {}", e);
+                    break;
+                },
+            };
+
+            claim_kmd_rewards(&ctx, &conf.params).await;
+            conf.interval
+        };
+        Timer::sleep(interval).await;
+    }
+}
+
+async fn claim_kmd_rewards(ctx: &MmArc, params: &KmdRewardsClaimParams) {
+    // Wait until KMD is activated by the user.
+    let coin = match lp_coinfind(ctx, KMD).await {
+        Ok(Some(MmCoinEnum::UtxoCoin(coin))) => coin,
+        Ok(_) => return,
+        Err(e) => {
+            error!("Error finding {}: {}", KMD, e);
+            return;
+        },
+    };
+
+    let locked_amount = get_locked_amount(ctx, KMD).to_decimal();
+    let claimed = match claim_kmd_rewards_if_required(&coin, params, &locked_amount).await {
+        Ok(Some(claimed)) => claimed,
+        Ok(None) => return,
+        Err(e) => {
+            warn!("Error claiming {} rewards: {}", KMD, e);
+            return;
+        },
+    };
+
+    if let Err(e) = save_claim_to_history(ctx, &coin, claimed.tx_details).await {
+        error!("Error saving the {} rewards claim to the history: {}", KMD, e);
+    }
+
+    let event = KmdRewardsClaimed {
+        coin: KMD.to_owned(),
+        tx_hash: claimed.tx_hash,
+        amount: claimed.amount,
+    };
+    let msg = format!(
+        "{} rewards {} claimed with tx {}",
+        event.coin, event.amount, event.tx_hash
+    );
+    info!("{}", msg);
+    dispatch_lp_event(ctx.clone(), LpEvents::KmdRewardsClaimed(event)).await;
+
+    let message_service_ctx = MessageServiceContext::from_ctx(ctx).unwrap();
+    if let Err(e) = message_service_ctx.send_message(msg, KMD_REWARDS_ROOM_ID, false).await {
+        error!("Error sending the KMD rewards notification: {}", e);
+    }
+}
+
+/// Adds the claiming transaction to the history right away, so it's shown before the history loop fetches it.
+/// The transaction is unconfirmed, so its height is updated by the history loop later.
+/// Does nothing if the history is not enabled for the coin.
+async fn save_claim_to_history(
+    ctx: &MmArc,
+    coin: &UtxoStandardCoin,
+    tx_details: TransactionDetails,
+) -> Result<(), String> {
+    let storage = try_s!(TxHistoryStorageBuilder::new(ctx).build());
+    let wallet_id = coin.history_wallet_id();
+    let is_initialized = storage
+        .is_initialized_for(&wallet_id)
+        .await
+        .map_err(|e| ERRL!("{:?}", e))?;
+    if !is_initialized {
+        return Ok(());
+    }
+    storage
+        .add_transactions_to_history(&wallet_id, vec![tx_details])
+        .await
+        .map_err(|e| ERRL!("{:?}", e))
+}
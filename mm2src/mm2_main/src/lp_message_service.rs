@@ -1,57 +1,140 @@
+#[cfg(not(target_arch = "wasm32"))]
+#[path = "notification/email/email.rs"]
+pub mod email;
+#[path = "notification/matrix/matrix.rs"] pub mod matrix;
 #[path = "notification/telegram/telegram.rs"] pub mod telegram;
+#[path = "notification/webhook/webhook.rs"] pub mod webhook;
 
+#[cfg(not(target_arch = "wasm32"))]
+use crate::mm2::lp_message_service::email::{EmailCfg, EmailClient, EmailError};
+use crate::mm2::lp_message_service::matrix::{MatrixClient, MatrixError, RoomIdRegistry};
 use crate::mm2::lp_message_service::telegram::{ChatIdRegistry, TelegramError, TgClient};
+use crate::mm2::lp_message_service::webhook::{WebhookCfg, WebhookClient, WebhookError};
 use async_trait::async_trait;
+use coins::{MarketCoinOps, MmCoinEnum};
+use common::log::{error, warn};
 use derive_more::Display;
+use futures::compat::Future01CompatExt;
 use futures::lock::Mutex as AsyncMutex;
 use mm2_core::mm_ctx::from_ctx;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
+use mm2_number::BigDecimal;
 use serde_json::{self as json};
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
 pub type MessageResult<T> = Result<T, MmError<MessageError>>;
 pub const MAKER_BOT_ROOM_ID: &str = "maker_bot";
 pub const DEFAULT_ROOM_ID: &str = "default";
+pub const KMD_REWARDS_ROOM_ID: &str = "kmd_rewards";
+pub const SWAP_RECOVERY_ROOM_ID: &str = "swap_recovery";
+pub const SWAP_FINISHED_ROOM_ID: &str = "swap_finished";
+pub const SWAP_FAILED_ROOM_ID: &str = "swap_failed";
+pub const LOW_BALANCE_ROOM_ID: &str = "low_balance";
 
 #[derive(Debug, Deserialize, Display, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum MessageError {
     #[display(fmt = "{}", _0)]
     TelegramError(TelegramError),
+    #[display(fmt = "{}", _0)]
+    WebhookError(WebhookError),
+    #[display(fmt = "{}", _0)]
+    MatrixError(MatrixError),
+    #[cfg(not(target_arch = "wasm32"))]
+    #[display(fmt = "{}", _0)]
+    EmailError(EmailError),
 }
 
 impl From<TelegramError> for MessageError {
     fn from(e: TelegramError) -> Self { MessageError::TelegramError(e) }
 }
 
+impl From<WebhookError> for MessageError {
+    fn from(e: WebhookError) -> Self { MessageError::WebhookError(e) }
+}
+
+impl From<MatrixError> for MessageError {
+    fn from(e: MatrixError) -> Self { MessageError::MatrixError(e) }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl From<EmailError> for MessageError {
+    fn from(e: EmailError) -> Self { MessageError::EmailError(e) }
+}
+
 #[async_trait]
 pub trait MessageServiceTraits {
     async fn send_message(&self, message: String, room_id: &str, disable_notification: bool) -> MessageResult<bool>;
 }
 
+type SharedMessageServiceTraits = Arc<dyn MessageServiceTraits + Send + Sync>;
+
+/// Sends the message via all the `services`.
+/// A failure of one service doesn't prevent the others from receiving the message, the last error is returned.
+async fn send_message_via(
+    services: &[SharedMessageServiceTraits],
+    message: String,
+    room_id: &str,
+    disable_notification: bool,
+) -> MessageResult<bool> {
+    let mut last_error = None;
+    for service in services.iter() {
+        if let Err(e) = service
+            .send_message(message.clone(), room_id, disable_notification)
+            .await
+        {
+            warn!("Error sending the '{}' room message: {}", room_id, e);
+            last_error = Some(e);
+        }
+    }
+    match last_error {
+        Some(e) => Err(e),
+        None => Ok(true),
+    }
+}
+
 #[derive(Default)]
 pub struct MessageService {
-    services: Vec<Box<dyn MessageServiceTraits + Send + Sync>>,
+    services: Vec<SharedMessageServiceTraits>,
+    /// The balances below which the [`LOW_BALANCE_ROOM_ID`] messages are sent by the coin tickers.
+    low_balance_thresholds: HashMap<String, BigDecimal>,
+    /// The coins which low balance has been notified about already.
+    /// A coin is removed once its balance gets back above the threshold, so the next drop is notified again.
+    low_balance_notified: HashSet<String>,
 }
 
 impl MessageService {
+    /// Sends the message via all attached services.
+    /// Prefer [`MessageServiceContext::send_message`] that doesn't hold the lock while the message is being sent.
     pub async fn send_message(
         &self,
         message: String,
         room_id: &str,
         disable_notification: bool,
     ) -> MessageResult<bool> {
-        for service in self.services.iter() {
-            service
-                .send_message(message.clone(), room_id, disable_notification)
-                .await?;
+        send_message_via(&self.services, message, room_id, disable_notification).await
+    }
+
+    /// Returns the message to send if the `balance` of the `ticker` coin dropped below the configured threshold.
+    fn low_balance_message(&mut self, ticker: &str, balance: &BigDecimal) -> Option<String> {
+        let threshold = self.low_balance_thresholds.get(ticker)?;
+        if balance >= threshold {
+            self.low_balance_notified.remove(ticker);
+            return None;
         }
-        Ok(true)
+        if !self.low_balance_notified.insert(ticker.to_owned()) {
+            return None;
+        }
+        Some(format!(
+            "{} balance {} is below the threshold {}",
+            ticker, balance, threshold
+        ))
     }
 
     pub fn attach_service(&mut self, service: Box<dyn MessageServiceTraits + Send + Sync>) -> &MessageService {
-        self.services.push(service);
+        self.services.push(Arc::from(service));
         self
     }
 
@@ -74,11 +157,62 @@ impl MessageServiceContext {
             Ok(MessageServiceContext::default())
         })))
     }
+
+    /// Sends the message via all attached services.
+    /// The lock is released before sending, so a slow service doesn't block the other notifications.
+    pub async fn send_message(
+        &self,
+        message: String,
+        room_id: &str,
+        disable_notification: bool,
+    ) -> MessageResult<bool> {
+        let services = self.message_service.lock().await.services.clone();
+        send_message_via(&services, message, room_id, disable_notification).await
+    }
+}
+
+/// Sends the [`LOW_BALANCE_ROOM_ID`] message if the spendable balance of the `coin` dropped below the threshold
+/// configured in the `low_balance_thresholds` of the `message_service_cfg`.
+pub async fn check_low_balance(ctx: &MmArc, coin: &MmCoinEnum) {
+    let ticker = coin.ticker();
+    let message_service_ctx = MessageServiceContext::from_ctx(ctx).unwrap();
+    if !message_service_ctx
+        .message_service
+        .lock()
+        .await
+        .low_balance_thresholds
+        .contains_key(ticker)
+    {
+        return;
+    }
+
+    let balance = match coin.my_spendable_balance().compat().await {
+        Ok(balance) => balance,
+        Err(e) => {
+            error!("Error getting {} balance: {}", ticker, e);
+            return;
+        },
+    };
+    let maybe_msg = message_service_ctx
+        .message_service
+        .lock()
+        .await
+        .low_balance_message(ticker, &balance);
+    if let Some(msg) = maybe_msg {
+        let _ = message_service_ctx.send_message(msg, LOW_BALANCE_ROOM_ID, false).await;
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
 pub struct MessageServiceCfg {
     telegram: Option<Telegram>,
+    #[serde(default)]
+    webhooks: Vec<WebhookCfg>,
+    matrix: Option<Matrix>,
+    #[cfg(not(target_arch = "wasm32"))]
+    email: Option<EmailCfg>,
+    #[serde(default)]
+    low_balance_thresholds: HashMap<String, BigDecimal>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -87,6 +221,13 @@ pub struct Telegram {
     chat_registry: ChatIdRegistry,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Matrix {
+    homeserver_url: String,
+    access_token: String,
+    room_registry: RoomIdRegistry,
+}
+
 #[derive(Display)]
 pub enum InitMessageServiceError {
     #[display(fmt = "Error deserializing '{}' config field: {}", field, error)]
@@ -107,7 +248,25 @@ pub async fn init_message_service(ctx: &MmArc) -> Result<(), MmError<InitMessage
         if let Some(telegram) = message_service_cfg.telegram {
             let tg_client = TgClient::new(telegram.api_key, None, telegram.chat_registry);
             message_service.attach_service(Box::new(tg_client));
-            let _ = message_service
+        }
+        for webhook_cfg in message_service_cfg.webhooks {
+            message_service.attach_service(Box::new(WebhookClient::new(webhook_cfg)));
+        }
+        if let Some(matrix) = message_service_cfg.matrix {
+            let matrix_client = MatrixClient::new(matrix.homeserver_url, matrix.access_token, matrix.room_registry);
+            message_service.attach_service(Box::new(matrix_client));
+        }
+        #[cfg(not(target_arch = "wasm32"))]
+        if let Some(email_cfg) = message_service_cfg.email {
+            message_service.attach_service(Box::new(EmailClient::new(email_cfg)));
+        }
+        message_service.low_balance_thresholds = message_service_cfg.low_balance_thresholds;
+
+        let has_services = !message_service.services.is_empty();
+        drop(message_service);
+
+        if has_services {
+            let _ = message_service_ctx
                 .send_message(
                     "message service successfully initialized".to_string(),
                     DEFAULT_ROOM_ID,
@@ -124,6 +283,7 @@ mod message_service_tests {
     use crate::mm2::lp_message_service::telegram::{ChatIdRegistry, TgClient};
     use crate::mm2::lp_message_service::MessageService;
     use common::block_on;
+    use mm2_number::BigDecimal;
     use std::collections::HashMap;
     use std::env::var;
 
@@ -154,4 +314,32 @@ mod message_service_tests {
             assert!(res.unwrap());
         }
     }
+
+    #[test]
+    fn test_low_balance_message() {
+        let mut message_service = MessageService::new();
+        message_service
+            .low_balance_thresholds
+            .insert("RICK".to_owned(), BigDecimal::from(10));
+
+        assert!(message_service
+            .low_balance_message("MORTY", &BigDecimal::from(1))
+            .is_none());
+        assert!(message_service
+            .low_balance_message("RICK", &BigDecimal::from(11))
+            .is_none());
+        assert!(message_service
+            .low_balance_message("RICK", &BigDecimal::from(9))
+            .is_some());
+        // Not repeated until the balance recovers.
+        assert!(message_service
+            .low_balance_message("RICK", &BigDecimal::from(8))
+            .is_none());
+        assert!(message_service
+            .low_balance_message("RICK", &BigDecimal::from(10))
+            .is_none());
+        assert!(message_service
+            .low_balance_message("RICK", &BigDecimal::from(5))
+            .is_some());
+    }
 }
@@ -19,7 +19,7 @@
 //
 
 use bitcrypto::sha256;
-use coins::register_balance_update_handler;
+use coins::{balance_metrics_loop, register_balance_update_handler, register_reserved_amounts_provider};
 use common::executor::{spawn, spawn_boxed, Timer};
 use common::log::{info, warn};
 use crypto::{CryptoCtx, CryptoInitError, HwError, HwProcessingError};
@@ -37,12 +37,15 @@ use std::time::Duration;
 
 #[cfg(not(target_arch = "wasm32"))]
 use crate::mm2::database::init_and_migrate_db;
+use crate::mm2::lp_kmd_rewards::kmd_rewards_claim_loop;
 use crate::mm2::lp_message_service::{init_message_service, InitMessageServiceError};
 use crate::mm2::lp_network::{lp_network_ports, p2p_event_process_loop, NetIdError, P2PContext};
 use crate::mm2::lp_ordermatch::{broadcast_maker_orders_keep_alive_loop, clean_memory_loop, init_ordermatch_context,
                                 lp_ordermatch_loop, orders_kick_start, BalanceUpdateOrdermatchHandler,
-                                OrdermatchInitError};
-use crate::mm2::lp_swap::{running_swaps_num, swap_kick_starts};
+                                OrdermatchInitError, ReservedAmountsOrdermatchProvider};
+use crate::mm2::lp_swap::{running_swaps_num, swap_kick_starts, swap_recovery_loop};
+use crate::mm2::rpc::api_keys::ApiKeysContext;
+use crate::mm2::rpc::rate_limiter::RateLimitContext;
 use crate::mm2::rpc::spawn_rpc;
 use crate::mm2::{MM_DATETIME, MM_VERSION};
 
@@ -52,7 +55,7 @@ cfg_native! {
     use db_common::sqlite::rusqlite::Error as SqlError;
 }
 
-#[path = "lp_init/init_context.rs"] mod init_context;
+#[path = "lp_init/init_context.rs"] pub mod init_context;
 #[path = "lp_init/init_hw.rs"] pub mod init_hw;
 
 const NETID_7777_SEEDNODES: [&str; 3] = ["seed1.defimania.live", "seed2.defimania.live", "seed3.defimania.live"];
@@ -313,6 +316,9 @@ fn fix_directories(ctx: &MmCtx) -> MmInitResult<()> {
     if !ensure_dir_is_writable(&dbdir.join("ORDERS").join("MY").join("HISTORY")) {
         return MmError::err(MmInitError::db_directory_is_not_writable("ORDERS/MY/HISTORY"));
     }
+    if !ensure_dir_is_writable(&dbdir.join("ORDERS").join("MY").join("SPLIT_TAKER")) {
+        return MmError::err(MmInitError::db_directory_is_not_writable("ORDERS/MY/SPLIT_TAKER"));
+    }
     if !ensure_dir_is_writable(&dbdir.join("TX_CACHE")) {
         return MmError::err(MmInitError::db_directory_is_not_writable("TX_CACHE"));
     }
@@ -351,6 +357,8 @@ fn migrate_db(ctx: &MmArc) -> MmInitResult<()> {
 fn migration_1(_ctx: &MmArc) {}
 
 pub async fn lp_init_continue(ctx: MmArc) -> MmInitResult<()> {
+    #[cfg(not(target_arch = "wasm32"))]
+    init_global_proxy(&ctx)?;
     init_ordermatch_context(&ctx)?;
     init_p2p(ctx.clone()).await?;
 
@@ -367,10 +375,22 @@ pub async fn lp_init_continue(ctx: MmArc) -> MmInitResult<()> {
         migrate_db(&ctx)?;
     }
 
+    // Load the API keys and the rate limits before the RPC is started to report the config errors early.
+    ApiKeysContext::from_ctx(&ctx).map_to_mm(|error| MmInitError::ErrorDeserializingConfig {
+        field: "api_keys".to_owned(),
+        error,
+    })?;
+    RateLimitContext::from_ctx(&ctx).map_to_mm(|error| MmInitError::ErrorDeserializingConfig {
+        field: "rpc_rate_limit".to_owned(),
+        error,
+    })?;
+
     init_message_service(&ctx).await?;
 
     let balance_update_ordermatch_handler = BalanceUpdateOrdermatchHandler::new(ctx.clone());
     register_balance_update_handler(ctx.clone(), Box::new(balance_update_ordermatch_handler)).await;
+    let reserved_amounts_provider = ReservedAmountsOrdermatchProvider::new(ctx.clone());
+    register_reserved_amounts_provider(ctx.clone(), Box::new(reserved_amounts_provider)).await;
 
     ctx.initialized.pin(true).map_to_mm(MmInitError::Internal)?;
 
@@ -383,6 +403,27 @@ pub async fn lp_init_continue(ctx: MmArc) -> MmInitResult<()> {
     spawn(broadcast_maker_orders_keep_alive_loop(ctx.clone()));
 
     spawn(clean_memory_loop(ctx.weak()));
+
+    spawn(swap_recovery_loop(ctx.weak()));
+
+    spawn(kmd_rewards_claim_loop(ctx.weak()));
+
+    spawn(balance_metrics_loop(ctx.weak()));
+
+    #[cfg(not(target_arch = "wasm32"))]
+    spawn(coins_activation::resume_z_coin_activations(ctx.clone()));
+    Ok(())
+}
+
+/// Routes the HTTP requests like the price and the notification ones through the `proxy` if it's configured.
+/// The coins use the global `proxy` unless they have their own.
+#[cfg(not(target_arch = "wasm32"))]
+fn init_global_proxy(ctx: &MmArc) -> MmInitResult<()> {
+    let proxy = json::from_value(ctx.conf["proxy"].clone()).map_to_mm(|e| MmInitError::ErrorDeserializingConfig {
+        field: "proxy".to_owned(),
+        error: e.to_string(),
+    })?;
+    mm2_net::native_http::set_global_proxy(proxy);
     Ok(())
 }
 
@@ -600,8 +641,6 @@ where
 
 #[cfg(not(target_arch = "wasm32"))]
 fn wss_certs(ctx: &MmArc) -> P2PResult<Option<WssCerts>> {
-    use futures_rustls::rustls;
-
     #[derive(Deserialize)]
     struct WssCertsInfo {
         server_priv_key: PathBuf,
@@ -617,16 +656,32 @@ fn wss_certs(ctx: &MmArc) -> P2PResult<Option<WssCerts>> {
             error: e.to_string(),
         })?;
 
+    let (server_priv_key, certs) = read_priv_key_and_certs(certs.server_priv_key, certs.certificate)?;
+    Ok(Some(WssCerts { server_priv_key, certs }))
+}
+
+/// Reads the PEM-encoded private key and certificate chain.
+/// Used by both the P2P WSS transport and the RPC TLS server.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_priv_key_and_certs(
+    server_priv_key: PathBuf,
+    certificate: PathBuf,
+) -> P2PResult<(
+    futures_rustls::rustls::PrivateKey,
+    Vec<futures_rustls::rustls::Certificate>,
+)> {
+    use futures_rustls::rustls;
+
     // First, try to extract the all PKCS8 private keys
     let mut server_priv_keys = extract_cert_from_file(
-        certs.server_priv_key.clone(),
+        server_priv_key.clone(),
         rustls::internal::pemfile::pkcs8_private_keys,
         "Private key, DER-encoded ASN.1 in either PKCS#8 or PKCS#1 format".to_owned(),
     )
     // or try to extract all PKCS1 private keys
     .or_else(|_| {
         extract_cert_from_file(
-            certs.server_priv_key.clone(),
+            server_priv_key.clone(),
             rustls::internal::pemfile::rsa_private_keys,
             "Private key, DER-encoded ASN.1 in either PKCS#8 or PKCS#1 format".to_owned(),
         )
@@ -634,10 +689,16 @@ fn wss_certs(ctx: &MmArc) -> P2PResult<Option<WssCerts>> {
     // `extract_cert_from_file` returns either non-empty vector or an error.
     let server_priv_key = server_priv_keys.remove(0);
 
-    let certs = extract_cert_from_file(
-        certs.certificate,
-        rustls::internal::pemfile::certs,
+    let certs = read_certs(certificate)?;
+    Ok((server_priv_key, certs))
+}
+
+/// Reads the non-empty PEM-encoded certificate chain.
+#[cfg(not(target_arch = "wasm32"))]
+pub(crate) fn read_certs(certificate: PathBuf) -> P2PResult<Vec<futures_rustls::rustls::Certificate>> {
+    extract_cert_from_file(
+        certificate,
+        futures_rustls::rustls::internal::pemfile::certs,
         "Certificate, DER-encoded X.509 format".to_owned(),
-    )?;
-    Ok(Some(WssCerts { server_priv_key, certs }))
+    )
 }
@@ -25,12 +25,12 @@ use best_orders::BestOrdersAction;
 use blake2::digest::{Update, VariableOutput};
 use blake2::Blake2bVar;
 use coins::utxo::{compressed_pub_key_from_priv_raw, ChecksumType, UtxoAddressFormat};
-use coins::{coin_conf, find_pair, lp_coinfind, BalanceTradeFeeUpdatedHandler, CoinProtocol, CoinsContext,
-            FeeApproxStage, MmCoinEnum};
+use coins::{coin_conf, find_pair, lp_coinfind, lp_coinfind_or_err, BalanceTradeFeeUpdatedHandler, CoinProtocol,
+            CoinsContext, FeeApproxStage, MmCoinEnum, ReservedAmounts, ReservedAmountsProvider};
 use common::executor::{spawn, Timer};
 use common::log::{error, warn, LogOnError};
 use common::time_cache::TimeCache;
-use common::{bits256, log, new_uuid, now_ms, spawn_abortable, AbortOnDropHandle};
+use common::{bits256, log, new_uuid, now_ms, spawn_abortable, AbortOnDropHandle, HttpStatusCode, StatusCode};
 use crypto::privkey::SerializableSecp256k1Keypair;
 use crypto::CryptoCtx;
 use derive_more::Display;
@@ -62,9 +62,11 @@ use uuid::Uuid;
 use crate::mm2::lp_network::{broadcast_p2p_msg, request_any_relay, request_one_peer, subscribe_to_topic, Libp2pPeerId,
                              P2PRequest};
 use crate::mm2::lp_swap::{calc_max_maker_vol, check_balance_for_maker_swap, check_balance_for_taker_swap,
-                          check_other_coin_balance_for_swap, insert_new_swap_to_db, is_pubkey_banned,
-                          lp_atomic_locktime, run_maker_swap, run_taker_swap, AtomicLocktimeVersion, MakerSwap,
-                          RunMakerSwapInput, RunTakerSwapInput, SwapConfirmationsSettings, TakerSwap};
+                          check_other_coin_balance_for_swap, check_secret_hash_algo_compatibility,
+                          fetch_swap_policy_prices, get_locked_amount, insert_new_swap_to_db, is_pubkey_banned,
+                          lp_atomic_locktime, run_maker_swap, run_taker_swap, swap_locktime_multiplier,
+                          swap_policy_requirements, AtomicLocktimeVersion, CheckBalanceError, MakerSwap,
+                          RunMakerSwapInput, RunTakerSwapInput, SwapConfirmationsSettings, SwapPolicyPrices, TakerSwap};
 
 pub use best_orders::{best_orders_rpc, best_orders_rpc_v2};
 use my_orders_storage::{delete_my_maker_order, delete_my_taker_order, save_maker_order_on_update,
@@ -92,6 +94,14 @@ mod my_orders_storage;
 mod order_requests_tracker;
 #[path = "lp_ordermatch/orderbook_depth.rs"] mod orderbook_depth;
 #[path = "lp_ordermatch/orderbook_rpc.rs"] mod orderbook_rpc;
+#[path = "lp_ordermatch/split_taker_order.rs"]
+mod split_taker_order;
+use split_taker_order::SplitTakerOrder;
+pub use split_taker_order::{split_taker_order_status, start_split_taker_order};
+#[path = "lp_ordermatch/trading_rpc.rs"] mod trading_rpc;
+pub use trading_rpc::{buy_rpc_v2, my_orders_rpc_v2, order_status_rpc_v2, sell_rpc_v2, set_price_rpc_v2,
+                      update_maker_order_rpc_v2, OrdermatchRpcError, OrdermatchRpcResult};
+
 #[cfg(all(test, not(target_arch = "wasm32")))]
 #[path = "ordermatch_tests.rs"]
 pub mod ordermatch_tests;
@@ -1062,13 +1072,49 @@ impl BalanceTradeFeeUpdatedHandler for BalanceUpdateOrdermatchHandler {
     }
 }
 
+/// Reports the amounts locked by the active swaps and the available volumes of my maker orders to the coins.
+pub struct ReservedAmountsOrdermatchProvider {
+    ctx: MmWeak,
+}
+
+impl ReservedAmountsOrdermatchProvider {
+    pub fn new(ctx: MmArc) -> Self { ReservedAmountsOrdermatchProvider { ctx: ctx.weak() } }
+}
+
+#[async_trait]
+impl ReservedAmountsProvider for ReservedAmountsOrdermatchProvider {
+    async fn reserved_amounts(&self, ticker: &str) -> ReservedAmounts {
+        let ctx = match MmArc::from_weak(&self.ctx) {
+            Some(ctx) => ctx,
+            None => return ReservedAmounts::default(),
+        };
+        let locked_by_swaps = get_locked_amount(&ctx, ticker).to_decimal();
+
+        let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).unwrap();
+        let my_maker_orders = ordermatch_ctx.maker_orders_ctx.lock().orders.clone();
+        let mut maker_order_volumes = Vec::new();
+        for order_mutex in my_maker_orders.values() {
+            let order = order_mutex.lock().await;
+            // The matched volumes are locked by the swaps already.
+            if order.base == ticker {
+                maker_order_volumes.push(order.available_amount().to_decimal());
+            }
+        }
+
+        ReservedAmounts {
+            locked_by_swaps,
+            maker_order_volumes,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub enum TakerAction {
     Buy,
     Sell,
 }
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, RpcSchema, Serialize)]
 #[cfg_attr(test, derive(Default))]
 pub struct OrderConfirmationsSettings {
     pub base_confs: u64,
@@ -1086,6 +1132,38 @@ impl OrderConfirmationsSettings {
             rel_nota: self.base_nota,
         }
     }
+
+    /// Raises the settings according to the swap policies of the base and rel coins for the given amounts.
+    pub fn with_swap_policies(
+        self,
+        ctx: &MmArc,
+        base_coin: &MmCoinEnum,
+        base_amount: &MmNumber,
+        rel_coin: &MmCoinEnum,
+        rel_amount: &MmNumber,
+        prices: &SwapPolicyPrices,
+    ) -> Result<OrderConfirmationsSettings, String> {
+        let mut settings = self;
+        let base_requirements = try_s!(swap_policy_requirements(
+            ctx,
+            base_coin.ticker(),
+            base_amount,
+            prices.base.as_ref()
+        ));
+        if let Some(requirements) = base_requirements {
+            requirements.raise_confs(&mut settings.base_confs, &mut settings.base_nota);
+        }
+        let rel_requirements = try_s!(swap_policy_requirements(
+            ctx,
+            rel_coin.ticker(),
+            rel_amount,
+            prices.rel.as_ref()
+        ));
+        if let Some(requirements) = rel_requirements {
+            requirements.raise_confs(&mut settings.rel_confs, &mut settings.rel_nota);
+        }
+        Ok(settings)
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -1459,7 +1537,7 @@ impl<'a> TakerOrderBuilder<'a> {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, Eq, PartialEq, RpcSchema, Serialize)]
 #[serde(tag = "type", content = "data")]
 pub enum MatchBy {
     Any,
@@ -1471,7 +1549,7 @@ impl Default for MatchBy {
     fn default() -> Self { MatchBy::Any }
 }
 
-#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Copy, Clone, Debug, Deserialize, Eq, PartialEq, Serialize, RpcSchema)]
 #[serde(tag = "type", content = "data")]
 enum OrderType {
     FillOrKill,
@@ -2390,6 +2468,46 @@ fn collect_orderbook_metrics(ctx: &MmArc, orderbook: &Orderbook) {
     }
 }
 
+type OrderPairMetricKey = (String, String, &'static str);
+
+/// Updates the number of my active orders by pair and type.
+/// The pairs reported previously are kept in `reported_pairs` to reset their gauges once all orders are gone.
+async fn collect_my_orders_metrics(
+    ctx: &MmArc,
+    ordermatch_ctx: &OrdermatchContext,
+    reported_pairs: &mut HashSet<OrderPairMetricKey>,
+) {
+    let mut count_by_pair: HashMap<OrderPairMetricKey, i64> = HashMap::new();
+    let maker_orders: Vec<_> = ordermatch_ctx
+        .maker_orders_ctx
+        .lock()
+        .orders
+        .values()
+        .cloned()
+        .collect();
+    for order in maker_orders {
+        let order = order.lock().await;
+        *count_by_pair
+            .entry((order.base.clone(), order.rel.clone(), "maker"))
+            .or_insert(0) += 1;
+    }
+    for order in ordermatch_ctx.my_taker_orders.lock().await.values() {
+        *count_by_pair
+            .entry((order.request.base.clone(), order.request.rel.clone(), "taker"))
+            .or_insert(0) += 1;
+    }
+    for pair in reported_pairs.drain() {
+        count_by_pair.entry(pair).or_insert(0);
+    }
+
+    for ((base, rel, order_type), count) in count_by_pair {
+        mm_gauge!(ctx.metrics, "orders.active", count, "base" => base.clone(), "rel" => rel.clone(), "type" => order_type);
+        if count > 0 {
+            reported_pairs.insert((base, rel, order_type));
+        }
+    }
+}
+
 #[derive(Default)]
 struct Orderbook {
     /// A map from (base, rel).
@@ -2824,12 +2942,32 @@ fn lp_connect_start_bob(ctx: MmArc, maker_match: MakerMatch, maker_order: MakerO
         let privkey = &ctx.secp256k1_key_pair().private().secret;
         let my_persistent_pub = compressed_pub_key_from_priv_raw(&privkey[..], ChecksumType::DSHA256).unwrap();
         let uuid = maker_match.request.uuid;
+        // the settings sent within MakerReserved are used, so both sides compute the same swap settings
         let my_conf_settings = choose_maker_confs_and_notas(
-            maker_order.conf_settings,
+            maker_match.reserved.conf_settings,
             &maker_match.request,
             &maker_coin,
             &taker_coin,
         );
+        // the multiplier required by my swap policies, the taker adopts it within the swap negotiation
+        let locktime_multiplier = match fetch_swap_policy_prices(&ctx, maker_coin.ticker(), taker_coin.ticker())
+            .await
+            .and_then(|prices| {
+                swap_locktime_multiplier(
+                    &ctx,
+                    maker_coin.ticker(),
+                    maker_match.reserved.get_base_amount(),
+                    taker_coin.ticker(),
+                    maker_match.reserved.get_rel_amount(),
+                    &prices,
+                )
+            }) {
+            Ok(multiplier) => multiplier,
+            Err(e) => {
+                log::error!("Error getting the swap {} locktime multiplier: {}", uuid, e);
+                return;
+            },
+        };
         // detect atomic lock time version implicitly by conf_settings existence in taker request
         let atomic_locktime_v = match maker_match.request.conf_settings {
             Some(_) => {
@@ -2842,7 +2980,8 @@ fn lp_connect_start_bob(ctx: MmArc, maker_match: MakerMatch, maker_order: MakerO
             },
             None => AtomicLocktimeVersion::V1,
         };
-        let lock_time = lp_atomic_locktime(maker_coin.ticker(), taker_coin.ticker(), atomic_locktime_v);
+        let lock_time =
+            lp_atomic_locktime(maker_coin.ticker(), taker_coin.ticker(), atomic_locktime_v) * locktime_multiplier;
         log_tag!(
             ctx,
             "";
@@ -2868,6 +3007,7 @@ fn lp_connect_start_bob(ctx: MmArc, maker_match: MakerMatch, maker_order: MakerO
             maker_coin,
             taker_coin,
             lock_time,
+            locktime_multiplier,
             maker_order.p2p_privkey.map(SerializableSecp256k1Keypair::into_inner),
             MakerSwap::generate_secret().into(),
         );
@@ -2911,8 +3051,29 @@ fn lp_connected_alice(ctx: MmArc, taker_order: TakerOrder, taker_match: TakerMat
         let taker_amount = taker_match.reserved.get_rel_amount().clone();
         let uuid = taker_match.reserved.taker_order_uuid;
 
+        // the settings exchanged within TakerRequest and MakerReserved are used,
+        // so both sides compute the same swap settings
         let my_conf_settings =
             choose_taker_confs_and_notas(&taker_order.request, &taker_match.reserved, &maker_coin, &taker_coin);
+        // the multiplier required by my swap policies, the maker one is adopted within the swap negotiation
+        let locktime_multiplier = match fetch_swap_policy_prices(&ctx, maker_coin.ticker(), taker_coin.ticker())
+            .await
+            .and_then(|prices| {
+                swap_locktime_multiplier(
+                    &ctx,
+                    maker_coin.ticker(),
+                    &maker_amount,
+                    taker_coin.ticker(),
+                    &taker_amount,
+                    &prices,
+                )
+            }) {
+            Ok(multiplier) => multiplier,
+            Err(e) => {
+                log::error!("Error getting the swap {} locktime multiplier: {}", uuid, e);
+                return;
+            },
+        };
         // detect atomic lock time version implicitly by conf_settings existence in maker reserved
         let atomic_locktime_v = match taker_match.reserved.conf_settings {
             Some(_) => {
@@ -2929,7 +3090,8 @@ fn lp_connected_alice(ctx: MmArc, taker_order: TakerOrder, taker_match: TakerMat
             },
             None => AtomicLocktimeVersion::V1,
         };
-        let locktime = lp_atomic_locktime(maker_coin.ticker(), taker_coin.ticker(), atomic_locktime_v);
+        let locktime =
+            lp_atomic_locktime(maker_coin.ticker(), taker_coin.ticker(), atomic_locktime_v) * locktime_multiplier;
         log_tag!(
             ctx,
             "";
@@ -2955,6 +3117,7 @@ fn lp_connected_alice(ctx: MmArc, taker_order: TakerOrder, taker_match: TakerMat
             maker_coin,
             taker_coin,
             locktime,
+            locktime_multiplier,
             taker_order.p2p_privkey.map(SerializableSecp256k1Keypair::into_inner),
         );
         run_taker_swap(RunTakerSwapInput::StartNew(taker_swap), ctx).await
@@ -2967,6 +3130,7 @@ pub async fn lp_ordermatch_loop(ctx: MmArc) {
         .secp256k1_pubkey_hex();
 
     let maker_order_timeout = ctx.conf["maker_order_timeout"].as_u64().unwrap_or(MAKER_ORDER_TIMEOUT);
+    let mut reported_order_pairs = HashSet::new();
     loop {
         if ctx.is_stopping() {
             break;
@@ -3001,6 +3165,7 @@ pub async fn lp_ordermatch_loop(ctx: MmArc) {
 
             collect_orderbook_metrics(&ctx, &orderbook);
         }
+        collect_my_orders_metrics(&ctx, &ordermatch_ctx, &mut reported_order_pairs).await;
 
         {
             let mut missing_uuids = Vec::new();
@@ -3224,12 +3389,16 @@ async fn handle_timed_out_maker_matches(ctx: MmArc, ordermatch_ctx: &OrdermatchC
 async fn process_maker_reserved(ctx: MmArc, from_pubkey: H256Json, reserved_msg: MakerReserved) {
     log::debug!("Processing MakerReserved {:?}", reserved_msg);
     let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).unwrap();
-    {
+    let (maker_coin_ticker, taker_coin_ticker) = {
         let my_taker_orders = ordermatch_ctx.my_taker_orders.lock().await;
-        if !my_taker_orders.contains_key(&reserved_msg.taker_order_uuid) {
-            return;
+        match my_taker_orders.get(&reserved_msg.taker_order_uuid) {
+            Some(order) => (
+                order.maker_coin_ticker().to_owned(),
+                order.taker_coin_ticker().to_owned(),
+            ),
+            None => return,
         }
-    }
+    };
 
     let our_public_id = ctx.public_id().unwrap();
     if our_public_id.bytes == from_pubkey.0 {
@@ -3252,6 +3421,13 @@ async fn process_maker_reserved(ctx: MmArc, from_pubkey: H256Json, reserved_msg:
 
     Timer::sleep(3.).await;
 
+    let prices = match fetch_swap_policy_prices(&ctx, &maker_coin_ticker, &taker_coin_ticker).await {
+        Ok(prices) => prices,
+        Err(e) => {
+            log::error!("Error fetching the swap policy prices for the order {}: {}", uuid, e);
+            return;
+        },
+    };
     let mut my_taker_orders = ordermatch_ctx.my_taker_orders.lock().await;
     let my_order = match my_taker_orders.entry(uuid) {
         Entry::Vacant(_) => return,
@@ -3275,6 +3451,16 @@ async fn process_maker_reserved(ctx: MmArc, from_pubkey: H256Json, reserved_msg:
                 && base_coin.is_coin_protocol_supported(&reserved_msg.base_protocol_info)
                 && rel_coin.is_coin_protocol_supported(&reserved_msg.rel_protocol_info)
             {
+                if let Err(e) =
+                    check_reserved_swap_policies(&ctx, &my_order.request, &reserved_msg, &base_coin, &rel_coin, &prices)
+                {
+                    log::warn!(
+                        "Skip MakerReserved of the order {} not satisfying the swap policies: {}",
+                        reserved_msg.maker_order_uuid,
+                        e
+                    );
+                    continue;
+                }
                 let connect = TakerConnect {
                     sender_pubkey: H256Json::from(our_public_id.bytes),
                     dest_pub_key: reserved_msg.sender_pubkey,
@@ -3356,6 +3542,35 @@ async fn process_taker_request(ctx: MmArc, from_pubkey: H256Json, taker_request:
     let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).unwrap();
     let storage = MyOrdersStorage::new(ctx.clone());
     let mut my_orders = ordermatch_ctx.maker_orders_ctx.lock().orders.clone();
+
+    // fetch the swap policy prices of the matching pairs before locking the orders to process the request,
+    // so the orders aren't locked while the price service is requested
+    let mut policy_prices = HashMap::new();
+    for (uuid, order) in my_orders.iter() {
+        if !taker_request.can_match_with_uuid(uuid) {
+            continue;
+        }
+        let pair = {
+            let order = order.lock().await;
+            match order.match_with_request(&taker_request) {
+                OrderMatchResult::Matched(_) => (order.base.clone(), order.rel.clone()),
+                _ => continue,
+            }
+        };
+        if policy_prices.contains_key(&pair) {
+            continue;
+        }
+        match fetch_swap_policy_prices(&ctx, &pair.0, &pair.1).await {
+            Ok(prices) => {
+                policy_prices.insert(pair, prices);
+            },
+            Err(e) => {
+                log::error!("Error fetching the {}/{} swap policy prices: {}", pair.0, pair.1, e);
+                return;
+            },
+        }
+    }
+
     let filtered = my_orders
         .iter_mut()
         .filter(|(uuid, _)| taker_request.can_match_with_uuid(uuid));
@@ -3372,6 +3587,29 @@ async fn process_taker_request(ctx: MmArc, from_pubkey: H256Json, taker_request:
                 && base_coin.is_coin_protocol_supported(taker_request.base_protocol_info_for_maker())
                 && rel_coin.is_coin_protocol_supported(taker_request.rel_protocol_info_for_maker())
             {
+                let conf_settings = order.conf_settings.unwrap_or(OrderConfirmationsSettings {
+                    base_confs: base_coin.required_confirmations(),
+                    base_nota: base_coin.requires_notarization(),
+                    rel_confs: rel_coin.required_confirmations(),
+                    rel_nota: rel_coin.requires_notarization(),
+                });
+                let prices = match policy_prices.get(&(order.base.clone(), order.rel.clone())) {
+                    Some(prices) => prices,
+                    // the order was updated after the prices had been fetched
+                    None => {
+                        log::warn!("No swap policy prices fetched for the order {}", uuid);
+                        return;
+                    },
+                };
+                let policies =
+                    conf_settings.with_swap_policies(&ctx, &base_coin, &base_amount, &rel_coin, &rel_amount, prices);
+                let conf_settings = match policies {
+                    Ok(conf_settings) => conf_settings,
+                    Err(e) => {
+                        log::error!("Error applying the swap policies to the order {}: {}", uuid, e);
+                        return;
+                    },
+                };
                 let reserved = MakerReserved {
                     dest_pub_key: taker_request.sender_pubkey,
                     sender_pubkey: our_public_id,
@@ -3381,14 +3619,7 @@ async fn process_taker_request(ctx: MmArc, from_pubkey: H256Json, taker_request:
                     rel: order.rel_orderbook_ticker().to_owned(),
                     taker_order_uuid: taker_request.uuid,
                     maker_order_uuid: *uuid,
-                    conf_settings: order.conf_settings.or_else(|| {
-                        Some(OrderConfirmationsSettings {
-                            base_confs: base_coin.required_confirmations(),
-                            base_nota: base_coin.requires_notarization(),
-                            rel_confs: rel_coin.required_confirmations(),
-                            rel_nota: rel_coin.requires_notarization(),
-                        })
-                    }),
+                    conf_settings: Some(conf_settings),
                     base_protocol_info: Some(base_coin.coin_protocol_info()),
                     rel_protocol_info: Some(rel_coin.coin_protocol_info()),
                 };
@@ -3524,6 +3755,7 @@ pub async fn buy(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     if rel_coin.wallet_only(&ctx) {
         return ERR!("Rel coin {} is wallet only", input.rel);
     }
+    try_s!(check_secret_hash_algo_compatibility(&base_coin, &rel_coin));
     let my_amount = &input.volume * &input.price;
     try_s!(
         check_balance_for_taker_swap(
@@ -3556,6 +3788,7 @@ pub async fn sell(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     if rel_coin.wallet_only(&ctx) {
         return ERR!("Rel coin {} is wallet only", input.rel);
     }
+    try_s!(check_secret_hash_algo_compatibility(&base_coin, &rel_coin));
     try_s!(
         check_balance_for_taker_swap(
             &ctx,
@@ -3641,25 +3874,56 @@ pub struct TakerRequestForRpc<'a> {
     conf_settings: &'a Option<OrderConfirmationsSettings>,
 }
 
-#[allow(clippy::needless_borrow)]
 pub async fn lp_auto_buy(
     ctx: &MmArc,
     base_coin: &MmCoinEnum,
     rel_coin: &MmCoinEnum,
     input: AutoBuyInput,
 ) -> Result<String, String> {
-    if input.price < MmNumber::from(BigRational::new(1.into(), 100_000_000.into())) {
-        return ERR!("Price is too low, minimum is 0.00000001");
+    let order = try_s!(create_taker_order(ctx, base_coin, rel_coin, input).await);
+    let result = json!({ "result": LpautobuyResult {
+        request: (&order.request).into(),
+        order_type: &order.order_type,
+        min_volume: order.min_volume.clone().into(),
+        base_orderbook_ticker: &order.base_orderbook_ticker,
+        rel_orderbook_ticker: &order.rel_orderbook_ticker,
+    } });
+    Ok(result.to_string())
+}
+
+/// Creates the new taker order, broadcasts the request and saves the order to the storage.
+#[allow(clippy::needless_borrow)]
+async fn create_taker_order(
+    ctx: &MmArc,
+    base_coin: &MmCoinEnum,
+    rel_coin: &MmCoinEnum,
+    input: AutoBuyInput,
+) -> OrdermatchRpcResult<TakerOrder> {
+    let min_price = MmNumber::from(BigRational::new(1.into(), 100_000_000.into()));
+    if input.price < min_price {
+        return MmError::err(OrdermatchRpcError::PriceTooLow {
+            price: input.price.to_decimal(),
+            threshold: min_price.to_decimal(),
+        });
     }
 
     let action = match Some(input.method.as_ref()) {
         Some("buy") => TakerAction::Buy,
         Some("sell") => TakerAction::Sell,
-        _ => return ERR!("Auto buy must be called only from buy/sell RPC methods"),
+        _ => {
+            return MmError::err(OrdermatchRpcError::InvalidParam {
+                param: "method".to_owned(),
+                reason: "Auto buy must be called only from buy/sell RPC methods".to_owned(),
+            })
+        },
     };
-    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    // the prices are fetched before locking the taker orders
+    let prices = fetch_swap_policy_prices(ctx, base_coin.ticker(), rel_coin.ticker())
+        .await
+        .map_to_mm(OrdermatchRpcError::InternalError)?;
+    let ordermatch_ctx = OrdermatchContext::from_ctx(ctx).map_to_mm(OrdermatchRpcError::InternalError)?;
     let mut my_taker_orders = ordermatch_ctx.my_taker_orders.lock().await;
-    let our_public_id = try_s!(ctx.public_id());
+    let our_public_id = ctx.public_id().map_to_mm(OrdermatchRpcError::InternalError)?;
     let rel_volume = &input.volume * &input.price;
     let conf_settings = OrderConfirmationsSettings {
         base_confs: input.base_confs.unwrap_or_else(|| base_coin.required_confirmations()),
@@ -3667,6 +3931,9 @@ pub async fn lp_auto_buy(
         rel_confs: input.rel_confs.unwrap_or_else(|| rel_coin.required_confirmations()),
         rel_nota: input.rel_nota.unwrap_or_else(|| rel_coin.requires_notarization()),
     };
+    let conf_settings = conf_settings
+        .with_swap_policies(ctx, base_coin, &input.volume, rel_coin, &rel_volume, &prices)
+        .map_to_mm(OrdermatchRpcError::InternalError)?;
     let mut order_builder = TakerOrderBuilder::new(base_coin, rel_coin)
         .with_base_amount(input.volume)
         .with_rel_amount(rel_volume)
@@ -3682,18 +3949,19 @@ pub async fn lp_auto_buy(
     if let Some(timeout) = input.timeout {
         order_builder = order_builder.with_timeout(timeout);
     }
-    let order = try_s!(order_builder.build());
+    let order = order_builder
+        .build()
+        .map_to_mm(|e| OrdermatchRpcError::from_taker_order_build_error(e, base_coin.ticker(), rel_coin.ticker()))?;
 
     let request_orderbook = false;
-    try_s!(
-        subscribe_to_orderbook_topic(
-            ctx,
-            order.base_orderbook_ticker(),
-            order.rel_orderbook_ticker(),
-            request_orderbook
-        )
-        .await
-    );
+    subscribe_to_orderbook_topic(
+        ctx,
+        order.base_orderbook_ticker(),
+        order.rel_orderbook_ticker(),
+        request_orderbook,
+    )
+    .await
+    .map_to_mm(OrdermatchRpcError::P2PError)?;
     broadcast_ordermatch_message(
         ctx,
         vec![order.orderbook_topic()],
@@ -3701,19 +3969,9 @@ pub async fn lp_auto_buy(
         order.p2p_keypair(),
     );
 
-    let result = json!({ "result": LpautobuyResult {
-        request: (&order.request).into(),
-        order_type: &order.order_type,
-        min_volume: order.min_volume.clone().into(),
-        base_orderbook_ticker: &order.base_orderbook_ticker,
-        rel_orderbook_ticker: &order.rel_orderbook_ticker,
-    } });
-
-    save_my_new_taker_order(ctx.clone(), &order)
-        .await
-        .map_err(|e| ERRL!("{}", e))?;
-    my_taker_orders.insert(order.request.uuid, order);
-    Ok(result.to_string())
+    save_my_new_taker_order(ctx.clone(), &order).await?;
+    my_taker_orders.insert(order.request.uuid, order.clone());
+    Ok(order)
 }
 
 /// Orderbook Item P2P message
@@ -4136,7 +4394,7 @@ impl OrderbookItem {
 
 fn get_true() -> bool { true }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct SetPriceReq {
     base: String,
     rel: String,
@@ -4156,7 +4414,7 @@ pub struct SetPriceReq {
     save_in_history: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct MakerOrderUpdateReq {
     uuid: Uuid,
     new_price: Option<MmNumber>,
@@ -4333,20 +4591,23 @@ struct CoinVolumeInfo {
     balance: BigDecimal,
 }
 
-async fn get_max_volume(ctx: &MmArc, my_coin: &MmCoinEnum, other_coin: &MmCoinEnum) -> Result<CoinVolumeInfo, String> {
-    let my_balance = try_s!(my_coin.my_spendable_balance().compat().await);
+async fn get_max_volume(
+    ctx: &MmArc,
+    my_coin: &MmCoinEnum,
+    other_coin: &MmCoinEnum,
+) -> OrdermatchRpcResult<CoinVolumeInfo> {
+    let my_balance = my_coin.my_spendable_balance().compat().await?;
     // first check if `rel_coin` balance is sufficient
-    let other_coin_trade_fee = try_s!(
-        other_coin
-            .get_receiver_trade_fee(FeeApproxStage::OrderIssue)
-            .compat()
-            .await
-    );
-    try_s!(check_other_coin_balance_for_swap(ctx, other_coin, None, other_coin_trade_fee).await);
+    let other_coin_trade_fee = other_coin
+        .get_receiver_trade_fee(FeeApproxStage::OrderIssue)
+        .compat()
+        .await
+        .mm_err(|e| CheckBalanceError::from_trade_preimage_error(e, other_coin.ticker()))?;
+    check_other_coin_balance_for_swap(ctx, other_coin, None, other_coin_trade_fee).await?;
     // calculate max maker volume
     // note the `calc_max_maker_vol` returns [`CheckBalanceError::NotSufficientBalance`] error if the balance of `base_coin` is not sufficient
     Ok(CoinVolumeInfo {
-        volume: try_s!(calc_max_maker_vol(ctx, my_coin, &my_balance, FeeApproxStage::OrderIssue).await),
+        volume: calc_max_maker_vol(ctx, my_coin, &my_balance, FeeApproxStage::OrderIssue).await?,
         balance: my_balance,
     })
 }
@@ -4374,51 +4635,41 @@ pub async fn check_balance_update_loop(ctx: MmWeak, ticker: String, balance: Opt
     }
 }
 
-pub async fn create_maker_order(ctx: &MmArc, req: SetPriceReq) -> Result<MakerOrder, String> {
-    let base_coin: MmCoinEnum = match try_s!(lp_coinfind(ctx, &req.base).await) {
-        Some(coin) => coin,
-        None => return ERR!("Base coin {} is not found", req.base),
-    };
-
-    let rel_coin: MmCoinEnum = match try_s!(lp_coinfind(ctx, &req.rel).await) {
-        Some(coin) => coin,
-        None => return ERR!("Rel coin {} is not found", req.rel),
-    };
+pub async fn create_maker_order(ctx: &MmArc, req: SetPriceReq) -> OrdermatchRpcResult<MakerOrder> {
+    let base_coin = lp_coinfind_or_err(ctx, &req.base).await?;
+    let rel_coin = lp_coinfind_or_err(ctx, &req.rel).await?;
 
     if base_coin.wallet_only(ctx) {
-        return ERR!("Base coin {} is wallet only", req.base);
+        return MmError::err(OrdermatchRpcError::CoinIsWalletOnly { coin: req.base });
     }
     if rel_coin.wallet_only(ctx) {
-        return ERR!("Rel coin {} is wallet only", req.rel);
+        return MmError::err(OrdermatchRpcError::CoinIsWalletOnly { coin: req.rel });
     }
+    check_secret_hash_algo_compatibility(&base_coin, &rel_coin).map_to_mm(OrdermatchRpcError::IncompatibleCoins)?;
 
     let CoinVolumeInfo { volume, balance } = if req.max {
-        try_s!(
-            get_max_volume(ctx, &base_coin, &rel_coin)
-                .or_else(|e| cancel_orders_on_error(ctx, &req, e))
-                .await
-        )
-    } else {
-        let balance = try_s!(
-            check_balance_for_maker_swap(
-                ctx,
-                &base_coin,
-                &rel_coin,
-                req.volume.clone(),
-                None,
-                None,
-                FeeApproxStage::OrderIssue
-            )
+        get_max_volume(ctx, &base_coin, &rel_coin)
             .or_else(|e| cancel_orders_on_error(ctx, &req, e))
-            .await
-        );
+            .await?
+    } else {
+        let balance = check_balance_for_maker_swap(
+            ctx,
+            &base_coin,
+            &rel_coin,
+            req.volume.clone(),
+            None,
+            None,
+            FeeApproxStage::OrderIssue,
+        )
+        .or_else(|e| cancel_orders_on_error(ctx, &req, e))
+        .await?;
         CoinVolumeInfo {
             volume: req.volume.clone(),
             balance,
         }
     };
 
-    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+    let ordermatch_ctx = OrdermatchContext::from_ctx(ctx).map_to_mm(OrdermatchRpcError::InternalError)?;
     if req.cancel_previous {
         cancel_previous_maker_orders(ctx, &ordermatch_ctx, &req.base, &req.rel).await;
     }
@@ -4438,21 +4689,20 @@ pub async fn create_maker_order(ctx: &MmArc, req: SetPriceReq) -> Result<MakerOr
         .with_base_orderbook_ticker(ordermatch_ctx.orderbook_ticker(base_coin.ticker()))
         .with_rel_orderbook_ticker(ordermatch_ctx.orderbook_ticker(rel_coin.ticker()));
 
-    let new_order = try_s!(builder.build());
+    let new_order = builder
+        .build()
+        .map_to_mm(|e| OrdermatchRpcError::from_maker_order_build_error(e, &req.base, &req.rel))?;
 
     let request_orderbook = false;
-    try_s!(
-        subscribe_to_orderbook_topic(
-            ctx,
-            new_order.base_orderbook_ticker(),
-            new_order.rel_orderbook_ticker(),
-            request_orderbook
-        )
-        .await
-    );
-    save_my_new_maker_order(ctx.clone(), &new_order)
-        .await
-        .map_err(|e| ERRL!("{}", e))?;
+    subscribe_to_orderbook_topic(
+        ctx,
+        new_order.base_orderbook_ticker(),
+        new_order.rel_orderbook_ticker(),
+        request_orderbook,
+    )
+    .await
+    .map_to_mm(OrdermatchRpcError::P2PError)?;
+    save_my_new_maker_order(ctx.clone(), &new_order).await?;
     maker_order_created_p2p_notify(
         ctx.clone(),
         &new_order,
@@ -4469,7 +4719,7 @@ pub async fn create_maker_order(ctx: &MmArc, req: SetPriceReq) -> Result<MakerOr
 
 pub async fn set_price(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     let req: SetPriceReq = try_s!(json::from_value(req));
-    let maker_order = create_maker_order(&ctx, req).await?;
+    let maker_order = try_s!(create_maker_order(&ctx, req).await);
     let rpc_result = MakerOrderForRpc::from(&maker_order);
     let res = try_s!(json::to_vec(&json!({ "result": rpc_result })));
     Ok(try_s!(Response::builder().body(res)))
@@ -4508,27 +4758,25 @@ async fn cancel_previous_maker_orders(
     }
 }
 
-pub async fn update_maker_order(ctx: &MmArc, req: MakerOrderUpdateReq) -> Result<MakerOrder, String> {
-    let ordermatch_ctx = try_s!(OrdermatchContext::from_ctx(ctx));
+pub async fn update_maker_order(ctx: &MmArc, req: MakerOrderUpdateReq) -> OrdermatchRpcResult<MakerOrder> {
+    let ordermatch_ctx = OrdermatchContext::from_ctx(ctx).map_to_mm(OrdermatchRpcError::InternalError)?;
     let order_mutex = {
         let maker_orders_ctx = ordermatch_ctx.maker_orders_ctx.lock();
         match maker_orders_ctx.get_order(&req.uuid) {
             Some(order) => order.clone(),
-            None => return ERR!("There is no order with UUID {}", req.uuid),
+            None => return MmError::err(OrdermatchRpcError::OrderNotFound { uuid: req.uuid }),
         }
     };
 
     let order_before_update = order_mutex.lock().await.clone();
     if order_before_update.has_ongoing_matches() {
-        return ERR!("Can't update an order that has ongoing matches");
+        return MmError::err(OrdermatchRpcError::OrderBeingMatched { uuid: req.uuid });
     }
 
     let base = order_before_update.base.as_str();
     let rel = order_before_update.rel.as_str();
-    let (base_coin, rel_coin) = match find_pair(ctx, base, rel).await {
-        Ok(Some(c)) => c,
-        _ => return ERR!("Base coin {} and/or rel coin {} are not activated", base, rel),
-    };
+    let base_coin = lp_coinfind_or_err(ctx, base).await?;
+    let rel_coin = lp_coinfind_or_err(ctx, rel).await?;
 
     let original_conf_settings = order_before_update.conf_settings.unwrap();
     let updated_conf_settings = OrderConfirmationsSettings {
@@ -4547,7 +4795,8 @@ pub async fn update_maker_order(ctx: &MmArc, req: MakerOrderUpdateReq) -> Result
     // Validate and Add new_price to update_msg if new_price is found in the request
     let new_price = match req.new_price {
         Some(new_price) => {
-            try_s!(validate_price(new_price.clone()));
+            validate_price(new_price.clone())
+                .map_to_mm(|e| OrdermatchRpcError::from_maker_order_build_error(e, base, rel))?;
             update_msg.with_new_price(new_price.clone().into());
             new_price
         },
@@ -4560,37 +4809,39 @@ pub async fn update_maker_order(ctx: &MmArc, req: MakerOrderUpdateReq) -> Result
     // Add min_volume to update_msg if min_volume is found in the request
     if let Some(min_volume) = req.min_volume.clone() {
         // Validate and Calculate Minimum Volume
-        let actual_min_vol = try_s!(validate_and_get_min_vol(
+        let actual_min_vol = validate_and_get_min_vol(
             min_base_amount.clone(),
             min_rel_amount.clone(),
             Some(min_volume),
-            new_price.clone()
-        ));
+            new_price.clone(),
+        )
+        .map_to_mm(|e| OrdermatchRpcError::from_maker_order_build_error(e, base, rel))?;
         update_msg.with_new_min_volume(actual_min_vol.into());
     }
 
     // Calculate order volume and add to update_msg if new_volume is found in the request
     let new_volume = if req.max.unwrap_or(false) {
-        let max_volume = try_s!(get_max_volume(ctx, &base_coin, &rel_coin).await).volume + reserved_amount.clone();
+        let max_volume = get_max_volume(ctx, &base_coin, &rel_coin).await?.volume + reserved_amount.clone();
         update_msg.with_new_max_volume(max_volume.clone().into());
         max_volume
     } else if Option::is_some(&req.volume_delta) {
         let volume = original_volume + req.volume_delta.unwrap();
         if volume <= MmNumber::from("0") {
-            return ERR!("New volume {} should be more than zero", volume);
-        }
-        try_s!(
-            check_balance_for_maker_swap(
-                ctx,
-                &base_coin,
-                &rel_coin,
-                volume.clone(),
-                None,
-                None,
-                FeeApproxStage::OrderIssue
-            )
-            .await
-        );
+            return MmError::err(OrdermatchRpcError::InvalidParam {
+                param: "volume_delta".to_owned(),
+                reason: format!("New volume {} should be more than zero", volume),
+            });
+        }
+        check_balance_for_maker_swap(
+            ctx,
+            &base_coin,
+            &rel_coin,
+            volume.clone(),
+            None,
+            None,
+            FeeApproxStage::OrderIssue,
+        )
+        .await?;
         update_msg.with_new_max_volume(volume.clone().into());
         volume
     } else {
@@ -4598,38 +4849,45 @@ pub async fn update_maker_order(ctx: &MmArc, req: MakerOrderUpdateReq) -> Result
     };
 
     if new_volume <= reserved_amount {
-        return ERR!(
-            "New volume {} should be more than reserved amount for order matches {}",
-            new_volume,
-            reserved_amount
-        );
+        return MmError::err(OrdermatchRpcError::InvalidParam {
+            param: "volume_delta".to_owned(),
+            reason: format!(
+                "New volume {} should be more than reserved amount for order matches {}",
+                new_volume, reserved_amount
+            ),
+        });
     }
 
     // Validate Order Volume
-    try_s!(validate_max_vol(
+    validate_max_vol(
         min_base_amount.clone(),
         min_rel_amount.clone(),
         new_volume.clone() - reserved_amount.clone(),
         req.min_volume.clone(),
-        new_price
-    ));
+        new_price,
+    )
+    .map_to_mm(|e| OrdermatchRpcError::from_maker_order_build_error(e, base, rel))?;
 
     let order_mutex = {
         let maker_orders_ctx = ordermatch_ctx.maker_orders_ctx.lock();
         match maker_orders_ctx.get_order(&req.uuid) {
             Some(order) => order.clone(),
-            None => return ERR!("Order with UUID: {} has been deleted", req.uuid),
+            None => return MmError::err(OrdermatchRpcError::OrderNotFound { uuid: req.uuid }),
         }
     };
 
     let mut order = order_mutex.lock().await;
     if *order != order_before_update {
-        return ERR!("Order state has changed after price/volume/balance checks. Please try to update the order again if it's still needed.");
+        // The order has been changed after price/volume/balance checks. The update can be requested again.
+        return MmError::err(OrdermatchRpcError::OrderStateChanged { uuid: req.uuid });
     }
     order.apply_updated(&update_msg);
     if let Err(e) = save_maker_order_on_update(ctx.clone(), &order).await {
         *order = order_before_update;
-        return ERR!("Error on saving updated order state to database:{}", e);
+        return MmError::err(OrdermatchRpcError::InternalError(format!(
+            "Error on saving updated order state to database: {}",
+            e
+        )));
     }
     update_msg.with_new_max_volume((new_volume - reserved_amount).into());
     maker_order_updated_p2p_notify(ctx.clone(), order.orderbook_topic(), update_msg, order.p2p_keypair());
@@ -4863,12 +5121,12 @@ pub async fn orders_history_by_filter(ctx: MmArc, req: Json) -> Result<Response<
     Ok(try_s!(Response::builder().body(res)))
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct CancelOrderReq {
     uuid: Uuid,
 }
 
-#[derive(Debug, Deserialize, Serialize, SerializeErrorType, Display)]
+#[derive(Debug, Deserialize, Serialize, SerializeErrorType, Display, RpcSchema)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum CancelOrderError {
     #[display(fmt = "Cannot retrieve order match context.")]
@@ -4879,7 +5137,17 @@ pub enum CancelOrderError {
     UUIDNotFound { uuid: Uuid },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+impl HttpStatusCode for CancelOrderError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CancelOrderError::CannotRetrieveOrderMatchContext => StatusCode::INTERNAL_SERVER_ERROR,
+            CancelOrderError::OrderBeingMatched { .. } => StatusCode::CONFLICT,
+            CancelOrderError::UUIDNotFound { .. } => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, RpcSchema)]
 pub struct CancelOrderResponse {
     result: String,
 }
@@ -5098,6 +5366,8 @@ fn my_taker_orders_dir(ctx: &MmArc) -> PathBuf { ctx.dbdir().join("ORDERS").join
 
 fn my_orders_history_dir(ctx: &MmArc) -> PathBuf { ctx.dbdir().join("ORDERS").join("MY").join("HISTORY") }
 
+fn my_split_taker_orders_dir(ctx: &MmArc) -> PathBuf { ctx.dbdir().join("ORDERS").join("MY").join("SPLIT_TAKER") }
+
 pub fn my_maker_order_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
     my_maker_orders_dir(ctx).join(format!("{}.json", uuid))
 }
@@ -5110,6 +5380,10 @@ fn my_order_history_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
     my_orders_history_dir(ctx).join(format!("{}.json", uuid))
 }
 
+fn my_split_taker_order_file_path(ctx: &MmArc, uuid: &Uuid) -> PathBuf {
+    my_split_taker_orders_dir(ctx).join(format!("{}.json", uuid))
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct HistoricalOrder {
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -5396,7 +5670,7 @@ pub struct RpcOrderbookEntry {
     conf_settings: Option<OrderConfirmationsSettings>,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, RpcSchema, Serialize)]
 pub struct RpcOrderbookEntryV2 {
     coin: String,
     address: OrderbookAddress,
@@ -5479,6 +5753,30 @@ fn choose_maker_confs_and_notas(
     }
 }
 
+/// Checks that the swap settings agreed with the maker satisfy the taker swap policies for the reserved amounts.
+/// The reserved amounts may exceed the requested ones, so the settings sent within the request may be insufficient.
+fn check_reserved_swap_policies(
+    ctx: &MmArc,
+    taker_req: &TakerRequest,
+    maker_reserved: &MakerReserved,
+    maker_coin: &MmCoinEnum,
+    taker_coin: &MmCoinEnum,
+    prices: &SwapPolicyPrices,
+) -> Result<(), String> {
+    let settings = choose_taker_confs_and_notas(taker_req, maker_reserved, maker_coin, taker_coin);
+    // the taker coin confirmations protect the maker, so only the maker coin ones are checked
+    let maker_coin_requirements = try_s!(swap_policy_requirements(
+        ctx,
+        maker_coin.ticker(),
+        maker_reserved.get_base_amount(),
+        prices.base.as_ref()
+    ));
+    if let Some(requirements) = maker_coin_requirements {
+        try_s!(requirements.check_confs(settings.maker_coin_confs, settings.maker_coin_nota));
+    }
+    Ok(())
+}
+
 fn choose_taker_confs_and_notas(
     taker_req: &TakerRequest,
     maker_reserved: &MakerReserved,
@@ -5521,7 +5819,7 @@ fn choose_taker_confs_and_notas(
     }
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, RpcSchema, Serialize)]
 #[serde(tag = "address_type", content = "address_data")]
 pub enum OrderbookAddress {
     Transparent(String),
@@ -5579,5 +5877,25 @@ fn orderbook_address(
         CoinProtocol::LIGHTNING { .. } => MmError::err(OrderbookAddrErr::CoinIsNotSupported(coin.to_owned())),
         #[cfg(not(target_arch = "wasm32"))]
         CoinProtocol::ZHTLC { .. } => Ok(OrderbookAddress::Shielded),
+        CoinProtocol::TENDERMINT(protocol_info) => {
+            coins::tendermint::account_id_from_pubkey_hex(&protocol_info.account_prefix, pubkey)
+                .map(OrderbookAddress::Transparent)
+                .map_to_mm(OrderbookAddrErr::AddrFromPubkeyError)
+        },
+        CoinProtocol::TENDERMINTTOKEN(protocol_info) => {
+            let platform_conf = coin_conf(ctx, &protocol_info.platform);
+            if platform_conf.is_null() {
+                return MmError::err(OrderbookAddrErr::PlatformCoinConfIsNull(protocol_info.platform));
+            }
+            let platform_protocol: CoinProtocol = json::from_value(platform_conf["protocol"].clone())?;
+            match platform_protocol {
+                CoinProtocol::TENDERMINT(platform_info) => {
+                    coins::tendermint::account_id_from_pubkey_hex(&platform_info.account_prefix, pubkey)
+                        .map(OrderbookAddress::Transparent)
+                        .map_to_mm(OrderbookAddrErr::AddrFromPubkeyError)
+                },
+                _ => MmError::err(OrderbookAddrErr::InvalidPlatformCoinProtocol(protocol_info.platform)),
+            }
+        },
     }
 }
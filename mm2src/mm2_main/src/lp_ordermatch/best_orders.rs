@@ -1,6 +1,6 @@
-use super::{addr_format_from_protocol_info, BaseRelProtocolInfo, OrderConfirmationsSettings,
+use super::{addr_format_from_protocol_info, BaseRelProtocolInfo, OrderConfirmationsSettings, OrderbookP2PItem,
             OrderbookP2PItemWithProof, OrdermatchContext, OrdermatchRequest};
-use crate::mm2::lp_network::{request_any_relay, P2PRequest};
+use crate::mm2::lp_network::{request_any_relay, P2PRequest, P2PRequestError};
 use crate::mm2::lp_ordermatch::{orderbook_address, RpcOrderbookEntryV2};
 use coins::{address_by_coin_conf_and_pubkey_str, coin_conf, is_wallet_only_conf, is_wallet_only_ticker};
 use common::{log, HttpStatusCode};
@@ -14,7 +14,7 @@ use serde_json::{self as json, Value as Json};
 use std::collections::{HashMap, HashSet};
 use uuid::Uuid;
 
-#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, RpcSchema, Serialize)]
 #[serde(rename_all = "lowercase")]
 pub enum BestOrdersAction {
     Buy,
@@ -37,7 +37,7 @@ struct BestOrdersP2PRes {
     conf_infos: HashMap<Uuid, OrderConfirmationsSettings>,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, RpcSchema)]
 #[serde(tag = "type", content = "value")]
 pub enum RequestBestOrdersBy {
     #[serde(rename = "volume")]
@@ -46,7 +46,7 @@ pub enum RequestBestOrdersBy {
     Number(usize),
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, RpcSchema)]
 pub struct BestOrdersRequestV2 {
     coin: String,
     action: BestOrdersAction,
@@ -212,6 +212,42 @@ pub fn process_best_orders_p2p_request_by_number(
     Ok(Some(encoded))
 }
 
+/// Requests the best orders of the `base/rel` pair from any relay.
+/// Returns the orders sorted by price, the best first.
+/// Note `base` and `rel` are the tickers of the taker's request and can be the orderbook tickers.
+pub(super) async fn request_best_orders_for_pair(
+    ctx: &MmArc,
+    base: &str,
+    rel: &str,
+    action: BestOrdersAction,
+    volume: BigRational,
+) -> Result<Vec<OrderbookP2PItem>, MmError<P2PRequestError>> {
+    let p2p_request = OrdermatchRequest::BestOrders {
+        coin: base.to_owned(),
+        action,
+        volume,
+    };
+    let best_orders_res =
+        request_any_relay::<BestOrdersP2PRes>(ctx.clone(), P2PRequest::Ordermatch(p2p_request)).await?;
+    let mut orders: Vec<_> = match best_orders_res {
+        Some((mut p2p_response, peer_id)) => {
+            log::debug!("Got best orders {:?} from peer {}", p2p_response, peer_id);
+            p2p_response
+                .orders
+                .remove(rel)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|order_w_proof| order_w_proof.order)
+                .collect()
+        },
+        None => Vec::new(),
+    };
+    // The maker's price is always the amount of the maker's `rel` per 1 unit of the maker's `base`,
+    // so the lower price is the better one for the taker in both cases.
+    orders.sort_by(|a, b| a.price.cmp(&b.price));
+    Ok(orders)
+}
+
 pub async fn best_orders_rpc(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, String> {
     let req: BestOrdersRequest = try_s!(json::from_value(req));
     let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).unwrap();
@@ -277,7 +313,7 @@ pub async fn best_orders_rpc(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>,
         .map_err(|e| ERRL!("{}", e))
 }
 
-#[derive(Debug, Display, Serialize, SerializeErrorType)]
+#[derive(Debug, Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum BestOrdersRpcError {
     CoinIsWalletOnly(String),
@@ -293,7 +329,7 @@ impl HttpStatusCode for BestOrdersRpcError {
     }
 }
 
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 pub struct BestOrdersV2Response {
     orders: HashMap<String, Vec<RpcOrderbookEntryV2>>,
     original_tickers: HashMap<String, HashSet<String>>,
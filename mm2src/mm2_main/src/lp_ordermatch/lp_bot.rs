@@ -28,7 +28,7 @@ pub use simple_market_maker_bot::{start_simple_market_maker_bot, stop_simple_mar
 #[path = "simple_market_maker_tests.rs"]
 pub mod simple_market_maker_tests;
 
-#[derive(Clone, Display)]
+#[derive(Clone, Display, Serialize)]
 #[display(fmt = "simple_market_maker_bot will stop within {} seconds", bot_refresh_rate)]
 pub struct TradingBotStopping {
     bot_refresh_rate: f64,
@@ -38,7 +38,7 @@ impl TradingBotStopping {
     fn event_id() -> TypeId { TypeId::of::<TradingBotStopping>() }
 }
 
-#[derive(Clone, Display)]
+#[derive(Clone, Display, Serialize)]
 #[display(fmt = "simple_market_maker_bot successfully started with {} pairs", nb_pairs)]
 pub struct TradingBotStarted {
     nb_pairs: usize,
@@ -48,7 +48,7 @@ impl TradingBotStarted {
     fn event_id() -> TypeId { TypeId::of::<TradingBotStarted>() }
 }
 
-#[derive(Clone, Display)]
+#[derive(Clone, Display, Serialize)]
 #[display(
     fmt = "simple_market_maker_bot successfully stopped - cancelled {} orders",
     nb_orders
@@ -61,7 +61,8 @@ impl TradingBotStopped {
     fn event_id() -> TypeId { TypeId::of::<TradingBotStopped>() }
 }
 
-#[derive(Clone, Display)]
+#[derive(Clone, Display, Serialize)]
+#[serde(tag = "type", content = "data")]
 pub enum TradingBotEvent {
     Started(TradingBotStarted),
     Stopping(TradingBotStopping),
@@ -78,6 +79,17 @@ impl EventUniqueId for TradingBotEvent {
     }
 }
 
+impl TradingBotEvent {
+    /// The IDs of all the trading bot events.
+    pub fn event_ids() -> Vec<TypeId> {
+        vec![
+            TradingBotStarted::event_id(),
+            TradingBotStopping::event_id(),
+            TradingBotStopped::event_id(),
+        ]
+    }
+}
+
 impl From<TradingBotStopping> for TradingBotEvent {
     fn from(trading_bot_stopping: TradingBotStopping) -> Self { TradingBotEvent::Stopping(trading_bot_stopping) }
 }
@@ -129,7 +141,7 @@ impl Default for TradingBotState {
 
 pub type SimpleMakerBotRegistry = HashMap<String, SimpleCoinMarketMakerCfg>;
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, RpcSchema, Serialize, Deserialize, Clone)]
 pub enum VolumeSettings {
     #[serde(rename = "percentage")]
     Percentage(MmNumber),
@@ -137,7 +149,7 @@ pub enum VolumeSettings {
     Usd(MmNumber),
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, RpcSchema, Serialize, Deserialize, Clone)]
 pub struct SimpleCoinMarketMakerCfg {
     pub base: String,
     pub rel: String,
@@ -186,8 +198,9 @@ impl TradingBotContext {
         let msg_format = format!("{}", trading_bot_event);
         info!("{}", msg_format);
         let message_service_ctx = MessageServiceContext::from_ctx(ctx).unwrap();
-        let message_service = message_service_ctx.message_service.lock().await;
-        let _ = message_service.send_message(msg_format, MAKER_BOT_ROOM_ID, false).await;
+        let _ = message_service_ctx
+            .send_message(msg_format, MAKER_BOT_ROOM_ID, false)
+            .await;
     }
 
     async fn on_maker_swap_status_changed(&self, ctx: &MmArc, swap_infos: &MakerSwapStatusChanged) {
@@ -205,8 +218,7 @@ impl TradingBotContext {
         match &*state {
             TradingBotState::Running(_) => {
                 let message_service_ctx = MessageServiceContext::from_ctx(ctx).unwrap();
-                let message_service = message_service_ctx.message_service.lock().await;
-                let _ = message_service
+                let _ = message_service_ctx
                     .send_message(msg.to_string(), MAKER_BOT_ROOM_ID, false)
                     .await;
             },
@@ -238,8 +250,10 @@ impl EventListener for ArcTradingBotContext {
 
     async fn process_event_async(&self, ctx: MmArc, event: Self::Event) {
         match &event {
+            LpEvents::KmdRewardsClaimed(_) => (),
             LpEvents::MakerSwapStatusChanged(swap_infos) => self.on_maker_swap_status_changed(&ctx, swap_infos).await,
             LpEvents::StopCtxEvent(_) => self.on_ctx_stop(&ctx).await,
+            LpEvents::SwapFundsRecovered(_) => (),
             LpEvents::TradingBotEvent(trading_bot_event) => self.on_trading_bot_event(&ctx, trading_bot_event).await,
         }
     }
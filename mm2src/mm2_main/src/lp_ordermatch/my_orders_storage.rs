@@ -1,5 +1,5 @@
-use super::{MakerOrder, MakerOrderCancellationReason, MyOrdersFilter, Order, RecentOrdersSelectResult, TakerOrder,
-            TakerOrderCancellationReason};
+use super::{MakerOrder, MakerOrderCancellationReason, MyOrdersFilter, Order, RecentOrdersSelectResult,
+            SplitTakerOrder, TakerOrder, TakerOrderCancellationReason};
 use async_trait::async_trait;
 use common::log::LogOnError;
 use common::{BoxFut, PagingOptions};
@@ -174,6 +174,15 @@ pub trait MyOrdersHistory {
     async fn load_order_from_history(&self, uuid: Uuid) -> MyOrdersResult<Order>;
 }
 
+/// The parent orders of the taker orders split across several makers.
+/// The parent order isn't changed after it's created, the statuses of its parts are tracked by the taker orders and swaps.
+#[async_trait]
+pub trait MySplitTakerOrders {
+    async fn save_split_taker_order(&self, order: &SplitTakerOrder) -> MyOrdersResult<()>;
+
+    async fn load_split_taker_order(&self, uuid: Uuid) -> MyOrdersResult<SplitTakerOrder>;
+}
+
 #[async_trait]
 pub trait MyOrdersFilteringHistory {
     async fn select_orders_by_filter(
@@ -209,7 +218,7 @@ mod native_impl {
                                           select_status_by_uuid, update_maker_order, update_order_status,
                                           update_was_taker};
     use crate::mm2::lp_ordermatch::{my_maker_order_file_path, my_maker_orders_dir, my_order_history_file_path,
-                                    my_taker_order_file_path, my_taker_orders_dir};
+                                    my_split_taker_order_file_path, my_taker_order_file_path, my_taker_orders_dir};
     use mm2_io::fs::{read_dir_json, read_json, remove_file_async, write_json, FsJsonError};
 
     const USE_TMP_FILE: bool = false;
@@ -308,6 +317,22 @@ mod native_impl {
         }
     }
 
+    #[async_trait]
+    impl MySplitTakerOrders for MyOrdersStorage {
+        async fn save_split_taker_order(&self, order: &SplitTakerOrder) -> MyOrdersResult<()> {
+            let path = my_split_taker_order_file_path(&self.ctx, &order.uuid);
+            write_json(order, &path, USE_TMP_FILE).await?;
+            Ok(())
+        }
+
+        async fn load_split_taker_order(&self, uuid: Uuid) -> MyOrdersResult<SplitTakerOrder> {
+            let path = my_split_taker_order_file_path(&self.ctx, &uuid);
+            read_json(&path)
+                .await?
+                .or_mm_err(|| MyOrdersError::NoSuchOrder { uuid })
+        }
+    }
+
     #[async_trait]
     impl MyOrdersFilteringHistory for MyOrdersStorage {
         async fn select_orders_by_filter(
@@ -352,7 +377,7 @@ mod wasm_impl {
     use super::*;
     use crate::mm2::lp_ordermatch::ordermatch_wasm_db::{DbTransactionError, InitDbError, MyActiveMakerOrdersTable,
                                                         MyActiveTakerOrdersTable, MyFilteringHistoryOrdersTable,
-                                                        MyHistoryOrdersTable};
+                                                        MyHistoryOrdersTable, MySplitTakerOrdersTable};
     use crate::mm2::lp_ordermatch::{OrdermatchContext, TakerAction};
     use common::log::warn;
     use num_traits::ToPrimitive;
@@ -534,6 +559,34 @@ mod wasm_impl {
         }
     }
 
+    #[async_trait]
+    impl MySplitTakerOrders for MyOrdersStorage {
+        async fn save_split_taker_order(&self, order: &SplitTakerOrder) -> MyOrdersResult<()> {
+            let db = self.ctx.ordermatch_db().await?;
+            let transaction = db.transaction().await?;
+            let table = transaction.table::<MySplitTakerOrdersTable>().await?;
+
+            let item = MySplitTakerOrdersTable {
+                uuid: order.uuid,
+                order_payload: order.clone(),
+            };
+            table.add_item(&item).await?;
+            Ok(())
+        }
+
+        async fn load_split_taker_order(&self, uuid: Uuid) -> MyOrdersResult<SplitTakerOrder> {
+            let db = self.ctx.ordermatch_db().await?;
+            let transaction = db.transaction().await?;
+            let table = transaction.table::<MySplitTakerOrdersTable>().await?;
+
+            table
+                .get_item_by_unique_index("uuid", uuid)
+                .await?
+                .map(|(_item_id, MySplitTakerOrdersTable { order_payload, .. })| order_payload)
+                .or_mm_err(|| MyOrdersError::NoSuchOrder { uuid })
+        }
+    }
+
     #[async_trait]
     impl MyOrdersFilteringHistory for MyOrdersStorage {
         async fn select_orders_by_filter(
@@ -981,4 +1034,49 @@ mod tests {
             .expect_err("!MyOrdersStorage::select_order_status should have failed");
         assert_eq!(err.into_inner(), MyOrdersError::NoSuchOrder { uuid: unknown_uuid });
     }
+
+    #[wasm_bindgen_test]
+    async fn test_split_taker_order() {
+        let ctx = MmCtxBuilder::new().with_test_db_namespace().into_mm_arc();
+        let storage = MyOrdersStorage::new(ctx.clone());
+
+        let uuid = new_uuid();
+        let order_json = json!({
+            "uuid": uuid,
+            "base": "RICK",
+            "rel": "MORTY",
+            "method": "buy",
+            "price": "1",
+            "volume": "3",
+            "created_at": now_ms() / 1000,
+            "parts": [{
+                "uuid": new_uuid(),
+                "maker_order_uuid": new_uuid(),
+                "maker_pubkey": "maker",
+                "maker_price": "1",
+                "volume": "3",
+            }],
+        });
+        let order: SplitTakerOrder = serde_json::from_value(order_json).unwrap();
+        storage
+            .save_split_taker_order(&order)
+            .await
+            .expect("!MyOrdersStorage::save_split_taker_order");
+
+        let actual = storage
+            .load_split_taker_order(uuid)
+            .await
+            .expect("!MyOrdersStorage::load_split_taker_order");
+        assert_eq!(
+            serde_json::to_value(&actual).unwrap(),
+            serde_json::to_value(&order).unwrap()
+        );
+
+        let unknown_uuid = new_uuid();
+        let err = storage
+            .load_split_taker_order(unknown_uuid)
+            .await
+            .expect_err("!MyOrdersStorage::load_split_taker_order should have failed");
+        assert_eq!(err.into_inner(), MyOrdersError::NoSuchOrder { uuid: unknown_uuid });
+    }
 }
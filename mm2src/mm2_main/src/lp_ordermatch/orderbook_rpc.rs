@@ -12,7 +12,7 @@ use mm2_number::{construct_detailed, BigRational, MmNumber, MmNumberMultiRepr};
 use num_traits::Zero;
 use serde_json::{self as json, Value as Json};
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct OrderbookReq {
     base: String,
     rel: String,
@@ -35,7 +35,7 @@ pub struct AggregatedOrderbookEntry {
     rel_max_volume_aggr: AggregatedRelVol,
 }
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, RpcSchema, Serialize)]
 pub struct AggregatedOrderbookEntryV2 {
     #[serde(flatten)]
     entry: RpcOrderbookEntryV2,
@@ -219,7 +219,7 @@ pub async fn orderbook_rpc(ctx: MmArc, req: Json) -> Result<Response<Vec<u8>>, S
     Ok(try_s!(Response::builder().body(response)))
 }
 
-#[derive(Debug, Display, Serialize, SerializeErrorType)]
+#[derive(Debug, Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum OrderbookRpcError {
     BaseRelSame,
@@ -241,7 +241,7 @@ impl HttpStatusCode for OrderbookRpcError {
     }
 }
 
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 pub struct OrderbookV2Response {
     asks: Vec<AggregatedOrderbookEntryV2>,
     base: String,
@@ -7,10 +7,10 @@ use uuid::Uuid;
 pub use mm2_db::indexed_db::{cursor_prelude, DbTransactionError, DbTransactionResult, InitDbError, InitDbResult,
                              ItemId};
 pub use tables::{MyActiveMakerOrdersTable, MyActiveTakerOrdersTable, MyFilteringHistoryOrdersTable,
-                 MyHistoryOrdersTable};
+                 MyHistoryOrdersTable, MySplitTakerOrdersTable};
 
 const DB_NAME: &str = "ordermatch";
-const DB_VERSION: u32 = 1;
+const DB_VERSION: u32 = 2;
 
 pub struct OrdermatchDb {
     inner: IndexedDb,
@@ -27,6 +27,7 @@ impl DbInstance for OrdermatchDb {
             .with_table::<MyActiveTakerOrdersTable>()
             .with_table::<MyHistoryOrdersTable>()
             .with_table::<MyFilteringHistoryOrdersTable>()
+            .with_table::<MySplitTakerOrdersTable>()
             .build()
             .await?;
         Ok(OrdermatchDb { inner })
@@ -41,7 +42,7 @@ impl Deref for OrdermatchDb {
 
 pub mod tables {
     use super::*;
-    use crate::mm2::lp_ordermatch::{MakerOrder, Order, TakerOrder};
+    use crate::mm2::lp_ordermatch::{MakerOrder, Order, SplitTakerOrder, TakerOrder};
     use serde_json::Value as Json;
 
     #[derive(Debug, Deserialize, Eq, PartialEq, Serialize)]
@@ -106,7 +107,7 @@ pub mod tables {
 
         fn on_upgrade_needed(upgrader: &DbUpgrader, old_version: u32, new_version: u32) -> OnUpgradeResult<()> {
             match (old_version, new_version) {
-                (0, 1) => {
+                (0, _) => {
                     let table = upgrader.create_table(Self::table_name())?;
                     table.create_index("uuid", true)?;
                     // TODO add other indexes during [`MyOrdersStorage::select_orders_by_filter`] implementation.
@@ -117,7 +118,31 @@ pub mod tables {
         }
     }
 
+    /// The parent orders of the taker orders split across several makers.
+    #[derive(Debug, Deserialize, Serialize)]
+    pub struct MySplitTakerOrdersTable {
+        pub uuid: Uuid,
+        pub order_payload: SplitTakerOrder,
+    }
+
+    impl TableSignature for MySplitTakerOrdersTable {
+        fn table_name() -> &'static str { "my_split_taker_orders" }
+
+        /// The table is added in the second version of the database.
+        fn on_upgrade_needed(upgrader: &DbUpgrader, old_version: u32, new_version: u32) -> OnUpgradeResult<()> {
+            match (old_version, new_version) {
+                (0, 2) | (1, 2) => {
+                    let table = upgrader.create_table(Self::table_name())?;
+                    table.create_index("uuid", true)?;
+                },
+                _ => (),
+            }
+            Ok(())
+        }
+    }
+
     /// [`TableSignature::on_upgrade_needed`] implementation common for the most tables with the only `uuid` unique index.
+    /// The tables are created in the first version of the database, so they're created from scratch on any version.
     fn on_upgrade_swap_table_by_uuid_v1(
         upgrader: &DbUpgrader,
         old_version: u32,
@@ -125,7 +150,7 @@ pub mod tables {
         table_name: &'static str,
     ) -> OnUpgradeResult<()> {
         match (old_version, new_version) {
-            (0, 1) => {
+            (0, _) => {
                 let table = upgrader.create_table(table_name)?;
                 table.create_index("uuid", true)?;
             },
@@ -112,7 +112,7 @@ impl From<std::string::String> for OrderProcessingError {
 }
 
 #[allow(dead_code)]
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct StartSimpleMakerBotRequest {
     cfg: SimpleMakerBotRegistry,
     price_url: Option<String>,
@@ -130,7 +130,7 @@ impl StartSimpleMakerBotRequest {
     }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, RpcSchema, Serialize)]
 pub struct StopSimpleMakerBotRes {
     result: String,
 }
@@ -140,7 +140,7 @@ impl StopSimpleMakerBotRes {
     pub fn get_result(&self) -> String { self.result.clone() }
 }
 
-#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, RpcSchema, Serialize)]
 pub struct StartSimpleMakerBotRes {
     result: String,
 }
@@ -155,7 +155,7 @@ enum VwapSide {
     Rel,
 }
 
-#[derive(Debug, Deserialize, Display, Serialize, SerializeErrorType)]
+#[derive(Debug, Deserialize, Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum StopSimpleMakerBotError {
     #[display(fmt = "The bot is already stopped")]
@@ -168,7 +168,7 @@ pub enum StopSimpleMakerBotError {
     InternalError(String),
 }
 
-#[derive(Debug, Deserialize, Display, Serialize, SerializeErrorType)]
+#[derive(Debug, Deserialize, Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum StartSimpleMakerBotError {
     #[display(fmt = "The bot is already started")]
@@ -530,7 +530,7 @@ async fn update_single_order(
 
     let resp = update_maker_order(ctx, req)
         .await
-        .map_to_mm(OrderProcessingError::OrderUpdateError)?;
+        .mm_err(|e| OrderProcessingError::OrderUpdateError(e.to_string()))?;
     info!("Successfully update order for {} - uuid: {}", key_trade_pair, resp.uuid);
     Ok(true)
 }
@@ -581,7 +581,7 @@ async fn create_single_order(
 
     let resp = create_maker_order(&ctx, req)
         .await
-        .map_to_mm(OrderProcessingError::OrderUpdateError)?;
+        .mm_err(|e| OrderProcessingError::OrderUpdateError(e.to_string()))?;
     info!("Successfully placed order for {} - uuid: {}", key_trade_pair, resp.uuid);
     Ok(true)
 }
@@ -0,0 +1,616 @@
+//! The taker request split across several makers.
+//!
+//! The split order requests the best orders of the pair, routes the requested volume across them
+//! starting from the best price and creates a `FillOrKill` taker order matching exactly one maker order for every part.
+//! Every part runs its own `TakerSwap`, and the parent order aggregates their statuses.
+//! The parent order is saved by [`MyOrdersStorage`], so its status is available after the node is restarted.
+
+use super::best_orders::{request_best_orders_for_pair, BestOrdersAction};
+use super::my_orders_storage::{MyOrdersError, MyOrdersStorage, MySplitTakerOrders};
+use super::orderbook_rpc::is_my_order;
+use super::{create_taker_order, AutoBuyInput, MatchBy, OrderType, OrderbookP2PItem, OrdermatchContext};
+use crate::mm2::lp_network::P2PRequestError;
+use crate::mm2::lp_swap::{active_swaps, check_balance_for_taker_swap, CheckBalanceError, SavedSwap, SavedSwapIo};
+use coins::{lp_coinfind_or_err, CoinFindError, FeeApproxStage};
+use common::log::{error, info};
+use common::{new_uuid, now_ms, HttpStatusCode};
+use crypto::CryptoCtx;
+use derive_more::Display;
+use http::StatusCode;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::{construct_detailed, MmNumber};
+use rpc::v1::types::H256 as H256Json;
+use std::collections::HashSet;
+use uuid::Uuid;
+
+construct_detailed!(DetailedVolume, volume);
+construct_detailed!(DetailedFilledVolume, filled_volume);
+construct_detailed!(DetailedUnfilledVolume, unfilled_volume);
+
+const DEFAULT_MAX_MAKERS: usize = 10;
+
+fn default_max_makers() -> usize { DEFAULT_MAX_MAKERS }
+
+pub type SplitTakerOrderResult<T> = Result<T, MmError<SplitTakerOrderError>>;
+
+#[derive(Deserialize, RpcSchema)]
+pub struct SplitTakerOrderRequest {
+    base: String,
+    rel: String,
+    /// Whether to buy or sell the `base` coin.
+    method: BestOrdersAction,
+    /// The worst price in `rel` per one unit of the `base` coin the taker accepts.
+    price: MmNumber,
+    /// The amount of the `base` coin to be bought or sold.
+    volume: MmNumber,
+    /// The maximum number of makers the volume can be split across.
+    #[serde(default = "default_max_makers")]
+    max_makers: usize,
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct SplitTakerOrderStatusRequest {
+    uuid: Uuid,
+}
+
+/// The parent order of the taker orders split across several makers.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SplitTakerOrder {
+    pub(super) uuid: Uuid,
+    base: String,
+    rel: String,
+    method: BestOrdersAction,
+    price: MmNumber,
+    volume: MmNumber,
+    created_at: u64,
+    parts: Vec<SplitTakerOrderPart>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+struct SplitTakerOrderPart {
+    /// The UUID of the taker order and of the swap started by it.
+    uuid: Uuid,
+    maker_order_uuid: Uuid,
+    maker_pubkey: String,
+    /// The maker order price.
+    maker_price: MmNumber,
+    /// The part volume in the `base` coin.
+    volume: MmNumber,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, RpcSchema, Serialize)]
+pub enum SplitTakerOrderPartStatus {
+    /// The taker order is waiting for the maker to reserve it.
+    Matching,
+    /// The swap is in progress.
+    Ongoing,
+    /// The swap finished successfully.
+    Finished,
+    /// The swap failed.
+    Failed,
+    /// The maker didn't reserve the order before the timeout.
+    NotMatched,
+}
+
+impl SplitTakerOrderPartStatus {
+    fn is_in_progress(&self) -> bool {
+        matches!(
+            self,
+            SplitTakerOrderPartStatus::Matching | SplitTakerOrderPartStatus::Ongoing
+        )
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, RpcSchema, Serialize)]
+pub enum SplitTakerOrderStatus {
+    InProgress,
+    Filled,
+    PartiallyFilled,
+    NotFilled,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct SplitTakerOrderPartResponse {
+    uuid: Uuid,
+    maker_order_uuid: Uuid,
+    maker_pubkey: String,
+    maker_price: MmNumber,
+    #[serde(flatten)]
+    volume: DetailedVolume,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status: Option<SplitTakerOrderPartStatus>,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct SplitTakerOrderResponse {
+    uuid: Uuid,
+    base: String,
+    rel: String,
+    method: BestOrdersAction,
+    #[serde(flatten)]
+    volume: DetailedVolume,
+    #[serde(flatten)]
+    unfilled_volume: DetailedUnfilledVolume,
+    parts: Vec<SplitTakerOrderPartResponse>,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct SplitTakerOrderStatusResponse {
+    uuid: Uuid,
+    base: String,
+    rel: String,
+    method: BestOrdersAction,
+    created_at: u64,
+    status: SplitTakerOrderStatus,
+    #[serde(flatten)]
+    volume: DetailedVolume,
+    #[serde(flatten)]
+    filled_volume: DetailedFilledVolume,
+    parts: Vec<SplitTakerOrderPartResponse>,
+}
+
+#[derive(Debug, Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum SplitTakerOrderError {
+    #[display(fmt = "No such coin {}", coin)]
+    NoSuchCoin { coin: String },
+    #[display(fmt = "Coin {} is wallet only", coin)]
+    CoinIsWalletOnly { coin: String },
+    #[display(fmt = "Rel coin can not be same as base")]
+    BaseEqualRel,
+    #[display(fmt = "'max_makers' must be greater than 0")]
+    MaxMakersIsZero,
+    #[display(fmt = "{}", _0)]
+    NotSufficientBalance(String),
+    #[display(fmt = "{}", _0)]
+    VolumeTooLow(String),
+    #[display(fmt = "No orders matching the price {} found", _0)]
+    NoMatchingOrders(MmNumber),
+    #[display(fmt = "Split taker order {} not found", _0)]
+    OrderNotFound(Uuid),
+    #[display(fmt = "P2P error: {}", _0)]
+    P2PError(String),
+    #[display(fmt = "Transport error: {}", _0)]
+    Transport(String),
+    #[display(fmt = "Internal error: {}", _0)]
+    InternalError(String),
+}
+
+impl HttpStatusCode for SplitTakerOrderError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SplitTakerOrderError::NoSuchCoin { .. }
+            | SplitTakerOrderError::CoinIsWalletOnly { .. }
+            | SplitTakerOrderError::BaseEqualRel
+            | SplitTakerOrderError::MaxMakersIsZero
+            | SplitTakerOrderError::NotSufficientBalance(_)
+            | SplitTakerOrderError::VolumeTooLow(_)
+            | SplitTakerOrderError::NoMatchingOrders(_) => StatusCode::BAD_REQUEST,
+            SplitTakerOrderError::OrderNotFound(_) => StatusCode::NOT_FOUND,
+            SplitTakerOrderError::P2PError(_)
+            | SplitTakerOrderError::Transport(_)
+            | SplitTakerOrderError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<CoinFindError> for SplitTakerOrderError {
+    fn from(e: CoinFindError) -> Self {
+        match e {
+            CoinFindError::NoSuchCoin { coin } => SplitTakerOrderError::NoSuchCoin { coin },
+        }
+    }
+}
+
+impl From<CheckBalanceError> for SplitTakerOrderError {
+    fn from(e: CheckBalanceError) -> Self {
+        match e {
+            e @ CheckBalanceError::NotSufficientBalance { .. }
+            | e @ CheckBalanceError::NotSufficientBaseCoinBalance { .. } => {
+                SplitTakerOrderError::NotSufficientBalance(e.to_string())
+            },
+            e @ CheckBalanceError::VolumeTooLow { .. } => SplitTakerOrderError::VolumeTooLow(e.to_string()),
+            CheckBalanceError::Transport(transport) => SplitTakerOrderError::Transport(transport),
+            CheckBalanceError::InternalError(internal) => SplitTakerOrderError::InternalError(internal),
+        }
+    }
+}
+
+impl From<P2PRequestError> for SplitTakerOrderError {
+    fn from(e: P2PRequestError) -> Self { SplitTakerOrderError::P2PError(e.to_string()) }
+}
+
+pub async fn start_split_taker_order(
+    ctx: MmArc,
+    req: SplitTakerOrderRequest,
+) -> SplitTakerOrderResult<SplitTakerOrderResponse> {
+    if req.base == req.rel {
+        return MmError::err(SplitTakerOrderError::BaseEqualRel);
+    }
+    if req.max_makers == 0 {
+        return MmError::err(SplitTakerOrderError::MaxMakersIsZero);
+    }
+    let base_coin = lp_coinfind_or_err(&ctx, &req.base).await?;
+    let rel_coin = lp_coinfind_or_err(&ctx, &req.rel).await?;
+    if base_coin.wallet_only(&ctx) {
+        return MmError::err(SplitTakerOrderError::CoinIsWalletOnly { coin: req.base });
+    }
+    if rel_coin.wallet_only(&ctx) {
+        return MmError::err(SplitTakerOrderError::CoinIsWalletOnly { coin: req.rel });
+    }
+
+    // Check if the whole volume can be traded before splitting it.
+    let (my_coin, other_coin, my_volume) = match req.method {
+        BestOrdersAction::Buy => (&rel_coin, &base_coin, &req.volume * &req.price),
+        BestOrdersAction::Sell => (&base_coin, &rel_coin, req.volume.clone()),
+    };
+    check_balance_for_taker_swap(
+        &ctx,
+        my_coin,
+        other_coin,
+        my_volume,
+        None,
+        None,
+        FeeApproxStage::OrderIssue,
+    )
+    .await?;
+
+    let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).map_to_mm(SplitTakerOrderError::InternalError)?;
+    let best_orders = request_best_orders_for_pair(
+        &ctx,
+        &ordermatch_ctx.orderbook_ticker_bypass(&req.base),
+        &ordermatch_ctx.orderbook_ticker_bypass(&req.rel),
+        req.method,
+        req.volume.to_ratio(),
+    )
+    .await?;
+
+    let my_pubkey = ctx.secp256k1_key_pair_as_option().map(|_| {
+        CryptoCtx::from_ctx(&ctx)
+            .expect("ctx is available")
+            .secp256k1_pubkey_hex()
+    });
+    let routes = route_volume(
+        &best_orders,
+        req.method,
+        &req.price,
+        &req.volume,
+        req.max_makers,
+        &my_pubkey,
+    );
+    if routes.is_empty() {
+        return MmError::err(SplitTakerOrderError::NoMatchingOrders(req.price));
+    }
+
+    let uuid = new_uuid();
+    let mut parts = Vec::with_capacity(routes.len());
+    for route in routes {
+        let input = AutoBuyInput {
+            base: req.base.clone(),
+            rel: req.rel.clone(),
+            price: req.price.clone(),
+            volume: route.volume.clone(),
+            timeout: None,
+            duration: None,
+            method: req.method.taker_method().to_owned(),
+            gui: None,
+            dest_pub_key: H256Json::default(),
+            match_by: MatchBy::Orders(HashSet::from([route.maker_order_uuid])),
+            order_type: OrderType::FillOrKill,
+            base_confs: None,
+            base_nota: None,
+            rel_confs: None,
+            rel_nota: None,
+            min_volume: None,
+            save_in_history: true,
+        };
+        match create_taker_order(&ctx, &base_coin, &rel_coin, input).await {
+            Ok(order) => parts.push(SplitTakerOrderPart {
+                uuid: order.request.uuid,
+                ..route
+            }),
+            Err(e) => error!(
+                "Error creating the part of the split taker order {} matching the maker order {}: {}",
+                uuid, route.maker_order_uuid, e
+            ),
+        }
+    }
+    if parts.is_empty() {
+        return MmError::err(SplitTakerOrderError::NoMatchingOrders(req.price));
+    }
+    info!("Split taker order {} into {} parts", uuid, parts.len());
+
+    let order = SplitTakerOrder {
+        uuid,
+        base: req.base,
+        rel: req.rel,
+        method: req.method,
+        price: req.price,
+        volume: req.volume,
+        created_at: now_ms() / 1000,
+        parts,
+    };
+    MyOrdersStorage::new(ctx.clone())
+        .save_split_taker_order(&order)
+        .await
+        .mm_err(|e| SplitTakerOrderError::InternalError(e.to_string()))?;
+
+    let routed_volume = order
+        .parts
+        .iter()
+        .fold(MmNumber::from(0), |total, part| total + part.volume.clone());
+    Ok(SplitTakerOrderResponse {
+        uuid,
+        base: order.base,
+        rel: order.rel,
+        method: order.method,
+        unfilled_volume: (&order.volume - &routed_volume).into(),
+        volume: order.volume.into(),
+        parts: order
+            .parts
+            .into_iter()
+            .map(|part| SplitTakerOrderPartResponse::new(part, None))
+            .collect(),
+    })
+}
+
+pub async fn split_taker_order_status(
+    ctx: MmArc,
+    req: SplitTakerOrderStatusRequest,
+) -> SplitTakerOrderResult<SplitTakerOrderStatusResponse> {
+    let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).map_to_mm(SplitTakerOrderError::InternalError)?;
+    let order = MyOrdersStorage::new(ctx.clone())
+        .load_split_taker_order(req.uuid)
+        .await
+        .mm_err(|e| match e {
+            MyOrdersError::NoSuchOrder { uuid } => SplitTakerOrderError::OrderNotFound(uuid),
+            e => SplitTakerOrderError::InternalError(e.to_string()),
+        })?;
+
+    let running_swaps = active_swaps(&ctx).map_to_mm(SplitTakerOrderError::InternalError)?;
+    let mut filled_volume = MmNumber::from(0);
+    let mut in_progress = false;
+    let mut parts = Vec::with_capacity(order.parts.len());
+    for part in order.parts {
+        let status = part_status(&ctx, &ordermatch_ctx, &running_swaps, &part.uuid).await?;
+        if status == SplitTakerOrderPartStatus::Finished {
+            filled_volume += part.volume.clone();
+        }
+        in_progress |= status.is_in_progress();
+        parts.push(SplitTakerOrderPartResponse::new(part, Some(status)));
+    }
+
+    let status = if in_progress {
+        SplitTakerOrderStatus::InProgress
+    } else if filled_volume == order.volume {
+        SplitTakerOrderStatus::Filled
+    } else if filled_volume == MmNumber::from(0) {
+        SplitTakerOrderStatus::NotFilled
+    } else {
+        SplitTakerOrderStatus::PartiallyFilled
+    };
+    Ok(SplitTakerOrderStatusResponse {
+        uuid: order.uuid,
+        base: order.base,
+        rel: order.rel,
+        method: order.method,
+        created_at: order.created_at,
+        status,
+        volume: order.volume.into(),
+        filled_volume: filled_volume.into(),
+        parts,
+    })
+}
+
+async fn part_status(
+    ctx: &MmArc,
+    ordermatch_ctx: &OrdermatchContext,
+    running_swaps: &[Uuid],
+    uuid: &Uuid,
+) -> SplitTakerOrderResult<SplitTakerOrderPartStatus> {
+    if ordermatch_ctx.my_taker_orders.lock().await.contains_key(uuid) {
+        return Ok(SplitTakerOrderPartStatus::Matching);
+    }
+    if running_swaps.contains(uuid) {
+        return Ok(SplitTakerOrderPartStatus::Ongoing);
+    }
+    let saved_swap = SavedSwap::load_my_swap_from_db(ctx, *uuid)
+        .await
+        .mm_err(|e| SplitTakerOrderError::InternalError(e.to_string()))?;
+    let status = match saved_swap {
+        Some(swap) if swap.is_finished_and_success() => SplitTakerOrderPartStatus::Finished,
+        Some(swap) if swap.is_finished() => SplitTakerOrderPartStatus::Failed,
+        Some(_) => SplitTakerOrderPartStatus::Ongoing,
+        None => SplitTakerOrderPartStatus::NotMatched,
+    };
+    Ok(status)
+}
+
+impl SplitTakerOrderPartResponse {
+    fn new(part: SplitTakerOrderPart, status: Option<SplitTakerOrderPartStatus>) -> Self {
+        SplitTakerOrderPartResponse {
+            uuid: part.uuid,
+            maker_order_uuid: part.maker_order_uuid,
+            maker_pubkey: part.maker_pubkey,
+            maker_price: part.maker_price,
+            volume: part.volume.into(),
+            status,
+        }
+    }
+}
+
+impl BestOrdersAction {
+    /// The name of the legacy RPC method that creates the taker order.
+    fn taker_method(&self) -> &'static str {
+        match self {
+            BestOrdersAction::Buy => "buy",
+            BestOrdersAction::Sell => "sell",
+        }
+    }
+}
+
+/// Splits the `volume` across the `orders` sorted by price, the best first.
+/// The orders not matching the `price` and the orders of `my_pubkey` are skipped.
+/// Note the [`SplitTakerOrderPart::uuid`] is not generated yet.
+fn route_volume(
+    orders: &[OrderbookP2PItem],
+    method: BestOrdersAction,
+    price: &MmNumber,
+    volume: &MmNumber,
+    max_makers: usize,
+    my_pubkey: &Option<String>,
+) -> Vec<SplitTakerOrderPart> {
+    let zero = MmNumber::from(0);
+    let one = MmNumber::from(1);
+    let mut remaining = volume.clone();
+    let mut routes = Vec::new();
+    for order in orders {
+        if routes.len() >= max_makers || remaining <= zero {
+            break;
+        }
+        if is_my_order(my_pubkey, &order.pubkey) {
+            continue;
+        }
+
+        let maker_price = MmNumber::from(order.price.clone());
+        let max_volume = MmNumber::from(order.max_volume.clone());
+        let min_volume = MmNumber::from(order.min_volume.clone());
+        // Convert the maker order volumes to the `base` coin of the taker request.
+        let (max_volume, min_volume) = match method {
+            BestOrdersAction::Buy => {
+                if &maker_price > price {
+                    break;
+                }
+                (max_volume, min_volume)
+            },
+            BestOrdersAction::Sell => {
+                // The maker price is the amount of `base` per 1 unit of `rel` in this case.
+                if &maker_price * price > one {
+                    break;
+                }
+                (&max_volume * &maker_price, &min_volume * &maker_price)
+            },
+        };
+
+        let part_volume = if remaining < max_volume {
+            remaining.clone()
+        } else {
+            max_volume
+        };
+        if part_volume < min_volume {
+            continue;
+        }
+        remaining = &remaining - &part_volume;
+        routes.push(SplitTakerOrderPart {
+            uuid: Uuid::nil(),
+            maker_order_uuid: order.uuid,
+            maker_pubkey: order.pubkey.clone(),
+            maker_price,
+            volume: part_volume,
+        });
+    }
+    routes
+}
+
+#[cfg(test)]
+mod split_taker_order_tests {
+    use super::*;
+    use mm2_number::BigRational;
+
+    fn maker_order(pubkey: &str, price: (i64, i64), max_volume: i64, min_volume: (i64, i64)) -> OrderbookP2PItem {
+        OrderbookP2PItem {
+            pubkey: pubkey.to_owned(),
+            base: "RICK".to_owned(),
+            rel: "MORTY".to_owned(),
+            price: BigRational::new(price.0.into(), price.1.into()),
+            max_volume: BigRational::from_integer(max_volume.into()),
+            min_volume: BigRational::new(min_volume.0.into(), min_volume.1.into()),
+            uuid: Uuid::new_v4(),
+            created_at: 0,
+        }
+    }
+
+    #[test]
+    fn test_route_volume_buy() {
+        let orders = vec![
+            maker_order("maker1", (1, 1), 3, (1, 10)),
+            maker_order("mine", (1, 1), 100, (1, 10)),
+            maker_order("maker2", (2, 1), 4, (1, 10)),
+            maker_order("maker3", (3, 1), 100, (1, 10)),
+        ];
+        let my_pubkey = Some("mine".to_owned());
+
+        let routes = route_volume(
+            &orders,
+            BestOrdersAction::Buy,
+            &MmNumber::from(2),
+            &MmNumber::from(5),
+            10,
+            &my_pubkey,
+        );
+        let actual: Vec<_> = routes.iter().map(|r| (r.maker_order_uuid, r.volume.clone())).collect();
+        let expected = vec![(orders[0].uuid, MmNumber::from(3)), (orders[2].uuid, MmNumber::from(2))];
+        assert_eq!(actual, expected);
+
+        // The third order doesn't match the price.
+        let routes = route_volume(
+            &orders,
+            BestOrdersAction::Buy,
+            &MmNumber::from(2),
+            &MmNumber::from(10),
+            10,
+            &my_pubkey,
+        );
+        let total = routes
+            .iter()
+            .fold(MmNumber::from(0), |total, r| total + r.volume.clone());
+        assert_eq!(total, MmNumber::from(7));
+
+        let routes = route_volume(
+            &orders,
+            BestOrdersAction::Buy,
+            &MmNumber::from(2),
+            &MmNumber::from(5),
+            1,
+            &my_pubkey,
+        );
+        assert_eq!(routes.len(), 1);
+    }
+
+    #[test]
+    fn test_route_volume_sell() {
+        // The maker sells MORTY for RICK, so the taker sells RICK for MORTY.
+        let orders = vec![
+            maker_order("maker1", (1, 2), 4, (1, 1)),
+            maker_order("maker2", (1, 1), 10, (8, 1)),
+            maker_order("maker3", (2, 1), 10, (1, 10)),
+        ];
+
+        // The taker wants at least 1 MORTY per 1 RICK, so the third order doesn't match.
+        let routes = route_volume(
+            &orders,
+            BestOrdersAction::Sell,
+            &MmNumber::from(1),
+            &MmNumber::from(10),
+            10,
+            &None,
+        );
+        let actual: Vec<_> = routes.iter().map(|r| (r.maker_order_uuid, r.volume.clone())).collect();
+        // 4 MORTY of the first order cost 2 RICK, the rest 8 RICK fit the min volume of the second order.
+        let expected = vec![(orders[0].uuid, MmNumber::from(2)), (orders[1].uuid, MmNumber::from(8))];
+        assert_eq!(actual, expected);
+
+        // The rest 3 RICK is less than the min volume of the second order.
+        let routes = route_volume(
+            &orders,
+            BestOrdersAction::Sell,
+            &MmNumber::from(1),
+            &MmNumber::from(5),
+            10,
+            &None,
+        );
+        let actual: Vec<_> = routes.iter().map(|r| (r.maker_order_uuid, r.volume.clone())).collect();
+        assert_eq!(actual, vec![(orders[0].uuid, MmNumber::from(2))]);
+    }
+}
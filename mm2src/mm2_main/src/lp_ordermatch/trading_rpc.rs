@@ -0,0 +1,400 @@
+//! The v2 versions of the trading RPCs: `sell`, `buy`, `setprice`, `update_maker_order`, `my_orders` and `order_status`.
+//!
+//! The responses keep the format of the legacy RPCs, but the errors are typed.
+
+use super::my_orders_storage::{MyOrdersError, MyOrdersFilteringHistory, MyOrdersHistory, MyOrdersStorage};
+use super::{create_maker_order, create_taker_order, get_true, update_maker_order, AutoBuyInput, LpautobuyResult,
+            MakerOrderBuildError, MakerOrderForMyOrdersRpc, MakerOrderForRpc, MakerOrderUpdateReq, MatchBy,
+            OrderForRpc, OrderForRpcWithCancellationReason, OrderType, OrdermatchContext, SetPriceReq, TakerAction,
+            TakerOrderBuildError, TakerOrderForRpc};
+use crate::mm2::lp_swap::{check_balance_for_taker_swap, check_secret_hash_algo_compatibility, CheckBalanceError};
+use coins::{lp_coinfind_or_err, BalanceError, CoinFindError, FeeApproxStage};
+use common::HttpStatusCode;
+use derive_more::Display;
+use http::StatusCode;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::{BigDecimal, MmNumber};
+use rpc::v1::types::H256 as H256Json;
+use serde::Serialize;
+use serde_json::{self as json, Value as Json};
+use std::collections::HashMap;
+use uuid::Uuid;
+
+pub type OrdermatchRpcResult<T> = Result<T, MmError<OrdermatchRpcError>>;
+
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum OrdermatchRpcError {
+    #[display(fmt = "No such coin {}", coin)]
+    NoSuchCoin { coin: String },
+    #[display(fmt = "Coin {} is wallet only", coin)]
+    CoinIsWalletOnly { coin: String },
+    #[display(fmt = "Rel coin can not be same as base")]
+    BaseEqualRel,
+    #[display(fmt = "{}", _0)]
+    IncompatibleCoins(String),
+    #[display(
+        fmt = "Not enough {} for swap: available {}, required at least {}, locked by swaps {:?}",
+        coin,
+        available,
+        required,
+        locked_by_swaps
+    )]
+    NotSufficientBalance {
+        coin: String,
+        available: BigDecimal,
+        required: BigDecimal,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        locked_by_swaps: Option<BigDecimal>,
+    },
+    #[display(
+        fmt = "Not enough base coin {} balance for swap: available {}, required at least {}, locked by swaps {:?}",
+        coin,
+        available,
+        required,
+        locked_by_swaps
+    )]
+    NotSufficientBaseCoinBalance {
+        coin: String,
+        available: BigDecimal,
+        required: BigDecimal,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        locked_by_swaps: Option<BigDecimal>,
+    },
+    #[display(
+        fmt = "The volume {} of the {} coin less than minimum transaction amount {}",
+        volume,
+        coin,
+        threshold
+    )]
+    VolumeTooLow {
+        coin: String,
+        volume: BigDecimal,
+        threshold: BigDecimal,
+    },
+    #[display(fmt = "Price {} is too low, required at least {}", price, threshold)]
+    PriceTooLow { price: BigDecimal, threshold: BigDecimal },
+    #[display(fmt = "Incorrect use of the '{}' parameter: {}", param, reason)]
+    InvalidParam { param: String, reason: String },
+    #[display(fmt = "Order {} not found", uuid)]
+    OrderNotFound { uuid: Uuid },
+    #[display(fmt = "Order {} is being matched now", uuid)]
+    OrderBeingMatched { uuid: Uuid },
+    /// The order was changed by another process while being updated. The request can be retried.
+    #[display(fmt = "Order {} state has changed during the update", uuid)]
+    OrderStateChanged { uuid: Uuid },
+    #[display(fmt = "P2P error: {}", _0)]
+    P2PError(String),
+    #[display(fmt = "Transport error: {}", _0)]
+    Transport(String),
+    #[display(fmt = "Internal error: {}", _0)]
+    InternalError(String),
+}
+
+impl HttpStatusCode for OrdermatchRpcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            OrdermatchRpcError::NoSuchCoin { .. }
+            | OrdermatchRpcError::CoinIsWalletOnly { .. }
+            | OrdermatchRpcError::BaseEqualRel
+            | OrdermatchRpcError::IncompatibleCoins(_)
+            | OrdermatchRpcError::NotSufficientBalance { .. }
+            | OrdermatchRpcError::NotSufficientBaseCoinBalance { .. }
+            | OrdermatchRpcError::VolumeTooLow { .. }
+            | OrdermatchRpcError::PriceTooLow { .. }
+            | OrdermatchRpcError::InvalidParam { .. } => StatusCode::BAD_REQUEST,
+            OrdermatchRpcError::OrderNotFound { .. } => StatusCode::NOT_FOUND,
+            OrdermatchRpcError::OrderBeingMatched { .. } | OrdermatchRpcError::OrderStateChanged { .. } => {
+                StatusCode::CONFLICT
+            },
+            OrdermatchRpcError::P2PError(_)
+            | OrdermatchRpcError::Transport(_)
+            | OrdermatchRpcError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<CoinFindError> for OrdermatchRpcError {
+    fn from(e: CoinFindError) -> Self {
+        match e {
+            CoinFindError::NoSuchCoin { coin } => OrdermatchRpcError::NoSuchCoin { coin },
+        }
+    }
+}
+
+impl From<BalanceError> for OrdermatchRpcError {
+    fn from(e: BalanceError) -> Self { OrdermatchRpcError::from(CheckBalanceError::from(e)) }
+}
+
+impl From<CheckBalanceError> for OrdermatchRpcError {
+    fn from(e: CheckBalanceError) -> Self {
+        match e {
+            CheckBalanceError::NotSufficientBalance {
+                coin,
+                available,
+                required,
+                locked_by_swaps,
+            } => OrdermatchRpcError::NotSufficientBalance {
+                coin,
+                available,
+                required,
+                locked_by_swaps,
+            },
+            CheckBalanceError::NotSufficientBaseCoinBalance {
+                coin,
+                available,
+                required,
+                locked_by_swaps,
+            } => OrdermatchRpcError::NotSufficientBaseCoinBalance {
+                coin,
+                available,
+                required,
+                locked_by_swaps,
+            },
+            CheckBalanceError::VolumeTooLow {
+                coin,
+                volume,
+                threshold,
+            } => OrdermatchRpcError::VolumeTooLow {
+                coin,
+                volume,
+                threshold,
+            },
+            CheckBalanceError::Transport(transport) => OrdermatchRpcError::Transport(transport),
+            CheckBalanceError::InternalError(internal) => OrdermatchRpcError::InternalError(internal),
+        }
+    }
+}
+
+impl From<MyOrdersError> for OrdermatchRpcError {
+    fn from(e: MyOrdersError) -> Self {
+        match e {
+            MyOrdersError::NoSuchOrder { uuid } => OrdermatchRpcError::OrderNotFound { uuid },
+            e => OrdermatchRpcError::InternalError(e.to_string()),
+        }
+    }
+}
+
+impl OrdermatchRpcError {
+    pub fn from_maker_order_build_error(e: MakerOrderBuildError, base: &str, rel: &str) -> OrdermatchRpcError {
+        match e {
+            MakerOrderBuildError::BaseEqualRel => OrdermatchRpcError::BaseEqualRel,
+            MakerOrderBuildError::MaxBaseVolTooLow { actual, threshold } => OrdermatchRpcError::VolumeTooLow {
+                coin: base.to_owned(),
+                volume: actual.to_decimal(),
+                threshold: threshold.to_decimal(),
+            },
+            MakerOrderBuildError::RelVolTooLow { actual, threshold } => OrdermatchRpcError::VolumeTooLow {
+                coin: rel.to_owned(),
+                volume: actual.to_decimal(),
+                threshold: threshold.to_decimal(),
+            },
+            MakerOrderBuildError::PriceTooLow { actual, threshold } => OrdermatchRpcError::PriceTooLow {
+                price: actual.to_decimal(),
+                threshold: threshold.to_decimal(),
+            },
+            e @ MakerOrderBuildError::MinBaseVolTooLow { .. }
+            | e @ MakerOrderBuildError::MaxBaseVolBelowMinBaseVol { .. } => OrdermatchRpcError::InvalidParam {
+                param: "min_volume".to_owned(),
+                reason: e.to_string(),
+            },
+            e @ MakerOrderBuildError::ConfSettingsNotSet => OrdermatchRpcError::InternalError(e.to_string()),
+        }
+    }
+
+    pub fn from_taker_order_build_error(e: TakerOrderBuildError, base: &str, rel: &str) -> OrdermatchRpcError {
+        match e {
+            TakerOrderBuildError::BaseEqualRel => OrdermatchRpcError::BaseEqualRel,
+            TakerOrderBuildError::BaseAmountTooLow { actual, threshold } => OrdermatchRpcError::VolumeTooLow {
+                coin: base.to_owned(),
+                volume: actual.to_decimal(),
+                threshold: threshold.to_decimal(),
+            },
+            TakerOrderBuildError::RelAmountTooLow { actual, threshold } => OrdermatchRpcError::VolumeTooLow {
+                coin: rel.to_owned(),
+                volume: actual.to_decimal(),
+                threshold: threshold.to_decimal(),
+            },
+            e @ TakerOrderBuildError::MinVolumeTooLow { .. }
+            | e @ TakerOrderBuildError::MaxBaseVolBelowMinBaseVol { .. } => OrdermatchRpcError::InvalidParam {
+                param: "min_volume".to_owned(),
+                reason: e.to_string(),
+            },
+            e @ TakerOrderBuildError::SenderPubkeyIsZero | e @ TakerOrderBuildError::ConfsSettingsNotSet => {
+                OrdermatchRpcError::InternalError(e.to_string())
+            },
+        }
+    }
+}
+
+fn to_json<T: Serialize>(value: T) -> OrdermatchRpcResult<Json> {
+    json::to_value(value).map_to_mm(|e| OrdermatchRpcError::InternalError(e.to_string()))
+}
+
+/// The request of the `sell` and `buy` RPCs.
+#[derive(Deserialize, RpcSchema)]
+pub struct TakerOrderRequest {
+    base: String,
+    rel: String,
+    price: MmNumber,
+    volume: MmNumber,
+    #[serde(default)]
+    timeout: Option<u64>,
+    #[serde(default)]
+    match_by: MatchBy,
+    #[serde(default)]
+    order_type: OrderType,
+    #[serde(default)]
+    base_confs: Option<u64>,
+    #[serde(default)]
+    base_nota: Option<bool>,
+    #[serde(default)]
+    rel_confs: Option<u64>,
+    #[serde(default)]
+    rel_nota: Option<bool>,
+    #[serde(default)]
+    min_volume: Option<MmNumber>,
+    #[serde(default = "get_true")]
+    save_in_history: bool,
+}
+
+pub async fn sell_rpc_v2(ctx: MmArc, req: TakerOrderRequest) -> OrdermatchRpcResult<Json> {
+    create_taker_order_rpc(ctx, req, TakerAction::Sell).await
+}
+
+pub async fn buy_rpc_v2(ctx: MmArc, req: TakerOrderRequest) -> OrdermatchRpcResult<Json> {
+    create_taker_order_rpc(ctx, req, TakerAction::Buy).await
+}
+
+async fn create_taker_order_rpc(ctx: MmArc, req: TakerOrderRequest, action: TakerAction) -> OrdermatchRpcResult<Json> {
+    if req.base == req.rel {
+        return MmError::err(OrdermatchRpcError::BaseEqualRel);
+    }
+    let base_coin = lp_coinfind_or_err(&ctx, &req.base).await?;
+    let rel_coin = lp_coinfind_or_err(&ctx, &req.rel).await?;
+    if base_coin.wallet_only(&ctx) {
+        return MmError::err(OrdermatchRpcError::CoinIsWalletOnly { coin: req.base });
+    }
+    if rel_coin.wallet_only(&ctx) {
+        return MmError::err(OrdermatchRpcError::CoinIsWalletOnly { coin: req.rel });
+    }
+    check_secret_hash_algo_compatibility(&base_coin, &rel_coin).map_to_mm(OrdermatchRpcError::IncompatibleCoins)?;
+
+    let (my_coin, other_coin, my_amount) = match action {
+        TakerAction::Buy => (&rel_coin, &base_coin, &req.volume * &req.price),
+        TakerAction::Sell => (&base_coin, &rel_coin, req.volume.clone()),
+    };
+    check_balance_for_taker_swap(
+        &ctx,
+        my_coin,
+        other_coin,
+        my_amount,
+        None,
+        None,
+        FeeApproxStage::OrderIssue,
+    )
+    .await?;
+
+    let method = match action {
+        TakerAction::Buy => "buy",
+        TakerAction::Sell => "sell",
+    };
+    let input = AutoBuyInput {
+        base: req.base,
+        rel: req.rel,
+        price: req.price,
+        volume: req.volume,
+        timeout: req.timeout,
+        duration: None,
+        method: method.to_owned(),
+        gui: None,
+        dest_pub_key: H256Json::default(),
+        match_by: req.match_by,
+        order_type: req.order_type,
+        base_confs: req.base_confs,
+        base_nota: req.base_nota,
+        rel_confs: req.rel_confs,
+        rel_nota: req.rel_nota,
+        min_volume: req.min_volume,
+        save_in_history: req.save_in_history,
+    };
+    let order = create_taker_order(&ctx, &base_coin, &rel_coin, input).await?;
+    to_json(LpautobuyResult {
+        request: (&order.request).into(),
+        order_type: &order.order_type,
+        min_volume: order.min_volume.clone().into(),
+        base_orderbook_ticker: &order.base_orderbook_ticker,
+        rel_orderbook_ticker: &order.rel_orderbook_ticker,
+    })
+}
+
+pub async fn set_price_rpc_v2(ctx: MmArc, req: SetPriceReq) -> OrdermatchRpcResult<Json> {
+    let order = create_maker_order(&ctx, req).await?;
+    to_json(MakerOrderForRpc::from(&order))
+}
+
+pub async fn update_maker_order_rpc_v2(ctx: MmArc, req: MakerOrderUpdateReq) -> OrdermatchRpcResult<Json> {
+    let order = update_maker_order(&ctx, req).await?;
+    to_json(MakerOrderForRpc::from(&order))
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct MyOrdersResponse {
+    maker_orders: HashMap<Uuid, Json>,
+    taker_orders: HashMap<Uuid, Json>,
+}
+
+pub async fn my_orders_rpc_v2(ctx: MmArc, _req: Json) -> OrdermatchRpcResult<MyOrdersResponse> {
+    let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).map_to_mm(OrdermatchRpcError::InternalError)?;
+    let my_maker_orders = ordermatch_ctx.maker_orders_ctx.lock().orders.clone();
+    let mut maker_orders = HashMap::with_capacity(my_maker_orders.len());
+    for (uuid, order_mutex) in my_maker_orders {
+        let order = order_mutex.lock().await;
+        maker_orders.insert(uuid, to_json(MakerOrderForMyOrdersRpc::from(&*order))?);
+    }
+
+    let my_taker_orders = ordermatch_ctx.my_taker_orders.lock().await;
+    let mut taker_orders = HashMap::with_capacity(my_taker_orders.len());
+    for (uuid, order) in my_taker_orders.iter() {
+        taker_orders.insert(*uuid, to_json(TakerOrderForRpc::from(order))?);
+    }
+    Ok(MyOrdersResponse {
+        maker_orders,
+        taker_orders,
+    })
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct OrderStatusRequest {
+    uuid: Uuid,
+}
+
+/// Returns the active order status or the order from the history with its cancellation reason.
+pub async fn order_status_rpc_v2(ctx: MmArc, req: OrderStatusRequest) -> OrdermatchRpcResult<Json> {
+    let ordermatch_ctx = OrdermatchContext::from_ctx(&ctx).map_to_mm(OrdermatchRpcError::InternalError)?;
+
+    let maybe_order_mutex = ordermatch_ctx.maker_orders_ctx.lock().get_order(&req.uuid).cloned();
+    if let Some(order_mutex) = maybe_order_mutex {
+        let order = order_mutex.lock().await;
+        return to_json(json!({
+            "type": "Maker",
+            "order": to_json(MakerOrderForMyOrdersRpc::from(&*order))?,
+        }));
+    }
+
+    if let Some(order) = ordermatch_ctx.my_taker_orders.lock().await.get(&req.uuid) {
+        return to_json(json!({
+            "type": "Taker",
+            "order": to_json(TakerOrderForRpc::from(order))?,
+        }));
+    }
+
+    let storage = MyOrdersStorage::new(ctx);
+    let order = storage.load_order_from_history(req.uuid).await?;
+    let cancellation_reason = storage.select_order_status(req.uuid).await?;
+    to_json(OrderForRpcWithCancellationReason {
+        order: OrderForRpc::from(&order),
+        cancellation_reason: &cancellation_reason,
+    })
+}
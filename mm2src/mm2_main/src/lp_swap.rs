@@ -57,8 +57,11 @@
 //  marketmaker
 //
 
+use crate::mm2::lp_message_service::{check_low_balance, MessageServiceContext, SWAP_FAILED_ROOM_ID,
+                                     SWAP_FINISHED_ROOM_ID};
 use crate::mm2::lp_network::{broadcast_p2p_msg, Libp2pPeerId};
 use async_std::sync as async_std_sync;
+use bitcrypto::{dhash160, sha256};
 use coins::{lp_coinfind, MmCoinEnum, TradeFee, TransactionEnum};
 use common::log::{debug, warn};
 use common::{bits256, calc_total_pages,
@@ -90,7 +93,11 @@ use std::sync::atomic::{AtomicU64, Ordering};
 #[path = "lp_swap/pubkey_banning.rs"] mod pubkey_banning;
 #[path = "lp_swap/recreate_swap_data.rs"] mod recreate_swap_data;
 #[path = "lp_swap/saved_swap.rs"] mod saved_swap;
+#[path = "lp_swap/simulate_swap.rs"] mod simulate_swap;
 #[path = "lp_swap/swap_lock.rs"] mod swap_lock;
+#[path = "lp_swap/swap_policy.rs"] mod swap_policy;
+#[path = "lp_swap/swap_recovery.rs"] mod swap_recovery;
+#[path = "lp_swap/swap_rpc.rs"] mod swap_rpc;
 #[path = "lp_swap/taker_swap.rs"] mod taker_swap;
 #[path = "lp_swap/trade_preimage.rs"] mod trade_preimage;
 
@@ -109,6 +116,13 @@ use pubkey_banning::BanReason;
 pub use pubkey_banning::{ban_pubkey_rpc, is_pubkey_banned, list_banned_pubkeys_rpc, unban_pubkeys_rpc};
 pub use recreate_swap_data::recreate_swap_data;
 pub use saved_swap::{SavedSwap, SavedSwapError, SavedSwapIo, SavedSwapResult};
+pub use simulate_swap::simulate_swap_rpc;
+pub use swap_policy::{apply_swap_policies, check_maker_locktime_multiplier, fetch_swap_policy_prices,
+                      swap_locktime_multiplier, swap_policy_requirements, CoinSwapPolicy, SwapPolicyPrices,
+                      SwapPolicyRequirements, SwapPolicyTier, MAX_LOCKTIME_MULTIPLIER};
+pub use swap_recovery::{swap_recovery_loop, SwapFundsRecovered};
+pub use swap_rpc::{active_swaps_rpc_v2, my_recent_swaps_rpc_v2, my_swap_status_rpc_v2, recover_funds_of_swap_rpc_v2,
+                   SwapRpcError, SwapRpcResult};
 use taker_swap::TakerSwapEvent;
 pub use taker_swap::{calc_max_taker_vol, check_balance_for_taker_swap, max_taker_vol, max_taker_vol_from_available,
                      run_taker_swap, taker_swap_trade_preimage, RunTakerSwapInput, TakerSavedSwap, TakerSwap,
@@ -308,7 +322,7 @@ const _SWAP_DEFAULT_MAX_CONFIRMS: u32 = 6;
 /// MM2 checks that swap payment is confirmed every WAIT_CONFIRM_INTERVAL seconds
 const WAIT_CONFIRM_INTERVAL: u64 = 15;
 
-#[derive(Debug, PartialEq, Serialize)]
+#[derive(Clone, Debug, PartialEq, RpcSchema, Serialize)]
 pub enum RecoveredSwapAction {
     RefundedMyPayment,
     SpentOtherPayment,
@@ -321,6 +335,17 @@ pub struct RecoveredSwap {
     transaction: TransactionEnum,
 }
 
+/// Whether the funds of a finished swap can be recovered right now.
+#[derive(Debug, PartialEq)]
+pub enum SwapRecoveryReadiness {
+    /// The my payment can be refunded or the other payment can be spent now.
+    Ready,
+    /// The my payment can be refunded in the given number of seconds.
+    HaveToWait(u64),
+    /// The funds are recovered already or there is nothing to recover.
+    NothingToRecover(String),
+}
+
 /// Represents the amount of a coin locked by ongoing swap
 #[derive(Debug)]
 pub struct LockedAmount {
@@ -366,6 +391,9 @@ struct SwapsContext {
     /// Very unpleasant consequences
     shutdown_rx: async_std_sync::Receiver<()>,
     swap_msgs: Mutex<HashMap<Uuid, SwapMsgStore>>,
+    /// The swaps which funds are recovered already or have nothing to recover, found by the swap recovery service
+    /// since the node start. The recovered swaps are also marked by the recovery events in the saved swaps.
+    recovered_swaps: Mutex<HashSet<Uuid>>,
     #[cfg(target_arch = "wasm32")]
     swap_db: ConstructibleDb<SwapDb>,
 }
@@ -393,6 +421,7 @@ impl SwapsContext {
                 banned_pubkeys: Mutex::new(HashMap::new()),
                 shutdown_rx,
                 swap_msgs: Mutex::new(HashMap::new()),
+                recovered_swaps: Mutex::new(HashSet::new()),
                 #[cfg(target_arch = "wasm32")]
                 swap_db: ConstructibleDb::new(ctx),
             })
@@ -489,7 +518,7 @@ pub fn active_swaps(ctx: &MmArc) -> Result<Vec<Uuid>, String> {
     Ok(uuids)
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, Deserialize, Eq, PartialEq, Serialize)]
 pub struct SwapConfirmationsSettings {
     pub maker_coin_confs: u64,
     pub maker_coin_nota: bool,
@@ -594,6 +623,92 @@ pub fn dex_fee_amount_from_taker_coin(taker_coin: &MmCoinEnum, maker_coin: &str,
     dex_fee_amount(taker_coin.ticker(), maker_coin, trade_amount, &dex_fee_threshold)
 }
 
+/// The hash function the swap payments are locked with.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SecretHashAlgo {
+    /// ripemd160(sha256(secret))
+    DHASH160,
+    /// sha256(secret)
+    SHA256,
+}
+
+impl SecretHashAlgo {
+    pub fn hash_secret(&self, secret: &[u8]) -> Vec<u8> {
+        match self {
+            SecretHashAlgo::DHASH160 => dhash160(secret).as_slice().to_vec(),
+            SecretHashAlgo::SHA256 => sha256(secret).as_slice().to_vec(),
+        }
+    }
+
+    pub fn hash_len(&self) -> usize {
+        match self {
+            SecretHashAlgo::DHASH160 => 20,
+            SecretHashAlgo::SHA256 => 32,
+        }
+    }
+}
+
+/// The Tendermint HTLCs support the SHA256 hash lock only.
+/// Note the Tendermint coins can be swapped with the coins supporting the SHA256 hash lock only,
+/// see [`check_secret_hash_algo_compatibility`].
+pub fn detect_secret_hash_algo(maker_coin: &MmCoinEnum, taker_coin: &MmCoinEnum) -> SecretHashAlgo {
+    if is_tendermint_coin(maker_coin) || is_tendermint_coin(taker_coin) {
+        SecretHashAlgo::SHA256
+    } else {
+        SecretHashAlgo::DHASH160
+    }
+}
+
+fn is_tendermint_coin(coin: &MmCoinEnum) -> bool {
+    matches!(coin, MmCoinEnum::Tendermint(_) | MmCoinEnum::TendermintToken(_))
+}
+
+/// The Tendermint coins and the coins locking the payments with the UTXO HTLC script support the SHA256 hash lock.
+/// The ETH/ERC20, QRC20, Solana/SPL and Lightning swaps are locked with `dhash160(secret)` only.
+fn supports_sha256_secret_hash(coin: &MmCoinEnum) -> bool {
+    match coin {
+        MmCoinEnum::UtxoCoin(_)
+        | MmCoinEnum::QtumCoin(_)
+        | MmCoinEnum::Bch(_)
+        | MmCoinEnum::SlpToken(_)
+        | MmCoinEnum::Tendermint(_)
+        | MmCoinEnum::TendermintToken(_) => true,
+        #[cfg(not(target_arch = "wasm32"))]
+        MmCoinEnum::ZCoin(_) => true,
+        _ => false,
+    }
+}
+
+/// Checks that both coins support the hash function the swap payments are locked with.
+pub fn check_secret_hash_algo_compatibility(base: &MmCoinEnum, rel: &MmCoinEnum) -> Result<(), String> {
+    if detect_secret_hash_algo(base, rel) == SecretHashAlgo::DHASH160 {
+        return Ok(());
+    }
+    for coin in [base, rel].iter() {
+        if !supports_sha256_secret_hash(coin) {
+            return ERR!(
+                "{} and {} can't be swapped: {} doesn't support the SHA256 hash lock required by the Tendermint coins",
+                base.ticker(),
+                rel.ticker(),
+                coin.ticker()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Returns the swap contract v2 address of the `coin` if the other side of the swap advertises the same address.
+/// The address is negotiated by [`coins::SwapOps::negotiate_swap_contract_addr`] as any other swap contract address.
+/// The swap contract v2 is opt-in for both sides, so the negotiated swap contract v1 is used otherwise.
+pub fn negotiate_swap_v2_contract(coin: &MmCoinEnum, other_swap_v2_contract: Option<&[u8]>) -> Option<BytesJson> {
+    let my_swap_v2_contract = coin.swap_v2_contract_address()?;
+    let other_swap_v2_contract = other_swap_v2_contract?;
+    match coin.negotiate_swap_contract_addr(Some(other_swap_v2_contract)) {
+        Ok(Some(negotiated)) if negotiated == my_swap_v2_contract => Some(negotiated),
+        _ => None,
+    }
+}
+
 #[derive(Clone, Debug, Eq, Deserialize, PartialEq, Serialize)]
 pub struct NegotiationDataV1 {
     started_at: u64,
@@ -621,6 +736,20 @@ pub struct NegotiationDataV3 {
     taker_coin_swap_contract: Vec<u8>,
     maker_coin_htlc_pub: Vec<u8>,
     taker_coin_htlc_pub: Vec<u8>,
+    /// The confirmations settings the sender applies to the swap including the per-coin swap policies.
+    /// Is `None` if the message is sent by an older node.
+    #[serde(default)]
+    conf_settings: Option<SwapConfirmationsSettings>,
+    /// The swap contract v2 addresses the sender supports, see [`negotiate_swap_v2_contract`].
+    /// Are `None` if the message is sent by an older node or the coins don't support the swap contract v2.
+    #[serde(default)]
+    maker_coin_swap_v2_contract: Option<Vec<u8>>,
+    #[serde(default)]
+    taker_coin_swap_v2_contract: Option<Vec<u8>>,
+    /// The payment locktime multiplier required by the sender swap policies, see [`check_maker_locktime_multiplier`].
+    /// Is `None` if the message is sent by an older node.
+    #[serde(default)]
+    locktime_multiplier: Option<u64>,
 }
 
 #[derive(Clone, Debug, Eq, Deserialize, PartialEq, Serialize)]
@@ -687,6 +816,34 @@ impl NegotiationDataMsg {
             NegotiationDataMsg::V3(v3) => Some(&v3.taker_coin_swap_contract),
         }
     }
+
+    pub fn maker_coin_swap_v2_contract(&self) -> Option<&[u8]> {
+        match self {
+            NegotiationDataMsg::V1(_) | NegotiationDataMsg::V2(_) => None,
+            NegotiationDataMsg::V3(v3) => v3.maker_coin_swap_v2_contract.as_deref(),
+        }
+    }
+
+    pub fn taker_coin_swap_v2_contract(&self) -> Option<&[u8]> {
+        match self {
+            NegotiationDataMsg::V1(_) | NegotiationDataMsg::V2(_) => None,
+            NegotiationDataMsg::V3(v3) => v3.taker_coin_swap_v2_contract.as_deref(),
+        }
+    }
+
+    pub fn conf_settings(&self) -> Option<&SwapConfirmationsSettings> {
+        match self {
+            NegotiationDataMsg::V1(_) | NegotiationDataMsg::V2(_) => None,
+            NegotiationDataMsg::V3(v3) => v3.conf_settings.as_ref(),
+        }
+    }
+
+    pub fn locktime_multiplier(&self) -> Option<u64> {
+        match self {
+            NegotiationDataMsg::V1(_) | NegotiationDataMsg::V2(_) => None,
+            NegotiationDataMsg::V3(v3) => v3.locktime_multiplier,
+        }
+    }
 }
 
 /// Data to be exchanged and validated on swap start, the replacement of LP_pubkeys_data, LP_choosei_data, etc.
@@ -737,7 +894,7 @@ async fn save_stats_swap(ctx: &MmArc, swap: &SavedSwap) -> Result<(), String> {
 
 /// The helper structure that makes easier to parse the response for GUI devs
 /// They won't have to parse the events themselves handling possible errors, index out of bounds etc.
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, RpcSchema)]
 pub struct MySwapInfo {
     pub my_coin: String,
     pub other_coin: String,
@@ -788,8 +945,8 @@ impl From<&str> for SwapError {
     fn from(e: &str) -> Self { SwapError { error: e.to_owned() } }
 }
 
-#[derive(Serialize)]
-struct MySwapStatusResponse {
+#[derive(RpcSchema, Serialize)]
+pub struct MySwapStatusResponse {
     #[serde(flatten)]
     swap: SavedSwap,
     my_info: Option<MySwapInfo>,
@@ -874,7 +1031,86 @@ async fn broadcast_my_swap_status(ctx: &MmArc, uuid: Uuid) -> Result<(), String>
     Ok(())
 }
 
-#[derive(Debug, Deserialize)]
+/// Spawns the processing of the finished swap,
+/// so a slow or hung notification service doesn't delay the completion of the swap.
+fn on_swap_finished(ctx: &MmArc, uuid: Uuid) { spawn(process_finished_swap(ctx.clone(), uuid)) }
+
+/// Collects the metrics of the finished swap, notifies about its outcome
+/// and checks whether the balances of the swap coins dropped below the configured thresholds.
+async fn process_finished_swap(ctx: MmArc, uuid: Uuid) {
+    let ctx = &ctx;
+    let swap = match SavedSwap::load_my_swap_from_db(ctx, uuid).await {
+        Ok(Some(swap)) => swap,
+        Ok(None) => return,
+        Err(e) => {
+            error!("Error loading swap {}: {}", uuid, e);
+            return;
+        },
+    };
+    collect_swap_metrics(ctx, &swap);
+    notify_swap_finished(ctx, &swap).await;
+
+    for ticker in [swap.maker_coin_ticker(), swap.taker_coin_ticker()].iter().flatten() {
+        if let Ok(Some(coin)) = lp_coinfind(ctx, ticker).await {
+            check_low_balance(ctx, &coin).await;
+        }
+    }
+}
+
+/// Records the outcome of the swap by pair and the duration of every swap step.
+/// A step duration is the time between the previous event and the event that finishes the step.
+fn collect_swap_metrics(ctx: &MmArc, swap: &SavedSwap) {
+    let role = match swap {
+        SavedSwap::Maker(_) => "maker",
+        SavedSwap::Taker(_) => "taker",
+    };
+    let maker_coin = swap.maker_coin_ticker().unwrap_or_default();
+    let taker_coin = swap.taker_coin_ticker().unwrap_or_default();
+    let result = if swap.is_finished_and_success() {
+        "success"
+    } else {
+        "failed"
+    };
+    mm_counter!(ctx.metrics, "swap.finished.count", 1,
+        "maker_coin" => maker_coin.clone(), "taker_coin" => taker_coin.clone(), "role" => role, "result" => result);
+    if let Some(event) = swap.first_error_event() {
+        mm_counter!(ctx.metrics, "swap.failed.count", 1,
+            "maker_coin" => maker_coin.clone(), "taker_coin" => taker_coin.clone(), "role" => role, "event" => event);
+    }
+
+    let events = swap.event_types();
+    for (prev, (event, timestamp)) in events.iter().zip(events.iter().skip(1)) {
+        let duration_ns = timestamp.saturating_sub(prev.1) * 1_000_000;
+        mm_timing!(ctx.metrics, "swap.step.timing", 0, duration_ns, "role" => role, "event" => event.clone());
+    }
+    if let (Some((_, started_at)), Some((_, finished_at))) = (events.first(), events.last()) {
+        let duration_ns = finished_at.saturating_sub(*started_at) * 1_000_000;
+        mm_timing!(ctx.metrics, "swap.total.timing", 0, duration_ns,
+            "maker_coin" => maker_coin, "taker_coin" => taker_coin, "role" => role);
+    }
+}
+
+/// Sends the [`SWAP_FINISHED_ROOM_ID`] or [`SWAP_FAILED_ROOM_ID`] message about the finished swap.
+async fn notify_swap_finished(ctx: &MmArc, swap: &SavedSwap) {
+    let uuid = swap.uuid();
+    let (room_id, outcome) = if swap.is_finished_and_success() {
+        (SWAP_FINISHED_ROOM_ID, "finished")
+    } else {
+        (SWAP_FAILED_ROOM_ID, "failed")
+    };
+    let msg = match swap.get_my_info() {
+        Some(info) => format!(
+            "Swap {} {}: {} {} for {} {}",
+            uuid, outcome, info.my_amount, info.my_coin, info.other_amount, info.other_coin
+        ),
+        None => format!("Swap {} {}", uuid, outcome),
+    };
+
+    let message_service_ctx = MessageServiceContext::from_ctx(ctx).unwrap();
+    let _ = message_service_ctx.send_message(msg, room_id, false).await;
+}
+
+#[derive(Debug, Deserialize, RpcSchema)]
 pub struct MySwapsFilter {
     pub my_coin: Option<String>,
     pub other_coin: Option<String>,
@@ -907,7 +1143,7 @@ pub async fn all_swaps_uuids_by_filter(ctx: MmArc, req: Json) -> Result<Response
     Ok(try_s!(Response::builder().body(res)))
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Deserialize, RpcSchema)]
 pub struct MyRecentSwapsReq {
     #[serde(flatten)]
     pub paging_options: PagingOptions,
@@ -1232,6 +1468,8 @@ mod lp_swap_tests {
     use serialization::{deserialize, serialize};
 
     use super::*;
+    use coins::{NegotiateSwapContractAddrErr, SwapOps, TestCoin};
+    use mocktopus::mocking::*;
 
     #[test]
     fn test_dex_fee_amount() {
@@ -1514,6 +1752,15 @@ mod lp_swap_tests {
             taker_coin_swap_contract: vec![1; 20],
             maker_coin_htlc_pub: vec![1; 33],
             taker_coin_htlc_pub: vec![1; 33],
+            conf_settings: Some(SwapConfirmationsSettings {
+                maker_coin_confs: 1,
+                maker_coin_nota: false,
+                taker_coin_confs: 2,
+                taker_coin_nota: true,
+            }),
+            maker_coin_swap_v2_contract: Some(vec![2; 20]),
+            taker_coin_swap_v2_contract: None,
+            locktime_multiplier: Some(2),
         });
 
         // v3 must be deserialized to v3, backward compatibility is not required
@@ -1522,6 +1769,96 @@ mod lp_swap_tests {
         let deserialized: NegotiationDataMsg = rmp_serde::from_read_ref(serialized.as_slice()).unwrap();
 
         assert_eq!(deserialized, v3);
+
+        // v3 without conf settings sent by an older node
+        #[derive(Serialize)]
+        struct NegotiationDataV3WithoutConfSettings {
+            started_at: u64,
+            payment_locktime: u64,
+            secret_hash: Vec<u8>,
+            maker_coin_swap_contract: Vec<u8>,
+            taker_coin_swap_contract: Vec<u8>,
+            maker_coin_htlc_pub: Vec<u8>,
+            taker_coin_htlc_pub: Vec<u8>,
+        }
+
+        let old_v3 = NegotiationDataV3WithoutConfSettings {
+            started_at: 0,
+            payment_locktime: 0,
+            secret_hash: vec![0; 20],
+            maker_coin_swap_contract: vec![1; 20],
+            taker_coin_swap_contract: vec![1; 20],
+            maker_coin_htlc_pub: vec![1; 33],
+            taker_coin_htlc_pub: vec![1; 33],
+        };
+
+        let expected = NegotiationDataMsg::V3(NegotiationDataV3 {
+            started_at: 0,
+            payment_locktime: 0,
+            secret_hash: vec![0; 20],
+            maker_coin_swap_contract: vec![1; 20],
+            taker_coin_swap_contract: vec![1; 20],
+            maker_coin_htlc_pub: vec![1; 33],
+            taker_coin_htlc_pub: vec![1; 33],
+            conf_settings: None,
+            maker_coin_swap_v2_contract: None,
+            taker_coin_swap_v2_contract: None,
+            locktime_multiplier: None,
+        });
+
+        let serialized = rmp_serde::to_vec(&old_v3).unwrap();
+
+        let deserialized: NegotiationDataMsg = rmp_serde::from_read_ref(serialized.as_slice()).unwrap();
+
+        assert_eq!(deserialized, expected);
+    }
+
+    #[test]
+    fn test_secret_hash_algo() {
+        let secret = [1; 32];
+        for algo in [SecretHashAlgo::DHASH160, SecretHashAlgo::SHA256].iter() {
+            assert_eq!(algo.hash_secret(&secret).len(), algo.hash_len());
+        }
+
+        let maker_coin = MmCoinEnum::Test(TestCoin::default());
+        let taker_coin = MmCoinEnum::Test(TestCoin::default());
+        assert_eq!(
+            detect_secret_hash_algo(&maker_coin, &taker_coin),
+            SecretHashAlgo::DHASH160
+        );
+        check_secret_hash_algo_compatibility(&maker_coin, &taker_coin).unwrap();
+        // The test coin stands for the coins locking the payments with `dhash160(secret)` only.
+        assert!(!supports_sha256_secret_hash(&maker_coin));
+    }
+
+    #[test]
+    fn test_negotiate_swap_v2_contract() {
+        let coin = MmCoinEnum::Test(TestCoin::default());
+        let swap_v2_contract = [2; 20];
+
+        TestCoin::swap_v2_contract_address.mock_safe(|_| MockResult::Return(Some(vec![2; 20].into())));
+        TestCoin::negotiate_swap_contract_addr.mock_safe(|_, other_addr| {
+            let result = match other_addr {
+                Some(addr) if addr == [2; 20] || addr == [1; 20] => Ok(Some(addr.to_vec().into())),
+                Some(addr) => MmError::err(NegotiateSwapContractAddrErr::UnexpectedOtherAddr(addr.into())),
+                None => MmError::err(NegotiateSwapContractAddrErr::NoOtherAddrAndNoFallback),
+            };
+            MockResult::Return(result)
+        });
+        assert_eq!(
+            negotiate_swap_v2_contract(&coin, Some(&swap_v2_contract[..])),
+            Some(swap_v2_contract.to_vec().into())
+        );
+        // The other side uses another swap contract v2.
+        assert_eq!(negotiate_swap_v2_contract(&coin, Some(&[3; 20][..])), None);
+        // The other side advertises the swap contract v1 as the swap contract v2.
+        assert_eq!(negotiate_swap_v2_contract(&coin, Some(&[1; 20][..])), None);
+        // The other side doesn't support the swap contract v2.
+        assert_eq!(negotiate_swap_v2_contract(&coin, None), None);
+
+        // The swap contract v2 isn't configured for the coin.
+        TestCoin::swap_v2_contract_address.mock_safe(|_| MockResult::Return(None));
+        assert_eq!(negotiate_swap_v2_contract(&coin, Some(&swap_v2_contract[..])), None);
     }
 
     #[test]
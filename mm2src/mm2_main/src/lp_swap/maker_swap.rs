@@ -2,11 +2,13 @@ use super::check_balance::{check_base_coin_balance_for_swap, check_my_coin_balan
                            CheckBalanceResult};
 use super::pubkey_banning::ban_pubkey_on_failed_swap;
 use super::swap_lock::{SwapLock, SwapLockOps};
+use super::swap_recovery::my_payment_refund_readiness;
 use super::trade_preimage::{TradePreimageRequest, TradePreimageRpcError, TradePreimageRpcResult};
 use super::{broadcast_my_swap_status, broadcast_swap_message_every, check_other_coin_balance_for_swap,
-            dex_fee_amount_from_taker_coin, get_locked_amount, recv_swap_msg, swap_topic, AtomicSwap, LockedAmount,
-            MySwapInfo, NegotiationDataMsg, NegotiationDataV2, NegotiationDataV3, RecoveredSwap, RecoveredSwapAction,
-            SavedSwap, SavedSwapIo, SavedTradeFee, SwapConfirmationsSettings, SwapError, SwapMsg, SwapsContext,
+            detect_secret_hash_algo, dex_fee_amount_from_taker_coin, get_locked_amount, negotiate_swap_v2_contract,
+            on_swap_finished, recv_swap_msg, swap_topic, AtomicSwap, LockedAmount, MySwapInfo, NegotiationDataMsg,
+            NegotiationDataV2, NegotiationDataV3, RecoveredSwap, RecoveredSwapAction, SavedSwap, SavedSwapIo,
+            SavedTradeFee, SwapConfirmationsSettings, SwapError, SwapMsg, SwapRecoveryReadiness, SwapsContext,
             TransactionIdentifier, WAIT_CONFIRM_INTERVAL};
 use crate::mm2::lp_dispatcher::{DispatcherContext, LpEvents};
 use crate::mm2::lp_network::subscribe_to_topic;
@@ -14,7 +16,6 @@ use crate::mm2::lp_ordermatch::{MakerOrderBuilder, OrderConfirmationsSettings};
 use crate::mm2::lp_price::fetch_swap_coins_price;
 use crate::mm2::lp_swap::{broadcast_p2p_tx_msg, tx_helper_topic};
 use crate::mm2::MM_VERSION;
-use bitcrypto::dhash160;
 use coins::{CanRefundHtlc, FeeApproxStage, FoundSwapTxSpend, MmCoinEnum, SearchForSwapTxSpendInput, TradeFee,
             TradePreimageValue, TransactionEnum, ValidatePaymentInput};
 use common::log::{debug, error, info, warn};
@@ -160,6 +161,9 @@ pub struct MakerSwapData {
     pub taker_coin_htlc_pubkey: Option<H264Json>,
     /// Temporary privkey used to sign P2P messages when applicable
     pub p2p_privkey: Option<SerializableSecp256k1Keypair>,
+    /// The payment locktime multiplier required by the swap policies, the `lock_duration` includes it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locktime_multiplier: Option<u64>,
 }
 
 pub struct MakerSwapMut {
@@ -192,6 +196,8 @@ pub struct MakerSwap {
     mutable: RwLock<MakerSwapMut>,
     conf_settings: SwapConfirmationsSettings,
     payment_locktime: u64,
+    /// The swap policies locktime multiplier the `payment_locktime` is multiplied by.
+    locktime_multiplier: u64,
     /// Temporary privkey used to sign P2P messages when applicable
     p2p_privkey: Option<KeyPair>,
     secret: H256,
@@ -214,7 +220,9 @@ impl MakerSwap {
             .secret_hash
             .as_ref()
             .map(|bytes| bytes.0.clone())
-            .unwrap_or_else(|| dhash160(self.secret.as_slice()).as_slice().to_vec())
+            .unwrap_or_else(|| {
+                detect_secret_hash_algo(&self.maker_coin, &self.taker_coin).hash_secret(self.secret.as_slice())
+            })
     }
 
     #[inline]
@@ -311,6 +319,7 @@ impl MakerSwap {
         maker_coin: MmCoinEnum,
         taker_coin: MmCoinEnum,
         payment_locktime: u64,
+        locktime_multiplier: u64,
         p2p_privkey: Option<KeyPair>,
         secret: H256,
     ) -> Self {
@@ -329,6 +338,7 @@ impl MakerSwap {
             taker_payment_confirmed: AtomicBool::new(false),
             conf_settings,
             payment_locktime,
+            locktime_multiplier,
             p2p_privkey,
             mutable: RwLock::new(MakerSwapMut {
                 data: MakerSwapData::default(),
@@ -357,8 +367,15 @@ impl MakerSwap {
             .taker_coin
             .swap_contract_address()
             .map_or_else(Vec::new, |addr| addr.0);
-
-        if r.data.maker_coin_htlc_pubkey != r.data.taker_coin_htlc_pubkey {
+        let maker_coin_swap_v2_contract = self.maker_coin.swap_v2_contract_address().map(|addr| addr.0);
+        let taker_coin_swap_v2_contract = self.taker_coin.swap_v2_contract_address().map(|addr| addr.0);
+        let supports_swap_v2 = maker_coin_swap_v2_contract.is_some() || taker_coin_swap_v2_contract.is_some();
+
+        // The swap contract v2 addresses and the locktime multiplier can be sent within `NegotiationDataV3` only.
+        if r.data.maker_coin_htlc_pubkey != r.data.taker_coin_htlc_pubkey
+            || supports_swap_v2
+            || self.locktime_multiplier != 1
+        {
             NegotiationDataMsg::V3(NegotiationDataV3 {
                 started_at: r.data.started_at,
                 payment_locktime: r.data.maker_payment_lock,
@@ -367,6 +384,10 @@ impl MakerSwap {
                 taker_coin_swap_contract,
                 maker_coin_htlc_pub: self.my_maker_coin_htlc_pub().into(),
                 taker_coin_htlc_pub: self.my_taker_coin_htlc_pub().into(),
+                conf_settings: Some(self.conf_settings),
+                maker_coin_swap_v2_contract,
+                taker_coin_swap_v2_contract,
+                locktime_multiplier: Some(self.locktime_multiplier),
             })
         } else {
             NegotiationDataMsg::V2(NegotiationDataV2 {
@@ -478,6 +499,7 @@ impl MakerSwap {
             maker_coin_htlc_pubkey: Some(maker_coin_htlc_key_pair.public_slice().into()),
             taker_coin_htlc_pubkey: Some(taker_coin_htlc_key_pair.public_slice().into()),
             p2p_privkey: self.p2p_privkey.map(SerializableSecp256k1Keypair::from),
+            locktime_multiplier: Some(self.locktime_multiplier),
         };
 
         Ok((Some(MakerSwapCommand::Negotiate), vec![MakerSwapEvent::Started(data)]))
@@ -519,13 +541,28 @@ impl MakerSwap {
             )]));
         }
 
+        // the taker adopts my locktime multiplier if it's not less than the one required by its swap policies
+        let taker_locktime_multiplier = taker_data.locktime_multiplier().unwrap_or(1);
+        if taker_locktime_multiplier != self.locktime_multiplier {
+            return Ok((Some(MakerSwapCommand::Finish), vec![MakerSwapEvent::NegotiateFailed(
+                ERRL!(
+                    "taker_data.locktime_multiplier {} not equal to mine {}",
+                    taker_locktime_multiplier,
+                    self.locktime_multiplier
+                )
+                .into(),
+            )]));
+        }
+
         let expected_lock_time = taker_data.started_at() + self.r().data.lock_duration;
         if taker_data.payment_locktime() != expected_lock_time {
             return Ok((Some(MakerSwapCommand::Finish), vec![MakerSwapEvent::NegotiateFailed(
                 ERRL!(
-                    "taker_data.payment_locktime {} not equal to expected {}",
+                    "taker_data.payment_locktime {} not equal to expected {}, taker conf settings {:?}, my conf settings {:?}",
                     taker_data.payment_locktime(),
-                    expected_lock_time
+                    expected_lock_time,
+                    taker_data.conf_settings(),
+                    self.conf_settings
                 )
                 .into(),
             )]));
@@ -555,6 +592,13 @@ impl MakerSwap {
             },
         };
 
+        let maker_coin_swap_contract_addr =
+            negotiate_swap_v2_contract(&self.maker_coin, taker_data.maker_coin_swap_v2_contract())
+                .or(maker_coin_swap_contract_addr);
+        let taker_coin_swap_contract_addr =
+            negotiate_swap_v2_contract(&self.taker_coin, taker_data.taker_coin_swap_v2_contract())
+                .or(taker_coin_swap_contract_addr);
+
         Ok((Some(MakerSwapCommand::WaitForTakerFee), vec![
             MakerSwapEvent::Negotiated(TakerNegotiationData {
                 taker_payment_locktime: taker_data.payment_locktime(),
@@ -1071,6 +1115,7 @@ impl MakerSwap {
             maker_coin,
             taker_coin,
             data.lock_duration,
+            data.locktime_multiplier.unwrap_or(1),
             data.p2p_privkey.map(SerializableSecp256k1Keypair::into_inner),
             data.secret.into(),
         );
@@ -1260,6 +1305,85 @@ impl MakerSwap {
             },
         }
     }
+
+    /// Checks whether [`MakerSwap::recover_funds`] can succeed right now without sending any transaction.
+    /// The maker payment spend is looked up by `search_for_swap_tx_spend_my`,
+    /// and the refund locktime is checked by `can_refund_htlc`.
+    pub async fn recovery_readiness(&self) -> Result<SwapRecoveryReadiness, String> {
+        if self.r().maker_payment_refund.is_some() {
+            return Ok(SwapRecoveryReadiness::NothingToRecover(
+                "Maker payment is refunded".to_owned(),
+            ));
+        }
+        if self.r().taker_payment_spend.is_some() && self.r().taker_payment_spend_confirmed {
+            return Ok(SwapRecoveryReadiness::NothingToRecover(
+                "Taker payment spend is confirmed".to_owned(),
+            ));
+        }
+        let maker_payment = match self.r().maker_payment.clone() {
+            Some(tx) => tx.tx_hex.0,
+            // The payment might be sent but not saved, it's looked up by `recover_funds`.
+            None => return Ok(SwapRecoveryReadiness::Ready),
+        };
+
+        let secret_hash = self.secret_hash();
+        let unique_data = self.unique_swap_data();
+        let maker_payment_lock = self.r().data.maker_payment_lock;
+        let other_maker_coin_htlc_pub = self.r().other_maker_coin_htlc_pub;
+        let maker_coin_start_block = self.r().data.maker_coin_start_block;
+        let maker_coin_swap_contract_address = self.r().data.maker_coin_swap_contract_address.clone();
+
+        let search_input = SearchForSwapTxSpendInput {
+            time_lock: maker_payment_lock as u32,
+            other_pub: other_maker_coin_htlc_pub.as_slice(),
+            secret_hash: secret_hash.as_slice(),
+            tx: &maker_payment,
+            search_from_block: maker_coin_start_block,
+            swap_contract_address: &maker_coin_swap_contract_address,
+            swap_unique_data: &unique_data,
+        };
+        match try_s!(self.maker_coin.search_for_swap_tx_spend_my(search_input).await) {
+            Some(FoundSwapTxSpend::Spent(_)) => (),
+            Some(FoundSwapTxSpend::Refunded(_)) => {
+                return Ok(SwapRecoveryReadiness::NothingToRecover(
+                    "Maker payment is refunded".to_owned(),
+                ))
+            },
+            None => {
+                return my_payment_refund_readiness(&self.maker_coin, maker_payment_lock, self.wait_refund_until())
+                    .await
+            },
+        }
+
+        // The maker payment is spent by the taker, so the taker payment has to be spent.
+        let taker_payment = match self.r().taker_payment.clone() {
+            Some(tx) => tx.tx_hex.0,
+            None => {
+                return Ok(SwapRecoveryReadiness::NothingToRecover(
+                    "No info about taker payment".to_owned(),
+                ))
+            },
+        };
+        let taker_payment_lock = self.taker_payment_lock.load(Ordering::Relaxed) as u32;
+        let other_taker_coin_htlc_pub = self.r().other_taker_coin_htlc_pub;
+        let taker_coin_start_block = self.r().data.taker_coin_start_block;
+        let taker_coin_swap_contract_address = self.r().data.taker_coin_swap_contract_address.clone();
+        let search_input = SearchForSwapTxSpendInput {
+            time_lock: taker_payment_lock,
+            other_pub: other_taker_coin_htlc_pub.as_slice(),
+            secret_hash: secret_hash.as_slice(),
+            tx: &taker_payment,
+            search_from_block: taker_coin_start_block,
+            swap_contract_address: &taker_coin_swap_contract_address,
+            swap_unique_data: &unique_data,
+        };
+        match try_s!(self.taker_coin.search_for_swap_tx_spend_other(search_input).await) {
+            Some(_) => Ok(SwapRecoveryReadiness::NothingToRecover(
+                "Taker payment is spent or refunded".to_owned(),
+            )),
+            None => Ok(SwapRecoveryReadiness::Ready),
+        }
+    }
 }
 
 impl AtomicSwap for MakerSwap {
@@ -1447,7 +1571,7 @@ impl MakerSavedEvent {
     }
 }
 
-#[derive(Clone)]
+#[derive(Clone, Serialize)]
 pub struct MakerSwapStatusChanged {
     pub uuid: Uuid,
     pub taker_coin: String,
@@ -1474,7 +1598,7 @@ impl MakerSwapStatusChanged {
     }
 }
 
-#[derive(Debug, Default, PartialEq, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct MakerSavedSwap {
     pub uuid: Uuid,
     pub my_order_uuid: Option<Uuid>,
@@ -1523,6 +1647,7 @@ impl MakerSavedSwap {
                 maker_coin_htlc_pubkey: None,
                 taker_coin_htlc_pubkey: None,
                 p2p_privkey: None,
+                locktime_multiplier: None,
             }),
         });
         events.push(MakerSavedEvent {
@@ -1797,6 +1922,7 @@ pub async fn run_maker_swap(swap: RunMakerSwapInput, ctx: MmArc) {
                                 error!("!broadcast_my_swap_status({}): {}", uuid, e);
                             }
                         }
+                        on_swap_finished(&ctx, uuid);
                         break;
                     },
                 }
@@ -2326,6 +2452,110 @@ mod maker_swap_tests {
         assert!(unsafe { SEND_MAKER_SPENDS_TAKER_PAYMENT_CALLED });
     }
 
+    /// The swap ended with `MakerPaymentRefundFailed` after the taker payment was received.
+    const MAKER_PAYMENT_REFUND_FAILED_SWAP: &str = r#"{"error_events":["StartFailed","NegotiateFailed","TakerFeeValidateFailed","MakerPaymentTransactionFailed","MakerPaymentDataSendFailed","TakerPaymentValidateFailed","TakerPaymentSpendFailed","TakerPaymentSpendConfirmFailed","MakerPaymentRefunded","MakerPaymentRefundFailed"],"events":[{"event":{"data":{"lock_duration":7800,"maker_amount":"0.58610590","maker_coin":"KMD","maker_coin_start_block":1450923,"maker_payment_confirmations":1,"maker_payment_lock":1563636475,"my_persistent_pub":"031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8","secret":"0000000000000000000000000000000000000000000000000000000000000000","started_at":1563620875,"taker":"14a96292bfcd7762ece8eb08ead915da927c2619277363853572f30880d5155e","taker_amount":"0.0077700000552410000000000","taker_coin":"LTC","taker_coin_start_block":1670837,"taker_payment_confirmations":1,"uuid":"9db641f5-4300-4527-9fa6-f1c391d42c35"},"type":"Started"},"timestamp":1563620875062},{"event":{"data":{"taker_payment_locktime":1563628675,"taker_pubkey":"02713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91"},"type":"Negotiated"},"timestamp":1563620915497},{"event":{"data":{"tx_hash":"6740136eaaa615d9d231969e3a9599d0fc59e53989237a8d31cd6fc86c160013","tx_hex":"0100000001a2586ea8294cedc55741bef625ba72c646399903391a7f6c604a58c6263135f2000000006b4830450221009c78c8ba4a7accab6b09f9a95da5bc59c81f4fc1e60b288ec3c5462b4d02ef01022056b63be1629cf17751d3cc5ffec51bcb1d7f9396e9ce9ca254d0f34104f7263a012102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ffffffff0210270000000000001976a914ca1e04745e8ca0c60d8c5881531d51bec470743f88ac78aa1900000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac5bf6325d"},"type":"TakerFeeValidated"},"timestamp":1563620976060},{"event":{"data":{"tx_hash":"d0f6e664cea9d89fe7b5cf8005fdca070d1ab1d05a482aaef95c08cdaecddf0a","tx_hex":"0400008085202f89019f1cbda354342cdf982046b331bbd3791f53b692efc6e4becc36be495b2977d9000000006b483045022100fa9d4557394141f6a8b9bfb8cd594a521fd8bcd1965dbf8bc4e04abc849ac66e0220589f521814c10a7561abfd5e432f7a2ee60d4875fe4604618af3207dae531ac00121031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ffffffff029e537e030000000017a9145534898009f1467191065f6890b96914b39a1c018791857702000000001976a914c3f710deb7320b0efa6edb14e3ebeeb9155fa90d88ac72ee325d000000000000000000000000000000"},"type":"MakerPaymentSent"},"timestamp":1563620976189},{"event":{"data":{"tx_hash":"1e883eb2f3991e84ba27f53651f89b7dda708678a5b9813d043577f222b9ca30","tx_hex":"01000000011300166cc86fcd318d7a238939e559fcd099953a9e9631d2d915a6aa6e134067010000006a47304402206781d5f2db2ff13d2ec7e266f774ea5630cc2dba4019e18e9716131b8b026051022006ebb33857b6d180f13aa6be2fc532f9734abde9d00ae14757e7d7ba3741c08c012102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ffffffff0228db0b000000000017a91483818667161bf94adda3964a81a231cbf6f5338187b0480c00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac7cf7325d"},"type":"TakerPaymentReceived"},"timestamp":1563621268320},{"event":{"type":"TakerPaymentWaitConfirmStarted"},"timestamp":1563621268321},{"event":{"type":"TakerPaymentValidatedAndConfirmed"},"timestamp":1563621778471},{"event":{"data":{"error":"lp_swap:2025] utxo:938] rpc_clients:719] JsonRpcError { request: JsonRpcRequest { jsonrpc: \"2.0\", id: \"9\", method: \"blockchain.transaction.broadcast\", params: [String(\"010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d\")] }, error: Response(Object({\"code\": Number(1), \"message\": String(\"the transaction was rejected by network rules.\\n\\nMissing inputs\\n[010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d]\")})) }"},"type":"TakerPaymentSpendFailed"},"timestamp":1563638060583},{"event":{"data":{"error":"lp_swap:2025] utxo:938] rpc_clients:719] JsonRpcError { request: JsonRpcRequest { jsonrpc: \"2.0\", id: \"9\", method: \"blockchain.transaction.broadcast\", params: [String(\"010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d\")] }, error: Response(Object({\"code\": Number(1), \"message\": String(\"the transaction was rejected by network rules.\\n\\nMissing inputs\\n[010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d]\")})) }"},"type":"MakerPaymentRefundFailed"},"timestamp":1563638060583},{"event":{"type":"Finished"},"timestamp":1563621778483}],"success_events":["Started","Negotiated","TakerFeeValidated","MakerPaymentSent","TakerPaymentReceived","TakerPaymentWaitConfirmStarted","TakerPaymentValidatedAndConfirmed","TakerPaymentSpent","TakerPaymentSpendConfirmStarted","TakerPaymentSpendConfirmed","Finished"],"uuid":"9db641f5-4300-4527-9fa6-f1c391d42c35"}"#;
+
+    fn maker_swap_for_recovery_test() -> MakerSwap {
+        let maker_saved_swap: MakerSavedSwap = json::from_str(MAKER_PAYMENT_REFUND_FAILED_SWAP).unwrap();
+        let key_pair =
+            key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid")
+                .unwrap();
+        let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+
+        TestCoin::ticker.mock_safe(|_| MockResult::Return("ticker"));
+        TestCoin::swap_contract_address.mock_safe(|_| MockResult::Return(None));
+        let maker_coin = MmCoinEnum::Test(TestCoin::default());
+        let taker_coin = MmCoinEnum::Test(TestCoin::default());
+        let (maker_swap, _) = MakerSwap::load_from_saved(ctx, maker_coin, taker_coin, maker_saved_swap).unwrap();
+        maker_swap
+    }
+
+    #[test]
+    fn test_recovery_readiness_maker_payment_not_spent() {
+        let maker_swap = maker_swap_for_recovery_test();
+        TestCoin::search_for_swap_tx_spend_my
+            .mock_safe(|_, _| MockResult::Return(Box::pin(futures::future::ready(Ok(None)))));
+
+        // The locktime has expired long ago, so the default `can_refund_htlc` allows the refund.
+        assert_eq!(
+            block_on(maker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::Ready)
+        );
+
+        maker_swap.w().data.maker_payment_lock = now_ms() / 1000 - 3690;
+        match block_on(maker_swap.recovery_readiness()) {
+            Ok(SwapRecoveryReadiness::HaveToWait(seconds)) => assert!(seconds <= 10),
+            other => panic!("Expected HaveToWait, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_recovery_readiness_maker_payment_spent() {
+        let maker_swap = maker_swap_for_recovery_test();
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _| {
+            MockResult::Return(Box::pin(futures::future::ready(Ok(Some(FoundSwapTxSpend::Spent(
+                eth_tx_for_test().into(),
+            ))))))
+        });
+        TestCoin::search_for_swap_tx_spend_other
+            .mock_safe(|_, _| MockResult::Return(Box::pin(futures::future::ready(Ok(None)))));
+        assert_eq!(
+            block_on(maker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::Ready)
+        );
+
+        // The taker payment is spent already, e.g. by the previous recovery.
+        TestCoin::search_for_swap_tx_spend_other.mock_safe(|_, _| {
+            MockResult::Return(Box::pin(futures::future::ready(Ok(Some(FoundSwapTxSpend::Spent(
+                eth_tx_for_test().into(),
+            ))))))
+        });
+        assert!(matches!(
+            block_on(maker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::NothingToRecover(_))
+        ));
+    }
+
+    #[test]
+    fn test_recovery_readiness_maker_payment_refunded() {
+        let maker_swap = maker_swap_for_recovery_test();
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _| {
+            MockResult::Return(Box::pin(futures::future::ready(Ok(Some(FoundSwapTxSpend::Refunded(
+                eth_tx_for_test().into(),
+            ))))))
+        });
+        assert!(matches!(
+            block_on(maker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::NothingToRecover(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_maker_recovery_event() {
+        let maker_saved_swap: MakerSavedSwap = json::from_str(MAKER_PAYMENT_REFUND_FAILED_SWAP).unwrap();
+        let events_len = maker_saved_swap.events.len();
+        let mut saved_swap = SavedSwap::Maker(maker_saved_swap);
+        assert!(saved_swap.is_recoverable());
+
+        saved_swap.add_recovery_event(&RecoveredSwap {
+            action: RecoveredSwapAction::RefundedMyPayment,
+            coin: "ticker".into(),
+            transaction: eth_tx_for_test().into(),
+        });
+        assert!(saved_swap.is_finished());
+        assert!(!saved_swap.is_recoverable());
+        match saved_swap {
+            SavedSwap::Maker(swap) => {
+                assert_eq!(swap.events.len(), events_len + 1);
+                assert!(matches!(
+                    swap.events[events_len - 1].event,
+                    MakerSwapEvent::MakerPaymentRefunded(_)
+                ));
+            },
+            SavedSwap::Taker(_) => panic!("Expected the maker swap"),
+        }
+    }
+
     #[test]
     fn test_recover_funds_should_not_refund_on_the_successful_swap() {
         let maker_saved_json = r#"{"type":"Maker","uuid":"12456076-58dd-4772-9d88-167d5fa103d2","my_order_uuid":"5ae22bf5-09cf-4828-87a7-c3aa7339ba10","events":[{"timestamp":1631695364907,"event":{"type":"Started","data":{"taker_coin":"KMD","maker_coin":"TKL","taker":"2b20b92e19e9e11b07f8309cebb1fcd1cce1606be8ab0de2c1b91f979c937996","secret":"0000000000000000000000000000000000000000000000000000000000000000","secret_hash":"65a10bd6dbdf6ebf7ec1f3bfb7451cde0582f9cb","my_persistent_pub":"03789c206e830f9e0083571f79e80eb58601d37bde8abb0c380d81127613060b74","lock_duration":31200,"maker_amount":"500","taker_amount":"140.7","maker_payment_confirmations":1,"maker_payment_requires_nota":false,"taker_payment_confirmations":2,"taker_payment_requires_nota":true,"maker_payment_lock":1631757764,"uuid":"12456076-58dd-4772-9d88-167d5fa103d2","started_at":1631695364,"maker_coin_start_block":61066,"taker_coin_start_block":2569118,"maker_payment_trade_fee":{"coin":"TKL","amount":"0.00001","paid_from_trading_vol":false},"taker_payment_spend_trade_fee":{"coin":"KMD","amount":"0.00001","paid_from_trading_vol":true}}}},{"timestamp":1631695366908,"event":{"type":"Negotiated","data":{"taker_payment_locktime":1631726564,"taker_pubkey":"032b20b92e19e9e11b07f8309cebb1fcd1cce1606be8ab0de2c1b91f979c937996","maker_coin_swap_contract_addr":null,"taker_coin_swap_contract_addr":null}}},{"timestamp":1631695367917,"event":{"type":"TakerFeeValidated","data":{"tx_hex":"0400008085202f8901562fdec6bbdac4c5c3212394e1fd439d3647ff04bdd79d51b9bbf697c9a925e7000000006a473044022074c71fcdc12654e3aa01c780b10d6c84b1d6ba28f0db476010002a1ed00e75cf022018e115923b1c1b5e872893fd6a1f270c0e8e3e84a869181c349aa78553e1423b0121032b20b92e19e9e11b07f8309cebb1fcd1cce1606be8ab0de2c1b91f979c937996ffffffff0251adf800000000001976a914ca1e04745e8ca0c60d8c5881531d51bec470743f88ac72731e4c080000001976a914dc1bea5367613f189da622e9bc5bdb2d61667e5b88ac08aa4161000000000000000000000000000000","tx_hash":"f315170aba20ff4d432b8a2d0a8fa0211444c8d27b56fc0d4fc2058e9f3c6e08"}}},{"timestamp":1631695368024,"event":{"type":"MakerPaymentSent","data":{"tx_hex":"0400008085202f8901bc488c4e0f9a3fe9d7f5dbcc17f61e7711a75c7ed277843988f3be4d236b9a02020000006a473044022027ac57a4a34b0d8561afc1ad63f9e1fb271d58577a80f26ba519017d65d882f802200b5617f32427b86b423de6740cd134fdb8b86c511943e778c65781573224cf4a012103789c206e830f9e0083571f79e80eb58601d37bde8abb0c380d81127613060b74ffffffff0300743ba40b00000017a914022be92579878d04c80d128cdfdcba4ed29a9f9a870000000000000000166a1465a10bd6dbdf6ebf7ec1f3bfb7451cde0582f9cb6494f93503c801001976a914bde146a76acf122caf5e460d01ddaf3be714247e88ac07b24161000000000000000000000000000000","tx_hash":"8693723462ef5ee6c3014230fd4a4aefe6bcd0eaeb727e1e5b33fe1105e9f8ad"}}},{"timestamp":1631696319310,"event":{"type":"TakerPaymentReceived","data":{"tx_hex":"0400008085202f8901086e3c9f8e05c24f0dfc567bd2c8441421a08f0a2d8a2b434dff20ba0a1715f3010000006a47304402207190691940b4834394c2a9e08a32b775f1c62a47ab76737c96c08e2937173988022040229094d51acb3d948413c349e36795b888a9b425b29ed7a96ed8eb97407d050121032b20b92e19e9e11b07f8309cebb1fcd1cce1606be8ab0de2c1b91f979c937996ffffffff038029a3460300000017a9146d0db00d111fcd0b83505cb805a3255cbaa8c747870000000000000000166a1465a10bd6dbdf6ebf7ec1f3bfb7451cde0582f9cb0a467b05050000001976a914dc1bea5367613f189da622e9bc5bdb2d61667e5b88acbfad4161000000000000000000000000000000","tx_hash":"a9b97c4c12c8eb637a7016459de644eae9e307efd2d051601d7d9f615fd62461"}}},{"timestamp":1631696319310,"event":{"type":"TakerPaymentWaitConfirmStarted"}},{"timestamp":1631697459816,"event":{"type":"TakerPaymentValidatedAndConfirmed"}},{"timestamp":1631697459821,"event":{"type":"TakerPaymentSpent","data":{"tx_hex":"0400008085202f89016124d65f619f7d1d6051d0d2ef07e3e9ea44e69d4516707a63ebc8124c7cb9a900000000d84830450221008c50c144382346247d7052a32e12f4d839fa22c12064b199d589cc62ead00c99022017e88f543e181fd92ebf32e1313ca6fb12f93226fd294c808b0904601102424f012068e659c506d57d94369ca520158d641ea997b0db39fdafb1e59b07867ad4be9d004c6b6304e42b4261b17521032b20b92e19e9e11b07f8309cebb1fcd1cce1606be8ab0de2c1b91f979c937996ac6782012088a91465a10bd6dbdf6ebf7ec1f3bfb7451cde0582f9cb882103789c206e830f9e0083571f79e80eb58601d37bde8abb0c380d81127613060b74ac68ffffffff019825a346030000001976a914bde146a76acf122caf5e460d01ddaf3be714247e88ace42b4261000000000000000000000000000000","tx_hash":"8a6d65518d3a01f6f659f11e0667373052ebfc2e600f80c6592dec556bee4a39"}}},{"timestamp":1631697459822,"event":{"type":"TakerPaymentSpendConfirmStarted"}},{"timestamp":1631697489840,"event":{"type":"TakerPaymentSpendConfirmed"}},{"timestamp":1631697489841,"event":{"type":"Finished"}}],"maker_amount":"500","maker_coin":"TKL","taker_amount":"140.7","taker_coin":"KMD","gui":"TOKEL-IDO","mm_version":"41170748d","success_events":["Started","Negotiated","TakerFeeValidated","MakerPaymentSent","TakerPaymentReceived","TakerPaymentWaitConfirmStarted","TakerPaymentValidatedAndConfirmed","TakerPaymentSpent","TakerPaymentSpendConfirmStarted","TakerPaymentSpendConfirmed","Finished"],"error_events":["StartFailed","NegotiateFailed","TakerFeeValidateFailed","MakerPaymentTransactionFailed","MakerPaymentDataSendFailed","MakerPaymentWaitConfirmFailed","TakerPaymentValidateFailed","TakerPaymentWaitConfirmFailed","TakerPaymentSpendFailed","TakerPaymentSpendConfirmFailed","MakerPaymentWaitRefundStarted","MakerPaymentRefunded","MakerPaymentRefundFailed"]}"#;
@@ -2465,4 +2695,24 @@ mod maker_swap_tests {
         let event = MakerSwapEvent::TakerPaymentValidateFailed("err".into());
         assert!(event.should_ban_taker());
     }
+
+    #[test]
+    fn test_saved_swap_event_types() {
+        let maker_saved_json = r#"{"error_events":["StartFailed","NegotiateFailed","TakerFeeValidateFailed","MakerPaymentTransactionFailed","MakerPaymentDataSendFailed","TakerPaymentValidateFailed","TakerPaymentSpendFailed","TakerPaymentSpendConfirmFailed","MakerPaymentRefunded","MakerPaymentRefundFailed"],"events":[{"event":{"data":{"lock_duration":7800,"maker_amount":"3.54932734","maker_coin":"KMD","maker_coin_start_block":1452970,"maker_payment_confirmations":1,"maker_payment_lock":1563759539,"my_persistent_pub":"031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8","secret":"0000000000000000000000000000000000000000000000000000000000000000","started_at":1563743939,"taker":"101ace6b08605b9424b0582b5cce044b70a3c8d8d10cb2965e039b0967ae92b9","taker_amount":"0.02004833998671660000000000","taker_coin":"ETH","taker_coin_start_block":8196380,"taker_payment_confirmations":1,"uuid":"3447b727-fe93-4357-8e5a-8cf2699b7e86"},"type":"Started"},"timestamp":1563743939211},{"event":{"data":{"taker_payment_locktime":1563751737,"taker_pubkey":"03101ace6b08605b9424b0582b5cce044b70a3c8d8d10cb2965e039b0967ae92b9"},"type":"Negotiated"},"timestamp":1563743979835},{"event":{"data":{"tx_hash":"a59203eb2328827de00bed699a29389792906e4f39fdea145eb40dc6b3821bd6","tx_hex":"f8690284ee6b280082520894d8997941dd1346e9231118d5685d866294f59e5b865af3107a4000801ca0743d2b7c9fad65805d882179062012261be328d7628ae12ee08eff8d7657d993a07eecbd051f49d35279416778faa4664962726d516ce65e18755c9b9406a9c2fd"},"type":"TakerFeeValidated"},"timestamp":1563744052878},{"event":{"data":{"error":"lp_swap:1888] eth:654] RPC error: Error { code: ServerError(-32010), message: \"Transaction with the same hash was already imported.\", data: None }"},"type":"MakerPaymentTransactionFailed"},"timestamp":1563744118577},{"event":{"type":"Finished"},"timestamp":1563763243350}],"success_events":["Started","Negotiated","TakerFeeValidated","MakerPaymentSent","TakerPaymentReceived","TakerPaymentWaitConfirmStarted","TakerPaymentValidatedAndConfirmed","TakerPaymentSpent","TakerPaymentSpendConfirmStarted","TakerPaymentSpendConfirmed","TakerPaymentSpendConfirmStarted","TakerPaymentSpendConfirmed","Finished"],"uuid":"3447b727-fe93-4357-8e5a-8cf2699b7e86"}"#;
+        let maker_saved_swap: MakerSavedSwap = json::from_str(maker_saved_json).unwrap();
+        let saved_swap = SavedSwap::Maker(maker_saved_swap);
+
+        let expected = vec![
+            ("Started".to_owned(), 1563743939211),
+            ("Negotiated".to_owned(), 1563743979835),
+            ("TakerFeeValidated".to_owned(), 1563744052878),
+            ("MakerPaymentTransactionFailed".to_owned(), 1563744118577),
+            ("Finished".to_owned(), 1563763243350),
+        ];
+        assert_eq!(saved_swap.event_types(), expected);
+        assert_eq!(
+            saved_swap.first_error_event(),
+            Some("MakerPaymentTransactionFailed".to_owned())
+        );
+    }
 }
@@ -13,7 +13,7 @@ use rpc::v1::types::{Bytes as BytesJson, H256 as H256Json};
 
 pub type RecreateSwapResult<T> = Result<T, MmError<RecreateSwapError>>;
 
-#[derive(Debug, Display, Serialize, SerializeErrorType)]
+#[derive(Debug, Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum RecreateSwapError {
     #[display(fmt = "Swap hasn't been started. Swap not recoverable")]
@@ -53,12 +53,14 @@ pub enum InputSwap {
     TakerSavedSwap(TakerSavedSwap),
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct RecreateSwapRequest {
+    /// Either a `SavedSwap` tagged by `type` or an untagged maker/taker swap.
+    #[rpc_schema(any)]
     swap: InputSwap,
 }
 
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 pub struct RecreateSwapResponse {
     swap: SavedSwap,
 }
@@ -149,6 +151,7 @@ fn recreate_maker_swap(ctx: MmArc, taker_swap: TakerSavedSwap) -> RecreateSwapRe
         maker_coin_htlc_pubkey: negotiated_event.maker_coin_htlc_pubkey,
         taker_coin_htlc_pubkey: negotiated_event.taker_coin_htlc_pubkey,
         p2p_privkey: None,
+        locktime_multiplier: started_event.locktime_multiplier,
     });
     maker_swap.events.push(MakerSavedEvent {
         timestamp: started_event_timestamp,
@@ -341,6 +344,7 @@ async fn recreate_taker_swap(ctx: MmArc, maker_swap: MakerSavedSwap) -> Recreate
         maker_coin_htlc_pubkey: negotiated_event.maker_coin_htlc_pubkey,
         taker_coin_htlc_pubkey: negotiated_event.taker_coin_htlc_pubkey,
         p2p_privkey: None,
+        locktime_multiplier: started_event.locktime_multiplier,
     });
     taker_swap.events.push(TakerSavedEvent {
         timestamp: started_event_timestamp,
@@ -359,6 +363,7 @@ async fn recreate_taker_swap(ctx: MmArc, maker_swap: MakerSavedSwap) -> Recreate
         taker_coin_swap_contract_addr: negotiated_event.taker_coin_swap_contract_addr,
         maker_coin_htlc_pubkey: started_event.maker_coin_htlc_pubkey,
         taker_coin_htlc_pubkey: started_event.taker_coin_htlc_pubkey,
+        locktime_multiplier: started_event.locktime_multiplier,
     });
     taker_swap.events.push(TakerSavedEvent {
         timestamp: negotiated_timestamp,
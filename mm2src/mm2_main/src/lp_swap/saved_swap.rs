@@ -1,12 +1,16 @@
-use crate::mm2::lp_swap::maker_swap::{MakerSavedSwap, MakerSwap, MakerSwapEvent};
-use crate::mm2::lp_swap::taker_swap::{TakerSavedSwap, TakerSwap, TakerSwapEvent};
-use crate::mm2::lp_swap::{MySwapInfo, RecoveredSwap};
+use crate::mm2::lp_swap::maker_swap::{MakerSavedEvent, MakerSavedSwap, MakerSwap, MakerSwapEvent, MAKER_ERROR_EVENTS};
+use crate::mm2::lp_swap::taker_swap::{TakerSavedEvent, TakerSavedSwap, TakerSwap, TakerSwapEvent, TAKER_ERROR_EVENTS};
+use crate::mm2::lp_swap::{MySwapInfo, RecoveredSwap, RecoveredSwapAction, SwapRecoveryReadiness, TransactionIdentifier};
 use async_trait::async_trait;
-use coins::lp_coinfind;
+use coins::{lp_coinfind, MmCoinEnum};
+use common::now_ms;
 use derive_more::Display;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
 use rpc::v1::types::H256 as H256Json;
+use rpc_schema::{RpcSchema, SchemaGenerator};
+use serde::Serialize;
+use serde_json::{self as json, Value as Json};
 use uuid::Uuid;
 
 pub type SavedSwapResult<T> = Result<T, MmError<SavedSwapError>>;
@@ -26,13 +30,29 @@ pub enum SavedSwapError {
     InternalError(String),
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(tag = "type")]
 pub enum SavedSwap {
     Maker(MakerSavedSwap),
     Taker(TakerSavedSwap),
 }
 
+/// The events of the saved swaps aren't described in detail,
+/// so the schema contains only the discriminating `type` field.
+impl RpcSchema for SavedSwap {
+    fn schema_name() -> Option<String> { Some("SavedSwap".to_owned()) }
+
+    fn json_schema(_gen: &mut SchemaGenerator) -> Json {
+        json!({
+            "type": "object",
+            "properties": {
+                "type": { "type": "string", "enum": ["Maker", "Taker"] },
+            },
+            "required": ["type"],
+        })
+    }
+}
+
 impl From<MakerSavedSwap> for SavedSwap {
     fn from(maker: MakerSavedSwap) -> Self { SavedSwap::Maker(maker) }
 }
@@ -84,20 +104,61 @@ impl SavedSwap {
         }
     }
 
-    pub async fn recover_funds(self, ctx: MmArc) -> Result<RecoveredSwap, String> {
+    /// Returns the types of the swap events (e.g. `MakerPaymentSent`) with their timestamps in milliseconds.
+    pub fn event_types(&self) -> Vec<(String, u64)> {
+        fn event_type<E: Serialize>(event: &E) -> String {
+            match json::to_value(event) {
+                Ok(json) => json["type"].as_str().unwrap_or_default().to_owned(),
+                Err(_) => String::new(),
+            }
+        }
+
+        match self {
+            SavedSwap::Maker(swap) => swap
+                .events
+                .iter()
+                .map(|e| (event_type(&e.event), e.timestamp))
+                .collect(),
+            SavedSwap::Taker(swap) => swap
+                .events
+                .iter()
+                .map(|e| (event_type(&e.event), e.timestamp))
+                .collect(),
+        }
+    }
+
+    /// Returns the type of the first error event of the swap if there is any.
+    pub fn first_error_event(&self) -> Option<String> {
+        let error_events: &[&str] = match self {
+            SavedSwap::Maker(_) => &MAKER_ERROR_EVENTS,
+            SavedSwap::Taker(_) => &TAKER_ERROR_EVENTS,
+        };
+        self.event_types()
+            .into_iter()
+            .map(|(event_type, _)| event_type)
+            .find(|event_type| error_events.contains(&event_type.as_str()))
+    }
+
+    /// Finds the maker and taker coins of the swap, they must be activated.
+    async fn find_swap_coins(&self, ctx: &MmArc) -> Result<(MmCoinEnum, MmCoinEnum), String> {
         let maker_ticker = try_s!(self.maker_coin_ticker());
-        let maker_coin = match lp_coinfind(&ctx, &maker_ticker).await {
+        let maker_coin = match lp_coinfind(ctx, &maker_ticker).await {
             Ok(Some(c)) => c,
             Ok(None) => return ERR!("Coin {} is not activated", maker_ticker),
             Err(e) => return ERR!("Error {} on {} coin find attempt", e, maker_ticker),
         };
 
         let taker_ticker = try_s!(self.taker_coin_ticker());
-        let taker_coin = match lp_coinfind(&ctx, &taker_ticker).await {
+        let taker_coin = match lp_coinfind(ctx, &taker_ticker).await {
             Ok(Some(c)) => c,
             Ok(None) => return ERR!("Coin {} is not activated", taker_ticker),
             Err(e) => return ERR!("Error {} on {} coin find attempt", e, taker_ticker),
         };
+        Ok((maker_coin, taker_coin))
+    }
+
+    pub async fn recover_funds(self, ctx: MmArc) -> Result<RecoveredSwap, String> {
+        let (maker_coin, taker_coin) = try_s!(self.find_swap_coins(&ctx).await);
         match self {
             SavedSwap::Maker(saved) => {
                 let (maker_swap, _) = try_s!(MakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
@@ -110,6 +171,57 @@ impl SavedSwap {
         }
     }
 
+    /// Checks whether the funds of the swap can be recovered right now without sending any transaction.
+    pub async fn recovery_readiness(self, ctx: MmArc) -> Result<SwapRecoveryReadiness, String> {
+        let (maker_coin, taker_coin) = try_s!(self.find_swap_coins(&ctx).await);
+        match self {
+            SavedSwap::Maker(saved) => {
+                let (maker_swap, _) = try_s!(MakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
+                maker_swap.recovery_readiness().await
+            },
+            SavedSwap::Taker(saved) => {
+                let (taker_swap, _) = try_s!(TakerSwap::load_from_saved(ctx, maker_coin, taker_coin, saved));
+                taker_swap.recovery_readiness().await
+            },
+        }
+    }
+
+    /// Adds the event of the recovered funds before the `Finished` event,
+    /// so the saved swap reflects the recovery and its funds aren't recovered again.
+    pub fn add_recovery_event(&mut self, recovered: &RecoveredSwap) {
+        let tx_ident = TransactionIdentifier {
+            tx_hex: recovered.transaction.tx_hex().into(),
+            tx_hash: recovered.transaction.tx_hash(),
+        };
+        let timestamp = now_ms();
+        match self {
+            SavedSwap::Maker(swap) => {
+                let event = match recovered.action {
+                    RecoveredSwapAction::RefundedMyPayment => MakerSwapEvent::MakerPaymentRefunded(tx_ident),
+                    RecoveredSwapAction::SpentOtherPayment => MakerSwapEvent::TakerPaymentSpent(tx_ident),
+                };
+                let index = if swap.is_finished() {
+                    swap.events.len() - 1
+                } else {
+                    swap.events.len()
+                };
+                swap.events.insert(index, MakerSavedEvent { timestamp, event });
+            },
+            SavedSwap::Taker(swap) => {
+                let event = match recovered.action {
+                    RecoveredSwapAction::RefundedMyPayment => TakerSwapEvent::TakerPaymentRefunded(tx_ident),
+                    RecoveredSwapAction::SpentOtherPayment => TakerSwapEvent::MakerPaymentSpent(tx_ident),
+                };
+                let index = if swap.is_finished() {
+                    swap.events.len() - 1
+                } else {
+                    swap.events.len()
+                };
+                swap.events.insert(index, TakerSavedEvent { timestamp, event });
+            },
+        }
+    }
+
     pub fn is_recoverable(&self) -> bool {
         match self {
             SavedSwap::Maker(saved) => saved.is_recoverable(),
@@ -0,0 +1,450 @@
+//! The dry-run simulation of the atomic swap.
+//!
+//! The node plays both sides of the swap using its own keys as the simulated counterparty.
+//! For every side the simulation:
+//! * negotiates the swap contract address;
+//! * generates and signs the swap payment without broadcasting it;
+//! * validates the payment as the counterparty would, except that the transaction isn't requested from the RPC;
+//! * checks that the payment can't be refunded before its locktime;
+//! * estimates the fees to send the payment and to spend the counterparty's one.
+//!
+//! The dex fee, the payment spend and the refund transactions are not built,
+//! only the dex fee amount and the fees of these transactions are estimated.
+
+use super::trade_preimage::TradeFeeResponse;
+use super::{apply_swap_policies, check_secret_hash_algo_compatibility, detect_secret_hash_algo,
+            dex_fee_amount_from_taker_coin, fetch_swap_policy_prices, lp_atomic_locktime, AtomicLocktimeVersion,
+            MakerSwap, SwapConfirmationsSettings};
+use coins::{is_wallet_only_ticker, lp_coinfind_or_err, CanRefundHtlc, CoinFindError, FeeApproxStage, MmCoinEnum,
+            TradeFee, TradePreimageValue, UnbroadcastSwapPaymentInput, ValidatePaymentInput};
+use common::{now_ms, HttpStatusCode};
+use derive_more::Display;
+use futures::compat::Future01CompatExt;
+use http::StatusCode;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use mm2_number::{BigDecimal, MmNumber};
+use rpc::v1::types::Bytes as BytesJson;
+use uuid::Uuid;
+
+pub type SimulateSwapRpcResult<T> = Result<T, MmError<SimulateSwapRpcError>>;
+
+#[derive(Deserialize, RpcSchema)]
+pub struct SimulateSwapRequest {
+    /// The coin the maker sends.
+    pub base: String,
+    /// The coin the taker sends.
+    pub rel: String,
+    /// The price in `rel` per one unit of the `base` coin.
+    pub price: MmNumber,
+    /// The amount of the `base` coin to be swapped.
+    pub volume: MmNumber,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct SimulateSwapResponse {
+    /// Whether every step of the simulation succeeded on both sides.
+    success: bool,
+    /// The swap payment lock duration in seconds.
+    lock_duration: u64,
+    maker: SimulatedSwapSide,
+    taker: SimulatedSwapSide,
+}
+
+/// The result of the simulation of one swap side.
+#[derive(RpcSchema, Serialize)]
+pub struct SimulatedSwapSide {
+    coin: String,
+    payment_amount: BigDecimal,
+    payment_locktime: u64,
+    required_confirmations: u64,
+    requires_notarization: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    swap_contract_address: Option<BytesJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payment_tx_hex: Option<BytesJson>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    payment_tx_hash: Option<BytesJson>,
+    fees: Vec<TradeFeeResponse>,
+    failures: Vec<SimulatedSwapFailure>,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct SimulatedSwapFailure {
+    stage: SimulatedSwapStage,
+    error: String,
+}
+
+#[derive(Clone, Copy, RpcSchema, Serialize)]
+pub enum SimulatedSwapStage {
+    NegotiateSwapContract,
+    CalcTradeFee,
+    GeneratePayment,
+    ValidatePayment,
+    CheckRefundLocktime,
+}
+
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum SimulateSwapRpcError {
+    #[display(fmt = "No such coin {}", coin)]
+    NoSuchCoin { coin: String },
+    #[display(fmt = "Coin {} is wallet only", coin)]
+    CoinIsWalletOnly { coin: String },
+    #[display(fmt = "Rel coin can not be same as base")]
+    BaseEqualRel,
+    #[display(
+        fmt = "The volume {} of the {} coin less than minimum transaction amount {}",
+        volume,
+        coin,
+        threshold
+    )]
+    VolumeTooLow {
+        coin: String,
+        volume: BigDecimal,
+        threshold: BigDecimal,
+    },
+    #[display(fmt = "Invalid swap policy: {}", _0)]
+    InvalidSwapPolicy(String),
+    #[display(fmt = "{}", _0)]
+    IncompatibleCoins(String),
+}
+
+impl HttpStatusCode for SimulateSwapRpcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SimulateSwapRpcError::NoSuchCoin { .. }
+            | SimulateSwapRpcError::CoinIsWalletOnly { .. }
+            | SimulateSwapRpcError::BaseEqualRel
+            | SimulateSwapRpcError::VolumeTooLow { .. }
+            | SimulateSwapRpcError::InvalidSwapPolicy(_)
+            | SimulateSwapRpcError::IncompatibleCoins(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl From<CoinFindError> for SimulateSwapRpcError {
+    fn from(e: CoinFindError) -> Self {
+        match e {
+            CoinFindError::NoSuchCoin { coin } => SimulateSwapRpcError::NoSuchCoin { coin },
+        }
+    }
+}
+
+pub async fn simulate_swap_rpc(ctx: MmArc, req: SimulateSwapRequest) -> SimulateSwapRpcResult<SimulateSwapResponse> {
+    if req.base == req.rel {
+        return MmError::err(SimulateSwapRpcError::BaseEqualRel);
+    }
+    if is_wallet_only_ticker(&ctx, &req.base) {
+        return MmError::err(SimulateSwapRpcError::CoinIsWalletOnly { coin: req.base });
+    }
+    if is_wallet_only_ticker(&ctx, &req.rel) {
+        return MmError::err(SimulateSwapRpcError::CoinIsWalletOnly { coin: req.rel });
+    }
+
+    let maker_coin = lp_coinfind_or_err(&ctx, &req.base).await?;
+    let taker_coin = lp_coinfind_or_err(&ctx, &req.rel).await?;
+    check_secret_hash_algo_compatibility(&maker_coin, &taker_coin)
+        .map_to_mm(SimulateSwapRpcError::IncompatibleCoins)?;
+
+    let maker_amount = req.volume;
+    let taker_amount = &maker_amount * &req.price;
+    check_volume(&maker_coin, &maker_amount)?;
+    check_volume(&taker_coin, &taker_amount)?;
+
+    let mut conf_settings = SwapConfirmationsSettings {
+        maker_coin_confs: maker_coin.required_confirmations(),
+        maker_coin_nota: maker_coin.requires_notarization(),
+        taker_coin_confs: taker_coin.required_confirmations(),
+        taker_coin_nota: taker_coin.requires_notarization(),
+    };
+    let prices = fetch_swap_policy_prices(&ctx, maker_coin.ticker(), taker_coin.ticker())
+        .await
+        .map_to_mm(SimulateSwapRpcError::InvalidSwapPolicy)?;
+    let locktime_multiplier = apply_swap_policies(
+        &ctx,
+        maker_coin.ticker(),
+        &maker_amount,
+        taker_coin.ticker(),
+        &taker_amount,
+        &prices,
+        &mut conf_settings,
+    )
+    .map_to_mm(SimulateSwapRpcError::InvalidSwapPolicy)?;
+    let lock_duration = lp_atomic_locktime(maker_coin.ticker(), taker_coin.ticker(), AtomicLocktimeVersion::V2 {
+        my_conf_settings: conf_settings,
+        other_conf_settings: conf_settings,
+    }) * locktime_multiplier;
+    let started_at = now_ms() / 1000;
+
+    let secret = MakerSwap::generate_secret();
+    let secret_hash = detect_secret_hash_algo(&maker_coin, &taker_coin).hash_secret(&secret);
+
+    // The maker uses the secret hash to derive the HTLC key pair, the taker uses the swap UUID.
+    let maker = SwapSideParams {
+        payment_amount: maker_amount.clone(),
+        payment_locktime: started_at + lock_duration * 2,
+        required_confirmations: conf_settings.maker_coin_confs,
+        requires_notarization: conf_settings.maker_coin_nota,
+        unique_swap_data: secret_hash.clone(),
+    };
+    let taker = SwapSideParams {
+        payment_amount: taker_amount.clone(),
+        payment_locktime: started_at + lock_duration,
+        required_confirmations: conf_settings.taker_coin_confs,
+        requires_notarization: conf_settings.taker_coin_nota,
+        unique_swap_data: Uuid::new_v4().as_bytes().to_vec(),
+    };
+
+    let mut maker_side = simulate_swap_side(&maker_coin, &maker, &taker, &secret_hash).await;
+    let mut taker_side = simulate_swap_side(&taker_coin, &taker, &maker, &secret_hash).await;
+
+    // Maker sends the payment and receives the taker payment.
+    simulate_trade_fees(&mut maker_side, &maker_coin, &taker_coin, &maker_amount).await;
+
+    // Taker sends the dex fee and the payment, and receives the maker payment.
+    let dex_fee = dex_fee_amount_from_taker_coin(&taker_coin, maker_coin.ticker(), &taker_amount);
+    taker_side.fees.push(TradeFeeResponse::from(TradeFee {
+        coin: taker_coin.ticker().to_owned(),
+        amount: dex_fee.clone(),
+        paid_from_trading_vol: false,
+    }));
+    match taker_coin
+        .get_fee_to_send_taker_fee(dex_fee.to_decimal(), FeeApproxStage::WithoutApprox)
+        .await
+    {
+        Ok(fee) => taker_side.fees.push(TradeFeeResponse::from(fee)),
+        Err(e) => taker_side.add_failure(SimulatedSwapStage::CalcTradeFee, e.to_string()),
+    }
+    simulate_trade_fees(&mut taker_side, &taker_coin, &maker_coin, &taker_amount).await;
+
+    let success = maker_side.failures.is_empty() && taker_side.failures.is_empty();
+    Ok(SimulateSwapResponse {
+        success,
+        lock_duration,
+        maker: maker_side,
+        taker: taker_side,
+    })
+}
+
+struct SwapSideParams {
+    payment_amount: MmNumber,
+    payment_locktime: u64,
+    required_confirmations: u64,
+    requires_notarization: bool,
+    unique_swap_data: Vec<u8>,
+}
+
+impl SimulatedSwapSide {
+    fn add_failure(&mut self, stage: SimulatedSwapStage, error: String) {
+        self.failures.push(SimulatedSwapFailure { stage, error })
+    }
+}
+
+fn check_volume(coin: &MmCoinEnum, volume: &MmNumber) -> SimulateSwapRpcResult<()> {
+    let threshold = coin.min_trading_vol();
+    if volume < &threshold {
+        return MmError::err(SimulateSwapRpcError::VolumeTooLow {
+            coin: coin.ticker().to_owned(),
+            volume: volume.to_decimal(),
+            threshold: threshold.to_decimal(),
+        });
+    }
+    Ok(())
+}
+
+/// Generates the swap payment and validates it as the other side would do.
+/// The node plays the both sides of the swap, so the HTLC pubkeys of both sides are derived from the node's keys
+/// and the swap unique data of the corresponding side.
+async fn simulate_swap_side(
+    coin: &MmCoinEnum,
+    params: &SwapSideParams,
+    other_params: &SwapSideParams,
+    secret_hash: &[u8],
+) -> SimulatedSwapSide {
+    let mut side = SimulatedSwapSide {
+        coin: coin.ticker().to_owned(),
+        payment_amount: params.payment_amount.to_decimal(),
+        payment_locktime: params.payment_locktime,
+        required_confirmations: params.required_confirmations,
+        requires_notarization: params.requires_notarization,
+        swap_contract_address: None,
+        payment_tx_hex: None,
+        payment_tx_hash: None,
+        fees: Vec::new(),
+        failures: Vec::new(),
+    };
+
+    let my_swap_contract = coin.swap_contract_address();
+    let other_swap_contract = my_swap_contract.as_ref().map(|addr| addr.0.as_slice());
+    match coin.negotiate_swap_contract_addr(other_swap_contract) {
+        Ok(addr) => side.swap_contract_address = addr,
+        Err(e) => {
+            side.add_failure(SimulatedSwapStage::NegotiateSwapContract, e.to_string());
+            return side;
+        },
+    }
+
+    let sender_htlc_pub = coin
+        .derive_htlc_key_pair(&params.unique_swap_data)
+        .public_slice()
+        .to_vec();
+    let receiver_htlc_pub = coin
+        .derive_htlc_key_pair(&other_params.unique_swap_data)
+        .public_slice()
+        .to_vec();
+    let payment_input = UnbroadcastSwapPaymentInput {
+        time_lock: params.payment_locktime as u32,
+        other_pub: receiver_htlc_pub,
+        secret_hash: secret_hash.to_vec(),
+        amount: params.payment_amount.to_decimal(),
+        swap_contract_address: side.swap_contract_address.clone(),
+        swap_unique_data: params.unique_swap_data.clone(),
+    };
+    let payment = match coin.gen_unbroadcast_swap_payment(payment_input).compat().await {
+        Ok(tx) => tx,
+        Err(e) => {
+            side.add_failure(SimulatedSwapStage::GeneratePayment, e.get_plain_text_format());
+            return side;
+        },
+    };
+    side.payment_tx_hex = Some(payment.tx_hex().into());
+    side.payment_tx_hash = Some(payment.tx_hash());
+
+    // The payment is validated by the receiver, so `other_pub` is the sender's HTLC pubkey
+    // and `unique_swap_data` is the receiver's one.
+    let validate_input = ValidatePaymentInput {
+        payment_tx: payment.tx_hex(),
+        time_lock: params.payment_locktime as u32,
+        other_pub: sender_htlc_pub,
+        secret_hash: secret_hash.to_vec(),
+        amount: params.payment_amount.to_decimal(),
+        swap_contract_address: side.swap_contract_address.clone(),
+        try_spv_proof_until: 0,
+        confirmations: 0,
+        unique_swap_data: other_params.unique_swap_data.clone(),
+    };
+    if let Err(e) = coin.validate_unbroadcast_swap_payment(validate_input).compat().await {
+        side.add_failure(SimulatedSwapStage::ValidatePayment, e);
+    }
+
+    // The payment must not be refundable right after it has been sent.
+    match coin.can_refund_htlc(params.payment_locktime).compat().await {
+        Ok(CanRefundHtlc::HaveToWait(_)) => (),
+        Ok(CanRefundHtlc::CanRefundNow) => side.add_failure(
+            SimulatedSwapStage::CheckRefundLocktime,
+            format!("Payment is refundable right away, locktime {}", params.payment_locktime),
+        ),
+        Err(e) => side.add_failure(SimulatedSwapStage::CheckRefundLocktime, e),
+    }
+
+    side
+}
+
+/// Calculates the fee to send the payment of `my_coin` and the fee to spend the payment of `other_coin`.
+async fn simulate_trade_fees(
+    side: &mut SimulatedSwapSide,
+    my_coin: &MmCoinEnum,
+    other_coin: &MmCoinEnum,
+    my_amount: &MmNumber,
+) {
+    let preimage_value = TradePreimageValue::Exact(my_amount.to_decimal());
+    match my_coin
+        .get_sender_trade_fee(preimage_value, FeeApproxStage::WithoutApprox)
+        .await
+    {
+        Ok(fee) => side.fees.push(TradeFeeResponse::from(fee)),
+        Err(e) => side.add_failure(SimulatedSwapStage::CalcTradeFee, e.to_string()),
+    }
+    match other_coin
+        .get_receiver_trade_fee(FeeApproxStage::WithoutApprox)
+        .compat()
+        .await
+    {
+        Ok(fee) => side.fees.push(TradeFeeResponse::from(fee)),
+        Err(e) => side.add_failure(SimulatedSwapStage::CalcTradeFee, e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod simulate_swap_tests {
+    use super::*;
+    use coins::utxo::UtxoTx;
+    use coins::{MarketCoinOps, MmCoin, SwapOps, TestCoin, TransactionEnum};
+    use common::block_on;
+    use keys::KeyPair;
+    use mocktopus::mocking::*;
+
+    fn side_params(payment_locktime: u64, unique_swap_data: Vec<u8>) -> SwapSideParams {
+        SwapSideParams {
+            payment_amount: MmNumber::from(1),
+            payment_locktime,
+            required_confirmations: 1,
+            requires_notarization: false,
+            unique_swap_data,
+        }
+    }
+
+    #[test]
+    fn test_simulate_maker_payment_validated_by_taker() {
+        let maker_key_pair = KeyPair::random_compressed();
+        let taker_key_pair = KeyPair::random_compressed();
+        let maker_pub = maker_key_pair.public_slice().to_vec();
+        let taker_pub = taker_key_pair.public_slice().to_vec();
+
+        TestCoin::ticker.mock_safe(|_| MockResult::Return("ticker"));
+        TestCoin::swap_contract_address.mock_safe(|_| MockResult::Return(None));
+        TestCoin::negotiate_swap_contract_addr.mock_safe(|_, _| MockResult::Return(Ok(None)));
+        TestCoin::derive_htlc_key_pair.mock_safe(move |_, unique_swap_data| {
+            let key_pair = if unique_swap_data == b"maker" {
+                maker_key_pair
+            } else {
+                taker_key_pair
+            };
+            MockResult::Return(key_pair)
+        });
+        let expected_other_pub = taker_pub.clone();
+        TestCoin::gen_unbroadcast_swap_payment.mock_safe(move |_, input| {
+            assert_eq!(input.other_pub, expected_other_pub);
+            assert_eq!(input.swap_unique_data, b"maker".to_vec());
+            MockResult::Return(Box::new(futures01::future::ok(
+                TransactionEnum::from(UtxoTx::default()),
+            )))
+        });
+        TestCoin::validate_unbroadcast_swap_payment.mock_safe(move |_, input| {
+            assert_eq!(input.other_pub, maker_pub);
+            assert_eq!(input.unique_swap_data, b"taker".to_vec());
+            MockResult::Return(Box::new(futures01::future::ok(())))
+        });
+        TestCoin::validate_maker_payment.mock_safe(|_, _| panic!("The production validation must not be used"));
+
+        let coin = MmCoinEnum::Test(TestCoin::default());
+        let maker = side_params(now_ms() / 1000 + 7800, b"maker".to_vec());
+        let taker = side_params(now_ms() / 1000 + 3900, b"taker".to_vec());
+        let side = block_on(simulate_swap_side(&coin, &maker, &taker, &[0; 20]));
+        assert!(side.failures.is_empty());
+        assert!(side.payment_tx_hex.is_some());
+    }
+
+    #[test]
+    fn test_simulate_payment_validation_failed() {
+        TestCoin::ticker.mock_safe(|_| MockResult::Return("ticker"));
+        TestCoin::swap_contract_address.mock_safe(|_| MockResult::Return(None));
+        TestCoin::negotiate_swap_contract_addr.mock_safe(|_, _| MockResult::Return(Ok(None)));
+        TestCoin::derive_htlc_key_pair.mock_safe(|_, _| MockResult::Return(KeyPair::random_compressed()));
+        TestCoin::gen_unbroadcast_swap_payment.mock_safe(|_, _| {
+            MockResult::Return(Box::new(futures01::future::ok(
+                TransactionEnum::from(UtxoTx::default()),
+            )))
+        });
+        TestCoin::validate_unbroadcast_swap_payment
+            .mock_safe(|_, _| MockResult::Return(Box::new(futures01::future::err("Invalid payment".to_owned()))));
+
+        let coin = MmCoinEnum::Test(TestCoin::default());
+        let maker = side_params(now_ms() / 1000 + 7800, b"maker".to_vec());
+        let taker = side_params(now_ms() / 1000 + 3900, b"taker".to_vec());
+        let side = block_on(simulate_swap_side(&coin, &taker, &maker, &[0; 20]));
+        assert_eq!(side.failures.len(), 1);
+        assert!(matches!(side.failures[0].stage, SimulatedSwapStage::ValidatePayment));
+    }
+}
@@ -0,0 +1,398 @@
+//! Per-coin swap policies scaling the required confirmations, notarization and payment locktime by the swap amount.
+//!
+//! The policy is set in the coin config, e.g.:
+//! ```json
+//! "swap_policy": {
+//!   "tiers": [
+//!     { "min_amount": "100", "required_confirmations": 3 },
+//!     { "min_usd_value": "10000", "required_confirmations": 5 },
+//!     { "min_amount": "1000", "required_confirmations": 10, "requires_notarization": true, "locktime_multiplier": 2 }
+//!   ]
+//! }
+//! ```
+//! A tier applies if the amount of the coin sent within the swap reaches its `min_amount`
+//! or the USD value of the amount reaches its `min_usd_value`.
+//! The requirements of all the applying tiers are combined, they can only raise the confirmations
+//! and notarization requirements set by the order or the coin config.
+//!
+//! The USD prices are fetched from the price service only if a `min_usd_value` tier is configured for any swap coin.
+//! The `min_usd_value` tiers are skipped if the prices are not available.
+//!
+//! The maker and taker exchange the raised confirmations settings within the `MakerReserved` and `TakerRequest`
+//! messages, so both sides start the swap with the same settings even if their policies differ.
+//! The locktime multipliers are exchanged within the swap negotiation data:
+//! the taker adopts the maker multiplier if it's not less than the one required by the taker policies
+//! and the maker checks that the taker replied with the same multiplier.
+
+use super::SwapConfirmationsSettings;
+use crate::mm2::lp_price::{fetch_swap_coins_price, CEXRates};
+use coins::coin_conf;
+use common::log::warn;
+use mm2_core::mm_ctx::MmArc;
+use mm2_number::MmNumber;
+use serde_json::{self as json, Value as Json};
+
+/// The max locktime multiplier accepted from the coin config and the other side of the swap.
+pub const MAX_LOCKTIME_MULTIPLIER: u64 = 10;
+
+fn default_locktime_multiplier() -> u64 { 1 }
+
+#[derive(Clone, Debug, Deserialize, PartialEq)]
+pub struct SwapPolicyTier {
+    /// The minimum amount of the coin the tier applies to.
+    #[serde(default)]
+    pub min_amount: Option<MmNumber>,
+    /// The minimum USD value of the coin amount the tier applies to.
+    #[serde(default)]
+    pub min_usd_value: Option<MmNumber>,
+    pub required_confirmations: u64,
+    #[serde(default)]
+    pub requires_notarization: bool,
+    /// The payment locktime is multiplied by the greatest multiplier of the maker and taker coin tiers.
+    #[serde(default = "default_locktime_multiplier")]
+    pub locktime_multiplier: u64,
+}
+
+impl SwapPolicyTier {
+    /// Whether the tier applies to the `amount` of the coin worth `usd_price` USD per coin.
+    pub fn applies_to(&self, amount: &MmNumber, usd_price: Option<&MmNumber>) -> bool {
+        let by_amount = matches!(&self.min_amount, Some(min_amount) if min_amount <= amount);
+        let by_usd_value = match (&self.min_usd_value, usd_price) {
+            (Some(min_usd_value), Some(usd_price)) => min_usd_value <= &(amount * usd_price),
+            _ => false,
+        };
+        by_amount || by_usd_value
+    }
+}
+
+/// The combined requirements of the swap policy tiers applying to the swap amount of the coin.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct SwapPolicyRequirements {
+    pub required_confirmations: u64,
+    pub requires_notarization: bool,
+    pub locktime_multiplier: u64,
+}
+
+impl SwapPolicyRequirements {
+    /// Raises the given confirmations and notarization requirements up to the policy ones.
+    pub fn raise_confs(&self, confs: &mut u64, nota: &mut bool) {
+        if *confs < self.required_confirmations {
+            *confs = self.required_confirmations;
+        }
+        *nota |= self.requires_notarization;
+    }
+
+    /// Checks that the given confirmations and notarization settings satisfy the policy.
+    pub fn check_confs(&self, confs: u64, nota: bool) -> Result<(), String> {
+        if confs < self.required_confirmations {
+            return ERR!(
+                "{} confirmations are less than {} required by the swap policy",
+                confs,
+                self.required_confirmations
+            );
+        }
+        if self.requires_notarization && !nota {
+            return ERR!("The swap policy requires notarization");
+        }
+        Ok(())
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq)]
+pub struct CoinSwapPolicy {
+    tiers: Vec<SwapPolicyTier>,
+}
+
+impl CoinSwapPolicy {
+    pub fn from_coin_conf(conf: &Json) -> Result<CoinSwapPolicy, String> {
+        if conf["swap_policy"].is_null() {
+            return Ok(CoinSwapPolicy::default());
+        }
+        let policy: CoinSwapPolicy = try_s!(json::from_value(conf["swap_policy"].clone()));
+        for tier in policy.tiers.iter() {
+            if tier.min_amount.is_none() && tier.min_usd_value.is_none() {
+                return ERR!("Either 'min_amount' or 'min_usd_value' must be set");
+            }
+            if tier.locktime_multiplier == 0 || tier.locktime_multiplier > MAX_LOCKTIME_MULTIPLIER {
+                return ERR!(
+                    "'locktime_multiplier' must be in the range [1, {}]",
+                    MAX_LOCKTIME_MULTIPLIER
+                );
+            }
+        }
+        Ok(policy)
+    }
+
+    /// Whether any tier is selected by the USD value of the amount.
+    pub fn has_usd_tiers(&self) -> bool { self.tiers.iter().any(|tier| tier.min_usd_value.is_some()) }
+
+    /// Returns the combined requirements of the tiers applying to the given `amount` of the coin if any.
+    pub fn requirements(&self, amount: &MmNumber, usd_price: Option<&MmNumber>) -> Option<SwapPolicyRequirements> {
+        self.tiers
+            .iter()
+            .filter(|tier| tier.applies_to(amount, usd_price))
+            .fold(None, |requirements, tier| {
+                let mut requirements = requirements.unwrap_or(SwapPolicyRequirements {
+                    required_confirmations: 0,
+                    requires_notarization: false,
+                    locktime_multiplier: 1,
+                });
+                requirements.required_confirmations =
+                    requirements.required_confirmations.max(tier.required_confirmations);
+                requirements.requires_notarization |= tier.requires_notarization;
+                requirements.locktime_multiplier = requirements.locktime_multiplier.max(tier.locktime_multiplier);
+                Some(requirements)
+            })
+    }
+}
+
+/// Returns the requirements of the swap policy applying to the given `amount` of the `ticker` coin if any.
+pub fn swap_policy_requirements(
+    ctx: &MmArc,
+    ticker: &str,
+    amount: &MmNumber,
+    usd_price: Option<&MmNumber>,
+) -> Result<Option<SwapPolicyRequirements>, String> {
+    let policy = try_s!(CoinSwapPolicy::from_coin_conf(&coin_conf(ctx, ticker)));
+    Ok(policy.requirements(amount, usd_price))
+}
+
+/// The USD prices of the swap coins the `min_usd_value` tiers are selected by.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct SwapPolicyPrices {
+    pub base: Option<MmNumber>,
+    pub rel: Option<MmNumber>,
+}
+
+impl From<CEXRates> for SwapPolicyPrices {
+    fn from(rates: CEXRates) -> Self {
+        SwapPolicyPrices {
+            base: Some(rates.base.into()),
+            rel: Some(rates.rel.into()),
+        }
+    }
+}
+
+/// Fetches the USD prices of the `base` and `rel` coins if the policy of any of them has the `min_usd_value` tiers.
+pub async fn fetch_swap_policy_prices(ctx: &MmArc, base: &str, rel: &str) -> Result<SwapPolicyPrices, String> {
+    let base_policy = try_s!(CoinSwapPolicy::from_coin_conf(&coin_conf(ctx, base)));
+    let rel_policy = try_s!(CoinSwapPolicy::from_coin_conf(&coin_conf(ctx, rel)));
+    if !base_policy.has_usd_tiers() && !rel_policy.has_usd_tiers() {
+        return Ok(SwapPolicyPrices::default());
+    }
+    match fetch_swap_coins_price(Some(base.to_owned()), Some(rel.to_owned())).await {
+        Some(rates) => Ok(rates.into()),
+        None => {
+            warn!(
+                "Couldn't fetch the {}/{} USD prices, the 'min_usd_value' swap policy tiers are skipped",
+                base, rel
+            );
+            Ok(SwapPolicyPrices::default())
+        },
+    }
+}
+
+/// Returns the payment locktime multiplier required by the maker and taker coin policies for the swap amounts.
+/// The `prices.base` and `prices.rel` are the maker and taker coin USD prices.
+pub fn swap_locktime_multiplier(
+    ctx: &MmArc,
+    maker_coin: &str,
+    maker_amount: &MmNumber,
+    taker_coin: &str,
+    taker_amount: &MmNumber,
+    prices: &SwapPolicyPrices,
+) -> Result<u64, String> {
+    let maker_coin_requirements = try_s!(swap_policy_requirements(
+        ctx,
+        maker_coin,
+        maker_amount,
+        prices.base.as_ref()
+    ));
+    let taker_coin_requirements = try_s!(swap_policy_requirements(
+        ctx,
+        taker_coin,
+        taker_amount,
+        prices.rel.as_ref()
+    ));
+    Ok(maker_coin_requirements
+        .into_iter()
+        .chain(taker_coin_requirements)
+        .map(|requirements| requirements.locktime_multiplier)
+        .fold(1, u64::max))
+}
+
+/// Checks the locktime multiplier sent by the maker within the swap negotiation data
+/// and returns the one the taker adopts.
+/// The maker multiplier is accepted if it's not less than the `required` by the taker swap policies.
+pub fn check_maker_locktime_multiplier(maker_multiplier: Option<u64>, required: u64) -> Result<u64, String> {
+    let maker_multiplier = maker_multiplier.unwrap_or(1);
+    if maker_multiplier > MAX_LOCKTIME_MULTIPLIER {
+        return ERR!(
+            "Maker locktime multiplier {} exceeds the max {}",
+            maker_multiplier,
+            MAX_LOCKTIME_MULTIPLIER
+        );
+    }
+    if maker_multiplier < required {
+        return ERR!(
+            "Maker locktime multiplier {} is less than {} required by the swap policy",
+            maker_multiplier,
+            required
+        );
+    }
+    Ok(maker_multiplier)
+}
+
+/// Raises the swap confirmations settings according to the maker and taker coin policies
+/// and returns the multiplier the payment locktime must be multiplied by.
+/// The `prices.base` and `prices.rel` are the maker and taker coin USD prices.
+pub fn apply_swap_policies(
+    ctx: &MmArc,
+    maker_coin: &str,
+    maker_amount: &MmNumber,
+    taker_coin: &str,
+    taker_amount: &MmNumber,
+    prices: &SwapPolicyPrices,
+    settings: &mut SwapConfirmationsSettings,
+) -> Result<u64, String> {
+    let maker_coin_requirements = try_s!(swap_policy_requirements(
+        ctx,
+        maker_coin,
+        maker_amount,
+        prices.base.as_ref()
+    ));
+    let taker_coin_requirements = try_s!(swap_policy_requirements(
+        ctx,
+        taker_coin,
+        taker_amount,
+        prices.rel.as_ref()
+    ));
+
+    let mut locktime_multiplier = 1;
+    if let Some(requirements) = maker_coin_requirements {
+        requirements.raise_confs(&mut settings.maker_coin_confs, &mut settings.maker_coin_nota);
+        locktime_multiplier = requirements.locktime_multiplier;
+    }
+    if let Some(requirements) = taker_coin_requirements {
+        requirements.raise_confs(&mut settings.taker_coin_confs, &mut settings.taker_coin_nota);
+        locktime_multiplier = locktime_multiplier.max(requirements.locktime_multiplier);
+    }
+    Ok(locktime_multiplier)
+}
+
+#[cfg(test)]
+mod swap_policy_tests {
+    use super::*;
+
+    fn test_policy() -> CoinSwapPolicy {
+        let conf = json!({
+            "coin": "RICK",
+            "swap_policy": {
+                "tiers": [
+                    { "min_amount": "1000", "required_confirmations": 10, "requires_notarization": true, "locktime_multiplier": 2 },
+                    { "min_usd_value": "5000", "required_confirmations": 5, "locktime_multiplier": 3 },
+                    { "min_amount": "100", "required_confirmations": 3 },
+                ]
+            }
+        });
+        CoinSwapPolicy::from_coin_conf(&conf).unwrap()
+    }
+
+    #[test]
+    fn test_coin_swap_policy_requirements() {
+        let policy = test_policy();
+        assert!(policy.has_usd_tiers());
+
+        assert_eq!(policy.requirements(&MmNumber::from("99.9"), None), None);
+
+        let requirements = policy.requirements(&MmNumber::from(100), None).unwrap();
+        assert_eq!(requirements.required_confirmations, 3);
+        assert!(!requirements.requires_notarization);
+        assert_eq!(requirements.locktime_multiplier, 1);
+
+        let requirements = policy.requirements(&MmNumber::from(5000), None).unwrap();
+        assert_eq!(requirements.required_confirmations, 10);
+        assert!(requirements.requires_notarization);
+        assert_eq!(requirements.locktime_multiplier, 2);
+
+        let mut confs = 20;
+        let mut nota = false;
+        requirements.raise_confs(&mut confs, &mut nota);
+        assert_eq!(confs, 20);
+        assert!(nota);
+
+        let no_policy = CoinSwapPolicy::from_coin_conf(&json!({"coin": "MORTY"})).unwrap();
+        assert!(!no_policy.has_usd_tiers());
+        assert_eq!(no_policy.requirements(&MmNumber::from(5000), None), None);
+    }
+
+    #[test]
+    fn test_coin_swap_policy_usd_value_tiers() {
+        let policy = test_policy();
+        let usd_price = MmNumber::from(100);
+
+        // 10 coins are worth 1000 USD.
+        assert_eq!(policy.requirements(&MmNumber::from(10), Some(&usd_price)), None);
+
+        // 50 coins are worth 5000 USD.
+        let requirements = policy.requirements(&MmNumber::from(50), Some(&usd_price)).unwrap();
+        assert_eq!(requirements, SwapPolicyRequirements {
+            required_confirmations: 5,
+            requires_notarization: false,
+            locktime_multiplier: 3,
+        });
+
+        // The requirements of the applying amount and USD value tiers are combined.
+        let requirements = policy.requirements(&MmNumber::from(1000), Some(&usd_price)).unwrap();
+        assert_eq!(requirements, SwapPolicyRequirements {
+            required_confirmations: 10,
+            requires_notarization: true,
+            locktime_multiplier: 3,
+        });
+
+        // The USD value tiers are skipped if the price is unknown.
+        let requirements = policy.requirements(&MmNumber::from(500), None).unwrap();
+        assert_eq!(requirements.required_confirmations, 3);
+        assert_eq!(requirements.locktime_multiplier, 1);
+    }
+
+    #[test]
+    fn test_swap_policy_requirements_check_confs() {
+        let requirements = SwapPolicyRequirements {
+            required_confirmations: 5,
+            requires_notarization: true,
+            locktime_multiplier: 1,
+        };
+        requirements.check_confs(5, true).unwrap();
+        requirements.check_confs(4, true).unwrap_err();
+        requirements.check_confs(10, false).unwrap_err();
+    }
+
+    #[test]
+    fn test_invalid_coin_swap_policy() {
+        let zero_multiplier = json!({
+            "swap_policy": { "tiers": [{ "min_amount": "1", "required_confirmations": 1, "locktime_multiplier": 0 }] }
+        });
+        CoinSwapPolicy::from_coin_conf(&zero_multiplier).unwrap_err();
+
+        let too_large_multiplier = json!({
+            "swap_policy": { "tiers": [{ "min_amount": "1", "required_confirmations": 1, "locktime_multiplier": 11 }] }
+        });
+        CoinSwapPolicy::from_coin_conf(&too_large_multiplier).unwrap_err();
+
+        let no_threshold = json!({
+            "swap_policy": { "tiers": [{ "required_confirmations": 1 }] }
+        });
+        CoinSwapPolicy::from_coin_conf(&no_threshold).unwrap_err();
+    }
+
+    #[test]
+    fn test_check_maker_locktime_multiplier() {
+        assert_eq!(check_maker_locktime_multiplier(None, 1), Ok(1));
+        assert_eq!(check_maker_locktime_multiplier(Some(3), 2), Ok(3));
+        assert_eq!(check_maker_locktime_multiplier(Some(2), 2), Ok(2));
+        check_maker_locktime_multiplier(None, 2).unwrap_err();
+        check_maker_locktime_multiplier(Some(2), 3).unwrap_err();
+        check_maker_locktime_multiplier(Some(MAX_LOCKTIME_MULTIPLIER + 1), 1).unwrap_err();
+    }
+}
@@ -0,0 +1,179 @@
+//! The background service recovering the funds of the swaps finished with an error.
+//!
+//! The service periodically scans the saved swaps that can be recovered, checks the my payment state
+//! by `search_for_swap_tx_spend_my` and `can_refund_htlc`, and refunds the my payment
+//! or spends the other payment as soon as it becomes possible.
+//! Every recovery is saved as the corresponding swap event, and reported with the [`SwapFundsRecovered`] event
+//! and the message service notification.
+
+use super::{active_swaps, RecoveredSwapAction, SavedSwap, SavedSwapIo, SwapRecoveryReadiness, SwapsContext};
+use crate::mm2::lp_dispatcher::{dispatch_lp_event, LpEvents};
+use crate::mm2::lp_message_service::{MessageServiceContext, SWAP_RECOVERY_ROOM_ID};
+use coins::{lp_coinfind, CanRefundHtlc, MmCoinEnum};
+use common::executor::Timer;
+use common::log::{debug, error, info};
+use common::now_ms;
+use futures::compat::Future01CompatExt;
+use mm2_core::mm_ctx::{MmArc, MmWeak};
+use rpc::v1::types::Bytes as BytesJson;
+use std::any::TypeId;
+use uuid::Uuid;
+
+/// The default interval between the stuck swaps scans, in seconds.
+const DEFAULT_SWAP_RECOVERY_INTERVAL: f64 = 600.;
+
+#[derive(Clone, Debug, Serialize)]
+pub struct SwapFundsRecovered {
+    pub uuid: Uuid,
+    pub action: RecoveredSwapAction,
+    pub coin: String,
+    pub tx_hash: BytesJson,
+}
+
+impl SwapFundsRecovered {
+    pub fn event_id() -> TypeId { TypeId::of::<SwapFundsRecovered>() }
+}
+
+/// Runs the stuck swaps recovery until the context is stopped.
+/// The service can be disabled by the `disable_swap_recovery` config option,
+/// the scan interval is set by the `swap_recovery_interval` option, in seconds.
+pub async fn swap_recovery_loop(ctx_weak: MmWeak) {
+    loop {
+        let interval = {
+            let ctx = match MmArc::from_weak(&ctx_weak) {
+                Some(ctx) => ctx,
+                None => return,
+            };
+            if ctx.is_stopping() {
+                break;
+            }
+            if ctx.conf["disable_swap_recovery"].as_bool().unwrap_or(false) {
+                info!("Swap recovery service is disabled");
+                break;
+            }
+
+            recover_stuck_swaps(&ctx).await;
+            ctx.conf["swap_recovery_interval"]
+                .as_f64()
+                .unwrap_or(DEFAULT_SWAP_RECOVERY_INTERVAL)
+        };
+        Timer::sleep(interval).await;
+    }
+}
+
+async fn recover_stuck_swaps(ctx: &MmArc) {
+    let swaps = match SavedSwap::load_all_my_swaps_from_db(ctx).await {
+        Ok(swaps) => swaps,
+        Err(e) => {
+            error!("Error loading the swaps to recover: {}", e);
+            return;
+        },
+    };
+    let running_swaps = match active_swaps(ctx) {
+        Ok(uuids) => uuids,
+        Err(e) => {
+            error!("Error getting the active swaps: {}", e);
+            return;
+        },
+    };
+    let swaps_ctx = match SwapsContext::from_ctx(ctx) {
+        Ok(swaps_ctx) => swaps_ctx,
+        Err(e) => {
+            error!("Error getting the swaps context: {}", e);
+            return;
+        },
+    };
+
+    for mut swap in swaps {
+        let uuid = *swap.uuid();
+        if !swap.is_recoverable()
+            || running_swaps.contains(&uuid)
+            || swaps_ctx.recovered_swaps.lock().unwrap().contains(&uuid)
+        {
+            continue;
+        }
+        // Wait until the both coins of the swap are activated by the user.
+        if !is_coin_activated(ctx, swap.maker_coin_ticker()).await
+            || !is_coin_activated(ctx, swap.taker_coin_ticker()).await
+        {
+            continue;
+        }
+
+        match swap.clone().recovery_readiness(ctx.clone()).await {
+            Ok(SwapRecoveryReadiness::Ready) => (),
+            Ok(SwapRecoveryReadiness::HaveToWait(seconds)) => {
+                debug!("Swap {} funds can be recovered in {} seconds", uuid, seconds);
+                continue;
+            },
+            Ok(SwapRecoveryReadiness::NothingToRecover(reason)) => {
+                debug!("Swap {} has nothing to recover: {}", uuid, reason);
+                swaps_ctx.recovered_swaps.lock().unwrap().insert(uuid);
+                continue;
+            },
+            Err(e) => {
+                error!("Error checking the swap {} recovery: {}", uuid, e);
+                continue;
+            },
+        }
+
+        let recovered = match swap.clone().recover_funds(ctx.clone()).await {
+            Ok(recovered) => recovered,
+            Err(e) => {
+                error!("Swap {} funds can't be recovered: {}", uuid, e);
+                continue;
+            },
+        };
+        swaps_ctx.recovered_swaps.lock().unwrap().insert(uuid);
+
+        // Save the recovery, so the swap isn't recovered again after the restart.
+        swap.add_recovery_event(&recovered);
+        if let Err(e) = swap.save_to_db(ctx).await {
+            error!("Error saving the recovered swap {}: {}", uuid, e);
+        }
+
+        let event = SwapFundsRecovered {
+            uuid,
+            action: recovered.action,
+            coin: recovered.coin,
+            tx_hash: recovered.transaction.tx_hash(),
+        };
+        let msg = format!(
+            "Swap {}: {:?} with {} tx {:02x}",
+            event.uuid, event.action, event.coin, event.tx_hash
+        );
+        info!("{}", msg);
+        dispatch_lp_event(ctx.clone(), LpEvents::SwapFundsRecovered(event)).await;
+
+        let message_service_ctx = MessageServiceContext::from_ctx(ctx).unwrap();
+        if let Err(e) = message_service_ctx
+            .send_message(msg, SWAP_RECOVERY_ROOM_ID, false)
+            .await
+        {
+            error!("Error sending the swap recovery notification: {}", e);
+        }
+    }
+}
+
+/// Checks whether the my payment can be refunded right now:
+/// not earlier than `wait_refund_until` like the swap does, and if `can_refund_htlc` allows it.
+pub(super) async fn my_payment_refund_readiness(
+    coin: &MmCoinEnum,
+    locktime: u64,
+    wait_refund_until: u64,
+) -> Result<SwapRecoveryReadiness, String> {
+    let now = now_ms() / 1000;
+    if now < wait_refund_until {
+        return Ok(SwapRecoveryReadiness::HaveToWait(wait_refund_until - now));
+    }
+    match try_s!(coin.can_refund_htlc(locktime).compat().await) {
+        CanRefundHtlc::CanRefundNow => Ok(SwapRecoveryReadiness::Ready),
+        CanRefundHtlc::HaveToWait(seconds) => Ok(SwapRecoveryReadiness::HaveToWait(seconds)),
+    }
+}
+
+async fn is_coin_activated(ctx: &MmArc, ticker: Result<String, String>) -> bool {
+    match ticker {
+        Ok(ticker) => matches!(lp_coinfind(ctx, &ticker).await, Ok(Some(_))),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,168 @@
+//! The v2 versions of the swap RPCs: `my_swap_status`, `my_recent_swaps`, `active_swaps` and `recover_funds_of_swap`.
+//!
+//! The responses keep the format of the legacy RPCs, but the errors are typed.
+
+use super::my_swaps_storage::{MySwapsError, MySwapsOps, MySwapsStorage};
+use super::{active_swaps, MyRecentSwapsReq, MySwapStatusResponse, RecoveredSwapAction, SavedSwap, SavedSwapError,
+            SavedSwapIo};
+use common::log::error;
+use common::{calc_total_pages, HttpStatusCode, StatusCode};
+use derive_more::Display;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use rpc::v1::types::Bytes as BytesJson;
+use std::collections::HashMap;
+use std::num::NonZeroUsize;
+use uuid::Uuid;
+
+pub type SwapRpcResult<T> = Result<T, MmError<SwapRpcError>>;
+
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum SwapRpcError {
+    #[display(fmt = "Swap {} not found", uuid)]
+    SwapNotFound { uuid: Uuid },
+    #[display(fmt = "Swap {} can't be recovered: {}", uuid, reason)]
+    SwapNotRecoverable { uuid: Uuid, reason: String },
+    #[display(fmt = "Storage error: {}", _0)]
+    StorageError(String),
+    #[display(fmt = "Internal error: {}", _0)]
+    InternalError(String),
+}
+
+impl HttpStatusCode for SwapRpcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            SwapRpcError::SwapNotFound { .. } => StatusCode::NOT_FOUND,
+            SwapRpcError::SwapNotRecoverable { .. } => StatusCode::BAD_REQUEST,
+            SwapRpcError::StorageError(_) | SwapRpcError::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl From<SavedSwapError> for SwapRpcError {
+    fn from(e: SavedSwapError) -> Self { SwapRpcError::StorageError(e.to_string()) }
+}
+
+impl From<MySwapsError> for SwapRpcError {
+    fn from(e: MySwapsError) -> Self { SwapRpcError::StorageError(e.to_string()) }
+}
+
+async fn load_my_swap(ctx: &MmArc, uuid: Uuid) -> SwapRpcResult<SavedSwap> {
+    SavedSwap::load_my_swap_from_db(ctx, uuid)
+        .await?
+        .or_mm_err(|| SwapRpcError::SwapNotFound { uuid })
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct MySwapStatusRequest {
+    uuid: Uuid,
+}
+
+/// Returns the status of swap performed on `my` node.
+pub async fn my_swap_status_rpc_v2(ctx: MmArc, req: MySwapStatusRequest) -> SwapRpcResult<MySwapStatusResponse> {
+    let swap = load_my_swap(&ctx, req.uuid).await?;
+    Ok(MySwapStatusResponse::from(swap))
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct MyRecentSwapsResponse {
+    swaps: Vec<MySwapStatusResponse>,
+    from_uuid: Option<Uuid>,
+    skipped: usize,
+    limit: usize,
+    total: usize,
+    page_number: NonZeroUsize,
+    total_pages: usize,
+    found_records: usize,
+}
+
+/// Returns the data of recent swaps of `my` node.
+pub async fn my_recent_swaps_rpc_v2(ctx: MmArc, req: MyRecentSwapsReq) -> SwapRpcResult<MyRecentSwapsResponse> {
+    let db_result = MySwapsStorage::new(ctx.clone())
+        .my_recent_swaps_with_filters(&req.filter, Some(&req.paging_options))
+        .await?;
+
+    let mut swaps = Vec::with_capacity(db_result.uuids.len());
+    for uuid in db_result.uuids.iter() {
+        match SavedSwap::load_my_swap_from_db(&ctx, *uuid).await {
+            Ok(Some(swap)) => swaps.push(MySwapStatusResponse::from(swap)),
+            Ok(None) => error!("No such swap with the uuid '{}'", uuid),
+            Err(e) => error!("Error loading a swap with the uuid '{}': {}", uuid, e),
+        }
+    }
+
+    Ok(MyRecentSwapsResponse {
+        swaps,
+        from_uuid: req.paging_options.from_uuid,
+        skipped: db_result.skipped,
+        limit: req.paging_options.limit,
+        total: db_result.total_count,
+        page_number: req.paging_options.page_number,
+        total_pages: calc_total_pages(db_result.total_count, req.paging_options.limit),
+        found_records: db_result.uuids.len(),
+    })
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct ActiveSwapsRequest {
+    #[serde(default)]
+    include_status: bool,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct ActiveSwapsResponse {
+    uuids: Vec<Uuid>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    statuses: Option<HashMap<Uuid, SavedSwap>>,
+}
+
+pub async fn active_swaps_rpc_v2(ctx: MmArc, req: ActiveSwapsRequest) -> SwapRpcResult<ActiveSwapsResponse> {
+    let uuids = active_swaps(&ctx).map_to_mm(SwapRpcError::InternalError)?;
+    let statuses = if req.include_status {
+        let mut statuses = HashMap::with_capacity(uuids.len());
+        for uuid in uuids.iter() {
+            match SavedSwap::load_my_swap_from_db(&ctx, *uuid).await {
+                Ok(Some(status)) => {
+                    statuses.insert(*uuid, status);
+                },
+                Ok(None) => (),
+                Err(e) => error!("Error on loading_from_db: {}", e),
+            }
+        }
+        Some(statuses)
+    } else {
+        None
+    };
+    Ok(ActiveSwapsResponse { uuids, statuses })
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct RecoverFundsOfSwapRequest {
+    uuid: Uuid,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct RecoverFundsOfSwapResponse {
+    action: RecoveredSwapAction,
+    coin: String,
+    tx_hash: BytesJson,
+    tx_hex: BytesJson,
+}
+
+pub async fn recover_funds_of_swap_rpc_v2(
+    ctx: MmArc,
+    req: RecoverFundsOfSwapRequest,
+) -> SwapRpcResult<RecoverFundsOfSwapResponse> {
+    let swap = load_my_swap(&ctx, req.uuid).await?;
+    let recovered = swap
+        .recover_funds(ctx)
+        .await
+        .map_to_mm(|reason| SwapRpcError::SwapNotRecoverable { uuid: req.uuid, reason })?;
+    Ok(RecoverFundsOfSwapResponse {
+        action: recovered.action,
+        coin: recovered.coin,
+        tx_hash: recovered.transaction.tx_hash(),
+        tx_hex: BytesJson::from(recovered.transaction.tx_hex()),
+    })
+}
@@ -2,12 +2,15 @@ use super::check_balance::{check_my_coin_balance_for_swap, CheckBalanceError, Ch
                            TakerFeeAdditionalInfo};
 use super::pubkey_banning::ban_pubkey_on_failed_swap;
 use super::swap_lock::{SwapLock, SwapLockOps};
+use super::swap_recovery::my_payment_refund_readiness;
 use super::trade_preimage::{TradePreimageRequest, TradePreimageRpcError, TradePreimageRpcResult};
-use super::{broadcast_my_swap_status, broadcast_swap_message_every, check_other_coin_balance_for_swap,
-            dex_fee_amount_from_taker_coin, dex_fee_rate, dex_fee_threshold, get_locked_amount, recv_swap_msg,
+use super::{broadcast_my_swap_status, broadcast_swap_message_every, check_maker_locktime_multiplier,
+            check_other_coin_balance_for_swap, detect_secret_hash_algo, dex_fee_amount_from_taker_coin, dex_fee_rate,
+            dex_fee_threshold, get_locked_amount, negotiate_swap_v2_contract, on_swap_finished, recv_swap_msg,
             swap_topic, AtomicSwap, LockedAmount, MySwapInfo, NegotiationDataMsg, NegotiationDataV2,
             NegotiationDataV3, RecoveredSwap, RecoveredSwapAction, SavedSwap, SavedSwapIo, SavedTradeFee,
-            SwapConfirmationsSettings, SwapError, SwapMsg, SwapsContext, TransactionIdentifier, WAIT_CONFIRM_INTERVAL};
+            SwapConfirmationsSettings, SwapError, SwapMsg, SwapRecoveryReadiness, SwapsContext, TransactionIdentifier,
+            WAIT_CONFIRM_INTERVAL};
 use crate::mm2::lp_network::subscribe_to_topic;
 use crate::mm2::lp_ordermatch::{MatchBy, OrderConfirmationsSettings, TakerAction, TakerOrderBuilder};
 use crate::mm2::lp_price::fetch_swap_coins_price;
@@ -103,7 +106,7 @@ async fn save_my_taker_swap_event(ctx: &MmArc, swap: &TakerSwap, event: TakerSav
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct TakerSavedEvent {
     pub timestamp: u64,
     pub event: TakerSwapEvent,
@@ -140,7 +143,7 @@ impl TakerSavedEvent {
     }
 }
 
-#[derive(Debug, Deserialize, PartialEq, Serialize)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct TakerSavedSwap {
     pub uuid: Uuid,
     pub my_order_uuid: Option<Uuid>,
@@ -398,6 +401,7 @@ pub async fn run_taker_swap(swap: RunTakerSwapInput, ctx: MmArc) {
                                 error!("!broadcast_my_swap_status({}): {}", uuid, e);
                             }
                         }
+                        on_swap_finished(&ctx, running_swap.uuid);
                         break;
                     },
                 }
@@ -453,6 +457,25 @@ pub struct TakerSwapData {
     pub taker_coin_htlc_pubkey: Option<H264Json>,
     /// Temporary privkey used to sign P2P messages when applicable
     pub p2p_privkey: Option<SerializableSecp256k1Keypair>,
+    /// The payment locktime multiplier the `lock_duration` includes.
+    /// Is set to the multiplier required by the swap policies and replaced by the maker one on the negotiation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locktime_multiplier: Option<u64>,
+}
+
+impl TakerSwapData {
+    /// Returns the lock duration multiplied by the given multiplier instead of the current one.
+    fn lock_duration_with_multiplier(&self, locktime_multiplier: u64) -> u64 {
+        self.lock_duration / self.locktime_multiplier.unwrap_or(1) * locktime_multiplier
+    }
+
+    /// Replaces the current locktime multiplier and updates the lock duration and the timestamps depending on it.
+    fn apply_locktime_multiplier(&mut self, locktime_multiplier: u64) {
+        self.lock_duration = self.lock_duration_with_multiplier(locktime_multiplier);
+        self.taker_payment_lock = self.started_at + self.lock_duration;
+        self.maker_payment_wait = maker_payment_wait(self.started_at, self.lock_duration);
+        self.locktime_multiplier = Some(locktime_multiplier);
+    }
 }
 
 pub struct TakerSwapMut {
@@ -486,6 +509,8 @@ pub struct TakerSwap {
     mutable: RwLock<TakerSwapMut>,
     conf_settings: SwapConfirmationsSettings,
     payment_locktime: u64,
+    /// The swap policies locktime multiplier the `payment_locktime` is multiplied by.
+    locktime_multiplier: u64,
     p2p_privkey: Option<KeyPair>,
 }
 
@@ -504,6 +529,9 @@ pub struct MakerNegotiationData {
     pub taker_coin_swap_contract_addr: Option<BytesJson>,
     pub maker_coin_htlc_pubkey: Option<H264Json>,
     pub taker_coin_htlc_pubkey: Option<H264Json>,
+    /// The maker locktime multiplier adopted on the negotiation.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub locktime_multiplier: Option<u64>,
 }
 
 impl MakerNegotiationData {
@@ -662,6 +690,10 @@ impl TakerSwap {
                 if data.taker_coin_swap_contract_addr.is_some() {
                     self.w().data.taker_coin_swap_contract_address = data.taker_coin_swap_contract_addr;
                 }
+
+                if let Some(locktime_multiplier) = data.locktime_multiplier {
+                    self.w().data.apply_locktime_multiplier(locktime_multiplier);
+                }
             },
             TakerSwapEvent::NegotiateFailed(err) => self.errors.lock().push(err),
             TakerSwapEvent::TakerFeeSent(tx) => self.w().taker_fee = Some(tx),
@@ -722,6 +754,7 @@ impl TakerSwap {
         maker_coin: MmCoinEnum,
         taker_coin: MmCoinEnum,
         payment_locktime: u64,
+        locktime_multiplier: u64,
         p2p_privkey: Option<KeyPair>,
     ) -> Self {
         TakerSwap {
@@ -739,6 +772,7 @@ impl TakerSwap {
             errors: PaMutex::new(Vec::new()),
             conf_settings,
             payment_locktime,
+            locktime_multiplier,
             p2p_privkey,
             mutable: RwLock::new(TakerSwapMut {
                 data: TakerSwapData::default(),
@@ -762,23 +796,37 @@ impl TakerSwap {
         secret_hash: Vec<u8>,
         maker_coin_swap_contract: Vec<u8>,
         taker_coin_swap_contract: Vec<u8>,
+        payment_locktime: u64,
+        locktime_multiplier: u64,
     ) -> NegotiationDataMsg {
         let r = self.r();
-        if r.data.maker_coin_htlc_pubkey != r.data.taker_coin_htlc_pubkey {
+        let maker_coin_swap_v2_contract = self.maker_coin.swap_v2_contract_address().map(|addr| addr.0);
+        let taker_coin_swap_v2_contract = self.taker_coin.swap_v2_contract_address().map(|addr| addr.0);
+        let supports_swap_v2 = maker_coin_swap_v2_contract.is_some() || taker_coin_swap_v2_contract.is_some();
+
+        // The swap contract v2 addresses and the locktime multiplier can be sent within `NegotiationDataV3` only.
+        if r.data.maker_coin_htlc_pubkey != r.data.taker_coin_htlc_pubkey
+            || supports_swap_v2
+            || locktime_multiplier != 1
+        {
             NegotiationDataMsg::V3(NegotiationDataV3 {
                 started_at: r.data.started_at,
-                payment_locktime: r.data.taker_payment_lock,
+                payment_locktime,
                 secret_hash,
                 maker_coin_swap_contract,
                 taker_coin_swap_contract,
                 maker_coin_htlc_pub: self.my_maker_coin_htlc_pub().into(),
                 taker_coin_htlc_pub: self.my_taker_coin_htlc_pub().into(),
+                conf_settings: Some(self.conf_settings),
+                maker_coin_swap_v2_contract,
+                taker_coin_swap_v2_contract,
+                locktime_multiplier: Some(locktime_multiplier),
             })
         } else {
             NegotiationDataMsg::V2(NegotiationDataV2 {
                 started_at: r.data.started_at,
                 secret_hash,
-                payment_locktime: r.data.taker_payment_lock,
+                payment_locktime,
                 persistent_pubkey: self.my_persistent_pub.to_vec(),
                 maker_coin_swap_contract,
                 taker_coin_swap_contract,
@@ -896,6 +944,7 @@ impl TakerSwap {
             maker_coin_htlc_pubkey: Some(maker_coin_htlc_key_pair.public_slice().into()),
             taker_coin_htlc_pubkey: Some(taker_coin_htlc_key_pair.public_slice().into()),
             p2p_privkey: self.p2p_privkey.map(SerializableSecp256k1Keypair::from),
+            locktime_multiplier: Some(self.locktime_multiplier),
         };
 
         Ok((Some(TakerSwapCommand::Negotiate), vec![TakerSwapEvent::Started(data)]))
@@ -927,13 +976,40 @@ impl TakerSwap {
             )]));
         }
 
-        let expected_lock_time = maker_data.started_at() + self.r().data.lock_duration * 2;
+        // the maker locktime multiplier is adopted if it's not less than the one required by my swap policies
+        let locktime_multiplier =
+            match check_maker_locktime_multiplier(maker_data.locktime_multiplier(), self.locktime_multiplier) {
+                Ok(multiplier) => multiplier,
+                Err(e) => {
+                    return Ok((Some(TakerSwapCommand::Finish), vec![TakerSwapEvent::NegotiateFailed(
+                        ERRL!("{}", e).into(),
+                    )]))
+                },
+            };
+        let lock_duration = self.r().data.lock_duration_with_multiplier(locktime_multiplier);
+        let my_payment_locktime = self.r().data.started_at + lock_duration;
+
+        let expected_lock_time = maker_data.started_at() + lock_duration * 2;
         if maker_data.payment_locktime() != expected_lock_time {
             return Ok((Some(TakerSwapCommand::Finish), vec![TakerSwapEvent::NegotiateFailed(
                 ERRL!(
-                    "maker_data.payment_locktime {} not equal to expected {}",
+                    "maker_data.payment_locktime {} not equal to expected {}, maker conf settings {:?}, my conf settings {:?}",
                     maker_data.payment_locktime(),
-                    expected_lock_time
+                    expected_lock_time,
+                    maker_data.conf_settings(),
+                    self.conf_settings
+                )
+                .into(),
+            )]));
+        }
+
+        let expected_secret_hash_len = detect_secret_hash_algo(&self.maker_coin, &self.taker_coin).hash_len();
+        if maker_data.secret_hash().len() != expected_secret_hash_len {
+            return Ok((Some(TakerSwapCommand::Finish), vec![TakerSwapEvent::NegotiateFailed(
+                ERRL!(
+                    "maker_data.secret_hash length {} not equal to expected {}",
+                    maker_data.secret_hash().len(),
+                    expected_secret_hash_len
                 )
                 .into(),
             )]));
@@ -973,7 +1049,16 @@ impl TakerSwap {
             maker_data.secret_hash().to_vec(),
             maker_coin_swap_contract_bytes,
             taker_coin_swap_contract_bytes,
+            my_payment_locktime,
+            locktime_multiplier,
         );
+        // The maker negotiates the swap contract v2 by the addresses sent within `my_negotiation_data` the same way.
+        let maker_coin_swap_contract_addr =
+            negotiate_swap_v2_contract(&self.maker_coin, maker_data.maker_coin_swap_v2_contract())
+                .or(maker_coin_swap_contract_addr);
+        let taker_coin_swap_contract_addr =
+            negotiate_swap_v2_contract(&self.taker_coin, maker_data.taker_coin_swap_v2_contract())
+                .or(taker_coin_swap_contract_addr);
 
         let taker_data = SwapMsg::NegotiationReply(my_negotiation_data);
         debug!("Sending taker negotiation data {:?}", taker_data);
@@ -1017,6 +1102,7 @@ impl TakerSwap {
                 taker_coin_swap_contract_addr,
                 maker_coin_htlc_pubkey: Some(maker_data.maker_coin_htlc_pub().into()),
                 taker_coin_htlc_pubkey: Some(maker_data.taker_coin_htlc_pub().into()),
+                locktime_multiplier: Some(locktime_multiplier),
             },
         )]))
     }
@@ -1461,6 +1547,7 @@ impl TakerSwap {
             maker_coin,
             taker_coin,
             data.lock_duration,
+            data.locktime_multiplier.unwrap_or(1),
             data.p2p_privkey.map(SerializableSecp256k1Keypair::into_inner),
         );
         let command = saved.events.last().unwrap().get_command();
@@ -1693,6 +1780,60 @@ impl TakerSwap {
             },
         }
     }
+
+    /// Checks whether [`TakerSwap::recover_funds`] can succeed right now without sending any transaction.
+    /// The taker payment spend is looked up by `search_for_swap_tx_spend_my`,
+    /// and the refund locktime is checked by `can_refund_htlc`.
+    pub async fn recovery_readiness(&self) -> Result<SwapRecoveryReadiness, String> {
+        if self.r().taker_payment_refund.is_some() {
+            return Ok(SwapRecoveryReadiness::NothingToRecover(
+                "Taker payment is refunded".to_owned(),
+            ));
+        }
+        if self.r().maker_payment_spend.is_some() {
+            return Ok(SwapRecoveryReadiness::NothingToRecover(
+                "Maker payment is spent".to_owned(),
+            ));
+        }
+        if self.r().maker_payment.is_none() {
+            return Ok(SwapRecoveryReadiness::NothingToRecover(
+                "No info about maker payment".to_owned(),
+            ));
+        }
+        // The maker has spent the taker payment, so the maker payment can be spent right away.
+        if self.r().taker_payment_spend.is_some() {
+            return Ok(SwapRecoveryReadiness::Ready);
+        }
+        let taker_payment = match self.r().taker_payment.clone() {
+            Some(tx) => tx.tx_hex.0,
+            // The payment might be sent but not saved, it's looked up by `recover_funds`.
+            None => return Ok(SwapRecoveryReadiness::Ready),
+        };
+
+        let other_taker_coin_htlc_pub = self.r().other_taker_coin_htlc_pub;
+        let secret_hash = self.r().secret_hash.0.clone();
+        let taker_payment_lock = self.r().data.taker_payment_lock;
+        let taker_coin_start_block = self.r().data.taker_coin_start_block;
+        let taker_coin_swap_contract_address = self.r().data.taker_coin_swap_contract_address.clone();
+        let unique_data = self.unique_swap_data();
+
+        let search_input = SearchForSwapTxSpendInput {
+            time_lock: taker_payment_lock as u32,
+            other_pub: other_taker_coin_htlc_pub.as_slice(),
+            secret_hash: &secret_hash,
+            tx: &taker_payment,
+            search_from_block: taker_coin_start_block,
+            swap_contract_address: &taker_coin_swap_contract_address,
+            swap_unique_data: &unique_data,
+        };
+        match try_s!(self.taker_coin.search_for_swap_tx_spend_my(search_input).await) {
+            Some(FoundSwapTxSpend::Spent(_)) => Ok(SwapRecoveryReadiness::Ready),
+            Some(FoundSwapTxSpend::Refunded(_)) => Ok(SwapRecoveryReadiness::NothingToRecover(
+                "Taker payment is refunded".to_owned(),
+            )),
+            None => my_payment_refund_readiness(&self.taker_coin, taker_payment_lock, self.wait_refund_until()).await,
+        }
+    }
 }
 
 impl AtomicSwap for TakerSwap {
@@ -2327,6 +2468,94 @@ mod taker_swap_tests {
         assert!(unsafe { MAKER_PAYMENT_SPEND_CALLED });
     }
 
+    /// The swap ended with `TakerPaymentRefundFailed` after the maker payment was received.
+    const TAKER_PAYMENT_REFUND_FAILED_SWAP: &str = r#"{"error_events":["StartFailed","NegotiateFailed","TakerFeeSendFailed","MakerPaymentValidateFailed","TakerPaymentTransactionFailed","TakerPaymentDataSendFailed","TakerPaymentWaitForSpendFailed","MakerPaymentSpendFailed","TakerPaymentRefunded","TakerPaymentRefundFailed"],"events":[{"event":{"data":{"lock_duration":7800,"maker":"1bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8","maker_amount":"0.58610590","maker_coin":"KMD","maker_coin_start_block":1450923,"maker_payment_confirmations":1,"maker_payment_wait":1563623475,"my_persistent_pub":"02713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91","started_at":1563620875,"taker_amount":"0.0077700000552410000000000","taker_coin":"LTC","taker_coin_start_block":1670837,"taker_payment_confirmations":1,"taker_payment_lock":1563628675,"uuid":"9db641f5-4300-4527-9fa6-f1c391d42c35"},"type":"Started"},"timestamp":1563620875766},{"event":{"data":{"maker_payment_locktime":1563636475,"maker_pubkey":"031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8","secret_hash":"7ed38daab6085c1a1e4426e61dc87a3c2c081a95"},"type":"Negotiated"},"timestamp":1563620955014},{"event":{"data":{"tx_hash":"6740136eaaa615d9d231969e3a9599d0fc59e53989237a8d31cd6fc86c160013","tx_hex":"0100000001a2586ea8294cedc55741bef625ba72c646399903391a7f6c604a58c6263135f2000000006b4830450221009c78c8ba4a7accab6b09f9a95da5bc59c81f4fc1e60b288ec3c5462b4d02ef01022056b63be1629cf17751d3cc5ffec51bcb1d7f9396e9ce9ca254d0f34104f7263a012102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ffffffff0210270000000000001976a914ca1e04745e8ca0c60d8c5881531d51bec470743f88ac78aa1900000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac5bf6325d"},"type":"TakerFeeSent"},"timestamp":1563620958220},{"event":{"data":{"tx_hash":"d0f6e664cea9d89fe7b5cf8005fdca070d1ab1d05a482aaef95c08cdaecddf0a","tx_hex":"0400008085202f89019f1cbda354342cdf982046b331bbd3791f53b692efc6e4becc36be495b2977d9000000006b483045022100fa9d4557394141f6a8b9bfb8cd594a521fd8bcd1965dbf8bc4e04abc849ac66e0220589f521814c10a7561abfd5e432f7a2ee60d4875fe4604618af3207dae531ac00121031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ffffffff029e537e030000000017a9145534898009f1467191065f6890b96914b39a1c018791857702000000001976a914c3f710deb7320b0efa6edb14e3ebeeb9155fa90d88ac72ee325d000000000000000000000000000000"},"type":"MakerPaymentReceived"},"timestamp":1563620999307},{"event":{"type":"MakerPaymentWaitConfirmStarted"},"timestamp":1563620999310},{"event":{"type":"MakerPaymentValidatedAndConfirmed"},"timestamp":1563621244153},{"event":{"data":{"tx_hash":"1e883eb2f3991e84ba27f53651f89b7dda708678a5b9813d043577f222b9ca30","tx_hex":"01000000011300166cc86fcd318d7a238939e559fcd099953a9e9631d2d915a6aa6e134067010000006a47304402206781d5f2db2ff13d2ec7e266f774ea5630cc2dba4019e18e9716131b8b026051022006ebb33857b6d180f13aa6be2fc532f9734abde9d00ae14757e7d7ba3741c08c012102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ffffffff0228db0b000000000017a91483818667161bf94adda3964a81a231cbf6f5338187b0480c00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac7cf7325d"},"type":"TakerPaymentSent"},"timestamp":1563621246370},{"event":{"data":{"error":"utxo:1145] rpc_clients:782] Waited too long until 1563628675 for output TransactionOutput { value: 777000, script_pubkey: a91483818667161bf94adda3964a81a231cbf6f5338187 } to be spent "},"type":"TakerPaymentWaitForSpendFailed"},"timestamp":1563638060370},{"event":{"data":{"error":"lp_swap:2025] utxo:938] rpc_clients:719] JsonRpcError { request: JsonRpcRequest { jsonrpc: \"2.0\", id: \"9\", method: \"blockchain.transaction.broadcast\", params: [String(\"010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d\")] }, error: Response(Object({\"code\": Number(1), \"message\": String(\"the transaction was rejected by network rules.\\n\\nMissing inputs\\n[010000000130cab922f27735043d81b9a5788670da7d9bf85136f527ba841e99f3b23e881e00000000b6473044022058a0c1da6bcf8c1418899ff8475f3ab6dddbff918528451c1fe71c2f7dad176302204c2e0bcf8f9b5f09e02ccfeb9256e9b34fb355ea655a5704a8a3fa920079b91501514c6b63048314335db1752102713015d3fa4d30259e90be5f131beb593bf0131f3af2dcdb304e3322d8d52b91ac6782012088a9147ed38daab6085c1a1e4426e61dc87a3c2c081a958821031bb83b58ec130e28e0a6d5d2acf2eb01b0d3f1670e021d47d31db8a858219da8ac68feffffff0188540a00000000001976a91406ccabfd5f9075ecd5e8d0d31c0e973a54d51e8288ac1c2b335d]\")})) }"},"type":"TakerPaymentRefundFailed"},"timestamp":1563638060583},{"event":{"type":"Finished"},"timestamp":1563638060585}],"success_events":["Started","Negotiated","TakerFeeSent","MakerPaymentReceived","MakerPaymentWaitConfirmStarted","MakerPaymentValidatedAndConfirmed","TakerPaymentSent","TakerPaymentSpent","MakerPaymentSpent","Finished"],"uuid":"9db641f5-4300-4527-9fa6-f1c391d42c35"}"#;
+
+    fn taker_swap_for_recovery_test() -> TakerSwap {
+        let taker_saved_swap: TakerSavedSwap = json::from_str(TAKER_PAYMENT_REFUND_FAILED_SWAP).unwrap();
+        let key_pair =
+            key_pair_from_seed("spice describe gravity federal blast come thank unfair canal monkey style afraid")
+                .unwrap();
+        let ctx = MmCtxBuilder::default().with_secp256k1_key_pair(key_pair).into_mm_arc();
+
+        TestCoin::ticker.mock_safe(|_| MockResult::Return("ticker"));
+        TestCoin::swap_contract_address.mock_safe(|_| MockResult::Return(None));
+        let maker_coin = MmCoinEnum::Test(TestCoin::default());
+        let taker_coin = MmCoinEnum::Test(TestCoin::default());
+        let (taker_swap, _) = TakerSwap::load_from_saved(ctx, maker_coin, taker_coin, taker_saved_swap).unwrap();
+        taker_swap
+    }
+
+    #[test]
+    fn test_recovery_readiness_taker_payment_not_spent() {
+        let taker_swap = taker_swap_for_recovery_test();
+        TestCoin::search_for_swap_tx_spend_my
+            .mock_safe(|_, _| MockResult::Return(Box::pin(futures::future::ready(Ok(None)))));
+
+        // The locktime has expired long ago, so the default `can_refund_htlc` allows the refund.
+        assert_eq!(
+            block_on(taker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::Ready)
+        );
+
+        taker_swap.w().data.taker_payment_lock = now_ms() / 1000 - 3690;
+        match block_on(taker_swap.recovery_readiness()) {
+            Ok(SwapRecoveryReadiness::HaveToWait(seconds)) => assert!(seconds <= 10),
+            other => panic!("Expected HaveToWait, found {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_recovery_readiness_taker_payment_spent_or_refunded() {
+        let taker_swap = taker_swap_for_recovery_test();
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _| {
+            MockResult::Return(Box::pin(futures::future::ready(Ok(Some(FoundSwapTxSpend::Spent(
+                eth_tx_for_test().into(),
+            ))))))
+        });
+        // The maker has spent the taker payment, so the maker payment can be spent.
+        assert_eq!(
+            block_on(taker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::Ready)
+        );
+
+        TestCoin::search_for_swap_tx_spend_my.mock_safe(|_, _| {
+            MockResult::Return(Box::pin(futures::future::ready(Ok(Some(FoundSwapTxSpend::Refunded(
+                eth_tx_for_test().into(),
+            ))))))
+        });
+        assert!(matches!(
+            block_on(taker_swap.recovery_readiness()),
+            Ok(SwapRecoveryReadiness::NothingToRecover(_))
+        ));
+    }
+
+    #[test]
+    fn test_add_taker_recovery_event() {
+        let taker_saved_swap: TakerSavedSwap = json::from_str(TAKER_PAYMENT_REFUND_FAILED_SWAP).unwrap();
+        let events_len = taker_saved_swap.events.len();
+        let mut saved_swap = SavedSwap::Taker(taker_saved_swap);
+        assert!(saved_swap.is_recoverable());
+
+        saved_swap.add_recovery_event(&RecoveredSwap {
+            action: RecoveredSwapAction::SpentOtherPayment,
+            coin: "ticker".into(),
+            transaction: eth_tx_for_test().into(),
+        });
+        assert!(saved_swap.is_finished());
+        assert!(!saved_swap.is_recoverable());
+        match saved_swap {
+            SavedSwap::Taker(swap) => {
+                assert_eq!(swap.events.len(), events_len + 1);
+                assert!(matches!(
+                    swap.events[events_len - 1].event,
+                    TakerSwapEvent::MakerPaymentSpent(_)
+                ));
+            },
+            SavedSwap::Maker(_) => panic!("Expected the taker swap"),
+        }
+    }
+
     #[test]
     fn test_recover_funds_taker_swap_not_finished() {
         // the json doesn't have Finished event at the end
@@ -2430,6 +2659,38 @@ mod taker_swap_tests {
         assert!(!swap.is_recoverable());
     }
 
+    #[test]
+    fn test_apply_locktime_multiplier() {
+        let mut data = TakerSwapData {
+            lock_duration: 7800 * 2,
+            started_at: 1000,
+            taker_payment_lock: 1000 + 7800 * 2,
+            maker_payment_wait: maker_payment_wait(1000, 7800 * 2),
+            locktime_multiplier: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(data.lock_duration_with_multiplier(2), 7800 * 2);
+        assert_eq!(data.lock_duration_with_multiplier(3), 7800 * 3);
+
+        // the maker multiplier is adopted on the negotiation
+        data.apply_locktime_multiplier(3);
+        assert_eq!(data.lock_duration, 7800 * 3);
+        assert_eq!(data.taker_payment_lock, 1000 + 7800 * 3);
+        assert_eq!(data.maker_payment_wait, maker_payment_wait(1000, 7800 * 3));
+        assert_eq!(data.locktime_multiplier, Some(3));
+
+        // the swaps started by an older version don't have the multiplier
+        let mut data = TakerSwapData {
+            lock_duration: 7800,
+            started_at: 1000,
+            ..Default::default()
+        };
+        assert_eq!(data.lock_duration_with_multiplier(1), 7800);
+        data.apply_locktime_multiplier(2);
+        assert_eq!(data.lock_duration, 7800 * 2);
+        assert_eq!(data.taker_payment_lock, 1000 + 7800 * 2);
+    }
+
     #[test]
     fn test_max_taker_vol_from_available() {
         let dex_fee_threshold = MmNumber::from("0.0001");
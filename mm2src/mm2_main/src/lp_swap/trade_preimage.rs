@@ -42,7 +42,7 @@ pub async fn trade_preimage_rpc(
     }
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct TradePreimageRequest {
     /// The base currency of the request.
     pub base: String,
@@ -61,7 +61,7 @@ pub struct TradePreimageRequest {
     pub max: bool,
 }
 
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 #[serde(rename_all = "lowercase")]
 pub enum TradePreimageMethod {
     SetPrice,
@@ -79,7 +79,7 @@ impl TradePreimageMethod {
     }
 }
 
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 #[serde(untagged)]
 #[allow(clippy::large_enum_variant)]
 pub enum TradePreimageResponse {
@@ -177,7 +177,7 @@ impl TradePreimageResponse {
 }
 
 /// The extended `coins::TradePreimageError` error.
-#[derive(Display, Serialize, SerializeErrorType)]
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum TradePreimageRpcError {
     #[display(
@@ -381,7 +381,7 @@ impl TradePreimageRpcError {
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, RpcSchema, Serialize)]
 pub struct TradeFeeResponse {
     coin: String,
     #[serde(flatten)]
@@ -430,7 +430,7 @@ impl From<TradeFee> for TotalTradeFee {
     }
 }
 
-#[derive(Clone, Serialize)]
+#[derive(Clone, RpcSchema, Serialize)]
 pub struct TotalTradeFeeResponse {
     coin: String,
     #[serde(flatten)]
@@ -60,6 +60,7 @@ use mm2_err_handle::prelude::*;
 pub mod database;
 
 #[path = "lp_dispatcher.rs"] pub mod lp_dispatcher;
+#[path = "lp_kmd_rewards.rs"] pub mod lp_kmd_rewards;
 #[path = "lp_message_service.rs"] pub mod lp_message_service;
 #[path = "lp_network.rs"] pub mod lp_network;
 #[path = "lp_ordermatch.rs"] pub mod lp_ordermatch;
@@ -286,6 +287,7 @@ The first command-line argument is special and designates the mode.
   events                     ..  Listen to a feed coming from a separate MM daemon and print it to stdout.
   vanity {substring}         ..  Tries to find an address with the given substring.
   update_config {SRC} {DST}  ..  Update the configuration of coins from the SRC config and save it to DST file.
+  export_rpc_schema {DST}    ..  Save the OpenAPI document of the `mmrpc: 2.0` methods to DST file.
   {JSON configuration}       ..  Run the MarketMaker daemon.
 
 Some (but not all) of the JSON configuration parameters (* - required):
@@ -304,6 +306,9 @@ Some (but not all) of the JSON configuration parameters (* - required):
   passphrase *   ..  Wallet seed.
                      Compressed WIFs and hexadecimal ECDSA keys (prefixed with 0x) are also accepted.
   panic          ..  Simulate a panic to see if backtrace works.
+  proxy          ..  SOCKS5 proxy for the electrum, web3, lightwalletd and HTTP requests,
+                     e.g. {"address": "127.0.0.1:9050"} for Tor. Optional "username" and "password".
+                     The coins can override it by the "proxy" field of the coins config.
   rpccors        ..  Access-Control-Allow-Origin header value to be used in all the RPC responses.
                      Default is currently 'http://localhost:3000'
   rpcip          ..  IP address to bind to for RPC server. Overrides the 127.0.0.1 default
@@ -312,6 +317,8 @@ Some (but not all) of the JSON configuration parameters (* - required):
   rpc_local_only ..  MM forbids some RPC requests from not loopback (localhost) IPs as additional security measure.
                      Defaults to `true`, set `false` to disable. `Use with caution`.
   rpcport        ..  If > 1000 overrides the 7783 default.
+  rpc_disable_http .. Don't start the HTTP RPC server, the requests are only accepted via the `mm2_rpc` library call.
+                     Defaults to `false`.
   i_am_seed      ..  Activate the seed node mode (acting as a relay for mm2 clients).
                      Defaults to `false`.
   seednodes      ..  Seednode IPs that node will use.
@@ -380,6 +387,13 @@ pub fn mm2_main() {
         }
         return;
     }
+    if first_arg == Some("export_rpc_schema") {
+        match on_export_rpc_schema(&args_os) {
+            Ok(_) => println!("Success"),
+            Err(e) => eprintln!("{}", e),
+        }
+        return;
+    }
 
     if first_arg == Some("--help") || first_arg == Some("-h") || first_arg == Some("help") {
         help();
@@ -487,6 +501,16 @@ fn on_update_config(args: &[OsString]) -> Result<(), String> {
     Ok(())
 }
 
+#[cfg(not(target_arch = "wasm32"))]
+fn on_export_rpc_schema(args: &[OsString]) -> Result<(), String> {
+    let dst_path = args.get(2).ok_or(ERRL!("Expect destination path."))?;
+
+    let document = rpc::schema::openapi_document();
+    let encoded = try_s!(json::to_vec_pretty(&document));
+    try_s!(std::fs::write(&dst_path, encoded));
+    Ok(())
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 fn init_logger(level: LogLevel) -> Result<(), String> {
     use common::log::UnifiedLoggerBuilder;
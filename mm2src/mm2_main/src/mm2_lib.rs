@@ -11,6 +11,7 @@
 #[macro_use] extern crate gstuff;
 #[macro_use] extern crate serde_json;
 #[macro_use] extern crate serde_derive;
+#[macro_use] extern crate rpc_schema_derive;
 #[macro_use] extern crate serialization_derive;
 #[macro_use] extern crate ser_error_derive;
 
@@ -1,21 +1,30 @@
 use super::*;
 use crate::mm2::lp_dispatcher::dispatch_lp_event;
-use crate::mm2::lp_dispatcher::StopCtxEvent;
+use crate::mm2::lp_dispatcher::{DispatcherContext, FfiEventCallback, FfiEventListener, StopCtxEvent};
+use crate::mm2::rpc::process_ffi_request;
 use common::crash_reports::init_crash_reports;
 use common::executor::spawn;
 use common::log::{register_callback, FfiCallback};
-use common::{block_on, now_float};
+use common::{block_on, err_to_rpc_json_string, now_float};
 use gstuff::any_to_str;
+use lazy_static::lazy_static;
 use libc::c_char;
 use mm2_core::mm_ctx::MmArc;
 use num_traits::FromPrimitive;
-use serde_json::{self as json};
+use serde_json::{self as json, Value as Json};
 use std::ffi::{CStr, CString};
 use std::panic::catch_unwind;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
 use std::thread;
 use std::time::Duration;
 
+lazy_static! {
+    /// The callback registered by [`mm2_register_event_callback`].
+    /// Kept outside of the context in order to survive the MM2 restarts.
+    static ref EVENT_CALLBACK: Mutex<Option<FfiEventCallback>> = Mutex::new(None);
+}
+
 #[derive(Debug, PartialEq, Primitive)]
 enum MainErr {
     Ok = 0,
@@ -70,7 +79,10 @@ pub unsafe extern "C" fn mm2_main(conf: *const c_char, log_cb: extern "C" fn(lin
             log!("lp_main already started!");
             return;
         }
-        let ctx_cb = &|ctx| CTX.store(ctx, Ordering::Relaxed);
+        let ctx_cb = &|ctx| {
+            CTX.store(ctx, Ordering::Relaxed);
+            add_event_listener(ctx);
+        };
         match catch_unwind(move || mm2::run_lp_main(Some(&conf), ctx_cb)) {
             Ok(Ok(_)) => log!("run_lp_main finished"),
             Ok(Err(err)) => log!("run_lp_main error: {}", err),
@@ -255,3 +267,106 @@ pub extern "C" fn mm2_stop() -> i8 {
 
     StopErr::Ok as i8
 }
+
+#[derive(Debug, PartialEq, Primitive)]
+enum RpcErr {
+    Ok = 0,
+    NotRunning = 1,
+    RpcIsNotUp = 2,
+    RequestIsNull = 3,
+    RequestNotUtf8 = 4,
+    RequestNotJson = 5,
+}
+
+/// Processes an RPC request (or a batch of requests) without the HTTP server,
+/// so the library host can disable the HTTP listener by the `rpc_disable_http` config option.
+///
+/// The request is processed asynchronously, the `callback` is invoked once with the HTTP status code
+/// and the JSON response. The `response` pointer is only valid during the `callback` call.
+/// The `callback` is not invoked if a non-zero error code is returned.
+#[no_mangle]
+#[allow(clippy::missing_safety_doc)]
+pub unsafe extern "C" fn mm2_rpc(
+    request: *const c_char,
+    callback: extern "C" fn(status_code: u16, response: *const c_char),
+) -> i8 {
+    match mm2_status() {
+        MainStatus::NotRunning => return RpcErr::NotRunning as i8,
+        MainStatus::NoContext | MainStatus::NoRpc => return RpcErr::RpcIsNotUp as i8,
+        MainStatus::RpcIsUp => (),
+    }
+    let ctx = match MmArc::from_ffi_handle(CTX.load(Ordering::Relaxed)) {
+        Ok(ctx) => ctx,
+        Err(_) => return RpcErr::NotRunning as i8,
+    };
+
+    if request.is_null() {
+        return RpcErr::RequestIsNull as i8;
+    }
+    let request = match CStr::from_ptr(request).to_str() {
+        Ok(s) => s,
+        Err(_) => return RpcErr::RequestNotUtf8 as i8,
+    };
+    let request_json: Json = match json::from_str(request) {
+        Ok(j) => j,
+        Err(_) => return RpcErr::RequestNotJson as i8,
+    };
+
+    spawn(async move {
+        let (status_code, body) = match process_ffi_request(ctx, request_json).await {
+            Ok(response) => (response.status().as_u16(), response.into_body()),
+            Err(e) => (500, err_to_rpc_json_string(&e).into_bytes()),
+        };
+        // The JSON strings escape the nul characters, so the serialized responses never contain them.
+        let response = CString::new(body)
+            .unwrap_or_else(|_| CString::new(err_to_rpc_json_string("Response contains a nul byte")).unwrap());
+        callback(status_code, response.as_ptr());
+    });
+
+    RpcErr::Ok as i8
+}
+
+/// Returns the callback registered by [`mm2_register_event_callback`] for the [`FfiEventListener`].
+fn registered_event_callback() -> Option<FfiEventCallback> { *EVENT_CALLBACK.lock().unwrap() }
+
+/// Adds the [`FfiEventListener`] to the MM2 instance if the event callback is registered.
+fn add_event_listener(ctx_h: u32) {
+    if EVENT_CALLBACK.lock().unwrap().is_none() {
+        return;
+    }
+    let ctx = match MmArc::from_ffi_handle(ctx_h) {
+        Ok(ctx) => ctx,
+        Err(_) => return,
+    };
+    spawn(async move {
+        let dispatcher_ctx = match DispatcherContext::from_ctx(&ctx) {
+            Ok(dispatcher_ctx) => dispatcher_ctx,
+            Err(e) => {
+                log!("mm2_event] !DispatcherContext::from_ctx: {}", e);
+                return;
+            },
+        };
+        dispatcher_ctx
+            .dispatcher
+            .write()
+            .await
+            .add_listener(FfiEventListener::new(registered_event_callback));
+    });
+}
+
+/// Registers the `callback` invoked with every MM2 event serialized as `{"type": "<event>", "data": <event data>}` JSON.
+/// The `event` pointer is only valid during the `callback` call.
+///
+/// The callback can be registered before or after `mm2_main`, it's kept over the MM2 restarts.
+/// Registering another callback replaces the previous one.
+#[no_mangle]
+pub extern "C" fn mm2_register_event_callback(callback: FfiEventCallback) {
+    *EVENT_CALLBACK.lock().unwrap() = Some(callback);
+
+    if LP_MAIN_RUNNING.load(Ordering::Relaxed) {
+        let ctx = CTX.load(Ordering::Relaxed);
+        if ctx != 0 {
+            add_event_listener(ctx);
+        }
+    }
+}
@@ -812,6 +812,18 @@ pub struct ZcoinTransactionDetails {
     pub coin: String,
     pub internal_id: i64,
     pub confirmations: u64,
+    pub received_notes: Vec<ZcoinNoteDetails>,
+    pub sent_notes: Vec<ZcoinNoteDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ZcoinNoteDetails {
+    pub output_index: u32,
+    pub address: String,
+    pub amount: BigDecimal,
+    pub is_change: bool,
+    pub memo: Option<String>,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -120,6 +120,20 @@ fn test_z_coin_tx_history() {
     let my_balance_change = BigDecimal::from_str("-0.1000100").unwrap();
     assert_eq!(withdraw_tx.my_balance_change, my_balance_change);
 
+    // the change is the only note received by our address
+    assert_eq!(withdraw_tx.received_notes.len(), 1);
+    assert!(withdraw_tx.received_notes[0].is_change);
+    assert_eq!(withdraw_tx.received_notes[0].amount, received_by_me);
+
+    // both the withdrawn amount and the change are recovered with our outgoing viewing key
+    assert_eq!(withdraw_tx.sent_notes.len(), 2);
+    let withdrawn_note = withdraw_tx.sent_notes.iter().find(|note| !note.is_change).unwrap();
+    assert_eq!(withdrawn_note.amount, BigDecimal::from_str("0.1").unwrap());
+    assert_eq!(
+        withdrawn_note.address,
+        "zs1g6z7dcfp5wg085fuzqlauf8d85ct4hke7xmwxe0djnq48909yfsj66hzj0fjgfgzynddud8n04g"
+    );
+
     // swap payment spent to our address
     let htlc_spend_tx = &response.result.transactions[1];
     assert_eq!(htlc_spend_tx.internal_id, 4);
@@ -0,0 +1,437 @@
+//! A minimal SMTP client sending the plain text notifications.
+//! Supports the implicit TLS, STARTTLS and the unencrypted connections, and the `AUTH PLAIN` authentication.
+
+use crate::mm2::lp_message_service::{MessageResult, MessageServiceTraits};
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use common::custom_futures::FutureTimerExt;
+use common::wio::drive03;
+use derive_more::Display;
+use lazy_static::lazy_static;
+use mm2_err_handle::prelude::*;
+use mm2_net::native_http::global_proxy;
+use mm2_net::socks5::socks5_connect;
+use std::collections::HashSet;
+use std::convert::TryFrom;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+use uuid::Uuid;
+use webpki_roots::TLS_SERVER_ROOTS;
+
+const DEFAULT_SUBJECT_PREFIX: &str = "[mm2]";
+/// The timeout of the TCP connection and of the TLS handshake in seconds.
+const SMTP_CONNECT_TIMEOUT: f64 = 30.;
+/// The timeout of a single SMTP reply or command write in seconds.
+/// Prevents a hung server from blocking the notification forever.
+const SMTP_IO_TIMEOUT: f64 = 60.;
+
+#[derive(Debug, Deserialize, Display, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum EmailError {
+    #[display(fmt = "Error connecting to the SMTP server: {}", _0)]
+    ConnectionError(String),
+    #[display(fmt = "TLS error: {}", _0)]
+    TlsError(String),
+    #[display(fmt = "SMTP server rejected '{}' command: {}", command, reply)]
+    UnexpectedReply { command: String, reply: String },
+    #[display(fmt = "SMTP {} timed out: {}", stage, reason)]
+    Timeout { stage: String, reason: String },
+}
+
+impl From<std::io::Error> for EmailError {
+    fn from(e: std::io::Error) -> Self { EmailError::ConnectionError(e.to_string()) }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SmtpSecurity {
+    /// TLS is established right after the TCP connection, usually on the 465 port.
+    Tls,
+    /// The connection is upgraded to TLS by the `STARTTLS` command, usually on the 587 port.
+    StartTls,
+    /// The connection is not encrypted. Should be used for the local relays only.
+    None,
+}
+
+impl Default for SmtpSecurity {
+    fn default() -> Self { SmtpSecurity::StartTls }
+}
+
+impl SmtpSecurity {
+    fn default_port(&self) -> u16 {
+        match self {
+            SmtpSecurity::Tls => 465,
+            SmtpSecurity::StartTls => 587,
+            SmtpSecurity::None => 25,
+        }
+    }
+}
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct EmailCfg {
+    pub smtp_host: String,
+    /// Defaults to the usual port of the chosen [`SmtpSecurity`].
+    #[serde(default)]
+    pub smtp_port: Option<u16>,
+    #[serde(default)]
+    pub security: SmtpSecurity,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// The rooms the recipients are subscribed to. The recipients receive the messages of all rooms if it's not set.
+    #[serde(default)]
+    pub rooms: Option<HashSet<String>>,
+    #[serde(default)]
+    pub subject_prefix: Option<String>,
+}
+
+lazy_static! {
+    static ref TLS_CONNECTOR: TlsConnector =
+        {
+            let mut cert_store = RootCertStore::empty();
+            cert_store.add_server_trust_anchors(TLS_SERVER_ROOTS.0.iter().map(|ta| {
+                OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)
+            }));
+            let config = ClientConfig::builder()
+                .with_safe_defaults()
+                .with_root_certificates(cert_store)
+                .with_no_client_auth();
+            TlsConnector::from(Arc::new(config))
+        };
+}
+
+#[derive(Debug, PartialEq)]
+struct SmtpReply {
+    code: u16,
+    text: String,
+}
+
+/// Parses a line of the SMTP reply. Returns the reply code, whether the line is the last one and the line text.
+fn parse_reply_line(line: &str) -> Option<(u16, bool, &str)> {
+    let line = line.trim_end_matches(|c| c == '\r' || c == '\n');
+    let code = line.get(..3)?.parse().ok()?;
+    match line.get(3..4) {
+        None | Some(" ") => Some((code, true, line.get(4..).unwrap_or_default())),
+        Some("-") => Some((code, false, &line[4..])),
+        Some(_) => None,
+    }
+}
+
+/// Fails with [`EmailError::Timeout`] if the `fut` isn't ready within `secs`.
+async fn with_timeout<T, F>(fut: F, secs: f64, stage: &str) -> Result<T, MmError<EmailError>>
+where
+    F: Future<Output = Result<T, MmError<EmailError>>>,
+{
+    Box::pin(fut)
+        .timeout_secs(secs)
+        .await
+        .map_to_mm(|e| EmailError::Timeout {
+            stage: stage.to_owned(),
+            reason: e.to_string(),
+        })?
+}
+
+struct SmtpConnection<S> {
+    stream: BufReader<S>,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> SmtpConnection<S> {
+    fn new(stream: S) -> Self {
+        SmtpConnection {
+            stream: BufReader::new(stream),
+        }
+    }
+
+    fn into_inner(self) -> S { self.stream.into_inner() }
+
+    async fn read_reply(&mut self) -> Result<SmtpReply, MmError<EmailError>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            let read = with_timeout(
+                async { Ok::<_, MmError<EmailError>>(self.stream.read_line(&mut line).await?) },
+                SMTP_IO_TIMEOUT,
+                "reply",
+            )
+            .await?;
+            if read == 0 {
+                return MmError::err(EmailError::ConnectionError(
+                    "Connection closed by the server".to_owned(),
+                ));
+            }
+            let (code, is_last, text) = parse_reply_line(&line)
+                .or_mm_err(|| EmailError::ConnectionError(format!("Invalid SMTP reply '{}'", line.trim_end())))?;
+            lines.push(text.to_owned());
+            if is_last {
+                return Ok(SmtpReply {
+                    code,
+                    text: lines.join("\n"),
+                });
+            }
+        }
+    }
+
+    /// Reads the reply and checks that the reply code is expected.
+    /// `command` is used in the error message only.
+    async fn expect_reply(&mut self, command: &str, expected_code: u16) -> Result<SmtpReply, MmError<EmailError>> {
+        let reply = self.read_reply().await?;
+        if reply.code != expected_code {
+            return MmError::err(EmailError::UnexpectedReply {
+                command: command.to_owned(),
+                reply: format!("{} {}", reply.code, reply.text),
+            });
+        }
+        Ok(reply)
+    }
+
+    async fn command(&mut self, command: &str, expected_code: u16) -> Result<SmtpReply, MmError<EmailError>> {
+        self.send_line(command).await?;
+        // Don't put the credentials into the error messages.
+        let command_name = command.split(' ').next().unwrap_or_default();
+        self.expect_reply(command_name, expected_code).await
+    }
+
+    async fn send_line(&mut self, line: &str) -> Result<(), MmError<EmailError>> {
+        let stream = self.stream.get_mut();
+        let write_fut = async {
+            stream.write_all(line.as_bytes()).await?;
+            stream.write_all(b"\r\n").await?;
+            stream.flush().await?;
+            Ok::<_, MmError<EmailError>>(())
+        };
+        with_timeout(write_fut, SMTP_IO_TIMEOUT, "write").await
+    }
+}
+
+#[derive(Clone)]
+pub struct EmailClient {
+    cfg: EmailCfg,
+}
+
+impl EmailClient {
+    pub fn new(cfg: EmailCfg) -> Self { EmailClient { cfg } }
+
+    fn is_subscribed(&self, room_id: &str) -> bool {
+        match self.cfg.rooms {
+            Some(ref rooms) => rooms.contains(room_id),
+            None => true,
+        }
+    }
+
+    fn port(&self) -> u16 { self.cfg.smtp_port.unwrap_or_else(|| self.cfg.security.default_port()) }
+
+    async fn connect_tls(
+        &self,
+        stream: TcpStream,
+    ) -> Result<tokio_rustls::client::TlsStream<TcpStream>, MmError<EmailError>> {
+        let server_name = ServerName::try_from(self.cfg.smtp_host.as_str())
+            .map_to_mm(|e| EmailError::TlsError(format!("Invalid SMTP host: {}", e)))?;
+        let handshake_fut = async {
+            TLS_CONNECTOR
+                .connect(server_name, stream)
+                .await
+                .map_to_mm(|e| EmailError::TlsError(e.to_string()))
+        };
+        with_timeout(handshake_fut, SMTP_CONNECT_TIMEOUT, "TLS handshake").await
+    }
+
+    async fn send_mail(&self, subject: &str, text: &str) -> Result<(), MmError<EmailError>> {
+        let connect_fut = async {
+            let stream = match global_proxy() {
+                Some(proxy) => socks5_connect(&proxy, &self.cfg.smtp_host, self.port()).await?,
+                None => TcpStream::connect((self.cfg.smtp_host.as_str(), self.port())).await?,
+            };
+            Ok::<_, MmError<EmailError>>(stream)
+        };
+        let stream = with_timeout(connect_fut, SMTP_CONNECT_TIMEOUT, "connection").await?;
+        match self.cfg.security {
+            SmtpSecurity::Tls => {
+                let stream = self.connect_tls(stream).await?;
+                let mut conn = SmtpConnection::new(stream);
+                conn.expect_reply("CONNECT", 220).await?;
+                self.send_mail_session(conn, subject, text).await
+            },
+            SmtpSecurity::StartTls => {
+                let mut conn = SmtpConnection::new(stream);
+                conn.expect_reply("CONNECT", 220).await?;
+                conn.command("EHLO localhost", 250).await?;
+                conn.command("STARTTLS", 220).await?;
+                let stream = self.connect_tls(conn.into_inner()).await?;
+                self.send_mail_session(SmtpConnection::new(stream), subject, text).await
+            },
+            SmtpSecurity::None => {
+                let mut conn = SmtpConnection::new(stream);
+                conn.expect_reply("CONNECT", 220).await?;
+                self.send_mail_session(conn, subject, text).await
+            },
+        }
+    }
+
+    /// Sends the mail via the connection that is greeted already.
+    async fn send_mail_session<S>(
+        &self,
+        mut conn: SmtpConnection<S>,
+        subject: &str,
+        text: &str,
+    ) -> Result<(), MmError<EmailError>>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+    {
+        conn.command("EHLO localhost", 250).await?;
+        if let (Some(username), Some(password)) = (&self.cfg.username, &self.cfg.password) {
+            let credentials = base64::encode(&format!("\0{}\0{}", username, password));
+            conn.command(&format!("AUTH PLAIN {}", credentials), 235).await?;
+        }
+        conn.command(&format!("MAIL FROM:<{}>", self.cfg.from), 250).await?;
+        for recipient in self.cfg.to.iter() {
+            conn.command(&format!("RCPT TO:<{}>", recipient), 250).await?;
+        }
+        conn.command("DATA", 354).await?;
+        let data = self.message_data(subject, text, Utc::now(), &self.new_message_id());
+        conn.send_line(&data).await?;
+        conn.command(".", 250).await?;
+        // The message is accepted already, so the result of `QUIT` doesn't matter.
+        conn.send_line("QUIT").await.ok();
+        Ok(())
+    }
+
+    /// Returns the unique `Message-ID` within the domain of the sender address.
+    fn new_message_id(&self) -> String {
+        let domain = match self.cfg.from.rsplit_once('@') {
+            Some((_, domain)) if !domain.is_empty() => domain,
+            _ => self.cfg.smtp_host.as_str(),
+        };
+        format!("<{}@{}>", Uuid::new_v4(), domain)
+    }
+
+    /// Returns the message headers and the body with the dot-stuffed lines.
+    /// The CR and LF characters are removed from the `subject`, so it can't inject other headers.
+    fn message_data(&self, subject: &str, text: &str, date: DateTime<Utc>, message_id: &str) -> String {
+        let subject: String = subject.chars().filter(|c| *c != '\r' && *c != '\n').collect();
+        let body: Vec<String> = text
+            .lines()
+            .map(|line| {
+                if line.starts_with('.') {
+                    format!(".{}", line)
+                } else {
+                    line.to_owned()
+                }
+            })
+            .collect();
+        format!(
+            "Date: {}\r\nMessage-ID: {}\r\nFrom: <{}>\r\nTo: {}\r\nSubject: {}\r\n\
+             Content-Type: text/plain; charset=utf-8\r\n\r\n{}",
+            date.to_rfc2822(),
+            message_id,
+            self.cfg.from,
+            self.cfg
+                .to
+                .iter()
+                .map(|recipient| format!("<{}>", recipient))
+                .collect::<Vec<_>>()
+                .join(", "),
+            subject,
+            body.join("\r\n")
+        )
+    }
+}
+
+#[async_trait]
+impl MessageServiceTraits for EmailClient {
+    async fn send_message(&self, message: String, room_id: &str, _disable_notification: bool) -> MessageResult<bool> {
+        if !self.is_subscribed(room_id) || self.cfg.to.is_empty() {
+            return Ok(false);
+        }
+        let subject_prefix = self.cfg.subject_prefix.as_deref().unwrap_or(DEFAULT_SUBJECT_PREFIX);
+        let subject = format!("{} {}", subject_prefix, room_id);
+
+        // The SMTP connection is driven by the shared Tokio runtime.
+        let client = self.clone();
+        let result = drive03(async move { client.send_mail(&subject, &message).await })
+            .await
+            .map_to_mm(|_| EmailError::ConnectionError("Spawned SMTP future has been canceled".to_owned()))?;
+        result?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod email_tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn email_cfg() -> EmailCfg {
+        EmailCfg {
+            smtp_host: "smtp.example.com".to_owned(),
+            smtp_port: None,
+            security: SmtpSecurity::default(),
+            username: None,
+            password: None,
+            from: "mm2@example.com".to_owned(),
+            to: vec!["ops@example.com".to_owned(), "dev@example.com".to_owned()],
+            rooms: None,
+            subject_prefix: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_reply_line() {
+        assert_eq!(
+            parse_reply_line("250-smtp.example.com\r\n"),
+            Some((250, false, "smtp.example.com"))
+        );
+        assert_eq!(parse_reply_line("250 STARTTLS\r\n"), Some((250, true, "STARTTLS")));
+        assert_eq!(parse_reply_line("354\r\n"), Some((354, true, "")));
+        assert_eq!(parse_reply_line("25"), None);
+        assert_eq!(parse_reply_line("250+text"), None);
+    }
+
+    #[test]
+    fn test_message_data() {
+        let client = EmailClient::new(email_cfg());
+        assert_eq!(client.port(), 587);
+
+        let date = Utc.ymd(2022, 10, 19).and_hms(4, 5, 6);
+        let message_id = "<id@example.com>";
+        let data = client.message_data("[mm2] swap_failed", "Swap failed\n.hidden line", date, message_id);
+        let expected = "Date: Wed, 19 Oct 2022 04:05:06 +0000\r\nMessage-ID: <id@example.com>\r\n\
+                        From: <mm2@example.com>\r\nTo: <ops@example.com>, <dev@example.com>\r\n\
+                        Subject: [mm2] swap_failed\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n\
+                        Swap failed\r\n..hidden line";
+        assert_eq!(data, expected);
+
+        let data = client.message_data("[mm2] room\r\nBcc: <eve@example.com>", "", date, message_id);
+        assert!(data.contains("\r\nSubject: [mm2] roomBcc: <eve@example.com>\r\n"));
+        assert!(!data.contains("\r\nBcc:"));
+    }
+
+    #[test]
+    fn test_new_message_id() {
+        let client = EmailClient::new(email_cfg());
+        let message_id = client.new_message_id();
+        assert!(message_id.starts_with('<'));
+        assert!(message_id.ends_with("@example.com>"));
+        assert_ne!(message_id, client.new_message_id());
+
+        let mut cfg = email_cfg();
+        cfg.from = "mm2".to_owned();
+        assert!(EmailClient::new(cfg).new_message_id().ends_with("@smtp.example.com>"));
+    }
+
+    #[test]
+    fn test_with_timeout() {
+        let pending = futures::future::pending::<Result<(), MmError<EmailError>>>();
+        let err = common::block_on(with_timeout(pending, 0.1, "reply")).unwrap_err();
+        match err.into_inner() {
+            EmailError::Timeout { stage, .. } => assert_eq!(stage, "reply"),
+            e => panic!("Unexpected error: {}", e),
+        }
+    }
+}
@@ -0,0 +1,120 @@
+use crate::mm2::lp_message_service::{MessageResult, MessageServiceTraits};
+use async_trait::async_trait;
+use common::now_ms;
+use derive_more::Display;
+use http::Method;
+use mm2_err_handle::prelude::*;
+use mm2_net::transport::{slurp_json_with_headers, SlurpError};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Matrix room IDs (e.g. `!abcdef:matrix.org`) by the message service room IDs.
+pub type RoomIdRegistry = HashMap<String, String>;
+
+#[derive(Debug, Deserialize, Display, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum MatrixError {
+    #[display(fmt = "{}", _0)]
+    RequestError(SlurpError),
+    #[display(fmt = "Matrix homeserver responded with {} status: {}", status, body)]
+    UnexpectedStatus { status: u16, body: String },
+}
+
+impl From<SlurpError> for MatrixError {
+    fn from(err: SlurpError) -> Self { MatrixError::RequestError(err) }
+}
+
+pub struct MatrixClient {
+    homeserver_url: String,
+    access_token: String,
+    room_id_registry: RoomIdRegistry,
+    /// Makes the transaction IDs unique if several messages are sent within the same millisecond.
+    txn_counter: AtomicU64,
+}
+
+impl MatrixClient {
+    pub fn new(homeserver_url: String, access_token: String, room_id_registry: RoomIdRegistry) -> Self {
+        MatrixClient {
+            homeserver_url: homeserver_url.trim_end_matches('/').to_owned(),
+            access_token,
+            room_id_registry,
+            txn_counter: AtomicU64::new(0),
+        }
+    }
+
+    fn send_message_url(&self, matrix_room_id: &str) -> String {
+        let txn_id = format!("mm2-{}-{}", now_ms(), self.txn_counter.fetch_add(1, Ordering::Relaxed));
+        format!(
+            "{}/_matrix/client/v3/rooms/{}/send/m.room.message/{}",
+            self.homeserver_url,
+            encode_path_segment(matrix_room_id),
+            txn_id
+        )
+    }
+}
+
+/// Percent-encodes everything except the unreserved characters of RFC 3986.
+fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => (byte as char).to_string(),
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+#[async_trait]
+impl MessageServiceTraits for MatrixClient {
+    async fn send_message(&self, message: String, room_id: &str, disable_notification: bool) -> MessageResult<bool> {
+        let matrix_room_id = match self.room_id_registry.get(room_id) {
+            Some(matrix_room_id) => matrix_room_id,
+            None => return Ok(false),
+        };
+
+        // The default push rules of Matrix don't notify about `m.notice` messages.
+        let msgtype = if disable_notification { "m.notice" } else { "m.text" };
+        let body = json!({ "msgtype": msgtype, "body": message }).to_string();
+        let authorization = format!("Bearer {}", self.access_token);
+        let headers = [("Authorization", authorization.as_str())];
+
+        let url = self.send_message_url(matrix_room_id);
+        let (status, _, response) = slurp_json_with_headers(Method::PUT, &url, body, &headers)
+            .await
+            .map_err(|e| MatrixError::RequestError(e.into_inner()))?;
+        if !status.is_success() {
+            let error = MatrixError::UnexpectedStatus {
+                status: status.as_u16(),
+                body: String::from_utf8_lossy(&response).into_owned(),
+            };
+            return MmError::err(error.into());
+        }
+        Ok(true)
+    }
+}
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod matrix_tests {
+    use super::*;
+    use common::block_on;
+    use std::env::var;
+
+    #[test]
+    fn test_send_message_url() {
+        let client = MatrixClient::new("https://matrix.org/".to_owned(), String::new(), HashMap::new());
+        let url = client.send_message_url("!abcdef:matrix.org");
+        assert!(url
+            .starts_with("https://matrix.org/_matrix/client/v3/rooms/%21abcdef%3Amatrix.org/send/m.room.message/mm2-"));
+        assert_ne!(url, client.send_message_url("!abcdef:matrix.org"));
+    }
+
+    #[test]
+    fn test_send_message() {
+        if let (Ok(access_token), Ok(room_id)) = (var("MATRIX_ACCESS_TOKEN"), var("MATRIX_ROOM_ID")) {
+            let room_id_registry = vec![("RustTestRoomId".to_string(), room_id)].into_iter().collect();
+            let client = MatrixClient::new("https://matrix.org".to_owned(), access_token, room_id_registry);
+            let resp = block_on(client.send_message("Hello from rust".to_string(), "RustTestRoomId", true)).unwrap();
+            assert!(resp);
+        }
+    }
+}
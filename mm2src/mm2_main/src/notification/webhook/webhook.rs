@@ -0,0 +1,236 @@
+use crate::mm2::lp_message_service::{MessageResult, MessageServiceTraits};
+use async_trait::async_trait;
+use common::executor::Timer;
+use common::log::warn;
+use common::now_ms;
+use derive_more::Display;
+use hmac::{Hmac, Mac, NewMac};
+use http::{Method, StatusCode};
+use mm2_err_handle::prelude::*;
+use mm2_net::transport::{slurp_json_with_headers, SlurpError};
+use serde_json::Value as Json;
+use sha2::Sha256;
+use std::collections::HashSet;
+
+/// Unix timestamp in seconds the payload was signed at.
+pub const TIMESTAMP_HEADER: &str = "X-MM-Timestamp";
+/// `sha256=<hex>` HMAC-SHA256 of the `<timestamp>.<body>` string.
+/// The timestamp is included to allow the receiver to reject the replayed requests.
+pub const SIGNATURE_HEADER: &str = "X-MM-Signature";
+const DEFAULT_MAX_RETRIES: u32 = 3;
+/// The delay before the first retry in seconds. The delay is doubled after every failed attempt.
+const RETRY_INITIAL_DELAY: f64 = 1.;
+/// PagerDuty limits the summary of an event to 1024 characters.
+const PAGER_DUTY_MAX_SUMMARY_LEN: usize = 1024;
+
+#[derive(Debug, Deserialize, Display, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum WebhookError {
+    #[display(fmt = "{}", _0)]
+    RequestError(SlurpError),
+    #[display(fmt = "Webhook responded with {} status: {}", status, body)]
+    UnexpectedStatus { status: u16, body: String },
+}
+
+impl From<SlurpError> for WebhookError {
+    fn from(err: SlurpError) -> Self { WebhookError::RequestError(err) }
+}
+
+/// The format of the payloads sent to the webhook.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum WebhookFormat {
+    /// `{"room_id": "...", "message": "...", "disable_notification": false, "timestamp": 1650000000}`
+    Generic,
+    /// The payload of the Slack incoming webhooks.
+    Slack,
+    /// The event of the PagerDuty Events API v2. Every message triggers an alert.
+    PagerDuty {
+        routing_key: String,
+        #[serde(default = "default_pager_duty_severity")]
+        severity: String,
+    },
+}
+
+impl Default for WebhookFormat {
+    fn default() -> Self { WebhookFormat::Generic }
+}
+
+fn default_pager_duty_severity() -> String { "warning".to_owned() }
+
+fn default_max_retries() -> u32 { DEFAULT_MAX_RETRIES }
+
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct WebhookCfg {
+    pub url: String,
+    /// The payloads are signed by HMAC-SHA256 if the secret is set. See [`SIGNATURE_HEADER`].
+    #[serde(default)]
+    pub secret: Option<String>,
+    #[serde(default)]
+    pub format: WebhookFormat,
+    /// The rooms the webhook is subscribed to. The webhook receives the messages of all rooms if it's not set.
+    #[serde(default)]
+    pub rooms: Option<HashSet<String>>,
+    /// How many times the request is retried if it fails with a transport error, 429 or 5xx status.
+    #[serde(default = "default_max_retries")]
+    pub max_retries: u32,
+}
+
+#[derive(Clone)]
+pub struct WebhookClient {
+    cfg: WebhookCfg,
+}
+
+impl WebhookClient {
+    pub fn new(cfg: WebhookCfg) -> Self { WebhookClient { cfg } }
+
+    fn is_subscribed(&self, room_id: &str) -> bool {
+        match self.cfg.rooms {
+            Some(ref rooms) => rooms.contains(room_id),
+            None => true,
+        }
+    }
+
+    fn payload(&self, message: &str, room_id: &str, disable_notification: bool, timestamp: u64) -> Json {
+        match self.cfg.format {
+            WebhookFormat::Generic => json!({
+                "room_id": room_id,
+                "message": message,
+                "disable_notification": disable_notification,
+                "timestamp": timestamp,
+            }),
+            WebhookFormat::Slack => json!({ "text": message }),
+            WebhookFormat::PagerDuty {
+                ref routing_key,
+                ref severity,
+            } => json!({
+                "routing_key": routing_key,
+                "event_action": "trigger",
+                "payload": {
+                    "summary": message.chars().take(PAGER_DUTY_MAX_SUMMARY_LEN).collect::<String>(),
+                    "source": "mm2",
+                    "severity": severity,
+                    "custom_details": { "room_id": room_id, "message": message },
+                },
+            }),
+        }
+    }
+
+    /// Sends the request once, returns whether the request can be retried in case of an error.
+    async fn post(&self, body: &str, headers: &[(&str, &str)]) -> Result<(), (WebhookError, bool)> {
+        match slurp_json_with_headers(Method::POST, &self.cfg.url, body.to_owned(), headers).await {
+            Ok((status, _, _)) if status.is_success() => Ok(()),
+            Ok((status, _, body)) => {
+                let retry = status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error();
+                let error = WebhookError::UnexpectedStatus {
+                    status: status.as_u16(),
+                    body: String::from_utf8_lossy(&body).into_owned(),
+                };
+                Err((error, retry))
+            },
+            Err(e) => Err((WebhookError::RequestError(e.into_inner()), true)),
+        }
+    }
+}
+
+/// Returns the hex-encoded HMAC-SHA256 of the `<timestamp>.<body>` string.
+pub fn sign_payload(secret: &str, timestamp: u64, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(timestamp.to_string().as_bytes());
+    mac.update(b".");
+    mac.update(body.as_bytes());
+    hex::encode(mac.finalize().into_bytes())
+}
+
+#[async_trait]
+impl MessageServiceTraits for WebhookClient {
+    async fn send_message(&self, message: String, room_id: &str, disable_notification: bool) -> MessageResult<bool> {
+        if !self.is_subscribed(room_id) {
+            return Ok(false);
+        }
+
+        let timestamp = now_ms() / 1000;
+        let body = self
+            .payload(&message, room_id, disable_notification, timestamp)
+            .to_string();
+        let timestamp_str = timestamp.to_string();
+        let signature = self
+            .cfg
+            .secret
+            .as_ref()
+            .map(|secret| format!("sha256={}", sign_payload(secret, timestamp, &body)));
+        let mut headers = vec![(TIMESTAMP_HEADER, timestamp_str.as_str())];
+        if let Some(ref signature) = signature {
+            headers.push((SIGNATURE_HEADER, signature.as_str()));
+        }
+
+        let mut delay = RETRY_INITIAL_DELAY;
+        let mut attempt = 0;
+        loop {
+            let (error, retry) = match self.post(&body, &headers).await {
+                Ok(()) => return Ok(true),
+                Err(e) => e,
+            };
+            if !retry || attempt >= self.cfg.max_retries {
+                return MmError::err(error.into());
+            }
+            attempt += 1;
+            warn!(
+                "Webhook '{}' request failed: {}. Retry {}/{} in {}s",
+                self.cfg.url, error, attempt, self.cfg.max_retries, delay
+            );
+            Timer::sleep(delay).await;
+            delay *= 2.;
+        }
+    }
+}
+
+#[cfg(test)]
+mod webhook_tests {
+    use super::*;
+    use serde_json as json;
+
+    fn webhook_cfg(format: WebhookFormat, rooms: Option<HashSet<String>>) -> WebhookCfg {
+        WebhookCfg {
+            url: "http://127.0.0.1:1".to_owned(),
+            secret: None,
+            format,
+            rooms,
+            max_retries: 0,
+        }
+    }
+
+    #[test]
+    fn test_sign_payload() {
+        // echo -n '1650000000.{"text":"hello"}' | openssl dgst -sha256 -hmac "secret"
+        let signature = sign_payload("secret", 1650000000, r#"{"text":"hello"}"#);
+        assert_eq!(
+            signature,
+            "d1c35df61bfc5004475be15d588577aa08e4de0287f7bbd3260f6d5eeddbf6ba"
+        );
+    }
+
+    #[test]
+    fn test_webhook_rooms() {
+        let client = WebhookClient::new(webhook_cfg(WebhookFormat::Slack, None));
+        assert!(client.is_subscribed("swap_failed"));
+
+        let rooms = vec!["swap_failed".to_owned()].into_iter().collect();
+        let client = WebhookClient::new(webhook_cfg(WebhookFormat::Slack, Some(rooms)));
+        assert!(client.is_subscribed("swap_failed"));
+        assert!(!client.is_subscribed("maker_bot"));
+    }
+
+    #[test]
+    fn test_webhook_payload_formats() {
+        let client = WebhookClient::new(webhook_cfg(WebhookFormat::Slack, None));
+        assert_eq!(client.payload("hello", "default", false, 1), json!({ "text": "hello" }));
+
+        let format: WebhookFormat = json::from_value(json!({ "type": "pager_duty", "routing_key": "key" })).unwrap();
+        let client = WebhookClient::new(webhook_cfg(format, None));
+        let payload = client.payload("hello", "swap_failed", false, 1);
+        assert_eq!(payload["routing_key"], "key");
+        assert_eq!(payload["payload"]["severity"], "warning");
+        assert_eq!(payload["payload"]["custom_details"]["room_id"], "swap_failed");
+    }
+}
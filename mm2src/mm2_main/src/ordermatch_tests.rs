@@ -1617,6 +1617,87 @@ fn test_choose_taker_confs_settings_sell_action() {
     assert_eq!(settings.maker_coin_confs, 2);
 }
 
+#[test]
+fn test_check_reserved_swap_policies() {
+    let ctx = MmCtxBuilder::default()
+        .with_conf(json!({
+            "coins": [
+                {
+                    "coin": "MAKER",
+                    "swap_policy": {
+                        "tiers": [
+                            { "min_amount": "100", "required_confirmations": 5, "requires_notarization": true, "locktime_multiplier": 2 }
+                        ]
+                    }
+                },
+                { "coin": "TAKER" }
+            ]
+        }))
+        .into_mm_arc();
+    let maker_coin = TestCoin::new("MAKER").into();
+    let taker_coin = TestCoin::new("TAKER").into();
+    let prices = SwapPolicyPrices::default();
+
+    let taker_conf_settings = OrderConfirmationsSettings {
+        base_confs: 1,
+        base_nota: false,
+        rel_confs: 1,
+        rel_nota: false,
+    };
+    let taker_order = TakerOrderBuilder::new(&maker_coin, &taker_coin)
+        .with_conf_settings(taker_conf_settings)
+        .build_unchecked();
+    let mut maker_reserved = MakerReserved {
+        base_amount: 10.into(),
+        rel_amount: 10.into(),
+        conf_settings: Some(taker_conf_settings),
+        ..Default::default()
+    };
+    // 10 MAKER don't reach the policy tier
+    check_reserved_swap_policies(
+        &ctx,
+        &taker_order.request,
+        &maker_reserved,
+        &maker_coin,
+        &taker_coin,
+        &prices,
+    )
+    .unwrap();
+
+    // the reserved 100 MAKER reach the tier requiring more confirmations than the requested ones
+    maker_reserved.base_amount = 100.into();
+    let err = check_reserved_swap_policies(
+        &ctx,
+        &taker_order.request,
+        &maker_reserved,
+        &maker_coin,
+        &taker_coin,
+        &prices,
+    )
+    .unwrap_err();
+    assert!(err.contains("required by the swap policy"), "{}", err);
+
+    let taker_conf_settings = OrderConfirmationsSettings {
+        base_confs: 5,
+        base_nota: true,
+        rel_confs: 1,
+        rel_nota: false,
+    };
+    let taker_order = TakerOrderBuilder::new(&maker_coin, &taker_coin)
+        .with_conf_settings(taker_conf_settings)
+        .build_unchecked();
+    // the confirmations are sufficient, the locktime multiplier is negotiated within the swap
+    check_reserved_swap_policies(
+        &ctx,
+        &taker_order.request,
+        &maker_reserved,
+        &maker_coin,
+        &taker_coin,
+        &prices,
+    )
+    .unwrap();
+}
+
 fn make_ctx_for_tests() -> (MmArc, String, [u8; 32]) {
     let ctx = MmArc::new(MmCtx::default());
     ctx.init_metrics().unwrap();
@@ -3275,3 +3356,24 @@ fn test_maker_order_balance_loops() {
     assert!(!maker_orders_ctx.balance_loop_exists(morty_ticker));
     assert_eq!(*maker_orders_ctx.count_by_tickers.get(morty_ticker).unwrap(), 0);
 }
+
+#[test]
+fn test_ordermatch_rpc_error_from_maker_order_build_error() {
+    let err = OrdermatchRpcError::from_maker_order_build_error(
+        MakerOrderBuildError::RelVolTooLow {
+            actual: MmNumber::from("0.0001"),
+            threshold: MmNumber::from("0.001"),
+        },
+        "RICK",
+        "MORTY",
+    );
+    match err {
+        OrdermatchRpcError::VolumeTooLow { ref coin, .. } => assert_eq!(coin, "MORTY"),
+        _ => panic!("Expected 'VolumeTooLow'"),
+    }
+    assert_eq!(err.status_code(), StatusCode::BAD_REQUEST);
+
+    let err = OrdermatchRpcError::from_maker_order_build_error(MakerOrderBuildError::BaseEqualRel, "RICK", "RICK");
+    let actual = json::to_value(&err).unwrap();
+    assert_eq!(actual, json!({ "error_type": "BaseEqualRel" }));
+}
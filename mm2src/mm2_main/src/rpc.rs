@@ -20,13 +20,13 @@
 //  Copyright © 2022 AtomicDEX. All rights reserved.
 //
 
-use crate::mm2::rpc::rate_limiter::RateLimitError;
+use crate::mm2::rpc::rate_limiter::{RateLimitClient, RateLimitError};
 #[cfg(not(target_arch = "wasm32"))] use common::log::warn;
 use common::log::{error, info};
 use common::{err_to_rpc_json_string, err_tp_rpc_json, HttpStatusCode};
 use derive_more::Display;
 use futures::future::{join_all, FutureExt};
-use http::header::{HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN};
+use http::header::{HeaderValue, ACCESS_CONTROL_ALLOW_ORIGIN, RETRY_AFTER};
 use http::request::Parts;
 use http::{Method, Request, Response, StatusCode};
 #[cfg(not(target_arch = "wasm32"))]
@@ -39,15 +39,19 @@ use regex::Regex;
 use serde::Serialize;
 use serde_json::{self as json, Value as Json};
 use std::borrow::Cow;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+#[cfg(not(target_arch = "wasm32"))] use std::path::PathBuf;
+#[cfg(not(target_arch = "wasm32"))] use std::sync::Arc;
 
+#[path = "rpc/api_keys.rs"] pub mod api_keys;
 #[path = "rpc/dispatcher/dispatcher.rs"] mod dispatcher;
 #[path = "rpc/dispatcher/dispatcher_legacy.rs"]
 mod dispatcher_legacy;
 #[path = "rpc/lp_commands/lp_commands.rs"] pub mod lp_commands;
 #[path = "rpc/lp_commands/lp_commands_legacy.rs"]
 pub mod lp_commands_legacy;
-#[path = "rpc/rate_limiter.rs"] mod rate_limiter;
+#[path = "rpc/rate_limiter.rs"] pub mod rate_limiter;
+#[path = "rpc/schema.rs"] pub mod schema;
 
 /// Lists the RPC method not requiring the "userpass" authentication.  
 /// None is also public to skip auth and display proper error in case of method is missing
@@ -90,6 +94,14 @@ pub enum DispatcherError {
     UserpassIsInvalid(RateLimitError),
     #[display(fmt = "Error parsing mmrpc version: {}", _0)]
     InvalidMmRpcVersion(String),
+    #[display(fmt = "API key is not allowed to call '{}'", _0)]
+    MethodIsNotAllowed(String),
+    #[display(fmt = "API key is not allowed to be used from {}", _0)]
+    IpIsNotAllowed(IpAddr),
+    #[display(fmt = "Too many requests, retry after {} seconds", retry_after_secs)]
+    TooManyRequests { retry_after_secs: u64 },
+    #[display(fmt = "Internal error: {}", _0)]
+    Internal(String),
 }
 
 impl HttpStatusCode for DispatcherError {
@@ -101,7 +113,11 @@ impl HttpStatusCode for DispatcherError {
             DispatcherError::LocalHostOnly
             | DispatcherError::UserpassIsNotSet
             | DispatcherError::UserpassIsInvalid(_)
+            | DispatcherError::MethodIsNotAllowed(_)
+            | DispatcherError::IpIsNotAllowed(_)
             | DispatcherError::Banned => StatusCode::FORBIDDEN,
+            DispatcherError::TooManyRequests { .. } => StatusCode::TOO_MANY_REQUESTS,
+            DispatcherError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
         }
     }
 }
@@ -110,6 +126,40 @@ impl From<serde_json::Error> for DispatcherError {
     fn from(e: serde_json::Error) -> Self { DispatcherError::InvalidRequest(e.to_string()) }
 }
 
+/// The sender of an RPC request.
+#[derive(Clone, Copy, Debug)]
+pub enum RpcClient {
+    /// The client connected to the HTTP server from the address.
+    Remote(SocketAddr),
+    /// The host of MM2 sending the requests directly, via the `mm2_rpc` C ABI or the WASM RPC channel.
+    InProcess,
+}
+
+impl RpcClient {
+    /// Returns the IP address of the remote client or `None` for the in-process requests.
+    pub fn ip(&self) -> Option<IpAddr> {
+        match self {
+            RpcClient::Remote(addr) => Some(addr.ip()),
+            RpcClient::InProcess => None,
+        }
+    }
+
+    fn is_local(&self) -> bool {
+        match self {
+            RpcClient::Remote(addr) => addr.ip().is_loopback(),
+            RpcClient::InProcess => true,
+        }
+    }
+
+    /// Returns the client limited by the `rpc_rate_limit` if the request isn't authorized by an API key.
+    fn rate_limit_client(&self) -> RateLimitClient {
+        match self {
+            RpcClient::Remote(addr) => RateLimitClient::Ip(addr.ip()),
+            RpcClient::InProcess => RateLimitClient::InProcess,
+        }
+    }
+}
+
 #[allow(unused_macros)]
 macro_rules! unwrap_or_err_response {
     ($e:expr, $($args:tt)*) => {
@@ -120,7 +170,7 @@ macro_rules! unwrap_or_err_response {
     };
 }
 
-async fn process_json_batch_requests(ctx: MmArc, requests: &[Json], client: SocketAddr) -> Result<Json, String> {
+async fn process_json_batch_requests(ctx: MmArc, requests: &[Json], client: RpcClient) -> Result<Json, String> {
     let mut futures = Vec::with_capacity(requests.len());
     for request in requests {
         futures.push(process_single_request(ctx.clone(), request.clone(), client));
@@ -143,7 +193,7 @@ async fn process_json_batch_requests(ctx: MmArc, requests: &[Json], client: Sock
 }
 
 #[cfg(target_arch = "wasm32")]
-async fn process_json_request(ctx: MmArc, req_json: Json, client: SocketAddr) -> Result<Json, String> {
+async fn process_json_request(ctx: MmArc, req_json: Json, client: RpcClient) -> Result<Json, String> {
     if let Some(requests) = req_json.as_array() {
         return process_json_batch_requests(ctx, &requests, client)
             .await
@@ -155,7 +205,7 @@ async fn process_json_request(ctx: MmArc, req_json: Json, client: SocketAddr) ->
 }
 
 #[cfg(not(target_arch = "wasm32"))]
-async fn process_json_request(ctx: MmArc, req_json: Json, client: SocketAddr) -> Result<Response<Vec<u8>>, String> {
+async fn process_json_request(ctx: MmArc, req_json: Json, client: RpcClient) -> Result<Response<Vec<u8>>, String> {
     if let Some(requests) = req_json.as_array() {
         let response = try_s!(process_json_batch_requests(ctx, requests, client).await);
         let res = try_s!(json::to_vec(&response));
@@ -165,14 +215,33 @@ async fn process_json_request(ctx: MmArc, req_json: Json, client: SocketAddr) ->
     process_single_request(ctx, req_json, client).await
 }
 
+/// Processes the request sent by the library host via the `mm2_rpc` C ABI, bypassing the HTTP server.
+/// The request is authorized by the `userpass` like the HTTP requests from the loopback interface,
+/// but it's rate limited as [`RateLimitClient::InProcess`] and the failed authentication attempts never ban it.
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(dead_code)] // Used by the native library only.
+pub async fn process_ffi_request(ctx: MmArc, req_json: Json) -> Result<Response<Vec<u8>>, String> {
+    process_json_request(ctx, req_json, RpcClient::InProcess).await
+}
+
 fn response_from_dispatcher_error(
     error: MmError<DispatcherError>,
     version: MmRpcVersion,
     id: Option<usize>,
 ) -> Response<Vec<u8>> {
     error!("RPC dispatcher error: {}", error);
+    let retry_after_secs = match error.get_inner() {
+        DispatcherError::TooManyRequests { retry_after_secs } => Some(*retry_after_secs),
+        _ => None,
+    };
     let response: MmRpcResponse<(), _> = MmRpcBuilder::err(error).version(version).id(id).build();
-    response.serialize_http_response()
+    let mut response = response.serialize_http_response();
+    if let Some(retry_after_secs) = retry_after_secs {
+        response
+            .headers_mut()
+            .insert(RETRY_AFTER, HeaderValue::from(retry_after_secs));
+    }
+    response
 }
 
 pub fn escape_answer<'a, S: Into<Cow<'a, str>>>(input: S) -> Cow<'a, str> {
@@ -204,7 +273,7 @@ pub fn escape_answer<'a, S: Into<Cow<'a, str>>>(input: S) -> Cow<'a, str> {
     }
 }
 
-async fn process_single_request(ctx: MmArc, req: Json, client: SocketAddr) -> Result<Response<Vec<u8>>, String> {
+async fn process_single_request(ctx: MmArc, req: Json, client: RpcClient) -> Result<Response<Vec<u8>>, String> {
     let local_only = ctx.conf["rpc_local_only"].as_bool().unwrap_or(true);
     if req["mmrpc"].is_null() {
         return dispatcher_legacy::process_single_request(ctx, req, client, local_only)
@@ -259,7 +328,7 @@ async fn rpc_service(req: Request<Body>, ctx_h: u32, client: SocketAddr) -> Resp
             return ERR!("Only POST requests are supported!");
         }
 
-        process_json_request(ctx, req_json, client).await
+        process_json_request(ctx, req_json, RpcClient::Remote(client)).await
     }
 
     let ctx = try_sf!(MmArc::from_ffi_handle(ctx_h));
@@ -302,12 +371,118 @@ async fn rpc_service(req: Request<Body>, ctx_h: u32, client: SocketAddr) -> Resp
     Response::from_parts(parts, Body::from(body_escaped))
 }
 
+/// The TLS config of the RPC server.
+/// The client certificate is required if the `client_ca` is set.
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Deserialize)]
+struct RpcTlsConf {
+    server_priv_key: PathBuf,
+    certificate: PathBuf,
+    #[serde(default)]
+    client_ca: Option<PathBuf>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn rpc_tls_config(ctx: &MmArc) -> Result<Option<Arc<tokio_rustls::rustls::ServerConfig>>, String> {
+    use crate::mm2::lp_native_dex::{read_certs, read_priv_key_and_certs};
+    use tokio_rustls::rustls::server::AllowAnyAuthenticatedClient;
+    use tokio_rustls::rustls::{Certificate, PrivateKey, RootCertStore, ServerConfig};
+
+    if ctx.conf["rpc_tls"].is_null() {
+        return Ok(None);
+    }
+    let conf: RpcTlsConf = try_s!(json::from_value(ctx.conf["rpc_tls"].clone()));
+
+    // The certificates are read by the same functions as the P2P `wss_certs`, then converted to the newer rustls types.
+    let (priv_key, certs) = try_s!(read_priv_key_and_certs(conf.server_priv_key, conf.certificate));
+    let priv_key = PrivateKey(priv_key.0);
+    let certs = certs.into_iter().map(|cert| Certificate(cert.0)).collect();
+
+    let builder = ServerConfig::builder().with_safe_defaults();
+    let builder = match conf.client_ca {
+        Some(client_ca) => {
+            let mut roots = RootCertStore::empty();
+            for cert in try_s!(read_certs(client_ca)) {
+                try_s!(roots.add(&Certificate(cert.0)));
+            }
+            builder.with_client_cert_verifier(AllowAnyAuthenticatedClient::new(roots))
+        },
+        None => builder.with_no_client_auth(),
+    };
+    let mut config = try_s!(builder.with_single_cert(certs, priv_key));
+    config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+    Ok(Some(Arc::new(config)))
+}
+
+/// The stream of the TLS connections established by [`accept_tls_connections`].
+#[cfg(not(target_arch = "wasm32"))]
+struct TlsIncoming {
+    connections: futures::channel::mpsc::Receiver<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>,
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+impl hyper::server::accept::Accept for TlsIncoming {
+    type Conn = tokio_rustls::server::TlsStream<tokio::net::TcpStream>;
+    type Error = std::io::Error;
+
+    fn poll_accept(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<Self::Conn, Self::Error>>> {
+        use futures::StreamExt;
+
+        self.get_mut().connections.poll_next_unpin(cx).map(|conn| conn.map(Ok))
+    }
+}
+
+/// The connection is dropped if the client doesn't complete the TLS handshake within this time.
+#[cfg(not(target_arch = "wasm32"))]
+const RPC_TLS_HANDSHAKE_TIMEOUT: f64 = 10.;
+
+/// Accepts the TCP connections and performs the TLS handshakes concurrently,
+/// so a client that doesn't complete the handshake doesn't block the others.
+/// The handshake that takes longer than `handshake_timeout` seconds is aborted.
+/// Stops once the [`TlsIncoming`] is dropped.
+#[cfg(not(target_arch = "wasm32"))]
+async fn accept_tls_connections(
+    listener: tokio::net::TcpListener,
+    acceptor: tokio_rustls::TlsAcceptor,
+    connections: futures::channel::mpsc::Sender<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>,
+    handshake_timeout: f64,
+) {
+    use common::custom_futures::FutureTimerExt;
+    use common::wio::CORE;
+    use futures::SinkExt;
+
+    while !connections.is_closed() {
+        let (stream, remote_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(e) => {
+                error!("Error accepting RPC connection: {}", e);
+                continue;
+            },
+        };
+        let acceptor = acceptor.clone();
+        let mut connections = connections.clone();
+        CORE.0.spawn(async move {
+            match Box::pin(acceptor.accept(stream)).timeout_secs(handshake_timeout).await {
+                Ok(Ok(tls_stream)) => {
+                    connections.send(tls_stream).await.ok();
+                },
+                Ok(Err(e)) => warn!("RPC TLS handshake with {} failed: {}", remote_addr, e),
+                Err(_) => warn!("RPC TLS handshake with {} timed out", remote_addr),
+            }
+        });
+    }
+}
+
 #[cfg(not(target_arch = "wasm32"))]
 pub extern "C" fn spawn_rpc(ctx_h: u32) {
     use common::wio::CORE;
     use hyper::server::conn::AddrStream;
     use hyper::service::{make_service_fn, service_fn};
     use std::convert::Infallible;
+    use tokio_rustls::server::TlsStream;
 
     // NB: We need to manually handle the incoming connections in order to get the remote IP address,
     // cf. https://github.com/hyperium/hyper/issues/1410#issuecomment-419510220.
@@ -317,21 +492,23 @@ pub extern "C" fn spawn_rpc(ctx_h: u32) {
 
     let ctx = MmArc::from_ffi_handle(ctx_h).expect("No context");
 
+    // The library host can talk to MM2 via the `mm2_rpc` C ABI only, without exposing a port to the other local apps.
+    if ctx.conf["rpc_disable_http"].as_bool().unwrap_or(false) {
+        log_tag!(
+            ctx,
+            "😉";
+            fmt = ">>>>>>>>>> DEX stats API enabled without HTTP listener at unixtime.{}  <<<<<<<<<",
+            gstuff::now_ms() / 1000
+        );
+        let _ = ctx.rpc_started.pin(true);
+        return;
+    }
+
     let rpc_ip_port = ctx.rpc_ip_port().unwrap();
+    let tls_config = rpc_tls_config(&ctx).unwrap_or_else(|e| panic!("Error loading 'rpc_tls': {}", e));
     // By entering the context, we tie `tokio::spawn` to this executor.
     let _runtime_guard = CORE.0.enter();
 
-    let server = Server::try_bind(&rpc_ip_port).unwrap_or_else(|_| panic!("Can't bind on {}", rpc_ip_port));
-    let make_svc = make_service_fn(move |socket: &AddrStream| {
-        let remote_addr = socket.remote_addr();
-        async move {
-            Ok::<_, Infallible>(service_fn(move |req: Request<Body>| async move {
-                let res = rpc_service(req, ctx_h, remote_addr).await;
-                Ok::<_, Infallible>(res)
-            }))
-        }
-    });
-
     let (shutdown_tx, shutdown_rx) = futures::channel::oneshot::channel::<()>();
     let mut shutdown_tx = Some(shutdown_tx);
     ctx.on_stop(Box::new(move || {
@@ -345,11 +522,58 @@ pub extern "C" fn spawn_rpc(ctx_h: u32) {
             ERR!("on_stop callback called twice!")
         }
     }));
-
-    let server = server
-        .http1_half_close(false)
-        .serve(make_svc)
-        .with_graceful_shutdown(shutdown_rx.then(|_| futures::future::ready(())));
+    let shutdown_signal = shutdown_rx.then(|_| futures::future::ready(()));
+
+    let server = match tls_config {
+        Some(tls_config) => {
+            let listener = std::net::TcpListener::bind(&rpc_ip_port)
+                .and_then(|listener| listener.set_nonblocking(true).map(|_| listener))
+                .and_then(tokio::net::TcpListener::from_std)
+                .unwrap_or_else(|_| panic!("Can't bind on {}", rpc_ip_port));
+            let (connections_tx, connections_rx) = futures::channel::mpsc::channel(1);
+            CORE.0.spawn(accept_tls_connections(
+                listener,
+                tokio_rustls::TlsAcceptor::from(tls_config),
+                connections_tx,
+                RPC_TLS_HANDSHAKE_TIMEOUT,
+            ));
+
+            let make_svc = make_service_fn(move |conn: &TlsStream<tokio::net::TcpStream>| {
+                let remote_addr = conn.get_ref().0.peer_addr();
+                async move {
+                    let remote_addr = remote_addr?;
+                    Ok::<_, std::io::Error>(service_fn(move |req: Request<Body>| async move {
+                        let res = rpc_service(req, ctx_h, remote_addr).await;
+                        Ok::<_, Infallible>(res)
+                    }))
+                }
+            });
+            Server::builder(TlsIncoming {
+                connections: connections_rx,
+            })
+            .http1_half_close(false)
+            .serve(make_svc)
+            .with_graceful_shutdown(shutdown_signal)
+            .boxed()
+        },
+        None => {
+            let server = Server::try_bind(&rpc_ip_port).unwrap_or_else(|_| panic!("Can't bind on {}", rpc_ip_port));
+            let make_svc = make_service_fn(move |socket: &AddrStream| {
+                let remote_addr = socket.remote_addr();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |req: Request<Body>| async move {
+                        let res = rpc_service(req, ctx_h, remote_addr).await;
+                        Ok::<_, Infallible>(res)
+                    }))
+                }
+            });
+            server
+                .http1_half_close(false)
+                .serve(make_svc)
+                .with_graceful_shutdown(shutdown_signal)
+                .boxed()
+        },
+    };
 
     let server = server.then(|r| {
         if let Err(err) = r {
@@ -385,10 +609,6 @@ pub fn spawn_rpc(ctx_h: u32) {
         return;
     }
 
-    let client: SocketAddr = "127.0.0.1:1"
-        .parse()
-        .expect("'127.0.0.1:1' must be valid socket address");
-
     let (request_tx, mut request_rx) = wasm_rpc::channel();
     let ctx_weak = ctx.weak();
     let fut = async move {
@@ -398,7 +618,7 @@ pub fn spawn_rpc(ctx_h: u32) {
                 None => break,
             };
 
-            let response = process_json_request(ctx, request_json, client).await;
+            let response = process_json_request(ctx, request_json, RpcClient::InProcess).await;
             if let Err(e) = response_tx.send(response) {
                 error!("Response is not processed: {:?}", e);
             }
@@ -423,3 +643,144 @@ pub fn spawn_rpc(ctx_h: u32) {
         common::now_ms() / 1000
     );
 }
+
+#[cfg(all(test, not(target_arch = "wasm32")))]
+mod tests {
+    use super::*;
+    use crate::mm2::lp_native_dex::read_certs;
+    use common::block_on;
+    use common::custom_futures::FutureTimerExt;
+    use common::wio::CORE;
+    use futures::channel::mpsc;
+    use futures::StreamExt;
+    use mm2_core::mm_ctx::MmCtxBuilder;
+    use std::convert::TryFrom;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio_rustls::rustls::{Certificate, ClientConfig, RootCertStore, ServerName};
+    use tokio_rustls::{TlsAcceptor, TlsConnector};
+
+    /// The server certificate for `localhost` is signed by the `rpc_tls_ca.pem` certificate.
+    const TEST_CERTS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/for_tests");
+
+    fn test_tls_acceptor() -> TlsAcceptor {
+        let conf = json!({
+            "rpc_tls": {
+                "server_priv_key": format!("{}/rpc_tls_server.key", TEST_CERTS_DIR),
+                "certificate": format!("{}/rpc_tls_server.pem", TEST_CERTS_DIR),
+            },
+        });
+        let ctx = MmCtxBuilder::default().with_conf(conf).into_mm_arc();
+        let tls_config = rpc_tls_config(&ctx).unwrap().expect("'rpc_tls' is set");
+        TlsAcceptor::from(tls_config)
+    }
+
+    fn test_tls_connector() -> TlsConnector {
+        let mut roots = RootCertStore::empty();
+        for cert in read_certs(PathBuf::from(format!("{}/rpc_tls_ca.pem", TEST_CERTS_DIR))).unwrap() {
+            roots.add(&Certificate(cert.0)).unwrap();
+        }
+        let config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        TlsConnector::from(Arc::new(config))
+    }
+
+    async fn spawn_tls_listener(
+        handshake_timeout: f64,
+    ) -> (
+        SocketAddr,
+        mpsc::Receiver<tokio_rustls::server::TlsStream<tokio::net::TcpStream>>,
+    ) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let (connections_tx, connections_rx) = mpsc::channel(1);
+        CORE.0.spawn(accept_tls_connections(
+            listener,
+            test_tls_acceptor(),
+            connections_tx,
+            handshake_timeout,
+        ));
+        (addr, connections_rx)
+    }
+
+    #[test]
+    fn test_rpc_tls_handshake() {
+        block_on(async {
+            let (addr, mut connections) = spawn_tls_listener(RPC_TLS_HANDSHAKE_TIMEOUT).await;
+
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            let client_stream = test_tls_connector().connect(server_name, stream).await.unwrap();
+
+            let server_stream = connections.next().timeout_secs(5.).await.unwrap().unwrap();
+            assert_eq!(
+                server_stream.get_ref().0.peer_addr().unwrap(),
+                client_stream.get_ref().0.local_addr().unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_rpc_tls_handshake_timeout() {
+        block_on(async {
+            let (addr, mut connections) = spawn_tls_listener(1.).await;
+
+            // The client connects, but never starts the TLS handshake.
+            let mut silent_stream = TcpStream::connect(addr).await.unwrap();
+
+            // The pending handshake doesn't block the other clients.
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let server_name = ServerName::try_from("localhost").unwrap();
+            test_tls_connector().connect(server_name, stream).await.unwrap();
+            connections.next().timeout_secs(5.).await.unwrap().unwrap();
+
+            // The server closes the connection once the handshake times out.
+            let mut buf = [0; 1];
+            let read = Box::pin(silent_stream.read(&mut buf)).timeout_secs(5.).await.unwrap();
+            assert_eq!(read.unwrap(), 0);
+            // The timed out connection isn't passed to the HTTP server.
+            assert!(connections.next().timeout_secs(0.5).await.is_err());
+        });
+    }
+
+    #[test]
+    fn test_process_ffi_request_is_never_banned() {
+        let conf = json!({
+            "rpc_password": "pass",
+            "rpc_rate_limit": { "failed_auth_limit": 1 },
+        });
+        let ctx = MmCtxBuilder::default().with_conf(conf).into_mm_arc();
+        let invalid_request = json!({
+            "mmrpc": "2.0",
+            "method": "get_rpc_schema",
+            "userpass": "invalid",
+            "params": { "method": "get_rpc_schema" },
+        });
+        let error_type = |response: Response<Vec<u8>>| {
+            assert_eq!(response.status(), StatusCode::FORBIDDEN);
+            let body: Json = json::from_slice(response.body()).unwrap();
+            body["error_type"].as_str().unwrap().to_owned()
+        };
+
+        for _ in 0..2 {
+            let response = block_on(process_ffi_request(ctx.clone(), invalid_request.clone())).unwrap();
+            assert_eq!(error_type(response), "UserpassIsInvalid");
+        }
+
+        // The client connected from the loopback interface is banned after the first attempt.
+        let remote = RpcClient::Remote("127.0.0.1:1".parse().unwrap());
+        let response = block_on(process_json_request(ctx.clone(), invalid_request.clone(), remote)).unwrap();
+        assert_eq!(error_type(response), "UserpassIsInvalid");
+        let response = block_on(process_json_request(ctx.clone(), invalid_request.clone(), remote)).unwrap();
+        assert_eq!(error_type(response), "Banned");
+
+        let mut request = invalid_request;
+        request["userpass"] = "pass".into();
+        let response = block_on(process_ffi_request(ctx, request)).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body: Json = json::from_slice(response.body()).unwrap();
+        assert!(body["result"]["paths"]["/#get_rpc_schema"].is_object());
+    }
+}
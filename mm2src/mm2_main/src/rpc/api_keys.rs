@@ -0,0 +1,521 @@
+//! API keys that can be used as the `userpass` instead of the `rpc_password`.
+//!
+//! Every key has a scope that limits the RPC methods it can call,
+//! an optional list of allowed methods (it can only narrow the scope down) and an optional IP allowlist.
+//! The keys are defined in the `api_keys` config field or created by the `create_api_key` RPC.
+//! Every use of an API key is saved to the audit log that can be requested by the `api_keys_audit_log` RPC.
+
+use crate::mm2::rpc::{DispatcherError, DispatcherResult, RpcClient};
+use bitcrypto::sha256;
+use common::log::{info, warn};
+use common::{now_ms, HttpStatusCode, StatusCode, SuccessResponse};
+use derive_more::Display;
+use futures::lock::Mutex as AsyncMutex;
+use mm2_core::mm_ctx::{from_ctx, MmArc};
+use mm2_err_handle::prelude::*;
+use primitives::hash::H256;
+use rand::Rng;
+use serde_json::{self as json, Value as Json};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+/// The max number of the audit log entries kept in memory.
+const AUDIT_LOG_CAPACITY: usize = 1000;
+const DEFAULT_AUDIT_LOG_LIMIT: usize = 100;
+#[cfg(not(target_arch = "wasm32"))]
+const API_KEYS_FILE_NAME: &str = "api_keys.json";
+
+/// The methods that don't change the node state and don't expose the secrets.
+const READ_ONLY_METHODS: &[&str] = &[
+    // Sorted alphanumerically (on the first letter) for readability.
+    "account_balance",
+    "active_swaps",
+    "all_swaps_uuids_by_filter",
+    "best_orders",
+    "coins_needed_for_kick_start",
+    "get_electrum_servers_status",
+    "get_enabled_coins",
+    "get_gossip_mesh",
+    "get_gossip_peer_topics",
+    "get_gossip_topic_peers",
+    "get_my_peer_id",
+    "get_peers_info",
+    "get_pending_staking_rewards",
+    "get_public_key",
+    "get_public_key_hash",
+    "get_raw_transaction",
+    "get_relay_mesh",
+    "get_rpc_schema",
+    "get_staking_infos",
+    "get_staking_validators",
+    "get_trade_fee",
+    "kmd_rewards_info",
+    "list_banned_pubkeys",
+    "list_rpc_tasks",
+    "max_taker_vol",
+    "metrics",
+    "min_trading_vol",
+    "my_balance",
+    "my_orders",
+    "my_recent_swaps",
+    "my_swap_status",
+    "my_tx_history",
+    "order_status",
+    "orderbook",
+    "orderbook_depth",
+    "orders_history_by_filter",
+    "split_taker_order_status",
+    "trade_preimage",
+    "validateaddress",
+    "version",
+    "z_coin_tx_history",
+];
+
+/// The methods placing, updating and cancelling the orders, in addition to [`READ_ONLY_METHODS`].
+const TRADING_METHODS: &[&str] = &[
+    "buy",
+    "cancel_all_orders",
+    "cancel_order",
+    "recover_funds_of_swap",
+    "sell",
+    "setprice",
+    "start_simple_market_maker_bot",
+    "start_split_taker_order",
+    "stop_simple_market_maker_bot",
+    "update_maker_order",
+];
+
+/// The methods withdrawing the funds, in addition to [`READ_ONLY_METHODS`].
+const WITHDRAW_METHODS: &[&str] = &[
+    "init_withdraw",
+    "send_raw_transaction",
+    "withdraw",
+    "withdraw_cancel",
+    "withdraw_status",
+    "withdraw_user_action",
+];
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, RpcSchema, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiKeyScope {
+    ReadOnly,
+    Trading,
+    Withdraw,
+    /// Allows every method like the `rpc_password` does.
+    Admin,
+}
+
+impl ApiKeyScope {
+    fn allows(&self, method: &str) -> bool {
+        let is_read_only = READ_ONLY_METHODS.contains(&method);
+        match self {
+            ApiKeyScope::ReadOnly => is_read_only,
+            ApiKeyScope::Trading => is_read_only || TRADING_METHODS.contains(&method),
+            ApiKeyScope::Withdraw => is_read_only || WITHDRAW_METHODS.contains(&method),
+            ApiKeyScope::Admin => true,
+        }
+    }
+}
+
+/// An API key defined in the `api_keys` config field.
+#[derive(Deserialize)]
+struct ApiKeyConf {
+    name: String,
+    key: String,
+    scope: ApiKeyScope,
+    #[serde(default)]
+    allowed_methods: Option<Vec<String>>,
+    #[serde(default)]
+    allowed_ips: Option<Vec<IpAddr>>,
+}
+
+/// An API key created by the `create_api_key` RPC.
+/// Only the hash of the key is stored.
+#[derive(Deserialize, Serialize)]
+struct StoredApiKey {
+    name: String,
+    key_hash: String,
+    scope: ApiKeyScope,
+    allowed_methods: Option<Vec<String>>,
+    allowed_ips: Option<Vec<IpAddr>>,
+}
+
+struct ApiKey {
+    key_hash: H256,
+    scope: ApiKeyScope,
+    allowed_methods: Option<HashSet<String>>,
+    allowed_ips: Option<HashSet<IpAddr>>,
+    from_config: bool,
+}
+
+impl ApiKey {
+    /// The `allowed_ips` don't restrict the in-process requests which `client_ip` is `None`.
+    fn check_access(&self, method: &str, client_ip: Option<IpAddr>) -> DispatcherResult<()> {
+        if let (Some(allowed_ips), Some(client_ip)) = (&self.allowed_ips, client_ip) {
+            if !allowed_ips.contains(&client_ip) {
+                return MmError::err(DispatcherError::IpIsNotAllowed(client_ip));
+            }
+        }
+        let method_is_listed = self
+            .allowed_methods
+            .as_ref()
+            .map_or(true, |allowed_methods| allowed_methods.contains(method));
+        if !method_is_listed || !self.scope.allows(method) {
+            return MmError::err(DispatcherError::MethodIsNotAllowed(method.to_owned()));
+        }
+        Ok(())
+    }
+
+    fn to_stored(&self, name: &str) -> StoredApiKey {
+        StoredApiKey {
+            name: name.to_owned(),
+            key_hash: hex::encode(self.key_hash.as_slice()),
+            scope: self.scope,
+            allowed_methods: self
+                .allowed_methods
+                .as_ref()
+                .map(|methods| methods.iter().cloned().collect()),
+            allowed_ips: self.allowed_ips.as_ref().map(|ips| ips.iter().copied().collect()),
+        }
+    }
+}
+
+#[derive(Clone, RpcSchema, Serialize)]
+pub struct AuditLogEntry {
+    /// Unix timestamp in milliseconds.
+    timestamp: u64,
+    key_name: String,
+    method: String,
+    /// `None` if the method is called in-process, via the `mm2_rpc` C ABI or the WASM RPC channel.
+    client_ip: Option<IpAddr>,
+    allowed: bool,
+}
+
+pub struct ApiKeysContext {
+    /// API keys by their names.
+    keys: AsyncMutex<HashMap<String, ApiKey>>,
+    audit_log: AsyncMutex<VecDeque<AuditLogEntry>>,
+}
+
+impl ApiKeysContext {
+    /// Obtains a reference to this mod context, creating it if necessary.
+    /// The keys are loaded from the config and from the keys file on the first call.
+    pub fn from_ctx(ctx: &MmArc) -> Result<Arc<ApiKeysContext>, String> {
+        Ok(try_s!(from_ctx(&ctx.api_keys_ctx, move || {
+            let mut keys = HashMap::new();
+            for stored in try_s!(load_stored_keys(ctx)) {
+                let key_hash = try_s!(hex::decode(&stored.key_hash));
+                if key_hash.len() != H256::size() {
+                    return ERR!("Invalid '{}' API key hash length", stored.name);
+                }
+                keys.insert(stored.name, ApiKey {
+                    key_hash: H256::from(key_hash.as_slice()),
+                    scope: stored.scope,
+                    allowed_methods: stored.allowed_methods.map(|methods| methods.into_iter().collect()),
+                    allowed_ips: stored.allowed_ips.map(|ips| ips.into_iter().collect()),
+                    from_config: false,
+                });
+            }
+
+            let keys_conf: Vec<ApiKeyConf> = if ctx.conf["api_keys"].is_null() {
+                Vec::new()
+            } else {
+                try_s!(json::from_value(ctx.conf["api_keys"].clone()))
+            };
+            for conf in keys_conf {
+                if keys.contains_key(&conf.name) {
+                    return ERR!("API key name '{}' is used more than once", conf.name);
+                }
+                keys.insert(conf.name, ApiKey {
+                    key_hash: sha256(conf.key.as_bytes()),
+                    scope: conf.scope,
+                    allowed_methods: conf.allowed_methods.map(|methods| methods.into_iter().collect()),
+                    allowed_ips: conf.allowed_ips.map(|ips| ips.into_iter().collect()),
+                    from_config: true,
+                });
+            }
+
+            Ok(ApiKeysContext {
+                keys: AsyncMutex::new(keys),
+                audit_log: AsyncMutex::new(VecDeque::with_capacity(AUDIT_LOG_CAPACITY)),
+            })
+        })))
+    }
+
+    /// Checks if the `userpass` is a known API key that is allowed to call the `method` by the `client`.
+    /// Returns the API key name on success or `None` if there is no such API key.
+    pub async fn authorize(
+        &self,
+        userpass: &str,
+        method: &str,
+        client: &RpcClient,
+    ) -> Option<DispatcherResult<String>> {
+        let key_hash = sha256(userpass.as_bytes());
+        let keys = self.keys.lock().await;
+        let (key_name, key) = keys.iter().find(|(_, key)| key.key_hash == key_hash)?;
+        let result = key.check_access(method, client.ip());
+
+        let entry = AuditLogEntry {
+            timestamp: now_ms(),
+            key_name: key_name.clone(),
+            method: method.to_owned(),
+            client_ip: client.ip(),
+            allowed: result.is_ok(),
+        };
+        drop(keys);
+        let result = result.map(|_| entry.key_name.clone());
+        self.add_audit_log_entry(entry).await;
+        Some(result)
+    }
+
+    async fn add_audit_log_entry(&self, entry: AuditLogEntry) {
+        let client = entry
+            .client_ip
+            .map_or_else(|| "the host process".to_owned(), |ip| ip.to_string());
+        if entry.allowed {
+            info!("API key '{}' called '{}' from {}", entry.key_name, entry.method, client);
+        } else {
+            warn!(
+                "API key '{}' is not allowed to call '{}' from {}",
+                entry.key_name, entry.method, client
+            );
+        }
+
+        let mut audit_log = self.audit_log.lock().await;
+        if audit_log.len() >= AUDIT_LOG_CAPACITY {
+            audit_log.pop_front();
+        }
+        audit_log.push_back(entry);
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+fn load_stored_keys(ctx: &MmArc) -> Result<Vec<StoredApiKey>, String> {
+    let path = ctx.dbdir().join(API_KEYS_FILE_NAME);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = try_s!(std::fs::read(&path));
+    Ok(try_s!(json::from_slice(&content)))
+}
+
+/// The keys created by the RPC are not persisted in the browser.
+#[cfg(target_arch = "wasm32")]
+fn load_stored_keys(_ctx: &MmArc) -> Result<Vec<StoredApiKey>, String> { Ok(Vec::new()) }
+
+#[cfg(not(target_arch = "wasm32"))]
+fn save_stored_keys(ctx: &MmArc, keys: &HashMap<String, ApiKey>) -> Result<(), String> {
+    let stored: Vec<_> = keys
+        .iter()
+        .filter(|(_, key)| !key.from_config)
+        .map(|(name, key)| key.to_stored(name))
+        .collect();
+    let content = try_s!(json::to_vec(&stored));
+    try_s!(std::fs::write(ctx.dbdir().join(API_KEYS_FILE_NAME), content));
+    Ok(())
+}
+
+#[cfg(target_arch = "wasm32")]
+fn save_stored_keys(_ctx: &MmArc, _keys: &HashMap<String, ApiKey>) -> Result<(), String> { Ok(()) }
+
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum ApiKeyRpcError {
+    #[display(fmt = "API key '{}' already exists", _0)]
+    NameIsAlreadyUsed(String),
+    #[display(fmt = "No such API key '{}'", _0)]
+    NoSuchApiKey(String),
+    #[display(fmt = "API key '{}' is defined in the config and can't be revoked", _0)]
+    CannotRevokeConfigKey(String),
+    Internal(String),
+}
+
+impl HttpStatusCode for ApiKeyRpcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ApiKeyRpcError::NameIsAlreadyUsed(_)
+            | ApiKeyRpcError::NoSuchApiKey(_)
+            | ApiKeyRpcError::CannotRevokeConfigKey(_) => StatusCode::BAD_REQUEST,
+            ApiKeyRpcError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct CreateApiKeyRequest {
+    name: String,
+    scope: ApiKeyScope,
+    #[serde(default)]
+    allowed_methods: Option<Vec<String>>,
+    #[serde(default)]
+    allowed_ips: Option<Vec<IpAddr>>,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct CreateApiKeyResponse {
+    name: String,
+    /// The key is returned only once and it can't be requested later.
+    key: String,
+}
+
+pub async fn create_api_key(ctx: MmArc, req: CreateApiKeyRequest) -> MmResult<CreateApiKeyResponse, ApiKeyRpcError> {
+    let api_keys_ctx = ApiKeysContext::from_ctx(&ctx).map_to_mm(ApiKeyRpcError::Internal)?;
+    let mut keys = api_keys_ctx.keys.lock().await;
+    if keys.contains_key(&req.name) {
+        return MmError::err(ApiKeyRpcError::NameIsAlreadyUsed(req.name));
+    }
+
+    let key = hex::encode(rand::thread_rng().gen::<[u8; 32]>());
+    keys.insert(req.name.clone(), ApiKey {
+        key_hash: sha256(key.as_bytes()),
+        scope: req.scope,
+        allowed_methods: req.allowed_methods.map(|methods| methods.into_iter().collect()),
+        allowed_ips: req.allowed_ips.map(|ips| ips.into_iter().collect()),
+        from_config: false,
+    });
+    save_stored_keys(&ctx, &keys).map_to_mm(ApiKeyRpcError::Internal)?;
+    Ok(CreateApiKeyResponse { name: req.name, key })
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct ApiKeyInfo {
+    name: String,
+    scope: ApiKeyScope,
+    allowed_methods: Option<Vec<String>>,
+    allowed_ips: Option<Vec<IpAddr>>,
+    from_config: bool,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct ListApiKeysResponse {
+    keys: Vec<ApiKeyInfo>,
+}
+
+pub async fn list_api_keys(ctx: MmArc, _req: Json) -> MmResult<ListApiKeysResponse, ApiKeyRpcError> {
+    let api_keys_ctx = ApiKeysContext::from_ctx(&ctx).map_to_mm(ApiKeyRpcError::Internal)?;
+    let keys = api_keys_ctx.keys.lock().await;
+    let mut keys: Vec<_> = keys
+        .iter()
+        .map(|(name, key)| {
+            let stored = key.to_stored(name);
+            ApiKeyInfo {
+                name: stored.name,
+                scope: stored.scope,
+                allowed_methods: stored.allowed_methods,
+                allowed_ips: stored.allowed_ips,
+                from_config: key.from_config,
+            }
+        })
+        .collect();
+    keys.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(ListApiKeysResponse { keys })
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct RevokeApiKeyRequest {
+    name: String,
+}
+
+pub async fn revoke_api_key(ctx: MmArc, req: RevokeApiKeyRequest) -> MmResult<SuccessResponse, ApiKeyRpcError> {
+    let api_keys_ctx = ApiKeysContext::from_ctx(&ctx).map_to_mm(ApiKeyRpcError::Internal)?;
+    let mut keys = api_keys_ctx.keys.lock().await;
+    match keys.get(&req.name) {
+        Some(key) if key.from_config => return MmError::err(ApiKeyRpcError::CannotRevokeConfigKey(req.name)),
+        Some(_) => (),
+        None => return MmError::err(ApiKeyRpcError::NoSuchApiKey(req.name)),
+    }
+    keys.remove(&req.name);
+    save_stored_keys(&ctx, &keys).map_to_mm(ApiKeyRpcError::Internal)?;
+    Ok(SuccessResponse::new())
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct ApiKeysAuditLogRequest {
+    #[serde(default)]
+    key_name: Option<String>,
+    #[serde(default = "default_audit_log_limit")]
+    limit: usize,
+}
+
+fn default_audit_log_limit() -> usize { DEFAULT_AUDIT_LOG_LIMIT }
+
+#[derive(RpcSchema, Serialize)]
+pub struct ApiKeysAuditLogResponse {
+    /// The most recent entries go first.
+    entries: Vec<AuditLogEntry>,
+}
+
+pub async fn api_keys_audit_log(
+    ctx: MmArc,
+    req: ApiKeysAuditLogRequest,
+) -> MmResult<ApiKeysAuditLogResponse, ApiKeyRpcError> {
+    let api_keys_ctx = ApiKeysContext::from_ctx(&ctx).map_to_mm(ApiKeyRpcError::Internal)?;
+    let audit_log = api_keys_ctx.audit_log.lock().await;
+    let entries = audit_log
+        .iter()
+        .rev()
+        .filter(|entry| req.key_name.as_ref().map_or(true, |name| &entry.key_name == name))
+        .take(req.limit)
+        .cloned()
+        .collect();
+    Ok(ApiKeysAuditLogResponse { entries })
+}
+
+#[cfg(test)]
+mod api_keys_tests {
+    use super::*;
+
+    fn api_key(scope: ApiKeyScope) -> ApiKey {
+        ApiKey {
+            key_hash: sha256(b"key"),
+            scope,
+            allowed_methods: None,
+            allowed_ips: None,
+            from_config: true,
+        }
+    }
+
+    #[test]
+    fn test_scope_allows() {
+        assert!(ApiKeyScope::ReadOnly.allows("my_balance"));
+        assert!(!ApiKeyScope::ReadOnly.allows("withdraw"));
+        assert!(!ApiKeyScope::ReadOnly.allows("withdraw_status"));
+        assert!(!ApiKeyScope::ReadOnly.allows("sell"));
+
+        assert!(ApiKeyScope::Trading.allows("sell"));
+        assert!(!ApiKeyScope::Trading.allows("withdraw"));
+
+        assert!(ApiKeyScope::Withdraw.allows("withdraw"));
+        assert!(!ApiKeyScope::Withdraw.allows("setprice"));
+
+        assert!(ApiKeyScope::Admin.allows("show_priv_key"));
+        assert!(!ApiKeyScope::Withdraw.allows("show_priv_key"));
+    }
+
+    #[test]
+    fn test_check_access() {
+        let localhost: IpAddr = "127.0.0.1".parse().unwrap();
+        let remote: IpAddr = "10.0.0.1".parse().unwrap();
+
+        let mut key = api_key(ApiKeyScope::Trading);
+        key.check_access("sell", Some(remote)).unwrap();
+
+        // allowed methods can't extend the scope
+        key.allowed_methods = Some(
+            vec!["my_balance".to_owned(), "withdraw".to_owned()]
+                .into_iter()
+                .collect(),
+        );
+        key.check_access("my_balance", Some(remote)).unwrap();
+        assert!(key.check_access("sell", Some(remote)).is_err());
+        assert!(key.check_access("withdraw", Some(remote)).is_err());
+
+        key.allowed_ips = Some(vec![localhost].into_iter().collect());
+        key.check_access("my_balance", Some(localhost)).unwrap();
+        assert!(key.check_access("my_balance", Some(remote)).is_err());
+        // the in-process requests don't have an IP address
+        key.check_access("my_balance", None).unwrap();
+        assert!(key.check_access("sell", None).is_err());
+    }
+}
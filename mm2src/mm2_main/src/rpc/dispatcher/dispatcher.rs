@@ -1,29 +1,38 @@
-use super::{DispatcherError, DispatcherResult, PUBLIC_METHODS};
-use crate::mm2::lp_native_dex::init_hw::{init_trezor, init_trezor_status, init_trezor_user_action};
-use crate::mm2::lp_ordermatch::{best_orders_rpc_v2, orderbook_rpc_v2, start_simple_market_maker_bot,
-                                stop_simple_market_maker_bot};
-use crate::mm2::rpc::rate_limiter::{process_rate_limit, RateLimitContext};
+use super::{DispatcherError, DispatcherResult, RpcClient, PUBLIC_METHODS};
+use crate::mm2::lp_native_dex::init_hw::{init_trezor, init_trezor_cancel, init_trezor_status, init_trezor_user_action};
+use crate::mm2::lp_ordermatch::{best_orders_rpc_v2, buy_rpc_v2, cancel_order, my_orders_rpc_v2, order_status_rpc_v2,
+                                orderbook_rpc_v2, sell_rpc_v2, set_price_rpc_v2, split_taker_order_status,
+                                start_simple_market_maker_bot, start_split_taker_order, stop_simple_market_maker_bot,
+                                update_maker_order_rpc_v2};
+use crate::mm2::rpc::api_keys::{api_keys_audit_log, create_api_key, list_api_keys, revoke_api_key, ApiKeysContext};
+use crate::mm2::rpc::rate_limiter::{list_rate_limited_clients, process_rate_limit, unban_rate_limited_clients,
+                                    RateLimitClient, RateLimitContext};
+use crate::mm2::rpc::schema::{get_rpc_schema, RpcSchemaRegistry};
 use crate::{mm2::lp_stats::{add_node_to_version_stat, remove_node_from_version_stat, start_version_stat_collection,
                             stop_version_stat_collection, update_version_stat_collection},
-            mm2::lp_swap::{recreate_swap_data, trade_preimage_rpc},
-            mm2::rpc::lp_commands::{get_public_key, get_public_key_hash}};
+            mm2::lp_swap::{active_swaps_rpc_v2, my_recent_swaps_rpc_v2, my_swap_status_rpc_v2,
+                           recover_funds_of_swap_rpc_v2, recreate_swap_data, simulate_swap_rpc, trade_preimage_rpc},
+            mm2::rpc::lp_commands::{get_public_key, get_public_key_hash, list_rpc_tasks}};
 use coins::hd_wallet::get_new_address;
 use coins::my_tx_history_v2::my_tx_history_v2_rpc;
 use coins::rpc_command::account_balance::account_balance;
-use coins::rpc_command::init_create_account::{init_create_new_account, init_create_new_account_status,
-                                              init_create_new_account_user_action};
-use coins::rpc_command::init_scan_for_new_addresses::{init_scan_for_new_addresses, init_scan_for_new_addresses_status};
-use coins::rpc_command::init_withdraw::{init_withdraw, withdraw_status, withdraw_user_action};
+use coins::rpc_command::get_electrum_servers_status::get_electrum_servers_status;
+use coins::rpc_command::init_create_account::{init_create_new_account, init_create_new_account_cancel,
+                                              init_create_new_account_status, init_create_new_account_user_action};
+use coins::rpc_command::init_scan_for_new_addresses::{init_scan_for_new_addresses, init_scan_for_new_addresses_cancel,
+                                                      init_scan_for_new_addresses_status};
+use coins::rpc_command::init_withdraw::{init_withdraw, withdraw_cancel, withdraw_status, withdraw_user_action};
+use coins::tendermint::{TendermintCoin, TendermintToken};
 use coins::utxo::bch::BchCoin;
 use coins::utxo::qtum::QtumCoin;
 use coins::utxo::slp::SlpToken;
 use coins::utxo::utxo_standard::UtxoStandardCoin;
-use coins::{add_delegation, get_raw_transaction, get_staking_infos, remove_delegation, sign_message, verify_message,
-            withdraw};
+use coins::{add_delegation, claim_staking_rewards, get_pending_staking_rewards, get_raw_transaction,
+            get_staking_infos, get_staking_validators, remove_delegation, sign_message, verify_message, withdraw};
 #[cfg(all(not(target_os = "ios"), not(target_os = "android"), not(target_arch = "wasm32")))]
 use coins::{SolanaCoin, SplToken};
 use coins_activation::{enable_l2, enable_platform_coin_with_tokens, enable_token, init_standalone_coin,
-                       init_standalone_coin_status, init_standalone_coin_user_action};
+                       init_standalone_coin_cancel, init_standalone_coin_status, init_standalone_coin_user_action};
 use common::log::{error, warn};
 use common::HttpStatusCode;
 use futures::Future as Future03;
@@ -33,7 +42,6 @@ use mm2_err_handle::prelude::*;
 use mm2_rpc::mm_protocol::{MmRpcBuilder, MmRpcRequest, MmRpcVersion};
 use serde::de::DeserializeOwned;
 use serde_json::{self as json, Value as Json};
-use std::net::SocketAddr;
 
 cfg_native! {
     use coins::lightning::{close_channel, connect_to_lightning_node, generate_invoice, get_channel_details,
@@ -45,23 +53,29 @@ cfg_native! {
 pub async fn process_single_request(
     ctx: MmArc,
     req: Json,
-    client: SocketAddr,
+    client: RpcClient,
     local_only: bool,
 ) -> DispatcherResult<Response<Vec<u8>>> {
     let request: MmRpcRequest = json::from_value(req)?;
 
     // https://github.com/artemii235/SuperNET/issues/368
     let method_name = Some(request.method.as_str());
-    if local_only && !client.ip().is_loopback() && !PUBLIC_METHODS.contains(&method_name) {
+    if local_only && !client.is_local() && !PUBLIC_METHODS.contains(&method_name) {
         return MmError::err(DispatcherError::LocalHostOnly);
     }
 
-    let rate_limit_ctx = RateLimitContext::from_ctx(&ctx).unwrap();
-    if rate_limit_ctx.is_banned(client.ip()).await {
+    let rate_limit_ctx = RateLimitContext::from_ctx(&ctx).map_to_mm(DispatcherError::Internal)?;
+    if rate_limit_ctx.is_banned(&client).await {
         return MmError::err(DispatcherError::Banned);
     }
 
-    auth(&request, &ctx, &client).await?;
+    let rate_limit_client = match auth(&request, &ctx, &client).await? {
+        Some(api_key_name) => RateLimitClient::ApiKey(api_key_name),
+        None => client.rate_limit_client(),
+    };
+    rate_limit_ctx
+        .check_request_rate(rate_limit_client, &request.method)
+        .await?;
     match request.mmrpc {
         MmRpcVersion::V2 => dispatcher_v2(request, ctx).await,
     }
@@ -102,9 +116,10 @@ where
     Ok(response.serialize_http_response())
 }
 
-async fn auth(request: &MmRpcRequest, ctx: &MmArc, client: &SocketAddr) -> DispatcherResult<()> {
+/// Returns the API key name if the request is authorized by an API key.
+async fn auth(request: &MmRpcRequest, ctx: &MmArc, client: &RpcClient) -> DispatcherResult<Option<String>> {
     if PUBLIC_METHODS.contains(&Some(request.method.as_str())) {
-        return Ok(());
+        return Ok(None);
     }
 
     let rpc_password = ctx.conf["rpc_password"].as_str().unwrap_or_else(|| {
@@ -112,85 +127,176 @@ async fn auth(request: &MmRpcRequest, ctx: &MmArc, client: &SocketAddr) -> Dispa
         ""
     });
     match request.userpass {
-        Some(ref userpass) if userpass == rpc_password => Ok(()),
-        Some(_) => Err(process_rate_limit(ctx, client).await),
+        Some(ref userpass) if userpass == rpc_password => Ok(None),
+        Some(ref userpass) => {
+            let api_keys_ctx = ApiKeysContext::from_ctx(ctx).map_to_mm(DispatcherError::Internal)?;
+            match api_keys_ctx.authorize(userpass, &request.method, client).await {
+                Some(result) => result.map(Some),
+                None => Err(process_rate_limit(ctx, client).await),
+            }
+        },
         None => MmError::err(DispatcherError::UserpassIsNotSet),
     }
 }
 
-async fn dispatcher_v2(request: MmRpcRequest, ctx: MmArc) -> DispatcherResult<Response<Vec<u8>>> {
-    match request.method.as_str() {
-        "account_balance" => handle_mmrpc(ctx, request, account_balance).await,
-        "add_delegation" => handle_mmrpc(ctx, request, add_delegation).await,
-        "add_node_to_version_stat" => handle_mmrpc(ctx, request, add_node_to_version_stat).await,
-        "best_orders" => handle_mmrpc(ctx, request, best_orders_rpc_v2).await,
-        "enable_bch_with_tokens" => handle_mmrpc(ctx, request, enable_platform_coin_with_tokens::<BchCoin>).await,
-        "enable_slp" => handle_mmrpc(ctx, request, enable_token::<SlpToken>).await,
-        "get_new_address" => handle_mmrpc(ctx, request, get_new_address).await,
-        "get_public_key" => handle_mmrpc(ctx, request, get_public_key).await,
-        "get_public_key_hash" => handle_mmrpc(ctx, request, get_public_key_hash).await,
-        "get_raw_transaction" => handle_mmrpc(ctx, request, get_raw_transaction).await,
-        "get_staking_infos" => handle_mmrpc(ctx, request, get_staking_infos).await,
-        "init_create_new_account" => handle_mmrpc(ctx, request, init_create_new_account).await,
-        "init_create_new_account_status" => handle_mmrpc(ctx, request, init_create_new_account_status).await,
-        "init_create_new_account_user_action" => handle_mmrpc(ctx, request, init_create_new_account_user_action).await,
-        "init_qtum" => handle_mmrpc(ctx, request, init_standalone_coin::<QtumCoin>).await,
-        "init_qtum_status" => handle_mmrpc(ctx, request, init_standalone_coin_status::<QtumCoin>).await,
-        "init_qtum_user_action" => handle_mmrpc(ctx, request, init_standalone_coin_user_action::<QtumCoin>).await,
-        "init_scan_for_new_addresses" => handle_mmrpc(ctx, request, init_scan_for_new_addresses).await,
-        "init_scan_for_new_addresses_status" => handle_mmrpc(ctx, request, init_scan_for_new_addresses_status).await,
-        "init_trezor" => handle_mmrpc(ctx, request, init_trezor).await,
-        "init_trezor_status" => handle_mmrpc(ctx, request, init_trezor_status).await,
-        "init_trezor_user_action" => handle_mmrpc(ctx, request, init_trezor_user_action).await,
-        "init_utxo" => handle_mmrpc(ctx, request, init_standalone_coin::<UtxoStandardCoin>).await,
-        "init_utxo_status" => handle_mmrpc(ctx, request, init_standalone_coin_status::<UtxoStandardCoin>).await,
-        "init_utxo_user_action" => {
-            handle_mmrpc(ctx, request, init_standalone_coin_user_action::<UtxoStandardCoin>).await
-        },
-        "init_withdraw" => handle_mmrpc(ctx, request, init_withdraw).await,
-        "my_tx_history" => handle_mmrpc(ctx, request, my_tx_history_v2_rpc).await,
-        "orderbook" => handle_mmrpc(ctx, request, orderbook_rpc_v2).await,
-        "recreate_swap_data" => handle_mmrpc(ctx, request, recreate_swap_data).await,
-        "remove_delegation" => handle_mmrpc(ctx, request, remove_delegation).await,
-        "remove_node_from_version_stat" => handle_mmrpc(ctx, request, remove_node_from_version_stat).await,
-        "sign_message" => handle_mmrpc(ctx, request, sign_message).await,
-        "start_simple_market_maker_bot" => handle_mmrpc(ctx, request, start_simple_market_maker_bot).await,
-        "start_version_stat_collection" => handle_mmrpc(ctx, request, start_version_stat_collection).await,
-        "stop_simple_market_maker_bot" => handle_mmrpc(ctx, request, stop_simple_market_maker_bot).await,
-        "stop_version_stat_collection" => handle_mmrpc(ctx, request, stop_version_stat_collection).await,
-        "trade_preimage" => handle_mmrpc(ctx, request, trade_preimage_rpc).await,
-        "update_version_stat_collection" => handle_mmrpc(ctx, request, update_version_stat_collection).await,
-        "verify_message" => handle_mmrpc(ctx, request, verify_message).await,
-        "withdraw" => handle_mmrpc(ctx, request, withdraw).await,
-        "withdraw_status" => handle_mmrpc(ctx, request, withdraw_status).await,
-        "withdraw_user_action" => handle_mmrpc(ctx, request, withdraw_user_action).await,
-        #[cfg(not(target_arch = "wasm32"))]
-        native_only_methods => match native_only_methods {
-            "close_channel" => handle_mmrpc(ctx, request, close_channel).await,
-            "connect_to_lightning_node" => handle_mmrpc(ctx, request, connect_to_lightning_node).await,
-            "enable_lightning" => handle_mmrpc(ctx, request, enable_l2::<LightningCoin>).await,
-            "generate_invoice" => handle_mmrpc(ctx, request, generate_invoice).await,
-            "get_channel_details" => handle_mmrpc(ctx, request, get_channel_details).await,
-            "get_claimable_balances" => handle_mmrpc(ctx, request, get_claimable_balances).await,
-            "get_payment_details" => handle_mmrpc(ctx, request, get_payment_details).await,
-            "init_z_coin" => handle_mmrpc(ctx, request, init_standalone_coin::<ZCoin>).await,
-            "init_z_coin_status" => handle_mmrpc(ctx, request, init_standalone_coin_status::<ZCoin>).await,
-            "init_z_coin_user_action" => handle_mmrpc(ctx, request, init_standalone_coin_user_action::<ZCoin>).await,
-            "list_closed_channels_by_filter" => handle_mmrpc(ctx, request, list_closed_channels_by_filter).await,
-            "list_open_channels_by_filter" => handle_mmrpc(ctx, request, list_open_channels_by_filter).await,
-            "list_payments_by_filter" => handle_mmrpc(ctx, request, list_payments_by_filter).await,
-            "open_channel" => handle_mmrpc(ctx, request, open_channel).await,
-            "send_payment" => handle_mmrpc(ctx, request, send_payment).await,
-            #[cfg(all(not(target_os = "ios"), not(target_os = "android")))]
-            "enable_solana_with_tokens" => {
-                handle_mmrpc(ctx, request, enable_platform_coin_with_tokens::<SolanaCoin>).await
-            },
-            #[cfg(all(not(target_os = "ios"), not(target_os = "android")))]
-            "enable_spl" => handle_mmrpc(ctx, request, enable_token::<SplToken>).await,
-            "z_coin_tx_history" => handle_mmrpc(ctx, request, coins::my_tx_history_v2::z_coin_tx_history_rpc).await,
-            _ => MmError::err(DispatcherError::NoSuchMethod),
-        },
-        #[cfg(target_arch = "wasm32")]
-        _ => MmError::err(DispatcherError::NoSuchMethod),
+/// Defines [`dispatcher_v2`] and [`rpc_schema_registry`] from the same table of the methods,
+/// so the registry always describes the methods the dispatcher handles.
+///
+/// Every method is `"name" => typed(handler)` if the request, response and error types of the handler
+/// implement `RpcSchema`, or `"name" => untyped(handler)` otherwise.
+/// The untyped methods are still listed in the OpenAPI document, but their types may be any JSON values.
+macro_rules! dispatcher_v2_methods {
+    (
+        common { $($(#[$common_meta:meta])* $common_method:literal => $common_kind:ident($common_handler:expr),)* }
+        native_only { $($(#[$native_meta:meta])* $native_method:literal => $native_kind:ident($native_handler:expr),)* }
+    ) => {
+        async fn dispatcher_v2(request: MmRpcRequest, ctx: MmArc) -> DispatcherResult<Response<Vec<u8>>> {
+            match request.method.as_str() {
+                $(
+                    $(#[$common_meta])*
+                    $common_method => handle_mmrpc(ctx, request, $common_handler).await,
+                )*
+                #[cfg(not(target_arch = "wasm32"))]
+                native_only_methods => match native_only_methods {
+                    $(
+                        $(#[$native_meta])*
+                        $native_method => handle_mmrpc(ctx, request, $native_handler).await,
+                    )*
+                    _ => MmError::err(DispatcherError::NoSuchMethod),
+                },
+                #[cfg(target_arch = "wasm32")]
+                _ => MmError::err(DispatcherError::NoSuchMethod),
+            }
+        }
+
+        /// Describes the methods of [`dispatcher_v2`].
+        pub(crate) fn rpc_schema_registry() -> RpcSchemaRegistry {
+            let mut registry = RpcSchemaRegistry::default();
+            $(
+                $(#[$common_meta])*
+                register_method!(registry, $common_kind, $common_method, $common_handler);
+            )*
+            #[cfg(not(target_arch = "wasm32"))]
+            {
+                $(
+                    $(#[$native_meta])*
+                    register_method!(registry, $native_kind, $native_method, $native_handler);
+                )*
+            }
+            registry
+        }
+    };
+}
+
+macro_rules! register_method {
+    ($registry:ident, typed, $method:literal, $handler:expr) => {
+        $registry.register($method, $handler)
+    };
+    ($registry:ident, untyped, $method:literal, $handler:expr) => {
+        $registry.register_untyped($method)
+    };
+}
+
+dispatcher_v2_methods! {
+    common {
+        "account_balance" => untyped(account_balance),
+        "active_swaps" => typed(active_swaps_rpc_v2),
+        "add_delegation" => untyped(add_delegation),
+        "add_node_to_version_stat" => typed(add_node_to_version_stat),
+        "api_keys_audit_log" => typed(api_keys_audit_log),
+        "best_orders" => typed(best_orders_rpc_v2),
+        "buy" => typed(buy_rpc_v2),
+        "cancel_order" => typed(cancel_order),
+        "claim_staking_rewards" => untyped(claim_staking_rewards),
+        "create_api_key" => typed(create_api_key),
+        "enable_bch_with_tokens" => untyped(enable_platform_coin_with_tokens::<BchCoin>),
+        "enable_slp" => untyped(enable_token::<SlpToken>),
+        "enable_tendermint_token" => untyped(enable_token::<TendermintToken>),
+        "enable_tendermint_with_assets" => untyped(enable_platform_coin_with_tokens::<TendermintCoin>),
+        "get_electrum_servers_status" => untyped(get_electrum_servers_status),
+        "get_new_address" => untyped(get_new_address),
+        "get_pending_staking_rewards" => untyped(get_pending_staking_rewards),
+        "get_public_key" => typed(get_public_key),
+        "get_public_key_hash" => typed(get_public_key_hash),
+        "get_raw_transaction" => untyped(get_raw_transaction),
+        "get_rpc_schema" => typed(get_rpc_schema),
+        "get_staking_infos" => untyped(get_staking_infos),
+        "get_staking_validators" => untyped(get_staking_validators),
+        "init_create_new_account" => untyped(init_create_new_account),
+        "init_create_new_account_cancel" => typed(init_create_new_account_cancel),
+        "init_create_new_account_status" => untyped(init_create_new_account_status),
+        "init_create_new_account_user_action" => untyped(init_create_new_account_user_action),
+        "init_qtum" => untyped(init_standalone_coin::<QtumCoin>),
+        "init_qtum_cancel" => typed(init_standalone_coin_cancel::<QtumCoin>),
+        "init_qtum_status" => untyped(init_standalone_coin_status::<QtumCoin>),
+        "init_qtum_user_action" => untyped(init_standalone_coin_user_action::<QtumCoin>),
+        "init_scan_for_new_addresses" => untyped(init_scan_for_new_addresses),
+        "init_scan_for_new_addresses_cancel" => typed(init_scan_for_new_addresses_cancel),
+        "init_scan_for_new_addresses_status" => untyped(init_scan_for_new_addresses_status),
+        "init_trezor" => typed(init_trezor),
+        "init_trezor_cancel" => typed(init_trezor_cancel),
+        "init_trezor_status" => untyped(init_trezor_status),
+        "init_trezor_user_action" => untyped(init_trezor_user_action),
+        "init_utxo" => untyped(init_standalone_coin::<UtxoStandardCoin>),
+        "init_utxo_cancel" => typed(init_standalone_coin_cancel::<UtxoStandardCoin>),
+        "init_utxo_status" => untyped(init_standalone_coin_status::<UtxoStandardCoin>),
+        "init_utxo_user_action" => untyped(init_standalone_coin_user_action::<UtxoStandardCoin>),
+        "init_withdraw" => untyped(init_withdraw),
+        "list_api_keys" => typed(list_api_keys),
+        "list_rate_limited_clients" => typed(list_rate_limited_clients),
+        "list_rpc_tasks" => typed(list_rpc_tasks),
+        "my_orders" => typed(my_orders_rpc_v2),
+        "my_recent_swaps" => typed(my_recent_swaps_rpc_v2),
+        "my_swap_status" => typed(my_swap_status_rpc_v2),
+        "my_tx_history" => untyped(my_tx_history_v2_rpc),
+        "order_status" => typed(order_status_rpc_v2),
+        "orderbook" => typed(orderbook_rpc_v2),
+        "recover_funds_of_swap" => typed(recover_funds_of_swap_rpc_v2),
+        "recreate_swap_data" => typed(recreate_swap_data),
+        "remove_delegation" => untyped(remove_delegation),
+        "remove_node_from_version_stat" => typed(remove_node_from_version_stat),
+        "revoke_api_key" => typed(revoke_api_key),
+        "sell" => typed(sell_rpc_v2),
+        "setprice" => typed(set_price_rpc_v2),
+        "sign_message" => untyped(sign_message),
+        "simulate_swap" => typed(simulate_swap_rpc),
+        "split_taker_order_status" => typed(split_taker_order_status),
+        "start_simple_market_maker_bot" => typed(start_simple_market_maker_bot),
+        "start_split_taker_order" => typed(start_split_taker_order),
+        "start_version_stat_collection" => typed(start_version_stat_collection),
+        "stop_simple_market_maker_bot" => typed(stop_simple_market_maker_bot),
+        "stop_version_stat_collection" => typed(stop_version_stat_collection),
+        "trade_preimage" => typed(trade_preimage_rpc),
+        "unban_rate_limited_clients" => typed(unban_rate_limited_clients),
+        "update_maker_order" => typed(update_maker_order_rpc_v2),
+        "update_version_stat_collection" => typed(update_version_stat_collection),
+        "verify_message" => untyped(verify_message),
+        "withdraw" => untyped(withdraw),
+        "withdraw_cancel" => typed(withdraw_cancel),
+        "withdraw_status" => untyped(withdraw_status),
+        "withdraw_user_action" => untyped(withdraw_user_action),
+    }
+    native_only {
+        "close_channel" => untyped(close_channel),
+        "connect_to_lightning_node" => untyped(connect_to_lightning_node),
+        "enable_lightning" => untyped(enable_l2::<LightningCoin>),
+        "generate_invoice" => untyped(generate_invoice),
+        "get_channel_details" => untyped(get_channel_details),
+        "get_claimable_balances" => untyped(get_claimable_balances),
+        "get_payment_details" => untyped(get_payment_details),
+        "init_z_coin" => untyped(init_standalone_coin::<ZCoin>),
+        "init_z_coin_cancel" => typed(init_standalone_coin_cancel::<ZCoin>),
+        "init_z_coin_status" => untyped(init_standalone_coin_status::<ZCoin>),
+        "init_z_coin_user_action" => untyped(init_standalone_coin_user_action::<ZCoin>),
+        "list_closed_channels_by_filter" => untyped(list_closed_channels_by_filter),
+        "list_open_channels_by_filter" => untyped(list_open_channels_by_filter),
+        "list_payments_by_filter" => untyped(list_payments_by_filter),
+        "open_channel" => untyped(open_channel),
+        "send_payment" => untyped(send_payment),
+        #[cfg(all(not(target_os = "ios"), not(target_os = "android")))]
+        "enable_solana_with_tokens" => untyped(enable_platform_coin_with_tokens::<SolanaCoin>),
+        #[cfg(all(not(target_os = "ios"), not(target_os = "android")))]
+        "enable_spl" => untyped(enable_token::<SplToken>),
+        "z_coin_tx_history" => untyped(coins::my_tx_history_v2::z_coin_tx_history_rpc),
     }
 }
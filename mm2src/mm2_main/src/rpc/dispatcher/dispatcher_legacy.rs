@@ -1,11 +1,11 @@
-use super::PUBLIC_METHODS;
-use common::HyRes;
+use super::{DispatcherError, RpcClient, PUBLIC_METHODS};
+use common::{err_to_rpc_json_string, HttpStatusCode, HyRes};
 use futures::compat::Future01CompatExt;
 use futures::{Future as Future03, FutureExt, TryFutureExt};
+use http::header::RETRY_AFTER;
 use http::Response;
 use mm2_core::mm_ctx::MmArc;
 use serde_json::{self as json, Value as Json};
-use std::net::SocketAddr;
 
 use super::lp_commands_legacy::*;
 use crate::mm2::lp_ordermatch::{best_orders_rpc, buy, cancel_all_orders_rpc, cancel_order_rpc, my_orders,
@@ -14,7 +14,8 @@ use crate::mm2::lp_ordermatch::{best_orders_rpc, buy, cancel_all_orders_rpc, can
 use crate::mm2::lp_swap::{active_swaps_rpc, all_swaps_uuids_by_filter, ban_pubkey_rpc, coins_needed_for_kick_start,
                           import_swaps, list_banned_pubkeys_rpc, max_taker_vol, my_recent_swaps_rpc, my_swap_status,
                           recover_funds_of_swap, stats_swap_status, unban_pubkeys_rpc};
-use crate::mm2::rpc::rate_limiter::{process_rate_limit, RateLimitContext};
+use crate::mm2::rpc::api_keys::ApiKeysContext;
+use crate::mm2::rpc::rate_limiter::{process_rate_limit, RateLimitClient, RateLimitContext};
 use coins::{convert_address, convert_utxo_address, get_enabled_coins, get_trade_fee, kmd_rewards_info, my_tx_history,
             send_raw_transaction, set_required_confirmations, set_requires_notarization, show_priv_key,
             validate_address};
@@ -27,17 +28,24 @@ pub enum DispatcherRes {
     NoMatch(Json),
 }
 
-async fn auth(json: &Json, ctx: &MmArc, client: &SocketAddr) -> Result<(), String> {
+/// Returns the API key name if the request is authorized by an API key.
+async fn auth(json: &Json, ctx: &MmArc, client: &RpcClient) -> Result<Option<String>, String> {
     if !PUBLIC_METHODS.contains(&json["method"].as_str()) {
         if !json["userpass"].is_string() {
             return Err("Userpass is not set!".to_string());
         }
 
         if json["userpass"] != ctx.conf["rpc_password"] {
-            return Err(format!("{}", process_rate_limit(ctx, client).await));
+            let userpass = json["userpass"].as_str().unwrap_or_default();
+            let method = json["method"].as_str().unwrap_or_default();
+            let api_keys_ctx = try_s!(ApiKeysContext::from_ctx(ctx));
+            return match api_keys_ctx.authorize(userpass, method, client).await {
+                Some(result) => result.map(Some).map_err(|e| e.to_string()),
+                None => Err(format!("{}", process_rate_limit(ctx, client).await)),
+            };
         }
     }
-    Ok(())
+    Ok(None)
 }
 
 /// Using async/await (futures 0.3) in `dispatcher`
@@ -121,18 +129,25 @@ pub fn dispatcher(req: Json, ctx: MmArc) -> DispatcherRes {
 pub async fn process_single_request(
     ctx: MmArc,
     req: Json,
-    client: SocketAddr,
+    client: RpcClient,
     local_only: bool,
 ) -> Result<Response<Vec<u8>>, String> {
     // https://github.com/artemii235/SuperNET/issues/368
-    if local_only && !client.ip().is_loopback() && !PUBLIC_METHODS.contains(&req["method"].as_str()) {
+    if local_only && !client.is_local() && !PUBLIC_METHODS.contains(&req["method"].as_str()) {
         return ERR!("Selected method can be called from localhost only!");
     }
-    let rate_limit_ctx = RateLimitContext::from_ctx(&ctx).unwrap();
-    if rate_limit_ctx.is_banned(client.ip()).await {
+    let rate_limit_ctx = try_s!(RateLimitContext::from_ctx(&ctx));
+    if rate_limit_ctx.is_banned(&client).await {
         return ERR!("Your ip is banned.");
     }
-    try_s!(auth(&req, &ctx, &client).await);
+    let rate_limit_client = match try_s!(auth(&req, &ctx, &client).await) {
+        Some(api_key_name) => RateLimitClient::ApiKey(api_key_name),
+        None => client.rate_limit_client(),
+    };
+    let method = req["method"].as_str().unwrap_or_default();
+    if let Err(e) = rate_limit_ctx.check_request_rate(rate_limit_client, method).await {
+        return too_many_requests_response(e.into_inner());
+    }
 
     let handler = match dispatcher(req, ctx.clone()) {
         DispatcherRes::Match(handler) => handler,
@@ -141,6 +156,15 @@ pub async fn process_single_request(
     Ok(try_s!(handler.compat().await))
 }
 
+fn too_many_requests_response(error: DispatcherError) -> Result<Response<Vec<u8>>, String> {
+    let mut response = Response::builder().status(error.status_code());
+    if let DispatcherError::TooManyRequests { retry_after_secs } = error {
+        response = response.header(RETRY_AFTER, retry_after_secs);
+    }
+    let body = err_to_rpc_json_string(&error.to_string()).into_bytes();
+    Ok(try_s!(response.body(body)))
+}
+
 /// The set of functions that convert the result of the updated handlers into the legacy format.
 mod into_legacy {
     use super::*;
@@ -1,3 +1,6 @@
+use crate::mm2::lp_native_dex::init_context::MmInitContext;
+use coins::CoinsContext;
+use coins_activation::CoinsActivationContext;
 use common::HttpStatusCode;
 use crypto::{CryptoCtx, CryptoInitError};
 use derive_more::Display;
@@ -5,11 +8,12 @@ use http::StatusCode;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
 use rpc::v1::types::H160 as H160Json;
+use rpc_task::rpc_common::ListRpcTasksResponse;
 use serde_json::Value as Json;
 
 pub type GetPublicKeyRpcResult<T> = Result<T, MmError<GetPublicKeyError>>;
 
-#[derive(Serialize, Display, SerializeErrorType)]
+#[derive(Serialize, Display, SerializeErrorType, RpcSchema)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum GetPublicKeyError {
     Internal(String),
@@ -19,7 +23,7 @@ impl From<CryptoInitError> for GetPublicKeyError {
     fn from(_: CryptoInitError) -> Self { GetPublicKeyError::Internal("public_key not available".to_string()) }
 }
 
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 pub struct GetPublicKeyResponse {
     public_key: String,
 }
@@ -37,7 +41,7 @@ pub async fn get_public_key(ctx: MmArc, _req: Json) -> GetPublicKeyRpcResult<Get
     Ok(GetPublicKeyResponse { public_key })
 }
 
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 pub struct GetPublicKeyHashResponse {
     public_key_hash: H160Json,
 }
@@ -46,3 +50,31 @@ pub async fn get_public_key_hash(ctx: MmArc, _req: Json) -> GetPublicKeyRpcResul
     let public_key_hash = ctx.rmd160().to_owned().into();
     Ok(GetPublicKeyHashResponse { public_key_hash })
 }
+
+#[derive(Serialize, Display, SerializeErrorType, RpcSchema)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum ListRpcTasksError {
+    Internal(String),
+}
+
+impl HttpStatusCode for ListRpcTasksError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            ListRpcTasksError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// Returns the short descriptions of all RPC tasks (`init_*` methods) that are not forgotten yet.
+/// The tasks are sorted by their ids, i.e. in the order they were spawned.
+pub async fn list_rpc_tasks(ctx: MmArc, _req: Json) -> MmResult<ListRpcTasksResponse, ListRpcTasksError> {
+    let coins_ctx = CoinsContext::from_ctx(&ctx).map_to_mm(ListRpcTasksError::Internal)?;
+    let coins_act_ctx = CoinsActivationContext::from_ctx(&ctx).map_to_mm(ListRpcTasksError::Internal)?;
+    let init_ctx = MmInitContext::from_ctx(&ctx).map_to_mm(ListRpcTasksError::Internal)?;
+
+    let mut tasks = coins_ctx.rpc_tasks_info().map_to_mm(ListRpcTasksError::Internal)?;
+    tasks.extend(coins_act_ctx.rpc_tasks_info().map_to_mm(ListRpcTasksError::Internal)?);
+    tasks.extend(init_ctx.rpc_tasks_info().map_to_mm(ListRpcTasksError::Internal)?);
+    tasks.sort_by_key(|task| task.task_id);
+    Ok(ListRpcTasksResponse { tasks })
+}
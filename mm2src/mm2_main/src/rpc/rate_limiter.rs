@@ -1,61 +1,449 @@
-use crate::mm2::rpc::DispatcherError;
+//! The RPC rate limiting.
+//!
+//! There are two independent mechanisms:
+//! * the client IP is banned for `ban_duration_secs` after `failed_auth_limit` failed authentication attempts;
+//! * every client (IP address or API key) has a token bucket per method class configured in the `rpc_rate_limit` config.
+//!   The request is rejected with the `429 Too Many Requests` status if the corresponding bucket is empty.
+//!
+//! Config example:
+//! ```json
+//! "rpc_rate_limit": {
+//!     "failed_auth_limit": 10,
+//!     "ban_duration_secs": 3600,
+//!     "default_limit": { "capacity": 100, "refill_per_sec": 10 },
+//!     "method_classes": [
+//!         { "name": "orderbook", "methods": ["orderbook", "best_orders"], "capacity": 20, "refill_per_sec": 2 },
+//!         { "name": "withdraw", "methods": ["withdraw", "init_withdraw"], "capacity": 5, "refill_per_sec": 0.1 }
+//!     ]
+//! }
+//! ```
+
+use crate::mm2::rpc::{DispatcherError, DispatcherResult, RpcClient};
+use common::log::warn;
+use common::{now_ms, HttpStatusCode, StatusCode};
 use derive_more::Display;
 use futures::lock::Mutex as AsyncMutex;
 use mm2_core::mm_ctx::from_ctx;
 use mm2_core::mm_ctx::MmArc;
 use mm2_err_handle::prelude::*;
-use std::collections::HashMap;
-use std::net::{IpAddr, SocketAddr};
+use serde_json::{self as json, Value as Json};
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
 use std::sync::Arc;
 
 pub const LIMIT_FAILED_REQUEST: usize = 10;
-pub type RateInfosRegistry = HashMap<IpAddr, usize>;
+const DEFAULT_BAN_DURATION_SECS: u64 = 3600;
+/// The full token buckets are removed once the number of the buckets exceeds this value.
+const MAX_BUCKETS_BEFORE_PRUNE: usize = 10_000;
+
+pub type RateInfosRegistry = HashMap<IpAddr, FailedAuthInfo>;
 
 #[derive(Display, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum RateLimitError {
     #[display(fmt = "Rate Limit attempts left: {}", _0)]
     NbAttemptsLeft(usize),
+    #[display(fmt = "In-process requests are not banned")]
+    InProcessClient,
+}
+
+#[derive(Clone, Deserialize)]
+pub struct TokenBucketConf {
+    /// The max number of the requests that can be sent at once.
+    capacity: u32,
+    /// The number of the requests added to the bucket per second.
+    refill_per_sec: f64,
+}
+
+impl TokenBucketConf {
+    fn validate(&self, name: &str) -> Result<(), String> {
+        if self.capacity == 0 {
+            return ERR!("'{}' capacity must be greater than 0", name);
+        }
+        if self.refill_per_sec.is_nan() || self.refill_per_sec <= 0. {
+            return ERR!("'{}' refill_per_sec must be greater than 0", name);
+        }
+        Ok(())
+    }
+}
+
+#[derive(Deserialize)]
+struct MethodClassConf {
+    name: String,
+    methods: Vec<String>,
+    #[serde(flatten)]
+    limit: TokenBucketConf,
+}
+
+#[derive(Deserialize)]
+struct RateLimitConf {
+    #[serde(default = "default_failed_auth_limit")]
+    failed_auth_limit: usize,
+    #[serde(default = "default_ban_duration_secs")]
+    ban_duration_secs: u64,
+    /// The limit of the methods that don't belong to any of the `method_classes`.
+    /// These methods are not limited if it's not set.
+    #[serde(default)]
+    default_limit: Option<TokenBucketConf>,
+    #[serde(default)]
+    method_classes: Vec<MethodClassConf>,
+}
+
+fn default_failed_auth_limit() -> usize { LIMIT_FAILED_REQUEST }
+
+fn default_ban_duration_secs() -> u64 { DEFAULT_BAN_DURATION_SECS }
+
+impl Default for RateLimitConf {
+    fn default() -> Self {
+        RateLimitConf {
+            failed_auth_limit: LIMIT_FAILED_REQUEST,
+            ban_duration_secs: DEFAULT_BAN_DURATION_SECS,
+            default_limit: None,
+            method_classes: Vec::new(),
+        }
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    last_refill_ms: u64,
+}
+
+impl TokenBucket {
+    fn new(conf: &TokenBucketConf, now_ms: u64) -> TokenBucket {
+        TokenBucket {
+            tokens: conf.capacity as f64,
+            last_refill_ms: now_ms,
+        }
+    }
+
+    fn refill(&mut self, conf: &TokenBucketConf, now_ms: u64) {
+        let elapsed_secs = now_ms.saturating_sub(self.last_refill_ms) as f64 / 1000.;
+        self.tokens = (self.tokens + elapsed_secs * conf.refill_per_sec).min(conf.capacity as f64);
+        self.last_refill_ms = now_ms;
+    }
+
+    /// Takes a token from the bucket or returns the number of seconds after which the token will be available.
+    fn try_take(&mut self, conf: &TokenBucketConf, now_ms: u64) -> Result<(), u64> {
+        self.refill(conf, now_ms);
+        if self.tokens >= 1. {
+            self.tokens -= 1.;
+            return Ok(());
+        }
+        Err(((1. - self.tokens) / conf.refill_per_sec).ceil() as u64)
+    }
+
+    fn is_full(&self, conf: &TokenBucketConf, now_ms: u64) -> bool {
+        let elapsed_secs = now_ms.saturating_sub(self.last_refill_ms) as f64 / 1000.;
+        self.tokens + elapsed_secs * conf.refill_per_sec >= conf.capacity as f64
+    }
+}
+
+/// The client whose requests are limited.
+/// The requests authorized by an API key are limited per the key, regardless of the IP address.
+#[derive(Clone, Eq, Hash, PartialEq)]
+pub enum RateLimitClient {
+    Ip(IpAddr),
+    ApiKey(String),
+    /// The host of MM2 sending the requests directly, see [`RpcClient::InProcess`].
+    /// Doesn't share the buckets with the HTTP clients connected from the loopback interface.
+    InProcess,
 }
 
 #[derive(Default)]
-pub struct RateLimitContext(AsyncMutex<RateInfosRegistry>);
+pub struct FailedAuthInfo {
+    attempts: usize,
+    last_attempt_ms: u64,
+    banned_until_ms: Option<u64>,
+}
+
+impl FailedAuthInfo {
+    /// The failed attempts are forgotten once the ban is over or after the ban duration since the last attempt.
+    fn is_expired(&self, now_ms: u64, ban_duration_ms: u64) -> bool {
+        match self.banned_until_ms {
+            Some(banned_until_ms) => banned_until_ms <= now_ms,
+            None => self.last_attempt_ms + ban_duration_ms <= now_ms,
+        }
+    }
+}
+
+pub struct RateLimitContext {
+    conf: RateLimitConf,
+    /// The index of the method class in the `conf.method_classes` by the method name.
+    method_classes: HashMap<String, usize>,
+    failed_auths: AsyncMutex<RateInfosRegistry>,
+    /// The token buckets by the client and the method class index.
+    /// `None` is the class of the methods limited by the `conf.default_limit`.
+    buckets: AsyncMutex<HashMap<(RateLimitClient, Option<usize>), TokenBucket>>,
+}
 
 impl RateLimitContext {
     pub fn from_ctx(ctx: &MmArc) -> Result<Arc<RateLimitContext>, String> {
         Ok(try_s!(from_ctx(&ctx.rate_limit_ctx, move || {
-            Ok(RateLimitContext::default())
+            let conf: RateLimitConf = if ctx.conf["rpc_rate_limit"].is_null() {
+                RateLimitConf::default()
+            } else {
+                try_s!(json::from_value(ctx.conf["rpc_rate_limit"].clone()))
+            };
+            RateLimitContext::from_conf(conf)
         })))
     }
 
-    pub async fn is_banned(&self, client_ip: IpAddr) -> bool {
-        let rate_infos = self.0.lock().await;
-        if let Some(limit) = rate_infos.get(&client_ip) {
-            return *limit >= LIMIT_FAILED_REQUEST;
+    fn from_conf(conf: RateLimitConf) -> Result<RateLimitContext, String> {
+        if let Some(ref default_limit) = conf.default_limit {
+            try_s!(default_limit.validate("default_limit"));
+        }
+        let mut method_classes = HashMap::new();
+        for (idx, class) in conf.method_classes.iter().enumerate() {
+            try_s!(class.limit.validate(&class.name));
+            for method in class.methods.iter() {
+                if method_classes.insert(method.clone(), idx).is_some() {
+                    return ERR!("'{}' method belongs to more than one method class", method);
+                }
+            }
+        }
+        Ok(RateLimitContext {
+            conf,
+            method_classes,
+            failed_auths: AsyncMutex::new(HashMap::new()),
+            buckets: AsyncMutex::new(HashMap::new()),
+        })
+    }
+
+    fn ban_duration_ms(&self) -> u64 { self.conf.ban_duration_secs * 1000 }
+
+    /// The in-process client is never banned.
+    pub async fn is_banned(&self, client: &RpcClient) -> bool {
+        let client_ip = match client.ip() {
+            Some(ip) => ip,
+            None => return false,
+        };
+        let now = now_ms();
+        let mut rate_infos = self.failed_auths.lock().await;
+        let info = match rate_infos.get(&client_ip) {
+            Some(info) => info,
+            None => return false,
+        };
+        if info.is_expired(now, self.ban_duration_ms()) {
+            rate_infos.remove(&client_ip);
+            return false;
+        }
+        info.banned_until_ms.is_some()
+    }
+
+    /// Takes a token from the `client` bucket of the `method` class.
+    /// Returns [`DispatcherError::TooManyRequests`] if the bucket is empty.
+    pub async fn check_request_rate(&self, client: RateLimitClient, method: &str) -> DispatcherResult<()> {
+        let class_idx = self.method_classes.get(method).copied();
+        let limit = match class_idx {
+            Some(idx) => &self.conf.method_classes[idx].limit,
+            None => match self.conf.default_limit {
+                Some(ref limit) => limit,
+                None => return Ok(()),
+            },
+        };
+
+        let now = now_ms();
+        let mut buckets = self.buckets.lock().await;
+        if buckets.len() >= MAX_BUCKETS_BEFORE_PRUNE {
+            let conf = &self.conf;
+            buckets.retain(|(_, class_idx), bucket| {
+                let limit = match class_idx {
+                    Some(idx) => &conf.method_classes[*idx].limit,
+                    None => conf.default_limit.as_ref().expect("Only limited methods have buckets"),
+                };
+                !bucket.is_full(limit, now)
+            });
         }
-        false
+
+        buckets
+            .entry((client, class_idx))
+            .or_insert_with(|| TokenBucket::new(limit, now))
+            .try_take(limit, now)
+            .map_to_mm(|retry_after_secs| DispatcherError::TooManyRequests { retry_after_secs })
+    }
+
+    async fn process_failed_auth(&self, client_ip: IpAddr) -> MmError<DispatcherError> {
+        let now = now_ms();
+        let mut rate_limit_registry = self.failed_auths.lock().await;
+        let info = rate_limit_registry
+            .entry(client_ip)
+            .or_insert_with(FailedAuthInfo::default);
+        if info.is_expired(now, self.ban_duration_ms()) {
+            *info = FailedAuthInfo::default();
+        }
+        if info.banned_until_ms.is_some() {
+            return MmError::new(DispatcherError::Banned);
+        }
+
+        info.attempts += 1;
+        info.last_attempt_ms = now;
+        if info.attempts >= self.conf.failed_auth_limit {
+            warn!(
+                "{} is banned for {} seconds after {} failed authentication attempts",
+                client_ip, self.conf.ban_duration_secs, info.attempts
+            );
+            info.banned_until_ms = Some(now + self.ban_duration_ms());
+        }
+        MmError::new(DispatcherError::UserpassIsInvalid(RateLimitError::NbAttemptsLeft(
+            self.conf.failed_auth_limit.saturating_sub(info.attempts),
+        )))
+    }
+}
+
+/// Counts the failed authentication attempt of the remote `client`.
+/// The in-process client is never banned, so its attempts aren't counted.
+pub async fn process_rate_limit(ctx: &MmArc, client: &RpcClient) -> MmError<DispatcherError> {
+    let client_ip = match client.ip() {
+        Some(ip) => ip,
+        None => return MmError::new(DispatcherError::UserpassIsInvalid(RateLimitError::InProcessClient)),
+    };
+    match RateLimitContext::from_ctx(ctx) {
+        Ok(rate_limit_ctx) => rate_limit_ctx.process_failed_auth(client_ip).await,
+        Err(e) => MmError::new(DispatcherError::Internal(e)),
     }
 }
 
-pub async fn process_rate_limit(ctx: &MmArc, client: &SocketAddr) -> MmError<DispatcherError> {
-    let rate_limit_ctx = RateLimitContext::from_ctx(ctx).unwrap();
-    let mut rate_limit_registry = rate_limit_ctx.0.lock().await;
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum RateLimitRpcError {
+    Internal(String),
+}
 
-    match rate_limit_registry.get_mut(&client.ip()) {
-        Some(limit) => {
-            if *limit >= LIMIT_FAILED_REQUEST {
-                return MmError::new(DispatcherError::Banned);
+impl HttpStatusCode for RateLimitRpcError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            RateLimitRpcError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct RateLimitedClientInfo {
+    ip: IpAddr,
+    failed_auth_attempts: usize,
+    /// Unix timestamp in seconds.
+    banned_until: Option<u64>,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct ListRateLimitedClientsResponse {
+    clients: Vec<RateLimitedClientInfo>,
+}
+
+pub async fn list_rate_limited_clients(
+    ctx: MmArc,
+    _req: Json,
+) -> MmResult<ListRateLimitedClientsResponse, RateLimitRpcError> {
+    let rate_limit_ctx = RateLimitContext::from_ctx(&ctx).map_to_mm(RateLimitRpcError::Internal)?;
+    let now = now_ms();
+    let ban_duration_ms = rate_limit_ctx.ban_duration_ms();
+    let mut rate_infos = rate_limit_ctx.failed_auths.lock().await;
+    rate_infos.retain(|_, info| !info.is_expired(now, ban_duration_ms));
+
+    let clients = rate_infos
+        .iter()
+        .map(|(ip, info)| RateLimitedClientInfo {
+            ip: *ip,
+            failed_auth_attempts: info.attempts,
+            banned_until: info.banned_until_ms.map(|banned_until_ms| banned_until_ms / 1000),
+        })
+        .collect();
+    Ok(ListRateLimitedClientsResponse { clients })
+}
+
+#[derive(Deserialize, RpcSchema)]
+#[serde(tag = "type", content = "data")]
+pub enum UnbanClientsBy {
+    All,
+    Few(HashSet<IpAddr>),
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct UnbanClientsRequest {
+    unban_by: UnbanClientsBy,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct UnbanClientsResponse {
+    unbanned: Vec<IpAddr>,
+    were_not_banned: Vec<IpAddr>,
+}
+
+/// Removes the bans and the failed authentication attempts of the clients.
+pub async fn unban_rate_limited_clients(
+    ctx: MmArc,
+    req: UnbanClientsRequest,
+) -> MmResult<UnbanClientsResponse, RateLimitRpcError> {
+    let rate_limit_ctx = RateLimitContext::from_ctx(&ctx).map_to_mm(RateLimitRpcError::Internal)?;
+    let mut rate_infos = rate_limit_ctx.failed_auths.lock().await;
+    let mut unbanned = Vec::new();
+    let mut were_not_banned = Vec::new();
+    match req.unban_by {
+        UnbanClientsBy::All => unbanned = rate_infos.drain().map(|(ip, _)| ip).collect(),
+        UnbanClientsBy::Few(ips) => {
+            for ip in ips {
+                match rate_infos.remove(&ip) {
+                    Some(_) => unbanned.push(ip),
+                    None => were_not_banned.push(ip),
+                }
             }
-            *limit += 1;
-            MmError::new(DispatcherError::UserpassIsInvalid(RateLimitError::NbAttemptsLeft(
-                LIMIT_FAILED_REQUEST - *limit,
-            )))
-        },
-        None => {
-            rate_limit_registry.insert(client.ip(), 1);
-            MmError::new(DispatcherError::UserpassIsInvalid(RateLimitError::NbAttemptsLeft(
-                LIMIT_FAILED_REQUEST - 1,
-            )))
         },
     }
+    Ok(UnbanClientsResponse {
+        unbanned,
+        were_not_banned,
+    })
+}
+
+#[cfg(test)]
+mod rate_limiter_tests {
+    use super::*;
+
+    #[test]
+    fn test_token_bucket() {
+        let conf = TokenBucketConf {
+            capacity: 2,
+            refill_per_sec: 0.5,
+        };
+        let mut bucket = TokenBucket::new(&conf, 0);
+        bucket.try_take(&conf, 0).unwrap();
+        bucket.try_take(&conf, 0).unwrap();
+        assert_eq!(bucket.try_take(&conf, 0), Err(2));
+        assert_eq!(bucket.try_take(&conf, 1000), Err(1));
+        bucket.try_take(&conf, 2000).unwrap();
+        assert!(!bucket.is_full(&conf, 2000));
+        // the bucket is not refilled above the capacity
+        assert!(bucket.is_full(&conf, 100_000));
+        bucket.try_take(&conf, 100_000).unwrap();
+        bucket.try_take(&conf, 100_000).unwrap();
+        assert!(bucket.try_take(&conf, 100_000).is_err());
+    }
+
+    #[test]
+    fn test_method_belongs_to_several_classes() {
+        let conf: RateLimitConf = json::from_value(json!({
+            "method_classes": [
+                { "name": "orderbook", "methods": ["orderbook"], "capacity": 10, "refill_per_sec": 1 },
+                { "name": "other", "methods": ["orderbook"], "capacity": 10, "refill_per_sec": 1 }
+            ]
+        }))
+        .unwrap();
+        assert!(RateLimitContext::from_conf(conf).is_err());
+    }
+
+    #[test]
+    fn test_failed_auth_info_expiration() {
+        let mut info = FailedAuthInfo {
+            attempts: 3,
+            last_attempt_ms: 1000,
+            banned_until_ms: None,
+        };
+        assert!(!info.is_expired(1500, 1000));
+        assert!(info.is_expired(2000, 1000));
+
+        info.banned_until_ms = Some(5000);
+        assert!(!info.is_expired(2000, 1000));
+        assert!(info.is_expired(5000, 1000));
+    }
 }
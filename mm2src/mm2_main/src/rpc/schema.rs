@@ -0,0 +1,274 @@
+//! The OpenAPI description of the `mmrpc: 2.0` methods.
+//!
+//! The schemas of the requests, responses and errors are derived from the handlers registered in
+//! [`super::dispatcher::rpc_schema_registry`] which is defined by the same table of the methods as `dispatcher_v2`.
+//! The document is served by the `get_rpc_schema` RPC and can be exported by the `export_rpc_schema` command.
+//! The up-to-date document is also kept in `mm2src/mm2_main/rpc_schema.json`, the tests check that.
+
+use super::dispatcher::rpc_schema_registry;
+use common::{HttpStatusCode, StatusCode};
+use derive_more::Display;
+use futures::Future;
+use mm2_core::mm_ctx::MmArc;
+use mm2_err_handle::prelude::*;
+use rpc_schema::{RpcSchema, SchemaGenerator};
+use serde_json::{Map, Value as Json};
+use std::collections::BTreeMap;
+
+pub const OPENAPI_VERSION: &str = "3.1.0";
+
+struct MethodSchema {
+    /// `None` if the types of the method don't implement [`RpcSchema`] yet.
+    types: Option<MethodTypes>,
+}
+
+struct MethodTypes {
+    params: Json,
+    result: Json,
+    error: Json,
+}
+
+/// Collects the schemas of the RPC methods and the named types they refer to.
+#[derive(Default)]
+pub struct RpcSchemaRegistry {
+    methods: BTreeMap<&'static str, MethodSchema>,
+    gen: SchemaGenerator,
+}
+
+impl RpcSchemaRegistry {
+    /// Registers the `method` with the request, response and error types of the `handler`.
+    /// The handler signature is the same as the one expected by `dispatcher_v2`.
+    pub fn register<Handler, Fut, Request, T, E>(&mut self, method: &'static str, _handler: Handler)
+    where
+        Handler: FnOnce(MmArc, Request) -> Fut,
+        Fut: Future<Output = Result<T, MmError<E>>>,
+        Request: RpcSchema,
+        T: RpcSchema,
+        E: RpcSchema,
+    {
+        let types = MethodTypes {
+            params: self.gen.subschema_for::<Request>(),
+            result: self.gen.subschema_for::<T>(),
+            error: self.gen.subschema_for::<E>(),
+        };
+        self.methods.insert(method, MethodSchema { types: Some(types) });
+    }
+
+    /// Registers the `method` which types aren't described yet.
+    /// The method is still listed in the document, but its params, result and error may be any JSON values.
+    pub fn register_untyped(&mut self, method: &'static str) {
+        self.methods.insert(method, MethodSchema { types: None });
+    }
+
+    pub fn methods(&self) -> impl Iterator<Item = &'static str> + '_ { self.methods.keys().copied() }
+
+    /// Returns the OpenAPI document that describes all methods or only the given one.
+    pub fn openapi_document(&self, only_method: Option<&str>) -> Json {
+        let paths: Map<String, Json> = self
+            .methods
+            .iter()
+            .filter(|(method, _)| only_method.map_or(true, |only| only == **method))
+            .map(|(method, schema)| (format!("/#{}", method), method_path_item(method, schema)))
+            .collect();
+
+        json!({
+            "openapi": OPENAPI_VERSION,
+            "info": {
+                "title": "AtomicDEX API",
+                "version": crate::mm2::MM_VERSION,
+                "description": "All methods are called by POST requests to the root path. \
+                                The path fragment designates the `method` field of the request.",
+            },
+            "paths": paths,
+            "components": {
+                "schemas": self.gen.definitions(),
+            },
+        })
+    }
+}
+
+fn untyped() -> Json { json!({ "x-untyped": true }) }
+
+fn method_path_item(method: &str, schema: &MethodSchema) -> Json {
+    let (params, result, error) = match schema.types {
+        Some(ref types) => (types.params.clone(), types.result.clone(), types.error.clone()),
+        None => (untyped(), untyped(), untyped()),
+    };
+
+    let request = json!({
+        "type": "object",
+        "properties": {
+            "mmrpc": { "type": "string", "const": "2.0" },
+            "method": { "type": "string", "const": method },
+            "params": params,
+            "userpass": { "type": "string" },
+            "id": { "type": "integer", "minimum": 0 },
+        },
+        "required": ["mmrpc", "method"],
+        "additionalProperties": false,
+    });
+    let success = json!({
+        "type": "object",
+        "properties": {
+            "mmrpc": { "type": "string", "const": "2.0" },
+            "result": result,
+            "id": { "anyOf": [{ "type": "integer", "minimum": 0 }, { "type": "null" }] },
+        },
+        "required": ["mmrpc", "result"],
+    });
+    // `error_type` and `error_data` are the fields of the flattened error.
+    let error_response = json!({
+        "allOf": [
+            {
+                "type": "object",
+                "properties": {
+                    "mmrpc": { "type": "string", "const": "2.0" },
+                    "error": { "type": "string" },
+                    "error_path": { "type": "string" },
+                    "error_trace": { "type": "string" },
+                    "id": { "anyOf": [{ "type": "integer", "minimum": 0 }, { "type": "null" }] },
+                },
+                "required": ["mmrpc", "error", "error_path", "error_trace"],
+            },
+            error,
+        ],
+    });
+
+    json!({
+        "post": {
+            "operationId": method,
+            "requestBody": {
+                "required": true,
+                "content": { "application/json": { "schema": request } },
+            },
+            "responses": {
+                "200": {
+                    "description": "Success",
+                    "content": { "application/json": { "schema": success } },
+                },
+                "default": {
+                    "description": "Error",
+                    "content": { "application/json": { "schema": error_response } },
+                },
+            },
+        },
+    })
+}
+
+#[derive(Display, Serialize, SerializeErrorType, RpcSchema)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum GetRpcSchemaError {
+    #[display(fmt = "No such method '{}'", _0)]
+    NoSuchMethod(String),
+}
+
+impl HttpStatusCode for GetRpcSchemaError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            GetRpcSchemaError::NoSuchMethod(_) => StatusCode::NOT_FOUND,
+        }
+    }
+}
+
+#[derive(Deserialize, RpcSchema)]
+pub struct GetRpcSchemaRequest {
+    /// Returns the document that describes this method only.
+    #[serde(default)]
+    method: Option<String>,
+}
+
+/// Returns the OpenAPI document that describes all `mmrpc: 2.0` methods.
+pub fn openapi_document() -> Json { rpc_schema_registry().openapi_document(None) }
+
+/// Returns the OpenAPI document of the `mmrpc: 2.0` methods.
+pub async fn get_rpc_schema(_ctx: MmArc, req: GetRpcSchemaRequest) -> MmResult<Json, GetRpcSchemaError> {
+    let registry = rpc_schema_registry();
+    if let Some(ref method) = req.method {
+        if !registry.methods().any(|known| known == method.as_str()) {
+            return MmError::err(GetRpcSchemaError::NoSuchMethod(method.clone()));
+        }
+    }
+    Ok(registry.openapi_document(req.method.as_deref()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The OpenAPI document kept in the repository, so the changes of the RPC types are seen in the code review.
+    /// Regenerated by running the tests with the `UPDATE_RPC_SCHEMA` environment variable set.
+    #[cfg(not(target_arch = "wasm32"))]
+    const EXPORTED_DOCUMENT_PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/rpc_schema.json");
+
+    #[test]
+    fn test_openapi_document() {
+        let document = rpc_schema_registry().openapi_document(None);
+        let paths = document["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/#get_rpc_schema"));
+        assert!(paths.contains_key("/#withdraw"));
+
+        let request = &paths["/#my_swap_status"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(request["properties"]["method"]["const"], "my_swap_status");
+        assert_eq!(
+            request["properties"]["params"]["$ref"],
+            "#/components/schemas/MySwapStatusRequest"
+        );
+        let schemas = &document["components"]["schemas"];
+        assert_eq!(schemas["MySwapStatusRequest"]["required"], json!(["uuid"]));
+        assert_eq!(schemas["SwapRpcError"]["oneOf"].as_array().unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_openapi_document_only_method() {
+        let document = rpc_schema_registry().openapi_document(Some("cancel_order"));
+        let paths = document["paths"].as_object().unwrap();
+        assert_eq!(paths.len(), 1);
+        assert!(paths.contains_key("/#cancel_order"));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_rpc_schema_registry_native_only_methods() {
+        let registry = rpc_schema_registry();
+        let methods: Vec<_> = registry.methods().collect();
+        assert!(methods.contains(&"withdraw"));
+        assert!(methods.contains(&"z_coin_tx_history"));
+
+        let document = registry.openapi_document(Some("init_z_coin_cancel"));
+        let request =
+            &document["paths"]["/#init_z_coin_cancel"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(
+            request["properties"]["params"]["$ref"],
+            "#/components/schemas/CancelRpcTaskRequest"
+        );
+        let document = registry.openapi_document(Some("z_coin_tx_history"));
+        let request =
+            &document["paths"]["/#z_coin_tx_history"]["post"]["requestBody"]["content"]["application/json"]["schema"];
+        assert_eq!(request["properties"]["params"], json!({ "x-untyped": true }));
+    }
+
+    #[test]
+    #[cfg(not(target_arch = "wasm32"))]
+    fn test_exported_openapi_document_is_up_to_date() {
+        let mut document = openapi_document();
+        // The version changes from commit to commit.
+        document["info"]["version"] = Json::Null;
+        let encoded = serde_json::to_string_pretty(&document).unwrap() + "\n";
+
+        if std::env::var_os("UPDATE_RPC_SCHEMA").is_some() {
+            std::fs::write(EXPORTED_DOCUMENT_PATH, encoded).unwrap();
+            return;
+        }
+        let exported = std::fs::read_to_string(EXPORTED_DOCUMENT_PATH).unwrap_or_else(|e| {
+            panic!(
+                "Error reading '{}': {}, run the tests with 'UPDATE_RPC_SCHEMA=1' to generate it",
+                EXPORTED_DOCUMENT_PATH, e
+            )
+        });
+        assert!(
+            exported == encoded,
+            "'{}' is outdated, run the tests with 'UPDATE_RPC_SCHEMA=1' to regenerate it",
+            EXPORTED_DOCUMENT_PATH
+        );
+    }
+}
@@ -3,5 +3,7 @@ pub mod transport;
 
 #[cfg(not(target_arch = "wasm32"))] pub mod ip_addr;
 #[cfg(not(target_arch = "wasm32"))] pub mod native_http;
+#[cfg(not(target_arch = "wasm32"))] pub mod native_ws;
+#[cfg(not(target_arch = "wasm32"))] pub mod socks5;
 #[cfg(target_arch = "wasm32")] pub mod wasm_http;
 #[cfg(target_arch = "wasm32")] pub mod wasm_ws;
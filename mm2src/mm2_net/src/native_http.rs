@@ -1,9 +1,50 @@
+use crate::socks5::{Socks5Connector, Socks5Proxy};
 use crate::transport::{SlurpError, SlurpResult};
-use common::wio::{drive03, HYPER};
+use common::wio::{drive03, CORE, HYPER};
 use futures::channel::oneshot::Canceled;
-use http::{header, HeaderValue, Request};
-use hyper::Body;
+use http::{header, HeaderValue, Method, Request};
+use hyper::{Body, Client};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use lazy_static::lazy_static;
 use mm2_err_handle::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+type ProxyClient = Client<HttpsConnector<Socks5Connector>>;
+
+lazy_static! {
+    /// The proxy used by the requests that aren't bound to any specific proxy, set by the `proxy` config option.
+    static ref GLOBAL_PROXY: Mutex<Option<Socks5Proxy>> = Mutex::new(None);
+    /// The shared clients connecting through the proxies.
+    static ref PROXY_CLIENTS: Mutex<HashMap<Socks5Proxy, ProxyClient>> = Mutex::new(HashMap::new());
+}
+
+/// Routes the [`slurp_req`] requests and the requests with no specific proxy through the `proxy`.
+pub fn set_global_proxy(proxy: Option<Socks5Proxy>) { *GLOBAL_PROXY.lock().unwrap() = proxy; }
+
+/// Returns the proxy set by [`set_global_proxy`].
+pub fn global_proxy() -> Option<Socks5Proxy> { GLOBAL_PROXY.lock().unwrap().clone() }
+
+fn proxy_client(proxy: &Socks5Proxy) -> ProxyClient {
+    PROXY_CLIENTS
+        .lock()
+        .unwrap()
+        .entry(proxy.clone())
+        .or_insert_with(|| {
+            let https = HttpsConnectorBuilder::new()
+                .with_webpki_roots()
+                .https_or_http()
+                .enable_http1()
+                .enable_http2()
+                .wrap_connector(Socks5Connector::new(proxy.clone()));
+            // Don't reuse the connections like the `HYPER` client doesn't.
+            Client::builder()
+                .executor(&*CORE)
+                .pool_max_idle_per_host(0)
+                .build(https)
+        })
+        .clone()
+}
 
 impl From<Canceled> for SlurpError {
     fn from(_: Canceled) -> Self { SlurpError::Internal("Spawned Slurp future has been canceled".to_owned()) }
@@ -29,16 +70,24 @@ impl From<http::Error> for SlurpError {
     fn from(e: http::Error) -> Self { SlurpError::InvalidRequest(e.to_string()) }
 }
 
-/// Executes a Hyper request, returning the response status, headers and body.
+/// Executes a Hyper request through the global proxy if it's set, returning the response status, headers and body.
 pub async fn slurp_req(request: Request<Vec<u8>>) -> SlurpResult {
+    let proxy = global_proxy();
+    slurp_req_via_proxy(request, proxy.as_ref()).await
+}
+
+/// Executes a Hyper request through the `proxy` or directly if it's `None`,
+/// returning the response status, headers and body.
+pub async fn slurp_req_via_proxy(request: Request<Vec<u8>>, proxy: Option<&Socks5Proxy>) -> SlurpResult {
     let uri = request.uri().to_string();
     let (head, body) = request.into_parts();
     let request = Request::from_parts(head, Body::from(body));
 
-    let request_f = HYPER.request(request);
-    let response = drive03(request_f)
-        .await?
-        .map_to_mm(|e| SlurpError::from_hyper_error(e, uri.clone()))?;
+    let response = match proxy {
+        Some(proxy) => drive03(proxy_client(proxy).request(request)).await?,
+        None => drive03(HYPER.request(request)).await?,
+    };
+    let response = response.map_to_mm(|e| SlurpError::from_hyper_error(e, uri.clone()))?;
     let status = response.status();
     let headers = response.headers().clone();
     let body = response.into_body();
@@ -64,6 +113,19 @@ pub async fn slurp_post_json(url: &str, body: String) -> SlurpResult {
     slurp_req(request).await
 }
 
+/// Executes a request with the JSON body and the additional headers, returning the response status, headers and body.
+pub async fn slurp_json_with_headers(method: Method, url: &str, body: String, headers: &[(&str, &str)]) -> SlurpResult {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(url)
+        .header(header::CONTENT_TYPE, HeaderValue::from_static("application/json"));
+    for (key, value) in headers {
+        builder = builder.header(*key, *value);
+    }
+    let request = builder.body(body.into())?;
+    slurp_req(request).await
+}
+
 #[cfg(test)]
 mod tests {
     use crate::native_http::slurp_url;
@@ -0,0 +1,256 @@
+//! A WebSocket client exchanging the text messages, e.g. the JSON-RPC requests and the `eth_subscribe` notifications.
+//!
+//! The connection can be established through a SOCKS5 proxy the same way as the HTTP requests.
+//! The protocol is handled by `soketto`, the pings are answered by its receiver.
+
+use crate::socks5::{socks5_connect, Socks5Proxy};
+use common::executor::spawn;
+use common::log::debug;
+use futures::channel::{mpsc, oneshot};
+use futures::{select, StreamExt};
+use http::Uri;
+use soketto::connection::{Receiver, Sender};
+use soketto::handshake::{Client, ServerResponse};
+use std::convert::TryFrom;
+use std::io::{self, ErrorKind};
+use std::sync::Arc;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpStream;
+use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use tokio_rustls::TlsConnector;
+use tokio_util::compat::{Compat, TokioAsyncReadCompatExt};
+
+/// The messages exceeding the limit are considered a protocol violation.
+const MAX_MESSAGE_SIZE: usize = 16 * 1024 * 1024;
+
+trait WsStream: AsyncRead + AsyncWrite + Send + Unpin {}
+
+impl<T: AsyncRead + AsyncWrite + Send + Unpin> WsStream for T {}
+
+type BoxedStream = Compat<Box<dyn WsStream>>;
+
+/// Sends the text messages to the WebSocket server.
+/// The connection is closed once all senders are dropped.
+#[derive(Clone, Debug)]
+pub struct WsSender {
+    tx: mpsc::UnboundedSender<String>,
+}
+
+impl WsSender {
+    pub fn send(&self, text: String) -> io::Result<()> {
+        self.tx
+            .unbounded_send(text)
+            .map_err(|_| io::Error::new(ErrorKind::NotConnected, "WebSocket connection is closed"))
+    }
+}
+
+/// The text messages received from the WebSocket server.
+/// The stream ends when the connection is closed.
+pub type WsReceiver = mpsc::UnboundedReceiver<String>;
+
+/// Connects to the `ws://` or `wss://` url, directly or through the `proxy`,
+/// and spawns the tasks reading and writing the messages.
+pub async fn ws_connect(url: &str, proxy: Option<&Socks5Proxy>) -> io::Result<(WsSender, WsReceiver)> {
+    let uri: Uri = url
+        .parse()
+        .map_err(|e| io::Error::new(ErrorKind::InvalidInput, format!("Invalid url {}: {}", url, e)))?;
+    let is_tls = match uri.scheme_str() {
+        Some("wss") => true,
+        Some("ws") => false,
+        _ => {
+            return Err(io::Error::new(
+                ErrorKind::InvalidInput,
+                format!("Expected ws:// or wss:// url, got {}", url),
+            ))
+        },
+    };
+    let host = uri
+        .host()
+        .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, format!("No host in {}", url)))?;
+    let port = uri.port_u16().unwrap_or(if is_tls { 443 } else { 80 });
+
+    let tcp = match proxy {
+        Some(proxy) => socks5_connect(proxy, host, port).await?,
+        None => TcpStream::connect((host, port)).await?,
+    };
+    tcp.set_nodelay(true)?;
+    let stream: Box<dyn WsStream> = if is_tls {
+        Box::new(tls_connect(tcp, host).await?)
+    } else {
+        Box::new(tcp)
+    };
+
+    let host_header = match uri.port_u16() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_owned(),
+    };
+    let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+    let mut client = Client::new(stream.compat(), &host_header, path);
+    match client.handshake().await.map_err(ws_error)? {
+        ServerResponse::Accepted { .. } => (),
+        ServerResponse::Redirect { status_code, location } => {
+            return Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("WebSocket handshake redirected with {} to {}", status_code, location),
+            ))
+        },
+        ServerResponse::Rejected { status_code } => {
+            return Err(io::Error::new(
+                ErrorKind::ConnectionRefused,
+                format!("WebSocket handshake rejected with {}", status_code),
+            ))
+        },
+    }
+    let mut builder = client.into_builder();
+    builder.set_max_message_size(MAX_MESSAGE_SIZE);
+    let (sender, receiver) = builder.finish();
+
+    let (outgoing_tx, outgoing_rx) = mpsc::unbounded();
+    let (incoming_tx, incoming_rx) = mpsc::unbounded();
+    let (stop_tx, stop_rx) = oneshot::channel();
+    spawn(write_loop(sender, outgoing_rx, stop_rx));
+    spawn(read_loop(receiver, incoming_tx, stop_tx));
+    Ok((WsSender { tx: outgoing_tx }, incoming_rx))
+}
+
+async fn tls_connect(tcp: TcpStream, host: &str) -> io::Result<tokio_rustls::client::TlsStream<TcpStream>> {
+    let mut roots = RootCertStore::empty();
+    roots.add_server_trust_anchors(
+        webpki_roots::TLS_SERVER_ROOTS
+            .0
+            .iter()
+            .map(|ta| OwnedTrustAnchor::from_subject_spki_name_constraints(ta.subject, ta.spki, ta.name_constraints)),
+    );
+    let config = ClientConfig::builder()
+        .with_safe_defaults()
+        .with_root_certificates(roots)
+        .with_no_client_auth();
+    let server_name = ServerName::try_from(host)
+        .map_err(|e| io::Error::new(ErrorKind::InvalidInput, format!("Invalid DNS name {}: {}", host, e)))?;
+    TlsConnector::from(Arc::new(config)).connect(server_name, tcp).await
+}
+
+fn ws_error<E: std::fmt::Display>(e: E) -> io::Error { io::Error::new(ErrorKind::Other, e.to_string()) }
+
+/// Forwards the received messages until the connection is closed or the [`WsReceiver`] is dropped.
+async fn read_loop(
+    mut receiver: Receiver<BoxedStream>,
+    incoming: mpsc::UnboundedSender<String>,
+    stop_writer: oneshot::Sender<()>,
+) {
+    loop {
+        let mut message = Vec::new();
+        if let Err(e) = receiver.receive_data(&mut message).await {
+            debug!("WebSocket connection read error: {}", e);
+            break;
+        }
+        let text = match String::from_utf8(message) {
+            Ok(text) => text,
+            Err(e) => {
+                debug!("WebSocket message is not UTF-8: {}", e);
+                break;
+            },
+        };
+        if incoming.unbounded_send(text).is_err() {
+            // The receiver is dropped, nobody is interested in the messages anymore.
+            break;
+        }
+    }
+    stop_writer.send(()).ok();
+}
+
+/// Writes the messages of the [`WsSender`]s.
+/// The connection is closed once all [`WsSender`]s are dropped or the reader is stopped.
+async fn write_loop(
+    mut sender: Sender<BoxedStream>,
+    mut outgoing: mpsc::UnboundedReceiver<String>,
+    mut stop: oneshot::Receiver<()>,
+) {
+    loop {
+        let text = select! {
+            text = outgoing.next() => match text {
+                Some(text) => text,
+                None => break,
+            },
+            _ = stop => break,
+        };
+        let sent = match sender.send_text(text).await {
+            Ok(()) => sender.flush().await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sent {
+            debug!("WebSocket connection write error: {}", e);
+            return;
+        }
+    }
+    sender.close().await.ok();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::block_on;
+    use soketto::handshake::server::Response;
+    use soketto::handshake::Server;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Accepts a single connection, completes the handshake, sends a message
+    /// and echoes the message received from the client.
+    async fn mock_server(listener: TcpListener) {
+        let (stream, _) = listener.accept().await.unwrap();
+        let mut server = Server::new(stream.compat());
+        let request = server.receive_request().await.unwrap();
+        let key = request.key();
+        server
+            .send_response(&Response::Accept { key, protocol: None })
+            .await
+            .unwrap();
+
+        let (mut sender, mut receiver) = server.into_builder().finish();
+        sender.send_text("hello").await.unwrap();
+        sender.flush().await.unwrap();
+
+        let mut message = Vec::new();
+        receiver.receive_data(&mut message).await.unwrap();
+        sender.send_text(std::str::from_utf8(&message).unwrap()).await.unwrap();
+        sender.flush().await.unwrap();
+    }
+
+    #[test]
+    fn test_ws_connect() {
+        block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+            let server_f = tokio::spawn(mock_server(listener));
+
+            let (sender, mut receiver) = ws_connect(&url, None).await.unwrap();
+            assert_eq!(receiver.next().await.unwrap(), "hello");
+            sender.send("ping".to_owned()).unwrap();
+            assert_eq!(receiver.next().await.unwrap(), "ping");
+            server_f.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_ws_connect_rejected() {
+        block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("ws://{}/ws", listener.local_addr().unwrap());
+            let server_f = tokio::spawn(async move {
+                let (mut stream, _) = listener.accept().await.unwrap();
+                // The request fits a single read.
+                let mut request = [0; 1024];
+                stream.read(&mut request).await.unwrap();
+                stream
+                    .write_all(b"HTTP/1.1 403 Forbidden\r\nContent-Length: 0\r\n\r\n")
+                    .await
+                    .unwrap();
+            });
+
+            let error = ws_connect(&url, None).await.unwrap_err();
+            assert_eq!(error.kind(), ErrorKind::ConnectionRefused, "{}", error);
+            server_f.await.unwrap();
+        });
+    }
+}
@@ -0,0 +1,273 @@
+//! A minimal SOCKS5 client (RFC 1928, RFC 1929) used to route the electrum, web3 and HTTP requests
+//! through a proxy like Tor.
+//!
+//! The target host names are resolved by the proxy, so the DNS requests don't leak
+//! and the `.onion` addresses can be reached.
+
+use futures::Future;
+use http::Uri;
+use hyper::service::Service;
+use serde::{Deserialize, Serialize};
+use std::io::{self, ErrorKind};
+use std::net::IpAddr;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const SOCKS_VERSION: u8 = 5;
+const AUTH_NONE: u8 = 0;
+const AUTH_USERNAME_PASSWORD: u8 = 2;
+const AUTH_NO_ACCEPTABLE_METHODS: u8 = 0xFF;
+const USERNAME_PASSWORD_VERSION: u8 = 1;
+const CMD_CONNECT: u8 = 1;
+const ATYP_IPV4: u8 = 1;
+const ATYP_DOMAIN_NAME: u8 = 3;
+const ATYP_IPV6: u8 = 4;
+
+/// The SOCKS5 proxy config, e.g. `{"address": "127.0.0.1:9050"}` for a local Tor daemon.
+#[derive(Clone, Debug, Deserialize, Eq, Hash, PartialEq, Serialize)]
+pub struct Socks5Proxy {
+    /// The `host:port` of the proxy.
+    pub address: String,
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub password: Option<String>,
+}
+
+/// Whether the `host` is a Tor hidden service that can be reached through a proxy only.
+pub fn is_onion_host(host: &str) -> bool { host.trim_end_matches('.').ends_with(".onion") }
+
+fn invalid_data(error: String) -> io::Error { io::Error::new(ErrorKind::InvalidData, error) }
+
+fn other_error(error: String) -> io::Error { io::Error::new(ErrorKind::Other, error) }
+
+/// Establishes a TCP connection to the `host:port` through the `proxy`.
+/// The `host` is sent to the proxy as is unless it's an IP address.
+pub async fn socks5_connect(proxy: &Socks5Proxy, host: &str, port: u16) -> io::Result<TcpStream> {
+    let mut stream = TcpStream::connect(proxy.address.as_str()).await?;
+    authenticate(&mut stream, proxy).await?;
+
+    let mut request = vec![SOCKS_VERSION, CMD_CONNECT, 0];
+    // The IPv6 hosts of the URIs are enclosed in brackets.
+    match host.trim_start_matches('[').trim_end_matches(']').parse::<IpAddr>() {
+        Ok(IpAddr::V4(ip)) => {
+            request.push(ATYP_IPV4);
+            request.extend_from_slice(&ip.octets());
+        },
+        Ok(IpAddr::V6(ip)) => {
+            request.push(ATYP_IPV6);
+            request.extend_from_slice(&ip.octets());
+        },
+        Err(_) => {
+            if host.is_empty() || host.len() > u8::MAX as usize {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid host name length: {}", host.len()),
+                ));
+            }
+            request.push(ATYP_DOMAIN_NAME);
+            request.push(host.len() as u8);
+            request.extend_from_slice(host.as_bytes());
+        },
+    }
+    request.extend_from_slice(&port.to_be_bytes());
+    stream.write_all(&request).await?;
+
+    let mut reply = [0u8; 4];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(invalid_data(format!("Unexpected SOCKS version {}", reply[0])));
+    }
+    if reply[1] != 0 {
+        return Err(other_error(format!(
+            "SOCKS5 proxy couldn't connect to {}:{}: {}",
+            host,
+            port,
+            reply_error(reply[1])
+        )));
+    }
+
+    // Skip the address the proxy bound to connect to the target.
+    let bound_addr_len = match reply[3] {
+        ATYP_IPV4 => 4,
+        ATYP_IPV6 => 16,
+        ATYP_DOMAIN_NAME => stream.read_u8().await? as usize,
+        atyp => return Err(invalid_data(format!("Unexpected SOCKS5 address type {}", atyp))),
+    };
+    let mut bound_addr = vec![0u8; bound_addr_len + 2];
+    stream.read_exact(&mut bound_addr).await?;
+
+    Ok(stream)
+}
+
+async fn authenticate(stream: &mut TcpStream, proxy: &Socks5Proxy) -> io::Result<()> {
+    let credentials = proxy
+        .username
+        .as_deref()
+        .map(|username| (username, proxy.password.as_deref().unwrap_or_default()));
+
+    let greeting: &[u8] = match credentials {
+        Some(_) => &[SOCKS_VERSION, 2, AUTH_NONE, AUTH_USERNAME_PASSWORD],
+        None => &[SOCKS_VERSION, 1, AUTH_NONE],
+    };
+    stream.write_all(greeting).await?;
+
+    let mut reply = [0u8; 2];
+    stream.read_exact(&mut reply).await?;
+    if reply[0] != SOCKS_VERSION {
+        return Err(invalid_data(format!("Unexpected SOCKS version {}", reply[0])));
+    }
+
+    match (reply[1], credentials) {
+        (AUTH_NONE, _) => Ok(()),
+        (AUTH_USERNAME_PASSWORD, Some((username, password))) => {
+            if username.len() > u8::MAX as usize || password.len() > u8::MAX as usize {
+                return Err(io::Error::new(
+                    ErrorKind::InvalidInput,
+                    "SOCKS5 username and password must not exceed 255 bytes",
+                ));
+            }
+            let mut request = vec![USERNAME_PASSWORD_VERSION, username.len() as u8];
+            request.extend_from_slice(username.as_bytes());
+            request.push(password.len() as u8);
+            request.extend_from_slice(password.as_bytes());
+            stream.write_all(&request).await?;
+
+            stream.read_exact(&mut reply).await?;
+            if reply[1] != 0 {
+                return Err(io::Error::new(
+                    ErrorKind::PermissionDenied,
+                    "SOCKS5 proxy rejected the username or password",
+                ));
+            }
+            Ok(())
+        },
+        (AUTH_NO_ACCEPTABLE_METHODS, _) => Err(io::Error::new(
+            ErrorKind::PermissionDenied,
+            "SOCKS5 proxy doesn't accept the offered authentication methods",
+        )),
+        (method, _) => Err(invalid_data(format!(
+            "SOCKS5 proxy chose unexpected authentication method {}",
+            method
+        ))),
+    }
+}
+
+fn reply_error(code: u8) -> &'static str {
+    match code {
+        1 => "general SOCKS server failure",
+        2 => "connection not allowed by ruleset",
+        3 => "network unreachable",
+        4 => "host unreachable",
+        5 => "connection refused",
+        6 => "TTL expired",
+        7 => "command not supported",
+        8 => "address type not supported",
+        _ => "unknown error",
+    }
+}
+
+/// The Hyper connector establishing the connections through the SOCKS5 proxy.
+#[derive(Clone, Debug)]
+pub struct Socks5Connector {
+    proxy: Socks5Proxy,
+}
+
+impl Socks5Connector {
+    pub fn new(proxy: Socks5Proxy) -> Socks5Connector { Socks5Connector { proxy } }
+}
+
+impl Service<Uri> for Socks5Connector {
+    type Response = TcpStream;
+    type Error = io::Error;
+    type Future = Pin<Box<dyn Future<Output = io::Result<TcpStream>> + Send>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> { Poll::Ready(Ok(())) }
+
+    fn call(&mut self, uri: Uri) -> Self::Future {
+        let proxy = self.proxy.clone();
+        Box::pin(async move {
+            let host = uri
+                .host()
+                .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, format!("No host in {}", uri)))?;
+            let port = match (uri.port_u16(), uri.scheme_str()) {
+                (Some(port), _) => port,
+                (None, Some("https")) => 443,
+                (None, _) => 80,
+            };
+            let stream = socks5_connect(&proxy, host, port).await?;
+            stream.set_nodelay(true)?;
+            Ok(stream)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use common::block_on;
+    use tokio::net::TcpListener;
+
+    /// Accepts a single connection, checks the handshake and echoes the data.
+    async fn mock_proxy(listener: TcpListener, expected_request: Vec<u8>) {
+        let (mut stream, _) = listener.accept().await.unwrap();
+
+        let mut greeting = [0u8; 4];
+        stream.read_exact(&mut greeting).await.unwrap();
+        assert_eq!(greeting, [SOCKS_VERSION, 2, AUTH_NONE, AUTH_USERNAME_PASSWORD]);
+        stream
+            .write_all(&[SOCKS_VERSION, AUTH_USERNAME_PASSWORD])
+            .await
+            .unwrap();
+
+        let mut auth = [0u8; 11];
+        stream.read_exact(&mut auth).await.unwrap();
+        assert_eq!(&auth, b"\x01\x04user\x04pass");
+        stream.write_all(&[USERNAME_PASSWORD_VERSION, 0]).await.unwrap();
+
+        let mut request = vec![0u8; expected_request.len()];
+        stream.read_exact(&mut request).await.unwrap();
+        assert_eq!(request, expected_request);
+        stream
+            .write_all(&[SOCKS_VERSION, 0, 0, ATYP_IPV4, 127, 0, 0, 1, 0, 80])
+            .await
+            .unwrap();
+
+        let mut data = [0u8; 4];
+        stream.read_exact(&mut data).await.unwrap();
+        stream.write_all(&data).await.unwrap();
+    }
+
+    #[test]
+    fn test_socks5_connect() {
+        block_on(async {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let proxy = Socks5Proxy {
+                address: listener.local_addr().unwrap().to_string(),
+                username: Some("user".to_owned()),
+                password: Some("pass".to_owned()),
+            };
+            let host = "electrum.example.onion";
+            let mut expected_request = vec![SOCKS_VERSION, CMD_CONNECT, 0, ATYP_DOMAIN_NAME, host.len() as u8];
+            expected_request.extend_from_slice(host.as_bytes());
+            expected_request.extend_from_slice(&50001u16.to_be_bytes());
+            let proxy_f = tokio::spawn(mock_proxy(listener, expected_request));
+
+            let mut stream = socks5_connect(&proxy, host, 50001).await.unwrap();
+            stream.write_all(b"ping").await.unwrap();
+            let mut data = [0u8; 4];
+            stream.read_exact(&mut data).await.unwrap();
+            assert_eq!(&data, b"ping");
+            proxy_f.await.unwrap();
+        });
+    }
+
+    #[test]
+    fn test_is_onion_host() {
+        assert!(is_onion_host("electrum.example.onion"));
+        assert!(is_onion_host("electrum.example.onion."));
+        assert!(!is_onion_host("electrum.example.com"));
+    }
+}
@@ -4,10 +4,10 @@ use mm2_err_handle::prelude::*;
 use serde::{Deserialize, Serialize};
 
 #[cfg(not(target_arch = "wasm32"))]
-pub use crate::native_http::{slurp_post_json, slurp_req, slurp_url};
+pub use crate::native_http::{slurp_json_with_headers, slurp_post_json, slurp_req, slurp_req_via_proxy, slurp_url};
 
 #[cfg(target_arch = "wasm32")]
-pub use crate::wasm_http::{slurp_post_json, slurp_url};
+pub use crate::wasm_http::{slurp_json_with_headers, slurp_post_json, slurp_url};
 
 pub type SlurpResult = Result<(StatusCode, HeaderMap, Vec<u8>), MmError<SlurpError>>;
 
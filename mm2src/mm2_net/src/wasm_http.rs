@@ -2,7 +2,7 @@ use crate::transport::{SlurpError, SlurpResult};
 use common::executor::spawn_local;
 use common::stringify_js_error;
 use futures::channel::oneshot;
-use http::{HeaderMap, StatusCode};
+use http::{HeaderMap, Method, StatusCode};
 use js_sys::Uint8Array;
 use mm2_err_handle::prelude::*;
 use std::collections::HashMap;
@@ -34,6 +34,26 @@ pub async fn slurp_post_json(url: &str, body: String) -> SlurpResult {
         .map(|(status_code, response)| (status_code, HeaderMap::new(), response.into_bytes()))
 }
 
+/// Executes a request with the JSON body and the additional headers, returning the response status, headers and body.
+/// Please note the return header map is empty, because `wasm_bindgen` doesn't provide the way to extract all headers.
+pub async fn slurp_json_with_headers(method: Method, url: &str, body: String, headers: &[(&str, &str)]) -> SlurpResult {
+    let mut request = match method {
+        Method::GET => FetchRequest::get(url),
+        Method::POST => FetchRequest::post(url),
+        Method::PUT => FetchRequest::put(url),
+        method => return MmError::err(SlurpError::InvalidRequest(format!("Unsupported method {}", method))),
+    };
+    request = request.header("Content-Type", "application/json");
+    for (key, value) in headers {
+        request = request.header(key, value);
+    }
+    request
+        .body_utf8(body)
+        .request_str()
+        .await
+        .map(|(status_code, response)| (status_code, HeaderMap::new(), response.into_bytes()))
+}
+
 pub struct FetchRequest {
     uri: String,
     method: FetchMethod,
@@ -63,6 +83,16 @@ impl FetchRequest {
         }
     }
 
+    pub fn put(uri: &str) -> FetchRequest {
+        FetchRequest {
+            uri: uri.to_owned(),
+            method: FetchMethod::Put,
+            headers: HashMap::new(),
+            body: None,
+            mode: None,
+        }
+    }
+
     pub fn body_utf8(mut self, body: String) -> FetchRequest {
         self.body = Some(RequestBody::Utf8(body));
         self
@@ -229,6 +259,7 @@ impl FetchRequest {
 enum FetchMethod {
     Get,
     Post,
+    Put,
 }
 
 impl FetchMethod {
@@ -236,6 +267,7 @@ impl FetchMethod {
         match self {
             FetchMethod::Get => "GET",
             FetchMethod::Post => "POST",
+            FetchMethod::Put => "PUT",
         }
     }
 }
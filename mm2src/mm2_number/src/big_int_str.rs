@@ -1,4 +1,5 @@
 use num_bigint::BigInt;
+use rpc_schema::{RpcSchema, SchemaGenerator};
 use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use std::fmt;
 
@@ -22,6 +23,12 @@ impl From<BigIntStr> for BigInt {
     fn from(other: BigIntStr) -> Self { other.0 }
 }
 
+impl RpcSchema for BigIntStr {
+    fn json_schema(_gen: &mut SchemaGenerator) -> serde_json::Value {
+        serde_json::json!({ "type": "string", "description": "Integer number, e.g. \"-10\"" })
+    }
+}
+
 impl Serialize for BigIntStr {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
         serializer.serialize_str(&self.0.to_string())
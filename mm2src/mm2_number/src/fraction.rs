@@ -3,12 +3,13 @@ use crate::from_dec_to_ratio;
 use bigdecimal::BigDecimal;
 use num_bigint::BigInt;
 use num_rational::BigRational;
+use rpc_schema_derive::RpcSchema;
 use serde::Serialize;
 use serde::{de, Deserialize, Deserializer};
 
 /// Rational number representation de/serializable in human readable form
 /// Should simplify the visual perception and parsing in code
-#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[derive(Clone, Debug, Eq, PartialEq, RpcSchema, Serialize)]
 pub struct Fraction {
     /// Numerator
     pub(crate) numer: BigIntStr,
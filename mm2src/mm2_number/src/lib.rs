@@ -15,6 +15,7 @@ pub use bigdecimal::BigDecimal;
 pub use num_bigint::{BigInt, BigUint};
 pub use num_rational::BigRational;
 pub use paste::paste;
+pub use rpc_schema;
 
 pub(crate) fn from_dec_to_ratio(d: &BigDecimal) -> BigRational {
     let (num, scale) = d.as_bigint_and_exponent();
@@ -5,6 +5,7 @@ use core::ops::{Add, AddAssign, Div, Mul, Sub};
 use num_bigint::BigInt;
 use num_rational::BigRational;
 use num_traits::Zero;
+use rpc_schema::{RpcSchema, SchemaGenerator};
 use serde::Serialize;
 use serde::{de, Deserialize, Deserializer};
 use serde_json::value::RawValue;
@@ -49,6 +50,21 @@ macro_rules! construct_detailed {
                 }
             }
 
+            impl $crate::rpc_schema::RpcSchema for $name {
+                fn schema_name() -> Option<String> { Some(stringify!($name).to_owned()) }
+
+                fn json_schema(gen: &mut $crate::rpc_schema::SchemaGenerator) -> $crate::rpc_schema::__private::Json {
+                    let fields = [
+                        (stringify!($base_field), gen.subschema_for::<$crate::BigDecimal>()),
+                        (stringify!([<$base_field _fraction>]), gen.subschema_for::<$crate::Fraction>()),
+                        (stringify!([<$base_field _rat>]), gen.subschema_for::<$crate::BigRational>()),
+                    ];
+                    let required = fields.iter().map(|(name, _)| name.to_string()).collect();
+                    let properties = fields.iter().map(|(name, schema)| (name.to_string(), schema.clone())).collect();
+                    $crate::rpc_schema::__private::object(properties, required)
+                }
+            }
+
             #[allow(dead_code)]
             impl $name {
                 pub fn as_ratio(&self) -> &$crate::BigRational {
@@ -94,6 +110,22 @@ impl<'de> Deserialize<'de> for MmNumber {
     }
 }
 
+/// The schema describes all the representations the MmNumber can be deserialized from.
+/// Note the MmNumber is always serialized to the big rational representation.
+impl RpcSchema for MmNumber {
+    fn schema_name() -> Option<String> { Some("MmNumber".to_owned()) }
+
+    fn json_schema(gen: &mut SchemaGenerator) -> serde_json::Value {
+        serde_json::json!({
+            "anyOf": [
+                gen.subschema_for::<BigDecimal>(),
+                gen.subschema_for::<BigRational>(),
+                gen.subschema_for::<Fraction>(),
+            ]
+        })
+    }
+}
+
 impl std::fmt::Display for MmNumber {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result { write!(f, "{}", from_ratio_to_dec(&self.0)) }
 }
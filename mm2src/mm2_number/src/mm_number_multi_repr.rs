@@ -3,10 +3,11 @@ use crate::from_ratio_to_dec;
 use crate::mm_number::MmNumber;
 use bigdecimal::BigDecimal;
 use num_rational::BigRational;
+use rpc_schema_derive::RpcSchema;
 use serde::Serialize;
 
 /// MmNumber representation in all available forms.
-#[derive(Debug, Serialize)]
+#[derive(Debug, RpcSchema, Serialize)]
 pub struct MmNumberMultiRepr {
     pub decimal: BigDecimal,
     pub rational: BigRational,
@@ -16,8 +16,11 @@ pub struct RpcTaskHandle<Task: RpcTask> {
 
 impl<Task: RpcTask> RpcTaskHandle<Task> {
     pub(crate) fn abort(self) {
-        self.lock_and_then(|mut task_manager| task_manager.cancel_task(self.task_id))
-            .ok();
+        self.lock_and_then(|mut task_manager| {
+            task_manager.on_task_aborted(self.task_id);
+            Ok(())
+        })
+        .ok();
     }
 
     fn lock_and_then<F, T>(&self, f: F) -> RpcTaskResult<T>
@@ -4,9 +4,11 @@ use futures::channel::oneshot;
 use mm2_err_handle::prelude::*;
 use mm2_rpc::mm_protocol::MmRpcResult;
 use serde::Serialize;
+use serde_json::Value as Json;
 use std::sync::atomic::AtomicU64;
 use std::time::Duration;
 
+#[macro_use] extern crate rpc_schema_derive;
 #[macro_use] extern crate ser_error_derive;
 #[macro_use] extern crate serde_derive;
 
@@ -60,6 +62,7 @@ pub enum TaskStatusError {
     InProgress,
     AwaitingUserAction,
     Finished,
+    Cancelled,
 }
 
 impl From<TimeoutError> for RpcTaskError {
@@ -77,6 +80,7 @@ where
     Ready(FinishedTaskResult<Item, Error>),
     InProgress(InProgressStatus),
     UserActionRequired(AwaitingStatus),
+    Cancelled,
 }
 
 impl<Item, Error, InProgressStatus, AwaitingStatus> RpcTaskStatus<Item, Error, InProgressStatus, AwaitingStatus>
@@ -93,10 +97,23 @@ where
             RpcTaskStatus::Ready(result) => RpcTaskStatus::Ready(result.map_err(f)),
             RpcTaskStatus::InProgress(in_progress) => RpcTaskStatus::InProgress(in_progress),
             RpcTaskStatus::UserActionRequired(awaiting) => RpcTaskStatus::UserActionRequired(awaiting),
+            RpcTaskStatus::Cancelled => RpcTaskStatus::Cancelled,
         }
     }
 }
 
+/// A short description of an RPC task returned by the `list_rpc_tasks` RPC.
+#[derive(Debug, RpcSchema, Serialize)]
+pub struct RpcTaskInfo {
+    pub task_id: TaskId,
+    /// The name of the RPC that initialized the task, e.g `init_withdraw`.
+    pub kind: String,
+    /// The time the task was spawned at, in milliseconds.
+    pub started_at: u64,
+    /// The last known status of the task serialized the same way as the `*_status` RPCs do.
+    pub status: Json,
+}
+
 enum TaskStatus<Task: RpcTaskTypes> {
     Ready(FinishedTaskResult<Task::Item, Task::Error>),
     InProgress(Task::InProgressStatus),
@@ -1,9 +1,10 @@
 use crate::task::RpcTaskTypes;
-use crate::{AtomicTaskId, FinishedTaskResult, RpcTask, RpcTaskError, RpcTaskHandle, RpcTaskResult, RpcTaskStatus,
-            RpcTaskStatusAlias, TaskAbortHandle, TaskAbortHandler, TaskId, TaskStatus, TaskStatusError,
+use crate::{AtomicTaskId, FinishedTaskResult, RpcTask, RpcTaskError, RpcTaskHandle, RpcTaskInfo, RpcTaskResult,
+            RpcTaskStatus, RpcTaskStatusAlias, TaskAbortHandle, TaskAbortHandler, TaskId, TaskStatus, TaskStatusError,
             UserActionSender};
 use common::executor::spawn;
 use common::log::{debug, warn};
+use common::now_ms;
 use futures::channel::oneshot;
 use futures::future::{select, Either};
 use mm2_err_handle::prelude::*;
@@ -21,10 +22,17 @@ fn next_rpc_task_id() -> TaskId { NEXT_RPC_TASK_ID.fetch_add(1, Ordering::Relaxe
 
 pub struct RpcTaskManager<Task: RpcTask> {
     tasks: HashMap<TaskId, TaskStatusExt<Task>>,
+    /// The time (in milliseconds) each of the `tasks` was spawned at.
+    started_at: HashMap<TaskId, u64>,
 }
 
 impl<Task: RpcTask> Default for RpcTaskManager<Task> {
-    fn default() -> Self { RpcTaskManager { tasks: HashMap::new() } }
+    fn default() -> Self {
+        RpcTaskManager {
+            tasks: HashMap::new(),
+            started_at: HashMap::new(),
+        }
+    }
 }
 
 impl<Task: RpcTask> RpcTaskManager<Task> {
@@ -70,36 +78,59 @@ impl<Task: RpcTask> RpcTaskManager<Task> {
     }
 
     /// Returns a task status if it exists, otherwise returns `None`.
+    /// Finished and cancelled tasks are removed from the manager if `forget_if_ready` is true.
     pub fn task_status(&mut self, task_id: TaskId, forget_if_ready: bool) -> Option<RpcTaskStatusAlias<Task>> {
-        let entry = match self.tasks.entry(task_id) {
-            Entry::Occupied(entry) => entry,
-            Entry::Vacant(_) => return None,
-        };
-        let rpc_status = match entry.get() {
-            TaskStatusExt::InProgress { status, .. } => RpcTaskStatus::InProgress(status.clone()),
-            TaskStatusExt::Awaiting { status, .. } => RpcTaskStatus::UserActionRequired(status.clone()),
-            TaskStatusExt::Ready(ready) => {
-                // I prefer cloning `ready` instead of removing `TaskStatusX` and matching/unwrapping it again.
-                let rpc_status = RpcTaskStatus::Ready(ready.clone());
-                if forget_if_ready {
-                    entry.remove();
-                }
-                rpc_status
-            },
-        };
+        let rpc_status = self.tasks.get(&task_id).map(TaskStatusExt::rpc_status)?;
+        let is_ready = matches!(rpc_status, RpcTaskStatus::Ready(_) | RpcTaskStatus::Cancelled);
+        if is_ready && forget_if_ready {
+            self.tasks.remove(&task_id);
+            self.started_at.remove(&task_id);
+        }
         Some(rpc_status)
     }
 
+    /// Returns the short descriptions of all tasks known to the manager.
+    /// The `kind` is usually the name of the RPC that spawns the tasks of this manager.
+    pub fn tasks_info(&self, kind: &str) -> Vec<RpcTaskInfo> {
+        self.tasks
+            .iter()
+            .map(|(task_id, status)| RpcTaskInfo {
+                task_id: *task_id,
+                kind: kind.to_owned(),
+                started_at: self.started_at.get(task_id).copied().unwrap_or_default(),
+                status: serde_json::to_value(status.rpc_status()).unwrap_or_default(),
+            })
+            .collect()
+    }
+
     pub fn new_shared() -> RpcTaskManagerShared<Task> { Arc::new(Mutex::new(Self::default())) }
 
     pub fn contains(&self, task_id: TaskId) -> bool { self.tasks.contains_key(&task_id) }
 
-    /// Cancel task if it's in progress.
+    /// Cancel task if it's in progress or awaits a user action.
+    /// The task is aborted once its `TaskAbortHandle` is dropped, and its status is set to `Cancelled`.
     pub fn cancel_task(&mut self, task_id: TaskId) -> RpcTaskResult<()> {
-        self.tasks
-            .remove(&task_id)
-            .map(|_| ())
-            .or_mm_err(|| self.rpc_task_error_if_not_found(task_id, TaskStatusError::InProgress))
+        match self.tasks.remove(&task_id) {
+            Some(TaskStatusExt::InProgress { .. }) | Some(TaskStatusExt::Awaiting { .. }) => {
+                self.tasks.insert(task_id, TaskStatusExt::Cancelled);
+                Ok(())
+            },
+            Some(unexpected) => {
+                // Return the status to the tasks container.
+                self.tasks.insert(task_id, unexpected);
+                MmError::err(self.rpc_task_error_if_not_found(task_id, TaskStatusError::InProgress))
+            },
+            None => MmError::err(RpcTaskError::NoSuchTask(task_id)),
+        }
+    }
+
+    /// Mark the task as cancelled if it has been aborted without the [`RpcTaskManager::cancel_task`] call.
+    pub(crate) fn on_task_aborted(&mut self, task_id: TaskId) {
+        if let Some(status) = self.tasks.get_mut(&task_id) {
+            if !matches!(status, TaskStatusExt::Ready(_)) {
+                *status = TaskStatusExt::Cancelled;
+            }
+        }
     }
 
     pub(crate) fn register_task(
@@ -119,6 +150,7 @@ impl<Task: RpcTask> RpcTaskManager<Task> {
                     status: task_initial_in_progress_status,
                     abort_handle,
                 });
+                self.started_at.insert(task_id, now_ms());
                 Ok((task_id, abort_handler))
             },
         }
@@ -140,6 +172,7 @@ impl<Task: RpcTask> RpcTaskManager<Task> {
             Some(TaskStatusExt::InProgress { .. }) => TaskStatusError::InProgress,
             Some(TaskStatusExt::Awaiting { .. }) => TaskStatusError::AwaitingUserAction,
             Some(TaskStatusExt::Ready(_)) => TaskStatusError::Finished,
+            Some(TaskStatusExt::Cancelled) => TaskStatusError::Cancelled,
             None => return RpcTaskError::NoSuchTask(task_id),
         };
         RpcTaskError::UnexpectedTaskStatus {
@@ -253,4 +286,184 @@ enum TaskStatusExt<Task: RpcTaskTypes> {
         next_in_progress_status: Task::InProgressStatus,
     },
     Ready(FinishedTaskResult<Task::Item, Task::Error>),
+    Cancelled,
+}
+
+impl<Task: RpcTaskTypes> TaskStatusExt<Task> {
+    fn rpc_status(&self) -> RpcTaskStatusAlias<Task> {
+        match self {
+            TaskStatusExt::InProgress { status, .. } => RpcTaskStatus::InProgress(status.clone()),
+            TaskStatusExt::Awaiting { status, .. } => RpcTaskStatus::UserActionRequired(status.clone()),
+            TaskStatusExt::Ready(ready) => RpcTaskStatus::Ready(ready.clone()),
+            TaskStatusExt::Cancelled => RpcTaskStatus::Cancelled,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use common::block_on;
+    use common::executor::Timer;
+    use derive_more::Display;
+    use serde_json::json;
+    use std::time::Duration;
+
+    #[derive(Clone, Display, Serialize, SerializeErrorType)]
+    #[serde(tag = "error_type", content = "error_data")]
+    enum TestTaskError {
+        Internal(String),
+    }
+
+    #[derive(Clone, Serialize)]
+    enum TestInProgressStatus {
+        Running,
+    }
+
+    #[derive(Clone, Serialize)]
+    enum TestAwaitingStatus {
+        WaitingForPin,
+    }
+
+    /// Runs until it's cancelled, or returns the user action if `wait_for_user_action` is true.
+    struct TestTask {
+        wait_for_user_action: bool,
+    }
+
+    impl RpcTaskTypes for TestTask {
+        type Item = String;
+        type Error = TestTaskError;
+        type InProgressStatus = TestInProgressStatus;
+        type AwaitingStatus = TestAwaitingStatus;
+        type UserAction = String;
+    }
+
+    #[async_trait]
+    impl RpcTask for TestTask {
+        fn initial_status(&self) -> Self::InProgressStatus { TestInProgressStatus::Running }
+
+        async fn run(self, task_handle: &RpcTaskHandle<Self>) -> Result<Self::Item, MmError<Self::Error>> {
+            if self.wait_for_user_action {
+                return task_handle
+                    .wait_for_user_action(Duration::from_secs(60), TestAwaitingStatus::WaitingForPin)
+                    .await
+                    .mm_err(|e| TestTaskError::Internal(e.to_string()));
+            }
+            loop {
+                Timer::sleep(1.).await;
+            }
+        }
+    }
+
+    fn task_status(manager: &RpcTaskManagerShared<TestTask>, task_id: TaskId) -> RpcTaskStatusAlias<TestTask> {
+        manager.lock().unwrap().task_status(task_id, false).unwrap()
+    }
+
+    fn wait_for_user_action_required(manager: &RpcTaskManagerShared<TestTask>, task_id: TaskId) {
+        block_on(async {
+            for _ in 0..100 {
+                if let RpcTaskStatus::UserActionRequired(_) = task_status(manager, task_id) {
+                    return;
+                }
+                Timer::sleep(0.01).await;
+            }
+            panic!("RPC task '{}' doesn't await a user action", task_id);
+        })
+    }
+
+    #[test]
+    fn test_cancel_running_task() {
+        let manager = RpcTaskManager::new_shared();
+        let task_id = RpcTaskManager::spawn_rpc_task(&manager, TestTask {
+            wait_for_user_action: false,
+        })
+        .unwrap();
+        assert!(matches!(
+            task_status(&manager, task_id),
+            RpcTaskStatus::InProgress(TestInProgressStatus::Running)
+        ));
+
+        manager.lock().unwrap().cancel_task(task_id).unwrap();
+        assert!(matches!(task_status(&manager, task_id), RpcTaskStatus::Cancelled));
+
+        // Let the aborted task finish. It must not override the `Cancelled` status.
+        block_on(Timer::sleep(0.1));
+        assert!(matches!(task_status(&manager, task_id), RpcTaskStatus::Cancelled));
+
+        let error = manager.lock().unwrap().cancel_task(task_id).unwrap_err();
+        assert!(matches!(error.into_inner(), RpcTaskError::UnexpectedTaskStatus {
+            actual: TaskStatusError::Cancelled,
+            ..
+        }));
+
+        // The cancelled task is forgotten once its status is requested with `forget_if_ready`.
+        let status = manager.lock().unwrap().task_status(task_id, true).unwrap();
+        assert!(matches!(status, RpcTaskStatus::Cancelled));
+        assert!(!manager.lock().unwrap().contains(task_id));
+    }
+
+    #[test]
+    fn test_cancel_awaiting_task() {
+        let manager = RpcTaskManager::new_shared();
+        let task_id = RpcTaskManager::spawn_rpc_task(&manager, TestTask {
+            wait_for_user_action: true,
+        })
+        .unwrap();
+        wait_for_user_action_required(&manager, task_id);
+
+        manager.lock().unwrap().cancel_task(task_id).unwrap();
+        assert!(matches!(task_status(&manager, task_id), RpcTaskStatus::Cancelled));
+
+        let error = manager
+            .lock()
+            .unwrap()
+            .on_user_action(task_id, "1234".to_owned())
+            .unwrap_err();
+        assert!(matches!(error.into_inner(), RpcTaskError::UnexpectedTaskStatus {
+            actual: TaskStatusError::Cancelled,
+            expected: TaskStatusError::AwaitingUserAction,
+            ..
+        }));
+    }
+
+    #[test]
+    fn test_tasks_info() {
+        let started_after = now_ms();
+        let manager = RpcTaskManager::new_shared();
+        let running_task_id = RpcTaskManager::spawn_rpc_task(&manager, TestTask {
+            wait_for_user_action: false,
+        })
+        .unwrap();
+        let awaiting_task_id = RpcTaskManager::spawn_rpc_task(&manager, TestTask {
+            wait_for_user_action: true,
+        })
+        .unwrap();
+        wait_for_user_action_required(&manager, awaiting_task_id);
+
+        let mut tasks_info = manager.lock().unwrap().tasks_info("init_test");
+        tasks_info.sort_by_key(|info| info.task_id);
+        assert_eq!(tasks_info.len(), 2);
+
+        let running = &tasks_info[0];
+        assert_eq!(running.task_id, running_task_id);
+        assert_eq!(running.kind, "init_test");
+        assert!(running.started_at >= started_after && running.started_at <= now_ms());
+        assert_eq!(running.status, json!({"status": "InProgress", "details": "Running"}));
+
+        let awaiting = &tasks_info[1];
+        assert_eq!(awaiting.task_id, awaiting_task_id);
+        assert_eq!(awaiting.kind, "init_test");
+        assert!(awaiting.started_at >= started_after && awaiting.started_at <= now_ms());
+        assert_eq!(
+            awaiting.status,
+            json!({"status": "UserActionRequired", "details": "WaitingForPin"})
+        );
+
+        // The last known status is reported for the cancelled tasks until they're forgotten.
+        manager.lock().unwrap().cancel_task(running_task_id).unwrap();
+        let tasks_info = manager.lock().unwrap().tasks_info("init_test");
+        let cancelled = tasks_info.iter().find(|info| info.task_id == running_task_id).unwrap();
+        assert_eq!(cancelled.status, json!({"status": "Cancelled"}));
+    }
 }
@@ -1,10 +1,10 @@
-use super::{RpcTaskError, TaskId};
+use super::{RpcTaskError, RpcTaskInfo, TaskId, TaskStatusError};
 use common::{true_f, HttpStatusCode, StatusCode};
 use derive_more::Display;
 
 /// In most cases, the RPC task status request may fail with either [`RpcTaskStatusError::NoSuchTask`] or [`RpcTaskStatusError::Internal`].
 /// Please do not add new error variants unless they are used in most cases.
-#[derive(Display, Serialize, SerializeErrorType)]
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum RpcTaskStatusError {
     NoSuchTask(TaskId),
@@ -22,7 +22,7 @@ impl HttpStatusCode for RpcTaskStatusError {
 
 /// In most cases, the RPC task action may fail with either [`RpcTaskStatusError::NoSuchTask`] or [`RpcTaskStatusError::Internal`].
 /// Please do not add new error variants unless they are used in most cases.
-#[derive(Display, Serialize, SerializeErrorType)]
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
 #[serde(tag = "error_type", content = "error_data")]
 pub enum RpcTaskUserActionError {
     NoSuchTask(TaskId),
@@ -47,9 +47,42 @@ impl HttpStatusCode for RpcTaskUserActionError {
     }
 }
 
+/// The RPC task cancellation may fail if the task doesn't exist or it's already finished/cancelled.
+#[derive(Display, RpcSchema, Serialize, SerializeErrorType)]
+#[serde(tag = "error_type", content = "error_data")]
+pub enum CancelRpcTaskError {
+    NoSuchTask(TaskId),
+    #[display(fmt = "RPC '{}' task is finished already", _0)]
+    TaskFinished(TaskId),
+    Internal(String),
+}
+
+impl From<RpcTaskError> for CancelRpcTaskError {
+    fn from(rpc_err: RpcTaskError) -> Self {
+        match rpc_err {
+            RpcTaskError::NoSuchTask(task_id) => CancelRpcTaskError::NoSuchTask(task_id),
+            RpcTaskError::UnexpectedTaskStatus {
+                task_id,
+                actual: TaskStatusError::Finished | TaskStatusError::Cancelled,
+                ..
+            } => CancelRpcTaskError::TaskFinished(task_id),
+            rpc_err => CancelRpcTaskError::Internal(rpc_err.to_string()),
+        }
+    }
+}
+
+impl HttpStatusCode for CancelRpcTaskError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            CancelRpcTaskError::NoSuchTask(_) | CancelRpcTaskError::TaskFinished(_) => StatusCode::BAD_REQUEST,
+            CancelRpcTaskError::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 /// In most cases, the RPC task status request consists of `task_id` and `forget_if_finished` fields only.
 /// Please do not add new fields unless they are used in most cases.
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct RpcTaskStatusRequest {
     pub task_id: TaskId,
     #[serde(default = "true_f")]
@@ -57,7 +90,7 @@ pub struct RpcTaskStatusRequest {
 }
 
 /// Please do not add new fields unless they are used in most cases.
-#[derive(Deserialize)]
+#[derive(Deserialize, RpcSchema)]
 pub struct RpcTaskUserActionRequest<UserAction> {
     pub task_id: TaskId,
     pub user_action: UserAction,
@@ -65,7 +98,17 @@ pub struct RpcTaskUserActionRequest<UserAction> {
 
 /// In most cases, the response to the RPC task initialization consists of `task_id` only.
 /// Please do not add new fields unless they are used in most cases.
-#[derive(Serialize)]
+#[derive(RpcSchema, Serialize)]
 pub struct InitRpcTaskResponse {
     pub task_id: TaskId,
 }
+
+#[derive(Deserialize, RpcSchema)]
+pub struct CancelRpcTaskRequest {
+    pub task_id: TaskId,
+}
+
+#[derive(RpcSchema, Serialize)]
+pub struct ListRpcTasksResponse {
+    pub tasks: Vec<RpcTaskInfo>,
+}